@@ -1,7 +1,7 @@
 use shopify_function_wasm_api::{Context, Value};
 use std::error::Error;
 
-#[cfg_attr(target_family = "wasm", export_name = "_start")]
+#[cfg_attr(target_os = "unknown", export_name = "_start")]
 fn main() {
     run().unwrap()
 }
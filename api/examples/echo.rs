@@ -4,7 +4,7 @@ use shopify_function_wasm_api::{
 };
 use std::error::Error;
 
-#[cfg_attr(target_family = "wasm", export_name = "_start")]
+#[cfg_attr(target_os = "unknown", export_name = "_start")]
 fn main() {
     run().unwrap()
 }
@@ -0,0 +1,37 @@
+use std::{error::Error, ops::ControlFlow};
+
+use shopify_function_wasm_api::Context;
+
+#[cfg_attr(target_os = "unknown", export_name = "_start")]
+fn main() {
+    run().unwrap()
+}
+
+// Sums the numbers in the input array up to the first negative one
+fn run() -> Result<(), Box<dyn Error>> {
+    shopify_function_wasm_api::init_panic_handler();
+    let mut context = Context::new();
+    let input = context.input_get()?;
+    let mut sum = 0;
+    let mut visited = 0;
+    let _ = input.for_each_until(|element| {
+        visited += 1;
+        match element.as_number() {
+            Some(n) if n >= 0.0 => {
+                sum += n as i32;
+                ControlFlow::Continue(())
+            }
+            _ => ControlFlow::Break(()),
+        }
+    });
+    context.write_object(
+        |ctx| {
+            ctx.write_utf8_str("sum")?;
+            ctx.write_i32(sum)?;
+            ctx.write_utf8_str("visited")?;
+            ctx.write_i32(visited)
+        },
+        2,
+    )?;
+    Ok(())
+}
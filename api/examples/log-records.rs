@@ -0,0 +1,11 @@
+use shopify_function_wasm_api::{log::Level, Context};
+
+#[cfg_attr(target_os = "unknown", export_name = "_start")]
+fn main() {
+    shopify_function_wasm_api::init_panic_handler();
+    let context = Context::new();
+    context.log("Hi!\n");
+    context.log_with_level(Level::Info, "Starting");
+    context.log_with_level(Level::Warn, "No cart lines");
+    context.log_with_level(Level::Error, "✌️");
+}
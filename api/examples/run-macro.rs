@@ -0,0 +1,7 @@
+use std::error::Error;
+
+shopify_function_wasm_api::run!(double);
+
+fn double(input: i32) -> Result<i32, Box<dyn Error>> {
+    input.checked_mul(2).ok_or_else(|| "input too large".into())
+}
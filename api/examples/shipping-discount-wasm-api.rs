@@ -0,0 +1,197 @@
+use shopify_function_wasm_api::{CachedInternedStringId, Context, Shape, Value};
+use std::error::Error;
+
+static LINES: CachedInternedStringId = CachedInternedStringId::new("lines");
+static AMOUNT_PER_QUANTITY: CachedInternedStringId =
+    CachedInternedStringId::new("amountPerQuantity");
+static AMOUNT: CachedInternedStringId = CachedInternedStringId::new("amount");
+static PRODUCT: CachedInternedStringId = CachedInternedStringId::new("product");
+static HAS_ANY_TAG: CachedInternedStringId = CachedInternedStringId::new("hasAnyTag");
+
+#[cfg_attr(target_os = "unknown", export_name = "_start")]
+fn main() {
+    run().unwrap()
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    shopify_function_wasm_api::init_panic_handler();
+    let mut context = Context::new();
+
+    let input = context.input_get()?;
+    let cart = input.get_obj_prop("cart");
+    let config = input
+        .get_obj_prop("discount")
+        .get_obj_prop("metafield")
+        .get_obj_prop("jsonValue");
+
+    let discount = Configuration::read(&config)
+        .filter(|config| eligible_subtotal(&cart) >= config.minimum_subtotal)
+        .map(|config| (config, delivery_group_ids(&cart)))
+        .filter(|(_, delivery_group_ids)| !delivery_group_ids.is_empty());
+
+    context.write_object(
+        |ctx| {
+            ctx.write_utf8_str("operations")?;
+            ctx.write_array(
+                |array_ctx| {
+                    if let Some((config, delivery_group_ids)) = &discount {
+                        write_delivery_discounts_add(array_ctx, config, delivery_group_ids)?;
+                    }
+                    Ok(())
+                },
+                discount.iter().len(),
+            )
+        },
+        1,
+    )?;
+
+    Ok(())
+}
+
+/// The discount configuration, stored as JSON in a metafield on the discount.
+struct Configuration {
+    minimum_subtotal: f64,
+    percentage: f64,
+}
+
+impl Configuration {
+    fn read(value: &Value) -> Option<Self> {
+        Some(Self {
+            minimum_subtotal: value.get_obj_prop("minimumSubtotal").as_number()?,
+            percentage: value.get_obj_prop("percentage").as_number()?,
+        })
+    }
+}
+
+// Helper function to sum the cost of lines whose product is not excluded from the discount
+fn eligible_subtotal(cart: &Value) -> f64 {
+    let lines = cart.get_interned_obj_prop(LINES.load());
+    let Some(lines_len) = lines.array_len() else {
+        return 0.0;
+    };
+
+    // the properties of each line, read in a single call
+    let line_shape = Shape::new(&["merchandise", "quantity", "cost"]);
+
+    let mut subtotal = 0.0;
+    for i in 0..lines_len {
+        let [merchandise, quantity, cost] = lines.get_at_index(i).read_shape(&line_shape);
+        let excluded = merchandise
+            .get_interned_obj_prop(PRODUCT.load())
+            .get_interned_obj_prop(HAS_ANY_TAG.load())
+            .as_bool()
+            .unwrap_or(false);
+        if excluded {
+            continue;
+        }
+
+        let quantity = quantity.as_number().unwrap_or(0.0);
+        let amount = cost
+            .get_interned_obj_prop(AMOUNT_PER_QUANTITY.load())
+            .get_interned_obj_prop(AMOUNT.load())
+            .as_string()
+            .and_then(|amount| amount.parse::<f64>().ok())
+            .unwrap_or(0.0);
+        subtotal += quantity * amount;
+    }
+
+    subtotal
+}
+
+fn delivery_group_ids(cart: &Value) -> Vec<String> {
+    let delivery_groups = cart.get_obj_prop("deliveryGroups");
+    let Some(delivery_groups_len) = delivery_groups.array_len() else {
+        return Vec::new();
+    };
+
+    (0..delivery_groups_len)
+        .filter_map(|i| {
+            delivery_groups
+                .get_at_index(i)
+                .get_obj_prop("id")
+                .as_string()
+        })
+        .collect()
+}
+
+fn write_delivery_discounts_add(
+    ctx: &mut Context,
+    config: &Configuration,
+    delivery_group_ids: &[String],
+) -> Result<(), shopify_function_wasm_api::write::Error> {
+    ctx.write_object(
+        |ctx| {
+            ctx.write_utf8_str("deliveryDiscountsAdd")?;
+            ctx.write_object(
+                |ctx| {
+                    ctx.write_utf8_str("candidates")?;
+                    ctx.write_array(
+                        |ctx| {
+                            ctx.write_object(
+                                |ctx| {
+                                    ctx.write_utf8_str("message")?;
+                                    ctx.write_utf8_str(&format!(
+                                        "{}% off shipping",
+                                        config.percentage
+                                    ))?;
+
+                                    ctx.write_utf8_str("targets")?;
+                                    ctx.write_array(
+                                        |ctx| {
+                                            for id in delivery_group_ids {
+                                                write_delivery_group_target(ctx, id)?;
+                                            }
+                                            Ok(())
+                                        },
+                                        delivery_group_ids.len(),
+                                    )?;
+
+                                    ctx.write_utf8_str("value")?;
+                                    ctx.write_object(
+                                        |ctx| {
+                                            ctx.write_utf8_str("percentage")?;
+                                            ctx.write_object(
+                                                |ctx| {
+                                                    ctx.write_utf8_str("value")?;
+                                                    ctx.write_f64(config.percentage)
+                                                },
+                                                1,
+                                            )
+                                        },
+                                        1,
+                                    )
+                                },
+                                3,
+                            )
+                        },
+                        1,
+                    )?;
+
+                    ctx.write_utf8_str("selectionStrategy")?;
+                    ctx.write_utf8_str("ALL")
+                },
+                2,
+            )
+        },
+        1,
+    )
+}
+
+fn write_delivery_group_target(
+    ctx: &mut Context,
+    id: &str,
+) -> Result<(), shopify_function_wasm_api::write::Error> {
+    ctx.write_object(
+        |ctx| {
+            ctx.write_utf8_str("deliveryGroup")?;
+            ctx.write_object(
+                |ctx| {
+                    ctx.write_utf8_str("id")?;
+                    ctx.write_utf8_str(id)
+                },
+                1,
+            )
+        },
+        1,
+    )
+}
@@ -0,0 +1,112 @@
+//! Arrays of booleans packed into bits.
+
+use crate::{read, Deserialize, Value};
+
+/// An array of booleans packed into bits, read with [`Value::as_bitset`].
+///
+/// The bits are stored eight to a byte, with the first element in the least significant bit of the
+/// first byte.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Bitset {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl Bitset {
+    /// Create a bitset of `len` bits from bytes packed by the provider. Bits past `len` are zero.
+    pub(crate) fn from_bytes(bytes: Vec<u8>, len: usize) -> Self {
+        debug_assert_eq!(bytes.len(), len.div_ceil(8));
+        Self { bytes, len }
+    }
+
+    /// The number of bits, which is the length of the array it was read from.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether there are no bits.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the bit at `index`, or `None` if it's out of bounds.
+    pub fn get(&self, index: usize) -> Option<bool> {
+        (index < self.len).then(|| self.bytes[index / 8] & (1 << (index % 8)) != 0)
+    }
+
+    /// The number of bits that are set.
+    pub fn count_ones(&self) -> usize {
+        self.bytes
+            .iter()
+            .map(|byte| byte.count_ones() as usize)
+            .sum()
+    }
+
+    /// Iterate over the bits, in order.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = bool> + '_ {
+        (0..self.len).map(|index| self.bytes[index / 8] & (1 << (index % 8)) != 0)
+    }
+
+    /// Iterate over the indices of the bits that are set, in order.
+    pub fn iter_ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.iter()
+            .enumerate()
+            .filter_map(|(index, bit)| bit.then_some(index))
+    }
+
+    /// The packed bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl Deserialize for Bitset {
+    fn deserialize(value: &Value) -> Result<Self, read::Error> {
+        value.as_bitset().ok_or_else(|| read::invalid_type(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Context;
+
+    #[test]
+    fn test_as_bitset() {
+        let flags: Vec<bool> = (0..20).map(|i| i % 3 == 0).collect();
+        let context = Context::new_with_input(serde_json::json!({
+            "flags": flags,
+            "empty": [],
+            "mixed": [true, 1],
+            "number": 1,
+        }));
+        let input = context.input_get().unwrap();
+
+        let bits = input.get_obj_prop("flags").as_bitset().unwrap();
+        assert_eq!(bits.len(), 20);
+        assert_eq!(bits.iter().collect::<Vec<_>>(), flags);
+        assert_eq!(bits.as_bytes(), [0b0100_1001, 0b1001_0010, 0b0000_0100]);
+        assert_eq!(bits.count_ones(), 7);
+        assert_eq!(
+            bits.iter_ones().collect::<Vec<_>>(),
+            [0, 3, 6, 9, 12, 15, 18]
+        );
+        assert_eq!(bits.get(20), None);
+        assert_eq!(input.get_obj_prop("flags").as_bool_vec(), Some(flags));
+
+        // slices are packed from their first element
+        let sliced = input.get_obj_prop("flags").slice(1..4).as_bitset().unwrap();
+        assert_eq!(sliced.iter().collect::<Vec<_>>(), [false, false, true]);
+
+        assert_eq!(
+            input.get_obj_prop("empty").as_bitset(),
+            Some(Bitset::default())
+        );
+        assert_eq!(input.get_obj_prop("mixed").as_bitset(), None);
+        assert_eq!(input.get_obj_prop("number").as_bitset(), None);
+        assert!(matches!(
+            Bitset::deserialize(&input.get_obj_prop("missing")),
+            Err(read::Error::Missing)
+        ));
+    }
+}
@@ -0,0 +1,378 @@
+//! Views of the cart for functions that read its lines and buyer, without writing or generating
+//! input types for them.
+//!
+//! Each view reads all of the fields it knows about with a single [`CachedShape`] read when it's
+//! created, and only copies a field from the input when its accessor is called, so fields the
+//! function doesn't use are never read. Fields the input query doesn't select are `None`, as are
+//! fields of the wrong type.
+//!
+//! # Example
+//! ```rust
+//! use shopify_function_wasm_api::{cart::Cart, Context};
+//!
+//! let context = Context::new_with_input(serde_json::json!({
+//!     "cart": {
+//!         "lines": [
+//!             {
+//!                 "id": "gid://shopify/CartLine/1",
+//!                 "quantity": 3,
+//!                 "merchandise": { "__typename": "ProductVariant", "sku": "SHIRT-M" },
+//!                 "cost": { "subtotalAmount": { "amount": "29.97" } },
+//!             },
+//!         ],
+//!         "buyerIdentity": { "email": "buyer@example.com", "isAuthenticated": true },
+//!     },
+//! }));
+//! let cart = Cart::from_input(&context.input_get().unwrap());
+//! for line in cart.lines() {
+//!     assert_eq!(line.quantity(), Some(3));
+//!     assert_eq!(line.merchandise().sku(), Some("SHIRT-M".to_string()));
+//!     assert_eq!(line.subtotal_amount(), Some(29.97));
+//! }
+//! let buyer_identity = cart.buyer_identity().unwrap();
+//! assert_eq!(buyer_identity.is_authenticated(), Some(true));
+//! ```
+
+use crate::{ArrayIter, CachedInternedStringId, CachedShape, Value};
+
+static CART: CachedInternedStringId = CachedInternedStringId::new("cart");
+static AMOUNT: CachedInternedStringId = CachedInternedStringId::new("amount");
+static CART_SHAPE: CachedShape<2> = CachedShape::new(&["lines", "buyerIdentity"]);
+static CART_LINE_SHAPE: CachedShape<4> =
+    CachedShape::new(&["id", "quantity", "merchandise", "cost"]);
+static CART_LINE_COST_SHAPE: CachedShape<3> =
+    CachedShape::new(&["amountPerQuantity", "subtotalAmount", "totalAmount"]);
+static MERCHANDISE_SHAPE: CachedShape<4> =
+    CachedShape::new(&["__typename", "id", "sku", "product"]);
+static PRODUCT_SHAPE: CachedShape<2> = CachedShape::new(&["id", "handle"]);
+static BUYER_IDENTITY_SHAPE: CachedShape<4> =
+    CachedShape::new(&["email", "phone", "isAuthenticated", "customer"]);
+static CUSTOMER_SHAPE: CachedShape<2> = CachedShape::new(&["id", "email"]);
+
+/// The amount of a `MoneyV2` object. `Decimal` amounts are strings in the input, but numbers are
+/// accepted too.
+fn money_amount(money: Value) -> Option<f64> {
+    let amount = money.get_interned_obj_prop(AMOUNT.load());
+    amount
+        .as_number()
+        .or_else(|| amount.as_string()?.parse().ok())
+}
+
+/// The cart, as selected by `cart { lines { ... } buyerIdentity { ... } }`.
+#[derive(Clone, Copy)]
+pub struct Cart {
+    lines: Value,
+    buyer_identity: Value,
+}
+
+impl Cart {
+    /// Read the cart object.
+    pub fn new(value: &Value) -> Self {
+        let [lines, buyer_identity] = value.read_shape(&CART_SHAPE.load());
+        Self {
+            lines,
+            buyer_identity,
+        }
+    }
+
+    /// Read the `cart` property of the input.
+    pub fn from_input(input: &Value) -> Self {
+        Self::new(&input.get_interned_obj_prop(CART.load()))
+    }
+
+    /// The lines of the cart, in order. There are none if the input query doesn't select them.
+    pub fn lines(&self) -> CartLines {
+        CartLines {
+            elements: self.lines.array_iter(),
+        }
+    }
+
+    /// The buyer of the cart, if there is one.
+    pub fn buyer_identity(&self) -> Option<BuyerIdentity> {
+        self.buyer_identity
+            .is_obj()
+            .then(|| BuyerIdentity::new(&self.buyer_identity))
+    }
+}
+
+/// An iterator over the lines of a cart, returned by [`Cart::lines`].
+pub struct CartLines {
+    elements: ArrayIter,
+}
+
+impl Iterator for CartLines {
+    type Item = CartLine;
+
+    fn next(&mut self) -> Option<CartLine> {
+        self.elements.next().map(|value| CartLine::new(&value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.elements.size_hint()
+    }
+}
+
+impl ExactSizeIterator for CartLines {}
+
+/// A line of the cart.
+#[derive(Clone, Copy)]
+pub struct CartLine {
+    value: Value,
+    id: Value,
+    quantity: Value,
+    merchandise: Value,
+    cost: Value,
+}
+
+impl CartLine {
+    /// Read a cart line object.
+    pub fn new(value: &Value) -> Self {
+        let [id, quantity, merchandise, cost] = value.read_shape(&CART_LINE_SHAPE.load());
+        Self {
+            value: *value,
+            id,
+            quantity,
+            merchandise,
+            cost,
+        }
+    }
+
+    /// The line object, for fields this view doesn't read, such as attributes.
+    pub fn as_value(&self) -> &Value {
+        &self.value
+    }
+
+    /// The line's ID.
+    pub fn id(&self) -> Option<String> {
+        self.id.as_string()
+    }
+
+    /// The quantity of the merchandise.
+    pub fn quantity(&self) -> Option<i32> {
+        self.quantity.as_number().map(|quantity| quantity as i32)
+    }
+
+    /// The merchandise of the line, whose fields are all `None` if it isn't selected.
+    pub fn merchandise(&self) -> Merchandise {
+        Merchandise::new(&self.merchandise)
+    }
+
+    /// The cost of a single unit of the merchandise, from `cost { amountPerQuantity { amount } }`.
+    pub fn amount_per_quantity(&self) -> Option<f64> {
+        let [amount_per_quantity, _, _] = self.cost.read_shape(&CART_LINE_COST_SHAPE.load());
+        money_amount(amount_per_quantity)
+    }
+
+    /// The cost of the line before discounts, from `cost { subtotalAmount { amount } }`.
+    pub fn subtotal_amount(&self) -> Option<f64> {
+        let [_, subtotal_amount, _] = self.cost.read_shape(&CART_LINE_COST_SHAPE.load());
+        money_amount(subtotal_amount)
+    }
+
+    /// The cost of the line after discounts, from `cost { totalAmount { amount } }`.
+    pub fn total_amount(&self) -> Option<f64> {
+        let [_, _, total_amount] = self.cost.read_shape(&CART_LINE_COST_SHAPE.load());
+        money_amount(total_amount)
+    }
+}
+
+/// The merchandise of a cart line, usually a product variant.
+#[derive(Clone, Copy)]
+pub struct Merchandise {
+    typename: Value,
+    id: Value,
+    sku: Value,
+    product: Value,
+}
+
+impl Merchandise {
+    /// Read a merchandise object.
+    pub fn new(value: &Value) -> Self {
+        let [typename, id, sku, product] = value.read_shape(&MERCHANDISE_SHAPE.load());
+        Self {
+            typename,
+            id,
+            sku,
+            product,
+        }
+    }
+
+    /// The merchandise's `__typename`, such as `ProductVariant`.
+    pub fn typename(&self) -> Option<String> {
+        self.typename.as_string()
+    }
+
+    /// Whether the merchandise is a product variant, without copying its `__typename`.
+    pub fn is_product_variant(&self) -> bool {
+        self.typename.str_trim_eq("ProductVariant")
+    }
+
+    /// The ID of the product variant.
+    pub fn id(&self) -> Option<String> {
+        self.id.as_string()
+    }
+
+    /// The SKU of the product variant.
+    pub fn sku(&self) -> Option<String> {
+        self.sku.as_string()
+    }
+
+    /// The ID of the variant's product.
+    pub fn product_id(&self) -> Option<String> {
+        let [id, _] = self.product.read_shape(&PRODUCT_SHAPE.load());
+        id.as_string()
+    }
+
+    /// The handle of the variant's product.
+    pub fn product_handle(&self) -> Option<String> {
+        let [_, handle] = self.product.read_shape(&PRODUCT_SHAPE.load());
+        handle.as_string()
+    }
+}
+
+/// The buyer of a cart.
+#[derive(Clone, Copy)]
+pub struct BuyerIdentity {
+    email: Value,
+    phone: Value,
+    is_authenticated: Value,
+    customer: Value,
+}
+
+impl BuyerIdentity {
+    /// Read a buyer identity object.
+    pub fn new(value: &Value) -> Self {
+        let [email, phone, is_authenticated, customer] =
+            value.read_shape(&BUYER_IDENTITY_SHAPE.load());
+        Self {
+            email,
+            phone,
+            is_authenticated,
+            customer,
+        }
+    }
+
+    /// The buyer's email address.
+    pub fn email(&self) -> Option<String> {
+        self.email.as_string()
+    }
+
+    /// The buyer's phone number.
+    pub fn phone(&self) -> Option<String> {
+        self.phone.as_string()
+    }
+
+    /// Whether the buyer is logged in.
+    pub fn is_authenticated(&self) -> Option<bool> {
+        self.is_authenticated.as_bool()
+    }
+
+    /// The ID of the buyer's customer account, if they have one.
+    pub fn customer_id(&self) -> Option<String> {
+        let [id, _] = self.customer.read_shape(&CUSTOMER_SHAPE.load());
+        id.as_string()
+    }
+
+    /// The email address of the buyer's customer account, if they have one.
+    pub fn customer_email(&self) -> Option<String> {
+        let [_, email] = self.customer.read_shape(&CUSTOMER_SHAPE.load());
+        email.as_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Context;
+
+    #[test]
+    fn test_cart() {
+        let context = Context::new_with_input(serde_json::json!({
+            "cart": {
+                "lines": [
+                    {
+                        "id": "gid://shopify/CartLine/1",
+                        "quantity": 2,
+                        "merchandise": {
+                            "__typename": "ProductVariant",
+                            "id": "gid://shopify/ProductVariant/1",
+                            "sku": "SHIRT-M",
+                            "product": { "id": "gid://shopify/Product/1", "handle": "shirt" },
+                        },
+                        "cost": {
+                            "amountPerQuantity": { "amount": "10.5" },
+                            "subtotalAmount": { "amount": "21.0" },
+                            "totalAmount": { "amount": 19 },
+                        },
+                    },
+                    {
+                        "quantity": 1,
+                        "merchandise": { "__typename": "CustomProduct" },
+                    },
+                ],
+                "buyerIdentity": {
+                    "email": "buyer@example.com",
+                    "isAuthenticated": true,
+                    "customer": { "id": "gid://shopify/Customer/1" },
+                },
+            },
+        }));
+        let cart = Cart::from_input(&context.input_get().unwrap());
+        let lines = cart.lines();
+        assert_eq!(lines.len(), 2);
+        let [first, second]: [CartLine; 2] = lines.collect::<Vec<_>>().try_into().ok().unwrap();
+
+        assert_eq!(first.id(), Some("gid://shopify/CartLine/1".to_string()));
+        assert_eq!(first.quantity(), Some(2));
+        assert_eq!(first.amount_per_quantity(), Some(10.5));
+        assert_eq!(first.subtotal_amount(), Some(21.0));
+        assert_eq!(first.total_amount(), Some(19.0));
+        let merchandise = first.merchandise();
+        assert!(merchandise.is_product_variant());
+        assert_eq!(merchandise.typename(), Some("ProductVariant".to_string()));
+        assert_eq!(
+            merchandise.id(),
+            Some("gid://shopify/ProductVariant/1".to_string())
+        );
+        assert_eq!(merchandise.sku(), Some("SHIRT-M".to_string()));
+        assert_eq!(
+            merchandise.product_id(),
+            Some("gid://shopify/Product/1".to_string())
+        );
+        assert_eq!(merchandise.product_handle(), Some("shirt".to_string()));
+
+        // fields that aren't selected are `None`
+        assert_eq!(second.id(), None);
+        assert_eq!(second.subtotal_amount(), None);
+        assert!(!second.merchandise().is_product_variant());
+        assert_eq!(second.merchandise().sku(), None);
+        assert_eq!(second.merchandise().product_id(), None);
+        assert!(second.as_value().is_obj());
+
+        let buyer_identity = cart.buyer_identity().unwrap();
+        assert_eq!(
+            buyer_identity.email(),
+            Some("buyer@example.com".to_string())
+        );
+        assert_eq!(buyer_identity.phone(), None);
+        assert_eq!(buyer_identity.is_authenticated(), Some(true));
+        assert_eq!(
+            buyer_identity.customer_id(),
+            Some("gid://shopify/Customer/1".to_string())
+        );
+        assert_eq!(buyer_identity.customer_email(), None);
+    }
+
+    #[test]
+    fn test_cart_without_selections() {
+        let context = Context::new_with_input(serde_json::json!({ "cart": {} }));
+        let cart = Cart::from_input(&context.input_get().unwrap());
+        assert_eq!(cart.lines().count(), 0);
+        assert!(cart.buyer_identity().is_none());
+
+        let context = Context::new_with_input(serde_json::json!({}));
+        let cart = Cart::from_input(&context.input_get().unwrap());
+        assert_eq!(cart.lines().len(), 0);
+        assert!(cart.buyer_identity().is_none());
+    }
+}
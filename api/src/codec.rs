@@ -0,0 +1,72 @@
+//! Formatting of values as JSON, matching how the platform converts function output to JSON, so
+//! snapshots of output don't change with the tool that printed them.
+
+pub use shopify_function_wasm_api_core::codec::FloatBuffer;
+
+/// A JSON formatter that writes numbers with [`FloatBuffer`].
+#[cfg(not(target_family = "wasm"))]
+struct CanonicalFormatter;
+
+#[cfg(not(target_family = "wasm"))]
+impl serde_json::ser::Formatter for CanonicalFormatter {
+    fn write_f64<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        value: f64,
+    ) -> std::io::Result<()> {
+        writer.write_all(
+            FloatBuffer::new()
+                .format(value)
+                .unwrap_or("null")
+                .as_bytes(),
+        )
+    }
+
+    fn write_f32<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        value: f32,
+    ) -> std::io::Result<()> {
+        self.write_f64(writer, value.into())
+    }
+}
+
+/// Print `value` as compact JSON, with numbers formatted as the platform formats them, e.g. to
+/// snapshot the value returned by
+/// [`Context::finalize_output_and_return`](crate::Context::finalize_output_and_return).
+///
+/// ```rust
+/// use shopify_function_wasm_api::codec::to_json_string;
+///
+/// assert_eq!(to_json_string(&serde_json::json!([1e300, 0.5, 1])), "[1e300,0.5,1]");
+/// ```
+#[cfg(not(target_family = "wasm"))]
+pub fn to_json_string(value: &serde_json::Value) -> String {
+    let mut json = Vec::new();
+    let mut serializer = serde_json::Serializer::with_formatter(&mut json, CanonicalFormatter);
+    serde::Serialize::serialize(value, &mut serializer).unwrap(); // infallible unwrap
+    String::from_utf8(json).unwrap() // infallible unwrap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Context;
+
+    #[test]
+    fn test_to_json_string() {
+        let mut context = Context::new_with_input(serde_json::json!(null));
+        context
+            .write_array_from_iter([1.0, 0.1, -1.5, 1e300, 1.5e-7, f64::MAX, f64::NAN])
+            .unwrap();
+        let output = context.finalize_output_and_return().unwrap();
+        assert_eq!(
+            to_json_string(&output),
+            "[1.0,0.1,-1.5,1e300,1.5e-7,1.7976931348623157e308,null]"
+        );
+        assert_eq!(
+            to_json_string(&serde_json::json!({"a": "b", "c": [true, null, -2]})),
+            r#"{"a":"b","c":[true,null,-2]}"#
+        );
+    }
+}
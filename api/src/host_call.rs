@@ -0,0 +1,120 @@
+//! Poll-based calls to the host, for data it provides lazily rather than as part of the input,
+//! such as currency rates.
+//!
+//! Hosts don't offer any capabilities yet, so every call currently finishes with
+//! [`Error::Unsupported`]. Calls never block, which lets capabilities that fetch data
+//! asynchronously be added later without changing how existing functions call the host.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{self, Poll},
+};
+
+use shopify_function_wasm_api_core::{
+    host_call::HostCallId,
+    read::{ErrorCode, NanBox, ValueRef},
+};
+
+use crate::{Context, InternedStringId, Value};
+
+/// An error that can occur when calling the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// The host does not support the requested capability.
+    #[error("Unsupported host capability")]
+    Unsupported,
+    /// The host call is not known to the host.
+    #[error("Unknown host call")]
+    UnknownHostCall,
+}
+
+/// A call to the host, created by [`Context::host_call`].
+///
+/// This is a [`Future`], so it can be awaited, and can also be polled directly with
+/// [`HostFuture::poll_result`].
+#[derive(Debug)]
+#[must_use = "host calls do nothing unless polled"]
+pub struct HostFuture {
+    id: HostCallId,
+}
+
+impl HostFuture {
+    /// Poll the host for the result of the call, without blocking.
+    pub fn poll_result(&self) -> Poll<Result<Value, Error>> {
+        let nan_box = NanBox::from_bits(unsafe { crate::shopify_function_host_call_poll(self.id) });
+        match nan_box.try_decode() {
+            Ok(ValueRef::Error(ErrorCode::HostCallPending)) => Poll::Pending,
+            Ok(ValueRef::Error(ErrorCode::HostCallUnsupported)) => {
+                Poll::Ready(Err(Error::Unsupported))
+            }
+            Ok(ValueRef::Error(ErrorCode::UnknownHostCall)) => {
+                Poll::Ready(Err(Error::UnknownHostCall))
+            }
+            _ => Poll::Ready(Ok(Value::from_nan_box(nan_box))),
+        }
+    }
+}
+
+impl Future for HostFuture {
+    type Output = Result<Value, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let result = self.poll_result();
+        if result.is_pending() {
+            // the host doesn't notify the guest when a call finishes, so ask to be polled again
+            cx.waker().wake_by_ref();
+        }
+        result
+    }
+}
+
+impl Context {
+    /// Start a call to the host for the `capability`, e.g. `currencyRate`, with an `argument`,
+    /// e.g. `CAD`. The call doesn't block; its result is read by polling the returned
+    /// [`HostFuture`].
+    pub fn host_call(
+        &self,
+        capability: InternedStringId,
+        argument: InternedStringId,
+    ) -> HostFuture {
+        let id = unsafe {
+            crate::shopify_function_host_call_start(capability.as_usize(), argument.as_usize())
+        };
+        HostFuture { id }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_call_unsupported() {
+        let context = Context::new_with_input(serde_json::json!({}));
+        let capability = context.intern_utf8_str("currencyRate");
+        let argument = context.intern_utf8_str("CAD");
+        let mut future = context.host_call(capability, argument);
+        assert!(matches!(
+            future.poll_result(),
+            Poll::Ready(Err(Error::Unsupported))
+        ));
+
+        let mut cx = task::Context::from_waker(task::Waker::noop());
+        assert!(matches!(
+            Pin::new(&mut future).poll(&mut cx),
+            Poll::Ready(Err(Error::Unsupported))
+        ));
+    }
+
+    #[test]
+    fn test_unknown_host_call() {
+        let _context = Context::new_with_input(serde_json::json!({}));
+        let future = HostFuture { id: 0 };
+        assert!(matches!(
+            future.poll_result(),
+            Poll::Ready(Err(Error::UnknownHostCall))
+        ));
+    }
+}
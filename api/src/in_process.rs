@@ -0,0 +1,193 @@
+//! A provider that runs in the current process, for evaluating functions natively.
+
+use shopify_function_provider::Instance;
+use shopify_function_wasm_api_core::host::InputEncoding;
+
+use crate::{write, Context};
+
+/// A provider that runs in the current process, so tools such as previews and simulations can
+/// evaluate a function's logic natively, without a Wasm runtime.
+///
+/// Each provider has its own input, output, logs and string interner, so several can be used
+/// independently, on the same thread or on different ones. Functions run against a provider with
+/// [`InProcessProvider::run`], which installs it on the current thread for the duration of the
+/// call. Values read from the input are only valid within the call they were read in.
+///
+/// # Example
+/// ```rust
+/// use shopify_function_wasm_api::{Deserialize, InProcessProvider, Serialize};
+///
+/// let mut first = InProcessProvider::new(&serde_json::json!(1));
+/// let mut second = InProcessProvider::new(&serde_json::json!(2));
+/// for provider in [&mut first, &mut second] {
+///     provider
+///         .run(|context| {
+///             let input = i32::deserialize(&context.input_get()?)?;
+///             (input * 10).serialize(context)?;
+///             Ok::<_, Box<dyn std::error::Error>>(())
+///         })
+///         .unwrap();
+/// }
+/// assert_eq!(first.output().unwrap(), serde_json::json!(10));
+/// assert_eq!(second.output().unwrap(), serde_json::json!(20));
+/// ```
+pub struct InProcessProvider {
+    instance: Instance,
+}
+
+/// Installs an instance on the current thread until it's dropped, including when the function
+/// panics, so the thread's own context is always restored.
+struct Installed<'a>(&'a mut Instance);
+
+impl<'a> Installed<'a> {
+    fn new(instance: &'a mut Instance) -> Self {
+        instance.swap();
+        Self(instance)
+    }
+}
+
+impl Drop for Installed<'_> {
+    fn drop(&mut self) {
+        self.0.swap();
+    }
+}
+
+impl InProcessProvider {
+    /// Create a provider whose input is `input`, which can be a [`serde_json::Value`] or any other
+    /// value implementing [`serde::Serialize`].
+    ///
+    /// # Panics
+    /// This will panic if the value fails to serialize, e.g. a map with non-string keys.
+    pub fn new<T: serde::Serialize + ?Sized>(input: &T) -> Self {
+        let bytes = rmp_serde::to_vec_named(input).expect("Failed to serialize input");
+        Self {
+            instance: Instance::new(bytes, InputEncoding::Msgpack),
+        }
+    }
+
+    /// Run `f` with a context that reads from and writes to this provider. The callbacks registered
+    /// with [`Context::on_new`] are called first, as the provider's state differs from the last
+    /// context's.
+    ///
+    /// The output can be written across several calls, so a value left unfinished isn't reported
+    /// until the output is read with [`InProcessProvider::output`].
+    pub fn run<T>(&mut self, f: impl FnOnce(&mut Context) -> T) -> T {
+        let _installed = Installed::new(&mut self.instance);
+        Context::notify_new();
+        let mut context = Context { auto_intern: None };
+        let result = f(&mut context);
+        // dropping the context would report unfinished output
+        context.auto_intern = None;
+        std::mem::forget(context);
+        result
+    }
+
+    /// Get the output written so far as a `serde_json::Value`, or an error if it isn't finished.
+    pub fn output(&mut self) -> Result<serde_json::Value, write::Error> {
+        self.run(|_| Context { auto_intern: None }.finalize_output_and_return())
+    }
+
+    /// Get the logs written so far, oldest first. Like the logs of a function running in Wasm,
+    /// only the most recent ones are kept.
+    pub fn logs(&mut self) -> String {
+        let _installed = Installed::new(&mut self.instance);
+        String::from_utf8_lossy(&shopify_function_provider::log::logs()).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CachedInternedStringId, Deserialize};
+
+    static CACHED_INTERNED_STRING_ID: CachedInternedStringId =
+        CachedInternedStringId::new("interned");
+
+    fn echo(provider: &mut InProcessProvider) {
+        provider
+            .run(|context| {
+                let input = context.input_get().unwrap();
+                let value = serde_json::Value::deserialize(&input).unwrap();
+                context.write_object(
+                    |context| {
+                        context.write_interned_utf8_str(CACHED_INTERNED_STRING_ID.load())?;
+                        crate::Serialize::serialize(&value, context)
+                    },
+                    1,
+                )
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_independent_providers() {
+        let mut first = InProcessProvider::new(&serde_json::json!("first"));
+        let mut second = InProcessProvider::new(&serde_json::json!("second"));
+        echo(&mut first);
+        echo(&mut second);
+        assert_eq!(
+            first.output().unwrap(),
+            serde_json::json!({ "interned": "first" })
+        );
+        assert_eq!(
+            second.output().unwrap(),
+            serde_json::json!({ "interned": "second" })
+        );
+    }
+
+    #[test]
+    fn test_thread_context_is_restored() {
+        let mut context = Context::new_with_input(serde_json::json!("thread"));
+        let mut provider = InProcessProvider::new(&serde_json::json!("provider"));
+        echo(&mut provider);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            provider.run(|_| panic!("in the provider"))
+        }));
+        assert!(result.is_err());
+        let input = context.input_get().unwrap();
+        assert_eq!(input.as_string(), Some("thread".to_string()));
+        context.write_utf8_str("done").unwrap();
+        assert_eq!(
+            context.finalize_output_and_return().unwrap(),
+            serde_json::json!("done")
+        );
+    }
+
+    #[test]
+    fn test_output_not_finished() {
+        let mut provider = InProcessProvider::new(&serde_json::json!(null));
+        provider.run(|context| context.write_object(|_| Ok(()), 1).unwrap_err());
+        assert!(matches!(
+            provider.output(),
+            Err(write::Error::ValueNotFinished)
+        ));
+    }
+
+    #[test]
+    fn test_providers_on_threads() {
+        let providers = ["a", "b"].map(|input| InProcessProvider::new(&serde_json::json!(input)));
+        let outputs = providers.map(|mut provider| {
+            std::thread::spawn(move || {
+                echo(&mut provider);
+                provider.output().unwrap()
+            })
+        });
+        assert_eq!(
+            outputs.map(|output| output.join().unwrap()),
+            [
+                serde_json::json!({ "interned": "a" }),
+                serde_json::json!({ "interned": "b" })
+            ]
+        );
+    }
+
+    #[test]
+    fn test_logs() {
+        let mut first = InProcessProvider::new(&serde_json::json!(null));
+        let mut second = InProcessProvider::new(&serde_json::json!(null));
+        first.run(|context| context.log("first\n"));
+        second.run(|context| context.log("second\n"));
+        assert_eq!(first.logs(), "first\n");
+        assert_eq!(second.logs(), "second\n");
+    }
+}
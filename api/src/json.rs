@@ -0,0 +1,172 @@
+//! Conversions between [`Value`] and [`serde_json::Value`].
+//!
+//! These are always available in non-Wasm targets, where they make it easy to construct inputs and assert
+//! on values in tests, and can be enabled in Wasm targets with the `serde_json` feature.
+
+use crate::{read, write, Context, Deserialize, Serialize, Value, ValueKind};
+
+/// The largest integer magnitude that can be represented exactly by an `f64`.
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+impl Deserialize for serde_json::Value {
+    /// Numbers without a fractional part are converted to JSON integers, since all numbers are read as `f64`.
+    /// Binary data, which JSON can't represent, is converted to an array of its bytes.
+    fn deserialize(value: &Value) -> Result<Self, read::Error> {
+        match value.kind() {
+            ValueKind::Null => Ok(serde_json::Value::Null),
+            ValueKind::Bool => value
+                .as_bool()
+                .map(serde_json::Value::Bool)
+                .ok_or(read::Error::InvalidType),
+            ValueKind::Number => {
+                let n = value.as_number().ok_or(read::Error::InvalidType)?;
+                if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER {
+                    Ok(serde_json::Value::from(n as i64))
+                } else {
+                    serde_json::Number::from_f64(n)
+                        .map(serde_json::Value::Number)
+                        .ok_or(read::Error::InvalidType)
+                }
+            }
+            ValueKind::String => value
+                .as_string()
+                .map(serde_json::Value::String)
+                .ok_or(read::Error::InvalidType),
+            ValueKind::Bytes => value
+                .as_bytes()
+                .map(|bytes| bytes.into_iter().map(serde_json::Value::from).collect())
+                .ok_or(read::Error::InvalidType),
+            ValueKind::Array => {
+                let len = value.array_len().ok_or(read::Error::InvalidType)?;
+                (0..len)
+                    .map(|i| Self::deserialize(&value.get_at_index(i)))
+                    .collect::<Result<_, _>>()
+                    .map(serde_json::Value::Array)
+            }
+            ValueKind::Object => {
+                let len = value.obj_len().ok_or(read::Error::InvalidType)?;
+                (0..len)
+                    .map(|i| {
+                        let key = value
+                            .get_obj_key_at_index(i)
+                            .ok_or(read::Error::InvalidType)?;
+                        Ok((key, Self::deserialize(&value.get_at_index(i))?))
+                    })
+                    .collect::<Result<_, _>>()
+                    .map(serde_json::Value::Object)
+            }
+            ValueKind::Error => Err(read::Error::InvalidType),
+        }
+    }
+}
+
+impl TryFrom<&Value> for serde_json::Value {
+    type Error = read::Error;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        Self::deserialize(value)
+    }
+}
+
+impl TryFrom<Value> for serde_json::Value {
+    type Error = read::Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        Self::deserialize(&value)
+    }
+}
+
+impl Serialize for serde_json::Value {
+    /// Integers that fit in an `i32` are written as integers; all other numbers are written as `f64`.
+    fn serialize(&self, context: &mut Context) -> Result<(), write::Error> {
+        match self {
+            serde_json::Value::Null => context.write_null(),
+            serde_json::Value::Bool(b) => context.write_bool(*b),
+            serde_json::Value::Number(n) => match n.as_i64().and_then(|n| i32::try_from(n).ok()) {
+                Some(n) => context.write_i32(n),
+                None => context.write_f64(n.as_f64().ok_or(write::Error::Unknown)?),
+            },
+            serde_json::Value::String(s) => context.write_utf8_str(s),
+            serde_json::Value::Array(values) => values.serialize(context),
+            serde_json::Value::Object(map) => context.write_object(
+                |context| {
+                    for (key, value) in map {
+                        context.write_utf8_str(key)?;
+                        value.serialize(context)?;
+                    }
+                    Ok(())
+                },
+                map.len(),
+            ),
+        }
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl From<serde_json::Value> for Context {
+    /// Equivalent to [`Context::new_with_input`].
+    fn from(input: serde_json::Value) -> Self {
+        Context::new_with_input(input)
+    }
+}
+
+impl Context {
+    /// Write a [`serde_json::Value`].
+    pub fn write_json(&mut self, value: &serde_json::Value) -> Result<(), write::Error> {
+        value.serialize(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_from_value() {
+        let input = serde_json::json!({
+            "null": null,
+            "bool": true,
+            "int": 1,
+            "negative": -2,
+            "float": 1.5,
+            "string": "a",
+            "array": [1, "b", [false]],
+            "object": { "nested": {} },
+        });
+        let context = Context::from(input.clone());
+        let value = context.input_get().unwrap();
+        assert_eq!(serde_json::Value::try_from(value).unwrap(), input);
+    }
+
+    #[test]
+    fn test_try_from_error_value() {
+        let context = Context::from(serde_json::json!([]));
+        let value = context.input_get().unwrap().get_at_index(0);
+        assert!(value.as_error().is_some());
+        assert!(matches!(
+            serde_json::Value::try_from(&value),
+            Err(read::Error::InvalidType)
+        ));
+    }
+
+    #[test]
+    fn test_write_json() {
+        let output = serde_json::json!({
+            "null": null,
+            "bool": false,
+            "int": 1,
+            "large": 10_000_000_000_i64,
+            "float": 1.5,
+            "string": "a",
+            "array": [1, "b", [true]],
+            "object": { "nested": {} },
+        });
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        context.write_json(&output).unwrap();
+        let result = context.finalize_output_and_return().unwrap();
+        assert_eq!(result["large"], serde_json::json!(10_000_000_000.0));
+        let mut expected = output;
+        expected["large"] = serde_json::json!(10_000_000_000.0);
+        assert_eq!(result, expected);
+    }
+}
@@ -21,17 +21,48 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! The [`run!`] macro defines this entrypoint for a function taking its deserialized input and
+//! returning its output.
 
 #![warn(missing_docs)]
 
 use shopify_function_wasm_api_core::read::{ErrorCode, NanBox, Val, ValueRef};
-use std::{cell::RefCell, collections::HashMap};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    hash::{BuildHasherDefault, DefaultHasher},
+    io,
+    marker::PhantomData,
+    ops::{Bound, ControlFlow, Range, RangeBounds},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, PoisonError,
+    },
+};
 
+mod bitset;
+pub mod cart;
+pub mod codec;
+pub mod host_call;
+#[cfg(not(target_family = "wasm"))]
+mod in_process;
+#[cfg(any(not(target_family = "wasm"), feature = "serde_json"))]
+mod json;
 pub mod log;
 pub mod read;
+#[cfg(feature = "serde")]
+pub mod serde_compat;
 pub mod write;
 
+pub use bitset::Bitset;
+#[cfg(not(target_family = "wasm"))]
+pub use in_process::InProcessProvider;
 pub use read::Deserialize;
+pub use shopify_function_wasm_api_core::read::ValueKind;
+/// Derive macros for [`Serialize`] and [`Deserialize`], enabled with the `derive` feature.
+#[cfg(feature = "derive")]
+pub use shopify_function_wasm_api_derive::{Deserialize, Serialize};
 pub use write::Serialize;
 
 #[cfg(target_family = "wasm")]
@@ -41,33 +72,119 @@ extern "C" {
     fn shopify_function_input_get() -> Val;
     fn shopify_function_input_get_val_len(scope: Val) -> usize;
     fn shopify_function_input_read_utf8_str(src: usize, out: *mut u8, len: usize);
+    fn shopify_function_input_read_utf8_str_with_len(scope: Val, out: *mut u8, cap: usize)
+        -> usize;
+    fn shopify_function_input_read_utf8_str_chunk(
+        scope: Val,
+        offset: usize,
+        out: *mut u8,
+        cap: usize,
+    ) -> usize;
+    fn shopify_function_input_read_msgpack_with_len(scope: Val, out: *mut u8, cap: usize) -> usize;
+    fn shopify_function_input_read_bool_bits_with_len(
+        scope: Val,
+        out: *mut u8,
+        cap: usize,
+    ) -> usize;
+    fn shopify_function_input_get_obj_entry_at_index(scope: Val, index: usize, out: *mut [Val; 2]);
+    fn shopify_function_input_read_array_elements(
+        scope: Val,
+        start: usize,
+        out: *mut Val,
+        cap: usize,
+    ) -> usize;
+    fn shopify_function_input_new_shape(
+        ids: *const shopify_function_wasm_api_core::InternedStringId,
+        len: usize,
+    ) -> usize;
+    fn shopify_function_input_read_shape(
+        scope: Val,
+        shape: usize,
+        out: *mut Val,
+        cap: usize,
+    ) -> usize;
     fn shopify_function_input_get_obj_prop(scope: Val, ptr: *const u8, len: usize) -> Val;
+    fn shopify_function_input_utf8_str_trim_eq(
+        scope: Val,
+        ptr: *const u8,
+        len: usize,
+        normalize: u32,
+    ) -> Val;
     fn shopify_function_input_get_interned_obj_prop(
         scope: Val,
         interned_string_id: shopify_function_wasm_api_core::InternedStringId,
     ) -> Val;
     fn shopify_function_input_get_at_index(scope: Val, index: usize) -> Val;
+    fn shopify_function_input_get_array_slice(scope: Val, start: usize, end: usize) -> Val;
     fn shopify_function_input_get_obj_key_at_index(scope: Val, index: usize) -> Val;
+    fn shopify_function_input_get_interned_obj_key_at_index(
+        scope: Val,
+        index: usize,
+    ) -> shopify_function_wasm_api_core::InternedStringId;
+    fn shopify_function_input_materialize(scope: Val) -> Val;
+    fn shopify_function_input_iter_new(scope: Val) -> usize;
+    fn shopify_function_input_iter_next(iter: usize) -> Val;
+    fn shopify_function_input_iter_key(iter: usize) -> Val;
+    fn shopify_function_input_iter_drop(iter: usize);
+    fn shopify_function_input_pin(scope: Val) -> usize;
+    fn shopify_function_input_get_pinned(pin: usize) -> Val;
+    fn shopify_function_input_unpin(pin: usize);
 
     // Write API.
     fn shopify_function_output_new_bool(bool: u32) -> usize;
     fn shopify_function_output_new_null() -> usize;
     fn shopify_function_output_new_i32(int: i32) -> usize;
+    fn shopify_function_output_new_i64(int: i64) -> usize;
+    fn shopify_function_output_new_u64(int: u64) -> usize;
     fn shopify_function_output_new_f64(float: f64) -> usize;
+    fn shopify_function_output_new_input_value(scope: Val) -> usize;
     fn shopify_function_output_new_utf8_str(ptr: *const u8, len: usize) -> usize;
+    fn shopify_function_output_new_bytes(ptr: *const u8, len: usize) -> usize;
+    fn shopify_function_output_new_msgpack(ptr: *const u8, len: usize) -> usize;
     fn shopify_function_output_new_interned_utf8_str(
         id: shopify_function_wasm_api_core::InternedStringId,
     ) -> usize;
+    fn shopify_function_output_new_dynamic_utf8_str(len_hint: usize) -> usize;
+    fn shopify_function_output_write_utf8_str_chunk(ptr: *const u8, len: usize) -> usize;
+    fn shopify_function_output_finish_utf8_str() -> usize;
     fn shopify_function_output_new_object(len: usize) -> usize;
     fn shopify_function_output_finish_object() -> usize;
     fn shopify_function_output_new_array(len: usize) -> usize;
     fn shopify_function_output_finish_array() -> usize;
+    fn shopify_function_output_new_dynamic_array() -> usize;
+    fn shopify_function_output_discard() -> usize;
+    fn shopify_function_output_checkpoint() -> usize;
+    fn shopify_function_output_rollback(id: usize) -> usize;
+    fn shopify_function_output_set_skip_nulls(skip: u32) -> u32;
+    fn shopify_function_output_len() -> usize;
+    fn shopify_function_dedup_set_new() -> usize;
+    fn shopify_function_dedup_set_insert_interned(
+        set: usize,
+        id: shopify_function_wasm_api_core::InternedStringId,
+    ) -> u32;
+    fn shopify_function_dedup_set_insert_hash(set: usize, hash: u64) -> u32;
+    fn shopify_function_dedup_set_drop(set: usize);
 
     // Log API.
-    fn shopify_function_log_new_utf8_str(ptr: *const u8, len: usize);
+    fn shopify_function_log_new_utf8_str_with_dropped_len(ptr: *const u8, len: usize) -> usize;
+    fn shopify_function_log_interned_strings() -> usize;
+    fn shopify_function_log_new_record(level: u32, ptr: *const u8, len: usize) -> usize;
 
     // Other.
     fn shopify_function_intern_utf8_str(ptr: *const u8, len: usize) -> usize;
+    fn shopify_function_interned_utf8_str_exists(
+        id: shopify_function_wasm_api_core::InternedStringId,
+    ) -> u32;
+    fn shopify_function_provider_version() -> u64;
+    fn shopify_function_deterministic_seed() -> u64;
+    fn shopify_function_set_work_budget(budget: usize);
+    fn shopify_function_host_call_start(
+        capability: shopify_function_wasm_api_core::InternedStringId,
+        argument: shopify_function_wasm_api_core::InternedStringId,
+    ) -> shopify_function_wasm_api_core::host_call::HostCallId;
+    fn shopify_function_host_call_poll(
+        id: shopify_function_wasm_api_core::host_call::HostCallId,
+    ) -> Val;
 }
 
 #[cfg(not(target_family = "wasm"))]
@@ -75,6 +192,43 @@ mod provider_fallback {
     use super::Val;
     use shopify_function_wasm_api_core::write::WriteResult;
 
+    #[cfg(target_pointer_width = "64")]
+    type DoubleUsize = u128;
+    #[cfg(target_pointer_width = "32")]
+    type DoubleUsize = u64;
+
+    /// A result the provider packs into one integer: the address of a buffer in the low bits, and a
+    /// length, ID or write result in the high bits. The conversions between integers and pointers
+    /// are kept here, rather than repeated in each function.
+    #[derive(Clone, Copy)]
+    struct PackedResult(DoubleUsize);
+
+    impl PackedResult {
+        /// The length, ID or write result.
+        fn high(self) -> usize {
+            (self.0 >> usize::BITS) as usize
+        }
+
+        /// The address of the buffer, as a pointer to its elements.
+        fn ptr<T>(self) -> *mut T {
+            let ptr = self.0 as usize as *mut T;
+            debug_assert!(ptr.is_aligned(), "misaligned provider buffer {ptr:p}");
+            ptr
+        }
+
+        /// Copy `len` elements from the provider's buffer to `out`.
+        unsafe fn copy_to<T>(self, out: *mut T, len: usize) {
+            debug_assert!(len == 0 || !self.ptr::<T>().is_null());
+            std::ptr::copy(self.ptr::<T>(), out, len);
+        }
+
+        /// Copy `len` elements from `src` to the provider's buffer.
+        unsafe fn copy_from<T>(self, src: *const T, len: usize) {
+            debug_assert!(len == 0 || !self.ptr::<T>().is_null());
+            std::ptr::copy(src, self.ptr::<T>(), len);
+        }
+    }
+
     // Read API.
     pub(crate) unsafe fn shopify_function_input_get() -> Val {
         shopify_function_provider::read::shopify_function_input_get()
@@ -90,6 +244,123 @@ mod provider_fallback {
         let src = shopify_function_provider::read::shopify_function_input_get_utf8_str_addr(src);
         std::ptr::copy(src as _, out, len);
     }
+    pub(crate) unsafe fn shopify_function_input_read_utf8_str_with_len(
+        scope: Val,
+        out: *mut u8,
+        cap: usize,
+    ) -> usize {
+        let addr_and_len = PackedResult(
+            shopify_function_provider::read::shopify_function_input_get_utf8_str_addr_and_len(
+                scope,
+            ),
+        );
+        let len = addr_and_len.high();
+        if len != usize::MAX {
+            addr_and_len.copy_to(out, len.min(cap));
+        }
+        len
+    }
+    pub(crate) unsafe fn shopify_function_input_read_msgpack_with_len(
+        scope: Val,
+        out: *mut u8,
+        cap: usize,
+    ) -> usize {
+        let addr_and_len = PackedResult(
+            shopify_function_provider::read::shopify_function_input_get_msgpack_addr_and_len(scope),
+        );
+        let len = addr_and_len.high();
+        if len != usize::MAX {
+            addr_and_len.copy_to(out, len.min(cap));
+        }
+        len
+    }
+    pub(crate) unsafe fn shopify_function_input_read_bool_bits_with_len(
+        scope: Val,
+        out: *mut u8,
+        cap: usize,
+    ) -> usize {
+        let addr_and_len = PackedResult(
+            shopify_function_provider::read::shopify_function_input_get_bool_bits_addr_and_len(
+                scope,
+            ),
+        );
+        let len = addr_and_len.high();
+        if len != usize::MAX {
+            addr_and_len.copy_to(out, len.min(cap));
+        }
+        len
+    }
+    pub(crate) unsafe fn shopify_function_input_read_utf8_str_chunk(
+        scope: Val,
+        offset: usize,
+        out: *mut u8,
+        cap: usize,
+    ) -> usize {
+        let addr_and_len = PackedResult(
+            shopify_function_provider::read::shopify_function_input_get_utf8_str_chunk_addr_and_len(
+                scope, offset, cap,
+            ),
+        );
+        let len = addr_and_len.high();
+        if len != usize::MAX {
+            addr_and_len.copy_to(out, len);
+        }
+        len
+    }
+    pub(crate) unsafe fn shopify_function_input_get_obj_entry_at_index(
+        scope: Val,
+        index: usize,
+        out: *mut [Val; 2],
+    ) {
+        let addr =
+            shopify_function_provider::read::shopify_function_input_get_obj_entry_at_index_addr(
+                scope, index,
+            );
+        std::ptr::copy(addr as *const [Val; 2], out, 1);
+    }
+    pub(crate) unsafe fn shopify_function_input_read_array_elements(
+        scope: Val,
+        start: usize,
+        out: *mut Val,
+        cap: usize,
+    ) -> usize {
+        let addr_and_len = PackedResult(
+            shopify_function_provider::read::shopify_function_input_get_array_elements_addr_and_len(
+                scope, start, cap,
+            ),
+        );
+        let len = addr_and_len.high();
+        if len != usize::MAX {
+            addr_and_len.copy_to(out, len);
+        }
+        len
+    }
+    pub(crate) unsafe fn shopify_function_input_new_shape(
+        ids: *const shopify_function_wasm_api_core::InternedStringId,
+        len: usize,
+    ) -> usize {
+        let id_and_addr =
+            PackedResult(shopify_function_provider::read::shopify_function_input_new_shape(len));
+        id_and_addr.copy_from(ids, len);
+        id_and_addr.high()
+    }
+    pub(crate) unsafe fn shopify_function_input_read_shape(
+        scope: Val,
+        shape: usize,
+        out: *mut Val,
+        cap: usize,
+    ) -> usize {
+        let addr_and_len = PackedResult(
+            shopify_function_provider::read::shopify_function_input_get_shape_values_addr_and_len(
+                scope, shape, cap,
+            ),
+        );
+        let len = addr_and_len.high();
+        if len != usize::MAX {
+            addr_and_len.copy_to(out, len);
+        }
+        len
+    }
     pub(crate) unsafe fn shopify_function_input_get_obj_prop(
         scope: Val,
         ptr: *const u8,
@@ -97,6 +368,16 @@ mod provider_fallback {
     ) -> Val {
         shopify_function_provider::read::shopify_function_input_get_obj_prop(scope, ptr as _, len)
     }
+    pub(crate) unsafe fn shopify_function_input_utf8_str_trim_eq(
+        scope: Val,
+        ptr: *const u8,
+        len: usize,
+        normalize: u32,
+    ) -> Val {
+        shopify_function_provider::read::shopify_function_input_utf8_str_trim_eq(
+            scope, ptr as _, len, normalize,
+        )
+    }
     pub(crate) unsafe fn shopify_function_input_get_interned_obj_prop(
         scope: Val,
         interned_string_id: shopify_function_wasm_api_core::InternedStringId,
@@ -109,12 +390,51 @@ mod provider_fallback {
     pub(crate) unsafe fn shopify_function_input_get_at_index(scope: Val, index: usize) -> Val {
         shopify_function_provider::read::shopify_function_input_get_at_index(scope, index)
     }
+    pub(crate) unsafe fn shopify_function_input_get_array_slice(
+        scope: Val,
+        start: usize,
+        end: usize,
+    ) -> Val {
+        shopify_function_provider::read::shopify_function_input_get_array_slice(scope, start, end)
+    }
     pub(crate) unsafe fn shopify_function_input_get_obj_key_at_index(
         scope: Val,
         index: usize,
     ) -> Val {
         shopify_function_provider::read::shopify_function_input_get_obj_key_at_index(scope, index)
     }
+    pub(crate) unsafe fn shopify_function_input_get_interned_obj_key_at_index(
+        scope: Val,
+        index: usize,
+    ) -> shopify_function_wasm_api_core::InternedStringId {
+        shopify_function_provider::read::shopify_function_input_get_interned_obj_key_at_index(
+            scope, index,
+        )
+    }
+    pub(crate) unsafe fn shopify_function_input_materialize(scope: Val) -> Val {
+        shopify_function_provider::read::shopify_function_input_materialize(scope)
+    }
+    pub(crate) unsafe fn shopify_function_input_iter_new(scope: Val) -> usize {
+        shopify_function_provider::read::shopify_function_input_iter_new(scope)
+    }
+    pub(crate) unsafe fn shopify_function_input_iter_next(iter: usize) -> Val {
+        shopify_function_provider::read::shopify_function_input_iter_next(iter)
+    }
+    pub(crate) unsafe fn shopify_function_input_iter_key(iter: usize) -> Val {
+        shopify_function_provider::read::shopify_function_input_iter_key(iter)
+    }
+    pub(crate) unsafe fn shopify_function_input_iter_drop(iter: usize) {
+        shopify_function_provider::read::shopify_function_input_iter_drop(iter)
+    }
+    pub(crate) unsafe fn shopify_function_input_pin(scope: Val) -> usize {
+        shopify_function_provider::read::shopify_function_input_pin(scope)
+    }
+    pub(crate) unsafe fn shopify_function_input_get_pinned(pin: usize) -> Val {
+        shopify_function_provider::read::shopify_function_input_get_pinned(pin)
+    }
+    pub(crate) unsafe fn shopify_function_input_unpin(pin: usize) {
+        shopify_function_provider::read::shopify_function_input_unpin(pin)
+    }
 
     // Write API.
     pub(crate) unsafe fn shopify_function_output_new_bool(bool: u32) -> usize {
@@ -126,23 +446,69 @@ mod provider_fallback {
     pub(crate) unsafe fn shopify_function_output_new_i32(int: i32) -> usize {
         shopify_function_provider::write::shopify_function_output_new_i32(int) as usize
     }
+    pub(crate) unsafe fn shopify_function_output_new_i64(int: i64) -> usize {
+        shopify_function_provider::write::shopify_function_output_new_i64(int) as usize
+    }
+    pub(crate) unsafe fn shopify_function_output_new_u64(int: u64) -> usize {
+        shopify_function_provider::write::shopify_function_output_new_u64(int) as usize
+    }
+    pub(crate) unsafe fn shopify_function_output_new_input_value(scope: Val) -> usize {
+        shopify_function_provider::write::shopify_function_output_new_input_value(scope) as usize
+    }
     pub(crate) unsafe fn shopify_function_output_new_f64(float: f64) -> usize {
         shopify_function_provider::write::shopify_function_output_new_f64(float) as usize
     }
     pub(crate) unsafe fn shopify_function_output_new_utf8_str(ptr: *const u8, len: usize) -> usize {
-        let result = shopify_function_provider::write::shopify_function_output_new_utf8_str(len);
-        let write_result = (result >> usize::BITS) as usize;
-        let dst = result as usize;
+        let result = PackedResult(
+            shopify_function_provider::write::shopify_function_output_new_utf8_str(len),
+        );
+        let write_result = result.high();
+        if write_result == WriteResult::Ok as usize {
+            result.copy_from(ptr, len);
+        }
+        write_result
+    }
+    pub(crate) unsafe fn shopify_function_output_new_bytes(ptr: *const u8, len: usize) -> usize {
+        let result =
+            PackedResult(shopify_function_provider::write::shopify_function_output_new_bytes(len));
+        let write_result = result.high();
         if write_result == WriteResult::Ok as usize {
-            std::ptr::copy(ptr as _, dst as _, len);
+            result.copy_from(ptr, len);
         }
         write_result
     }
+    pub(crate) unsafe fn shopify_function_output_new_msgpack(ptr: *const u8, len: usize) -> usize {
+        let result = PackedResult(
+            shopify_function_provider::write::shopify_function_output_new_msgpack(len),
+        );
+        result.copy_from(ptr, len);
+        shopify_function_provider::write::shopify_function_output_finish_msgpack(len) as usize
+    }
     pub(crate) unsafe fn shopify_function_output_new_interned_utf8_str(
         id: shopify_function_wasm_api_core::InternedStringId,
     ) -> usize {
         shopify_function_provider::write::shopify_function_output_new_interned_utf8_str(id) as usize
     }
+    pub(crate) unsafe fn shopify_function_output_new_dynamic_utf8_str(len_hint: usize) -> usize {
+        shopify_function_provider::write::shopify_function_output_new_dynamic_utf8_str(len_hint)
+            as usize
+    }
+    pub(crate) unsafe fn shopify_function_output_write_utf8_str_chunk(
+        ptr: *const u8,
+        len: usize,
+    ) -> usize {
+        let result = PackedResult(
+            shopify_function_provider::write::shopify_function_output_write_utf8_str_chunk(len),
+        );
+        let write_result = result.high();
+        if write_result == WriteResult::Ok as usize {
+            result.copy_from(ptr, len);
+        }
+        write_result
+    }
+    pub(crate) unsafe fn shopify_function_output_finish_utf8_str() -> usize {
+        shopify_function_provider::write::shopify_function_output_finish_utf8_str() as usize
+    }
     pub(crate) unsafe fn shopify_function_output_new_object(len: usize) -> usize {
         shopify_function_provider::write::shopify_function_output_new_object(len) as usize
     }
@@ -155,28 +521,105 @@ mod provider_fallback {
     pub(crate) unsafe fn shopify_function_output_finish_array() -> usize {
         shopify_function_provider::write::shopify_function_output_finish_array() as usize
     }
+    pub(crate) unsafe fn shopify_function_output_new_dynamic_array() -> usize {
+        shopify_function_provider::write::shopify_function_output_new_dynamic_array() as usize
+    }
+    pub(crate) unsafe fn shopify_function_output_discard() -> usize {
+        shopify_function_provider::write::shopify_function_output_discard() as usize
+    }
+    pub(crate) unsafe fn shopify_function_output_checkpoint() -> usize {
+        shopify_function_provider::write::shopify_function_output_checkpoint()
+    }
+    pub(crate) unsafe fn shopify_function_output_rollback(id: usize) -> usize {
+        shopify_function_provider::write::shopify_function_output_rollback(id) as usize
+    }
+    pub(crate) unsafe fn shopify_function_output_set_skip_nulls(skip: u32) -> u32 {
+        shopify_function_provider::write::shopify_function_output_set_skip_nulls(skip)
+    }
+    pub(crate) unsafe fn shopify_function_output_len() -> usize {
+        shopify_function_provider::write::shopify_function_output_len()
+    }
+    pub(crate) unsafe fn shopify_function_dedup_set_new() -> usize {
+        shopify_function_provider::dedup::shopify_function_dedup_set_new()
+    }
+    pub(crate) unsafe fn shopify_function_dedup_set_insert_interned(
+        set: usize,
+        id: shopify_function_wasm_api_core::InternedStringId,
+    ) -> u32 {
+        shopify_function_provider::dedup::shopify_function_dedup_set_insert_interned(set, id)
+    }
+    pub(crate) unsafe fn shopify_function_dedup_set_insert_hash(set: usize, hash: u64) -> u32 {
+        shopify_function_provider::dedup::shopify_function_dedup_set_insert_hash(set, hash)
+    }
+    pub(crate) unsafe fn shopify_function_dedup_set_drop(set: usize) {
+        shopify_function_provider::dedup::shopify_function_dedup_set_drop(set)
+    }
 
     // Logging.
-    pub(crate) unsafe fn shopify_function_log_new_utf8_str(ptr: *const u8, len: usize) {
+    pub(crate) unsafe fn shopify_function_log_new_utf8_str_with_dropped_len(
+        ptr: *const u8,
+        len: usize,
+    ) -> usize {
         let addr = shopify_function_provider::log::shopify_function_log_new_utf8_str(len)
-            as *const [usize; 5];
+            as *const [usize; 6];
         let array = *addr;
         let source_offset = array[0];
         let dst_offset1 = array[1];
         let len1 = array[2];
         let dst_offset2 = array[3];
         let len2 = array[4];
+        let dropped_len = array[5];
         std::ptr::copy(ptr.add(source_offset) as _, dst_offset1 as _, len1);
         std::ptr::copy(ptr.add(source_offset).add(len1), dst_offset2 as _, len2);
+        dropped_len
+    }
+    pub(crate) unsafe fn shopify_function_log_interned_strings() -> usize {
+        shopify_function_provider::log::shopify_function_log_interned_strings()
+    }
+    pub(crate) unsafe fn shopify_function_log_new_record(
+        level: u32,
+        ptr: *const u8,
+        len: usize,
+    ) -> usize {
+        let result = PackedResult(
+            shopify_function_provider::log::shopify_function_log_new_record(level, len),
+        );
+        result.copy_from(ptr, len);
+        result.high()
     }
 
     // Other.
     pub(crate) unsafe fn shopify_function_intern_utf8_str(ptr: *const u8, len: usize) -> usize {
-        let result = shopify_function_provider::shopify_function_intern_utf8_str(len);
-        let id = (result >> usize::BITS) as usize;
-        let dst = result as usize;
-        std::ptr::copy(ptr as _, dst as _, len);
-        id
+        let result = PackedResult(shopify_function_provider::shopify_function_intern_utf8_str(
+            len,
+        ));
+        result.copy_from(ptr, len);
+        result.high()
+    }
+    pub(crate) unsafe fn shopify_function_interned_utf8_str_exists(
+        id: shopify_function_wasm_api_core::InternedStringId,
+    ) -> u32 {
+        shopify_function_provider::shopify_function_interned_utf8_str_exists(id)
+    }
+    pub(crate) unsafe fn shopify_function_provider_version() -> u64 {
+        shopify_function_provider::shopify_function_provider_version()
+    }
+    pub(crate) unsafe fn shopify_function_deterministic_seed() -> u64 {
+        shopify_function_provider::shopify_function_deterministic_seed()
+    }
+    pub(crate) unsafe fn shopify_function_set_work_budget(budget: usize) {
+        shopify_function_provider::shopify_function_set_work_budget(budget)
+    }
+    pub(crate) unsafe fn shopify_function_host_call_start(
+        capability: shopify_function_wasm_api_core::InternedStringId,
+        argument: shopify_function_wasm_api_core::InternedStringId,
+    ) -> shopify_function_wasm_api_core::host_call::HostCallId {
+        shopify_function_provider::host_call::shopify_function_host_call_start(capability, argument)
+    }
+    pub(crate) unsafe fn shopify_function_host_call_poll(
+        id: shopify_function_wasm_api_core::host_call::HostCallId,
+    ) -> Val {
+        shopify_function_provider::host_call::shopify_function_host_call_poll(id)
     }
 }
 #[cfg(not(target_family = "wasm"))]
@@ -185,18 +628,54 @@ use provider_fallback::*;
 /// An identifier for an interned UTF-8 string.
 ///
 /// This is returned by [`Context::intern_utf8_str`], and can be used for both reading and writing.
+///
+/// IDs are only valid for the string interner they were interned with. In non-Wasm builds, where
+/// each thread has its own context and string interner, the ID also records which interner it
+/// belongs to, so using it with another interner (e.g. an ID shared between tests running on
+/// different threads) is reported as an unknown interned string instead of silently resolving to
+/// a different string.
 #[derive(Clone, Copy, PartialEq, Debug)]
-pub struct InternedStringId(shopify_function_wasm_api_core::InternedStringId);
+pub struct InternedStringId {
+    id: shopify_function_wasm_api_core::InternedStringId,
+    #[cfg(not(target_family = "wasm"))]
+    generation: usize,
+}
 
 impl InternedStringId {
+    /// An ID that is never known to any string interner.
+    #[cfg(not(target_family = "wasm"))]
+    const UNKNOWN: shopify_function_wasm_api_core::InternedStringId = usize::MAX;
+
+    fn new(id: shopify_function_wasm_api_core::InternedStringId) -> Self {
+        Self {
+            id,
+            #[cfg(not(target_family = "wasm"))]
+            generation: shopify_function_provider::string_interner_generation(),
+        }
+    }
+
+    #[cfg(target_family = "wasm")]
     fn as_usize(&self) -> usize {
-        self.0
+        self.id
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn as_usize(&self) -> usize {
+        if self.generation == shopify_function_provider::string_interner_generation() {
+            self.id
+        } else {
+            Self::UNKNOWN
+        }
     }
 }
 
+/// A `HashMap` whose hasher has fixed keys. The default hasher seeds itself with random keys,
+/// which on WASI targets are read from the host with an import functions can't call.
+pub(crate) type FixedHashMap<K, V> = HashMap<K, V, BuildHasherDefault<DefaultHasher>>;
+
 // The underlying string interner is thread local so the cache needs to be thread local too
 thread_local! {
-    static INTERNED_STRING_CACHE: RefCell<HashMap::<&'static str, InternedStringId>> = RefCell::new(HashMap::new());
+    static INTERNED_STRING_CACHE: RefCell<FixedHashMap::<&'static str, InternedStringId>> = RefCell::new(FixedHashMap::default());
 }
 
 /// A mechanism for caching interned string IDs.
@@ -213,15 +692,189 @@ impl CachedInternedStringId {
     /// Load the interned string ID.
     pub fn load(&self) -> InternedStringId {
         INTERNED_STRING_CACHE.with_borrow_mut(|cache| {
-            *cache.entry(self.value).or_insert_with(|| {
-                InternedStringId(unsafe {
-                    shopify_function_intern_utf8_str(self.value.as_ptr(), self.value.len())
-                })
-            })
+            let id = cache.entry(self.value).or_insert_with(|| self.intern());
+            // each `InProcessProvider` has its own string interner, so IDs cached while another
+            // was running are interned again
+            #[cfg(not(target_family = "wasm"))]
+            if id.as_usize() == InternedStringId::UNKNOWN {
+                *id = self.intern();
+            }
+            *id
+        })
+    }
+
+    fn intern(&self) -> InternedStringId {
+        InternedStringId::new(unsafe {
+            shopify_function_intern_utf8_str(self.value.as_ptr(), self.value.len())
         })
     }
 }
 
+/// The keys of objects that are read together, such as the fields of a struct.
+///
+/// Declaring a shape once and reading objects with [`Value::read_shape`] resolves all of their
+/// properties in a single call to the provider, which also remembers where each key was found, so
+/// objects with the same layout (e.g. the lines of a cart) are read without scanning their keys.
+///
+/// Like [`InternedStringId`]s, shapes are only valid for the provider context they were created
+/// in. Reading with a shape from another context produces [`ErrorCode::UnknownShape`] errors.
+///
+/// # Example
+/// ```rust
+/// use shopify_function_wasm_api::{Context, Shape};
+///
+/// let context = Context::new_with_input(serde_json::json!({ "id": "1", "quantity": 2 }));
+/// let input = context.input_get().unwrap();
+/// let shape = Shape::new(&["id", "quantity", "merchandise"]);
+/// let [id, quantity, merchandise] = input.read_shape(&shape);
+/// assert_eq!(id.as_string(), Some("1".to_string()));
+/// assert_eq!(quantity.as_number(), Some(2.0));
+/// assert!(merchandise.is_null());
+/// ```
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Shape<const N: usize> {
+    id: usize,
+    #[cfg(not(target_family = "wasm"))]
+    generation: usize,
+}
+
+impl<const N: usize> Shape<N> {
+    /// Create a shape from its keys, which are interned.
+    pub fn new(keys: &[&str; N]) -> Self {
+        let ids =
+            keys.map(|key| unsafe { shopify_function_intern_utf8_str(key.as_ptr(), key.len()) });
+        Self::from_ids(&ids)
+    }
+
+    /// Create a shape from the interned string IDs of its keys.
+    pub fn from_interned(keys: &[InternedStringId; N]) -> Self {
+        Self::from_ids(&keys.map(|key| key.as_usize()))
+    }
+
+    fn from_ids(ids: &[shopify_function_wasm_api_core::InternedStringId; N]) -> Self {
+        let id = unsafe { shopify_function_input_new_shape(ids.as_ptr(), N) };
+        Self {
+            id,
+            #[cfg(not(target_family = "wasm"))]
+            generation: shopify_function_provider::string_interner_generation(),
+        }
+    }
+
+    /// The same shape with another number of keys, for caching shapes of any length.
+    fn with_len<const M: usize>(self) -> Shape<M> {
+        Shape {
+            id: self.id,
+            #[cfg(not(target_family = "wasm"))]
+            generation: self.generation,
+        }
+    }
+
+    #[cfg(target_family = "wasm")]
+    fn as_usize(&self) -> usize {
+        self.id
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn as_usize(&self) -> usize {
+        // shapes are kept for as long as the string interner their keys were interned with
+        if self.generation == shopify_function_provider::string_interner_generation() {
+            self.id
+        } else {
+            usize::MAX
+        }
+    }
+}
+
+// Like the interned string cache, the shape cache is per thread
+thread_local! {
+    static SHAPE_CACHE: RefCell<FixedHashMap<(usize, usize), Shape<0>>> = RefCell::new(FixedHashMap::default());
+}
+
+/// A mechanism for caching shapes, like [`CachedInternedStringId`] for interned strings.
+///
+/// The shape is created the first time it's loaded, and created again when it's loaded in another
+/// provider context, so it can be declared once in a `static`.
+///
+/// # Example
+/// ```rust
+/// use shopify_function_wasm_api::{CachedShape, Context};
+///
+/// static LINE: CachedShape<2> = CachedShape::new(&["id", "quantity"]);
+///
+/// let context = Context::new_with_input(serde_json::json!({ "id": "1", "quantity": 2 }));
+/// let [id, quantity] = context.input_get().unwrap().read_shape(&LINE.load());
+/// assert_eq!(id.as_string(), Some("1".to_string()));
+/// assert_eq!(quantity.as_number(), Some(2.0));
+/// ```
+pub struct CachedShape<const N: usize> {
+    keys: &'static [&'static str; N],
+}
+
+impl<const N: usize> CachedShape<N> {
+    /// Create a new cached shape from its keys.
+    pub const fn new(keys: &'static [&'static str; N]) -> Self {
+        Self { keys }
+    }
+
+    /// Load the shape.
+    pub fn load(&self) -> Shape<N> {
+        // the keys are 'static, so their address identifies the shape
+        let cache_key = (self.keys.as_ptr() as usize, N);
+        if let Some(shape) = SHAPE_CACHE.with_borrow(|cache| cache.get(&cache_key).copied()) {
+            let shape = shape.with_len::<N>();
+            if shape.as_usize() != usize::MAX {
+                return shape;
+            }
+        }
+        let ids = self.keys.map(|key| CachedInternedStringId::new(key).load());
+        let shape = Shape::from_interned(&ids);
+        SHAPE_CACHE.with_borrow_mut(|cache| cache.insert(cache_key, shape.with_len::<0>()));
+        shape
+    }
+}
+
+/// The initial buffer size when reading strings whose length doesn't fit in the NaN-box.
+const LONG_STRING_CAPACITY: usize = 64 * 1024;
+
+/// The initial buffer size when reading values encoded as MessagePack.
+const MSGPACK_CAPACITY: usize = 4 * 1024;
+
+/// Read bytes with a function that copies at most `cap` of them to `out` and returns their full
+/// length, or `usize::MAX` if they can't be read. The bytes are copied into a buffer with room for
+/// `capacity` bytes, so a second call is only needed if they don't fit.
+fn read_bytes_with_len(capacity: usize, read: impl Fn(*mut u8, usize) -> usize) -> Option<Vec<u8>> {
+    let mut buf = Vec::with_capacity(capacity);
+    read_bytes_into(&mut buf, read)?;
+    Some(buf)
+}
+
+/// Like [`read_bytes_with_len`], but replaces the contents of `buf`, using its spare capacity
+/// before growing it.
+fn read_bytes_into(buf: &mut Vec<u8>, read: impl Fn(*mut u8, usize) -> usize) -> Option<()> {
+    buf.clear();
+    let len = match read(buf.as_mut_ptr(), buf.capacity()) {
+        usize::MAX => return None,
+        len if len > buf.capacity() => {
+            buf.reserve_exact(len);
+            read(buf.as_mut_ptr(), buf.capacity())
+        }
+        len => len,
+    };
+    unsafe { buf.set_len(len) };
+    Some(())
+}
+
+// Scratch buffer for `Value::with_str`, which is per thread like the provider context
+thread_local! {
+    static STR_SCRATCH: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The length of strings that are compared to a value on the stack, rather than on the heap.
+const STACK_STR_EQ_LEN: usize = 128;
+
+/// The maximum number of array elements read per provider call in [`Value::for_each_until`].
+const ARRAY_ELEMENTS_BATCH_SIZE: usize = 32;
+
 /// A value read from the input.
 ///
 /// This can be any of the following types:
@@ -232,14 +885,51 @@ impl CachedInternedStringId {
 /// - object
 /// - array
 /// - error
+///
+/// Values refer to data owned by the provider's context, which is local to the thread that read
+/// them, so they can't be sent to or shared with other threads:
+///
+/// ```rust,compile_fail
+/// use shopify_function_wasm_api::Context;
+///
+/// let context = Context::new_with_input(serde_json::json!([1, 2, 3]));
+/// let value = context.input_get().unwrap();
+/// std::thread::spawn(move || value.array_len());
+/// ```
 #[derive(Copy, Clone)]
 pub struct Value {
     nan_box: NanBox,
+    /// The type of the value, decoded once when the value is read so type checks don't decode the
+    /// NaN-box again.
+    kind: ValueKind,
+    /// Opts out of `Send` and `Sync`.
+    _not_send_sync: PhantomData<*const ()>,
 }
 
 impl Value {
+    fn from_nan_box(nan_box: NanBox) -> Self {
+        Self {
+            nan_box,
+            kind: nan_box.kind(),
+            _not_send_sync: PhantomData,
+        }
+    }
+
     fn new_child(&self, nan_box: NanBox) -> Self {
-        Self { nan_box }
+        Self::from_nan_box(nan_box)
+    }
+
+    /// Resolve the exact length of an array or object from the length encoded in its
+    /// NaN-box. Lengths of [`NanBox::MAX_VALUE_LENGTH`] or more don't fit in the NaN-box, so
+    /// they are looked up from the provider, which signals a failed lookup with `usize::MAX`.
+    fn exact_len(&self, len: usize) -> Option<usize> {
+        if len < NanBox::MAX_VALUE_LENGTH {
+            return Some(len);
+        }
+        match unsafe { shopify_function_input_get_val_len(self.nan_box.to_bits()) } {
+            usize::MAX => None,
+            len => Some(len),
+        }
     }
 
     /// Intern a string. This is just a convenience method equivalent to calling [`Context::intern_utf8_str`], if you don't have a [`Context`] easily accessible.
@@ -247,7 +937,16 @@ impl Value {
         let len = s.len();
         let ptr = s.as_ptr();
         let id = unsafe { shopify_function_intern_utf8_str(ptr, len) };
-        InternedStringId(id)
+        InternedStringId::new(id)
+    }
+
+    /// Get the type of the value.
+    ///
+    /// The type is decoded when the value is read, so matching on it is cheaper than probing each
+    /// type with [`Value::as_bool`], [`Value::as_number`] and similar methods. Values with an
+    /// unknown type are errors.
+    pub fn kind(&self) -> ValueKind {
+        self.kind
     }
 
     /// Get the value as a boolean, if it is one.
@@ -258,9 +957,50 @@ impl Value {
         }
     }
 
-    /// Check if the value is null.
+    /// Get the value as a boolean, or why it isn't one: [`read::Error::Read`] with the error code if
+    /// the value is an error, [`read::Error::Missing`] if it is a missing object property, and
+    /// [`read::Error::InvalidType`] otherwise.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::{read::Error, Context};
+    /// use shopify_function_wasm_api_core::read::ErrorCode;
+    ///
+    /// let context = Context::new_with_input(serde_json::json!({ "a": true, "b": 1 }));
+    /// let input = context.input_get().unwrap();
+    /// assert!(input.get_obj_prop("a").try_as_bool().unwrap());
+    /// assert!(matches!(input.get_obj_prop("b").try_as_bool(), Err(Error::InvalidType)));
+    /// assert!(matches!(input.get_obj_prop("c").try_as_bool(), Err(Error::Missing)));
+    /// assert!(matches!(
+    ///     input.get_obj_prop("b").get_obj_prop("c").try_as_bool(),
+    ///     Err(Error::Read(ErrorCode::NotAnObject))
+    /// ));
+    /// ```
+    pub fn try_as_bool(&self) -> Result<bool, read::Error> {
+        self.as_bool().ok_or_else(|| read::invalid_type(self))
+    }
+
+    /// Check if the value is null, which includes a missing object property.
     pub fn is_null(&self) -> bool {
-        matches!(self.nan_box.try_decode(), Ok(ValueRef::Null))
+        self.kind == ValueKind::Null
+    }
+
+    /// Check if the value is a missing object property. Missing properties are also null, so this
+    /// tells them apart from properties that are explicitly `null`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let context = Context::new_with_input(serde_json::json!({ "a": null }));
+    /// let input = context.input_get().unwrap();
+    /// assert!(input.get_obj_prop("a").is_null());
+    /// assert!(!input.get_obj_prop("a").is_missing());
+    /// assert!(input.get_obj_prop("b").is_null());
+    /// assert!(input.get_obj_prop("b").is_missing());
+    /// ```
+    pub fn is_missing(&self) -> bool {
+        self.nan_box.is_missing()
     }
 
     /// Get the value as a number, if it is one. Note that this will apply to both integers and floats.
@@ -271,86 +1011,340 @@ impl Value {
         }
     }
 
+    /// Get the value as a number, or why it isn't one, as with [`Value::try_as_bool`].
+    pub fn try_as_number(&self) -> Result<f64, read::Error> {
+        self.as_number().ok_or_else(|| read::invalid_type(self))
+    }
+
     /// Get the value as a string, if it is one.
     pub fn as_string(&self) -> Option<String> {
         match self.nan_box.try_decode() {
-            Ok(ValueRef::String { ptr, len }) => {
-                let len = if len == NanBox::MAX_VALUE_LENGTH {
-                    unsafe { shopify_function_input_get_val_len(self.nan_box.to_bits()) }
-                } else {
-                    len
-                };
+            Ok(ValueRef::String { ptr, len }) if len < NanBox::MAX_VALUE_LENGTH => {
                 let mut buf = vec![0; len];
                 unsafe { shopify_function_input_read_utf8_str(ptr as _, buf.as_mut_ptr(), len) };
                 Some(unsafe { String::from_utf8_unchecked(buf) })
             }
+            Ok(ValueRef::String { .. }) => self.read_long_string(),
             _ => None,
         }
     }
 
-    /// Check if the value is an object.
-    pub fn is_obj(&self) -> bool {
-        matches!(self.nan_box.try_decode(), Ok(ValueRef::Object { .. }))
-    }
-
-    /// Get a property from the object.
-    pub fn get_obj_prop(&self, prop: &str) -> Self {
-        let scope = unsafe {
-            shopify_function_input_get_obj_prop(self.nan_box.to_bits(), prop.as_ptr(), prop.len())
-        };
-        self.new_child(NanBox::from_bits(scope))
+    /// Get the value as a string, or why it isn't one, as with [`Value::try_as_bool`].
+    pub fn try_as_string(&self) -> Result<String, read::Error> {
+        self.as_string().ok_or_else(|| read::invalid_type(self))
     }
 
-    /// Get a property from the object by its interned string ID.
-    pub fn get_interned_obj_prop(&self, interned_string_id: InternedStringId) -> Self {
-        let scope = unsafe {
-            shopify_function_input_get_interned_obj_prop(
-                self.nan_box.to_bits(),
-                interned_string_id.as_usize(),
-            )
-        };
-        self.new_child(NanBox::from_bits(scope))
+    /// Read a string too long for its length to fit in the NaN-box. The string is copied into a
+    /// buffer with room for [`LONG_STRING_CAPACITY`] bytes while its length is returned by the
+    /// same call, so a second call is only needed for strings that don't fit.
+    fn read_long_string(&self) -> Option<String> {
+        let buf = read_bytes_with_len(LONG_STRING_CAPACITY, |out, cap| unsafe {
+            shopify_function_input_read_utf8_str_with_len(self.nan_box.to_bits(), out, cap)
+        })?;
+        Some(unsafe { String::from_utf8_unchecked(buf) })
+    }
+
+    /// Read the string into `buf`, replacing its contents, and get it as a `&str`, if the value is a
+    /// string.
+    ///
+    /// Unlike [`Value::as_string`], this doesn't allocate once `buf` has room for the string, so
+    /// reusing one buffer to read many strings, such as the IDs of every cart line, saves an
+    /// allocation for each of them. `buf` is cleared if the value is not a string.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let context = Context::new_with_input(serde_json::json!(["a", "bc", 1]));
+    /// let input = context.input_get().unwrap();
+    /// let mut buf = Vec::new();
+    /// let lens: Vec<usize> = input
+    ///     .array_iter()
+    ///     .filter_map(|value| value.read_str_into(&mut buf).map(str::len))
+    ///     .collect();
+    /// assert_eq!(lens, [1, 2]);
+    /// ```
+    pub fn read_str_into<'a>(&self, buf: &'a mut Vec<u8>) -> Option<&'a str> {
+        buf.clear();
+        match self.nan_box.try_decode() {
+            Ok(ValueRef::String { ptr, len }) if len < NanBox::MAX_VALUE_LENGTH => {
+                buf.reserve(len);
+                unsafe {
+                    shopify_function_input_read_utf8_str(ptr as _, buf.as_mut_ptr(), len);
+                    buf.set_len(len);
+                }
+            }
+            Ok(ValueRef::String { .. }) => read_bytes_into(buf, |out, cap| unsafe {
+                shopify_function_input_read_utf8_str_with_len(self.nan_box.to_bits(), out, cap)
+            })?,
+            _ => return None,
+        }
+        Some(unsafe { std::str::from_utf8_unchecked(buf) })
+    }
+
+    /// Call `f` with the string, if the value is one, read into a scratch buffer that's reused
+    /// across calls, so reading a string doesn't allocate a `String` for it like
+    /// [`Value::as_string`] does.
+    ///
+    /// Calls can be nested, e.g. to compare two strings, in which case the inner call reads into a
+    /// buffer of its own.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let context = Context::new_with_input(serde_json::json!({ "sku": "SHIRT-M" }));
+    /// let sku = context.input_get().unwrap().get_obj_prop("sku");
+    /// assert_eq!(sku.with_str(|sku| sku.starts_with("SHIRT")), Some(true));
+    /// ```
+    pub fn with_str<R>(&self, f: impl FnOnce(&str) -> R) -> Option<R> {
+        // the buffer is taken rather than borrowed, so nested calls don't conflict
+        let mut buf = STR_SCRATCH.take();
+        let result = self.read_str_into(&mut buf).map(f);
+        STR_SCRATCH.set(buf);
+        result
+    }
+
+    /// Get the value as bytes, if it's binary data, such as a hash, rather than a base64 string.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// struct Bin(&'static [u8]);
+    ///
+    /// impl serde::Serialize for Bin {
+    ///     fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    ///         serializer.serialize_bytes(self.0)
+    ///     }
+    /// }
+    ///
+    /// let context = Context::new_with_serializable(&Bin(&[0xde, 0xad]));
+    /// let input = context.input_get().unwrap();
+    /// assert_eq!(input.as_bytes(), Some(vec![0xde, 0xad]));
+    /// assert_eq!(input.as_string(), None);
+    /// ```
+    pub fn as_bytes(&self) -> Option<Vec<u8>> {
+        match self.nan_box.try_decode() {
+            Ok(ValueRef::Bytes { ptr, len }) if len < NanBox::MAX_VALUE_LENGTH => {
+                let mut buf = vec![0; len];
+                unsafe { shopify_function_input_read_utf8_str(ptr as _, buf.as_mut_ptr(), len) };
+                Some(buf)
+            }
+            Ok(ValueRef::Bytes { .. }) => {
+                read_bytes_with_len(LONG_STRING_CAPACITY, |out, cap| unsafe {
+                    shopify_function_input_read_utf8_str_with_len(self.nan_box.to_bits(), out, cap)
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Get a reader over the bytes of the string, which copies them from the input as they are
+    /// read.
+    ///
+    /// This lets streaming parsers, such as CSV or base64 decoders, consume large strings in chunks
+    /// instead of reading the whole string into a `String` first. Reading fails with
+    /// [`std::io::ErrorKind::InvalidData`] if the value is not a string.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    /// use std::io::{BufRead, BufReader};
+    ///
+    /// let context = Context::new_with_input(serde_json::json!("a,b\nc,d"));
+    /// let input = context.input_get().unwrap();
+    /// let lines: Vec<String> = BufReader::new(input.string_reader())
+    ///     .lines()
+    ///     .collect::<Result<_, _>>()
+    ///     .unwrap();
+    /// assert_eq!(lines, ["a,b", "c,d"]);
+    /// ```
+    pub fn string_reader(&self) -> StringReader {
+        StringReader {
+            string: *self,
+            offset: 0,
+        }
+    }
+
+    /// Check if the value is a string equal to `other` once leading and trailing whitespace is
+    /// trimmed from both, e.g. to match a user-entered code against configuration. The strings are
+    /// compared by the provider, without reading the value into a `String`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let context = Context::new_with_input(serde_json::json!(" SUMMER10\n"));
+    /// let input = context.input_get().unwrap();
+    /// assert!(input.str_trim_eq("SUMMER10"));
+    /// assert!(!input.str_trim_eq("summer10"));
+    /// ```
+    pub fn str_trim_eq(&self, other: &str) -> bool {
+        self.utf8_str_trim_eq(other, false).unwrap_or(false)
+    }
+
+    /// Like [`Value::str_trim_eq`], but compares the NFC normalizations of the strings, so
+    /// characters written with combining marks equal their precomposed forms. Normalizing in the
+    /// provider saves the function from including Unicode normalization tables.
+    ///
+    /// Providers only normalize strings when built with the `nfc-normalization` feature, and fail
+    /// with [`ErrorCode::NormalizationUnsupported`] otherwise.
+    pub fn str_trim_eq_nfc(&self, other: &str) -> Result<bool, read::Error> {
+        self.utf8_str_trim_eq(other, true)
+    }
+
+    fn utf8_str_trim_eq(&self, other: &str, normalize: bool) -> Result<bool, read::Error> {
+        let eq = unsafe {
+            shopify_function_input_utf8_str_trim_eq(
+                self.nan_box.to_bits(),
+                other.as_ptr(),
+                other.len(),
+                normalize as u32,
+            )
+        };
+        Self::from_nan_box(NanBox::from_bits(eq)).try_as_bool()
+    }
+
+    /// Check if the value is an object.
+    pub fn is_obj(&self) -> bool {
+        self.kind == ValueKind::Object
+    }
+
+    /// Get a property from the object.
+    pub fn get_obj_prop(&self, prop: &str) -> Self {
+        self.get_obj_prop_bytes(prop.as_bytes())
+    }
+
+    /// Get a property from the object, or why it can't be read: [`read::Error::Missing`] if the
+    /// object doesn't have it, and [`read::Error::Read`] with the error code if this value is not an
+    /// object, or is itself an error.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::{read::Error, Context};
+    /// use shopify_function_wasm_api_core::read::ErrorCode;
+    ///
+    /// let context = Context::new_with_input(serde_json::json!({ "a": { "b": null }, "c": 1 }));
+    /// let input = context.input_get().unwrap();
+    /// let a = input.try_get_obj_prop("a").unwrap();
+    /// assert!(a.try_get_obj_prop("b").unwrap().is_null());
+    /// assert!(matches!(a.try_get_obj_prop("c"), Err(Error::Missing)));
+    /// assert!(matches!(
+    ///     input.get_obj_prop("c").try_get_obj_prop("d"),
+    ///     Err(Error::Read(ErrorCode::NotAnObject))
+    /// ));
+    /// ```
+    pub fn try_get_obj_prop(&self, prop: &str) -> Result<Self, read::Error> {
+        let value = self.get_obj_prop(prop);
+        match value.as_error() {
+            Some(code) => Err(read::Error::Read(code)),
+            None if value.is_missing() => Err(read::Error::Missing),
+            None => Ok(value),
+        }
+    }
+
+    /// Get a property from the object by the bytes of its key, which are compared exactly.
+    ///
+    /// This is useful for keys produced at runtime, e.g. sliced from another string, as they can be
+    /// looked up without being copied into a `String` or validated as UTF-8.
+    pub fn get_obj_prop_bytes(&self, prop: &[u8]) -> Self {
+        let scope = unsafe {
+            shopify_function_input_get_obj_prop(self.nan_box.to_bits(), prop.as_ptr(), prop.len())
+        };
+        self.new_child(NanBox::from_bits(scope))
+    }
+
+    /// Get a property from the object by its interned string ID.
+    pub fn get_interned_obj_prop(&self, interned_string_id: InternedStringId) -> Self {
+        let scope = unsafe {
+            shopify_function_input_get_interned_obj_prop(
+                self.nan_box.to_bits(),
+                interned_string_id.as_usize(),
+            )
+        };
+        self.new_child(NanBox::from_bits(scope))
+    }
+
+    /// Get the properties of the object for each key of `shape`, in the order of its keys.
+    ///
+    /// This takes a single call to the provider, rather than one per key with
+    /// [`Value::get_interned_obj_prop`]. Missing properties are null, and every value is an error
+    /// if this value is not an object.
+    pub fn read_shape<const N: usize>(&self, shape: &Shape<N>) -> [Self; N] {
+        // an unknown shape reads nothing, leaving every value as an error
+        let mut values = [NanBox::error(ErrorCode::UnknownShape).to_bits(); N];
+        unsafe {
+            shopify_function_input_read_shape(
+                self.nan_box.to_bits(),
+                shape.as_usize(),
+                values.as_mut_ptr(),
+                N,
+            )
+        };
+        values.map(|value| self.new_child(NanBox::from_bits(value)))
     }
 
     /// Check if the value is an array.
     pub fn is_array(&self) -> bool {
-        matches!(self.nan_box.try_decode(), Ok(ValueRef::Array { .. }))
+        self.kind == ValueKind::Array
     }
 
     /// Get the length of the array, if it is one.
+    ///
+    /// The exact length is returned for arrays of any size, including those with more elements
+    /// than can be encoded in the NaN-box. `None` is returned if the value is not an array or
+    /// its length could not be read.
     pub fn array_len(&self) -> Option<usize> {
         match self.nan_box.try_decode() {
-            Ok(ValueRef::Array { len, .. }) => {
-                let len = if len == NanBox::MAX_VALUE_LENGTH {
-                    unsafe { shopify_function_input_get_val_len(self.nan_box.to_bits()) }
-                } else {
-                    len
-                };
-                if len == usize::MAX {
-                    None
-                } else {
-                    Some(len)
-                }
-            }
+            Ok(ValueRef::Array { len, .. }) => self.exact_len(len),
             _ => None,
         }
     }
 
+    /// Get the length of the array, or `None` if the value is null, checking the value once for
+    /// deserializing optional collections.
+    fn try_array_len(&self) -> Result<Option<usize>, read::Error> {
+        match self.kind {
+            ValueKind::Null => Ok(None),
+            ValueKind::Array => self.array_len().map(Some).ok_or(read::Error::InvalidType),
+            _ => Err(read::invalid_type(self)),
+        }
+    }
+
+    /// Read the `len` elements of the array with a single call to the provider, or `None` if they
+    /// can't be read.
+    fn array_elements(&self, len: usize) -> Option<impl ExactSizeIterator<Item = Self> + '_> {
+        let mut elements = Vec::with_capacity(len);
+        if len > 0 {
+            let read = unsafe {
+                shopify_function_input_read_array_elements(
+                    self.nan_box.to_bits(),
+                    0,
+                    elements.as_mut_ptr(),
+                    len,
+                )
+            };
+            if read == usize::MAX {
+                return None;
+            }
+            unsafe { elements.set_len(read) };
+        }
+        Some(
+            elements
+                .into_iter()
+                .map(|element| self.new_child(NanBox::from_bits(element))),
+        )
+    }
+
     /// Get the length of the object, if it is one.
+    ///
+    /// The exact number of entries is returned for objects of any size, including those with
+    /// more entries than can be encoded in the NaN-box. `None` is returned if the value is not
+    /// an object or its length could not be read.
     pub fn obj_len(&self) -> Option<usize> {
         match self.nan_box.try_decode() {
-            Ok(ValueRef::Object { len, .. }) => {
-                let len = if len == NanBox::MAX_VALUE_LENGTH {
-                    unsafe { shopify_function_input_get_val_len(self.nan_box.to_bits()) }
-                } else {
-                    len
-                };
-                if len == usize::MAX {
-                    None
-                } else {
-                    Some(len)
-                }
-            }
+            Ok(ValueRef::Object { len, .. }) => self.exact_len(len),
             _ => None,
         }
     }
@@ -361,20 +1355,348 @@ impl Value {
         self.new_child(NanBox::from_bits(scope))
     }
 
+    /// Get a view over a range of the array's elements, such as the first few cart lines.
+    ///
+    /// The slice is an array backed by this one, so its length, elements, iteration and
+    /// [`Value::to_msgpack_vec`] work without copying the elements into a new array. Like slicing a
+    /// `Vec`, the range is of element indices, but it's clamped to the length of the array rather
+    /// than panicking, so the slice has fewer elements if the array is too short. The slice is an
+    /// error value if this value is not an array.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let context = Context::new_with_input(serde_json::json!([1, 2, 3, 4]));
+    /// let input = context.input_get().unwrap();
+    /// let page = input.slice(1..3);
+    /// assert_eq!(page.array_len(), Some(2));
+    /// assert_eq!(page.get_at_index(0).as_number(), Some(2.0));
+    /// assert_eq!(input.slice(..10).array_len(), Some(4));
+    /// ```
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> Self {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.saturating_add(1),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => usize::MAX,
+        };
+        let scope =
+            unsafe { shopify_function_input_get_array_slice(self.nan_box.to_bits(), start, end) };
+        self.new_child(NanBox::from_bits(scope))
+    }
+
+    /// Iterate over the elements of the array, in order.
+    ///
+    /// The provider keeps a cursor into the array, so each element is read right after the one
+    /// before it rather than looked up by its index, and iterating over the array takes time
+    /// proportional to its length. The iterator is empty if the value is not an array.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let context = Context::new_with_input(serde_json::json!([1, 2, 3]));
+    /// let input = context.input_get().unwrap();
+    /// let sum: f64 = input.array_iter().filter_map(|element| element.as_number()).sum();
+    /// assert_eq!(sum, 6.0);
+    /// ```
+    pub fn array_iter(&self) -> ArrayIter {
+        ArrayIter {
+            cursor: InputCursor::new(*self, self.array_len().unwrap_or(0)),
+        }
+    }
+
+    /// Call `f` with each element of the array, in order, until it returns
+    /// [`ControlFlow::Break`].
+    ///
+    /// Elements are read from the input in batches, which takes far fewer calls to the provider
+    /// than reading them one at a time with [`Value::get_at_index`], while still stopping early,
+    /// e.g. at the first invalid cart line.
+    ///
+    /// Returns [`ControlFlow::Break`] if `f` stopped early. Nothing is read if the value is not an
+    /// array.
+    pub fn for_each_until<F: FnMut(Value) -> ControlFlow<()>>(&self, mut f: F) -> ControlFlow<()> {
+        if !self.is_array() {
+            return ControlFlow::Continue(());
+        }
+        let mut elements = [0; ARRAY_ELEMENTS_BATCH_SIZE];
+        let mut start = 0;
+        // Reading an element means reading past all the elements before it, so batches start small
+        // and grow, to avoid reading far ahead of an early exit.
+        let mut batch_len = 1;
+        loop {
+            let len = unsafe {
+                shopify_function_input_read_array_elements(
+                    self.nan_box.to_bits(),
+                    start,
+                    elements.as_mut_ptr(),
+                    batch_len,
+                )
+            };
+            if len == usize::MAX {
+                return ControlFlow::Continue(());
+            }
+            for &element in &elements[..len] {
+                f(self.new_child(NanBox::from_bits(element)))?;
+            }
+            if len < batch_len {
+                return ControlFlow::Continue(());
+            }
+            start += len;
+            batch_len = (batch_len * 2).min(ARRAY_ELEMENTS_BATCH_SIZE);
+        }
+    }
+
     /// Get the key of an object by its index.
     pub fn get_obj_key_at_index(&self, index: usize) -> Option<String> {
-        match self.nan_box.try_decode() {
-            Ok(ValueRef::Object { .. }) => {
-                let scope = unsafe {
-                    shopify_function_input_get_obj_key_at_index(self.nan_box.to_bits(), index)
-                };
-                let value = self.new_child(NanBox::from_bits(scope));
-                value.as_string()
-            }
-            _ => None,
+        if !self.is_obj() {
+            return None;
+        }
+        self.obj_key_at_index(index).as_string()
+    }
+
+    /// Get the key of an object by its index, as an interned string, which is interned by the
+    /// provider rather than copied into the guest.
+    ///
+    /// Keys with the same bytes have the same ID, including strings interned earlier with
+    /// [`Context::intern_utf8_str`], so keys that are repeated across many objects, such as the
+    /// attribute names of each cart line, can be compared or mapped by ID without reading them.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let context = Context::new_with_input(serde_json::json!([{ "size": "M" }, { "size": "L" }]));
+    /// let size = context.intern_utf8_str("size");
+    /// let input = context.input_get().unwrap();
+    /// assert_eq!(input.get_at_index(0).get_obj_key_interned_at_index(0), Some(size));
+    /// assert_eq!(input.get_at_index(1).get_obj_key_interned_at_index(0), Some(size));
+    /// assert_eq!(input.get_at_index(1).get_obj_key_interned_at_index(1), None);
+    /// ```
+    pub fn get_obj_key_interned_at_index(&self, index: usize) -> Option<InternedStringId> {
+        match unsafe {
+            shopify_function_input_get_interned_obj_key_at_index(self.nan_box.to_bits(), index)
+        } {
+            usize::MAX => None,
+            id => Some(InternedStringId::new(id)),
+        }
+    }
+
+    fn obj_key_at_index(&self, index: usize) -> Self {
+        let scope =
+            unsafe { shopify_function_input_get_obj_key_at_index(self.nan_box.to_bits(), index) };
+        self.new_child(NanBox::from_bits(scope))
+    }
+
+    /// Iterate over the keys of the object, in order.
+    ///
+    /// Keys are string values, which can be compared to a `&str` without reading them into a
+    /// `String`, or read with [`Value::as_string`]. The iterator is empty if the value is not an
+    /// object.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let context = Context::new_with_input(serde_json::json!({ "a": 1, "b": 2 }));
+    /// let input = context.input_get().unwrap();
+    /// let keys: Vec<String> = input.obj_keys().filter_map(|key| key.as_string()).collect();
+    /// assert_eq!(keys, ["a", "b"]);
+    /// ```
+    pub fn obj_keys(&self) -> ObjKeys {
+        ObjKeys {
+            object: *self,
+            indices: 0..self.obj_len().unwrap_or(0),
+        }
+    }
+
+    fn obj_entry_at_index(&self, index: usize) -> (Self, Self) {
+        let mut entry = [0; 2];
+        unsafe {
+            shopify_function_input_get_obj_entry_at_index(self.nan_box.to_bits(), index, &mut entry)
+        };
+        let [key, value] = entry.map(|val| self.new_child(NanBox::from_bits(val)));
+        (key, value)
+    }
+
+    /// Iterate over the keys and values of the object, in order.
+    ///
+    /// Each entry is read with a single call to the provider, which makes this cheaper than
+    /// iterating over [`Value::obj_keys`] and [`Value::obj_values`] together. The iterator is empty
+    /// if the value is not an object.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let context = Context::new_with_input(serde_json::json!({ "a": 1, "b": 2 }));
+    /// let input = context.input_get().unwrap();
+    /// let sum: f64 = input
+    ///     .obj_entries()
+    ///     .filter(|(key, _)| *key != "b")
+    ///     .filter_map(|(_, value)| value.as_number())
+    ///     .sum();
+    /// assert_eq!(sum, 1.0);
+    /// ```
+    pub fn obj_entries(&self) -> ObjEntries {
+        ObjEntries {
+            object: *self,
+            indices: 0..self.obj_len().unwrap_or(0),
+        }
+    }
+
+    /// Iterate over the keys and values of the object, in order, like [`Value::obj_entries`].
+    ///
+    /// Like [`Value::array_iter`], the provider keeps a cursor into the object, so each entry is
+    /// read right after the one before it rather than looked up by its index. The iterator is empty
+    /// if the value is not an object.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let context = Context::new_with_input(serde_json::json!({ "a": 1, "b": 2 }));
+    /// let input = context.input_get().unwrap();
+    /// let keys: Vec<String> = input
+    ///     .object_entries()
+    ///     .filter(|(_, value)| *value == 2.0)
+    ///     .filter_map(|(key, _)| key.as_string())
+    ///     .collect();
+    /// assert_eq!(keys, ["b"]);
+    /// ```
+    pub fn object_entries(&self) -> ObjectEntries {
+        ObjectEntries {
+            cursor: InputCursor::new(*self, self.obj_len().unwrap_or(0)),
+        }
+    }
+
+    /// Get the length of the object, or `None` if the value is null, checking the value once for
+    /// deserializing optional collections.
+    fn try_obj_len(&self) -> Result<Option<usize>, read::Error> {
+        match self.kind {
+            ValueKind::Null => Ok(None),
+            ValueKind::Object => self.obj_len().map(Some).ok_or(read::Error::InvalidType),
+            _ => Err(read::invalid_type(self)),
+        }
+    }
+
+    /// Iterate over the entries of an object whose length is already known.
+    fn obj_entries_with_len(&self, len: usize) -> ObjEntries {
+        ObjEntries {
+            object: *self,
+            indices: 0..len,
         }
     }
 
+    /// Iterate over the values of the object, in the same order as [`Value::obj_keys`].
+    ///
+    /// The iterator is empty if the value is not an object.
+    pub fn obj_values(&self) -> ObjValues {
+        ObjValues {
+            object: *self,
+            indices: 0..self.obj_len().unwrap_or(0),
+        }
+    }
+
+    /// Read this value and everything nested in it up front.
+    ///
+    /// Arrays and objects are read lazily: each access only reads as far into the input as it needs
+    /// to. This is cheapest when a subtree is traversed once, or only partially. If you will iterate
+    /// over a subtree multiple times, or access its elements out of order, materializing it first
+    /// pays the reading cost once so that all later accesses within it are direct lookups. The
+    /// trade-off is that the entire subtree is read, even the parts that are never accessed.
+    ///
+    /// Returns this value, or an error value if the subtree could not be read. Values other than
+    /// arrays and objects are returned unchanged.
+    pub fn materialize(&self) -> Self {
+        let scope = unsafe { shopify_function_input_materialize(self.nan_box.to_bits()) };
+        self.new_child(NanBox::from_bits(scope))
+    }
+
+    /// Pin this value in the provider, so it can be read again with [`PinnedValue::get`] without
+    /// resolving its path from the input again.
+    ///
+    /// Values found during one pass over the input, such as the cart lines a discount applies to,
+    /// can be stashed and revisited in a later pass. Unlike a copy of the [`Value`], a
+    /// [`PinnedValue`] that outlives the input it was read from returns an
+    /// [`ErrorCode::UnknownPinnedValue`] error rather than reading a value that no longer exists.
+    /// Each pin has its own ID, so it never refers to a value pinned from a later input, and
+    /// dropping it never unpins another pin. The value is unpinned when the [`PinnedValue`] is
+    /// dropped.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let context = Context::new_with_input(serde_json::json!({ "lines": [{ "id": "a" }] }));
+    /// let line = context.input_get().unwrap().get_obj_prop("lines").get_at_index(0).pin();
+    /// assert_eq!(line.get().get_obj_prop("id").as_string().as_deref(), Some("a"));
+    /// ```
+    pub fn pin(&self) -> PinnedValue {
+        PinnedValue {
+            id: unsafe { shopify_function_input_pin(self.nan_box.to_bits()) },
+            _not_send_sync: PhantomData,
+        }
+    }
+
+    /// Get the value encoded as MessagePack, or `None` if it can't be read.
+    ///
+    /// Arrays and objects are copied as they are in the input, so subtrees can be hashed, cached, or
+    /// forwarded, such as stashing a line item in a metafield, without reading them value by value.
+    /// Other values are encoded, with numbers that have no fractional part encoded as integers.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let context = Context::new_with_input(serde_json::json!({"line": {"quantity": 2}}));
+    /// let line = context.input_get().unwrap().get_obj_prop("line");
+    /// let bytes = line.to_msgpack_vec().unwrap();
+    /// assert_eq!(bytes, b"\x81\xa8quantity\x02");
+    /// ```
+    pub fn to_msgpack_vec(&self) -> Option<Vec<u8>> {
+        read_bytes_with_len(MSGPACK_CAPACITY, |out, cap| unsafe {
+            shopify_function_input_read_msgpack_with_len(self.nan_box.to_bits(), out, cap)
+        })
+    }
+
+    /// Get an array of booleans as a [`Bitset`], if it is one.
+    ///
+    /// The provider packs the booleans into bits, so the whole array is read with a single call,
+    /// rather than one per element, and takes an eighth of the memory of a `Vec<bool>`. This suits
+    /// large arrays of flags, such as eligibility masks. `None` is returned if the value is not an
+    /// array, or has elements other than booleans.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let context = Context::new_with_input(serde_json::json!([true, false, true]));
+    /// let input = context.input_get().unwrap();
+    /// let bits = input.as_bitset().unwrap();
+    /// assert_eq!(bits.len(), 3);
+    /// assert_eq!(bits.get(2), Some(true));
+    /// assert_eq!(bits.count_ones(), 2);
+    /// ```
+    pub fn as_bitset(&self) -> Option<Bitset> {
+        let len = self.array_len()?;
+        let bytes = read_bytes_with_len(len.div_ceil(8), |out, cap| unsafe {
+            shopify_function_input_read_bool_bits_with_len(self.nan_box.to_bits(), out, cap)
+        })?;
+        Some(Bitset::from_bytes(bytes, len))
+    }
+
+    /// Get an array of booleans as a `Vec<bool>`, if it is one. Like [`Value::as_bitset`], the
+    /// array is read with a single call to the provider.
+    pub fn as_bool_vec(&self) -> Option<Vec<bool>> {
+        self.as_bitset().map(|bits| bits.iter().collect())
+    }
+
     /// Get the error code, if it is one.
     pub fn as_error(&self) -> Option<ErrorCode> {
         match self.nan_box.try_decode() {
@@ -382,12 +1704,320 @@ impl Value {
             _ => None,
         }
     }
+
+    /// Get the raw bits of the value's NaN-box, to be turned back into a value with
+    /// [`Value::from_raw_parts`].
+    ///
+    /// This is for framework code, such as derive macros and generated types, that caches values
+    /// in a form that doesn't borrow the input, e.g. in a `static` or in an index built in an
+    /// earlier pass. Numbers, booleans and null are encoded in the bits, but strings, arrays and
+    /// objects refer to the input held by the current provider context.
+    pub fn to_raw_bits(&self) -> Val {
+        self.nan_box.to_bits()
+    }
+
+    /// Create a value from the raw bits of its NaN-box, as returned by [`Value::to_raw_bits`].
+    ///
+    /// # Safety
+    /// `bits` must have been returned by [`Value::to_raw_bits`] for a value read from the input of
+    /// `context`, on the current thread, since the input was last replaced, e.g. by another
+    /// [`Context`] being created or an [`InProcessProvider`](crate::InProcessProvider) run.
+    /// Values refer to the input by address, so rehydrating bits from an earlier input reads
+    /// whatever is at that address now, which may be another value or freed memory.
+    ///
+    /// [`Value::pin`] can be used instead where values are kept across inputs, since the provider
+    /// checks pinned values each time they're read. In debug builds, bits that aren't a valid
+    /// NaN-box, such as bits with an unknown type tag, panic.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::{Context, Value};
+    ///
+    /// let context = Context::new_with_input(serde_json::json!({ "id": "1" }));
+    /// let bits = context.input_get().unwrap().get_obj_prop("id").to_raw_bits();
+    /// // SAFETY: the bits were read from the input of `context`, which hasn't been replaced
+    /// let id = unsafe { Value::from_raw_parts(&context, bits) };
+    /// assert_eq!(id.as_string().as_deref(), Some("1"));
+    /// ```
+    pub unsafe fn from_raw_parts(context: &Context, bits: Val) -> Self {
+        // the context is only taken to tie the bits to the input they were read from
+        let _ = context;
+        let nan_box = NanBox::from_bits(bits);
+        debug_assert!(
+            nan_box.try_decode().is_ok(),
+            "invalid NaN-box bits {bits:#x}"
+        );
+        Self::from_nan_box(nan_box)
+    }
+}
+
+/// A reader over the bytes of a string, returned by [`Value::string_reader`].
+pub struct StringReader {
+    string: Value,
+    offset: usize,
+}
+
+impl io::Read for StringReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = unsafe {
+            shopify_function_input_read_utf8_str_chunk(
+                self.string.nan_box.to_bits(),
+                self.offset,
+                buf.as_mut_ptr(),
+                buf.len(),
+            )
+        };
+        if len == usize::MAX {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "value is not a string",
+            ));
+        }
+        self.offset += len;
+        Ok(len)
+    }
+}
+
+/// An iterator over the keys of an object, returned by [`Value::obj_keys`].
+pub struct ObjKeys {
+    object: Value,
+    indices: Range<usize>,
+}
+
+impl Iterator for ObjKeys {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        let index = self.indices.next()?;
+        Some(self.object.obj_key_at_index(index))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl ExactSizeIterator for ObjKeys {}
+
+/// An iterator over the keys and values of an object, returned by [`Value::obj_entries`].
+pub struct ObjEntries {
+    object: Value,
+    indices: Range<usize>,
+}
+
+impl Iterator for ObjEntries {
+    type Item = (Value, Value);
+
+    fn next(&mut self) -> Option<(Value, Value)> {
+        let index = self.indices.next()?;
+        Some(self.object.obj_entry_at_index(index))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl ExactSizeIterator for ObjEntries {}
+
+/// An iterator over the values of an object, returned by [`Value::obj_values`].
+pub struct ObjValues {
+    object: Value,
+    indices: Range<usize>,
+}
+
+impl Iterator for ObjValues {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        let index = self.indices.next()?;
+        Some(self.object.get_at_index(index))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl ExactSizeIterator for ObjValues {}
+
+/// A cursor the provider keeps over the elements of an array or the entries of an object, which is
+/// dropped along with it.
+struct InputCursor {
+    value: Value,
+    id: usize,
+    remaining: usize,
+}
+
+impl InputCursor {
+    fn new(value: Value, len: usize) -> Self {
+        // empty arrays and objects, and other values, have nothing to iterate over
+        let id = if len > 0 {
+            unsafe { shopify_function_input_iter_new(value.nan_box.to_bits()) }
+        } else {
+            usize::MAX
+        };
+        Self {
+            value,
+            id,
+            remaining: if id == usize::MAX { 0 } else { len },
+        }
+    }
+
+    /// Read the next element of the array or value of the object.
+    fn next(&mut self) -> Option<Value> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let scope = unsafe { shopify_function_input_iter_next(self.id) };
+        Some(self.value.new_child(NanBox::from_bits(scope)))
+    }
+
+    /// Read the key of the object's entry last read with [`InputCursor::next`].
+    fn key(&self) -> Value {
+        let scope = unsafe { shopify_function_input_iter_key(self.id) };
+        self.value.new_child(NanBox::from_bits(scope))
+    }
+}
+
+impl Drop for InputCursor {
+    fn drop(&mut self) {
+        if self.id != usize::MAX {
+            unsafe { shopify_function_input_iter_drop(self.id) };
+        }
+    }
+}
+
+/// An iterator over the elements of an array, returned by [`Value::array_iter`].
+pub struct ArrayIter {
+    cursor: InputCursor,
+}
+
+impl Iterator for ArrayIter {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        self.cursor.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.cursor.remaining, Some(self.cursor.remaining))
+    }
+}
+
+impl ExactSizeIterator for ArrayIter {}
+
+/// An iterator over the keys and values of an object, returned by [`Value::object_entries`].
+pub struct ObjectEntries {
+    cursor: InputCursor,
+}
+
+impl Iterator for ObjectEntries {
+    type Item = (Value, Value);
+
+    fn next(&mut self) -> Option<(Value, Value)> {
+        let value = self.cursor.next()?;
+        Some((self.cursor.key(), value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.cursor.remaining, Some(self.cursor.remaining))
+    }
+}
+
+impl ExactSizeIterator for ObjectEntries {}
+
+/// A value pinned in the provider with [`Value::pin`], which is unpinned when this is dropped.
+pub struct PinnedValue {
+    id: usize,
+    /// Opts out of `Send` and `Sync`, like [`Value`].
+    _not_send_sync: PhantomData<*const ()>,
+}
+
+impl PinnedValue {
+    /// Get the pinned value, or an [`ErrorCode::UnknownPinnedValue`] error if the input it was
+    /// read from was replaced.
+    pub fn get(&self) -> Value {
+        let scope = unsafe { shopify_function_input_get_pinned(self.id) };
+        Value::from_nan_box(NanBox::from_bits(scope))
+    }
+}
+
+impl Drop for PinnedValue {
+    fn drop(&mut self) {
+        unsafe { shopify_function_input_unpin(self.id) };
+    }
+}
+
+impl Value {
+    /// Check whether the value is a string with the bytes `other`.
+    ///
+    /// Strings of a different length are rejected from the length in the NaN-box without reading
+    /// them, and short strings are read into a buffer on the stack rather than into a `String`.
+    fn utf8_str_eq(&self, other: &[u8]) -> bool {
+        match self.nan_box.try_decode() {
+            Ok(ValueRef::String { ptr, len }) if len < NanBox::MAX_VALUE_LENGTH => {
+                if len != other.len() {
+                    return false;
+                }
+                let read = |buf: &mut [u8]| unsafe {
+                    shopify_function_input_read_utf8_str(ptr as _, buf.as_mut_ptr(), len)
+                };
+                if len <= STACK_STR_EQ_LEN {
+                    let mut buf = [0; STACK_STR_EQ_LEN];
+                    read(&mut buf);
+                    &buf[..len] == other
+                } else {
+                    let mut buf = vec![0; len];
+                    read(&mut buf);
+                    buf == other
+                }
+            }
+            Ok(ValueRef::String { .. }) => self
+                .read_long_string()
+                .is_some_and(|s| s.as_bytes() == other),
+            _ => false,
+        }
+    }
+}
+
+/// Strings are compared without copying them into a `String`, e.g. `value == "gid://shopify/Product/1"`.
+impl PartialEq<str> for Value {
+    fn eq(&self, other: &str) -> bool {
+        self.utf8_str_eq(other.as_bytes())
+    }
+}
+
+impl PartialEq<&str> for Value {
+    fn eq(&self, other: &&str) -> bool {
+        self.utf8_str_eq(other.as_bytes())
+    }
+}
+
+impl PartialEq<f64> for Value {
+    fn eq(&self, other: &f64) -> bool {
+        self.as_number() == Some(*other)
+    }
+}
+
+impl PartialEq<bool> for Value {
+    fn eq(&self, other: &bool) -> bool {
+        self.as_bool() == Some(*other)
+    }
 }
 
 /// A context for reading and writing values.
 ///
 /// This is created by calling [`Context::new`], and is used to read values from the input and write values to the output.
-pub struct Context;
+///
+/// In non-Wasm debug builds, such as unit tests, dropping a context panics if a value was started
+/// but not finished, e.g. because an error returned part-way through writing an object was ignored,
+/// or if a value was written but the output was never returned with
+/// [`Context::finalize_output_and_return`].
+pub struct Context {
+    auto_intern: Option<Box<write::AutoIntern>>,
+}
 
 /// An error that can occur when creating a [`Context`].
 #[derive(Debug)]
@@ -407,7 +2037,85 @@ impl std::fmt::Display for ContextError {
     }
 }
 
+/// An error returned by [`Context::run_function`], from the step of running the function that
+/// failed.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum RunError<E> {
+    /// The input could not be read.
+    #[error("Failed to read input: {0}")]
+    Input(ContextError),
+    /// The input could not be deserialized.
+    #[error("Failed to deserialize input: {0}")]
+    Read(read::Error),
+    /// The function returned an error.
+    #[error("Function failed: {0}")]
+    Function(E),
+    /// The output could not be serialized.
+    #[error("Failed to serialize output: {0}")]
+    Write(write::Error),
+}
+
+/// The callbacks registered with [`Context::on_new`].
+static NEW_CONTEXT_CALLBACKS: Mutex<Vec<fn()>> = Mutex::new(Vec::new());
+
+/// Whether any callbacks were registered with [`Context::on_new`], so that creating a context
+/// doesn't lock [`NEW_CONTEXT_CALLBACKS`] when there are none.
+static HAS_NEW_CONTEXT_CALLBACKS: AtomicBool = AtomicBool::new(false);
+
+/// The top-level input field holding the version of the schema the input conforms to.
+pub const SCHEMA_VERSION_FIELD: &str = "__schemaVersion";
+
+/// An error returned by [`Context::expect_schema`] when the input does not conform to the expected
+/// schema version.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum SchemaError {
+    /// The input has no schema version, or it is not a string.
+    #[error("Input has no schema version, expected {expected}")]
+    Missing {
+        /// The expected schema version.
+        expected: String,
+    },
+    /// The input has a different schema version.
+    #[error("Input has schema version {actual}, expected {expected}")]
+    Mismatch {
+        /// The expected schema version.
+        expected: String,
+        /// The schema version of the input.
+        actual: String,
+    },
+}
+
 impl Context {
+    /// Deserialize the input, run `function` with it, and serialize its output, which the host then
+    /// finalizes. This is what [`run!`] does with a new context.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::{Context, RunError};
+    ///
+    /// fn double(input: i32) -> Result<i32, String> {
+    ///     input.checked_mul(2).ok_or_else(|| "too large".to_string())
+    /// }
+    ///
+    /// let mut context = Context::new_with_input(serde_json::json!(21));
+    /// context.run_function(double).unwrap();
+    /// assert_eq!(context.finalize_output_and_return().unwrap(), serde_json::json!(42));
+    ///
+    /// let mut context = Context::new_with_input(serde_json::json!(i32::MAX));
+    /// assert!(matches!(context.run_function(double), Err(RunError::Function(_))));
+    /// ```
+    pub fn run_function<I: Deserialize, O: Serialize, E>(
+        &mut self,
+        function: impl FnOnce(I) -> Result<O, E>,
+    ) -> Result<(), RunError<E>> {
+        let input = self.input_get().map_err(RunError::Input)?;
+        let input = I::deserialize(&input).map_err(RunError::Read)?;
+        let output = function(input).map_err(RunError::Function)?;
+        output.serialize(self).map_err(RunError::Write)
+    }
+
     /// Create a new context.
     ///
     /// This is only intended to be invoked when compiled to a Wasm target.
@@ -420,7 +2128,53 @@ impl Context {
 
         #[cfg(target_family = "wasm")]
         {
-            Self
+            Self::notify_new();
+            Self { auto_intern: None }
+        }
+    }
+
+    /// Register a callback that is called each time a context is created, after the provider has
+    /// been initialized with its input.
+    ///
+    /// Frameworks built on this API can use this to reset their own caches of provider state, such
+    /// as interned string IDs or shapes, which aren't valid for every context (e.g. in unit tests
+    /// with a new input, or with an [`InProcessProvider`](crate::InProcessProvider), which calls the
+    /// callbacks each time it runs). Callbacks are called on the thread creating the context, in
+    /// the order they were registered, and are never unregistered, so register each one once.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    /// use std::cell::Cell;
+    ///
+    /// thread_local! {
+    ///     static CONTEXTS: Cell<usize> = const { Cell::new(0) };
+    /// }
+    ///
+    /// Context::on_new(|| CONTEXTS.set(CONTEXTS.get() + 1));
+    /// let _context = Context::new_with_input(serde_json::json!(null));
+    /// assert_eq!(CONTEXTS.get(), 1);
+    /// ```
+    pub fn on_new(callback: fn()) {
+        NEW_CONTEXT_CALLBACKS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(callback);
+        HAS_NEW_CONTEXT_CALLBACKS.store(true, Ordering::Release);
+    }
+
+    /// Call the callbacks registered with [`Context::on_new`].
+    fn notify_new() {
+        if !HAS_NEW_CONTEXT_CALLBACKS.load(Ordering::Acquire) {
+            return;
+        }
+        // copied, so callbacks can register more callbacks
+        let callbacks = NEW_CONTEXT_CALLBACKS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        for callback in callbacks {
+            callback();
         }
     }
 
@@ -429,27 +2183,184 @@ impl Context {
     /// This is only available when compiled to a non-Wasm target, for usage in unit tests.
     #[cfg(not(target_family = "wasm"))]
     pub fn new_with_input(input: serde_json::Value) -> Self {
-        let bytes = rmp_serde::to_vec(&input).unwrap();
+        Self::new_with_serializable(&input)
+    }
+
+    /// Create a new context from any value implementing [`serde::Serialize`], which will be the
+    /// top-level value of the input.
+    ///
+    /// This lets tests pass their input types, or a borrowed `serde_json::Value`, directly instead
+    /// of building a JSON document first. Structs are serialized as objects keyed by field name.
+    ///
+    /// This is only available when compiled to a non-Wasm target, for usage in unit tests.
+    ///
+    /// # Panics
+    /// This will panic if the value fails to serialize, e.g. a map with non-string keys.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// #[derive(serde::Serialize)]
+    /// struct Line {
+    ///     quantity: i32,
+    /// }
+    ///
+    /// let context = Context::new_with_serializable(&Line { quantity: 2 });
+    /// let input = context.input_get().unwrap();
+    /// assert_eq!(input.get_obj_prop("quantity").as_number(), Some(2.0));
+    /// ```
+    #[cfg(not(target_family = "wasm"))]
+    pub fn new_with_serializable<T: serde::Serialize + ?Sized>(input: &T) -> Self {
+        let bytes = rmp_serde::to_vec_named(input).expect("Failed to serialize input");
         shopify_function_provider::initialize_from_msgpack_bytes(bytes);
-        Self
+        Self::notify_new();
+        Self { auto_intern: None }
+    }
+
+    /// Get the top-level value of the input.
+    pub fn input_get(&self) -> Result<Value, ContextError> {
+        let val = unsafe { shopify_function_input_get() };
+        Ok(Value::from_nan_box(NanBox::from_bits(val)))
+    }
+
+    /// Check that the input conforms to the `expected` schema version, as given by its top-level
+    /// [`SCHEMA_VERSION_FIELD`].
+    ///
+    /// Checking this up front surfaces a change to the input schema as a single, descriptive error,
+    /// rather than as a failure deep inside deserialization.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::{Context, SchemaError};
+    ///
+    /// let context = Context::new_with_input(serde_json::json!({ "__schemaVersion": "2025-07" }));
+    /// assert_eq!(context.expect_schema("2025-07"), Ok(()));
+    /// assert_eq!(
+    ///     context.expect_schema("2025-10"),
+    ///     Err(SchemaError::Mismatch { expected: "2025-10".to_string(), actual: "2025-07".to_string() }),
+    /// );
+    /// ```
+    pub fn expect_schema(&self, expected: &str) -> Result<(), SchemaError> {
+        let input = self.input_get().map_err(|_| SchemaError::Missing {
+            expected: expected.to_string(),
+        })?;
+        match input.get_obj_prop(SCHEMA_VERSION_FIELD).as_string() {
+            Some(actual) if actual == expected => Ok(()),
+            Some(actual) => Err(SchemaError::Mismatch {
+                expected: expected.to_string(),
+                actual,
+            }),
+            None => Err(SchemaError::Missing {
+                expected: expected.to_string(),
+            }),
+        }
     }
 
-    /// Get the top-level value of the input.
-    pub fn input_get(&self) -> Result<Value, ContextError> {
-        let val = unsafe { shopify_function_input_get() };
-        Ok(Value {
-            nan_box: NanBox::from_bits(val),
-        })
+    /// Check whether an interned string ID is known to the provider's string interner.
+    pub fn is_interned_utf8_str(&self, id: InternedStringId) -> bool {
+        unsafe { shopify_function_interned_utf8_str_exists(id.as_usize()) != 0 }
     }
 
     /// Intern a string. This can lead to performance gains if you are using the same string multiple times,
     /// as it saves unnecessary string copies. For example, if you are reading the same property from multiple objects,
     /// or serializing the same key on an object, you can intern the string once and reuse it.
     pub fn intern_utf8_str(&self, s: &str) -> InternedStringId {
-        let len = s.len();
-        let ptr = s.as_ptr();
-        let id = unsafe { shopify_function_intern_utf8_str(ptr, len) };
-        InternedStringId(id)
+        self.intern_bytes(s.as_bytes())
+    }
+
+    /// Intern a sequence of bytes, which does not need to be valid UTF-8.
+    ///
+    /// Interned strings are stored and compared as raw bytes, so this can look up keys exactly as
+    /// they appear in the input, e.g. unusual keys in metafield JSON. Writing an ID interned from
+    /// invalid UTF-8 to the output produces an invalid string, so such IDs should only be used for
+    /// reading.
+    pub fn intern_bytes(&self, bytes: &[u8]) -> InternedStringId {
+        let id = unsafe { shopify_function_intern_utf8_str(bytes.as_ptr(), bytes.len()) };
+        InternedStringId::new(id)
+    }
+
+    /// Write every interned string and its ID to the logs, one per line, returning the number of
+    /// interned strings.
+    ///
+    /// This is meant for debugging, e.g. to find out why a key interned by the function doesn't
+    /// match the input. The logs are a ring buffer, so the output may push out earlier logs.
+    pub fn log_interned_strings(&self) -> usize {
+        unsafe { shopify_function_log_interned_strings() }
+    }
+
+    /// Get the interned strings and their IDs, in the order they were interned. Strings that
+    /// aren't valid UTF-8 are converted lossily.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn interned_strings(&self) -> Vec<(InternedStringId, String)> {
+        shopify_function_provider::interned_strings()
+            .into_iter()
+            .map(|(id, bytes)| {
+                (
+                    InternedStringId::new(id),
+                    String::from_utf8_lossy(&bytes).into_owned(),
+                )
+            })
+            .collect()
+    }
+
+    /// Get the `(major, minor, patch)` version of the provider running this function.
+    ///
+    /// This is useful for logging which provider a function ran against when investigating issues,
+    /// or for adapting to behavior that differs between provider versions.
+    pub fn provider_version(&self) -> (u16, u16, u16) {
+        let version = unsafe { shopify_function_provider_version() };
+        (
+            (version >> 32) as u16,
+            (version >> 16) as u16,
+            version as u16,
+        )
+    }
+
+    /// Get a seed derived from the input, for functions that need pseudo-randomness, such as
+    /// bucketing carts into A/B test groups.
+    ///
+    /// Functions must produce the same output each time they run with the same input, so they
+    /// shouldn't read clocks or random numbers. This seed is the same every time the function
+    /// runs with the same input, so it can seed a pseudo-random number generator instead.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let context = Context::new_with_input(serde_json::json!({ "cart": { "id": "1" } }));
+    /// let in_experiment = context.deterministic_seed() % 100 < 10;
+    /// let again = Context::new_with_input(serde_json::json!({ "cart": { "id": "1" } }));
+    /// assert_eq!(again.deterministic_seed() % 100 < 10, in_experiment);
+    /// ```
+    pub fn deterministic_seed(&self) -> u64 {
+        unsafe { shopify_function_deterministic_seed() }
+    }
+
+    /// Limit the number of reads of the input that look up or traverse values, such as getting a
+    /// property or an element, or advancing an iterator, for functions that defend against large
+    /// inputs.
+    ///
+    /// Each value read spends one unit of the budget, as do slices of arrays and reads or
+    /// comparisons of strings, and reads fail with [`ErrorCode::BudgetExceeded`] once it's spent.
+    /// A function can then stop and write a meaningful output, rather than run until the
+    /// platform's fuel limit stops it without one. Setting the budget again replaces what's left
+    /// of it.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    /// use shopify_function_wasm_api_core::read::ErrorCode;
+    ///
+    /// let context = Context::new_with_input(serde_json::json!([1, 2, 3]));
+    /// let input = context.input_get().unwrap();
+    /// context.set_work_budget(2);
+    /// let elements: Vec<_> = input.array_iter().collect();
+    /// assert_eq!(elements[1].as_number(), Some(2.0));
+    /// assert_eq!(elements[2].as_error(), Some(ErrorCode::BudgetExceeded));
+    /// ```
+    pub fn set_work_budget(&self, budget: usize) {
+        unsafe { shopify_function_set_work_budget(budget) }
     }
 }
 
@@ -459,6 +2370,65 @@ impl Default for Context {
     }
 }
 
+#[cfg(all(not(target_family = "wasm"), debug_assertions))]
+impl Drop for Context {
+    fn drop(&mut self) {
+        // don't turn an existing panic into an abort
+        if std::thread::panicking() {
+            return;
+        }
+        if let Some(description) = shopify_function_provider::write::unfinished_output_description()
+        {
+            panic!("Context dropped with unfinished output: {description}");
+        }
+    }
+}
+
+/// Define a function's entrypoint, which runs `function`, a `fn(Input) -> Result<Output, E>` whose
+/// input implements [`Deserialize`], output implements [`Serialize`], and error implements
+/// [`Display`](std::fmt::Display).
+///
+/// This defines `main`, exported as `_start` for `wasm32-unknown-unknown`, which configures panics
+/// with [`init_panic_handler`], creates a [`Context`] and calls [`Context::run_function`] with it.
+/// If any step fails, the error is logged and the function traps, so the host reports it as
+/// failed.
+///
+/// # Example
+/// ```rust,no_run
+/// /// Allow ordering at most one of each item.
+/// fn function(quantities: Vec<i32>) -> Result<bool, String> {
+///     if let Some(quantity) = quantities.iter().find(|quantity| **quantity < 0) {
+///         return Err(format!("invalid quantity {quantity}"));
+///     }
+///     Ok(quantities.iter().all(|quantity| *quantity <= 1))
+/// }
+///
+/// shopify_function_wasm_api::run!(function);
+/// ```
+#[macro_export]
+macro_rules! run {
+    ($function:expr) => {
+        #[cfg_attr(target_os = "unknown", export_name = "_start")]
+        fn main() {
+            $crate::run_main($function)
+        }
+    };
+}
+
+/// Run `function` with a new context, as the `main` defined by [`run!`] does.
+#[doc(hidden)]
+pub fn run_main<I: Deserialize, O: Serialize, E: std::fmt::Display>(
+    function: impl FnOnce(I) -> Result<O, E>,
+) {
+    init_panic_handler();
+    let mut context = Context::new();
+    if let Err(error) = context.run_function(function) {
+        // logged like a panic, as the host only reads the logs of a function that traps
+        log::log_utf8_str(&format!("{error}\n"));
+        std::process::abort();
+    }
+}
+
 /// Configures panics to write to the logging API.
 pub fn init_panic_handler() {
     #[cfg(target_family = "wasm")]
@@ -485,10 +2455,18 @@ mod tests {
         let id2 = CACHED_INTERNED_STRING_ID.load();
         context.write_interned_utf8_str(id).unwrap();
         assert_eq!(id, id2);
+        assert_eq!(
+            context.finalize_output_and_return().unwrap(),
+            serde_json::json!("test")
+        );
 
         // Test writing again with new context in same test to test same thread execution.
         let mut context = Context::new_with_input(serde_json::json!({}));
         context.write_interned_utf8_str(id).unwrap();
+        assert_eq!(
+            context.finalize_output_and_return().unwrap(),
+            serde_json::json!("test")
+        );
     }
 
     #[test]
@@ -496,6 +2474,10 @@ mod tests {
         let mut context = Context::new_with_input(serde_json::json!({}));
         let id = CACHED_INTERNED_STRING_ID.load();
         context.write_interned_utf8_str(id).unwrap();
+        assert_eq!(
+            context.finalize_output_and_return().unwrap(),
+            serde_json::json!("test")
+        );
     }
 
     #[test]
@@ -503,22 +2485,273 @@ mod tests {
         let mut context = Context::new_with_input(serde_json::json!({}));
         let id = CACHED_INTERNED_STRING_ID.load();
         context.write_interned_utf8_str(id).unwrap();
+        assert_eq!(
+            context.finalize_output_and_return().unwrap(),
+            serde_json::json!("test")
+        );
         // Test this still works across multiple threads.
         thread::spawn(|| {
             let mut context = Context::new_with_input(serde_json::json!({}));
             let id = CACHED_INTERNED_STRING_ID.load();
             context.write_interned_utf8_str(id).unwrap();
+            assert_eq!(
+                context.finalize_output_and_return().unwrap(),
+                serde_json::json!("test")
+            );
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_str_trim_eq() {
+        // "café" with a precomposed é in the input and a combining accent in the comparison
+        let context = Context::new_with_input(serde_json::json!({
+            "code": "\u{2003}caf\u{e9}\t",
+            "number": 1,
+        }));
+        let input = context.input_get().unwrap();
+        let code = input.get_obj_prop("code");
+        assert!(code.str_trim_eq(" caf\u{e9} "));
+        assert!(!code.str_trim_eq("cafe\u{301}"));
+        assert!(code.str_trim_eq_nfc("cafe\u{301}").unwrap());
+        assert!(!code.str_trim_eq_nfc("cafe").unwrap());
+        assert!(!input.get_obj_prop("number").str_trim_eq("1"));
+        assert!(!input.get_obj_prop("missing").str_trim_eq(""));
+    }
+
+    #[test]
+    fn test_on_new() {
+        use std::cell::Cell;
+
+        thread_local! {
+            static CONTEXTS: Cell<usize> = const { Cell::new(0) };
+            static CACHED_ID: Cell<Option<InternedStringId>> = const { Cell::new(None) };
+        }
+        fn reset() {
+            CONTEXTS.set(CONTEXTS.get() + 1);
+            CACHED_ID.set(None);
+        }
+        fn register_nested() {
+            // callbacks can register callbacks without deadlocking
+            Context::on_new(|| {});
+        }
+        Context::on_new(reset);
+        Context::on_new(register_nested);
+
+        let mut context = Context::new_with_input(serde_json::json!(null));
+        assert_eq!(CONTEXTS.get(), 1);
+        CACHED_ID.set(Some(context.intern_utf8_str("key")));
+        let mut provider = InProcessProvider::new(&serde_json::json!(null));
+        provider.run(|_| {});
+        assert_eq!(CONTEXTS.get(), 2);
+        assert_eq!(CACHED_ID.get(), None);
+        context = Context::new_with_input(serde_json::json!(null));
+        assert_eq!(CONTEXTS.get(), 3);
+        context.write_null().unwrap();
+        assert_eq!(
+            context.finalize_output_and_return().unwrap(),
+            serde_json::Value::Null
+        );
+    }
+
+    #[test]
+    fn test_provider_version() {
+        let context = Context::new_with_input(serde_json::json!(null));
+        let (major, _, _) = context.provider_version();
+        assert_eq!(
+            format!("shopify_function_v{major}"),
+            shopify_function_provider::PROVIDER_MODULE_NAME
+        );
+    }
+
+    #[test]
+    fn test_interned_strings() {
+        let context = Context::new_with_input(serde_json::json!(null));
+        let foo = context.intern_utf8_str("foo");
+        let invalid = context.intern_bytes(&[b'a', 0xff]);
+        assert_eq!(
+            context.interned_strings(),
+            [(foo, "foo".to_string()), (invalid, "a\u{fffd}".to_string())]
+        );
+        assert_eq!(context.log_interned_strings(), 2);
+    }
+
+    #[test]
+    fn test_log() {
+        let context = Context::new_with_input(serde_json::json!(null));
+        assert_eq!(context.log(&"a".repeat(995)), 0);
+        assert_eq!(context.log(&"b".repeat(10)), 4);
+    }
+
+    #[test]
+    fn test_get_obj_prop_bytes() {
+        let context = Context::new_with_input(serde_json::json!({ "a": 1, "ab": 2 }));
+        let input = context.input_get().unwrap();
+        let key = "gid://shopify/ab";
+        let suffix = &key.as_bytes()[key.len() - 2..];
+        assert_eq!(input.get_obj_prop_bytes(suffix).as_number(), Some(2.0));
+        assert_eq!(
+            input.get_obj_prop_bytes(&suffix[..1]).as_number(),
+            Some(1.0)
+        );
+        assert!(input.get_obj_prop_bytes(b"b").is_null());
+        assert!(input.get_obj_prop_bytes(&[0xff]).is_null());
+    }
+
+    #[test]
+    fn test_run_function_errors() {
+        let mut context = Context::new_with_input(serde_json::json!("a"));
+        let result = context.run_function(|input: i32| Ok::<_, String>(input));
+        assert!(matches!(
+            result,
+            Err(RunError::Read(read::Error::InvalidType))
+        ));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Failed to deserialize input: Invalid type"
+        );
+
+        let mut context = Context::new_with_input(serde_json::json!(1));
+        let result = context.run_function(|_: i32| Err::<i32, _>("failed"));
+        assert_eq!(result.unwrap_err().to_string(), "Function failed: failed");
+    }
+
+    #[test]
+    fn test_try_getters() {
+        let context = Context::new_with_input(serde_json::json!({
+            "bool": false,
+            "number": 1.5,
+            "string": "s",
+            "null": null,
+        }));
+        let input = context.input_get().unwrap();
+        let prop = |name| input.try_get_obj_prop(name).unwrap();
+        assert!(!prop("bool").try_as_bool().unwrap());
+        assert_eq!(prop("number").try_as_number().unwrap(), 1.5);
+        assert_eq!(prop("string").try_as_string().unwrap(), "s");
+        assert!(matches!(
+            prop("null").try_as_string(),
+            Err(read::Error::InvalidType)
+        ));
+        assert!(matches!(
+            input.get_obj_prop("missing").try_as_number(),
+            Err(read::Error::Missing)
+        ));
+        assert!(matches!(
+            input.try_get_obj_prop("missing"),
+            Err(read::Error::Missing)
+        ));
+
+        // errors carry their code, including through values read from them
+        let not_an_object = input.get_obj_prop("string").get_obj_prop("a");
+        assert!(matches!(
+            not_an_object.try_as_string(),
+            Err(read::Error::Read(ErrorCode::NotAnObject))
+        ));
+        assert!(matches!(
+            not_an_object.try_get_obj_prop("b"),
+            Err(read::Error::Read(ErrorCode::NotAnObject))
+        ));
+        assert!(matches!(
+            prop("null").try_get_obj_prop("a"),
+            Err(read::Error::Read(ErrorCode::NotAnObject))
+        ));
+    }
+
+    #[test]
+    fn test_intern_bytes() {
+        let context = Context::new_with_input(serde_json::json!({ "a\u{0}b": 1, "ü": 2 }));
+        let input = context.input_get().unwrap();
+        let nul = context.intern_bytes(b"a\0b");
+        assert_eq!(input.get_interned_obj_prop(nul).as_number(), Some(1.0));
+        let umlaut = context.intern_bytes("ü".as_bytes());
+        assert_eq!(input.get_interned_obj_prop(umlaut).as_number(), Some(2.0));
+        let partial = context.intern_bytes(&"ü".as_bytes()[..1]);
+        assert!(context.is_interned_utf8_str(partial));
+        assert!(input.get_interned_obj_prop(partial).is_null());
+    }
+
+    #[test]
+    fn test_expect_schema_missing() {
+        [
+            serde_json::json!({}),
+            serde_json::json!({ "__schemaVersion": 1 }),
+            serde_json::json!(["2025-07"]),
+        ]
+        .into_iter()
+        .for_each(|input| {
+            let context = Context::new_with_input(input);
+            assert_eq!(
+                context.expect_schema("2025-07"),
+                Err(SchemaError::Missing {
+                    expected: "2025-07".to_string()
+                })
+            );
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "Context dropped with unfinished output: Object(")]
+    fn test_drop_with_unfinished_output() {
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        let result = context.write_object(|ctx| ctx.write_utf8_str("key"), 1);
+        assert!(matches!(result, Err(write::Error::ObjectLengthError)));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Context dropped with unfinished output: the value was finished but never finalized"
+    )]
+    fn test_drop_with_unfinalized_output() {
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        context.write_array(|_| Ok(()), 0).unwrap();
+    }
+
+    #[test]
+    fn test_drop_with_finalized_or_no_output() {
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        context.write_array(|_| Ok(()), 0).unwrap();
+        assert_eq!(
+            context.finalize_output_and_return().unwrap(),
+            serde_json::json!([])
+        );
+
+        let context = Context::new_with_input(serde_json::json!({}));
+        assert!(context.input_get().unwrap().is_obj());
+    }
+
+    #[test]
+    fn test_interned_string_id_from_another_thread() {
+        let context = Context::new_with_input(serde_json::json!({ "a": 1, "b": 2 }));
+        let b = context.intern_utf8_str("b");
+        let a = thread::spawn(|| {
+            let context = Context::new_with_input(serde_json::json!({}));
+            context.intern_utf8_str("a")
         })
         .join()
         .unwrap();
+        let input = context.input_get().unwrap();
+
+        assert!(context.is_interned_utf8_str(b));
+        assert!(!context.is_interned_utf8_str(a));
+        assert_eq!(input.get_interned_obj_prop(b).as_number(), Some(2.0));
+        assert_eq!(
+            input.get_interned_obj_prop(a).as_error(),
+            Some(ErrorCode::UnknownInternedString)
+        );
+
+        let mut context = context;
+        assert!(matches!(
+            context.write_interned_utf8_str(a),
+            Err(write::Error::UnknownInternedString)
+        ));
     }
 
     #[test]
     fn test_array_len_with_null_ptr() {
         Context::new_with_input(serde_json::json!({}));
-        let value = Value {
-            nan_box: NanBox::array(0, NanBox::MAX_VALUE_LENGTH),
-        };
+        let value = Value::from_nan_box(NanBox::array(0, NanBox::MAX_VALUE_LENGTH));
         let len = value.array_len();
         assert_eq!(len, None);
     }
@@ -526,9 +2759,7 @@ mod tests {
     #[test]
     fn test_array_len_with_non_length_eligible_nan_box() {
         Context::new_with_input(serde_json::json!({}));
-        let value = Value {
-            nan_box: NanBox::null(),
-        };
+        let value = Value::from_nan_box(NanBox::null());
         let len = value.array_len();
         assert_eq!(len, None);
     }
@@ -536,19 +2767,710 @@ mod tests {
     #[test]
     fn test_obj_len_with_null_ptr() {
         Context::new_with_input(serde_json::json!({}));
-        let value = Value {
-            nan_box: NanBox::obj(0, NanBox::MAX_VALUE_LENGTH),
-        };
+        let value = Value::from_nan_box(NanBox::obj(0, NanBox::MAX_VALUE_LENGTH));
         let len = value.obj_len();
         assert_eq!(len, None);
     }
 
     #[test]
-    fn test_obj_len_with_non_length_eligible_nan_box() {
+    fn test_string_with_null_ptr() {
         Context::new_with_input(serde_json::json!({}));
-        let value = Value {
-            nan_box: NanBox::null(),
+        let value = Value::from_nan_box(NanBox::string(0, NanBox::MAX_VALUE_LENGTH));
+        assert_eq!(value.as_string(), None);
+    }
+
+    #[test]
+    fn test_materialize() {
+        let input = serde_json::json!({
+            "lines": [{ "quantity": 1 }, { "quantity": 2, "nested": [[3]] }],
+            "name": "cart",
+        });
+        let context = Context::new_with_input(input);
+        let value = context.input_get().unwrap();
+        let materialized = value.materialize();
+        assert_eq!(materialized.nan_box.to_bits(), value.nan_box.to_bits());
+
+        let lines = materialized.get_obj_prop("lines");
+        assert_eq!(lines.array_len(), Some(2));
+        assert_eq!(
+            lines
+                .get_at_index(1)
+                .get_obj_prop("nested")
+                .get_at_index(0)
+                .get_at_index(0)
+                .as_number(),
+            Some(3.0)
+        );
+        assert_eq!(
+            lines.get_at_index(0).get_obj_prop("quantity").as_number(),
+            Some(1.0)
+        );
+        assert_eq!(
+            materialized.get_obj_prop("name").as_string(),
+            Some("cart".to_string())
+        );
+    }
+
+    #[test]
+    fn test_materialize_partially_read_value() {
+        let context = Context::new_with_input(serde_json::json!([[1, 2], [3, 4], [5, 6]]));
+        let value = context.input_get().unwrap();
+        assert_eq!(value.get_at_index(1).get_at_index(0).as_number(), Some(3.0));
+        let value = value.materialize();
+        assert_eq!(value.get_at_index(2).get_at_index(1).as_number(), Some(6.0));
+        assert_eq!(value.get_at_index(0).get_at_index(1).as_number(), Some(2.0));
+    }
+
+    #[test]
+    fn test_materialize_non_collection_values() {
+        let context = Context::new_with_input(serde_json::json!("a"));
+        let value = context.input_get().unwrap();
+        assert_eq!(value.materialize().as_string(), Some("a".to_string()));
+
+        let value = Value::from_nan_box(NanBox::array(0, 1));
+        assert_eq!(value.materialize().as_error(), Some(ErrorCode::ReadError));
+    }
+
+    #[test]
+    fn test_kind() {
+        let context = Context::new_with_input(serde_json::json!([null, true, 1.5, "a", {}, []]));
+        let value = context.input_get().unwrap();
+        assert_eq!(value.kind(), ValueKind::Array);
+        let kinds: Vec<ValueKind> = (0..6).map(|i| value.get_at_index(i).kind()).collect();
+        assert_eq!(
+            kinds,
+            [
+                ValueKind::Null,
+                ValueKind::Bool,
+                ValueKind::Number,
+                ValueKind::String,
+                ValueKind::Object,
+                ValueKind::Array,
+            ]
+        );
+        assert_eq!(value.get_obj_prop("a").kind(), ValueKind::Error);
+    }
+
+    #[test]
+    fn test_obj_keys_and_values() {
+        let context = Context::new_with_input(serde_json::json!({ "a": 1, "b": [2], "c": null }));
+        let input = context.input_get().unwrap();
+        let keys = input.obj_keys();
+        assert_eq!(keys.len(), 3);
+        assert!(keys.eq(["a", "b", "c"]));
+        let mut values = input.obj_values();
+        assert_eq!(values.next().and_then(|value| value.as_number()), Some(1.0));
+        assert_eq!(values.len(), 2);
+        assert!(values.next().is_some_and(|value| value.is_array()));
+        assert!(values.next().is_some_and(|value| value.is_null()));
+        assert!(values.next().is_none());
+
+        let array = input.get_obj_prop("b");
+        assert_eq!(array.obj_keys().len(), 0);
+        assert_eq!(array.obj_values().len(), 0);
+    }
+
+    #[test]
+    fn test_obj_entries() {
+        let context = Context::new_with_input(serde_json::json!({ "a": 1, "b": [2] }));
+        let input = context.input_get().unwrap();
+        let mut entries = input.obj_entries();
+        assert_eq!(entries.len(), 2);
+        let (key, value) = entries.next().unwrap();
+        assert!(key == "a" && value == 1.0);
+        let (key, value) = entries.next().unwrap();
+        assert!(key == "b" && value.is_array());
+        assert!(entries.next().is_none());
+
+        let array = input.get_obj_prop("b");
+        assert_eq!(array.obj_entries().len(), 0);
+        let (key, value) = array.obj_entry_at_index(0);
+        assert_eq!(key.as_error(), Some(ErrorCode::NotAnObject));
+        assert_eq!(value.as_error(), Some(ErrorCode::NotAnObject));
+        let (key, value) = input.obj_entry_at_index(2);
+        assert!(key.as_error().is_some() && value.as_error().is_some());
+    }
+
+    #[test]
+    fn test_array_iter() {
+        let context = Context::new_with_input(serde_json::json!({ "a": [1, [2, 3]], "b": [] }));
+        let input = context.input_get().unwrap();
+        let mut elements = input.get_obj_prop("a").array_iter();
+        assert_eq!(elements.len(), 2);
+        assert!(elements.next().unwrap() == 1.0);
+        let nested: Vec<f64> = elements
+            .next()
+            .unwrap()
+            .array_iter()
+            .filter_map(|element| element.as_number())
+            .collect();
+        assert_eq!(nested, [2.0, 3.0]);
+        assert!(elements.next().is_none());
+
+        assert_eq!(input.get_obj_prop("b").array_iter().len(), 0);
+        assert_eq!(input.array_iter().len(), 0);
+        assert_eq!(input.get_obj_prop("c").array_iter().len(), 0);
+    }
+
+    #[test]
+    fn test_object_entries() {
+        let context = Context::new_with_input(serde_json::json!({ "a": 1, "b": { "c": 2 } }));
+        let input = context.input_get().unwrap();
+        let mut entries = input.object_entries();
+        assert_eq!(entries.len(), 2);
+        let (key, value) = entries.next().unwrap();
+        assert!(key == "a" && value == 1.0);
+        let (key, value) = entries.next().unwrap();
+        assert!(key == "b" && value.is_obj());
+        assert!(entries.next().is_none());
+
+        assert_eq!(input.get_obj_prop("a").object_entries().len(), 0);
+        let nested = input.get_obj_prop("b");
+        assert_eq!(nested.array_iter().len(), 0);
+        let (key, value) = nested.object_entries().next().unwrap();
+        assert!(key == "c" && value == 2.0);
+    }
+
+    #[test]
+    fn test_input_iterators_are_reused() {
+        let context = Context::new_with_input(serde_json::json!([[1], [2], [3]]));
+        let input = context.input_get().unwrap();
+        let ids: Vec<usize> = input
+            .array_iter()
+            .map(|element| element.array_iter().cursor.id)
+            .collect();
+        // the outer iterator is 0, and each inner one is dropped before the next is created
+        assert_eq!(ids, [1, 1, 1]);
+
+        // an iterator's ID is unknown once it's dropped
+        let scope = unsafe { shopify_function_input_iter_next(1) };
+        assert_eq!(
+            NanBox::from_bits(scope).try_decode(),
+            Ok(ValueRef::Error(ErrorCode::UnknownIterator))
+        );
+    }
+
+    #[test]
+    fn test_set_work_budget() {
+        let context = Context::new_with_input(serde_json::json!({ "a": 1, "b": [1, 2, 3] }));
+        let input = context.input_get().unwrap();
+        context.set_work_budget(3);
+        let [a, b] = input.read_shape(&Shape::new(&["a", "b"]));
+        assert!(a == 1.0 && b.is_array());
+        let elements: Vec<Value> = b.array_elements(3).unwrap().collect();
+        assert_eq!(elements[0].as_number(), Some(1.0));
+        assert_eq!(elements[1].as_error(), Some(ErrorCode::BudgetExceeded));
+        assert_eq!(
+            input.get_obj_prop("a").as_error(),
+            Some(ErrorCode::BudgetExceeded)
+        );
+
+        context.set_work_budget(1);
+        assert_eq!(input.get_obj_prop("a").as_number(), Some(1.0));
+    }
+
+    #[test]
+    fn test_work_budget_for_slices_and_strings() {
+        let context = Context::new_with_input(serde_json::json!([" a ", " b ", " c "]));
+        let input = context.input_get().unwrap();
+
+        // walking the array by slicing off its first element never reads an element by index
+        context.set_work_budget(2);
+        let mut rest = input;
+        for _ in 0..3 {
+            rest = rest.slice(1..);
+        }
+        assert_eq!(rest.as_error(), Some(ErrorCode::BudgetExceeded));
+
+        context.set_work_budget(3);
+        let first = input.get_at_index(0);
+        assert!(first.str_trim_eq_nfc("a").unwrap());
+        assert!(first.str_trim_eq("a"));
+        assert!(matches!(
+            first.str_trim_eq_nfc("a"),
+            Err(read::Error::Read(ErrorCode::BudgetExceeded))
+        ));
+        assert_eq!(first.to_msgpack_vec(), None);
+    }
+
+    #[test]
+    fn test_pin() {
+        let context = Context::new_with_input(serde_json::json!([{ "id": "a" }, { "id": "b" }]));
+        let input = context.input_get().unwrap();
+        let pinned: Vec<PinnedValue> = input.array_iter().map(|line| line.pin()).collect();
+        // reading the input again doesn't change the pinned values
+        assert!(input.get_at_index(1).get_obj_prop("id") == "b");
+        assert!(pinned[0].get().get_obj_prop("id") == "a");
+        assert!(pinned[1].get().get_obj_prop("id") == "b");
+
+        // pins from the replaced input don't refer to pins from the new one, which reuse their
+        // places in the provider, and dropping them doesn't unpin the new ones
+        let context = Context::new_with_input(serde_json::json!(["c"]));
+        let new_pin = context.input_get().unwrap().get_at_index(0).pin();
+        for pin in &pinned {
+            assert_eq!(pin.get().as_error(), Some(ErrorCode::UnknownPinnedValue));
+        }
+        drop(pinned);
+        assert!(new_pin.get() == "c");
+    }
+
+    #[test]
+    fn test_string_reader() {
+        let long = "abc".repeat(1000);
+        let context = Context::new_with_input(serde_json::json!([long, "", 1]));
+        let input = context.input_get().unwrap();
+
+        let mut reader = input.get_at_index(0).string_reader();
+        let mut chunk = [0; 7];
+        assert_eq!(io::Read::read(&mut reader, &mut chunk).unwrap(), 7);
+        assert_eq!(&chunk, b"abcabca");
+        let mut rest = String::new();
+        io::Read::read_to_string(&mut reader, &mut rest).unwrap();
+        assert_eq!(rest, long[7..]);
+        assert_eq!(io::Read::read(&mut reader, &mut chunk).unwrap(), 0);
+
+        let mut empty = Vec::new();
+        io::Read::read_to_end(&mut input.get_at_index(1).string_reader(), &mut empty).unwrap();
+        assert!(empty.is_empty());
+
+        let err = io::Read::read(&mut input.get_at_index(2).string_reader(), &mut chunk);
+        assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_eq_primitives() {
+        let long = "a".repeat(STACK_STR_EQ_LEN + 1);
+        let context = Context::new_with_input(serde_json::json!({
+            "id": "gid://shopify/Product/1",
+            "empty": "",
+            "long": long,
+            "quantity": 2,
+            "price": 1.5,
+            "valid": true,
+        }));
+        let input = context.input_get().unwrap();
+
+        let id = input.get_obj_prop("id");
+        assert!(id == "gid://shopify/Product/1");
+        assert!(id == *"gid://shopify/Product/1");
+        assert!(id != "gid://shopify/Product/2");
+        assert!(id != "gid://shopify/Product/10");
+        assert!(input.get_obj_prop("empty") == "");
+        assert!(input.get_obj_prop("long") == long.as_str());
+        assert!(input.get_obj_prop("long") != long[1..]);
+        // strings whose length doesn't fit in the NaN-box
+        let Ok(ValueRef::String { ptr, .. }) = input.get_obj_prop("long").nan_box.try_decode()
+        else {
+            panic!("expected a string");
+        };
+        let value = Value::from_nan_box(NanBox::string(ptr, NanBox::MAX_VALUE_LENGTH));
+        assert!(value == long.as_str());
+        assert!(value != long[1..]);
+
+        assert!(input.get_obj_prop("quantity") == 2.0);
+        assert!(input.get_obj_prop("price") == 1.5);
+        assert!(input.get_obj_prop("price") != 2.0);
+        assert!(input.get_obj_prop("valid") == true);
+        assert!(input.get_obj_prop("valid") != false);
+
+        // values of other types are never equal
+        assert!(input.get_obj_prop("quantity") != "2");
+        assert!(id != true);
+        assert!(input.get_obj_prop("valid") != 1.0);
+        assert!(input.get_obj_prop("missing") != "");
+        assert!(input != "");
+    }
+
+    #[test]
+    fn test_for_each_until() {
+        [
+            0,
+            1,
+            ARRAY_ELEMENTS_BATCH_SIZE,
+            ARRAY_ELEMENTS_BATCH_SIZE + 1,
+            100,
+        ]
+        .into_iter()
+        .for_each(|len| {
+            let elements: Vec<usize> = (0..len).collect();
+            let context = Context::new_with_input(serde_json::json!(elements));
+            let value = context.input_get().unwrap();
+            let mut visited = Vec::new();
+            let result = value.for_each_until(|element| {
+                visited.push(element.as_number().unwrap() as usize);
+                ControlFlow::Continue(())
+            });
+            assert_eq!(result, ControlFlow::Continue(()));
+            assert_eq!(visited, elements);
+        });
+    }
+
+    #[test]
+    fn test_for_each_until_breaks_early() {
+        let context = Context::new_with_input(serde_json::json!({
+            "lines": (0..100).map(|i| serde_json::json!({ "quantity": i % 50 })).collect::<Vec<_>>(),
+        }));
+        let lines = context.input_get().unwrap().get_obj_prop("lines");
+        let mut visited = 0;
+        let result = lines.for_each_until(|line| {
+            visited += 1;
+            if line.get_obj_prop("quantity").as_number() == Some(40.0) {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(result, ControlFlow::Break(()));
+        assert_eq!(visited, 41);
+    }
+
+    #[test]
+    fn test_for_each_until_non_array() {
+        let context = Context::new_with_input(serde_json::json!({ "a": 1 }));
+        let value = context.input_get().unwrap();
+        let result = value.for_each_until(|_| panic!("should not be called"));
+        assert_eq!(result, ControlFlow::Continue(()));
+
+        let value = Value::from_nan_box(NanBox::array(0, 1));
+        let result = value.for_each_until(|_| panic!("should not be called"));
+        assert_eq!(result, ControlFlow::Continue(()));
+    }
+
+    #[test]
+    fn test_new_with_serializable() {
+        #[derive(serde::Serialize)]
+        struct Line<'a> {
+            id: &'a str,
+            quantity: i32,
+            attributes: Vec<(&'a str, Option<bool>)>,
+        }
+
+        let context = Context::new_with_serializable(&[Line {
+            id: "gid://shopify/CartLine/1",
+            quantity: 3,
+            attributes: vec![("gift", Some(true)), ("engraving", None)],
+        }]);
+        let line = context.input_get().unwrap().get_at_index(0);
+        assert_eq!(
+            line.get_obj_prop("id").as_string(),
+            Some("gid://shopify/CartLine/1".to_string())
+        );
+        assert_eq!(line.get_obj_prop("quantity").as_number(), Some(3.0));
+        let attributes = line.get_obj_prop("attributes");
+        assert_eq!(attributes.array_len(), Some(2));
+        assert_eq!(
+            attributes.get_at_index(0).get_at_index(1).as_bool(),
+            Some(true)
+        );
+        assert!(attributes.get_at_index(1).get_at_index(1).is_null());
+
+        let json = serde_json::json!({ "a": [1, 2] });
+        let context = Context::new_with_serializable(&json);
+        assert_eq!(
+            context.input_get().unwrap().get_obj_prop("a").array_len(),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_read_shape() {
+        let context = Context::new_with_input(serde_json::json!([
+            { "id": "1", "quantity": 1, "merchandise": { "id": "a" } },
+            { "merchandise": { "id": "b" }, "id": "2", "quantity": 2 },
+            { "id": "3", "cost": 3 },
+        ]));
+        let shape = Shape::new(&["id", "quantity", "merchandise"]);
+        let lines = context.input_get().unwrap();
+        let lines: Vec<_> = (0..3)
+            .map(|index| {
+                let [id, quantity, merchandise] = lines.get_at_index(index).read_shape(&shape);
+                (
+                    id.as_string(),
+                    quantity.as_number(),
+                    merchandise.get_obj_prop("id").as_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (Some("1".to_string()), Some(1.0), Some("a".to_string())),
+                (Some("2".to_string()), Some(2.0), Some("b".to_string())),
+                (Some("3".to_string()), None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cached_shape() {
+        static SHAPE: CachedShape<2> = CachedShape::new(&["a", "b"]);
+        for input in [
+            serde_json::json!({ "a": 1, "b": 2 }),
+            serde_json::json!({ "b": 2, "a": 1 }),
+        ] {
+            // each context creates the shape again
+            let context = Context::new_with_input(input);
+            let [a, b] = context.input_get().unwrap().read_shape(&SHAPE.load());
+            assert_eq!((a.as_number(), b.as_number()), (Some(1.0), Some(2.0)));
+            assert_eq!(SHAPE.load(), SHAPE.load());
+        }
+    }
+
+    #[test]
+    fn test_read_shape_from_interned() {
+        let context = Context::new_with_input(serde_json::json!({ "a": 1, "b": 2 }));
+        let shape = Shape::from_interned(&[context.intern_utf8_str("b")]);
+        let [b] = context.input_get().unwrap().read_shape(&shape);
+        assert_eq!(b.as_number(), Some(2.0));
+    }
+
+    #[test]
+    fn test_read_shape_non_object() {
+        let context = Context::new_with_input(serde_json::json!([1]));
+        let shape = Shape::new(&["a", "b"]);
+        let values = context.input_get().unwrap().read_shape(&shape);
+        assert!(values
+            .iter()
+            .all(|value| value.as_error() == Some(ErrorCode::NotAnObject)));
+    }
+
+    #[test]
+    fn test_read_shape_from_another_thread() {
+        let shape = std::thread::spawn(|| Shape::new(&["a"])).join().unwrap();
+        let context = Context::new_with_input(serde_json::json!({ "a": 1 }));
+        let [a] = context.input_get().unwrap().read_shape(&shape);
+        assert_eq!(a.as_error(), Some(ErrorCode::UnknownShape));
+    }
+
+    #[test]
+    fn test_long_string() {
+        [10, LONG_STRING_CAPACITY, LONG_STRING_CAPACITY + 1, 100_000]
+            .into_iter()
+            .for_each(|len| {
+                let string = "a".repeat(len);
+                let context = Context::new_with_input(serde_json::json!(string));
+                let Ok(ValueRef::String { ptr, .. }) =
+                    context.input_get().unwrap().nan_box.try_decode()
+                else {
+                    panic!("expected a string");
+                };
+                // Force the length lookup, since native NaN-boxes can hold much longer lengths.
+                let value = Value::from_nan_box(NanBox::string(ptr, NanBox::MAX_VALUE_LENGTH));
+                assert_eq!(value.as_string(), Some(string));
+            });
+    }
+
+    #[test]
+    fn test_read_str_into() {
+        let long = "a".repeat(LONG_STRING_CAPACITY + 1);
+        let context = Context::new_with_input(serde_json::json!(["abc", long, 1, ""]));
+        let input = context.input_get().unwrap();
+        let mut buf = Vec::new();
+        assert_eq!(input.get_at_index(0).read_str_into(&mut buf), Some("abc"));
+        let capacity = buf.capacity();
+        assert_eq!(input.get_at_index(0).read_str_into(&mut buf), Some("abc"));
+        assert_eq!(buf.capacity(), capacity);
+
+        let Ok(ValueRef::String { ptr, .. }) = input.get_at_index(1).nan_box.try_decode() else {
+            panic!("expected a string");
         };
+        // Force the length lookup, since native NaN-boxes can hold much longer lengths.
+        let value = Value::from_nan_box(NanBox::string(ptr, NanBox::MAX_VALUE_LENGTH));
+        assert_eq!(value.read_str_into(&mut buf), Some(long.as_str()));
+
+        assert_eq!(input.get_at_index(2).read_str_into(&mut buf), None);
+        assert!(buf.is_empty());
+        assert_eq!(input.get_at_index(3).read_str_into(&mut buf), Some(""));
+    }
+
+    #[test]
+    fn test_with_str() {
+        let context = Context::new_with_input(serde_json::json!(["abc", "de", 1]));
+        let input = context.input_get().unwrap();
+        let [a, b, c] = [0, 1, 2].map(|i| input.get_at_index(i));
+        assert_eq!(a.with_str(str::len), Some(3));
+        assert_eq!(
+            a.with_str(|a| b.with_str(|b| format!("{a}{b}"))),
+            Some(Some("abcde".to_string()))
+        );
+        assert_eq!(c.with_str(str::len), None);
+        assert_eq!(b.with_str(str::to_string), Some("de".to_string()));
+    }
+
+    #[test]
+    fn test_raw_bits() {
+        let context = Context::new_with_input(serde_json::json!({ "lines": [1, 2], "id": "a" }));
+        let input = context.input_get().unwrap();
+        let bits = [input.get_obj_prop("lines"), input.get_obj_prop("id")].map(|v| v.to_raw_bits());
+        let [lines, id] = bits.map(|bits| unsafe { Value::from_raw_parts(&context, bits) });
+        assert_eq!(lines.kind(), ValueKind::Array);
+        assert_eq!(lines.get_at_index(1).as_number(), Some(2.0));
+        assert_eq!(id.as_string().as_deref(), Some("a"));
+        assert_eq!(id.to_raw_bits(), bits[1]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "invalid NaN-box bits")]
+    fn test_raw_bits_with_unknown_tag() {
+        let context = Context::new_with_input(serde_json::json!(null));
+        // tag 7 is the bytes tag (6) combined with the bool tag (1)
+        let bits = NanBox::bytes(0, 0).to_bits() | NanBox::bool(false).to_bits();
+        let _ = unsafe { Value::from_raw_parts(&context, bits) };
+    }
+
+    #[test]
+    fn test_bytes() {
+        struct Bin(Vec<u8>);
+
+        impl serde::Serialize for Bin {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(&self.0)
+            }
+        }
+
+        [0, 10, LONG_STRING_CAPACITY + 1]
+            .into_iter()
+            .for_each(|len| {
+                let bytes: Vec<u8> = (0..len).map(|i| i as u8).collect();
+                let context = Context::new_with_serializable(&[Bin(bytes.clone())]);
+                let value = context.input_get().unwrap().get_at_index(0);
+                assert_eq!(value.kind(), ValueKind::Bytes);
+                assert_eq!(value.as_bytes(), Some(bytes.clone()));
+                assert_eq!(value.as_string(), None);
+                assert!(
+                    serde_json::Value::deserialize(&value).unwrap() == serde_json::json!(bytes)
+                );
+
+                let Ok(ValueRef::Bytes { ptr, .. }) = value.nan_box.try_decode() else {
+                    panic!("expected bytes");
+                };
+                // Force the length lookup, since native NaN-boxes can hold much longer lengths.
+                let value = Value::from_nan_box(NanBox::bytes(ptr, NanBox::MAX_VALUE_LENGTH));
+                assert_eq!(value.as_bytes(), Some(bytes));
+            });
+        let context = Context::new_with_input(serde_json::json!("abc"));
+        assert_eq!(context.input_get().unwrap().as_bytes(), None);
+    }
+
+    #[test]
+    fn test_slice() {
+        let input = serde_json::json!({
+            "lines": (0..5).map(|i| serde_json::json!({ "quantity": i })).collect::<Vec<_>>(),
+        });
+        let context = Context::new_with_input(input.clone());
+        let lines = context.input_get().unwrap().get_obj_prop("lines");
+
+        let first = lines.slice(..2);
+        assert!(first.is_array());
+        assert_eq!(first.array_len(), Some(2));
+        let mut quantities = Vec::new();
+        let _ = first.for_each_until(|line| {
+            quantities.push(line.get_obj_prop("quantity").as_number().unwrap());
+            ControlFlow::Continue(())
+        });
+        assert_eq!(quantities, [0.0, 1.0]);
+        assert_eq!(
+            lines.slice(3..).to_msgpack_vec(),
+            Some(rmp_serde::to_vec(&input["lines"].as_array().unwrap()[3..]).unwrap())
+        );
+
+        // slices can be sliced again, and ranges past the end are clamped
+        let nested = lines.slice(1..=3).slice(2..10);
+        assert_eq!(nested.array_len(), Some(1));
+        assert_eq!(
+            nested.get_at_index(0).get_obj_prop("quantity").as_number(),
+            Some(3.0)
+        );
+        assert_eq!(
+            nested.get_at_index(1).as_error(),
+            Some(ErrorCode::IndexOutOfBounds)
+        );
+        assert_eq!(lines.slice(7..).array_len(), Some(0));
+        assert_eq!(lines.slice(7..).to_msgpack_vec(), Some(vec![0x90]));
+
+        assert_eq!(
+            lines.get_at_index(0).slice(..).as_error(),
+            Some(ErrorCode::NotAnArray)
+        );
+    }
+
+    #[test]
+    fn test_to_msgpack_vec() {
+        let input = serde_json::json!({
+            "lines": [{"id": "gid://shopify/CartLine/1", "quantity": 2, "cost": 1.5}],
+            "note": "x".repeat(MSGPACK_CAPACITY),
+            "gift": false,
+        });
+        let context = Context::new_with_input(input.clone());
+        let root = context.input_get().unwrap();
+        assert_eq!(
+            root.to_msgpack_vec(),
+            Some(rmp_serde::to_vec(&input).unwrap())
+        );
+        // subtrees are copied without reading them first
+        for key in ["lines", "note", "gift"] {
+            assert_eq!(
+                root.get_obj_prop(key).to_msgpack_vec(),
+                Some(rmp_serde::to_vec(&input[key]).unwrap())
+            );
+        }
+        let line = root.get_obj_prop("lines").get_at_index(0);
+        assert_eq!(
+            line.get_obj_prop("quantity").to_msgpack_vec(),
+            Some(vec![0x02])
+        );
+        assert_eq!(root.get_obj_prop("missing").to_msgpack_vec(), None);
+    }
+
+    #[test]
+    fn test_array_len_around_wasm_max_value_length() {
+        // The NaN-box length limit on 32-bit targets is (2^14) - 1.
+        [16_382, 16_383, 16_384].into_iter().for_each(|len| {
+            let context = Context::new_with_input(serde_json::json!(vec![0; len]));
+            let value = context.input_get().unwrap();
+            assert_eq!(value.array_len(), Some(len));
+        });
+    }
+
+    #[test]
+    fn test_array_len_with_100k_elements() {
+        let len = 100_000;
+        let elements: Vec<usize> = (0..len).collect();
+        let context = Context::new_with_input(serde_json::json!(elements));
+        let value = context.input_get().unwrap();
+        assert_eq!(value.array_len(), Some(len));
+        assert_eq!(value.obj_len(), None);
+        assert_eq!(
+            value.get_at_index(len - 1).as_number(),
+            Some((len - 1) as f64)
+        );
+        assert_eq!(
+            value.get_at_index(len).as_error(),
+            Some(ErrorCode::IndexOutOfBounds)
+        );
+    }
+
+    #[test]
+    fn test_obj_len_with_100k_entries() {
+        let len = 100_000;
+        let entries: serde_json::Map<String, serde_json::Value> = (0..len)
+            .map(|i| (format!("key{i}"), serde_json::json!(i)))
+            .collect();
+        let context = Context::new_with_input(serde_json::Value::Object(entries));
+        let value = context.input_get().unwrap();
+        assert_eq!(value.obj_len(), Some(len));
+        assert_eq!(value.array_len(), None);
+        assert_eq!(
+            value.get_obj_prop(&format!("key{}", len - 1)).as_number(),
+            Some((len - 1) as f64)
+        );
+    }
+
+    #[test]
+    fn test_obj_len_with_non_length_eligible_nan_box() {
+        Context::new_with_input(serde_json::json!({}));
+        let value = Value::from_nan_box(NanBox::null());
         let len = value.obj_len();
         assert_eq!(len, None);
     }
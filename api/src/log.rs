@@ -1,14 +1,443 @@
 //! The log API for the Shopify Function Wasm API.
+//!
+//! Besides [`Context::log`], records can be logged with the [`sf_log!`](crate::sf_log),
+//! [`sf_warn!`](crate::sf_warn) and [`sf_error!`](crate::sf_error) macros, which take a format
+//! string and its arguments, optionally followed by a `;` and key-value pairs. Each record is
+//! written as one line with its level, the message, and the key-value pairs as a JSON object:
+//!
+//! ```rust
+//! use shopify_function_wasm_api::sf_warn;
+//!
+//! let lines = 3;
+//! // logs `warn: discount not applied {"cart":"gid://shopify/Cart/1","lines":3}`
+//! sf_warn!("discount not applied"; cart = "gid://shopify/Cart/1", lines = lines);
+//! ```
+//!
+//! Metrics are logged with [`Context::write_metrics`], in a record of their own that starts with
+//! `metrics: ` rather than a level, followed by the metrics as a JSON object. Using this format,
+//! rather than logging metrics as key-value pairs of other records, lets tooling find the metrics
+//! of any function the same way:
+//!
+//! ```rust
+//! use shopify_function_wasm_api::Context;
+//!
+//! let mut context = Context::new_with_input(serde_json::json!({ "lines": [1, 2, 3] }));
+//! let lines = context.input_get().unwrap().get_obj_prop("lines");
+//! // logs `metrics: {"lines_processed":3,"discount_ratio":0.25}`
+//! context.write_metrics(|m| {
+//!     m.count("lines_processed", lines.array_len().unwrap_or(0));
+//!     m.gauge("discount_ratio", 0.25);
+//! });
+//! ```
+
+use std::fmt::{self, Write};
+
+use shopify_function_wasm_api_core::codec::FloatBuffer;
+pub use shopify_function_wasm_api_core::log::Level;
 
 use crate::Context;
 
-pub(super) fn log_utf8_str(message: &str) {
-    unsafe { crate::shopify_function_log_new_utf8_str(message.as_ptr(), message.len()) };
+pub(super) fn log_utf8_str(message: &str) -> usize {
+    unsafe {
+        crate::shopify_function_log_new_utf8_str_with_dropped_len(message.as_ptr(), message.len())
+    }
 }
 
 impl Context {
-    /// Log `message`.
-    pub fn log(&mut self, message: &str) {
+    /// Log `message`, returning the number of bytes dropped from the logs to make room for it.
+    ///
+    /// The logs are a ring buffer, so once they're full, the oldest bytes are dropped, as is the
+    /// start of a message too long to fit at all. A non-zero result can be used to warn that the logs
+    /// were truncated, rather than debugging output going missing silently.
+    pub fn log(&self, message: &str) -> usize {
         log_utf8_str(message)
     }
+
+    /// Log `message` as a record with `level`, returning the number of records dropped to make
+    /// room for it.
+    ///
+    /// Records are kept apart from the logs written by [`Context::log`] and the logging macros,
+    /// and hosts get them separately, each with its level and a sequence number, so they can be
+    /// filtered by level without parsing lines. Once the messages of the records kept are longer
+    /// than the provider's capacity for them, the oldest records are dropped, but the newest one is
+    /// always kept.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::{log::Level, Context};
+    ///
+    /// let context = Context::new_with_input(serde_json::json!(null));
+    /// assert_eq!(context.log_with_level(Level::Warn, "discount not applied"), 0);
+    /// ```
+    pub fn log_with_level(&self, level: Level, message: &str) -> usize {
+        unsafe {
+            crate::shopify_function_log_new_record(level as u32, message.as_ptr(), message.len())
+        }
+    }
+}
+
+/// A value of a key-value pair in a log record, which is written as JSON.
+pub trait LogValue {
+    /// Write the value as JSON.
+    fn write_json(&self, f: &mut dyn Write) -> fmt::Result;
+}
+
+fn write_json_str(f: &mut dyn Write, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c < ' ' => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+impl LogValue for str {
+    fn write_json(&self, f: &mut dyn Write) -> fmt::Result {
+        write_json_str(f, self)
+    }
+}
+
+impl LogValue for String {
+    fn write_json(&self, f: &mut dyn Write) -> fmt::Result {
+        write_json_str(f, self)
+    }
+}
+
+impl LogValue for char {
+    fn write_json(&self, f: &mut dyn Write) -> fmt::Result {
+        write_json_str(f, self.encode_utf8(&mut [0; 4]))
+    }
+}
+
+impl LogValue for bool {
+    fn write_json(&self, f: &mut dyn Write) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+macro_rules! impl_log_value_for_int {
+    ($($ty:ty),*) => {
+        $(
+            impl LogValue for $ty {
+                fn write_json(&self, f: &mut dyn Write) -> fmt::Result {
+                    write!(f, "{self}")
+                }
+            }
+        )*
+    };
+}
+
+impl_log_value_for_int!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+macro_rules! impl_log_value_for_float {
+    ($($ty:ty),*) => {
+        $(
+            impl LogValue for $ty {
+                /// Non-finite numbers can't be represented in JSON, so they're written as `null`.
+                fn write_json(&self, f: &mut dyn Write) -> fmt::Result {
+                    f.write_str(FloatBuffer::new().format(*self as f64).unwrap_or("null"))
+                }
+            }
+        )*
+    };
+}
+
+impl_log_value_for_float!(f32, f64);
+
+impl<T: LogValue> LogValue for Option<T> {
+    fn write_json(&self, f: &mut dyn Write) -> fmt::Result {
+        match self {
+            Some(value) => value.write_json(f),
+            None => f.write_str("null"),
+        }
+    }
+}
+
+impl<T: LogValue + ?Sized> LogValue for &T {
+    fn write_json(&self, f: &mut dyn Write) -> fmt::Result {
+        (**self).write_json(f)
+    }
+}
+
+/// The number of bytes of a record that are formatted on the stack, before moving to the heap.
+const RECORD_STACK_LEN: usize = 256;
+
+/// A buffer for formatting a log record, which is kept on the stack unless the record is longer
+/// than [`RECORD_STACK_LEN`].
+struct RecordBuffer {
+    stack: [u8; RECORD_STACK_LEN],
+    len: usize,
+    heap: Option<String>,
+}
+
+impl RecordBuffer {
+    fn new() -> Self {
+        Self {
+            stack: [0; RECORD_STACK_LEN],
+            len: 0,
+            heap: None,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match &self.heap {
+            Some(heap) => heap,
+            // SAFETY: only whole strings are copied to the stack
+            None => unsafe { std::str::from_utf8_unchecked(&self.stack[..self.len]) },
+        }
+    }
+}
+
+impl Write for RecordBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if let Some(heap) = &mut self.heap {
+            heap.push_str(s);
+        } else if let Some(dst) = self.stack.get_mut(self.len..self.len + s.len()) {
+            dst.copy_from_slice(s.as_bytes());
+            self.len += s.len();
+        } else {
+            let mut heap = String::with_capacity(self.len + s.len());
+            heap.push_str(self.as_str());
+            heap.push_str(s);
+            self.heap = Some(heap);
+        }
+        Ok(())
+    }
+}
+
+fn format_record(
+    level: Level,
+    message: fmt::Arguments<'_>,
+    fields: &[(&str, &dyn LogValue)],
+) -> Result<RecordBuffer, fmt::Error> {
+    let mut record = RecordBuffer::new();
+    write!(record, "{level}: {message}")?;
+    if let Some(((first_key, first_value), rest)) = fields.split_first() {
+        record.write_str(" {")?;
+        write_json_str(&mut record, first_key)?;
+        record.write_char(':')?;
+        first_value.write_json(&mut record)?;
+        for (key, value) in rest {
+            record.write_char(',')?;
+            write_json_str(&mut record, key)?;
+            record.write_char(':')?;
+            value.write_json(&mut record)?;
+        }
+        record.write_char('}')?;
+    }
+    record.write_char('\n')?;
+    Ok(record)
+}
+
+/// The metrics of a function, which are logged together by [`Context::write_metrics`].
+///
+/// Metrics are written in the order they're recorded. Names should be unique, as tooling may only
+/// read one of the metrics with the same name.
+pub struct Metrics {
+    record: RecordBuffer,
+    len: usize,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let mut record = RecordBuffer::new();
+        // writing to a `RecordBuffer` can't fail
+        let _ = record.write_str("metrics: {");
+        Self { record, len: 0 }
+    }
+
+    fn write(&mut self, name: &str, value: &dyn LogValue) -> fmt::Result {
+        if self.len > 0 {
+            self.record.write_char(',')?;
+        }
+        self.len += 1;
+        write_json_str(&mut self.record, name)?;
+        self.record.write_char(':')?;
+        value.write_json(&mut self.record)
+    }
+
+    /// Record a count of something the function did, such as the number of lines processed.
+    pub fn count(&mut self, name: &str, value: usize) {
+        // writing to a `RecordBuffer` can't fail
+        let _ = self.write(name, &value);
+    }
+
+    /// Record a measurement, such as a ratio. Non-finite values are written as `null`.
+    pub fn gauge(&mut self, name: &str, value: f64) {
+        let _ = self.write(name, &value);
+    }
+
+    fn finish(mut self) -> RecordBuffer {
+        let _ = self.record.write_str("}\n");
+        self.record
+    }
+}
+
+impl Context {
+    /// Log the metrics recorded by `f` as one record, returning the number of bytes dropped from
+    /// the logs to make room for it. See the [`log`](crate::log) module for the format of the
+    /// record.
+    ///
+    /// Nothing is logged if `f` records no metrics.
+    pub fn write_metrics<F: FnOnce(&mut Metrics)>(&mut self, f: F) -> usize {
+        let mut metrics = Metrics::new();
+        f(&mut metrics);
+        if metrics.len == 0 {
+            return 0;
+        }
+        log_utf8_str(metrics.finish().as_str())
+    }
+}
+
+/// Log a record with one call to the provider, returning the number of bytes dropped from the logs
+/// to make room for it. This is called by the logging macros.
+#[doc(hidden)]
+pub fn log_record(
+    level: Level,
+    message: fmt::Arguments<'_>,
+    fields: &[(&str, &dyn LogValue)],
+) -> usize {
+    match format_record(level, message, fields) {
+        Ok(record) => log_utf8_str(record.as_str()),
+        // only a `Display` implementation returning an error fails to format, and there's nowhere
+        // better to report it
+        Err(fmt::Error) => log_utf8_str("error: failed to format log record\n"),
+    }
+}
+
+/// Log a record at the info level, returning the number of bytes dropped from the logs to make room
+/// for it.
+///
+/// Takes a format string and its arguments, as [`format!`] does, optionally followed by a `;` and
+/// `key = value` pairs, whose values implement [`LogValue`](crate::log::LogValue). See the
+/// [`log`](crate::log) module for the format of records.
+#[macro_export]
+macro_rules! sf_log {
+    ($($args:tt)+) => {
+        $crate::__log_record!($crate::log::Level::Info, $($args)+)
+    };
+}
+
+/// Log a record at the warn level, like [`sf_log!`].
+#[macro_export]
+macro_rules! sf_warn {
+    ($($args:tt)+) => {
+        $crate::__log_record!($crate::log::Level::Warn, $($args)+)
+    };
+}
+
+/// Log a record at the error level, like [`sf_log!`].
+#[macro_export]
+macro_rules! sf_error {
+    ($($args:tt)+) => {
+        $crate::__log_record!($crate::log::Level::Error, $($args)+)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_record {
+    ($level:expr, $fmt:literal $(, $arg:expr)* $(,)? $(; $($key:ident = $value:expr),+ $(,)?)?) => {
+        $crate::log::log_record(
+            $level,
+            ::std::format_args!($fmt $(, $arg)*),
+            &[$($((::std::stringify!($key), &$value as &dyn $crate::log::LogValue)),+)?],
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(
+        level: Level,
+        message: fmt::Arguments<'_>,
+        fields: &[(&str, &dyn LogValue)],
+    ) -> String {
+        format_record(level, message, fields)
+            .unwrap()
+            .as_str()
+            .to_string()
+    }
+
+    #[test]
+    fn test_format_record() {
+        assert_eq!(
+            record(Level::Info, format_args!("checked {} lines", 2), &[]),
+            "info: checked 2 lines\n"
+        );
+        assert_eq!(
+            record(
+                Level::Error,
+                format_args!("invalid\nline"),
+                &[
+                    ("id", &"a\"b\\\u{1}"),
+                    ("count", &3),
+                    ("ratio", &f64::NAN),
+                    ("max", &1e300),
+                    ("missing", &None::<bool>),
+                ]
+            ),
+            "error: invalid\nline {\"id\":\"a\\\"b\\\\\\u0001\",\"count\":3,\"ratio\":null,\"max\":1e300,\"missing\":null}\n"
+        );
+
+        // longer records are moved to the heap
+        let long = "a".repeat(RECORD_STACK_LEN);
+        assert_eq!(
+            record(Level::Warn, format_args!("{long}"), &[("ok", &true)]),
+            format!("warn: {long} {{\"ok\":true}}\n")
+        );
+    }
+
+    #[test]
+    fn test_metrics() {
+        let mut metrics = Metrics::new();
+        metrics.count("lines_processed", 3);
+        metrics.gauge("discount_ratio", 0.25);
+        metrics.gauge("\"ratio\"", f64::INFINITY);
+        assert_eq!(
+            metrics.finish().as_str(),
+            "metrics: {\"lines_processed\":3,\"discount_ratio\":0.25,\"\\\"ratio\\\"\":null}\n"
+        );
+
+        let mut context = Context::new_with_input(serde_json::json!(null));
+        assert_eq!(context.write_metrics(|m| m.count("lines_processed", 0)), 0);
+        assert_eq!(context.write_metrics(|_| {}), 0);
+    }
+
+    #[test]
+    fn test_log_with_level() {
+        let context = Context::new_with_input(serde_json::json!(null));
+        assert_eq!(context.log_with_level(Level::Info, "checked 2 lines"), 0);
+        assert_eq!(context.log_with_level(Level::Error, "invalid cart"), 0);
+        let records: serde_json::Value =
+            rmp_serde::from_slice(&shopify_function_provider::log::log_records()).unwrap();
+        assert_eq!(
+            records,
+            serde_json::json!([
+                { "level": 0, "sequence": 0, "message": "checked 2 lines" },
+                { "level": 2, "sequence": 1, "message": "invalid cart" },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_log_macros() {
+        let _context = Context::new_with_input(serde_json::json!(null));
+        let lines = 3;
+        assert_eq!(crate::sf_log!("checked {lines} lines"), 0);
+        assert_eq!(crate::sf_warn!("discount not applied"; lines = lines,), 0);
+        assert_eq!(
+            crate::sf_error!("{} failed", "validation"; code = "INVALID", retry = false),
+            0
+        );
+    }
 }
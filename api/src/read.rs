@@ -1,9 +1,22 @@
 //! The read API for the Shopify Function Wasm API.
 //!
 //! This consists primarily of the `Deserialize` trait for converting [`Value`] into other types.
+//!
+//! Object properties that are missing are read as null, but can be told apart from properties that
+//! are explicitly `null` with [`Value::is_missing`]. When deserializing:
+//! - `Option<T>` is `None` for both `null` and a missing property, including when `T` is a
+//!   collection, so `Option<Vec<T>>` is `None` rather than an empty `Vec`.
+//! - Collections, such as `Vec<T>` and `HashMap<String, T>`, fail with [`Error::Missing`] for a
+//!   missing property, and with [`Error::InvalidType`] for `null`, as neither is an empty
+//!   collection.
+//!
+//! Values that are errors, such as a property looked up on a value that is not an object, fail with
+//! [`Error::Read`] and the provider's error code, rather than [`Error::InvalidType`].
 
-use crate::Value;
+use crate::{write::InternedKeyMap, Value};
+use shopify_function_wasm_api_core::read::ErrorCode;
 use std::collections::{BTreeMap, HashMap};
+use std::hash::BuildHasher;
 
 /// An error that can occur when deserializing a value.
 #[derive(Debug, thiserror::Error)]
@@ -12,6 +25,26 @@ pub enum Error {
     /// The value is not of the expected type.
     #[error("Invalid type")]
     InvalidType,
+    /// The value is a missing object property, but a value was expected. Properties that may be
+    /// missing can be deserialized as an `Option`.
+    #[error("Missing value")]
+    Missing,
+    /// The value could not be read, with the provider's error code, e.g.
+    /// [`ErrorCode::NotAnObject`] for a property looked up on a value that is not an object.
+    #[error("Read error: {0:?}")]
+    Read(ErrorCode),
+}
+
+/// The error for a value that is not of the expected type, which is [`Error::Read`] if it is an
+/// error and [`Error::Missing`] if it is a missing object property.
+pub(crate) fn invalid_type(value: &Value) -> Error {
+    if let Some(code) = value.as_error() {
+        Error::Read(code)
+    } else if value.is_missing() {
+        Error::Missing
+    } else {
+        Error::InvalidType
+    }
 }
 
 /// A trait for types that can be deserialized from a [`Value`].
@@ -43,6 +76,18 @@ pub enum Error {
 pub trait Deserialize: Sized {
     /// Deserialize a value from a [`Value`].
     fn deserialize(value: &Value) -> Result<Self, Error>;
+
+    /// Deserialize a value that may be null, which is how `Option<Self>` is deserialized.
+    ///
+    /// Collections override this to check whether the value is null and read its length together,
+    /// rather than checking the value again once it's known not to be null.
+    fn deserialize_option(value: &Value) -> Result<Option<Self>, Error> {
+        if value.is_null() {
+            Ok(None)
+        } else {
+            Ok(Some(Self::deserialize(value)?))
+        }
+    }
 }
 
 impl Deserialize for Value {
@@ -67,6 +112,10 @@ impl Deserialize for bool {
     }
 }
 
+// Numbers are stored in the NaN-box itself, so they're read without calling the provider. The cast
+// saturates and truncates, so converting back gives the same number if it's an integer in range,
+// and also if it's just past `MAX` for 64-bit types, whose `MAX` rounds up to that number as an
+// `f64`. The upper bound excludes it, and `MAX as f64 + 1.0` is exactly `2^BITS` for every type.
 macro_rules! impl_deserialize_for_int {
     ($ty:ty) => {
         impl Deserialize for $ty {
@@ -74,11 +123,8 @@ macro_rules! impl_deserialize_for_int {
                 value
                     .as_number()
                     .and_then(|n| {
-                        if n.trunc() == n && n >= <$ty>::MIN as f64 && n <= <$ty>::MAX as f64 {
-                            Some(n as $ty)
-                        } else {
-                            None
-                        }
+                        let int = n as $ty;
+                        (int as f64 == n && n < <$ty>::MAX as f64 + 1.0).then_some(int)
                     })
                     .ok_or(Error::InvalidType)
             }
@@ -121,62 +167,87 @@ impl Deserialize for char {
 }
 
 impl<T: Deserialize> Deserialize for Option<T> {
+    /// Both `null` and a missing object property are `None`.
     fn deserialize(value: &Value) -> Result<Self, Error> {
-        if value.is_null() {
-            Ok(None)
-        } else {
-            Ok(Some(T::deserialize(value)?))
-        }
+        T::deserialize_option(value)
     }
 }
 
 impl<T: Deserialize> Deserialize for Vec<T> {
     fn deserialize(value: &Value) -> Result<Self, Error> {
-        if let Some(len) = value.array_len() {
-            let mut vec = Vec::with_capacity(len);
-            for i in 0..len {
-                vec.push(T::deserialize(&value.get_at_index(i))?);
-            }
-            Ok(vec)
-        } else {
-            Err(Error::InvalidType)
-        }
+        Self::deserialize_option(value)?.ok_or_else(|| invalid_type(value))
     }
-}
 
-impl<T: Deserialize> Deserialize for HashMap<String, T> {
-    fn deserialize(value: &Value) -> Result<Self, Error> {
-        let Some(obj_len) = value.obj_len() else {
-            return Err(Error::InvalidType);
+    /// The elements are read with a single call to the provider.
+    fn deserialize_option(value: &Value) -> Result<Option<Self>, Error> {
+        let Some(len) = value.try_array_len()? else {
+            return Ok(None);
         };
+        value
+            .array_elements(len)
+            .ok_or(Error::InvalidType)?
+            .map(|element| T::deserialize(&element))
+            .collect::<Result<_, _>>()
+            .map(Some)
+    }
+}
 
-        let mut map = HashMap::new();
+/// Deserialize the entries of an object, or `None` if it's null.
+fn deserialize_obj_entries<T: Deserialize, C: FromIterator<(String, T)>>(
+    value: &Value,
+) -> Result<Option<C>, Error> {
+    let Some(len) = value.try_obj_len()? else {
+        return Ok(None);
+    };
+    value
+        .obj_entries_with_len(len)
+        .map(|(key, value)| {
+            let key = key.as_string().ok_or(Error::InvalidType)?;
+            Ok((key, T::deserialize(&value)?))
+        })
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
 
-        for i in 0..obj_len {
-            let key = value.get_obj_key_at_index(i).ok_or(Error::InvalidType)?;
-            let value = value.get_at_index(i);
-            map.insert(key, T::deserialize(&value)?);
-        }
+impl<T: Deserialize, S: BuildHasher + Default> Deserialize for HashMap<String, T, S> {
+    fn deserialize(value: &Value) -> Result<Self, Error> {
+        Self::deserialize_option(value)?.ok_or_else(|| invalid_type(value))
+    }
 
-        Ok(map)
+    fn deserialize_option(value: &Value) -> Result<Option<Self>, Error> {
+        deserialize_obj_entries(value)
     }
 }
 
 impl<T: Deserialize> Deserialize for BTreeMap<String, T> {
     fn deserialize(value: &Value) -> Result<Self, Error> {
-        let Some(obj_len) = value.obj_len() else {
-            return Err(Error::InvalidType);
-        };
+        Self::deserialize_option(value)?.ok_or_else(|| invalid_type(value))
+    }
 
-        let mut map = BTreeMap::new();
+    fn deserialize_option(value: &Value) -> Result<Option<Self>, Error> {
+        deserialize_obj_entries(value)
+    }
+}
 
-        for i in 0..obj_len {
-            let key = value.get_obj_key_at_index(i).ok_or(Error::InvalidType)?;
-            let value = value.get_at_index(i);
-            map.insert(key, T::deserialize(&value)?);
-        }
+/// Keys are interned by the provider rather than copied, see
+/// [`Value::get_obj_key_interned_at_index`].
+impl<V: Deserialize> Deserialize for InternedKeyMap<V> {
+    fn deserialize(value: &Value) -> Result<Self, Error> {
+        Self::deserialize_option(value)?.ok_or_else(|| invalid_type(value))
+    }
 
-        Ok(map)
+    fn deserialize_option(value: &Value) -> Result<Option<Self>, Error> {
+        let Some(len) = value.try_obj_len()? else {
+            return Ok(None);
+        };
+        let mut map = Self::with_capacity(len);
+        for index in 0..len {
+            let key = value
+                .get_obj_key_interned_at_index(index)
+                .ok_or(Error::InvalidType)?;
+            map.insert(key, V::deserialize(&value.get_at_index(index))?);
+        }
+        Ok(Some(map))
     }
 }
 
@@ -186,7 +257,7 @@ macro_rules! impl_deserialize_tuple {
             impl<#(T~N: Deserialize,)*> Deserialize for (#(T~N,)*) {
                 fn deserialize(value: &Value) -> Result<Self, Error> {
                     let Some(len) = value.array_len() else {
-                        return Err(Error::InvalidType);
+                        return Err(invalid_type(value));
                     };
 
                     if len != $n {
@@ -209,7 +280,7 @@ macro_rules! impl_deserialize_array {
         impl<T: Deserialize> Deserialize for [T; $n] {
             fn deserialize(value: &Value) -> Result<Self, Error> {
                 let Some(len) = value.array_len() else {
-                    return Err(Error::InvalidType);
+                    return Err(invalid_type(value));
                 };
 
                 if len != $n {
@@ -253,7 +324,10 @@ mod tests {
             paste::paste! {
                 #[test]
                 fn [<test_deserialize_ $ty>]() {
-                    [$ty::MIN, 0 as $ty, $ty::MAX].iter().for_each(|&n| {
+                    // numbers are read as `f64`, so this is `MAX` for types that fit in one, and the
+                    // largest value below `MAX` an `f64` holds exactly for 64-bit types
+                    let max = $ty::MAX - $ty::MAX.checked_shr(f64::MANTISSA_DIGITS).unwrap_or(0);
+                    [$ty::MIN, 0 as $ty, max].iter().for_each(|&n| {
                         let value = serde_json::json!(n);
                         let result: $ty = deserialize_json_value(value).unwrap();
                         assert_eq!(result, n);
@@ -274,6 +348,63 @@ mod tests {
     test_deserialize_int!(usize);
     test_deserialize_int!(isize);
 
+    #[test]
+    fn test_deserialize_int_out_of_range() {
+        for n in [1.5, -0.5, 128.0, -129.0, 1e20] {
+            assert!(matches!(
+                deserialize_json_value::<i8>(serde_json::json!(n)),
+                Err(Error::InvalidType)
+            ));
+        }
+        assert!(matches!(
+            deserialize_json_value::<u32>(serde_json::json!(-1)),
+            Err(Error::InvalidType)
+        ));
+        assert_eq!(
+            deserialize_json_value::<i32>(serde_json::json!(-0.0)).unwrap(),
+            0
+        );
+        assert_eq!(
+            deserialize_json_value::<i64>(serde_json::json!(-9_007_199_254_740_993.0)).unwrap(),
+            -9_007_199_254_740_992
+        );
+    }
+
+    #[test]
+    fn test_deserialize_64_bit_int_out_of_range() {
+        let two_pow_63 = 2f64.powi(63);
+        let two_pow_64 = 2f64.powi(64);
+        // `i64::MAX` and `u64::MAX` are read as these, as they're rounded to the nearest `f64`
+        for value in [
+            serde_json::json!(two_pow_63),
+            serde_json::json!(i64::MAX as u64 + 1),
+        ] {
+            assert!(matches!(
+                deserialize_json_value::<i64>(value.clone()),
+                Err(Error::InvalidType)
+            ));
+            assert!(matches!(
+                deserialize_json_value::<isize>(value.clone()),
+                Err(Error::InvalidType)
+            ));
+            assert_eq!(deserialize_json_value::<u64>(value).unwrap(), 1 << 63);
+        }
+        for value in [serde_json::json!(two_pow_64), serde_json::json!(u64::MAX)] {
+            assert!(matches!(
+                deserialize_json_value::<u64>(value.clone()),
+                Err(Error::InvalidType)
+            ));
+            assert!(matches!(
+                deserialize_json_value::<usize>(value),
+                Err(Error::InvalidType)
+            ));
+        }
+        assert_eq!(
+            deserialize_json_value::<i64>(serde_json::json!(-two_pow_63)).unwrap(),
+            i64::MIN
+        );
+    }
+
     #[test]
     fn test_deserialize_f64() {
         let value = serde_json::json!(1.0);
@@ -346,6 +477,119 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_deserialize_interned_key_map() {
+        let context = Context::new_with_input(serde_json::json!([
+            { "size": "M", "color": "red" },
+            { "color": "blue" },
+        ]));
+        let [size, color] = ["size", "color"].map(|key| context.intern_utf8_str(key));
+        let maps: Vec<InternedKeyMap<String>> =
+            Deserialize::deserialize(&context.input_get().unwrap()).unwrap();
+        assert_eq!(
+            maps[0].iter().collect::<Vec<_>>(),
+            // `json!` sorts the keys of objects
+            [(color, &"red".to_string()), (size, &"M".to_string())]
+        );
+        assert_eq!(maps[1].get(color), Some(&"blue".to_string()));
+        assert_eq!(maps[1].get(size), None);
+
+        let context = Context::new_with_input(serde_json::json!({ "null": null }));
+        let input = context.input_get().unwrap();
+        type Map = InternedKeyMap<i32>;
+        assert!(Option::<Map>::deserialize(&input.get_obj_prop("null"))
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            Map::deserialize(&input.get_obj_prop("missing")),
+            Err(Error::Missing)
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_null_missing_and_empty() {
+        let context = Context::new_with_input(serde_json::json!({
+            "null": null,
+            "emptyArray": [],
+            "emptyObject": {},
+            "optionalElements": [null, 1],
+        }));
+        let input = context.input_get().unwrap();
+        let [null, missing, empty_array, empty_object] =
+            ["null", "missing", "emptyArray", "emptyObject"].map(|key| input.get_obj_prop(key));
+        assert!(null.is_null() && !null.is_missing());
+        assert!(missing.is_null() && missing.is_missing());
+        assert!(!empty_array.is_missing() && !empty_object.is_missing());
+
+        assert_eq!(Option::<Vec<i32>>::deserialize(&null).unwrap(), None);
+        assert_eq!(Option::<Vec<i32>>::deserialize(&missing).unwrap(), None);
+        assert_eq!(
+            Option::<Vec<i32>>::deserialize(&empty_array).unwrap(),
+            Some(vec![])
+        );
+        assert!(matches!(
+            Vec::<i32>::deserialize(&null),
+            Err(Error::InvalidType)
+        ));
+        assert!(matches!(
+            Vec::<i32>::deserialize(&missing),
+            Err(Error::Missing)
+        ));
+        assert!(Vec::<i32>::deserialize(&empty_array).unwrap().is_empty());
+        assert!(matches!(
+            <[i32; 0]>::deserialize(&missing),
+            Err(Error::Missing)
+        ));
+        assert!(matches!(
+            <(i32, i32)>::deserialize(&missing),
+            Err(Error::Missing)
+        ));
+
+        type Map = HashMap<String, i32>;
+        assert_eq!(Option::<Map>::deserialize(&null).unwrap(), None);
+        assert_eq!(Option::<Map>::deserialize(&missing).unwrap(), None);
+        assert_eq!(
+            Option::<Map>::deserialize(&empty_object).unwrap(),
+            Some(Map::new())
+        );
+        assert!(matches!(Map::deserialize(&null), Err(Error::InvalidType)));
+        assert!(matches!(Map::deserialize(&missing), Err(Error::Missing)));
+        assert!(matches!(
+            BTreeMap::<String, i32>::deserialize(&missing),
+            Err(Error::Missing)
+        ));
+        assert_eq!(Map::deserialize(&empty_object).unwrap(), Map::new());
+
+        assert_eq!(
+            Option::<Vec<Option<i32>>>::deserialize(&input.get_obj_prop("optionalElements"))
+                .unwrap(),
+            Some(vec![None, Some(1)])
+        );
+    }
+
+    #[test]
+    fn test_deserialize_optional_collections() {
+        let elements: Vec<i32> = (0..20_000).collect();
+        let context = Context::new_with_input(serde_json::json!({
+            "long": elements,
+            "number": 1,
+        }));
+        let input = context.input_get().unwrap();
+        assert_eq!(
+            Option::<Vec<i32>>::deserialize(&input.get_obj_prop("long")).unwrap(),
+            Some(elements)
+        );
+        let number = input.get_obj_prop("number");
+        assert!(matches!(
+            Option::<Vec<i32>>::deserialize(&number),
+            Err(Error::InvalidType)
+        ));
+        assert!(matches!(
+            Option::<BTreeMap<String, i32>>::deserialize(&number),
+            Err(Error::InvalidType)
+        ));
+    }
+
     #[test]
     fn test_deserialize_unit() {
         let value = serde_json::json!(null);
@@ -0,0 +1,835 @@
+//! Adapters between [`serde`] and the Wasm API, enabled with the `serde` feature.
+//!
+//! [`ValueDeserializer`] implements [`serde::Deserializer`] over a [`Value`], and
+//! [`ContextSerializer`] implements [`serde::Serializer`] over a [`Context`], so types that
+//! already derive `serde::Deserialize` and `serde::Serialize` can be read from the input and written
+//! to the output without implementing [`crate::Deserialize`] and [`crate::Serialize`].
+//!
+//! Values are read lazily, as they are by [`crate::Deserialize`] implementations: struct fields are
+//! looked up by their names, so properties of the input without a field aren't read, and ignored
+//! values aren't read either. Like struct fields in the derived implementations, missing properties
+//! are skipped, so serde's defaults and `Option` fields apply to them.
+//!
+//! # Example
+//! ```rust
+//! use shopify_function_wasm_api::{serde_compat, Context};
+//!
+//! #[derive(serde::Deserialize)]
+//! #[serde(rename_all = "camelCase")]
+//! struct Line {
+//!     quantity: i32,
+//!     merchandise_id: String,
+//! }
+//!
+//! #[derive(serde::Serialize)]
+//! struct Output {
+//!     total: i32,
+//! }
+//!
+//! let mut context = Context::new_with_input(serde_json::json!([
+//!     { "quantity": 2, "merchandiseId": "gid://shopify/ProductVariant/1" },
+//!     { "quantity": 3, "merchandiseId": "gid://shopify/ProductVariant/2" },
+//! ]));
+//! let input = context.input_get().unwrap();
+//! let lines: Vec<Line> = serde_compat::from_value(&input).unwrap();
+//! let total = lines.iter().map(|line| line.quantity).sum();
+//! serde_compat::to_context(&mut context, &Output { total }).unwrap();
+//! let output = context.finalize_output_and_return().unwrap();
+//! assert_eq!(output, serde_json::json!({ "total": 5 }));
+//! ```
+
+use crate::{read, write, ArrayIter, Context, ObjectEntries, Value};
+use serde::{
+    de::{self, value::BorrowedStrDeserializer, IntoDeserializer},
+    ser,
+};
+use shopify_function_wasm_api_core::read::ValueKind;
+use std::fmt::Display;
+
+/// An error from reading or writing a value with serde.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// The value could not be read.
+    #[error(transparent)]
+    Read(#[from] read::Error),
+    /// The value could not be written.
+    #[error(transparent)]
+    Write(#[from] write::Error),
+    /// An object was serialized without its length, which the output needs up front, such as a
+    /// struct with a `#[serde(flatten)]` field.
+    #[error("Object length unknown")]
+    ObjectLengthUnknown,
+    /// An error from a `serde::Deserialize` or `serde::Serialize` implementation.
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Custom(msg.to_string())
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Custom(msg.to_string())
+    }
+}
+
+/// Deserialize a `T` from `value` with its `serde::Deserialize` implementation.
+pub fn from_value<T: de::DeserializeOwned>(value: &Value) -> Result<T, Error> {
+    T::deserialize(ValueDeserializer::new(*value))
+}
+
+/// Serialize `value` to the output of `context` with its `serde::Serialize` implementation.
+pub fn to_context<T: ser::Serialize + ?Sized>(
+    context: &mut Context,
+    value: &T,
+) -> Result<(), Error> {
+    value.serialize(ContextSerializer::new(context))
+}
+
+/// A [`serde::Deserializer`] over a [`Value`].
+pub struct ValueDeserializer {
+    value: Value,
+}
+
+impl ValueDeserializer {
+    /// Create a deserializer over `value`.
+    pub fn new(value: Value) -> Self {
+        Self { value }
+    }
+
+    fn invalid_type(&self) -> Error {
+        Error::Read(read::invalid_type(&self.value))
+    }
+}
+
+// numbers in this range without a fractional part are read as i64 or u64
+const I64_MIN: f64 = i64::MIN as f64;
+const U64_END: f64 = 18_446_744_073_709_551_616.0;
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value.kind() {
+            ValueKind::Null => visitor.visit_unit(),
+            ValueKind::Bool => visitor.visit_bool(self.value.as_bool().unwrap_or_default()),
+            ValueKind::Number => {
+                let n = self.value.as_number().unwrap_or_default();
+                // numbers are f64s, so integers are visited as integers for integer fields
+                if n.fract() != 0.0 || !(I64_MIN..U64_END).contains(&n) {
+                    visitor.visit_f64(n)
+                } else if n < 0.0 {
+                    visitor.visit_i64(n as i64)
+                } else {
+                    visitor.visit_u64(n as u64)
+                }
+            }
+            ValueKind::String => match self.value.as_string() {
+                Some(s) => visitor.visit_string(s),
+                None => Err(self.invalid_type()),
+            },
+            ValueKind::Bytes => match self.value.as_bytes() {
+                Some(bytes) => visitor.visit_byte_buf(bytes),
+                None => Err(self.invalid_type()),
+            },
+            ValueKind::Array => visitor.visit_seq(SeqAccess {
+                elements: self.value.array_iter(),
+            }),
+            ValueKind::Object => visitor.visit_map(MapAccess {
+                entries: self.value.object_entries(),
+                value: None,
+            }),
+            _ => Err(self.invalid_type()),
+        }
+    }
+
+    fn deserialize_f32<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value.as_number() {
+            Some(n) => visitor.visit_f64(n),
+            None => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.value.is_null() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if !self.value.is_obj() {
+            return self.deserialize_any(visitor);
+        }
+        visitor.visit_map(StructAccess {
+            object: self.value,
+            fields: fields.iter(),
+            value: None,
+        })
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.value.kind() {
+            // unit variants are strings, and other variants are objects with the variant as the key
+            ValueKind::String => match self.value.as_string() {
+                Some(variant) => visitor.visit_enum(variant.into_deserializer()),
+                None => Err(self.invalid_type()),
+            },
+            ValueKind::Object if self.value.obj_len() == Some(1) => {
+                visitor.visit_enum(de::value::MapAccessDeserializer::new(MapAccess {
+                    entries: self.value.object_entries(),
+                    value: None,
+                }))
+            }
+            _ => Err(self.invalid_type()),
+        }
+    }
+
+    fn deserialize_ignored_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 char str string bytes byte_buf unit
+        unit_struct seq tuple tuple_struct map identifier
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for ValueDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+struct SeqAccess {
+    elements: ArrayIter,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        self.elements
+            .next()
+            .map(|element| seed.deserialize(ValueDeserializer::new(element)))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.elements.len())
+    }
+}
+
+struct MapAccess {
+    entries: ObjectEntries,
+    /// The value of the entry whose key was last read.
+    value: Option<Value>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.value = Some(value);
+        seed.deserialize(ValueDeserializer::new(key)).map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| Error::Custom("value read before its key".to_string()))?;
+        seed.deserialize(ValueDeserializer::new(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+/// Reads the properties of an object for the fields of a struct, skipping missing properties.
+struct StructAccess {
+    object: Value,
+    fields: std::slice::Iter<'static, &'static str>,
+    /// The value of the field whose key was last read.
+    value: Option<Value>,
+}
+
+impl<'de> de::MapAccess<'de> for StructAccess {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        for field in self.fields.by_ref() {
+            let value = self.object.get_obj_prop(field);
+            if value.is_missing() {
+                continue;
+            }
+            self.value = Some(value);
+            return seed
+                .deserialize(BorrowedStrDeserializer::<Error>::new(field))
+                .map(Some);
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| Error::Custom("value read before its key".to_string()))?;
+        seed.deserialize(ValueDeserializer::new(value))
+    }
+}
+
+/// A [`serde::Serializer`] that writes to the output of a [`Context`].
+///
+/// Integers are written exactly, and byte arrays are written as arrays of numbers. Unit variants
+/// of enums are written as strings, and other variants as objects with the variant as the only key,
+/// like `serde_json` writes them. Object keys must be strings, and maps must have a known length,
+/// as the output needs the number of properties of each object up front.
+pub struct ContextSerializer<'a> {
+    context: &'a mut Context,
+}
+
+impl<'a> ContextSerializer<'a> {
+    /// Create a serializer that writes to the output of `context`.
+    pub fn new(context: &'a mut Context) -> Self {
+        Self { context }
+    }
+
+    /// Start an object for a variant of an enum, with the variant as its only key.
+    fn start_variant(self, variant: &'static str) -> Result<&'a mut Context, Error> {
+        write::map_result(unsafe { crate::shopify_function_output_new_object(1) })?;
+        self.context.write_utf8_str(variant)?;
+        Ok(self.context)
+    }
+}
+
+/// Writes the elements of arrays and the properties of objects, and finishes them.
+pub struct Compound<'a> {
+    context: &'a mut Context,
+    /// The number of objects to finish after the array or object, for variants of enums.
+    variant_objects: usize,
+}
+
+impl<'a> Compound<'a> {
+    fn new_array(
+        context: &'a mut Context,
+        len: Option<usize>,
+        variant_objects: usize,
+    ) -> Result<Self, Error> {
+        write::map_result(unsafe {
+            match len {
+                Some(len) => crate::shopify_function_output_new_array(len),
+                None => crate::shopify_function_output_new_dynamic_array(),
+            }
+        })?;
+        Ok(Self {
+            context,
+            variant_objects,
+        })
+    }
+
+    fn new_object(
+        context: &'a mut Context,
+        len: Option<usize>,
+        variant_objects: usize,
+    ) -> Result<Self, Error> {
+        let len = len.ok_or(Error::ObjectLengthUnknown)?;
+        write::map_result(unsafe { crate::shopify_function_output_new_object(len) })?;
+        Ok(Self {
+            context,
+            variant_objects,
+        })
+    }
+
+    fn write<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(ContextSerializer::new(self.context))
+    }
+
+    fn write_field<T: ser::Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.context.write_utf8_str(key)?;
+        self.write(value)
+    }
+
+    fn finish_array(self) -> Result<(), Error> {
+        write::map_result(unsafe { crate::shopify_function_output_finish_array() })?;
+        self.finish_variant_objects()
+    }
+
+    fn finish_object(self) -> Result<(), Error> {
+        write::map_result(unsafe { crate::shopify_function_output_finish_object() })?;
+        self.finish_variant_objects()
+    }
+
+    fn finish_variant_objects(self) -> Result<(), Error> {
+        for _ in 0..self.variant_objects {
+            write::map_result(unsafe { crate::shopify_function_output_finish_object() })?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> ser::Serializer for ContextSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        Ok(self.context.write_bool(v)?)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.serialize_i32(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.serialize_i32(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        Ok(self.context.write_i32(v)?)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        Ok(self.context.write_i64(v)?)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.serialize_i32(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.serialize_i32(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        Ok(self.context.write_u64(v)?)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        Ok(self.context.write_f64(v)?)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        Ok(self.context.write_utf8_str(v)?)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        Ok(self
+            .context
+            .write_array_from_iter(v.iter().map(|&byte| i32::from(byte)))?)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(self.context.write_null()?)
+    }
+
+    fn serialize_some<T: ser::Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(self.context.write_null()?)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ser::Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ser::Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let context = self.start_variant(variant)?;
+        value.serialize(ContextSerializer::new(context))?;
+        write::map_result(unsafe { crate::shopify_function_output_finish_object() })?;
+        Ok(())
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Compound<'a>, Error> {
+        Compound::new_array(self.context, len, 0)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'a>, Error> {
+        Compound::new_array(self.context, Some(len), 0)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        Compound::new_array(self.context, Some(len), 0)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        let context = self.start_variant(variant)?;
+        Compound::new_array(context, Some(len), 1)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Compound<'a>, Error> {
+        Compound::new_object(self.context, len, 0)
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Compound<'a>, Error> {
+        Compound::new_object(self.context, Some(len), 0)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        let context = self.start_variant(variant)?;
+        Compound::new_object(context, Some(len), 1)
+    }
+}
+
+impl ser::SerializeSeq for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.write(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish_array()
+    }
+}
+
+impl ser::SerializeTuple for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.write(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish_array()
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.write(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish_array()
+    }
+}
+
+impl ser::SerializeTupleVariant for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.write(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish_array()
+    }
+}
+
+impl ser::SerializeMap for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: ser::Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.write(key)
+    }
+
+    fn serialize_value<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.write(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish_object()
+    }
+}
+
+impl ser::SerializeStruct for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.write_field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish_object()
+    }
+}
+
+impl ser::SerializeStructVariant for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.write_field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish_object()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    fn deserialize<T: de::DeserializeOwned>(input: serde_json::Value) -> Result<T, Error> {
+        let context = Context::new_with_input(input);
+        from_value(&context.input_get().unwrap())
+    }
+
+    fn serialize<T: ser::Serialize + ?Sized>(value: &T) -> serde_json::Value {
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        to_context(&mut context, value).unwrap();
+        context.finalize_output_and_return().unwrap()
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Line {
+        quantity: i32,
+        merchandise_id: String,
+        #[serde(default)]
+        attribute: Option<String>,
+        cost: f64,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    enum Operation {
+        Noop,
+        Percentage(f64),
+        Range(i32, i32),
+        Fixed { amount: u64 },
+    }
+
+    #[test]
+    fn test_deserialize_struct() {
+        let line: Line = deserialize(serde_json::json!({
+            "quantity": 2,
+            "merchandiseId": "gid://shopify/ProductVariant/1",
+            "cost": 1.5,
+            "unused": [1, 2, 3],
+        }))
+        .unwrap();
+        assert_eq!(
+            line,
+            Line {
+                quantity: 2,
+                merchandise_id: "gid://shopify/ProductVariant/1".to_string(),
+                attribute: None,
+                cost: 1.5,
+            }
+        );
+    }
+
+    #[test]
+    fn test_deserialize_collections() {
+        let lines: Vec<Option<u8>> = deserialize(serde_json::json!([1, null, 3])).unwrap();
+        assert_eq!(lines, vec![Some(1), None, Some(3)]);
+
+        let map: BTreeMap<String, (bool, i64)> =
+            deserialize(serde_json::json!({ "a": [true, -5], "b": [false, 7] })).unwrap();
+        assert_eq!(
+            map,
+            BTreeMap::from([("a".to_string(), (true, -5)), ("b".to_string(), (false, 7))])
+        );
+    }
+
+    #[test]
+    fn test_deserialize_enum() {
+        let operations: Vec<Operation> = deserialize(serde_json::json!([
+            "Noop",
+            { "Percentage": 10 },
+            { "Range": [1, 5] },
+            { "Fixed": { "amount": 3 } },
+        ]))
+        .unwrap();
+        assert_eq!(
+            operations,
+            vec![
+                Operation::Noop,
+                Operation::Percentage(10.0),
+                Operation::Range(1, 5),
+                Operation::Fixed { amount: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deserialize_errors() {
+        assert!(matches!(
+            deserialize::<Line>(serde_json::json!({ "quantity": 2 })),
+            Err(Error::Custom(_))
+        ));
+        assert!(matches!(
+            deserialize::<u8>(serde_json::json!(1.5)),
+            Err(Error::Custom(_))
+        ));
+        assert!(matches!(
+            deserialize::<Operation>(serde_json::json!(1)),
+            Err(Error::Read(read::Error::InvalidType))
+        ));
+    }
+
+    #[test]
+    fn test_serialize() {
+        let line = Line {
+            quantity: 2,
+            merchandise_id: "gid://shopify/ProductVariant/1".to_string(),
+            attribute: None,
+            cost: 1.5,
+        };
+        assert_eq!(
+            serialize(&line),
+            serde_json::json!({
+                "quantity": 2,
+                "merchandiseId": "gid://shopify/ProductVariant/1",
+                "attribute": null,
+                "cost": 1.5,
+            })
+        );
+        assert_eq!(
+            serialize(&[
+                Operation::Noop,
+                Operation::Percentage(10.0),
+                Operation::Range(1, 5),
+                Operation::Fixed { amount: u64::MAX },
+            ]),
+            serde_json::json!([
+                "Noop",
+                { "Percentage": 10.0 },
+                { "Range": [1, 5] },
+                { "Fixed": { "amount": u64::MAX } },
+            ])
+        );
+        assert_eq!(
+            serialize(&BTreeMap::from([("a", 'b')])),
+            serde_json::json!({ "a": "b" })
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = serde_json::json!({
+            "quantity": -3,
+            "merchandiseId": "gid://shopify/ProductVariant/2",
+            "attribute": "gift",
+            "cost": 0.25,
+        });
+        let line: Line = deserialize(input.clone()).unwrap();
+        assert_eq!(serialize(&line), input);
+    }
+}
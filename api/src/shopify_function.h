@@ -26,9 +26,11 @@ __attribute__((import_name("shopify_function_input_get")))
 extern Val shopify_function_input_get();
 
 /**
- * Gets the length of a value (for arrays, objects, or strings)
+ * Gets the exact length of a value (for arrays, objects, or strings)
+ * Must be used when the length encoded in the value is (2^14) - 1,
+ * since longer lengths are truncated to that value in the encoding.
  * @param scope The value to get the length of
- * @return The length of the value
+ * @return The length of the value, or SIZE_MAX for other values or if the length could not be read
  */
 __attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
 __attribute__((import_name("shopify_function_input_get_val_len")))
@@ -36,6 +38,7 @@ extern size_t shopify_function_input_get_val_len(Val scope);
 
 /**
  * Reads a UTF-8 encoded string from the input into the provided buffer
+ * Also reads the data of binary values, whose pointer and length are decoded the same way
  * @param src The source address of the string
  * @param out The output buffer to write the string to
  * @param len The length of the string
@@ -59,7 +62,7 @@ extern Val shopify_function_input_get_obj_prop(Val scope, const uint8_t* ptr, si
  * Gets an object property by interned string ID
  * @param scope The object to get the property from
  * @param interned_string_id The interned string ID of the property name
- * @return The property value
+ * @return The property value, or an error value if the interned string ID is unknown
  */
 __attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
 __attribute__((import_name("shopify_function_input_get_interned_obj_prop")))
@@ -75,6 +78,17 @@ __attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
 __attribute__((import_name("shopify_function_input_get_at_index")))
 extern Val shopify_function_input_get_at_index(Val scope, size_t index);
 
+/**
+ * Gets a view over a range of an array's elements, which is itself an array
+ * @param scope The array or array slice to slice
+ * @param start The index of the first element, clamped to the array's length
+ * @param end The index one past the last element, clamped between start and the array's length
+ * @return The slice, or an error if the value is not an array
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_get_array_slice")))
+extern Val shopify_function_input_get_array_slice(Val scope, size_t start, size_t end);
+
 /**
  * Gets an object key at the specified index
  * @param scope The object to get the key from
@@ -85,6 +99,211 @@ __attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
 __attribute__((import_name("shopify_function_input_get_obj_key_at_index")))
 extern Val shopify_function_input_get_obj_key_at_index(Val scope, size_t index);
 
+/**
+ * Gets an object key at the specified index as an interned string, interning it in the provider
+ * Keys with the same bytes, and strings interned earlier with the same bytes, have the same ID
+ * @param scope The object to get the key from
+ * @param index The index of the key
+ * @return The interned string ID of the key, or SIZE_MAX if the value is not an object or the
+ * index is out of bounds
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_get_interned_obj_key_at_index")))
+extern InternedStringId shopify_function_input_get_interned_obj_key_at_index(Val scope, size_t index);
+
+/**
+ * Reads a string value into a buffer, copying at most cap bytes
+ * @param scope The string value
+ * @param out The destination buffer
+ * @param cap The capacity of the destination buffer
+ * @return The full length of the string in bytes, or SIZE_MAX if the value is not a string
+ * or binary data
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_read_utf8_str_with_len")))
+extern size_t shopify_function_input_read_utf8_str_with_len(Val scope, uint8_t* out, size_t cap);
+
+/**
+ * Reads an array or object value and all values nested in it up front
+ * @param scope The value to materialize
+ * @return The same value, or an error value if it could not be read
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_materialize")))
+extern Val shopify_function_input_materialize(Val scope);
+
+/**
+ * Creates an iterator over the elements of an array value or the entries of an object value,
+ * which keeps a cursor in the provider so each element is read after the one before it
+ * @param scope The array or object value
+ * @return The ID of the iterator, or SIZE_MAX if the value is not an array or object
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_iter_new")))
+extern size_t shopify_function_input_iter_new(Val scope);
+
+/**
+ * Advances an iterator
+ * @param iter The ID of the iterator
+ * @return The next element of the array or value of the object, or an IndexOutOfBounds error value once there are none
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_iter_next")))
+extern Val shopify_function_input_iter_next(size_t iter);
+
+/**
+ * Gets the key of the object entry an iterator last advanced to
+ * @param iter The ID of the iterator
+ * @return The key, or an error value if the iterator is over an array or hasn't advanced
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_iter_key")))
+extern Val shopify_function_input_iter_key(size_t iter);
+
+/**
+ * Drops an iterator, so that its ID can be reused
+ * @param iter The ID of the iterator
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_iter_drop")))
+extern void shopify_function_input_iter_drop(size_t iter);
+
+/**
+ * Pins a value, so it can be read again with shopify_function_input_get_pinned without resolving
+ * its path from the input. Pins are released with shopify_function_input_unpin
+ * @param scope The value to pin
+ * @return The ID of the pin, which no later pin reuses, or SIZE_MAX if too many values are pinned
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_pin")))
+extern size_t shopify_function_input_pin(Val scope);
+
+/**
+ * Gets a pinned value
+ * @param pin The ID of the pin
+ * @return The pinned value, or an UnknownPinnedValue error if it was unpinned or pinned from
+ *         another input
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_get_pinned")))
+extern Val shopify_function_input_get_pinned(size_t pin);
+
+/**
+ * Unpins a value. IDs that were already unpinned or pinned from another input are ignored
+ * @param pin The ID of the pin
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_unpin")))
+extern void shopify_function_input_unpin(size_t pin);
+
+/**
+ * Reads a batch of elements of an array value into a buffer
+ * @param scope The array value
+ * @param start The index of the first element to read
+ * @param out The buffer to read the element values into
+ * @param cap The maximum number of elements to read
+ * @return The number of elements read, or SIZE_MAX if the value is not an array
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_read_array_elements")))
+extern size_t shopify_function_input_read_array_elements(Val scope, size_t start, Val* out, size_t cap);
+
+/**
+ * Creates a shape, the keys of objects whose properties are read together
+ * @param ids The interned string IDs of the keys
+ * @param len The number of keys
+ * @return The ID of the new shape
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_new_shape")))
+extern size_t shopify_function_input_new_shape(const InternedStringId* ids, size_t len);
+
+/**
+ * Reads the properties of an object for each key of a shape into a buffer
+ * @param scope The object value
+ * @param shape The shape ID
+ * @param out The buffer to read the property values into
+ * @param cap The maximum number of values to read
+ * @return The number of values read, or SIZE_MAX if the shape does not exist
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_read_shape")))
+extern size_t shopify_function_input_read_shape(Val scope, size_t shape, Val* out, size_t cap);
+
+/**
+ * Reads the key and the value of an object's entry at an index with a single call
+ * @param scope The object value
+ * @param index The index of the entry
+ * @param out Pointer to write the key and the value to, one after the other
+ * Both are error values if the scope is not an object or the index is out of bounds
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_get_obj_entry_at_index")))
+extern void shopify_function_input_get_obj_entry_at_index(Val scope, size_t index, Val* out);
+
+/**
+ * Reads part of a string, starting at a byte offset, into a buffer
+ * @param scope The string value
+ * @param offset The byte offset to start reading from
+ * @param out Pointer to the buffer to write the bytes to
+ * @param cap The capacity of the buffer in bytes
+ * @return The number of bytes written, which is 0 at the end of the string, or SIZE_MAX if the value is not a string
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_read_utf8_str_chunk")))
+extern size_t shopify_function_input_read_utf8_str_chunk(Val scope, size_t offset, uint8_t* out, size_t cap);
+
+/**
+ * Reads a value encoded as MessagePack into a buffer, copying at most cap bytes. Arrays and objects
+ * are copied as they are in the input. Other values are encoded, with numbers that have no
+ * fractional part encoded as integers.
+ * @param scope The value
+ * @param out The destination buffer
+ * @param cap The capacity of the destination buffer
+ * @return The full length of the encoding in bytes, or SIZE_MAX if the value could not be read
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_read_msgpack_with_len")))
+extern size_t shopify_function_input_read_msgpack_with_len(Val scope, uint8_t* out, size_t cap);
+
+/**
+ * Reads an array of booleans packed into bits, eight to a byte with the first element in the least
+ * significant bit of the first byte, copying at most cap bytes.
+ * @param scope The array
+ * @param out The destination buffer
+ * @param cap The capacity of the destination buffer
+ * @return The full length of the bits in bytes, or SIZE_MAX if the value is not an array of booleans
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_read_bool_bits_with_len")))
+extern size_t shopify_function_input_read_bool_bits_with_len(Val scope, uint8_t* out, size_t cap);
+
+/**
+ * Reads a string into a buffer allocated with the module's own allocator, for languages whose
+ * runtimes can't size a buffer before reading into it. The module must export `cabi_realloc`,
+ * `canonical_abi_realloc` or `malloc`, and owns the returned buffer.
+ * @param scope The string value
+ * @return The length of the string in the most significant 32 bits and the address of the buffer in
+ *         the least significant 32 bits, or a length of UINT32_MAX if the value is not a string
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_read_utf8_str_alloc")))
+extern uint64_t shopify_function_input_read_utf8_str_alloc(Val scope);
+
+/**
+ * Compares a string value with a string once leading and trailing whitespace is trimmed from both,
+ * e.g. to match user-entered codes, without the module including Unicode normalization tables.
+ * @param scope The string value
+ * @param ptr The string to compare with (as a UTF-8 string)
+ * @param len The length of the string to compare with
+ * @param normalize 1 to compare the NFC normalizations of the strings, 0 to compare them as they are
+ * @return A boolean that is true if the strings are equal, and false if not or if the value is not a
+ * string, or a NormalizationUnsupported error if the provider was built without normalization
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_input_utf8_str_trim_eq")))
+extern Val shopify_function_input_utf8_str_trim_eq(Val scope, const uint8_t* ptr, size_t len, uint32_t normalize);
+
 // Write API
 /**
  * Creates a new boolean output value
@@ -112,6 +331,24 @@ __attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
 __attribute__((import_name("shopify_function_output_new_i32")))
 extern WriteResult shopify_function_output_new_i32(int32_t value);
 
+/**
+ * Creates a new signed 64-bit integer output value, written exactly rather than as a float
+ * @param value The integer value
+ * @return WriteResult indicating success or failure
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_output_new_i64")))
+extern WriteResult shopify_function_output_new_i64(int64_t value);
+
+/**
+ * Creates a new unsigned 64-bit integer output value, written exactly rather than as a float
+ * @param value The integer value
+ * @return WriteResult indicating success or failure
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_output_new_u64")))
+extern WriteResult shopify_function_output_new_u64(uint64_t value);
+
 /**
  * Creates a new 64-bit float output value
  * @param value The float value
@@ -121,6 +358,15 @@ __attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
 __attribute__((import_name("shopify_function_output_new_f64")))
 extern WriteResult shopify_function_output_new_f64(double value);
 
+/**
+ * Writes a value read from the input, copying it as it is encoded in the input
+ * @param scope The input value
+ * @return WriteResult indicating success or failure
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_output_new_input_value")))
+extern WriteResult shopify_function_output_new_input_value(Val scope);
+
 /**
  * Creates a new UTF-8 string output value
  * @param ptr The string data
@@ -131,6 +377,27 @@ __attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
 __attribute__((import_name("shopify_function_output_new_utf8_str")))
 extern WriteResult shopify_function_output_new_utf8_str(const uint8_t* ptr, size_t len);
 
+/**
+ * Creates a new binary output value, written as MessagePack bin rather than a string
+ * @param ptr The binary data
+ * @param len The length of the data in bytes
+ * @return WriteResult indicating success or failure
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_output_new_bytes")))
+extern WriteResult shopify_function_output_new_bytes(const uint8_t* ptr, size_t len);
+
+/**
+ * Writes a MessagePack fragment serialized ahead of time as one output value
+ * The fragment is copied to the output as it is, and must be exactly one complete value
+ * @param ptr The MessagePack fragment
+ * @param len The length of the fragment in bytes
+ * @return WriteResult indicating success or failure
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_output_new_msgpack")))
+extern WriteResult shopify_function_output_new_msgpack(const uint8_t* ptr, size_t len);
+
 /**
  * Creates a new UTF-8 string output value from an interned string ID
  * @param id The interned string ID
@@ -140,6 +407,35 @@ __attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
 __attribute__((import_name("shopify_function_output_new_interned_utf8_str")))
 extern WriteResult shopify_function_output_new_interned_utf8_str(InternedStringId id);
 
+/**
+ * Creates a new UTF-8 string output value that is written in chunks
+ * Must be paired with shopify_function_output_finish_utf8_str, which sets the length to
+ * the number of bytes written with shopify_function_output_write_utf8_str_chunk
+ * @param len_hint The expected length of the string in bytes, to reserve space for
+ * @return WriteResult indicating success or failure
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_output_new_dynamic_utf8_str")))
+extern WriteResult shopify_function_output_new_dynamic_utf8_str(size_t len_hint);
+
+/**
+ * Appends a chunk of UTF-8 data to the string started with shopify_function_output_new_dynamic_utf8_str
+ * @param ptr The chunk data
+ * @param len The length of the chunk in bytes
+ * @return WriteResult indicating success or failure
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_output_write_utf8_str_chunk")))
+extern WriteResult shopify_function_output_write_utf8_str_chunk(const uint8_t* ptr, size_t len);
+
+/**
+ * Finalizes a string output value started with shopify_function_output_new_dynamic_utf8_str
+ * @return WriteResult indicating success or failure
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_output_finish_utf8_str")))
+extern WriteResult shopify_function_output_finish_utf8_str();
+
 /**
  * Creates a new object output value with the specified number of properties
  * @param len The number of properties
@@ -174,6 +470,95 @@ __attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
 __attribute__((import_name("shopify_function_output_finish_array")))
 extern WriteResult shopify_function_output_finish_array();
 
+/**
+ * Creates a new array output value whose length is set when it is finished
+ * @return WriteResult indicating success or failure
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_output_new_dynamic_array")))
+extern WriteResult shopify_function_output_new_dynamic_array();
+
+/**
+ * Discards all output written so far, so it can be written again from the start
+ * @return WriteResult indicating success or failure
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_output_discard")))
+extern WriteResult shopify_function_output_discard();
+
+/**
+ * Records the current point in the output, to roll back to later
+ * @return The ID of the checkpoint
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_output_checkpoint")))
+extern size_t shopify_function_output_checkpoint();
+
+/**
+ * Removes all output written since a checkpoint, along with later checkpoints
+ * @param id The ID of the checkpoint
+ * @return WriteResult indicating success or failure
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_output_rollback")))
+extern WriteResult shopify_function_output_rollback(size_t id);
+
+/**
+ * Sets whether objects started afterwards leave out fields whose value is null.
+ * Nulls written while it's unset are kept, even in objects started while it was set
+ * @param skip Non-zero to leave out null fields
+ * @return Whether null fields were left out before, as 1 or 0
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_output_set_skip_nulls")))
+extern uint32_t shopify_function_output_set_skip_nulls(uint32_t skip);
+
+/**
+ * Gets the number of bytes of output written so far, in its msgpack encoding, so functions can
+ * leave out optional values before reaching the output size limit
+ * @return The length of the output in bytes
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_output_len")))
+extern size_t shopify_function_output_len();
+
+/**
+ * Creates a set of keys for skipping duplicates, such as operations keyed by ID, kept by the
+ * provider until it's dropped with shopify_function_dedup_set_drop
+ * @return The ID of the set
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_dedup_set_new")))
+extern size_t shopify_function_dedup_set_new();
+
+/**
+ * Inserts an interned string into a set. Strings interned more than once are the same key
+ * @param set The ID of the set
+ * @param id The interned string ID
+ * @return 1 if the string wasn't in the set, or the set or string doesn't exist, 0 otherwise
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_dedup_set_insert_interned")))
+extern uint32_t shopify_function_dedup_set_insert_interned(size_t set, InternedStringId id);
+
+/**
+ * Inserts a hash of a key computed by the caller into a set
+ * @param set The ID of the set
+ * @param hash The 64-bit hash of the key
+ * @return 1 if the hash wasn't in the set, or the set doesn't exist, 0 otherwise
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_dedup_set_insert_hash")))
+extern uint32_t shopify_function_dedup_set_insert_hash(size_t set, uint64_t hash);
+
+/**
+ * Drops a set, so its ID can be reused
+ * @param set The ID of the set
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_dedup_set_drop")))
+extern void shopify_function_dedup_set_drop(size_t set);
+
 // Other
 /**
  * Interns a UTF-8 string and returns its ID for efficient reuse
@@ -185,6 +570,61 @@ __attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
 __attribute__((import_name("shopify_function_intern_utf8_str")))
 extern InternedStringId shopify_function_intern_utf8_str(const uint8_t* ptr, size_t len);
 
+/**
+ * Checks whether an interned string ID exists in the string interner
+ * @param id The interned string ID
+ * @return 1 if the ID exists, 0 otherwise
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_interned_utf8_str_exists")))
+extern uint32_t shopify_function_interned_utf8_str_exists(InternedStringId id);
+
+/**
+ * Gets the version of the provider
+ * @return The version, with bits 32-47 for the major version, bits 16-31 for the minor version, and bits 0-15 for the patch version
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_provider_version")))
+extern uint64_t shopify_function_provider_version();
+
+/**
+ * Gets a seed derived from the input, which is the same every time the function runs with the same input
+ * Use it to seed pseudo-random number generators instead of reading clocks or random numbers
+ * @return The seed
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_deterministic_seed")))
+extern uint64_t shopify_function_deterministic_seed();
+
+/**
+ * Limits the number of reads of the input that look up or traverse values, such as getting a
+ * property or an element, or advancing an iterator
+ * Each value read spends one unit, and reads fail with a BudgetExceeded error once the budget is spent
+ * @param budget The number of reads left
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_set_work_budget")))
+extern void shopify_function_set_work_budget(size_t budget);
+
+/**
+ * Starts a call to the host for data it provides lazily, without blocking
+ * @param capability The interned string naming the requested capability
+ * @param argument The interned string with the argument for the capability
+ * @return The ID of the host call, to poll for its result
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_host_call_start")))
+extern size_t shopify_function_host_call_start(InternedStringId capability, InternedStringId argument);
+
+/**
+ * Polls a host call for its result
+ * @param id The ID of the host call
+ * @return The result, or an error value if the call is pending, unsupported or unknown
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_host_call_poll")))
+extern Val shopify_function_host_call_poll(size_t id);
+
 /**
  * Logs a new UTF-8 string output value
  * @param ptr The string data
@@ -194,4 +634,34 @@ __attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
 __attribute__((import_name("shopify_function_log_new_utf8_str")))
 extern void shopify_function_log_new_utf8_str(const uint8_t* ptr, size_t len);
 
+/**
+ * Logs a new UTF-8 string output value, like shopify_function_log_new_utf8_str
+ * @param ptr The string data
+ * @param len The length of the string
+ * @return The number of bytes dropped from the logs, which are a ring buffer, to fit the string
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_log_new_utf8_str_with_dropped_len")))
+extern size_t shopify_function_log_new_utf8_str_with_dropped_len(const uint8_t* ptr, size_t len);
+
+/**
+ * Logs a message with a level as a structured record, which the host reads separately from the
+ * logs after the function finishes
+ * @param level The log level (see LogLevel in shopify_function_abi.h)
+ * @param ptr The message data
+ * @param len The length of the message
+ * @return The number of earlier records dropped to keep the records within their capacity
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_log_new_record")))
+extern size_t shopify_function_log_new_record(uint32_t level, const uint8_t* ptr, size_t len);
+
+/**
+ * Writes each interned string with its ID to the logs, one per line, for debugging
+ * @return The number of interned strings
+ */
+__attribute__((import_module(SHOPIFY_FUNCTION_IMPORT_MODULE)))
+__attribute__((import_name("shopify_function_log_interned_strings")))
+extern size_t shopify_function_log_interned_strings(void);
+
 #endif // SHOPIFY_FUNCTION_H
@@ -24,12 +24,13 @@
   ;;   - Array: number of elements.
   ;;   - String: number of utf8-encoded bytes.
   ;;   - Object: number of entries.
-  ;;   - -1 for all other values.
+  ;;   - -1 for all other values, or if the length could not be read.
   ;;
   ;; Note that calling this function is not required in all cases, if
-  ;; the length value is equal or less than (2^14) - 1, the length
-  ;; value will be encoded as an immediate in its NaNBox
-  ;; representation.
+  ;; the length value is less than (2^14) - 1, the length value will be
+  ;; encoded as an immediate in its NaNBox representation. A NaNBox
+  ;; length of (2^14) - 1 means the value has at least that many
+  ;; elements, and this function must be called to get the exact length.
   ;;
   ;; Parameters
   ;;   - scope: NaNBox encoded value.
@@ -43,6 +44,7 @@
   ;; Length can be obtained from the length bits of the NanBox or from `shopify_function_input_get_val_len` call.
   ;; The caller must allocate a buffer of sufficient size.
   ;; No return value - the string is copied directly into the provided buffer.
+  ;; Also reads the data of binary values, whose pointer and length are decoded the same way.
   ;; Parameters:
   ;;   - src: i32 memory address of the string.
   ;;   - out: i32 pointer to the destination buffer.
@@ -73,6 +75,8 @@
   ;;   - interned_string_id: i32 ID of the interned string.
   ;; Returns:
   ;;   - i64 NanBox value of the property.
+  ;; Errors:
+  ;;   - If the interned string ID is unknown, returns a NanBox with ErrorCode::UnknownInternedString.
   (import "shopify_function_v2" "shopify_function_input_get_interned_obj_prop" 
     (func (param $scope i64) (param $interned_string_id i32) (result i64))
   )
@@ -89,6 +93,21 @@
     (func (param $scope i64) (param $index i32) (result i64))
   )
 
+  ;; Gets a view over a range of an array's elements.
+  ;; The slice is an array whose elements are read from the sliced array as they're accessed, so
+  ;; it can be indexed, iterated and copied without copying the elements into a new array.
+  ;; Parameters:
+  ;;   - scope: i64 NanBox value of the array or array slice.
+  ;;   - start: i32 index of the first element, clamped to the array's length.
+  ;;   - end: i32 index one past the last element, clamped between start and the array's length.
+  ;; Returns:
+  ;;   - i64 NanBox array value of the slice.
+  ;; Errors:
+  ;;   - If the value is not an array, returns a NanBox with ErrorCode::NotAnArray.
+  (import "shopify_function_v2" "shopify_function_input_get_array_slice"
+    (func (param $scope i64) (param $start i32) (param $end i32) (result i64))
+  )
+
   ;; Gets a key name at specified index from an object.
   ;; Used for dynamic iteration of object keys by index.
   ;; Parameters:
@@ -102,6 +121,230 @@
     (func (param $scope i64) (param $index i32) (result i64))
   )
 
+  ;; Gets the key at specified index from an object as an interned string.
+  ;; The key is interned by the provider rather than copied into WebAssembly memory. Keys with the
+  ;; same bytes, and strings interned earlier with the same bytes, have the same ID.
+  ;; Parameters:
+  ;;   - scope: i64 NanBox value of the object.
+  ;;   - index: i32 index of the key to retrieve (zero-based).
+  ;; Returns:
+  ;;   - i32 interned string ID of the key, or -1 if the value is not an object or the index is
+  ;;     out of bounds.
+  (import "shopify_function_v2" "shopify_function_input_get_interned_obj_key_at_index"
+    (func (param $scope i64) (param $index i32) (result i32))
+  )
+
+  ;; Reads a UTF-8 encoded string value into a destination buffer and returns its length.
+  ;; Copies at most `cap` bytes, and returns the full length of the string in bytes, so strings
+  ;; whose length does not fit in the NanBox can be read without first calling
+  ;; `shopify_function_input_get_val_len`. If the returned length is greater than `cap`, the
+  ;; caller must call this function again with a buffer of at least that size.
+  ;; Parameters:
+  ;;   - scope: i64 NanBox value of the string.
+  ;;   - out: i32 pointer to the destination buffer.
+  ;;   - cap: i32 capacity of the destination buffer in bytes.
+  ;; Returns:
+  ;;   - i32 length of the string in bytes, or -1 if the value is not a string or binary data.
+  (import "shopify_function_v2" "shopify_function_input_read_utf8_str_with_len"
+    (func (param $scope i64) (param $out i32) (param $cap i32) (result i32))
+  )
+
+  ;; Reads an array or object value and all values nested in it up front.
+  ;; Values are otherwise read lazily, as far as each access needs. Materializing a value pays the
+  ;; reading cost once, so that later accesses within it are direct lookups. This is useful for
+  ;; values that will be traversed multiple times or out of order, at the cost of reading parts of
+  ;; the value that may never be accessed.
+  ;; Parameters:
+  ;;   - scope: i64 NanBox value to materialize.
+  ;; Returns:
+  ;;   - i64 the same NanBox value, or a NanBox error if the value could not be read.
+  (import "shopify_function_v2" "shopify_function_input_materialize"
+    (func (param $scope i64) (result i64))
+  )
+
+  ;; Creates an iterator over the elements of an array value or the entries of an object value.
+  ;; The provider keeps a cursor into the value, so each element is read right after the one before
+  ;; it rather than looked up by its index. Iterators are dropped with
+  ;; shopify_function_input_iter_drop once they're no longer needed.
+  ;; Parameters:
+  ;;   - scope: i64 NanBox array or object value.
+  ;; Returns:
+  ;;   - i32 ID of the iterator, or -1 if the value is not an array or object.
+  (import "shopify_function_v2" "shopify_function_input_iter_new"
+    (func (param $scope i64) (result i32))
+  )
+
+  ;; Advances an iterator.
+  ;; Parameters:
+  ;;   - iter: i32 ID of the iterator.
+  ;; Returns:
+  ;;   - i64 NanBox next element of the array or value of the object, an IndexOutOfBounds error
+  ;;     once there are none, or an UnknownIterator error if the iterator doesn't exist.
+  (import "shopify_function_v2" "shopify_function_input_iter_next"
+    (func (param $iter i32) (result i64))
+  )
+
+  ;; Gets the key of the object entry an iterator last advanced to.
+  ;; Parameters:
+  ;;   - iter: i32 ID of the iterator.
+  ;; Returns:
+  ;;   - i64 NanBox key, or a NanBox error if the iterator is over an array or hasn't advanced.
+  (import "shopify_function_v2" "shopify_function_input_iter_key"
+    (func (param $iter i32) (result i64))
+  )
+
+  ;; Drops an iterator, so that its ID can be reused.
+  ;; Parameters:
+  ;;   - iter: i32 ID of the iterator.
+  (import "shopify_function_v2" "shopify_function_input_iter_drop"
+    (func (param $iter i32))
+  )
+
+  ;; Pins a value, so it can be read again with shopify_function_input_get_pinned without
+  ;; resolving its path from the input. Pins are released with shopify_function_input_unpin.
+  ;; Parameters:
+  ;;   - scope: i64 NanBox value to pin.
+  ;; Returns:
+  ;;   - i32 ID of the pin, which no later pin reuses, or -1 if too many values are pinned.
+  (import "shopify_function_v2" "shopify_function_input_pin"
+    (func (param $scope i64) (result i32))
+  )
+
+  ;; Gets a pinned value.
+  ;; Parameters:
+  ;;   - pin: i32 ID of the pin.
+  ;; Returns:
+  ;;   - i64 NanBox pinned value, or an UnknownPinnedValue error if the value was unpinned or
+  ;;     pinned from another input.
+  (import "shopify_function_v2" "shopify_function_input_get_pinned"
+    (func (param $pin i32) (result i64))
+  )
+
+  ;; Unpins a value. IDs that were already unpinned or pinned from another input are ignored.
+  ;; Parameters:
+  ;;   - pin: i32 ID of the pin.
+  (import "shopify_function_v2" "shopify_function_input_unpin"
+    (func (param $pin i32))
+  )
+
+  ;; Reads a batch of elements of an array value into a buffer.
+  ;; This takes a single call for many elements, compared to calling
+  ;; shopify_function_input_get_at_index for each one.
+  ;; Parameters:
+  ;;   - scope: i64 NanBox value of the array.
+  ;;   - start: i32 index of the first element to read.
+  ;;   - out: i32 pointer to a buffer for the NanBox values of the elements.
+  ;;   - cap: i32 maximum number of elements to read.
+  ;; Returns:
+  ;;   - i32 number of elements read, or -1 if the value is not an array. Fewer than `cap`
+  ;;     elements are read when the end of the array is reached.
+  (import "shopify_function_v2" "shopify_function_input_read_array_elements"
+    (func (param $scope i64) (param $start i32) (param $out i32) (param $cap i32) (result i32))
+  )
+
+  ;; Creates a shape: the keys of objects whose properties are read together with
+  ;; shopify_function_input_read_shape.
+  ;; Parameters:
+  ;;   - ids: i32 pointer to an array of interned string IDs, one per key.
+  ;;   - len: i32 number of keys.
+  ;; Returns:
+  ;;   - i32 ID of the new shape.
+  (import "shopify_function_v2" "shopify_function_input_new_shape"
+    (func (param $ids i32) (param $len i32) (result i32))
+  )
+
+  ;; Reads the properties of an object for each key of a shape into a buffer, in the
+  ;; order of the keys. Missing properties are read as null, and every value is an
+  ;; error value if the scope is not an object.
+  ;; Parameters:
+  ;;   - scope: i64 NaN-boxed object value.
+  ;;   - shape: i32 ID of the shape, returned by shopify_function_input_new_shape.
+  ;;   - out: i32 pointer to a buffer of i64 values to read the property values into.
+  ;;   - cap: i32 maximum number of values to read.
+  ;; Returns:
+  ;;   - i32 number of values read, or -1 if the shape does not exist.
+  (import "shopify_function_v2" "shopify_function_input_read_shape"
+    (func (param $scope i64) (param $shape i32) (param $out i32) (param $cap i32) (result i32))
+  )
+
+  ;; Reads the key and the value of an object's entry at an index with a single call.
+  ;; Parameters:
+  ;; - scope: NanBox value of the object
+  ;; - index: The index of the entry
+  ;; - out: Pointer to write the NanBox key and the NanBox value to, one after the other (16 bytes)
+  ;; Both are error values if the scope is not an object or the index is out of bounds.
+  (import "shopify_function_v2" "shopify_function_input_get_obj_entry_at_index"
+    (func (param $scope i64) (param $index i32) (param $out i32))
+  )
+
+  ;; Reads part of a string, starting at a byte offset, into a buffer.
+  ;; Parameters:
+  ;; - scope: NanBox value of the string
+  ;; - offset: The byte offset to start reading from
+  ;; - out: Pointer to the buffer to write the bytes to
+  ;; - cap: The capacity of the buffer in bytes
+  ;; Returns: the number of bytes written, which is 0 at the end of the string, or -1 if the value is not a string.
+  (import "shopify_function_v2" "shopify_function_input_read_utf8_str_chunk"
+    (func (param $scope i64) (param $offset i32) (param $out i32) (param $cap i32) (result i32))
+  )
+
+  ;; Reads a value encoded as MessagePack into a buffer, copying at most `cap` bytes.
+  ;; Arrays and objects are copied as they are in the input, so they can be hashed, cached, or
+  ;; forwarded without reading them value by value. Other values are encoded, with numbers that
+  ;; have no fractional part encoded as integers. If the returned length is greater than `cap`,
+  ;; the caller must call this function again with a buffer of at least that size.
+  ;; Parameters:
+  ;;   - scope: i64 NanBox value to encode.
+  ;;   - out: i32 pointer to the destination buffer.
+  ;;   - cap: i32 capacity of the destination buffer in bytes.
+  ;; Returns:
+  ;;   - i32 length of the encoding in bytes, or -1 if the value could not be read.
+  (import "shopify_function_v2" "shopify_function_input_read_msgpack_with_len"
+    (func (param $scope i64) (param $out i32) (param $cap i32) (result i32))
+  )
+
+  ;; Reads an array of booleans packed into bits, copying at most `cap` bytes.
+  ;; The bits are stored eight to a byte, with the first element in the least significant bit of
+  ;; the first byte, so large arrays of flags are read with a single call. If the returned length is
+  ;; greater than `cap`, the caller must call this function again with a buffer of at least that size.
+  ;; Parameters:
+  ;;   - scope: i64 NanBox array value.
+  ;;   - out: i32 pointer to the destination buffer.
+  ;;   - cap: i32 capacity of the destination buffer in bytes.
+  ;; Returns:
+  ;;   - i32 length of the bits in bytes, or -1 if the value is not an array of booleans.
+  (import "shopify_function_v2" "shopify_function_input_read_bool_bits_with_len"
+    (func (param $scope i64) (param $out i32) (param $cap i32) (result i32))
+  )
+
+  ;; Reads a string into a buffer allocated with the module's own allocator.
+  ;; For languages whose runtimes can't size a buffer before reading into it, such as TinyGo and
+  ;; Swift. The module must export `cabi_realloc`, `canonical_abi_realloc` or `malloc`, and owns
+  ;; the returned buffer.
+  ;; Parameters:
+  ;;   - scope: i64 NanBox string value.
+  ;; Returns:
+  ;;   - i64 with the length of the string in the most significant 32 bits and the address of the
+  ;;     buffer in the least significant 32 bits, or a length of -1 if the value is not a string.
+  (import "shopify_function_v2" "shopify_function_input_read_utf8_str_alloc"
+    (func (param $scope i64) (result i64))
+  )
+
+  ;; Compares a string value with a string once leading and trailing whitespace is trimmed from
+  ;; both, e.g. to match user-entered codes. Comparing NFC normalizations in the provider saves the
+  ;; module from including Unicode normalization tables for a single comparison.
+  ;; Parameters:
+  ;;   - scope: i64 NanBox string value.
+  ;;   - ptr: i32 pointer to the string to compare with.
+  ;;   - len: i32 length of the string to compare with in bytes.
+  ;;   - normalize: i32 1 to compare the NFC normalizations of the strings, 0 to compare them as they are.
+  ;; Returns:
+  ;;   - i64 NanBox boolean, true if the strings are equal and false if not or if the value is not a
+  ;;     string, or a NormalizationUnsupported error if the provider was built without normalization.
+  (import "shopify_function_v2" "shopify_function_input_utf8_str_trim_eq"
+    (func (param $scope i64) (param $ptr i32) (param $len i32) (param $normalize i32) (result i64))
+  )
+
   ;; Write API Functions - Used to build response data to return from the function.
 
   ;; Writes a new boolean output value.
@@ -135,6 +378,26 @@
     (func (param $value i32) (result i32))
   )
 
+  ;; Writes a new signed 64-bit integer output value.
+  ;; Used for integers outside of the 32-bit range, such as IDs, which f64 can't represent exactly.
+  ;; Parameters:
+  ;;   - value: i64 integer value.
+  ;; Returns:
+  ;;   - i32 status code indicating success or failure.
+  (import "shopify_function_v2" "shopify_function_output_new_i64"
+    (func (param $value i64) (result i32))
+  )
+
+  ;; Writes a new unsigned 64-bit integer output value.
+  ;; The value is interpreted as unsigned, so integers up to 2^64 - 1 are written exactly.
+  ;; Parameters:
+  ;;   - value: i64 integer value, interpreted as unsigned.
+  ;; Returns:
+  ;;   - i32 status code indicating success or failure.
+  (import "shopify_function_v2" "shopify_function_output_new_u64"
+    (func (param $value i64) (result i32))
+  )
+
   ;; Writes a new floating point output value.
   ;; Used for decimal or large numeric values.
   ;; Provides full IEEE 754 double precision.
@@ -146,6 +409,17 @@
     (func (param $value f64) (result i32))
   )
 
+  ;; Writes a value read from the input.
+  ;; The value is copied as it is encoded in the input, so arrays and objects are written without
+  ;; reading them value by value. Strings from the input can be written as object keys.
+  ;; Parameters:
+  ;;   - scope: i64 NanBox value read from the input.
+  ;; Returns:
+  ;;   - i32 status code indicating success or failure.
+  (import "shopify_function_v2" "shopify_function_output_new_input_value"
+    (func (param $scope i64) (result i32))
+  )
+
   ;; Writes a new string output value.
   ;; Used for text values in the response.
   ;; The string data is copied from WebAssembly memory.
@@ -158,6 +432,29 @@
     (func (param $ptr i32) (param $len i32) (result i32))
   )
 
+  ;; Writes a new binary output value, such as a hash, as MessagePack bin rather than a string.
+  ;; The data is copied from WebAssembly memory.
+  ;; Parameters:
+  ;;   - ptr: i32 pointer to the data in WebAssembly memory.
+  ;;   - len: i32 length of the data in bytes.
+  ;; Returns:
+  ;;   - i32 status code indicating success or failure
+  (import "shopify_function_v2" "shopify_function_output_new_bytes"
+    (func (param $ptr i32) (param $len i32) (result i32))
+  )
+
+  ;; Writes a MessagePack fragment serialized ahead of time, such as static metadata, as one output
+  ;; value. The fragment is copied from WebAssembly memory to the output as it is, and must be
+  ;; exactly one complete value, or an invalid MessagePack status code is returned.
+  ;; Parameters:
+  ;;   - ptr: i32 pointer to the fragment in WebAssembly memory.
+  ;;   - len: i32 length of the fragment in bytes.
+  ;; Returns:
+  ;;   - i32 status code indicating success or failure
+  (import "shopify_function_v2" "shopify_function_output_new_msgpack"
+    (func (param $ptr i32) (param $len i32) (result i32))
+  )
+
   ;; Writes a new string output value from an interned string.
   ;; More efficient than direct string when reusing string values.
   ;; Especially useful for repetitive property names.
@@ -169,6 +466,36 @@
     (func (param $id i32) (result i32))
   )
 
+  ;; Initializes a new string output value that is written in chunks, so it doesn't need to
+  ;; be built in WebAssembly memory first.
+  ;; Must be paired with shopify_function_output_finish_utf8_str, which sets the length to the
+  ;; number of bytes written with shopify_function_output_write_utf8_str_chunk.
+  ;; Parameters:
+  ;;   - len_hint: i32 expected length of the string in bytes, to reserve space for.
+  ;; Returns:
+  ;;   - i32 status code indicating success or failure.
+  (import "shopify_function_v2" "shopify_function_output_new_dynamic_utf8_str"
+    (func (param $len_hint i32) (result i32))
+  )
+
+  ;; Appends a chunk to the string started with shopify_function_output_new_dynamic_utf8_str.
+  ;; The chunk data is copied from WebAssembly memory.
+  ;; Parameters:
+  ;;   - ptr: i32 pointer to the chunk data in WebAssembly memory.
+  ;;   - len: i32 length of the chunk in bytes.
+  ;; Returns:
+  ;;   - i32 status code indicating success or failure.
+  (import "shopify_function_v2" "shopify_function_output_write_utf8_str_chunk"
+    (func (param $ptr i32) (param $len i32) (result i32))
+  )
+
+  ;; Finalizes a string output value started with shopify_function_output_new_dynamic_utf8_str.
+  ;; Returns:
+  ;;   - i32 status code indicating success or failure.
+  (import "shopify_function_v2" "shopify_function_output_finish_utf8_str"
+    (func (result i32))
+  )
+
   ;; Initializes a new object output value.
   ;; Must be paired with shopify_function_output_finish_object.
   ;; Properties are added using alternating key/value calls to write API functions.
@@ -211,6 +538,95 @@
     (func (result i32))
   )
 
+  ;; Initializes a new array output value whose length is not known up front.
+  ;; Must be paired with shopify_function_output_finish_array, which sets the length to the
+  ;; number of elements added. This avoids counting elements ahead of time, e.g. when filtering.
+  ;; Returns:
+  ;;   - i32 status code indicating success or failure.
+  (import "shopify_function_v2" "shopify_function_output_new_dynamic_array"
+    (func (result i32))
+  )
+
+  ;; Discards all output written so far, including unfinished objects and arrays,
+  ;; so the output can be written again from the start.
+  ;; Returns:
+  ;;   - i32 status code indicating success or failure.
+  (import "shopify_function_v2" "shopify_function_output_discard"
+    (func (result i32))
+  )
+
+  ;; Records the current point in the output, so it can be rolled back to with
+  ;; shopify_function_output_rollback.
+  ;; Returns:
+  ;;   - i32 ID of the checkpoint.
+  (import "shopify_function_v2" "shopify_function_output_checkpoint"
+    (func (result i32))
+  )
+
+  ;; Removes all output written since a checkpoint, returning the output to the state
+  ;; it was in when the checkpoint was recorded. Checkpoints recorded after it are removed.
+  ;; Parameters:
+  ;;   - id: i32 ID of the checkpoint, from shopify_function_output_checkpoint.
+  ;; Returns:
+  ;;   - i32 status code indicating success or failure.
+  (import "shopify_function_v2" "shopify_function_output_rollback"
+    (func (param $id i32) (result i32))
+  )
+
+  ;; Sets whether objects started afterwards leave out fields whose value is null,
+  ;; removing the key when the null is written. Nulls written while it's unset are kept,
+  ;; even in objects started while it was set.
+  ;; Parameters:
+  ;;   - skip: i32 non-zero to leave out null fields.
+  ;; Returns:
+  ;;   - i32 1 if null fields were left out before, 0 otherwise.
+  (import "shopify_function_v2" "shopify_function_output_set_skip_nulls"
+    (func (param $skip i32) (result i32))
+  )
+
+  ;; Gets the number of bytes of output written so far, in its msgpack encoding.
+  ;; Lets functions leave out optional values before reaching the output size limit.
+  ;; Returns:
+  ;;   - i32 length of the output in bytes.
+  (import "shopify_function_v2" "shopify_function_output_len"
+    (func (result i32))
+  )
+
+  ;; Creates a set of keys for skipping duplicates, such as operations keyed by ID.
+  ;; The set is kept by the provider until it's dropped with shopify_function_dedup_set_drop.
+  ;; Returns:
+  ;;   - i32 ID of the set.
+  (import "shopify_function_v2" "shopify_function_dedup_set_new"
+    (func (result i32))
+  )
+
+  ;; Inserts an interned string into a set. Strings interned more than once are the same key.
+  ;; Parameters:
+  ;;   - set: i32 ID of the set.
+  ;;   - id: i32 ID of the interned string.
+  ;; Returns:
+  ;;   - i32 1 if the string wasn't in the set, or the set or string doesn't exist, 0 otherwise.
+  (import "shopify_function_v2" "shopify_function_dedup_set_insert_interned"
+    (func (param $set i32) (param $id i32) (result i32))
+  )
+
+  ;; Inserts a hash of a key computed by the caller into a set.
+  ;; Parameters:
+  ;;   - set: i32 ID of the set.
+  ;;   - hash: i64 hash of the key.
+  ;; Returns:
+  ;;   - i32 1 if the hash wasn't in the set, or the set doesn't exist, 0 otherwise.
+  (import "shopify_function_v2" "shopify_function_dedup_set_insert_hash"
+    (func (param $set i32) (param $hash i64) (result i32))
+  )
+
+  ;; Drops a set, so its ID can be reused.
+  ;; Parameters:
+  ;;   - set: i32 ID of the set.
+  (import "shopify_function_v2" "shopify_function_dedup_set_drop"
+    (func (param $set i32))
+  )
+
   ;; Other Functions
 
   ;; Interns a UTF-8 string for reuse.
@@ -228,6 +644,68 @@
     (func (param $ptr i32) (param $len i32) (result i32))
   )
 
+  ;; Checks whether an interned string ID exists in the string interner.
+  ;; Can be used to validate an ID before writing it with shopify_function_output_new_interned_utf8_str,
+  ;; for example when the ID may have been interned by a different context.
+  ;; Parameters:
+  ;;   - id: i32 ID of the interned string.
+  ;; Returns:
+  ;;   - i32 1 if the ID exists, 0 otherwise.
+  (import "shopify_function_v2" "shopify_function_interned_utf8_str_exists"
+    (func (param $id i32) (result i32))
+  )
+
+  ;; Gets the version of the provider.
+  ;; Useful for logging which provider a function ran against, or adapting to behavior that
+  ;; differs between provider versions.
+  ;; Returns:
+  ;;   - i64 version, with bits 32 to 47 for the major version, bits 16 to 31 for the minor
+  ;;     version, and bits 0 to 15 for the patch version.
+  (import "shopify_function_v2" "shopify_function_provider_version"
+    (func (result i64))
+  )
+
+  ;; Gets a seed derived from the input, which is the same every time the function runs with
+  ;; the same input. Functions must be deterministic, so use this to seed pseudo-random number
+  ;; generators (e.g. for A/B bucketing) instead of reading clocks or random numbers.
+  ;; Returns:
+  ;;   - i64 seed.
+  (import "shopify_function_v2" "shopify_function_deterministic_seed"
+    (func (result i64))
+  )
+
+  ;; Limits the number of reads of the input that look up or traverse values, such as getting a
+  ;; property or an element, or advancing an iterator. Each value read spends one unit, and reads
+  ;; fail with a BudgetExceeded error once the budget is spent, so defensive functions can stop
+  ;; before the fuel limit is reached. Setting the budget again replaces what's left of it.
+  ;; Parameters:
+  ;;   - budget: i32 number of reads left.
+  (import "shopify_function_v2" "shopify_function_set_work_budget"
+    (func (param $budget i32))
+  )
+
+  ;; Starts a call to the host for data it provides lazily, such as currency rates.
+  ;; The call doesn't block; poll it with shopify_function_host_call_poll for its result.
+  ;; Parameters:
+  ;;   - capability: i32 ID of an interned string naming the requested capability.
+  ;;   - argument: i32 ID of an interned string with the argument for the capability.
+  ;; Returns:
+  ;;   - i32 ID of the host call.
+  (import "shopify_function_v2" "shopify_function_host_call_start"
+    (func (param $capability i32) (param $argument i32) (result i32))
+  )
+
+  ;; Polls a host call for its result.
+  ;; Parameters:
+  ;;   - id: i32 ID of the host call, from shopify_function_host_call_start.
+  ;; Returns:
+  ;;   - i64 NanBox value with the result, or an error: HostCallPending if the call should be
+  ;;     polled again later, HostCallUnsupported if the host doesn't support the capability, or
+  ;;     UnknownHostCall if the ID does not exist.
+  (import "shopify_function_v2" "shopify_function_host_call_poll"
+    (func (param $id i32) (result i64))
+  )
+
   ;; Logs a new string output value.
   ;; Used for text values in the logs.
   ;; The string data is copied from WebAssembly memory.
@@ -237,4 +715,33 @@
   (import "shopify_function_v2" "shopify_function_log_new_utf8_str"
     (func (param $ptr i32) (param $len i32))
   )
+
+  ;; Logs a new string output value, like shopify_function_log_new_utf8_str.
+  ;; The logs are a ring buffer, so once they're full, the oldest bytes are dropped to fit new ones.
+  ;; Parameters:
+  ;;   - ptr: i32 pointer to string data in WebAssembly memory.
+  ;;   - len: i32 length of string in bytes.
+  ;; Returns:
+  ;;   - i32 number of bytes dropped from the logs.
+  (import "shopify_function_v2" "shopify_function_log_new_utf8_str_with_dropped_len"
+    (func (param $ptr i32) (param $len i32) (result i32))
+  )
+
+  ;; Logs a message with a level as a structured record, which the host reads separately from the
+  ;; logs after the function finishes.
+  ;; Parameters:
+  ;;   - level: i32 log level (0 = info, 1 = warn, 2 = error).
+  ;;   - ptr: i32 pointer to the message data.
+  ;;   - len: i32 length of the message in bytes.
+  ;; Returns: the number of earlier records dropped to keep the records within their capacity (i32)
+  (import "shopify_function_v2" "shopify_function_log_new_record"
+    (func (param $level i32) (param $ptr i32) (param $len i32) (result i32))
+  )
+
+  ;; Writes each interned string with its ID to the logs, one per line, for debugging.
+  ;; Parameters: none
+  ;; Returns: the number of interned strings (i32)
+  (import "shopify_function_v2" "shopify_function_log_interned_strings"
+    (func (result i32))
+  )
 )
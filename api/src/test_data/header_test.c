@@ -14,10 +14,13 @@ volatile void* imports[] = {
     (void*)shopify_function_input_get_obj_prop,
     (void*)shopify_function_input_get_interned_obj_prop,
     (void*)shopify_function_input_get_at_index,
+    (void*)shopify_function_input_get_array_slice,
     (void*)shopify_function_input_get_obj_key_at_index,
     (void*)shopify_function_output_new_bool,
     (void*)shopify_function_output_new_null,
     (void*)shopify_function_output_new_i32,
+    (void*)shopify_function_output_new_i64,
+    (void*)shopify_function_output_new_u64,
     (void*)shopify_function_output_new_f64,
     (void*)shopify_function_output_new_utf8_str,
     (void*)shopify_function_output_new_interned_utf8_str,
@@ -26,5 +29,49 @@ volatile void* imports[] = {
     (void*)shopify_function_output_new_array,
     (void*)shopify_function_output_finish_array,
     (void*)shopify_function_intern_utf8_str,
-    (void*)shopify_function_log_new_utf8_str
+    (void*)shopify_function_log_new_utf8_str,
+    (void*)shopify_function_log_new_utf8_str_with_dropped_len,
+    (void*)shopify_function_interned_utf8_str_exists,
+    (void*)shopify_function_input_read_utf8_str_with_len,
+    (void*)shopify_function_input_materialize,
+    (void*)shopify_function_input_iter_new,
+    (void*)shopify_function_input_iter_next,
+    (void*)shopify_function_input_iter_key,
+    (void*)shopify_function_input_iter_drop,
+    (void*)shopify_function_input_read_array_elements,
+    (void*)shopify_function_output_new_dynamic_array,
+    (void*)shopify_function_provider_version,
+    (void*)shopify_function_output_discard,
+    (void*)shopify_function_output_checkpoint,
+    (void*)shopify_function_output_rollback,
+    (void*)shopify_function_host_call_start,
+    (void*)shopify_function_host_call_poll,
+    (void*)shopify_function_input_new_shape,
+    (void*)shopify_function_input_read_shape,
+    (void*)shopify_function_log_interned_strings,
+    (void*)shopify_function_input_get_obj_entry_at_index,
+    (void*)shopify_function_input_read_utf8_str_chunk,
+    (void*)shopify_function_input_read_msgpack_with_len,
+    (void*)shopify_function_input_read_bool_bits_with_len,
+    (void*)shopify_function_input_read_utf8_str_alloc,
+    (void*)shopify_function_input_utf8_str_trim_eq,
+    (void*)shopify_function_output_new_dynamic_utf8_str,
+    (void*)shopify_function_output_write_utf8_str_chunk,
+    (void*)shopify_function_output_finish_utf8_str,
+    (void*)shopify_function_output_new_input_value,
+    (void*)shopify_function_deterministic_seed,
+    (void*)shopify_function_set_work_budget,
+    (void*)shopify_function_output_set_skip_nulls,
+    (void*)shopify_function_log_new_record,
+    (void*)shopify_function_input_pin,
+    (void*)shopify_function_input_get_pinned,
+    (void*)shopify_function_input_unpin,
+    (void*)shopify_function_output_new_bytes,
+    (void*)shopify_function_input_get_interned_obj_key_at_index,
+    (void*)shopify_function_output_len,
+    (void*)shopify_function_output_new_msgpack,
+    (void*)shopify_function_dedup_set_new,
+    (void*)shopify_function_dedup_set_insert_interned,
+    (void*)shopify_function_dedup_set_insert_hash,
+    (void*)shopify_function_dedup_set_drop
 };
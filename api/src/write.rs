@@ -2,12 +2,69 @@
 //!
 //! This consists primarily of the `Serialize` trait for writing values to a [`Context`].
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, DefaultHasher, Hash, Hasher};
+use std::marker::PhantomData;
+use std::{fmt, io};
 
 use crate::Context;
+use crate::FixedHashMap;
 use crate::InternedStringId;
+use crate::Value;
 use shopify_function_wasm_api_core::write::WriteResult;
 
+/// Counts how many times each string was written, to intern the ones written more than a threshold.
+pub(crate) struct AutoIntern {
+    threshold: usize,
+    strings: FixedHashMap<Box<str>, AutoInternedString>,
+}
+
+enum AutoInternedString {
+    Counted(usize),
+    Interned(InternedStringId),
+}
+
+impl AutoIntern {
+    fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            strings: FixedHashMap::default(),
+        }
+    }
+
+    /// Record a write of `value`, returning its interned string ID if it should be written as one.
+    fn record(&mut self, value: &str) -> Option<InternedStringId> {
+        let Some(string) = self.strings.get_mut(value) else {
+            if self.threshold == 0 {
+                let id = intern(value);
+                self.strings
+                    .insert(value.into(), AutoInternedString::Interned(id));
+                return Some(id);
+            }
+            self.strings
+                .insert(value.into(), AutoInternedString::Counted(1));
+            return None;
+        };
+        match string {
+            AutoInternedString::Interned(id) => Some(*id),
+            AutoInternedString::Counted(count) if *count < self.threshold => {
+                *count += 1;
+                None
+            }
+            AutoInternedString::Counted(_) => {
+                let id = intern(value);
+                *string = AutoInternedString::Interned(id);
+                Some(id)
+            }
+        }
+    }
+}
+
+fn intern(value: &str) -> InternedStringId {
+    let id = unsafe { crate::shopify_function_intern_utf8_str(value.as_ptr(), value.len()) };
+    InternedStringId::new(id)
+}
+
 /// An error that can occur when writing a value.
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
@@ -38,13 +95,37 @@ pub enum Error {
     /// The value is not an array, but was expected to be one based on the current context.
     #[error("Not an array")]
     NotAnArray,
+    /// The interned string ID is not known to the string interner, for example because it was interned
+    /// with a different context.
+    #[error("Unknown interned string")]
+    UnknownInternedString,
+    /// The checkpoint is not known, because the output was rolled back to an earlier checkpoint or
+    /// discarded after it was created.
+    #[error("Unknown checkpoint")]
+    UnknownCheckpoint,
+    /// The value is not a string being written in chunks, but was expected to be one, e.g. when
+    /// attempting to finish a string.
+    #[error("Not a string")]
+    NotAString,
+    /// The output can't be discarded, because the host already read part of it while the function
+    /// was running.
+    #[error("Output already read")]
+    OutputAlreadyRead,
+    /// The input value written to the output could not be read, for example because it's an error
+    /// value or a missing property.
+    #[error("Invalid input value")]
+    InvalidInputValue,
+    /// The MessagePack fragment written to the output is not exactly one complete value, for
+    /// example because it's truncated or has trailing bytes.
+    #[error("Invalid MessagePack")]
+    InvalidMsgpack,
     /// An unknown error occurred. This occurs when a new error code is added that this version of the API does not know about.
     #[error("Unknown error")]
     Unknown,
 }
 
-fn map_result(result: usize) -> Result<(), Error> {
-    match WriteResult::from_repr(result) {
+pub(crate) fn map_result(result: usize) -> Result<(), Error> {
+    match u32::try_from(result).ok().and_then(WriteResult::from_repr) {
         Some(WriteResult::Ok) => Ok(()),
         Some(WriteResult::IoError) => Err(Error::IoError),
         Some(WriteResult::ExpectedKey) => Err(Error::ExpectedKey),
@@ -54,6 +135,12 @@ fn map_result(result: usize) -> Result<(), Error> {
         Some(WriteResult::ValueNotFinished) => Err(Error::ValueNotFinished),
         Some(WriteResult::ArrayLengthError) => Err(Error::ArrayLengthError),
         Some(WriteResult::NotAnArray) => Err(Error::NotAnArray),
+        Some(WriteResult::UnknownInternedString) => Err(Error::UnknownInternedString),
+        Some(WriteResult::UnknownCheckpoint) => Err(Error::UnknownCheckpoint),
+        Some(WriteResult::NotAString) => Err(Error::NotAString),
+        Some(WriteResult::OutputAlreadyRead) => Err(Error::OutputAlreadyRead),
+        Some(WriteResult::InvalidInputValue) => Err(Error::InvalidInputValue),
+        Some(WriteResult::InvalidMsgpack) => Err(Error::InvalidMsgpack),
         None => Err(Error::Unknown),
     }
 }
@@ -74,55 +161,815 @@ impl Context {
         map_result(unsafe { crate::shopify_function_output_new_i32(value) })
     }
 
+    /// Write an i64 value.
+    ///
+    /// Integers outside of the range of `i32` are written exactly, rather than rounded like they
+    /// would be when written as an f64, so this suits 64-bit IDs.
+    pub fn write_i64(&mut self, value: i64) -> Result<(), Error> {
+        map_result(unsafe { crate::shopify_function_output_new_i64(value) })
+    }
+
+    /// Write a u64 value. See [`Context::write_i64`].
+    pub fn write_u64(&mut self, value: u64) -> Result<(), Error> {
+        map_result(unsafe { crate::shopify_function_output_new_u64(value) })
+    }
+
     /// Write a f64 value.
     pub fn write_f64(&mut self, value: f64) -> Result<(), Error> {
         map_result(unsafe { crate::shopify_function_output_new_f64(value) })
     }
 
+    /// Write a value read from the input.
+    ///
+    /// The value is copied as it's encoded in the input, with one call to the provider, so arrays
+    /// and objects are written without reading them value by value. Strings from the input can be
+    /// written as object keys.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let mut context = Context::new_with_input(serde_json::json!({"line": {"quantity": 2}}));
+    /// let line = context.input_get().unwrap().get_obj_prop("line");
+    /// context
+    ///     .write_object(
+    ///         |context| {
+    ///             context.write_utf8_str("original")?;
+    ///             context.write_input_value(&line)?;
+    ///             context.write_utf8_str("approved")?;
+    ///             context.write_bool(true)
+    ///         },
+    ///         2,
+    ///     )
+    ///     .unwrap();
+    /// let output = context.finalize_output_and_return().unwrap();
+    /// assert_eq!(
+    ///     output,
+    ///     serde_json::json!({"original": {"quantity": 2}, "approved": true})
+    /// );
+    /// ```
+    pub fn write_input_value(&mut self, value: &Value) -> Result<(), Error> {
+        map_result(unsafe {
+            crate::shopify_function_output_new_input_value(value.nan_box.to_bits())
+        })
+    }
+
     /// Write a UTF-8 string value.
+    ///
+    /// If an auto-intern threshold was set with [`Context::set_auto_intern_threshold`], strings
+    /// written more times than the threshold are interned and written as interned strings.
     pub fn write_utf8_str(&mut self, value: &str) -> Result<(), Error> {
+        if let Some(auto_intern) = &mut self.auto_intern {
+            if let Some(id) = auto_intern.record(value) {
+                return self.write_interned_utf8_str(id);
+            }
+        }
         map_result(unsafe {
             crate::shopify_function_output_new_utf8_str(value.as_ptr(), value.len())
         })
     }
 
+    /// Automatically intern strings written with [`Context::write_utf8_str`] more than
+    /// `threshold` times, so that later writes use the interned string instead of copying it to
+    /// the output. Passing `None` turns this off.
+    ///
+    /// This speeds up serializers that write the same object keys many times without managing
+    /// interned string IDs themselves. It's off by default because every distinct string written
+    /// while it's on is counted, which costs a lookup per string and memory for each one, so it
+    /// only pays off when most strings written are repeated, like the keys of many similar objects.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let mut context = Context::new_with_input(serde_json::json!(null));
+    /// context.set_auto_intern_threshold(Some(2));
+    /// ```
+    pub fn set_auto_intern_threshold(&mut self, threshold: Option<usize>) {
+        self.auto_intern = threshold.map(|threshold| Box::new(AutoIntern::new(threshold)));
+    }
+
+    /// Leave out the fields of objects whose value is null, such as `None` values of `Option`
+    /// fields, so GraphQL outputs don't have to check every optional field to omit it.
+    ///
+    /// It applies to objects started while it's on, including those written by nested
+    /// [`Serialize`] implementations, and to nulls written while it's still on. The key written
+    /// before a skipped null is removed along with it, and the object's length is reduced to the
+    /// number of fields that remain, so it's still given as the number of fields before skipping.
+    /// Use [`Context::write_null_field`] to write a null field that isn't skipped.
+    ///
+    /// Nulls written after a [`Context::checkpoint`] that was created after their key are kept,
+    /// as rolling back to it would restore the key.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::{Context, Serialize};
+    ///
+    /// let mut context = Context::new_with_input(serde_json::json!(null));
+    /// context.set_skip_nulls(true);
+    /// let note: Option<&str> = None;
+    /// context
+    ///     .write_object(
+    ///         |context| {
+    ///             context.write_utf8_str("note")?;
+    ///             note.serialize(context)?;
+    ///             context.write_utf8_str("quantity")?;
+    ///             context.write_i32(2)?;
+    ///             context.write_null_field("discount")
+    ///         },
+    ///         3,
+    ///     )
+    ///     .unwrap();
+    /// let output = context.finalize_output_and_return().unwrap();
+    /// assert_eq!(output, serde_json::json!({ "quantity": 2, "discount": null }));
+    /// ```
+    pub fn set_skip_nulls(&mut self, skip: bool) {
+        unsafe { crate::shopify_function_output_set_skip_nulls(skip as u32) };
+    }
+
+    /// Write a key and a null value, even when nulls are being skipped with
+    /// [`Context::set_skip_nulls`], for fields that have to be null explicitly, such as to clear
+    /// a value.
+    pub fn write_null_field(&mut self, key: &str) -> Result<(), Error> {
+        self.write_utf8_str(key)?;
+        let skip = unsafe { crate::shopify_function_output_set_skip_nulls(0) };
+        let result = self.write_null();
+        unsafe { crate::shopify_function_output_set_skip_nulls(skip) };
+        result
+    }
+
+    /// Write binary data, such as a hash, as a MessagePack `bin` value rather than a base64
+    /// string. Binary data can't be an object key.
+    pub fn write_bytes(&mut self, value: &[u8]) -> Result<(), Error> {
+        map_result(unsafe { crate::shopify_function_output_new_bytes(value.as_ptr(), value.len()) })
+    }
+
+    /// Write a MessagePack fragment serialized ahead of time, such as static metadata, as one
+    /// value. The fragment is copied to the output as it is, after checking that it's exactly one
+    /// complete value. Like any other value, a string fragment can be an object key.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// // `{"version": 2}`
+    /// const METADATA: &[u8] = &[0x81, 0xa7, b'v', b'e', b'r', b's', b'i', b'o', b'n', 0x02];
+    ///
+    /// let mut context = Context::new_with_input(serde_json::json!({}));
+    /// context
+    ///     .write_object(
+    ///         |context| {
+    ///             context.write_utf8_str("metadata")?;
+    ///             context.write_raw_msgpack(METADATA)
+    ///         },
+    ///         1,
+    ///     )
+    ///     .unwrap();
+    /// let output = context.finalize_output_and_return().unwrap();
+    /// assert_eq!(output, serde_json::json!({ "metadata": { "version": 2 } }));
+    /// ```
+    pub fn write_raw_msgpack(&mut self, fragment: &[u8]) -> Result<(), Error> {
+        map_result(unsafe {
+            crate::shopify_function_output_new_msgpack(fragment.as_ptr(), fragment.len())
+        })
+    }
+
     /// Write an interned UTF-8 string value.
     pub fn write_interned_utf8_str(&mut self, id: InternedStringId) -> Result<(), Error> {
         map_result(unsafe { crate::shopify_function_output_new_interned_utf8_str(id.as_usize()) })
     }
 
+    /// Start writing a UTF-8 string value in chunks, using the returned [`StringSink`], which
+    /// implements [`std::fmt::Write`] and [`std::io::Write`].
+    ///
+    /// The chunks are copied straight to the output, so strings built by formatting or templating
+    /// don't need to be collected into a `String` first. `len_hint` is the expected length of the
+    /// string in bytes, which is reserved in the output up front. The string is finished when the
+    /// sink is dropped, or by calling [`StringSink::finish`].
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    /// use std::fmt::Write;
+    ///
+    /// let mut context = Context::new_with_input(serde_json::json!({}));
+    /// let mut sink = context.string_sink(16).unwrap();
+    /// write!(sink, "<b>{}</b> x {}", "Hat", 2).unwrap();
+    /// sink.finish().unwrap();
+    /// let output = context.finalize_output_and_return().unwrap();
+    /// assert_eq!(output, serde_json::json!("<b>Hat</b> x 2"));
+    /// ```
+    pub fn string_sink(&mut self, len_hint: usize) -> Result<StringSink<'_>, Error> {
+        map_result(unsafe { crate::shopify_function_output_new_dynamic_utf8_str(len_hint) })?;
+        Ok(StringSink {
+            _context: self,
+            partial_char: Vec::new(),
+            finished: false,
+        })
+    }
+
     /// Write an object. You must provide the exact number of key-value pairs you will write.
-    pub fn write_object<F: FnOnce(&mut Self) -> Result<(), Error>>(
+    ///
+    /// The value returned by `f` is returned once the object is finished, so it can compute
+    /// something while writing, such as a count.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let mut context = Context::new_with_input(serde_json::json!(null));
+    /// let written = context
+    ///     .write_object(
+    ///         |context| {
+    ///             context.write_utf8_str("key")?;
+    ///             context.write_bool(true)?;
+    ///             Ok(1)
+    ///         },
+    ///         1,
+    ///     )
+    ///     .unwrap();
+    /// assert_eq!(written, 1);
+    /// assert_eq!(
+    ///     context.finalize_output_and_return().unwrap(),
+    ///     serde_json::json!({ "key": true })
+    /// );
+    /// ```
+    pub fn write_object<R, F: FnOnce(&mut Self) -> Result<R, Error>>(
         &mut self,
         f: F,
         len: usize,
-    ) -> Result<(), Error> {
+    ) -> Result<R, Error> {
         map_result(unsafe { crate::shopify_function_output_new_object(len) })?;
-        f(self)?;
-        map_result(unsafe { crate::shopify_function_output_finish_object() })
+        let value = f(self)?;
+        map_result(unsafe { crate::shopify_function_output_finish_object() })?;
+        Ok(value)
     }
 
-    /// Write an array. You must provide the exact number of values you will write.
-    pub fn write_array<F: FnOnce(&mut Self) -> Result<(), Error>>(
+    /// Write an object using an [`ObjectWriter`], which writes each key-value pair as a single field.
+    /// You must provide the exact number of fields you will write. The value returned by `f` is
+    /// returned once the object is finished.
+    pub fn write_object_fields<R, F: FnOnce(&mut ObjectWriter) -> Result<R, Error>>(
         &mut self,
         f: F,
         len: usize,
-    ) -> Result<(), Error> {
+    ) -> Result<R, Error> {
+        self.write_object(|context| f(&mut ObjectWriter { context }), len)
+    }
+
+    /// Write an array. You must provide the exact number of values you will write. The value
+    /// returned by `f` is returned once the array is finished.
+    pub fn write_array<R, F: FnOnce(&mut Self) -> Result<R, Error>>(
+        &mut self,
+        f: F,
+        len: usize,
+    ) -> Result<R, Error> {
         map_result(unsafe { crate::shopify_function_output_new_array(len) })?;
-        f(self)?;
-        map_result(unsafe { crate::shopify_function_output_finish_array() })
+        let value = f(self)?;
+        map_result(unsafe { crate::shopify_function_output_finish_array() })?;
+        Ok(value)
+    }
+
+    /// Write an array with a value for each item of an iterator, using the iterator's length as the
+    /// array's length. This avoids collecting items into a `Vec` only to know how many there are.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let mut context = Context::new_with_input(serde_json::json!({}));
+    /// let quantities = [1, 2, 3];
+    /// context.write_array_from_iter(quantities.iter().map(|quantity| quantity * 2)).unwrap();
+    /// let output = context.finalize_output_and_return().unwrap();
+    /// assert_eq!(output, serde_json::json!([2, 4, 6]));
+    /// ```
+    pub fn write_array_from_iter<I>(&mut self, iter: I) -> Result<(), Error>
+    where
+        I: IntoIterator,
+        I::IntoIter: ExactSizeIterator,
+        I::Item: Serialize,
+    {
+        let iter = iter.into_iter();
+        let len = iter.len();
+        self.write_array(
+            |context| {
+                iter.into_iter()
+                    .try_for_each(|item| item.serialize(context))
+            },
+            len,
+        )
+    }
+
+    /// Write an object with a key-value pair for each item of an iterator, using the iterator's
+    /// length as the object's length.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let mut context = Context::new_with_input(serde_json::json!({}));
+    /// let lines = ["a", "b"];
+    /// context.write_object_from_iter(lines.iter().map(|id| (id, id.len() as i32))).unwrap();
+    /// let output = context.finalize_output_and_return().unwrap();
+    /// assert_eq!(output, serde_json::json!({ "a": 1, "b": 1 }));
+    /// ```
+    pub fn write_object_from_iter<I, K, V>(&mut self, iter: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        I::IntoIter: ExactSizeIterator,
+        K: AsRef<str>,
+        V: Serialize,
+    {
+        let iter = iter.into_iter();
+        let len = iter.len();
+        self.write_object(
+            |context| {
+                iter.into_iter().try_for_each(|(key, value)| {
+                    context.write_utf8_str(key.as_ref())?;
+                    value.serialize(context)
+                })
+            },
+            len,
+        )
+    }
+
+    /// Write an array using an [`ArrayWriter`], without providing the number of values up front.
+    ///
+    /// This is useful when elements are filtered out while writing, as it avoids a separate pass to
+    /// count them. The length is filled in when the array is finished.
+    pub fn write_dynamic_array<R, F: FnOnce(&mut ArrayWriter) -> Result<R, Error>>(
+        &mut self,
+        f: F,
+    ) -> Result<R, Error> {
+        map_result(unsafe { crate::shopify_function_output_new_dynamic_array() })?;
+        let value = f(&mut ArrayWriter { context: self })?;
+        map_result(unsafe { crate::shopify_function_output_finish_array() })?;
+        Ok(value)
+    }
+
+    /// Record the current point in the output, so that anything written after it can be removed
+    /// with [`Context::rollback_to`].
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::{Context, write::Error};
+    ///
+    /// let mut context = Context::new_with_input(serde_json::json!({}));
+    /// context.write_dynamic_array(|array| {
+    ///     for quantity in [1, 2, 3] {
+    ///         let checkpoint = array.context().checkpoint();
+    ///         let result = array.context().write_object(|ctx| {
+    ///             ctx.write_utf8_str("quantity")?;
+    ///             ctx.write_i32(quantity)?;
+    ///             if quantity == 2 {
+    ///                 // a late validation failure
+    ///                 return Err(Error::IoError);
+    ///             }
+    ///             Ok(())
+    ///         }, 1);
+    ///         if result.is_err() {
+    ///             array.context().rollback_to(checkpoint)?;
+    ///         }
+    ///     }
+    ///     Ok(())
+    /// }).unwrap();
+    /// let output = context.finalize_output_and_return().unwrap();
+    /// assert_eq!(output, serde_json::json!([{ "quantity": 1 }, { "quantity": 3 }]));
+    /// ```
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        Checkpoint(unsafe { crate::shopify_function_output_checkpoint() })
+    }
+
+    /// Remove everything written since `checkpoint` was recorded, including the start of any
+    /// objects and arrays, so the output is as it was at that point.
+    ///
+    /// The checkpoint can be rolled back to again, but checkpoints recorded after it can't.
+    pub fn rollback_to(&mut self, checkpoint: Checkpoint) -> Result<(), Error> {
+        map_result(unsafe { crate::shopify_function_output_rollback(checkpoint.0) })
+    }
+
+    /// The number of bytes of output written so far, in its MessagePack encoding.
+    ///
+    /// This lets functions whose output may approach the platform's output size limit leave out
+    /// optional detail, such as messages, once it's nearly reached, rather than fail when the
+    /// output is finalized. Unfinished objects that skip null fields may shrink when they're
+    /// finished, so the length can be slightly more than the final output's.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// const DETAIL_LIMIT: usize = 32;
+    ///
+    /// let mut context = Context::new_with_input(serde_json::json!(null));
+    /// context.write_array(|array| {
+    ///     for i in 0..3 {
+    ///         array.write_object(|line| {
+    ///             line.write_utf8_str("quantity")?;
+    ///             line.write_i32(i)?;
+    ///             line.write_utf8_str("message")?;
+    ///             if line.output_len() < DETAIL_LIMIT {
+    ///                 line.write_utf8_str("in stock")
+    ///             } else {
+    ///                 line.write_null()
+    ///             }
+    ///         }, 2)?;
+    ///     }
+    ///     Ok(())
+    /// }, 3).unwrap();
+    /// let output = context.finalize_output_and_return().unwrap();
+    /// assert_eq!(output, serde_json::json!([
+    ///     { "quantity": 0, "message": "in stock" },
+    ///     { "quantity": 1, "message": null },
+    ///     { "quantity": 2, "message": null },
+    /// ]));
+    /// ```
+    pub fn output_len(&self) -> usize {
+        unsafe { crate::shopify_function_output_len() }
+    }
+
+    /// Create a set that keeps track of keys already seen, so functions emitting operations keyed
+    /// by ID can skip duplicates without building a `HashSet` of `String`s. The set is kept by
+    /// the provider, and removed when the returned [`DedupSet`] is dropped.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let mut context = Context::new_with_input(serde_json::json!({}));
+    /// let mut seen = context.dedup_set();
+    /// let targets = ["gid://shopify/CartLine/1", "gid://shopify/CartLine/2", "gid://shopify/CartLine/1"];
+    /// let unique: Vec<_> = targets.into_iter().filter(|id| seen.insert_str(id)).collect();
+    /// assert_eq!(unique, ["gid://shopify/CartLine/1", "gid://shopify/CartLine/2"]);
+    /// ```
+    pub fn dedup_set(&self) -> DedupSet {
+        DedupSet {
+            id: unsafe { crate::shopify_function_dedup_set_new() },
+            _not_send_sync: PhantomData,
+        }
+    }
+
+    /// Discard any output written so far, including unfinished objects and arrays, and write
+    /// `error` as the only output, in the form `{ "errors": [error] }`.
+    ///
+    /// The output is complete afterwards, so nothing else can be written. This gives functions a
+    /// consistent way to fail part-way through writing their output.
+    ///
+    /// # Example
+    /// ```rust
+    /// use shopify_function_wasm_api::Context;
+    ///
+    /// let mut context = Context::new_with_input(serde_json::json!({}));
+    /// let result = context.write_object(|ctx| {
+    ///     ctx.write_utf8_str("operations")?;
+    ///     ctx.write_array(|ctx| ctx.write_null(), 2)
+    /// }, 1);
+    /// context.fail_with(result.unwrap_err()).unwrap();
+    /// let output = context.finalize_output_and_return().unwrap();
+    /// assert_eq!(output, serde_json::json!({
+    ///     "errors": [{ "localizedMessage": "Array length error", "target": "$" }]
+    /// }));
+    /// ```
+    pub fn fail_with(&mut self, error: impl Into<FunctionError>) -> Result<(), Error> {
+        map_result(unsafe { crate::shopify_function_output_discard() })?;
+        let error = error.into();
+        self.write_object(
+            |ctx| {
+                ctx.write_utf8_str("errors")?;
+                ctx.write_array(|ctx| error.serialize(ctx), 1)
+            },
+            1,
+        )
     }
 
     #[cfg(not(target_family = "wasm"))]
     /// Finalize the output and return the serialized value as a `serde_json::Value`.
     /// This is only available in non-Wasm targets, and therefore only recommended for use in tests.
+    ///
+    ///
+    /// To snapshot the output as JSON text, print it with [`codec::to_json_string`](crate::codec::to_json_string),
+    /// which formats numbers as the platform does when it converts the output to JSON.
     pub fn finalize_output_and_return(self) -> Result<serde_json::Value, Error> {
+        // an unfinished value is reported as an error here, rather than by dropping the context
+        let _ = std::mem::ManuallyDrop::new(self);
         let (result, bytes) = shopify_function_provider::write::shopify_function_output_finalize_and_return_msgpack_bytes();
         map_result(result as usize)
             .and_then(|_| rmp_serde::from_slice(&bytes).map_err(|_| Error::IoError))
     }
 }
 
+/// A point in the output recorded by [`Context::checkpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint(usize);
+
+/// An error in the shape functions use to report errors in their output, e.g. validation errors:
+/// `{ "localizedMessage": ..., "target": ... }`.
+///
+/// Errors from reading input and writing output can be converted into a `FunctionError`, using
+/// their description as the message. See [`Context::fail_with`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionError {
+    localized_message: String,
+    target: String,
+}
+
+impl FunctionError {
+    /// The target of errors that are not about a particular part of the input.
+    pub const ROOT_TARGET: &'static str = "$";
+
+    /// Create an error with a message, targeting [`FunctionError::ROOT_TARGET`].
+    pub fn new(localized_message: impl Into<String>) -> Self {
+        Self {
+            localized_message: localized_message.into(),
+            target: Self::ROOT_TARGET.to_string(),
+        }
+    }
+
+    /// Set the path of the part of the input the error is about, e.g. `$.cart`.
+    pub fn with_target(mut self, target: impl Into<String>) -> Self {
+        self.target = target.into();
+        self
+    }
+
+    /// The message of the error.
+    pub fn localized_message(&self) -> &str {
+        &self.localized_message
+    }
+
+    /// The path of the part of the input the error is about.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+}
+
+impl Serialize for FunctionError {
+    fn serialize(&self, context: &mut Context) -> Result<(), Error> {
+        context.write_object_fields(
+            |object| {
+                object.field("localizedMessage", self.localized_message.as_str())?;
+                object.field("target", self.target.as_str())
+            },
+            2,
+        )
+    }
+}
+
+impl From<Error> for FunctionError {
+    fn from(error: Error) -> Self {
+        Self::new(error.to_string())
+    }
+}
+
+impl From<crate::read::Error> for FunctionError {
+    fn from(error: crate::read::Error) -> Self {
+        Self::new(error.to_string())
+    }
+}
+
+impl From<&str> for FunctionError {
+    fn from(localized_message: &str) -> Self {
+        Self::new(localized_message)
+    }
+}
+
+impl From<String> for FunctionError {
+    fn from(localized_message: String) -> Self {
+        Self::new(localized_message)
+    }
+}
+
+/// A writer for the fields of an object, created by [`Context::write_object_fields`].
+///
+/// # Example
+/// ```rust
+/// use shopify_function_wasm_api::Context;
+///
+/// let mut context = Context::new_with_input(serde_json::json!({}));
+/// let value_key = context.intern_utf8_str("value");
+/// context.write_object_fields(|object| {
+///     object.interned_field(value_key, &1)?;
+///     object.field("other", "a")
+/// }, 2).unwrap();
+/// let output = context.finalize_output_and_return().unwrap();
+/// assert_eq!(output, serde_json::json!({ "value": 1, "other": "a" }));
+/// ```
+pub struct ObjectWriter<'a> {
+    context: &'a mut Context,
+}
+
+impl ObjectWriter<'_> {
+    /// Write a field with a UTF-8 string key.
+    pub fn field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        self.context.write_utf8_str(key)?;
+        value.serialize(self.context)
+    }
+
+    /// Write a field with an interned string key.
+    ///
+    /// Returns [`Error::UnknownInternedString`] without writing anything if the ID is not known to the
+    /// string interner, which the provider checks as it writes the key.
+    pub fn interned_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: InternedStringId,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.context.write_interned_utf8_str(key)?;
+        value.serialize(self.context)
+    }
+
+    /// Get the underlying context, for writing keys and values separately.
+    pub fn context(&mut self) -> &mut Context {
+        self.context
+    }
+}
+
+/// A writer for the values of an array, created by [`Context::write_dynamic_array`].
+///
+/// # Example
+/// ```rust
+/// use shopify_function_wasm_api::Context;
+///
+/// let mut context = Context::new_with_input(serde_json::json!({}));
+/// let quantities = [Some(1), None, Some(3)];
+/// context.write_dynamic_array(|array| {
+///     for quantity in &quantities {
+///         array.push_some(quantity.as_ref())?;
+///     }
+///     array.push(&4)
+/// }).unwrap();
+/// let output = context.finalize_output_and_return().unwrap();
+/// assert_eq!(output, serde_json::json!([1, 3, 4]));
+/// ```
+pub struct ArrayWriter<'a> {
+    context: &'a mut Context,
+}
+
+impl ArrayWriter<'_> {
+    /// Write a value.
+    pub fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(self.context)
+    }
+
+    /// Write a value if there is one. Nothing is written for `None`, unlike serializing an
+    /// [`Option`], which writes `null`.
+    pub fn push_some<T: Serialize + ?Sized>(&mut self, value: Option<&T>) -> Result<(), Error> {
+        match value {
+            Some(value) => self.push(value),
+            None => Ok(()),
+        }
+    }
+
+    /// Get the underlying context, for writing values directly.
+    pub fn context(&mut self) -> &mut Context {
+        self.context
+    }
+}
+
+/// A writer for a UTF-8 string value, created by [`Context::string_sink`].
+///
+/// Bytes written with [`std::io::Write`] must be valid UTF-8, although a character can be split
+/// across writes.
+pub struct StringSink<'a> {
+    // borrowed so nothing else is written until the string is finished
+    _context: &'a mut Context,
+    /// The start of a character whose remaining bytes haven't been written yet.
+    partial_char: Vec<u8>,
+    finished: bool,
+}
+
+impl StringSink<'_> {
+    /// Finish the string.
+    ///
+    /// This also happens when the sink is dropped, but finishing it explicitly returns
+    /// [`Error::IoError`] if the bytes written ended part-way through a character, which is
+    /// left out of the string.
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.finished = true;
+        map_result(unsafe { crate::shopify_function_output_finish_utf8_str() })?;
+        if self.partial_char.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::IoError)
+        }
+    }
+}
+
+impl Drop for StringSink<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            unsafe { crate::shopify_function_output_finish_utf8_str() };
+        }
+    }
+}
+
+fn write_utf8_str_chunk(chunk: &str) -> Result<(), Error> {
+    if chunk.is_empty() {
+        return Ok(());
+    }
+    map_result(unsafe {
+        crate::shopify_function_output_write_utf8_str_chunk(chunk.as_ptr(), chunk.len())
+    })
+}
+
+fn invalid_utf8() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "stream did not contain valid UTF-8",
+    )
+}
+
+impl fmt::Write for StringSink<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if !self.partial_char.is_empty() {
+            return Err(fmt::Error);
+        }
+        write_utf8_str_chunk(s).map_err(|_| fmt::Error)
+    }
+}
+
+impl io::Write for StringSink<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+        // complete a character split across writes before writing the rest
+        while !self.partial_char.is_empty() {
+            let Some((&byte, tail)) = rest.split_first() else {
+                return Ok(buf.len());
+            };
+            rest = tail;
+            self.partial_char.push(byte);
+            match std::str::from_utf8(&self.partial_char) {
+                Ok(char) => {
+                    write_utf8_str_chunk(char).map_err(io::Error::other)?;
+                    self.partial_char.clear();
+                }
+                Err(error) if error.error_len().is_none() => {}
+                Err(_) => return Err(invalid_utf8()),
+            }
+        }
+        let (valid, partial_char) = match std::str::from_utf8(rest) {
+            Ok(valid) => (valid, &[][..]),
+            Err(error) if error.error_len().is_none() => {
+                let (valid, partial_char) = rest.split_at(error.valid_up_to());
+                // SAFETY: the bytes up to `valid_up_to` are valid UTF-8
+                (
+                    unsafe { std::str::from_utf8_unchecked(valid) },
+                    partial_char,
+                )
+            }
+            Err(_) => return Err(invalid_utf8()),
+        };
+        write_utf8_str_chunk(valid).map_err(io::Error::other)?;
+        self.partial_char.extend_from_slice(partial_char);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A set of keys kept by the provider, created by [`Context::dedup_set`].
+///
+/// Keys are interned strings, or hashes of any other value. Each insert returns whether the key
+/// is new, so duplicates can be skipped as they're found.
+pub struct DedupSet {
+    id: usize,
+    /// Opts out of `Send` and `Sync`, like [`Value`].
+    _not_send_sync: PhantomData<*const ()>,
+}
+
+impl DedupSet {
+    /// Insert an interned string, returning whether a string with the same bytes wasn't in the
+    /// set. IDs that weren't interned with this context are always considered new, so nothing is
+    /// skipped by mistake.
+    pub fn insert_interned(&mut self, id: InternedStringId) -> bool {
+        unsafe { crate::shopify_function_dedup_set_insert_interned(self.id, id.as_usize()) != 0 }
+    }
+
+    /// Insert a string that isn't interned, returning whether it wasn't in the set. It's inserted
+    /// by its hash, like [`DedupSet::insert_hash`], rather than copied to the provider, so it's a
+    /// different key than the same string inserted with [`DedupSet::insert_interned`].
+    pub fn insert_str(&mut self, s: &str) -> bool {
+        self.insert_hash(s)
+    }
+
+    /// Insert the hash of `key`, such as a tuple of IDs, returning whether it wasn't in the set.
+    ///
+    /// Keys are compared by their 64-bit hash alone, so distinct keys whose hashes collide are
+    /// considered duplicates, which is vanishingly unlikely.
+    pub fn insert_hash<T: Hash + ?Sized>(&mut self, key: &T) -> bool {
+        // the default hasher has fixed keys, so equal keys always have the same hash
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        unsafe { crate::shopify_function_dedup_set_insert_hash(self.id, hasher.finish()) != 0 }
+    }
+}
+
+impl Drop for DedupSet {
+    fn drop(&mut self) {
+        unsafe { crate::shopify_function_dedup_set_drop(self.id) };
+    }
+}
+
 /// A trait for types that can be serialized.
 ///
 /// # Example
@@ -160,6 +1007,12 @@ impl Serialize for bool {
     }
 }
 
+impl<T: Serialize + ?Sized> Serialize for &T {
+    fn serialize(&self, context: &mut Context) -> Result<(), Error> {
+        (**self).serialize(context)
+    }
+}
+
 impl Serialize for () {
     fn serialize(&self, context: &mut Context) -> Result<(), Error> {
         context.write_null()
@@ -172,6 +1025,18 @@ impl Serialize for i32 {
     }
 }
 
+impl Serialize for i64 {
+    fn serialize(&self, context: &mut Context) -> Result<(), Error> {
+        context.write_i64(*self)
+    }
+}
+
+impl Serialize for u64 {
+    fn serialize(&self, context: &mut Context) -> Result<(), Error> {
+        context.write_u64(*self)
+    }
+}
+
 impl Serialize for f64 {
     fn serialize(&self, context: &mut Context) -> Result<(), Error> {
         context.write_f64(*self)
@@ -218,7 +1083,7 @@ impl<T: Serialize> Serialize for [T] {
     }
 }
 
-impl<K: AsRef<str>, V: Serialize> Serialize for HashMap<K, V> {
+impl<K: AsRef<str>, V: Serialize, S: BuildHasher> Serialize for HashMap<K, V, S> {
     fn serialize(&self, context: &mut Context) -> Result<(), Error> {
         context.write_object(
             |context| {
@@ -233,6 +1098,129 @@ impl<K: AsRef<str>, V: Serialize> Serialize for HashMap<K, V> {
     }
 }
 
+/// Serialized with keys in sorted order, so unlike a [`HashMap`], the output is deterministic.
+impl<K: AsRef<str>, V: Serialize> Serialize for BTreeMap<K, V> {
+    fn serialize(&self, context: &mut Context) -> Result<(), Error> {
+        context.write_object(
+            |context| {
+                for (key, value) in self {
+                    key.as_ref().serialize(context)?;
+                    value.serialize(context)?;
+                }
+                Ok(())
+            },
+            self.len(),
+        )
+    }
+}
+
+/// A map with interned string keys, serialized as an object with keys in the order they were first
+/// inserted.
+///
+/// Keys are written by their interned IDs rather than copied into the output, which makes this
+/// faster than a [`HashMap`] or [`BTreeMap`] for map-shaped output whose keys come from a known set,
+/// such as attribute names. Lookups are linear, so it's intended for small maps.
+///
+/// Maps can also be read from objects in the input, whose keys are interned by the provider rather
+/// than copied, so reading many objects with the same keys copies each key only once.
+///
+/// # Example
+/// ```rust
+/// use shopify_function_wasm_api::{Context, Serialize, write::InternedKeyMap};
+///
+/// let mut context = Context::new_with_input(serde_json::json!({}));
+/// let mut attributes = InternedKeyMap::new();
+/// attributes.insert(context.intern_utf8_str("size"), "M".to_string());
+/// attributes.insert(context.intern_utf8_str("color"), "red".to_string());
+/// attributes.serialize(&mut context).unwrap();
+/// let output = context.finalize_output_and_return().unwrap();
+/// assert_eq!(output, serde_json::json!({ "size": "M", "color": "red" }));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct InternedKeyMap<V> {
+    entries: Vec<(InternedStringId, V)>,
+}
+
+impl<V> InternedKeyMap<V> {
+    /// Create an empty map.
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Create an empty map with space for at least `capacity` entries.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Insert a value, returning the value it replaced if the key was already present. A replaced
+    /// value keeps its key's original position.
+    pub fn insert(&mut self, key: InternedStringId, value: V) -> Option<V> {
+        match self.entries.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, existing)) => Some(std::mem::replace(existing, value)),
+            None => {
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    /// Get the value for a key.
+    pub fn get(&self, key: InternedStringId) -> Option<&V> {
+        self.entries
+            .iter()
+            .find_map(|(k, value)| (*k == key).then_some(value))
+    }
+
+    /// The number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the map has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over the entries in the order they were first inserted.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (InternedStringId, &V)> {
+        self.entries.iter().map(|(key, value)| (*key, value))
+    }
+}
+
+impl<V> Default for InternedKeyMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> FromIterator<(InternedStringId, V)> for InternedKeyMap<V> {
+    fn from_iter<I: IntoIterator<Item = (InternedStringId, V)>>(iter: I) -> Self {
+        let mut map = Self::new();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+impl<V: Serialize> Serialize for InternedKeyMap<V> {
+    fn serialize(&self, context: &mut Context) -> Result<(), Error> {
+        context.write_object_fields(
+            |object| {
+                for (key, value) in &self.entries {
+                    object.interned_field(*key, value)?;
+                }
+                Ok(())
+            },
+            self.len(),
+        )
+    }
+}
+
 impl<T: Serialize> Serialize for Option<T> {
     fn serialize(&self, context: &mut Context) -> Result<(), Error> {
         match self {
@@ -242,6 +1230,13 @@ impl<T: Serialize> Serialize for Option<T> {
     }
 }
 
+/// Values read from the input are copied to the output, see [`Context::write_input_value`].
+impl Serialize for Value {
+    fn serialize(&self, context: &mut Context) -> Result<(), Error> {
+        context.write_input_value(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -276,6 +1271,54 @@ mod tests {
             });
     }
 
+    #[test]
+    fn test_i64_and_u64_serialize() {
+        [
+            0,
+            -1,
+            i64::from(i32::MIN) - 1,
+            (1 << 53) + 1,
+            i64::MAX,
+            i64::MIN,
+        ]
+        .into_iter()
+        .for_each(|value| {
+            let result = serialize_and_return(&value);
+            assert_eq!(result, serde_json::json!(value));
+        });
+        [0, (1 << 53) + 1, u64::MAX].into_iter().for_each(|value| {
+            let result = serialize_and_return(&value);
+            assert_eq!(result, serde_json::json!(value));
+        });
+    }
+
+    #[test]
+    fn test_value_serialize() {
+        let input = serde_json::json!({
+            "lines": [{"id": "gid://shopify/CartLine/1", "quantity": 2}, {"id": "2"}],
+            "note": "gift",
+        });
+        let mut context = Context::new_with_input(input.clone());
+        let root = context.input_get().unwrap();
+        let lines = root.get_obj_prop("lines");
+        let mut output = HashMap::new();
+        output.insert("first", lines.get_at_index(0));
+        output.insert("lines", lines);
+        output.insert("note", root.get_obj_prop("note"));
+        output.serialize(&mut context).unwrap();
+        assert_eq!(
+            context.finalize_output_and_return().unwrap(),
+            serde_json::json!({"first": input["lines"][0], "lines": input["lines"], "note": "gift"})
+        );
+
+        let mut context = Context::new_with_input(input);
+        let missing = context.input_get().unwrap().get_obj_prop("missing");
+        assert!(matches!(
+            missing.serialize(&mut context),
+            Err(Error::InvalidInputValue)
+        ));
+    }
+
     #[test]
     fn test_f64_serialize() {
         [0.0, 1.0, -1.0, f64::MAX, f64::MIN]
@@ -308,6 +1351,194 @@ mod tests {
         assert_eq!(result, serde_json::json!(value));
     }
 
+    #[test]
+    fn test_object_writer() {
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        let key = context.intern_utf8_str("interned");
+        context
+            .write_object_fields(
+                |object| {
+                    object.interned_field(key, &1)?;
+                    object.field("plain", "value")?;
+                    object.context().write_utf8_str("raw")?;
+                    object.context().write_bool(true)
+                },
+                3,
+            )
+            .unwrap();
+        let result = context.finalize_output_and_return().unwrap();
+        assert_eq!(
+            result,
+            serde_json::json!({ "interned": 1, "plain": "value", "raw": true })
+        );
+    }
+
+    #[test]
+    fn test_auto_intern() {
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        context.set_auto_intern_threshold(Some(2));
+        let values = ["a", "a", "b", "a", "a", "b"];
+        values.serialize(&mut context).unwrap();
+        assert_eq!(
+            context.interned_strings(),
+            [(InternedStringId::new(0), "a".to_string())]
+        );
+        assert_eq!(
+            context.finalize_output_and_return().unwrap(),
+            serde_json::json!(values)
+        );
+
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        context.set_auto_intern_threshold(Some(0));
+        context.write_utf8_str("c").unwrap();
+        assert_eq!(
+            context.interned_strings().last(),
+            Some(&(InternedStringId::new(1), "c".to_string()))
+        );
+
+        context.set_auto_intern_threshold(None);
+        assert!(context.auto_intern.is_none());
+        assert_eq!(
+            context.finalize_output_and_return().unwrap(),
+            serde_json::json!("c")
+        );
+    }
+
+    #[test]
+    fn test_object_writer_unknown_interned_string() {
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        let unknown = InternedStringId::new(usize::MAX);
+        assert!(!context.is_interned_utf8_str(unknown));
+        let result = context.write_object_fields(
+            |object| {
+                let result = object.interned_field(unknown, &1);
+                assert!(matches!(result, Err(Error::UnknownInternedString)));
+                object.field("key", &1)
+            },
+            1,
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            context.finalize_output_and_return().unwrap(),
+            serde_json::json!({ "key": 1 })
+        );
+    }
+
+    #[test]
+    fn test_dynamic_array() {
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        context
+            .write_dynamic_array(|array| {
+                (0..10).try_for_each(|i| array.push_some(Some(&i).filter(|i| *i % 3 == 0)))?;
+                array.push("a")?;
+                array.context().write_dynamic_array(|_| Ok(()))?;
+                array
+                    .context()
+                    .write_array(|context| context.write_null(), 1)
+            })
+            .unwrap();
+        let result = context.finalize_output_and_return().unwrap();
+        assert_eq!(result, serde_json::json!([0, 3, 6, 9, "a", [], [null]]));
+    }
+
+    #[test]
+    fn test_closures_return_values() {
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        let (written, len) = context
+            .write_object_fields(
+                |object| {
+                    object.context().write_utf8_str("lines")?;
+                    let written = object.context().write_dynamic_array(|array| {
+                        ["a", "", "b"].into_iter().try_fold(0, |written, s| {
+                            if s.is_empty() {
+                                return Ok(written);
+                            }
+                            array.push(s)?;
+                            Ok(written + 1)
+                        })
+                    })?;
+                    object.context().write_utf8_str("empty")?;
+                    let len = object.context().write_array(|_| Ok(0), 0)?;
+                    Ok((written, len))
+                },
+                2,
+            )
+            .unwrap();
+        assert_eq!((written, len), (2, 0));
+        let result = context.finalize_output_and_return().unwrap();
+        assert_eq!(
+            result,
+            serde_json::json!({ "lines": ["a", "b"], "empty": [] })
+        );
+    }
+
+    #[test]
+    fn test_dynamic_array_in_object() {
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        context
+            .write_object_fields(
+                |object| {
+                    object.context().write_utf8_str("lines")?;
+                    object.context().write_dynamic_array(|array| {
+                        ["a", "", "b"]
+                            .into_iter()
+                            .try_for_each(|s| array.push_some(Some(s).filter(|s| !s.is_empty())))
+                    })?;
+                    object.field("count", &2)
+                },
+                2,
+            )
+            .unwrap();
+        let result = context.finalize_output_and_return().unwrap();
+        assert_eq!(
+            result,
+            serde_json::json!({ "lines": ["a", "b"], "count": 2 })
+        );
+    }
+
+    #[test]
+    fn test_string_sink() {
+        use std::fmt::Write as _;
+        use std::io::Write as _;
+
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        context
+            .write_array(
+                |context| {
+                    let mut sink = context.string_sink(0)?;
+                    std::fmt::Write::write_fmt(&mut sink, format_args!("{}-{}", "a", 1)).unwrap();
+                    // "é" split across writes
+                    sink.write_all(&[b'b', 0xc3]).unwrap();
+                    sink.write_all(&[0xa9]).unwrap();
+                    sink.finish()?;
+
+                    let mut sink = context.string_sink(0)?;
+                    assert_eq!(
+                        sink.write(&[0xff]).unwrap_err().kind(),
+                        std::io::ErrorKind::InvalidData
+                    );
+                    sink.write_all(&[0xc3]).unwrap();
+                    assert!(sink.write_str("c").is_err());
+                    assert!(matches!(sink.finish(), Err(Error::IoError)));
+
+                    // dropping the sink finishes the string
+                    context.string_sink(0)?.write_str("d").unwrap();
+                    Ok(())
+                },
+                3,
+            )
+            .unwrap();
+        let output = context.finalize_output_and_return().unwrap();
+        assert_eq!(output, serde_json::json!(["a-1bé", "", "d"]));
+    }
+
+    #[test]
+    fn test_write_unknown_interned_utf8_str() {
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        let result = context.write_interned_utf8_str(InternedStringId::new(usize::MAX));
+        assert!(matches!(result, Err(Error::UnknownInternedString)));
+    }
+
     #[test]
     fn test_option_serialize() {
         [Some(1), None].into_iter().for_each(|option| {
@@ -315,4 +1546,304 @@ mod tests {
             assert_eq!(result, serde_json::json!(option));
         });
     }
+
+    #[test]
+    fn test_skip_nulls() {
+        struct Line {
+            id: i32,
+            note: Option<String>,
+        }
+
+        impl Serialize for Line {
+            fn serialize(&self, context: &mut Context) -> Result<(), Error> {
+                context.write_object_fields(
+                    |object| {
+                        object.field("id", &self.id)?;
+                        object.field("note", &self.note)
+                    },
+                    2,
+                )
+            }
+        }
+
+        let lines = [
+            Line { id: 1, note: None },
+            Line {
+                id: 2,
+                note: Some("gift".to_string()),
+            },
+        ];
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        context.set_skip_nulls(true);
+        context
+            .write_object(
+                |context| {
+                    context.write_utf8_str("lines")?;
+                    lines.serialize(context)?;
+                    context.write_null_field("discount")?;
+                    context.write_utf8_str("message")?;
+                    context.write_null()
+                },
+                3,
+            )
+            .unwrap();
+        let output = context.finalize_output_and_return().unwrap();
+        assert_eq!(
+            output,
+            serde_json::json!({
+                "lines": [{ "id": 1 }, { "id": 2, "note": "gift" }],
+                "discount": null,
+            })
+        );
+
+        // objects started while nulls aren't skipped keep them
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        context
+            .write_object(
+                |context| {
+                    context.set_skip_nulls(true);
+                    context.write_null_field("discount")?;
+                    context.write_utf8_str("message")?;
+                    context.write_null()
+                },
+                2,
+            )
+            .unwrap();
+        let output = context.finalize_output_and_return().unwrap();
+        assert_eq!(
+            output,
+            serde_json::json!({ "discount": null, "message": null })
+        );
+    }
+
+    #[test]
+    fn test_fail_with_discards_unfinished_output() {
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        let result: Result<(), Error> = context.write_object(
+            |ctx| {
+                ctx.write_utf8_str("operations")?;
+                ctx.write_dynamic_array(|array| {
+                    array.push(&1)?;
+                    Err(Error::IoError)
+                })
+            },
+            1,
+        );
+        context
+            .fail_with(FunctionError::from(result.unwrap_err()).with_target("$.cart"))
+            .unwrap();
+        assert!(matches!(
+            context.write_null(),
+            Err(Error::ValueAlreadyWritten)
+        ));
+        assert_eq!(
+            context.finalize_output_and_return().unwrap(),
+            serde_json::json!({
+                "errors": [{ "localizedMessage": "I/O error", "target": "$.cart" }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_fail_with_message() {
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        context.write_i32(1).unwrap();
+        context.fail_with("Invalid configuration").unwrap();
+        assert_eq!(
+            context.finalize_output_and_return().unwrap(),
+            serde_json::json!({
+                "errors": [{ "localizedMessage": "Invalid configuration", "target": "$" }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_function_error_from_read_error() {
+        let error = FunctionError::from(crate::read::Error::InvalidType);
+        assert_eq!(error.localized_message(), "Invalid type");
+        assert_eq!(error.target(), FunctionError::ROOT_TARGET);
+    }
+
+    #[test]
+    fn test_rollback_to() {
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        context
+            .write_object_fields(
+                |object| {
+                    let checkpoint = object.context().checkpoint();
+                    object.field("discarded", &vec![1, 2])?;
+                    let later = object.context().checkpoint();
+                    object.context().rollback_to(checkpoint)?;
+                    assert!(matches!(
+                        object.context().rollback_to(later),
+                        Err(Error::UnknownCheckpoint)
+                    ));
+                    object.field("kept", &1)
+                },
+                1,
+            )
+            .unwrap();
+        assert_eq!(
+            context.finalize_output_and_return().unwrap(),
+            serde_json::json!({ "kept": 1 })
+        );
+    }
+
+    #[test]
+    fn test_write_bytes() {
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        context
+            .write_object(|context| context.write_bytes(&[1, 2]), 1)
+            .unwrap_err();
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        context
+            .write_object(
+                |context| {
+                    context.write_utf8_str("hash")?;
+                    context.write_bytes(&[0xde, 0xad])
+                },
+                1,
+            )
+            .unwrap();
+        let (_, bytes) =
+            shopify_function_provider::write::shopify_function_output_finalize_and_return_msgpack_bytes();
+        assert_eq!(
+            bytes,
+            [&[0x81, 0xa4][..], b"hash", &[0xc4, 0x02, 0xde, 0xad]].concat()
+        );
+    }
+
+    #[test]
+    fn test_write_raw_msgpack() {
+        let metadata = rmp_serde::to_vec_named(&serde_json::json!({ "tags": ["a", "b"] })).unwrap();
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        context
+            .write_object(
+                |context| {
+                    context.write_raw_msgpack(&rmp_serde::to_vec("metadata").unwrap())?;
+                    assert!(matches!(
+                        context.write_raw_msgpack(&metadata[..metadata.len() - 1]),
+                        Err(Error::InvalidMsgpack)
+                    ));
+                    context.write_raw_msgpack(&metadata)?;
+                    context.write_utf8_str("count")?;
+                    context.write_raw_msgpack(&[0x01])
+                },
+                2,
+            )
+            .unwrap();
+        let output = context.finalize_output_and_return().unwrap();
+        assert_eq!(
+            output,
+            serde_json::json!({ "metadata": { "tags": ["a", "b"] }, "count": 1 })
+        );
+    }
+
+    #[test]
+    fn test_dedup_set() {
+        let context = Context::new_with_input(serde_json::json!({}));
+        let mut seen = context.dedup_set();
+        assert!(seen.insert_str("gid://shopify/CartLine/1"));
+        assert!(!seen.insert_str("gid://shopify/CartLine/1"));
+        // strings interned more than once are the same key
+        let id = context.intern_utf8_str("gid://shopify/CartLine/2");
+        assert!(seen.insert_interned(id));
+        assert!(!seen.insert_interned(context.intern_utf8_str("gid://shopify/CartLine/2")));
+        assert!(seen.insert_hash(&("line", 1)));
+        assert!(!seen.insert_hash(&("line", 1)));
+        assert!(seen.insert_hash(&("line", 2)));
+
+        // sets are independent, and start empty once another is dropped
+        let mut other = context.dedup_set();
+        assert!(other.insert_hash(&("line", 1)));
+        drop(seen);
+        let mut seen = context.dedup_set();
+        assert!(seen.insert_str("gid://shopify/CartLine/1"));
+        assert!(!other.insert_hash(&("line", 1)));
+    }
+
+    #[test]
+    fn test_output_len() {
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        assert_eq!(context.output_len(), 0);
+        context
+            .write_array(
+                |array| {
+                    array.write_utf8_str("abc")?;
+                    assert_eq!(array.output_len(), 5);
+                    let checkpoint = array.checkpoint();
+                    array.write_utf8_str("too long")?;
+                    array.rollback_to(checkpoint)?;
+                    assert_eq!(array.output_len(), 5);
+                    array.write_i32(1)
+                },
+                2,
+            )
+            .unwrap();
+        let len = context.output_len();
+        let (_, bytes) =
+            shopify_function_provider::write::shopify_function_output_finalize_and_return_msgpack_bytes();
+        assert_eq!(len, bytes.len());
+    }
+
+    #[test]
+    fn test_btree_map_serialize() {
+        let map: BTreeMap<&str, i32> = [("b", 2), ("c", 3), ("a", 1)].into_iter().collect();
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        map.serialize(&mut context).unwrap();
+        let (_, bytes) =
+            shopify_function_provider::write::shopify_function_output_finalize_and_return_msgpack_bytes();
+        // keys are written in sorted order
+        assert_eq!(bytes, rmp_serde::to_vec(&map).unwrap());
+    }
+
+    #[test]
+    fn test_interned_key_map_serialize() {
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        let [b, a] = ["b", "a"].map(|key| context.intern_utf8_str(key));
+        let mut map: InternedKeyMap<i32> = [(b, 1), (a, 2)].into_iter().collect();
+        assert_eq!(map.insert(b, 3), Some(1));
+        assert_eq!(map.get(b), Some(&3));
+        assert_eq!(map.len(), 2);
+        map.serialize(&mut context).unwrap();
+        let (_, bytes) =
+            shopify_function_provider::write::shopify_function_output_finalize_and_return_msgpack_bytes();
+        // a map of 2 entries, with keys in insertion order: { "b": 3, "a": 2 }
+        assert_eq!(bytes, [0x82, 0xa1, b'b', 0x03, 0xa1, b'a', 0x02]);
+    }
+
+    #[test]
+    fn test_write_array_from_iter() {
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        let lines = ["a".to_string(), "b".to_string()];
+        context
+            .write_object(
+                |context| {
+                    context.write_utf8_str("ids")?;
+                    context.write_array_from_iter(lines.iter())?;
+                    context.write_utf8_str("empty")?;
+                    context.write_array_from_iter(std::iter::empty::<i32>())
+                },
+                2,
+            )
+            .unwrap();
+        assert_eq!(
+            context.finalize_output_and_return().unwrap(),
+            serde_json::json!({ "ids": ["a", "b"], "empty": [] })
+        );
+    }
+
+    #[test]
+    fn test_write_object_from_iter() {
+        let mut context = Context::new_with_input(serde_json::json!({}));
+        context
+            .write_object_from_iter(
+                (1..4).map(|i| (format!("key{i}"), Some(i).filter(|i| i % 2 == 1))),
+            )
+            .unwrap();
+        assert_eq!(
+            context.finalize_output_and_return().unwrap(),
+            serde_json::json!({ "key1": 1, "key2": null, "key3": 3 })
+        );
+    }
 }
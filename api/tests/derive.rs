@@ -0,0 +1,98 @@
+use shopify_function_wasm_api::{read, Context, Deserialize, Serialize};
+// the `derive` feature re-exports the macros alongside the traits
+#[cfg(not(feature = "derive"))]
+use shopify_function_wasm_api_derive::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[shopify_function(rename_all = "camelCase")]
+struct CartLine {
+    quantity: i32,
+    merchandise_id: String,
+    #[shopify_function(rename = "attr")]
+    attribute: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Wrapper<T> {
+    lines: Vec<T>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Empty {}
+
+fn deserialize<T: Deserialize>(input: serde_json::Value) -> Result<T, read::Error> {
+    let context = Context::new_with_input(input);
+    T::deserialize(&context.input_get().unwrap())
+}
+
+fn serialize<T: Serialize>(value: &T) -> serde_json::Value {
+    let mut context = Context::new_with_input(serde_json::json!({}));
+    value.serialize(&mut context).unwrap();
+    context.finalize_output_and_return().unwrap()
+}
+
+#[test]
+fn test_deserialize() {
+    let wrapper: Wrapper<CartLine> = deserialize(serde_json::json!({
+        "lines": [
+            { "quantity": 1, "merchandiseId": "gid://1", "attr": "a" },
+            { "quantity": 2, "merchandiseId": "gid://2" },
+        ]
+    }))
+    .unwrap();
+    assert_eq!(
+        wrapper,
+        Wrapper {
+            lines: vec![
+                CartLine {
+                    quantity: 1,
+                    merchandise_id: "gid://1".to_string(),
+                    attribute: Some("a".to_string()),
+                },
+                CartLine {
+                    quantity: 2,
+                    merchandise_id: "gid://2".to_string(),
+                    attribute: None,
+                },
+            ]
+        }
+    );
+}
+
+#[test]
+fn test_deserialize_errors() {
+    assert!(matches!(
+        deserialize::<CartLine>(serde_json::json!([])),
+        Err(read::Error::InvalidType)
+    ));
+    assert!(matches!(
+        deserialize::<CartLine>(serde_json::json!({ "quantity": 1 })),
+        Err(read::Error::InvalidType)
+    ));
+}
+
+#[test]
+fn test_serialize() {
+    let wrapper = Wrapper {
+        lines: vec![CartLine {
+            quantity: 1,
+            merchandise_id: "gid://1".to_string(),
+            attribute: None,
+        }],
+    };
+    assert_eq!(
+        serialize(&wrapper),
+        serde_json::json!({
+            "lines": [{ "quantity": 1, "merchandiseId": "gid://1", "attr": null }]
+        })
+    );
+}
+
+#[test]
+fn test_round_trip_empty() {
+    assert_eq!(serialize(&Empty {}), serde_json::json!({}));
+    assert_eq!(
+        deserialize::<Empty>(serde_json::json!({})).unwrap(),
+        Empty {}
+    );
+}
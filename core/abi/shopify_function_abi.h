@@ -0,0 +1,163 @@
+// Generated by `cargo shopify-function generate-abi`. Do not edit.
+#ifndef SHOPIFY_FUNCTION_ABI_H
+#define SHOPIFY_FUNCTION_ABI_H
+
+#include <stdint.h>
+
+// The result of a write operation.
+typedef uint32_t ShopifyFunctionWriteResult;
+// The write operation was successful.
+#define SHOPIFY_FUNCTION_WRITE_RESULT_OK 0
+// An error occurred while writing to the output.
+#define SHOPIFY_FUNCTION_WRITE_RESULT_IO_ERROR 1
+// Tried to write a value when a key was expected.
+#define SHOPIFY_FUNCTION_WRITE_RESULT_EXPECTED_KEY 2
+// The object length was not honoured.
+#define SHOPIFY_FUNCTION_WRITE_RESULT_OBJECT_LENGTH_ERROR 3
+// Tried to write a value when a value was already written.
+#define SHOPIFY_FUNCTION_WRITE_RESULT_VALUE_ALREADY_WRITTEN 4
+// The value is not an object, but an operation expected an object.
+#define SHOPIFY_FUNCTION_WRITE_RESULT_NOT_AN_OBJECT 5
+// Value not finished.
+#define SHOPIFY_FUNCTION_WRITE_RESULT_VALUE_NOT_FINISHED 6
+// The array length was not honoured.
+#define SHOPIFY_FUNCTION_WRITE_RESULT_ARRAY_LENGTH_ERROR 7
+// The value is not an array, but an operation expected an array.
+#define SHOPIFY_FUNCTION_WRITE_RESULT_NOT_AN_ARRAY 8
+// The interned string ID does not exist in the string interner.
+#define SHOPIFY_FUNCTION_WRITE_RESULT_UNKNOWN_INTERNED_STRING 9
+// The checkpoint does not exist, or was removed by rolling back to an earlier checkpoint.
+#define SHOPIFY_FUNCTION_WRITE_RESULT_UNKNOWN_CHECKPOINT 10
+// The value is not a string being written in chunks, but an operation expected one.
+#define SHOPIFY_FUNCTION_WRITE_RESULT_NOT_A_STRING 11
+// The output can't be discarded, because the host already read part of it.
+#define SHOPIFY_FUNCTION_WRITE_RESULT_OUTPUT_ALREADY_READ 12
+// The input value could not be read, so it could not be written to the output.
+#define SHOPIFY_FUNCTION_WRITE_RESULT_INVALID_INPUT_VALUE 13
+// The MessagePack fragment is not exactly one complete value, so it could not be written to
+the output.
+
+#define SHOPIFY_FUNCTION_WRITE_RESULT_INVALID_MSGPACK 14
+
+// The error code of a NaN-boxed value with the error tag.
+typedef uint32_t ShopifyFunctionErrorCode;
+// The NanBox could not be decoded.
+#define SHOPIFY_FUNCTION_ERROR_CODE_DECODE_ERROR 0
+// The value is not an object, but an operation expected an object.
+#define SHOPIFY_FUNCTION_ERROR_CODE_NOT_AN_OBJECT 1
+// Index is out of bounds on the byte array.
+#define SHOPIFY_FUNCTION_ERROR_CODE_BYTE_ARRAY_OUT_OF_BOUNDS 2
+// An error occurred while attempting to read a value.
+#define SHOPIFY_FUNCTION_ERROR_CODE_READ_ERROR 3
+// The value is not an array, but an operation expected an array.
+#define SHOPIFY_FUNCTION_ERROR_CODE_NOT_AN_ARRAY 4
+// The index is out of bounds for the array.
+#define SHOPIFY_FUNCTION_ERROR_CODE_INDEX_OUT_OF_BOUNDS 5
+// The value is not indexable. Indexable values are objects and arrays.
+#define SHOPIFY_FUNCTION_ERROR_CODE_NOT_INDEXABLE 6
+// The interned string ID does not exist in the string interner.
+#define SHOPIFY_FUNCTION_ERROR_CODE_UNKNOWN_INTERNED_STRING 7
+// The host call has not finished yet, and should be polled again.
+#define SHOPIFY_FUNCTION_ERROR_CODE_HOST_CALL_PENDING 8
+// The host does not support the capability requested by the host call.
+#define SHOPIFY_FUNCTION_ERROR_CODE_HOST_CALL_UNSUPPORTED 9
+// The host call ID does not exist.
+#define SHOPIFY_FUNCTION_ERROR_CODE_UNKNOWN_HOST_CALL 10
+// The shape ID does not exist.
+#define SHOPIFY_FUNCTION_ERROR_CODE_UNKNOWN_SHAPE 11
+// The value is msgpack binary data or an extension type, which can't be read.
+#define SHOPIFY_FUNCTION_ERROR_CODE_UNSUPPORTED_VALUE 12
+// The input contains the msgpack marker reserved by the specification, so it can't be read.
+#define SHOPIFY_FUNCTION_ERROR_CODE_RESERVED_MARKER 13
+// An object in the input has the same key more than once, which the host asked to reject.
+#define SHOPIFY_FUNCTION_ERROR_CODE_DUPLICATE_OBJECT_KEY 14
+// The keys of an object in the input aren't sorted, which the host asked to reject.
+#define SHOPIFY_FUNCTION_ERROR_CODE_UNSORTED_OBJECT_KEYS 15
+// The iterator ID does not exist.
+#define SHOPIFY_FUNCTION_ERROR_CODE_UNKNOWN_ITERATOR 16
+// The work budget the guest set is spent.
+#define SHOPIFY_FUNCTION_ERROR_CODE_BUDGET_EXCEEDED 17
+// The pinned value ID does not exist, or the value was unpinned.
+#define SHOPIFY_FUNCTION_ERROR_CODE_UNKNOWN_PINNED_VALUE 18
+// The provider ran out of memory for reading the input, e.g. because the host limited it.
+#define SHOPIFY_FUNCTION_ERROR_CODE_OUT_OF_MEMORY 19
+// The provider was built without Unicode normalization, so it can't compare normalized strings.
+#define SHOPIFY_FUNCTION_ERROR_CODE_NORMALIZATION_UNSUPPORTED 20
+
+// The tag of a NaN-boxed value.
+typedef uint32_t ShopifyFunctionTag;
+// Null type.
+#define SHOPIFY_FUNCTION_TAG_NULL 0
+// Boolean type.
+#define SHOPIFY_FUNCTION_TAG_BOOL 1
+// Number type, encoded as a 64-bit floating point.
+#define SHOPIFY_FUNCTION_TAG_NUMBER 2
+// String type, encoded as UTF-8.
+#define SHOPIFY_FUNCTION_TAG_STRING 3
+// An object pointer.
+#define SHOPIFY_FUNCTION_TAG_OBJECT 4
+// An array pointer.
+#define SHOPIFY_FUNCTION_TAG_ARRAY 5
+// A byte array pointer, for msgpack binary data.
+#define SHOPIFY_FUNCTION_TAG_BYTES 6
+// An error code.
+#define SHOPIFY_FUNCTION_TAG_ERROR 15
+
+// The encoding of the input passed to `initialize_with_encoding`.
+typedef uint32_t ShopifyFunctionInputEncoding;
+// MessagePack.
+#define SHOPIFY_FUNCTION_INPUT_ENCODING_MSGPACK 0
+// JSON, which is converted to msgpack when the input is first read.
+#define SHOPIFY_FUNCTION_INPUT_ENCODING_JSON 1
+
+// The compression of the input passed to `initialize_with_compression`.
+typedef uint32_t ShopifyFunctionInputCompression;
+// Not compressed.
+#define SHOPIFY_FUNCTION_INPUT_COMPRESSION_NONE 0
+// Gzip, supported by providers built with the `gzip-input` feature.
+#define SHOPIFY_FUNCTION_INPUT_COMPRESSION_GZIP 1
+// Zstandard, supported by providers built with the `zstd-input` feature.
+#define SHOPIFY_FUNCTION_INPUT_COMPRESSION_ZSTD 2
+
+// How strictly the provider checks object keys, set with `set_input_key_validation`.
+typedef uint32_t ShopifyFunctionInputKeyValidation;
+// Keys aren't checked, and the first property with a key is read.
+#define SHOPIFY_FUNCTION_INPUT_KEY_VALIDATION_NONE 0
+// Keys are unique within each object.
+#define SHOPIFY_FUNCTION_INPUT_KEY_VALIDATION_UNIQUE 1
+// Keys are unique and sorted by their bytes within each object.
+#define SHOPIFY_FUNCTION_INPUT_KEY_VALIDATION_SORTED 2
+
+// The encoding of the output located by the finalize results.
+typedef uint32_t ShopifyFunctionOutputEncoding;
+// MessagePack.
+#define SHOPIFY_FUNCTION_OUTPUT_ENCODING_MSGPACK 0
+// Pretty-printed JSON.
+#define SHOPIFY_FUNCTION_OUTPUT_ENCODING_JSON 1
+// JSON without whitespace.
+#define SHOPIFY_FUNCTION_OUTPUT_ENCODING_COMPACT_JSON 2
+
+// The compression of the output located by the finalize results.
+typedef uint32_t ShopifyFunctionOutputCompression;
+// Not compressed.
+#define SHOPIFY_FUNCTION_OUTPUT_COMPRESSION_NONE 0
+// A Zstandard frame.
+#define SHOPIFY_FUNCTION_OUTPUT_COMPRESSION_ZSTD 1
+
+// The level of a log record, passed to `shopify_function_log_new_record`.
+typedef uint32_t ShopifyFunctionLogLevel;
+// Information about what the function did.
+#define SHOPIFY_FUNCTION_LOG_LEVEL_INFO 0
+// Something unexpected that the function handled.
+#define SHOPIFY_FUNCTION_LOG_LEVEL_WARN 1
+// Something that stopped the function from working as intended.
+#define SHOPIFY_FUNCTION_LOG_LEVEL_ERROR 2
+
+// The version of the layout of the finalize results.
+#define SHOPIFY_FUNCTION_FINALIZE_RESULTS_VERSION 3
+// The size of the finalize results in bytes.
+#define SHOPIFY_FUNCTION_FINALIZE_RESULTS_SIZE 36
+// The longest length encoded in a NaN-boxed value; longer lengths must be queried from the provider.
+#define SHOPIFY_FUNCTION_MAX_VALUE_LENGTH 16383
+
+#endif // SHOPIFY_FUNCTION_ABI_H
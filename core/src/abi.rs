@@ -0,0 +1,321 @@
+//! A description of the enums and constants shared by the provider, the guest API and hosts, for
+//! bindings in languages other than Rust.
+//!
+//! The description is published as a C header, [`C_HEADER_FILE_NAME`], and a JSON descriptor,
+//! [`JSON_DESCRIPTOR_FILE_NAME`], in the `abi` directory of this crate. They're generated from the
+//! definitions in this crate by `cargo shopify-function generate-abi`, and a test checks that they're
+//! up to date.
+
+use std::fmt::Write;
+
+use strum::{EnumMessage, IntoEnumIterator};
+
+use crate::{
+    host::{
+        FinalizeResults, InputCompression, InputEncoding, InputKeyValidation, OutputCompression,
+        OutputEncoding,
+    },
+    log::Level,
+    read::{ErrorCode, Tag},
+    write::WriteResult,
+};
+
+/// The name of the generated C header.
+pub const C_HEADER_FILE_NAME: &str = "shopify_function_abi.h";
+
+/// The name of the generated JSON descriptor.
+pub const JSON_DESCRIPTOR_FILE_NAME: &str = "shopify_function_abi.json";
+
+/// The prefix of the names in the C header.
+const C_PREFIX: &str = "SHOPIFY_FUNCTION_";
+
+/// An enum passed between the provider, the guest and hosts as a `u32`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbiEnum {
+    pub name: &'static str,
+    pub doc: &'static str,
+    pub variants: Vec<AbiVariant>,
+}
+
+/// A variant of an [`AbiEnum`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbiVariant {
+    pub name: String,
+    pub value: u32,
+    pub doc: &'static str,
+}
+
+/// A constant that's part of the ABI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbiConstant {
+    pub name: &'static str,
+    pub value: u32,
+    pub doc: &'static str,
+}
+
+fn abi_enum<E>(
+    name: &'static str,
+    doc: &'static str,
+    value: impl Fn(E) -> u32,
+    include: impl Fn(&E) -> bool,
+) -> AbiEnum
+where
+    E: IntoEnumIterator + EnumMessage + std::fmt::Debug + Copy,
+{
+    let variants = E::iter()
+        .filter(include)
+        .map(|variant| AbiVariant {
+            name: format!("{variant:?}"),
+            value: value(variant),
+            doc: variant.get_documentation().unwrap_or_default(),
+        })
+        .collect();
+    AbiEnum {
+        name,
+        doc,
+        variants,
+    }
+}
+
+/// The enums of the ABI.
+pub fn enums() -> Vec<AbiEnum> {
+    vec![
+        abi_enum(
+            "WriteResult",
+            "The result of a write operation.",
+            |result: WriteResult| result as u32,
+            |_| true,
+        ),
+        abi_enum(
+            "ErrorCode",
+            "The error code of a NaN-boxed value with the error tag.",
+            |code: ErrorCode| code as u32,
+            // codes the provider doesn't return are decoded as `Unknown`, which has no value
+            |code| !matches!(code, ErrorCode::Unknown),
+        ),
+        abi_enum(
+            "Tag",
+            "The tag of a NaN-boxed value.",
+            |tag: Tag| tag as u32,
+            |_| true,
+        ),
+        abi_enum(
+            "InputEncoding",
+            "The encoding of the input passed to `initialize_with_encoding`.",
+            |encoding: InputEncoding| encoding as u32,
+            |_| true,
+        ),
+        abi_enum(
+            "InputCompression",
+            "The compression of the input passed to `initialize_with_compression`.",
+            |compression: InputCompression| compression as u32,
+            |_| true,
+        ),
+        abi_enum(
+            "InputKeyValidation",
+            "How strictly the provider checks object keys, set with `set_input_key_validation`.",
+            |validation: InputKeyValidation| validation as u32,
+            |_| true,
+        ),
+        abi_enum(
+            "OutputEncoding",
+            "The encoding of the output located by the finalize results.",
+            |encoding: OutputEncoding| encoding as u32,
+            |_| true,
+        ),
+        abi_enum(
+            "OutputCompression",
+            "The compression of the output located by the finalize results.",
+            |compression: OutputCompression| compression as u32,
+            |_| true,
+        ),
+        abi_enum(
+            "LogLevel",
+            "The level of a log record, passed to `shopify_function_log_new_record`.",
+            |level: Level| level as u32,
+            |_| true,
+        ),
+    ]
+}
+
+/// The constants of the ABI.
+pub fn constants() -> Vec<AbiConstant> {
+    vec![
+        AbiConstant {
+            name: "FinalizeResultsVersion",
+            value: FinalizeResults::VERSION,
+            doc: "The version of the layout of the finalize results.",
+        },
+        AbiConstant {
+            name: "FinalizeResultsSize",
+            value: FinalizeResults::SIZE as u32,
+            doc: "The size of the finalize results in bytes.",
+        },
+        AbiConstant {
+            // the NaN-box layout depends on the pointer width, so this is the value on Wasm
+            name: "MaxValueLength",
+            value: (1 << 14) - 1,
+            doc: "The longest length encoded in a NaN-boxed value; longer lengths must be queried from the provider.",
+        },
+    ]
+}
+
+/// Convert a `CamelCase` name to `SCREAMING_SNAKE_CASE`.
+fn screaming_snake_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, c) in name.char_indices() {
+        if c.is_ascii_uppercase() && i > 0 {
+            out.push('_');
+        }
+        out.push(c.to_ascii_uppercase());
+    }
+    out
+}
+
+/// Generate the C header.
+pub fn c_header() -> String {
+    let mut out = String::new();
+    out.push_str("// Generated by `cargo shopify-function generate-abi`. Do not edit.\n");
+    out.push_str("#ifndef SHOPIFY_FUNCTION_ABI_H\n#define SHOPIFY_FUNCTION_ABI_H\n\n");
+    out.push_str("#include <stdint.h>\n");
+    for abi_enum in enums() {
+        let prefix = format!("{C_PREFIX}{}", screaming_snake_case(abi_enum.name));
+        out.push('\n');
+        writeln!(out, "// {}", abi_enum.doc).unwrap();
+        writeln!(out, "typedef uint32_t ShopifyFunction{};", abi_enum.name).unwrap();
+        for variant in &abi_enum.variants {
+            writeln!(out, "// {}", variant.doc).unwrap();
+            writeln!(
+                out,
+                "#define {prefix}_{} {}",
+                screaming_snake_case(&variant.name),
+                variant.value
+            )
+            .unwrap();
+        }
+    }
+    out.push('\n');
+    for constant in constants() {
+        writeln!(out, "// {}", constant.doc).unwrap();
+        writeln!(
+            out,
+            "#define {C_PREFIX}{} {}",
+            screaming_snake_case(constant.name),
+            constant.value
+        )
+        .unwrap();
+    }
+    out.push_str("\n#endif // SHOPIFY_FUNCTION_ABI_H\n");
+    out
+}
+
+fn json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c < ' ' => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Generate the JSON descriptor.
+pub fn json_descriptor() -> String {
+    let enums = enums()
+        .iter()
+        .map(|abi_enum| {
+            let variants = abi_enum
+                .variants
+                .iter()
+                .map(|variant| {
+                    format!(
+                        "        {{ \"name\": {}, \"value\": {}, \"doc\": {} }}",
+                        json_str(&variant.name),
+                        variant.value,
+                        json_str(variant.doc)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(",\n");
+            format!(
+                "    {{\n      \"name\": {},\n      \"repr\": \"u32\",\n      \"doc\": {},\n      \"variants\": [\n{variants}\n      ]\n    }}",
+                json_str(abi_enum.name),
+                json_str(abi_enum.doc)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",\n");
+    let constants = constants()
+        .iter()
+        .map(|constant| {
+            format!(
+                "    {{ \"name\": {}, \"value\": {}, \"doc\": {} }}",
+                json_str(constant.name),
+                constant.value,
+                json_str(constant.doc)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",\n");
+    format!("{{\n  \"enums\": [\n{enums}\n  ],\n  \"constants\": [\n{constants}\n  ]\n}}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checked_in(file_name: &str) -> String {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("abi")
+            .join(file_name);
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn test_artifacts_up_to_date() {
+        assert!(
+            checked_in(C_HEADER_FILE_NAME) == c_header()
+                && checked_in(JSON_DESCRIPTOR_FILE_NAME) == json_descriptor(),
+            "the ABI artifacts are out of date, run `cargo shopify-function generate-abi`"
+        );
+    }
+
+    #[test]
+    fn test_screaming_snake_case() {
+        assert_eq!(screaming_snake_case("NotAString"), "NOT_A_STRING");
+        assert_eq!(screaming_snake_case("IoError"), "IO_ERROR");
+        assert_eq!(screaming_snake_case("Ok"), "OK");
+    }
+
+    #[test]
+    fn test_variants_documented() {
+        for abi_enum in enums() {
+            for variant in &abi_enum.variants {
+                assert!(
+                    !variant.doc.is_empty(),
+                    "{}::{}",
+                    abi_enum.name,
+                    variant.name
+                );
+            }
+        }
+    }
+
+    #[cfg(target_pointer_width = "32")]
+    #[test]
+    fn test_max_value_length() {
+        let constant = constants()
+            .into_iter()
+            .find(|constant| constant.name == "MaxValueLength")
+            .unwrap();
+        assert_eq!(
+            constant.value as usize,
+            crate::read::NanBox::MAX_VALUE_LENGTH
+        );
+    }
+}
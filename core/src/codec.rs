@@ -0,0 +1,49 @@
+//! Formatting shared by every place the output is converted to JSON, so it matches the platform's
+//! conversion byte for byte and snapshots of JSON output don't change with the tool that made them.
+
+/// A buffer for formatting `f64`s as JSON, which can be reused across values.
+///
+/// Numbers are written in the shortest form that parses back to the same `f64`, as the platform
+/// does: with a fractional part for integers, e.g. `1.0`, and with an exponent for very large and
+/// very small numbers, e.g. `1e300`.
+#[derive(Default, Clone, Copy)]
+pub struct FloatBuffer(ryu::Buffer);
+
+impl FloatBuffer {
+    /// Create a buffer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Format `value`, returning `None` for NaN and infinities, which can't be represented in JSON
+    /// and are written as `null` instead.
+    pub fn format(&mut self, value: f64) -> Option<&str> {
+        value.is_finite().then(|| self.0.format_finite(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let mut buffer = FloatBuffer::new();
+        for (value, expected) in [
+            (0.0, "0.0"),
+            (-0.0, "-0.0"),
+            (1.0, "1.0"),
+            (0.1, "0.1"),
+            (-1.5, "-1.5"),
+            (1e300, "1e300"),
+            (1.5e-7, "1.5e-7"),
+            (f64::MAX, "1.7976931348623157e308"),
+            (9007199254740993.0, "9007199254740992.0"),
+        ] {
+            assert_eq!(buffer.format(value), Some(expected), "{value:?}");
+        }
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert_eq!(buffer.format(value), None);
+        }
+    }
+}
@@ -0,0 +1,606 @@
+//! The interface between hosts and the provider, for running a function.
+//!
+//! A host runs a function by:
+//! 1. Calling the provider's `initialize` export with the length of the msgpack-encoded input. It
+//!    returns the address in the provider's memory to copy the input to, or 0 if a buffer of that
+//!    length could not be allocated (see [`input_offset`]). Hosts with input in another
+//!    [`InputEncoding`] call `initialize_with_encoding` instead, which also returns 0 for encodings
+//!    the provider doesn't support.
+//! 2. Copying the input to that address. Large inputs can be copied compressed, by calling
+//!    `initialize_with_compression` with the compressed length and an [`InputCompression`]
+//!    instead. The provider's `input_compression_supported` export returns 1 for the compressions
+//!    it supports, and 0 for others, for which `initialize_with_compression` returns 0.
+//!    Hosts can also size the arena the provider reads the input into, by calling
+//!    `initialize_with_arena` with the same arguments followed by those of an [`ArenaConfig`]. It
+//!    also returns 0 if the arena's first chunk could not be allocated.
+//! 3. Calling the function's `_start` export.
+//! 4. Calling the provider's `finalize` export, which returns the address in the provider's memory
+//!    of the [`FinalizeResults`], locating the output and the logs.
+//!
+//! Hosts can also read msgpack output while the function is running, e.g. when it calls a host
+//! import, to handle large outputs incrementally or report progress. The provider's
+//! `output_chunk_available` export returns the number of bytes of output that won't change and
+//! haven't been read yet, and `output_read_chunk` takes a number of bytes up to that and returns the
+//! address of those bytes in the provider's memory, or 0 if fewer are available. The bytes must be
+//! copied before the provider is called again. The output returned by `finalize` is then only the
+//! rest of the output, and the function can no longer discard its output.
+//!
+//! Hosts can run a function again with the same provider instance by initializing it again, which
+//! resets it. Hosts that also keep the function's instance warm can call the provider's
+//! `set_retain_string_interner` export with 1 to keep the interned strings and shapes when it's
+//! next initialized, so the IDs the function cached stay valid and refer to the same strings, and
+//! with 0 to reset them again. It returns 1 if it was set, and 0 for other values.
+//!
+//! The logs are a ring buffer, so the oldest logs are dropped once it's full. The provider's
+//! `log_dropped_len` export returns the number of bytes dropped, so hosts can report that the logs
+//! were truncated. Records logged with a level are kept apart from them, and encoded as described
+//! in the [`log`](crate::log) module.
+//!
+//! The layout of [`FinalizeResults`] is identified by [`FinalizeResults::VERSION`], which the
+//! provider's `finalize_results_version` export returns, so hosts can check they read it correctly.
+
+use std::{error::Error, fmt};
+
+/// An error reading the results of running a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostError {
+    /// The provider could not allocate a buffer for an input of this length.
+    InputTooLarge { len: usize },
+    /// The provider uses a different layout for its finalize results.
+    UnsupportedFinalizeResultsVersion { version: u32 },
+    /// The finalize results are not within the provider's memory.
+    FinalizeResultsOutOfBounds { offset: usize, memory_len: usize },
+    /// The finalize results were copied from the provider's memory with the wrong length.
+    InvalidFinalizeResultsLength { len: usize },
+    /// A region the finalize results point to is not within the provider's memory.
+    RegionOutOfBounds {
+        region: &'static str,
+        region_offset: u32,
+        region_len: u32,
+        memory_len: usize,
+    },
+    /// The output is compressed with an unknown [`OutputCompression`].
+    UnknownOutputCompression { compression: u32 },
+}
+
+impl fmt::Display for HostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InputTooLarge { len } => {
+                write!(f, "the provider could not allocate {len} bytes for the input")
+            }
+            Self::UnsupportedFinalizeResultsVersion { version } => write!(
+                f,
+                "unsupported finalize results version {version}, expected {}",
+                FinalizeResults::VERSION
+            ),
+            Self::FinalizeResultsOutOfBounds { offset, memory_len } => write!(
+                f,
+                "finalize results at offset {offset} are outside of the provider's memory of {memory_len} bytes"
+            ),
+            Self::InvalidFinalizeResultsLength { len } => write!(
+                f,
+                "finalize results are {len} bytes long, expected {}",
+                FinalizeResults::SIZE
+            ),
+            Self::RegionOutOfBounds {
+                region,
+                region_offset,
+                region_len,
+                memory_len,
+            } => write!(
+                f,
+                "{region} at offset {region_offset} with length {region_len} is outside of the provider's memory of {memory_len} bytes"
+            ),
+            Self::UnknownOutputCompression { compression } => {
+                write!(f, "the output is compressed with unknown compression {compression}")
+            }
+        }
+    }
+}
+
+impl Error for HostError {}
+
+/// Check the address returned by the provider's `initialize` export for an input of `input_len`
+/// bytes, returning the offset to copy the input to.
+pub fn input_offset(initialize_result: u32, input_len: usize) -> Result<usize, HostError> {
+    match initialize_result {
+        0 => Err(HostError::InputTooLarge { len: input_len }),
+        offset => Ok(offset as usize),
+    }
+}
+
+/// The encoding of the input passed to the provider's `initialize_with_encoding` export.
+///
+/// Input in encodings other than msgpack is converted to msgpack the first time it is read, so it
+/// costs more to read than msgpack input.
+#[repr(u32)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, strum::FromRepr, strum::EnumIter, strum::EnumMessage,
+)]
+pub enum InputEncoding {
+    /// MessagePack.
+    #[default]
+    Msgpack = 0,
+    /// JSON, which is converted to msgpack when the input is first read.
+    Json = 1,
+}
+
+/// The compression of the input passed to the provider's `initialize_with_compression` export.
+///
+/// Compressed input is decompressed before it's first read. Providers only support the compressions
+/// they were built with the features for.
+#[repr(u32)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, strum::FromRepr, strum::EnumIter, strum::EnumMessage,
+)]
+pub enum InputCompression {
+    /// Not compressed.
+    #[default]
+    None = 0,
+    /// Gzip, supported by providers built with the `gzip-input` feature.
+    Gzip = 1,
+    /// Zstandard, supported by providers built with the `zstd-input` feature.
+    Zstd = 2,
+}
+
+/// How the provider sizes the arena it reads the input into, passed to its `initialize_with_arena`
+/// export after the input length, encoding and compression.
+///
+/// The arena grows in chunks, each twice the size of the last, starting from a small one. Hosts
+/// that know how much a function reads can size its first chunk to fit, and limit how large the
+/// arena can grow, so reading the input fails with [`ErrorCode::OutOfMemory`] rather than
+/// aborting once the limit is reached.
+///
+/// [`ErrorCode::OutOfMemory`]: crate::read::ErrorCode::OutOfMemory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ArenaConfig {
+    /// The size in bytes of the first chunk, which is allocated when the provider is initialized,
+    /// or 0 for the default, which is allocated when the input is first read.
+    pub initial_capacity: usize,
+    /// The most bytes the chunks can take altogether, or 0 for no limit.
+    pub allocation_limit: usize,
+}
+
+/// How strictly the provider checks the keys of objects in the input, set with its
+/// `set_input_key_validation` export after `initialize`.
+///
+/// The platform encodes objects with unique keys sorted by their bytes. Hosts that guarantee this
+/// can have the provider check it when the input is first read, which then fails with
+/// [`ErrorCode::DuplicateObjectKey`] or [`ErrorCode::UnsortedObjectKeys`] if it doesn't hold. With
+/// sorted keys, the provider also stops looking for a property once it's past where its key would
+/// be.
+///
+/// [`ErrorCode::DuplicateObjectKey`]: crate::read::ErrorCode::DuplicateObjectKey
+/// [`ErrorCode::UnsortedObjectKeys`]: crate::read::ErrorCode::UnsortedObjectKeys
+#[repr(u32)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, strum::FromRepr, strum::EnumIter, strum::EnumMessage,
+)]
+pub enum InputKeyValidation {
+    /// Keys aren't checked, and the first property with a key is read.
+    #[default]
+    None = 0,
+    /// Keys are unique within each object.
+    Unique = 1,
+    /// Keys are unique and sorted by their bytes within each object.
+    Sorted = 2,
+}
+
+/// The encoding of the output located by the [`FinalizeResults`].
+///
+/// Providers built with the `json-output` feature have a `set_output_encoding` export, which hosts
+/// call after `initialize` to have `finalize` return the output as JSON: pretty-printed, e.g. to
+/// show it in local development tools, or compact, for hosts that consume JSON. It returns 1 if the
+/// encoding was set, or 0 if the provider doesn't support it. The function writes its output the
+/// same way whichever encoding the host asks for, and it is converted when it's finalized.
+#[repr(u32)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, strum::FromRepr, strum::EnumIter, strum::EnumMessage,
+)]
+pub enum OutputEncoding {
+    /// MessagePack.
+    #[default]
+    Msgpack = 0,
+    /// Pretty-printed JSON.
+    Json = 1,
+    /// JSON without whitespace.
+    CompactJson = 2,
+}
+
+/// The compression of the output located by the [`FinalizeResults`].
+///
+/// Providers built with the `zstd-output` feature have a `set_output_compression` export, which
+/// hosts that can decompress the output call after `initialize` with a compression and a threshold
+/// in bytes, to have `finalize` compress output longer than the threshold. It returns 1 if the
+/// compression was set, or 0 if the provider doesn't support it. Output is only compressed when
+/// hosts ask for it, and [`FinalizeResults::output_compression`] records whether it was.
+#[repr(u32)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, strum::FromRepr, strum::EnumIter, strum::EnumMessage,
+)]
+pub enum OutputCompression {
+    /// Not compressed.
+    #[default]
+    None = 0,
+    /// A Zstandard frame.
+    Zstd = 1,
+}
+
+/// A region of the provider's memory.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MemoryRegion {
+    pub offset: u32,
+    pub len: u32,
+}
+
+impl MemoryRegion {
+    /// Get the bytes of this region, or `None` if it is not within `memory`.
+    pub fn get<'a>(&self, memory: &'a [u8]) -> Option<&'a [u8]> {
+        let start = self.offset as usize;
+        let end = start.checked_add(self.len as usize)?;
+        memory.get(start..end)
+    }
+}
+
+/// The results of running a function, written to the provider's memory by its `finalize` export.
+///
+/// This is laid out as nine little-endian `u32`s without padding: the offset and length of the
+/// output, then of the older part of the logs, then of the newer part of the logs, then the
+/// [`OutputCompression`] of the output, and then the offset and length of the log records. The logs
+/// are kept in a ring buffer, so they are split in two parts once it wraps around; the second part
+/// is empty otherwise.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FinalizeResults {
+    /// The output, which is msgpack-encoded unless another [`OutputEncoding`] was set.
+    pub output: MemoryRegion,
+    /// The logs, in order.
+    pub logs: [MemoryRegion; 2],
+    /// The [`OutputCompression`] of the output, which is 0 unless the host asked for compression.
+    pub output_compression: u32,
+    /// The log records, encoded as described in the [`log`](crate::log) module.
+    pub log_records: MemoryRegion,
+}
+
+impl FinalizeResults {
+    /// The version of this layout.
+    pub const VERSION: u32 = 3;
+
+    /// The size of the results in bytes.
+    pub const SIZE: usize = 36;
+
+    /// Parse the results from their little-endian bytes.
+    pub fn from_le_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+        let fields: Vec<u32> = bytes
+            .as_chunks::<4>()
+            .0
+            .iter()
+            .map(|field| u32::from_le_bytes(*field))
+            .collect();
+        let region = |index: usize| MemoryRegion {
+            offset: fields[index * 2],
+            len: fields[index * 2 + 1],
+        };
+        Self {
+            output: region(0),
+            logs: [region(1), region(2)],
+            output_compression: fields[6],
+            log_records: MemoryRegion {
+                offset: fields[7],
+                len: fields[8],
+            },
+        }
+    }
+
+    /// Get the little-endian bytes of the results.
+    pub fn to_le_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        let fields = [
+            self.output.offset,
+            self.output.len,
+            self.logs[0].offset,
+            self.logs[0].len,
+            self.logs[1].offset,
+            self.logs[1].len,
+            self.output_compression,
+            self.log_records.offset,
+            self.log_records.len,
+        ];
+        for (chunk, field) in bytes.as_chunks_mut::<4>().0.iter_mut().zip(fields) {
+            chunk.copy_from_slice(&field.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Parse the results from bytes copied out of the provider's memory, checking their length and
+    /// output compression.
+    ///
+    /// The regions aren't checked, since they point into the provider's memory. Hosts that can
+    /// access it should use [`read`](Self::read) instead, which checks them too.
+    pub fn parse(bytes: &[u8]) -> Result<Self, HostError> {
+        let bytes = bytes
+            .try_into()
+            .map_err(|_| HostError::InvalidFinalizeResultsLength { len: bytes.len() })?;
+        let results = Self::from_le_bytes(bytes);
+        results.compression()?;
+        Ok(results)
+    }
+
+    /// Read the results at `offset`, the address returned by the provider's `finalize` export, in
+    /// the provider's `memory`, checking that they and the regions they point to are within it.
+    pub fn read(memory: &[u8], offset: usize) -> Result<Self, HostError> {
+        let bytes = offset
+            .checked_add(Self::SIZE)
+            .and_then(|end| memory.get(offset..end))
+            .ok_or(HostError::FinalizeResultsOutOfBounds {
+                offset,
+                memory_len: memory.len(),
+            })?;
+        let results = Self::parse(bytes)?;
+        results.output(memory)?;
+        results.logs(memory)?;
+        results.log_records(memory)?;
+        Ok(results)
+    }
+
+    /// Get the compression of the output.
+    pub fn compression(&self) -> Result<OutputCompression, HostError> {
+        OutputCompression::from_repr(self.output_compression).ok_or(
+            HostError::UnknownOutputCompression {
+                compression: self.output_compression,
+            },
+        )
+    }
+
+    /// Get the output from the provider's `memory`, which is compressed if
+    /// [`compression`](Self::compression) isn't [`OutputCompression::None`].
+    pub fn output<'a>(&self, memory: &'a [u8]) -> Result<&'a [u8], HostError> {
+        Self::region(memory, self.output, "output")
+    }
+
+    /// Get the logs from the provider's `memory`, joining the two parts of the ring buffer.
+    pub fn logs(&self, memory: &[u8]) -> Result<Vec<u8>, HostError> {
+        let first = Self::region(memory, self.logs[0], "logs")?;
+        let second = Self::region(memory, self.logs[1], "logs")?;
+        Ok([first, second].concat())
+    }
+
+    /// Get the log records from the provider's `memory`.
+    pub fn log_records<'a>(&self, memory: &'a [u8]) -> Result<&'a [u8], HostError> {
+        Self::region(memory, self.log_records, "log records")
+    }
+
+    fn region<'a>(
+        memory: &'a [u8],
+        region: MemoryRegion,
+        name: &'static str,
+    ) -> Result<&'a [u8], HostError> {
+        region.get(memory).ok_or(HostError::RegionOutOfBounds {
+            region: name,
+            region_offset: region.offset,
+            region_len: region.len,
+            memory_len: memory.len(),
+        })
+    }
+
+    /// Check the version returned by the provider's `finalize_results_version` export.
+    pub fn check_version(version: u32) -> Result<(), HostError> {
+        if version == Self::VERSION {
+            Ok(())
+        } else {
+            Err(HostError::UnsupportedFinalizeResultsVersion { version })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const _: () = assert!(std::mem::size_of::<FinalizeResults>() == FinalizeResults::SIZE);
+
+    fn results() -> FinalizeResults {
+        FinalizeResults {
+            output: MemoryRegion { offset: 36, len: 3 },
+            logs: [
+                MemoryRegion { offset: 41, len: 2 },
+                MemoryRegion { offset: 39, len: 2 },
+            ],
+            output_compression: OutputCompression::None as u32,
+            log_records: MemoryRegion { offset: 43, len: 3 },
+        }
+    }
+
+    fn memory() -> Vec<u8> {
+        let mut memory = results().to_le_bytes().to_vec();
+        memory.extend(b"outcdabrec");
+        memory
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let results = results();
+        assert_eq!(
+            FinalizeResults::from_le_bytes(&results.to_le_bytes()),
+            results
+        );
+    }
+
+    #[test]
+    fn test_read() {
+        let memory = memory();
+        let results = FinalizeResults::read(&memory, 0).unwrap();
+        assert_eq!(results.output(&memory), Ok(&b"out"[..]));
+        assert_eq!(results.logs(&memory), Ok(b"abcd".to_vec()));
+        assert_eq!(results.log_records(&memory), Ok(&b"rec"[..]));
+        assert_eq!(results.compression(), Ok(OutputCompression::None));
+    }
+
+    #[test]
+    fn test_read_output_compression() {
+        let mut memory = memory();
+        let mut results = results();
+        results.output_compression = OutputCompression::Zstd as u32;
+        memory[..FinalizeResults::SIZE].copy_from_slice(&results.to_le_bytes());
+        assert_eq!(
+            FinalizeResults::read(&memory, 0).map(|results| results.compression()),
+            Ok(Ok(OutputCompression::Zstd))
+        );
+
+        results.output_compression = 2;
+        memory[..FinalizeResults::SIZE].copy_from_slice(&results.to_le_bytes());
+        assert_eq!(
+            FinalizeResults::read(&memory, 0),
+            Err(HostError::UnknownOutputCompression { compression: 2 })
+        );
+    }
+
+    #[test]
+    fn test_read_results_out_of_bounds() {
+        let memory = memory();
+        for offset in [memory.len() - FinalizeResults::SIZE + 1, usize::MAX] {
+            assert_eq!(
+                FinalizeResults::read(&memory, offset),
+                Err(HostError::FinalizeResultsOutOfBounds {
+                    offset,
+                    memory_len: memory.len()
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_region_out_of_bounds() {
+        let mut memory = memory();
+        let mut results = results();
+        results.logs[1] = MemoryRegion {
+            offset: u32::MAX,
+            len: u32::MAX,
+        };
+        memory[..FinalizeResults::SIZE].copy_from_slice(&results.to_le_bytes());
+        assert_eq!(
+            FinalizeResults::read(&memory, 0),
+            Err(HostError::RegionOutOfBounds {
+                region: "logs",
+                region_offset: u32::MAX,
+                region_len: u32::MAX,
+                memory_len: memory.len()
+            })
+        );
+
+        results.output.len = 100;
+        assert!(matches!(
+            results.output(&memory),
+            Err(HostError::RegionOutOfBounds {
+                region: "output",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse() {
+        let results = results();
+        assert_eq!(FinalizeResults::parse(&results.to_le_bytes()), Ok(results));
+        for len in [0, FinalizeResults::SIZE - 1, FinalizeResults::SIZE + 1] {
+            assert_eq!(
+                FinalizeResults::parse(&vec![0; len]),
+                Err(HostError::InvalidFinalizeResultsLength { len })
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_and_read_arbitrary_bytes() {
+        // xorshift, so failures are reproducible without a fuzzing dependency
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..10_000 {
+            let len = (next() % 64) as usize;
+            let memory: Vec<u8> = (0..len)
+                .map(|_| match next() % 4 {
+                    // small values are more likely to be valid offsets and lengths
+                    0 => (next() % 64) as u8,
+                    1 => 0,
+                    _ => next() as u8,
+                })
+                .collect();
+            if let Ok(results) = FinalizeResults::parse(&memory) {
+                assert_eq!(results.to_le_bytes()[..], memory[..]);
+            }
+            let offset = (next() % 48) as usize;
+            if let Ok(results) = FinalizeResults::read(&memory, offset) {
+                let output = results.output(&memory).unwrap();
+                assert_eq!(output.len(), results.output.len as usize);
+                let logs = results.logs(&memory).unwrap();
+                assert_eq!(
+                    logs.len(),
+                    results.logs[0].len as usize + results.logs[1].len as usize
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_input_offset() {
+        assert_eq!(input_offset(1024, 10), Ok(1024));
+        assert_eq!(
+            input_offset(0, 10),
+            Err(HostError::InputTooLarge { len: 10 })
+        );
+    }
+
+    #[test]
+    fn test_input_encoding() {
+        for encoding in [InputEncoding::Msgpack, InputEncoding::Json] {
+            assert_eq!(InputEncoding::from_repr(encoding as u32), Some(encoding));
+        }
+        assert_eq!(InputEncoding::from_repr(2), None);
+    }
+
+    #[test]
+    fn test_input_compression() {
+        for compression in [
+            InputCompression::None,
+            InputCompression::Gzip,
+            InputCompression::Zstd,
+        ] {
+            assert_eq!(
+                InputCompression::from_repr(compression as u32),
+                Some(compression)
+            );
+        }
+        assert_eq!(InputCompression::from_repr(3), None);
+    }
+
+    #[test]
+    fn test_output_encoding() {
+        for encoding in [
+            OutputEncoding::Msgpack,
+            OutputEncoding::Json,
+            OutputEncoding::CompactJson,
+        ] {
+            assert_eq!(OutputEncoding::from_repr(encoding as u32), Some(encoding));
+        }
+        assert_eq!(OutputEncoding::from_repr(3), None);
+    }
+
+    #[test]
+    fn test_check_version() {
+        assert_eq!(
+            FinalizeResults::check_version(FinalizeResults::VERSION),
+            Ok(())
+        );
+        assert_eq!(
+            FinalizeResults::check_version(1),
+            Err(HostError::UnsupportedFinalizeResultsVersion { version: 1 })
+        );
+    }
+}
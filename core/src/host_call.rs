@@ -0,0 +1,13 @@
+//! Poll-based calls to the host, for data the host provides lazily rather than as part of the
+//! input, e.g. currency rates.
+//!
+//! A call is started with `shopify_function_host_call_start`, which returns a [`HostCallId`]
+//! immediately, and is then polled with `shopify_function_host_call_poll` until its result is
+//! ready. Polling returns the result as a value, or one of the errors
+//! [`ErrorCode::HostCallPending`](crate::read::ErrorCode::HostCallPending),
+//! [`ErrorCode::HostCallUnsupported`](crate::read::ErrorCode::HostCallUnsupported) or
+//! [`ErrorCode::UnknownHostCall`](crate::read::ErrorCode::UnknownHostCall). Because a call never
+//! blocks, hosts can add capabilities that fetch data asynchronously without changing the ABI.
+
+/// Identifies a call started with `shopify_function_host_call_start`.
+pub type HostCallId = usize;
@@ -1,3 +1,8 @@
+pub mod abi;
+pub mod codec;
+pub mod host;
+pub mod host_call;
+pub mod log;
 pub mod read;
 pub mod write;
 
@@ -0,0 +1,45 @@
+//! Log records with a level, which functions log alongside their plain-text logs.
+//!
+//! The provider keeps the newest records, and its `finalize` export locates them with
+//! [`FinalizeResults::log_records`](crate::host::FinalizeResults::log_records). They're encoded as
+//! a msgpack array of maps, oldest first, each with these keys:
+//!
+//! - `level`: the [`Level`] of the record, as an integer.
+//! - `sequence`: the number of records logged before it, counting those dropped to make room for
+//!   newer ones, so gaps in the sequence show where records were dropped.
+//! - `message`: the message, as a string.
+
+use std::fmt;
+
+/// The level of a log record.
+#[repr(u32)]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    strum::FromRepr,
+    strum::EnumIter,
+    strum::EnumMessage,
+)]
+pub enum Level {
+    /// Information about what the function did.
+    Info = 0,
+    /// Something unexpected that the function handled.
+    Warn = 1,
+    /// Something that stopped the function from working as intended.
+    Error = 2,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+        })
+    }
+}
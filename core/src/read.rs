@@ -1,6 +1,13 @@
-use std::error::Error;
+use std::{error::Error, fmt};
 
 /// A type alias to represent raw NaN-boxed values.
+///
+/// Values passed between the guest and the provider in Wasm are always `u64`, i.e. a single `i64`
+/// parameter or result. Values are only `u128` in native builds, where pointers don't fit in the
+/// NaN-box's value bits, and where the API calls the provider's functions directly rather than
+/// across a Wasm boundary, so there's no packing to avoid. For that reason there are no host calls
+/// that pass values as two `i64`s. Memory64 guests would need a wider encoding, and aren't
+/// supported.
 #[cfg(target_pointer_width = "64")]
 pub type Val = u128;
 #[cfg(target_pointer_width = "32")]
@@ -73,6 +80,10 @@ impl NanBox {
     /// The maximum number that can be encoed in the number of bits reserved for
     /// [`Self::VALUE_LENGTH_SIZE`].
     /// This is (2^14) - 1.
+    ///
+    /// Longer lengths are truncated to this value when encoded, so a value
+    /// with this length has at least this many elements and its exact length
+    /// must be queried from the provider.
     pub const MAX_VALUE_LENGTH: usize = (1 << Self::VALUE_LENGTH_SIZE) - 1;
     /// Mask to retrive the value from the payload.
     const VALUE_MASK: Val = Self::PAYLOAD_MASK & !Self::TAG_MASK;
@@ -102,6 +113,17 @@ impl NanBox {
         Self::encode(0, 0, Tag::Null)
     }
 
+    /// Create the representation of a missing object property, which is a null with a payload of
+    /// 1, so it is read as null but can be told apart from an explicit `null`.
+    pub fn missing() -> Self {
+        Self::encode(1, 0, Tag::Null)
+    }
+
+    /// Whether this is a missing object property, rather than any other value, including `null`.
+    pub fn is_missing(&self) -> bool {
+        *self == Self::missing()
+    }
+
     /// Create a new NaN-boxed number.
     pub fn number(val: f64) -> Self {
         assert!(!val.is_nan());
@@ -128,7 +150,29 @@ impl NanBox {
         Self::encode(ptr as _, len, Tag::Array)
     }
 
-    pub fn try_decode(&self) -> Result<ValueRef, Box<dyn Error>> {
+    /// Create a new NaN-boxed byte array.
+    pub fn bytes(ptr: usize, len: usize) -> Self {
+        Self::encode(ptr as _, len, Tag::Bytes)
+    }
+
+    /// Get the type of the value from its tag, without decoding its payload. Values with an
+    /// unknown tag are errors.
+    pub fn kind(&self) -> ValueKind {
+        if self.0 & Self::NAN_MASK != Self::NAN_MASK {
+            return ValueKind::Number;
+        }
+        match ((self.0 & Self::TAG_MASK) >> Self::VALUE_SIZE) as u8 {
+            0 => ValueKind::Null,
+            1 => ValueKind::Bool,
+            3 => ValueKind::String,
+            4 => ValueKind::Object,
+            5 => ValueKind::Array,
+            6 => ValueKind::Bytes,
+            _ => ValueKind::Error,
+        }
+    }
+
+    pub fn try_decode(&self) -> Result<ValueRef, DecodeError> {
         if self.0 & Self::NAN_MASK != Self::NAN_MASK {
             #[cfg(target_pointer_width = "32")]
             let value = self.0;
@@ -149,19 +193,22 @@ impl NanBox {
         match tag {
             Tag::Bool => Ok(ValueRef::Bool(ptr != 0)),
             Tag::Null => Ok(ValueRef::Null),
-            Tag::Number => unreachable!("Number values are not NaN-boxed."),
+            // numbers are stored as floats rather than NaN-boxed
+            Tag::Number => Err(DecodeError::InvalidTag(tag as u8)),
             Tag::Array => Ok(ValueRef::Array { ptr, len }),
             Tag::String => Ok(ValueRef::String { ptr, len }),
             Tag::Object => Ok(ValueRef::Object { ptr, len }),
+            Tag::Bytes => Ok(ValueRef::Bytes { ptr, len }),
             Tag::Error => Ok(ValueRef::Error(
-                ErrorCode::from_repr(val as usize).unwrap_or(ErrorCode::Unknown),
+                ErrorCode::from_repr(val as u32).unwrap_or(ErrorCode::Unknown),
             )),
         }
     }
 
-    fn tag(&self) -> Result<Tag, Box<dyn Error>> {
-        let tag = (self.0 & Self::PAYLOAD_MASK) >> Self::VALUE_SIZE;
-        Tag::from_val(tag)
+    fn tag(&self) -> Result<Tag, DecodeError> {
+        // the payload bits above the value are the 4 tag bits
+        let tag = ((self.0 & Self::PAYLOAD_MASK) >> Self::VALUE_SIZE) as u8;
+        Tag::from_u8(tag)
     }
 
     fn encode(ptr: usize, len: usize, tag: Tag) -> Self {
@@ -171,6 +218,27 @@ impl NanBox {
     }
 }
 
+/// The type of a NaN-boxed value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueKind {
+    /// Null.
+    Null,
+    /// A boolean.
+    Bool,
+    /// A number, integer or float.
+    Number,
+    /// A UTF-8 string.
+    String,
+    /// An object.
+    Object,
+    /// An array.
+    Array,
+    /// Binary data, such as a hash.
+    Bytes,
+    /// A read error.
+    Error,
+}
+
 /// An unwrapped representation of a NaN-boxed value.
 #[derive(Debug, PartialEq)]
 pub enum ValueRef {
@@ -180,12 +248,13 @@ pub enum ValueRef {
     String { ptr: usize, len: usize },
     Object { ptr: usize, len: usize },
     Array { ptr: usize, len: usize },
+    Bytes { ptr: usize, len: usize },
     Error(ErrorCode),
 }
 
-#[derive(Debug, Clone, Copy, strum::EnumIter, strum::FromRepr)]
+#[derive(Debug, Clone, Copy, strum::EnumIter, strum::EnumMessage, strum::FromRepr)]
 #[repr(u8)]
-enum Tag {
+pub(crate) enum Tag {
     /// Null type.
     Null = 0,
     /// Boolean type.
@@ -198,6 +267,8 @@ enum Tag {
     Object = 4,
     /// An array pointer.
     Array = 5,
+    /// A byte array pointer, for msgpack binary data.
+    Bytes = 6,
     /// An error code.
     Error = NanBox::MAX_TAG_VALUE, // this should be the last tag
 }
@@ -207,17 +278,34 @@ impl Tag {
         *self as Val
     }
 
-    fn from_val(v: Val) -> Result<Self, Box<dyn Error>> {
-        match u8::try_from(v) {
-            Ok(v) => Self::from_repr(v).ok_or_else(|| format!("Unknown tag: {v}").into()),
-            Err(_) => Err(format!("Unknown tag: {v}").into()),
+    fn from_u8(v: u8) -> Result<Self, DecodeError> {
+        Self::from_repr(v).ok_or(DecodeError::InvalidTag(v))
+    }
+}
+
+/// An error decoding a NaN-boxed value.
+///
+/// This has no heap-allocated payload, so decoding doesn't pull allocation and formatting into
+/// guest builds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The tag bits are not the tag of a NaN-boxed value.
+    InvalidTag(u8),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidTag(tag) => write!(f, "Unknown tag: {tag}"),
         }
     }
 }
 
+impl Error for DecodeError {}
+
 /// An error code.
-#[derive(Debug, Clone, Copy, PartialEq, strum::EnumIter, strum::FromRepr)]
-#[repr(usize)]
+#[derive(Debug, Clone, Copy, PartialEq, strum::EnumIter, strum::EnumMessage, strum::FromRepr)]
+#[repr(u32)]
 #[non_exhaustive]
 pub enum ErrorCode {
     /// The NanBox could not be decoded.
@@ -234,6 +322,34 @@ pub enum ErrorCode {
     IndexOutOfBounds = 5,
     /// The value is not indexable. Indexable values are objects and arrays.
     NotIndexable = 6,
+    /// The interned string ID does not exist in the string interner.
+    UnknownInternedString = 7,
+    /// The host call has not finished yet, and should be polled again.
+    HostCallPending = 8,
+    /// The host does not support the capability requested by the host call.
+    HostCallUnsupported = 9,
+    /// The host call ID does not exist.
+    UnknownHostCall = 10,
+    /// The shape ID does not exist.
+    UnknownShape = 11,
+    /// The value is msgpack binary data or an extension type, which can't be read.
+    UnsupportedValue = 12,
+    /// The input contains the msgpack marker reserved by the specification, so it can't be read.
+    ReservedMarker = 13,
+    /// An object in the input has the same key more than once, which the host asked to reject.
+    DuplicateObjectKey = 14,
+    /// The keys of an object in the input aren't sorted, which the host asked to reject.
+    UnsortedObjectKeys = 15,
+    /// The iterator ID does not exist.
+    UnknownIterator = 16,
+    /// The work budget the guest set is spent.
+    BudgetExceeded = 17,
+    /// The pinned value ID does not exist, or the value was unpinned.
+    UnknownPinnedValue = 18,
+    /// The provider ran out of memory for reading the input, e.g. because the host limited it.
+    OutOfMemory = 19,
+    /// The provider was built without Unicode normalization, so it can't compare normalized strings.
+    NormalizationUnsupported = 20,
     /// An unknown error code.
     Unknown,
 }
@@ -243,6 +359,37 @@ mod tests {
     use super::*;
     use strum::IntoEnumIterator;
 
+    #[test]
+    fn test_kind() {
+        assert_eq!(NanBox::null().kind(), ValueKind::Null);
+        assert_eq!(NanBox::bool(true).kind(), ValueKind::Bool);
+        assert_eq!(NanBox::number(-1.5).kind(), ValueKind::Number);
+        assert_eq!(NanBox::number(f64::INFINITY).kind(), ValueKind::Number);
+        assert_eq!(NanBox::string(8, 3).kind(), ValueKind::String);
+        assert_eq!(NanBox::obj(8, 3).kind(), ValueKind::Object);
+        assert_eq!(NanBox::array(8, 3).kind(), ValueKind::Array);
+        assert_eq!(NanBox::bytes(8, 3).kind(), ValueKind::Bytes);
+        assert_eq!(
+            NanBox::error(ErrorCode::NotAnObject).kind(),
+            ValueKind::Error
+        );
+        // unknown tags are errors, as they can't be decoded
+        let unknown_tag = NanBox::from_bits(NanBox::null().to_bits() | (7 << NanBox::VALUE_SIZE));
+        assert_eq!(unknown_tag.kind(), ValueKind::Error);
+        assert_eq!(unknown_tag.try_decode(), Err(DecodeError::InvalidTag(7)));
+    }
+
+    #[test]
+    fn test_decode_nan_boxed_number_tag() {
+        let number_tag = NanBox::encode(0, 0, Tag::Number);
+        assert_eq!(number_tag.kind(), ValueKind::Error);
+        assert_eq!(number_tag.try_decode(), Err(DecodeError::InvalidTag(2)));
+        assert_eq!(
+            DecodeError::InvalidTag(2).to_string(),
+            "Unknown tag: 2".to_string()
+        );
+    }
+
     #[test]
     fn test_tag_less_than_max_tag_value() {
         Tag::iter().for_each(|tag| {
@@ -309,6 +456,15 @@ mod tests {
         let null = NanBox::null();
         let value_ref = null.try_decode().unwrap();
         assert_eq!(value_ref, ValueRef::Null);
+        assert!(!null.is_missing());
+    }
+
+    #[test]
+    fn test_missing_roundtrip() {
+        let missing = NanBox::missing();
+        assert_eq!(missing.kind(), ValueKind::Null);
+        assert_eq!(missing.try_decode().unwrap(), ValueRef::Null);
+        assert!(missing.is_missing());
     }
 
     #[test]
@@ -347,6 +503,13 @@ mod tests {
         assert_eq!(value_ref, ValueRef::Object { ptr, len });
     }
 
+    #[test]
+    fn test_bytes_roundtrip() {
+        let boxed = NanBox::bytes(1, 2);
+        let value_ref = boxed.try_decode().unwrap();
+        assert_eq!(value_ref, ValueRef::Bytes { ptr: 1, len: 2 });
+    }
+
     #[test]
     fn test_error_roundtrip() {
         ErrorCode::iter().for_each(|code| {
@@ -1,5 +1,8 @@
-#[repr(usize)]
-#[derive(Debug, strum::FromRepr, PartialEq, Eq)]
+/// The result of a write operation.
+#[repr(u32)]
+#[derive(
+    Debug, Clone, Copy, strum::FromRepr, strum::EnumIter, strum::EnumMessage, PartialEq, Eq,
+)]
 pub enum WriteResult {
     /// The write operation was successful.
     Ok = 0,
@@ -19,4 +22,17 @@ pub enum WriteResult {
     ArrayLengthError = 7,
     /// The value is not an array, but an operation expected an array.
     NotAnArray = 8,
+    /// The interned string ID does not exist in the string interner.
+    UnknownInternedString = 9,
+    /// The checkpoint does not exist, or was removed by rolling back to an earlier checkpoint.
+    UnknownCheckpoint = 10,
+    /// The value is not a string being written in chunks, but an operation expected one.
+    NotAString = 11,
+    /// The output can't be discarded, because the host already read part of it.
+    OutputAlreadyRead = 12,
+    /// The input value could not be read, so it could not be written to the output.
+    InvalidInputValue = 13,
+    /// The MessagePack fragment is not exactly one complete value, so it could not be written to
+    /// the output.
+    InvalidMsgpack = 14,
 }
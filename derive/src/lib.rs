@@ -0,0 +1,229 @@
+//! # Shopify Function Wasm API derive macros
+//!
+//! Derives `Serialize` and `Deserialize` from `shopify_function_wasm_api` for structs with named
+//! fields. Enable the `derive` feature of `shopify_function_wasm_api` rather than depending on this
+//! crate directly.
+//!
+//! Each field is written and read under a key that is interned once and cached, so a derived
+//! implementation makes the same provider calls as a hand-written one using
+//! `CachedInternedStringId`.
+//!
+//! ## Attributes
+//!
+//! - `#[shopify_function(rename_all = "camelCase")]` on the struct renames every field's key.
+//!   `"camelCase"` and `"snake_case"` are supported.
+//! - `#[shopify_function(rename = "key")]` on a field sets its key, overriding `rename_all`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, parse_quote, Data, DeriveInput, Fields, GenericParam, Generics, Ident,
+    LitStr,
+};
+
+/// Derive `shopify_function_wasm_api::Serialize`, writing the struct as an object with one
+/// property per field.
+#[proc_macro_derive(Serialize, attributes(shopify_function))]
+pub fn derive_serialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_serialize(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derive `shopify_function_wasm_api::Deserialize`, reading each field from the property of the
+/// same key. Missing properties are read as null, so only `Option` fields may be absent.
+#[proc_macro_derive(Deserialize, attributes(shopify_function))]
+pub fn derive_deserialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_deserialize(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_serialize(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = named_fields(&input)?;
+    let name = &input.ident;
+    let generics = add_trait_bounds(
+        input.generics.clone(),
+        quote!(::shopify_function_wasm_api::Serialize),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let keys = key_statics(&fields);
+    let idents = fields.iter().map(|field| &field.ident);
+    let indices = 0..fields.len();
+    let len = fields.len();
+
+    Ok(quote! {
+        impl #impl_generics ::shopify_function_wasm_api::Serialize for #name #ty_generics #where_clause {
+            fn serialize(
+                &self,
+                context: &mut ::shopify_function_wasm_api::Context,
+            ) -> ::core::result::Result<(), ::shopify_function_wasm_api::write::Error> {
+                #keys
+                context.write_object_fields(
+                    |object| {
+                        #(object.interned_field(KEYS[#indices].load(), &self.#idents)?;)*
+                        ::core::result::Result::Ok(())
+                    },
+                    #len,
+                )
+            }
+        }
+    })
+}
+
+fn expand_deserialize(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = named_fields(&input)?;
+    let name = &input.ident;
+    let generics = add_trait_bounds(
+        input.generics.clone(),
+        quote!(::shopify_function_wasm_api::Deserialize),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let keys = key_statics(&fields);
+    let idents = fields.iter().map(|field| &field.ident);
+    let indices = 0..fields.len();
+
+    Ok(quote! {
+        impl #impl_generics ::shopify_function_wasm_api::Deserialize for #name #ty_generics #where_clause {
+            fn deserialize(
+                value: &::shopify_function_wasm_api::Value,
+            ) -> ::core::result::Result<Self, ::shopify_function_wasm_api::read::Error> {
+                #keys
+                if !value.is_obj() {
+                    return ::core::result::Result::Err(
+                        ::shopify_function_wasm_api::read::Error::InvalidType,
+                    );
+                }
+                ::core::result::Result::Ok(Self {
+                    #(#idents: ::shopify_function_wasm_api::Deserialize::deserialize(
+                        &value.get_interned_obj_prop(KEYS[#indices].load()),
+                    )?,)*
+                })
+            }
+        }
+    })
+}
+
+struct Field<'a> {
+    ident: &'a Ident,
+    key: String,
+}
+
+fn named_fields(input: &DeriveInput) -> syn::Result<Vec<Field<'_>>> {
+    let rename_all = container_rename_all(input)?;
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "only structs can be derived",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "only structs with named fields can be derived",
+        ));
+    };
+    fields
+        .named
+        .iter()
+        .map(|field| {
+            let ident = field.ident.as_ref().expect("named field");
+            let mut rename = None;
+            for attr in &field.attrs {
+                if !attr.path().is_ident("shopify_function") {
+                    continue;
+                }
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("rename") {
+                        rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                        Ok(())
+                    } else {
+                        Err(meta.error("unsupported field attribute"))
+                    }
+                })?;
+            }
+            let key = rename.unwrap_or_else(|| rename_all.apply(&ident.to_string()));
+            Ok(Field { ident, key })
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy)]
+enum RenameAll {
+    None,
+    CamelCase,
+}
+
+impl RenameAll {
+    fn apply(self, field: &str) -> String {
+        let field = field.strip_prefix("r#").unwrap_or(field);
+        match self {
+            RenameAll::None => field.to_string(),
+            RenameAll::CamelCase => {
+                let mut key = String::with_capacity(field.len());
+                let mut upper = false;
+                for c in field.chars() {
+                    if c == '_' {
+                        upper = !key.is_empty();
+                    } else if upper {
+                        key.extend(c.to_uppercase());
+                        upper = false;
+                    } else {
+                        key.push(c);
+                    }
+                }
+                key
+            }
+        }
+    }
+}
+
+fn container_rename_all(input: &DeriveInput) -> syn::Result<RenameAll> {
+    let mut rename_all = RenameAll::None;
+    for attr in &input.attrs {
+        if !attr.path().is_ident("shopify_function") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename_all") {
+                let value = meta.value()?.parse::<LitStr>()?;
+                rename_all = match value.value().as_str() {
+                    "camelCase" => RenameAll::CamelCase,
+                    "snake_case" => RenameAll::None,
+                    _ => return Err(syn::Error::new_spanned(
+                        value,
+                        "unsupported `rename_all` value, expected \"camelCase\" or \"snake_case\"",
+                    )),
+                };
+                Ok(())
+            } else {
+                Err(meta.error("unsupported container attribute"))
+            }
+        })?;
+    }
+    Ok(rename_all)
+}
+
+/// The keys are declared inside the generated function so they don't collide with items in the
+/// caller's module. Statics in generic functions are shared by every instantiation.
+fn key_statics(fields: &[Field]) -> TokenStream2 {
+    let keys = fields.iter().map(|field| &field.key);
+    let len = fields.len();
+    quote! {
+        static KEYS: [::shopify_function_wasm_api::CachedInternedStringId; #len] = [
+            #(::shopify_function_wasm_api::CachedInternedStringId::new(#keys),)*
+        ];
+    }
+}
+
+fn add_trait_bounds(mut generics: Generics, bound: TokenStream2) -> Generics {
+    for param in &mut generics.params {
+        if let GenericParam::Type(param) = param {
+            param.bounds.push(parse_quote!(#bound));
+        }
+    }
+    generics
+}
@@ -0,0 +1,168 @@
+//! A corpus of conformance cases for Shopify Functions, and a runner for them.
+//!
+//! Each case is a JSON file in [`corpus_dir`] describing a function to run, its input, the output
+//! it must produce, and the most fuel and memory it may use. The corpus is written in terms of
+//! function behavior rather than a particular language, so SDKs and providers in other languages
+//! can depend on this crate (or the corpus directory alone) and run the same cases against their
+//! own builds of each function, making behavior and performance expectations explicit across all
+//! of them. See `corpus/README.md` for the file format.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use shopify_function_wasm_api_core::log::Level;
+
+use crate::run_module;
+
+/// A conformance case.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Case {
+    /// The name of the case, which is the name of its file without the extension.
+    #[serde(skip)]
+    pub name: String,
+    /// The function to run, e.g. `cart-checkout-validation-wasm-api`.
+    pub function: String,
+    /// The input to the function.
+    pub input: serde_json::Value,
+    /// The output the function must produce.
+    pub expected_output: serde_json::Value,
+    /// The most fuel the function may consume.
+    pub max_fuel: u64,
+    /// The most memory in bytes the function and provider may use, combined.
+    pub max_memory_bytes: usize,
+}
+
+/// A log record written by a function, decoded from [`RunResult::log_records`](crate::RunResult).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRecord {
+    /// The level of the record.
+    pub level: Level,
+    /// The number of records logged before this one, including any that were dropped.
+    pub sequence: u64,
+    /// The message.
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EncodedLogRecord {
+    level: u32,
+    sequence: u64,
+    message: String,
+}
+
+/// Decode the log records the provider's `finalize` locates, oldest first, failing if any of them
+/// has a level this crate doesn't know.
+pub fn decode_log_records(bytes: &[u8]) -> Result<Vec<LogRecord>> {
+    let records: Vec<EncodedLogRecord> =
+        rmp_serde::from_slice(bytes).context("Failed to decode the log records")?;
+    records
+        .into_iter()
+        .map(|record| {
+            let level = Level::from_repr(record.level).with_context(|| {
+                format!(
+                    "Log record {} has unknown level {}",
+                    record.sequence, record.level
+                )
+            })?;
+            Ok(LogRecord {
+                level,
+                sequence: record.sequence,
+                message: record.message,
+            })
+        })
+        .collect()
+}
+
+/// The directory of the corpus in this crate.
+pub fn corpus_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("corpus")
+}
+
+/// Load every case in `dir`, sorted by name.
+pub fn load_corpus(dir: &Path) -> Result<Vec<Case>> {
+    let mut cases = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut case: Case = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        case.name = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        cases.push(case);
+    }
+    cases.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(cases)
+}
+
+impl Case {
+    /// Run the case with the function at `module_path`, returning a description of each way the
+    /// run did not meet the case's expectations.
+    pub fn run(&self, module_path: &Path, provider_path: &Path) -> Result<Vec<String>> {
+        let input = rmp_serde::to_vec(&self.input)?;
+        let result = run_module(module_path, provider_path, &input)?;
+        let output: serde_json::Value = rmp_serde::from_slice(&result.output)
+            .context("Failed to decode the function output")?;
+
+        let mut failures = Vec::new();
+        if output != self.expected_output {
+            failures.push(format!(
+                "expected output {}, got {output}",
+                self.expected_output
+            ));
+        }
+        if result.fuel > self.max_fuel {
+            failures.push(format!(
+                "consumed {} fuel, more than the maximum of {}",
+                result.fuel, self.max_fuel
+            ));
+        }
+        if result.memory_bytes > self.max_memory_bytes {
+            failures.push(format!(
+                "used {} bytes of memory, more than the maximum of {}",
+                result.memory_bytes, self.max_memory_bytes
+            ));
+        }
+        Ok(failures)
+    }
+}
+
+/// Run every case, using `module_path` to find the module for each case's function. Fails with
+/// every case that did not meet its expectations, rather than stopping at the first one.
+pub fn run_corpus(
+    cases: &[Case],
+    provider_path: &Path,
+    module_path: impl Fn(&str) -> PathBuf,
+) -> Result<()> {
+    let mut failures = Vec::new();
+    for case in cases {
+        match case.run(&module_path(&case.function), provider_path) {
+            Ok(case_failures) => failures.extend(
+                case_failures
+                    .into_iter()
+                    .map(|failure| format!("{}: {failure}", case.name)),
+            ),
+            Err(e) => failures.push(format!("{}: failed to run: {e:?}", case.name)),
+        }
+    }
+    if !failures.is_empty() {
+        bail!(
+            "{} conformance failure(s):\n{}",
+            failures.len(),
+            failures.join("\n")
+        );
+    }
+    Ok(())
+}
@@ -1,10 +1,23 @@
 use anyhow::Result;
+use shopify_function_wasm_api_core::host::{
+    input_offset, FinalizeResults, InputEncoding, OutputEncoding,
+};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::LazyLock;
+use std::sync::{LazyLock, Mutex};
+use wasmtime::{Config, Engine, Linker, Module, Store};
 
-fn workspace_root() -> std::path::PathBuf {
+pub mod conformance;
+
+const STARTING_FUEL: u64 = u64::MAX;
+
+const EXAMPLES_DIR: &str = "target/wasm32-unknown-unknown/release/examples";
+const WASIP2_EXAMPLES_DIR: &str = "target/wasip2/wasm32-wasip2/release/examples";
+
+fn workspace_root() -> PathBuf {
     let manifest_dir = env!("CARGO_MANIFEST_DIR");
-    std::path::PathBuf::from(manifest_dir).join("..")
+    PathBuf::from(manifest_dir).join("..")
 }
 
 /// Builds the provider library to a `.wasm` file
@@ -25,6 +38,98 @@ fn build_provider() -> Result<()> {
     Ok(())
 }
 
+/// Builds the provider library with the `json-output` feature to a `.wasm` file, in a separate
+/// target directory so it doesn't replace the production provider
+fn build_provider_with_json_output() -> Result<()> {
+    let status = Command::new("cargo")
+        .args([
+            "build",
+            "--release",
+            "--target",
+            "wasm32-unknown-unknown",
+            "-p",
+            "shopify_function_provider",
+            "--features",
+            "json-output",
+            "--target-dir",
+        ])
+        .arg(workspace_root().join("target/json-output"))
+        .status()?;
+    if !status.success() {
+        anyhow::bail!(status);
+    }
+    Ok(())
+}
+
+/// A build of the provider with another opt level or other features than the production build, to
+/// check that it behaves the same and to measure its fuel and size.
+#[derive(Debug, Clone, Copy)]
+pub struct ProviderVariant {
+    /// The name of the variant, which is also the name of the target directory it's built in.
+    pub name: &'static str,
+    /// The `opt-level` of the release profile.
+    pub opt_level: &'static str,
+    /// The provider features that are enabled.
+    pub features: &'static [&'static str],
+}
+
+/// The provider variants that are compared with the production build.
+pub const PROVIDER_VARIANTS: &[ProviderVariant] = &[
+    ProviderVariant {
+        name: "opt-level-s",
+        opt_level: "s",
+        features: &[],
+    },
+    ProviderVariant {
+        name: "opt-level-z",
+        opt_level: "z",
+        features: &[],
+    },
+    ProviderVariant {
+        name: "all-features",
+        opt_level: "3",
+        features: &[
+            "json-output",
+            "gzip-input",
+            "zstd-input",
+            "zstd-output",
+            "nfc-normalization",
+        ],
+    },
+];
+
+impl ProviderVariant {
+    fn target_dir(&self) -> PathBuf {
+        workspace_root()
+            .join("target/provider-variants")
+            .join(self.name)
+    }
+}
+
+/// Builds the provider variant to a `.wasm` file, in a separate target directory so it doesn't
+/// replace the production provider
+fn build_provider_variant(variant: &ProviderVariant) -> Result<()> {
+    let status = Command::new("cargo")
+        .args([
+            "build",
+            "--release",
+            "--target",
+            "wasm32-unknown-unknown",
+            "-p",
+            "shopify_function_provider",
+            "--features",
+            &variant.features.join(","),
+            "--target-dir",
+        ])
+        .arg(variant.target_dir())
+        .env("CARGO_PROFILE_RELEASE_OPT_LEVEL", variant.opt_level)
+        .status()?;
+    if !status.success() {
+        anyhow::bail!(status);
+    }
+    Ok(())
+}
+
 /// Builds the example to a `.wasm` file
 fn build_example(name: &str) -> Result<()> {
     let status = Command::new("cargo")
@@ -45,10 +150,36 @@ fn build_example(name: &str) -> Result<()> {
     Ok(())
 }
 
-/// Applies the trampoline to the example
-fn apply_trampoline_to_example(name: &str) -> Result<()> {
-    let workspace_root = workspace_root();
-    let examples_dir = workspace_root.join("target/wasm32-unknown-unknown/release/examples");
+/// Builds the example to a core `.wasm` module for `wasm32-wasip2`, in a separate target directory
+/// so it doesn't replace the `wasm32-unknown-unknown` build
+fn build_wasip2_example(name: &str) -> Result<()> {
+    let status = Command::new("cargo")
+        .args([
+            "build",
+            "--release",
+            "--target",
+            "wasm32-wasip2",
+            "-p",
+            "shopify_function_wasm_api",
+            "--example",
+            name,
+            "--target-dir",
+        ])
+        .arg(workspace_root().join("target/wasip2"))
+        // functions are core modules, but the target's linker wraps them in a component by default
+        .env(
+            "CARGO_TARGET_WASM32_WASIP2_RUSTFLAGS",
+            "-Clink-arg=--skip-wit-component",
+        )
+        .status()?;
+    if !status.success() {
+        anyhow::bail!(status);
+    }
+    Ok(())
+}
+
+/// Applies the trampoline to the example in `examples_dir`
+fn apply_trampoline_to_example(examples_dir: &Path, name: &str) -> Result<()> {
     let example_path = examples_dir.join(name).with_extension("wasm");
     let merged_path = example_path.with_extension("merged.wasm");
     shopify_function_trampoline::trampoline_existing_module(example_path, merged_path)?;
@@ -58,13 +189,234 @@ fn apply_trampoline_to_example(name: &str) -> Result<()> {
 
 static BUILD_PROVIDER_RESULT: LazyLock<Result<()>> = LazyLock::new(build_provider);
 
-/// Builds the trampoline, provider, and example, and merges the example with the trampoline
-pub fn prepare_example(name: &str) -> Result<()> {
+/// Builds the provider
+pub fn prepare_provider() -> Result<()> {
     BUILD_PROVIDER_RESULT
         .as_ref()
         .map_err(|e| anyhow::anyhow!("Failed to build provider: {}", e))?;
+    Ok(())
+}
+
+static BUILD_PROVIDER_WITH_JSON_OUTPUT_RESULT: LazyLock<Result<()>> =
+    LazyLock::new(build_provider_with_json_output);
+
+/// Builds the provider with the `json-output` feature
+pub fn prepare_provider_with_json_output() -> Result<()> {
+    BUILD_PROVIDER_WITH_JSON_OUTPUT_RESULT
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("Failed to build provider with JSON output: {}", e))?;
+    Ok(())
+}
+
+/// The results of building each provider variant, by name, so each is only built once.
+static BUILD_PROVIDER_VARIANT_RESULTS: Mutex<Vec<(&str, Result<(), String>)>> =
+    Mutex::new(Vec::new());
+
+/// Builds a provider variant, returning the path of its module.
+pub fn prepare_provider_variant(variant: &ProviderVariant) -> Result<PathBuf> {
+    let mut results = BUILD_PROVIDER_VARIANT_RESULTS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    let result = match results.iter().find(|(name, _)| *name == variant.name) {
+        Some((_, result)) => result.clone(),
+        None => {
+            let result = build_provider_variant(variant).map_err(|e| e.to_string());
+            results.push((variant.name, result.clone()));
+            result
+        }
+    };
+    result
+        .map_err(|e| anyhow::anyhow!("Failed to build provider variant {}: {}", variant.name, e))?;
+    Ok(variant
+        .target_dir()
+        .join("wasm32-unknown-unknown/release/shopify_function_provider.wasm"))
+}
+
+/// Builds the trampoline, provider, and example, and merges the example with the trampoline
+pub fn prepare_example(name: &str) -> Result<()> {
+    prepare_provider()?;
     build_example(name).map_err(|e| anyhow::anyhow!("Failed to build example: {}", e))?;
-    apply_trampoline_to_example(name)
+    apply_trampoline_to_example(&workspace_root().join(EXAMPLES_DIR), name)
+        .map_err(|e| anyhow::anyhow!("Failed to apply trampoline: {}", e))?;
+    Ok(())
+}
+
+/// Builds the provider and the example for `wasm32-wasip2`, and merges the example with the
+/// trampoline
+pub fn prepare_wasip2_example(name: &str) -> Result<()> {
+    prepare_provider()?;
+    build_wasip2_example(name).map_err(|e| anyhow::anyhow!("Failed to build example: {}", e))?;
+    apply_trampoline_to_example(&workspace_root().join(WASIP2_EXAMPLES_DIR), name)
         .map_err(|e| anyhow::anyhow!("Failed to apply trampoline: {}", e))?;
     Ok(())
 }
+
+/// The path of the provider module built by [`prepare_example`].
+pub fn provider_module_path() -> PathBuf {
+    workspace_root().join("target/wasm32-unknown-unknown/release/shopify_function_provider.wasm")
+}
+
+/// The path of the provider module built by [`prepare_provider_with_json_output`].
+pub fn json_output_provider_module_path() -> PathBuf {
+    workspace_root()
+        .join("target/json-output/wasm32-unknown-unknown/release/shopify_function_provider.wasm")
+}
+
+/// The path of an example module built and merged with the trampoline by [`prepare_example`].
+pub fn example_module_path(name: &str) -> PathBuf {
+    workspace_root()
+        .join(EXAMPLES_DIR)
+        .join(format!("{name}.merged.wasm"))
+}
+
+/// The path of an example module built and merged with the trampoline by
+/// [`prepare_wasip2_example`].
+pub fn wasip2_example_module_path(name: &str) -> PathBuf {
+    workspace_root()
+        .join(WASIP2_EXAMPLES_DIR)
+        .join(format!("{name}.merged.wasm"))
+}
+
+/// The result of running a function module.
+#[derive(Debug)]
+pub struct RunResult {
+    /// The output, which is msgpack-encoded unless another output encoding was requested.
+    pub output: Vec<u8>,
+    /// The logs written by the function.
+    pub logs: String,
+    /// The number of bytes dropped from the logs because they exceeded the capacity.
+    pub log_dropped_len: u32,
+    /// The log records written by the function, which can be decoded with
+    /// [`conformance::decode_log_records`].
+    pub log_records: Vec<u8>,
+    /// The fuel consumed by the function.
+    pub fuel: u64,
+    /// The combined size in bytes of the function's and the provider's memories after running.
+    pub memory_bytes: usize,
+}
+
+/// A trap while running a function, along with the logs written up to that point.
+#[derive(Debug)]
+pub struct CallFuncError {
+    pub trap_error: anyhow::Error,
+    pub logs: String,
+}
+
+impl Display for CallFuncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}\n\nLogs: {}", self.trap_error, self.logs)
+    }
+}
+
+/// Runs a trampolined function module against a provider module with msgpack-encoded input.
+pub fn run_module(
+    module_path: &Path,
+    provider_path: &Path,
+    input_bytes: &[u8],
+) -> Result<RunResult> {
+    run_module_with_encodings(
+        module_path,
+        provider_path,
+        input_bytes,
+        InputEncoding::Msgpack,
+        OutputEncoding::Msgpack,
+    )
+}
+
+/// Runs a trampolined function module against a provider module with input in `input_encoding`,
+/// returning output in `output_encoding`. Output encodings other than msgpack need a provider
+/// built with the `json-output` feature.
+pub fn run_module_with_encodings(
+    module_path: &Path,
+    provider_path: &Path,
+    input_bytes: &[u8],
+    input_encoding: InputEncoding,
+    output_encoding: OutputEncoding,
+) -> Result<RunResult> {
+    let engine = Engine::new(Config::new().consume_fuel(true))?;
+
+    let module = Module::from_file(&engine, module_path)?;
+
+    let provider = Module::from_file(&engine, provider_path)?;
+
+    let mut linker = Linker::new(&engine);
+
+    let mut store = Store::new(&engine, ());
+
+    let provider_instance = linker.instantiate(&mut store, &provider)?;
+    store.set_fuel(STARTING_FUEL)?;
+    let version = provider_instance
+        .get_typed_func::<(), u32>(&mut store, "finalize_results_version")?
+        .call(&mut store, ())?;
+    FinalizeResults::check_version(version)?;
+    let init_func = provider_instance
+        .get_typed_func::<(u32, u32), u32>(&mut store, "initialize_with_encoding")?;
+    let input_buffer_offset = input_offset(
+        init_func.call(&mut store, (input_bytes.len() as _, input_encoding as u32))?,
+        input_bytes.len(),
+    )?;
+    if output_encoding != OutputEncoding::Msgpack {
+        let set_output_encoding = provider_instance
+            .get_typed_func::<u32, u32>(&mut store, "set_output_encoding")?
+            .call(&mut store, output_encoding as u32)?;
+        if set_output_encoding == 0 {
+            anyhow::bail!("The provider does not support {output_encoding:?} output");
+        }
+    }
+    provider_instance
+        .get_memory(&mut store, "memory")
+        .unwrap()
+        .write(&mut store, input_buffer_offset, input_bytes)?;
+    linker.instance(
+        &mut store,
+        shopify_function_provider::PROVIDER_MODULE_NAME,
+        provider_instance,
+    )?;
+    // modules built for WASI targets import WASI functions, which functions shouldn't need to call
+    linker.define_unknown_imports_as_traps(&module)?;
+
+    store.set_fuel(STARTING_FUEL)?;
+    let instance = linker.instantiate(&mut store, &module)?;
+
+    let func = instance.get_typed_func::<(), ()>(&mut store, "_start")?;
+
+    let result = func.call(&mut store, ());
+
+    let fuel = STARTING_FUEL.saturating_sub(store.get_fuel().unwrap_or_default());
+
+    let results_offset = provider_instance
+        .get_typed_func::<(), u32>(&mut store, "finalize")?
+        .call(&mut store, ())?;
+    let memory = provider_instance.get_memory(&mut store, "memory").unwrap();
+    let results = FinalizeResults::read(memory.data(&store), results_offset as usize)?;
+    let output = results.output(memory.data(&store))?.to_vec();
+    let logs = results.logs(memory.data(&store))?;
+    let log_records = results.log_records(memory.data(&store))?.to_vec();
+    let log_dropped_len = provider_instance
+        .get_typed_func::<(), u32>(&mut store, "log_dropped_len")?
+        .call(&mut store, ())?;
+
+    let memory_bytes = memory.data_size(&store)
+        + instance
+            .get_memory(&mut store, "memory")
+            .map_or(0, |memory| memory.data_size(&store));
+
+    drop(store);
+
+    let logs = String::from_utf8_lossy(&logs).to_string();
+    if let Err(e) = result {
+        return Err(anyhow::anyhow!(CallFuncError {
+            trap_error: e,
+            logs,
+        }));
+    }
+
+    Ok(RunResult {
+        output,
+        logs,
+        log_dropped_len,
+        log_records,
+        fuel,
+        memory_bytes,
+    })
+}
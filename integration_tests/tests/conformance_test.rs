@@ -0,0 +1,20 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use integration_tests::{
+    conformance::{corpus_dir, load_corpus, run_corpus},
+    example_module_path, prepare_example, provider_module_path,
+};
+
+#[test]
+fn test_conformance_corpus() -> Result<()> {
+    let cases = load_corpus(&corpus_dir())?;
+    assert!(!cases.is_empty(), "The conformance corpus is empty");
+
+    let functions: BTreeSet<&str> = cases.iter().map(|case| case.function.as_str()).collect();
+    for function in functions {
+        prepare_example(function).map_err(|e| anyhow::anyhow!("Failed to prepare example: {e}"))?;
+    }
+
+    run_corpus(&cases, &provider_module_path(), example_module_path)
+}
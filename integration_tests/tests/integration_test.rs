@@ -1,9 +1,16 @@
-use anyhow::{Error, Result};
-use integration_tests::prepare_example;
-use std::{fmt::Display, sync::LazyLock};
-use wasmtime::{Config, Engine, Linker, Module, Store};
+use anyhow::Result;
+use integration_tests::{
+    conformance::{decode_log_records, LogRecord},
+    example_module_path, json_output_provider_module_path, prepare_example, prepare_provider,
+    prepare_provider_with_json_output, prepare_wasip2_example, provider_module_path, run_module,
+    run_module_with_encodings, wasip2_example_module_path, CallFuncError,
+};
+use shopify_function_wasm_api_core::{
+    host::{InputEncoding, OutputEncoding},
+    log::Level,
+};
+use std::sync::LazyLock;
 
-const STARTING_FUEL: u64 = u64::MAX;
 const THRESHOLD_PERCENTAGE: f64 = 2.0;
 
 /// Used to detect any significant changes in the fuel consumption when making
@@ -34,82 +41,12 @@ fn assert_fuel_consumed_within_threshold(target_fuel: u64, fuel_consumed: u64) {
 }
 
 fn run_example(example: &str, input_bytes: Vec<u8>) -> Result<(Vec<u8>, String, u64)> {
-    let manifest_dir = env!("CARGO_MANIFEST_DIR");
-    let workspace_root = std::path::PathBuf::from(manifest_dir).join("..");
-    let engine = Engine::new(Config::new().consume_fuel(true))?;
-
-    let module_path = workspace_root.join(format!(
-        "target/wasm32-unknown-unknown/release/examples/{example}.merged.wasm"
-    ));
-
-    let module = Module::from_file(&engine, workspace_root.join(module_path))?;
-
-    let provider = Module::from_file(
-        &engine,
-        workspace_root.join("target/wasm32-unknown-unknown/release/shopify_function_provider.wasm"),
+    let result = run_module(
+        &example_module_path(example),
+        &provider_module_path(),
+        &input_bytes,
     )?;
-
-    let mut linker = Linker::new(&engine);
-
-    let mut store = Store::new(&engine, ());
-
-    let provider_instance = linker.instantiate(&mut store, &provider)?;
-    store.set_fuel(STARTING_FUEL)?;
-    let init_func = provider_instance.get_typed_func::<i32, i32>(&mut store, "initialize")?;
-    let input_buffer_offset = init_func.call(&mut store, input_bytes.len() as _)?;
-    provider_instance
-        .get_memory(&mut store, "memory")
-        .unwrap()
-        .write(&mut store, input_buffer_offset as usize, &input_bytes)?;
-    linker.instance(
-        &mut store,
-        shopify_function_provider::PROVIDER_MODULE_NAME,
-        provider_instance,
-    )?;
-
-    store.set_fuel(STARTING_FUEL)?;
-    let instance = linker.instantiate(&mut store, &module)?;
-
-    let func = instance.get_typed_func::<(), ()>(&mut store, "_start")?;
-
-    let result = func.call(&mut store, ());
-
-    let instructions = STARTING_FUEL.saturating_sub(store.get_fuel().unwrap_or_default());
-
-    let results_offset = provider_instance
-        .get_typed_func::<(), u32>(&mut store, "finalize")?
-        .call(&mut store, ())?;
-    let memory = provider_instance.get_memory(&mut store, "memory").unwrap();
-    let mut buf = [0; 24];
-    memory.read(&store, results_offset as usize, &mut buf)?;
-
-    let output_offset = u32::from_le_bytes(buf[0..4].try_into().unwrap()) as usize;
-    let output_len = u32::from_le_bytes(buf[4..8].try_into().unwrap()) as usize;
-    let logs_offset1 = u32::from_le_bytes(buf[8..12].try_into().unwrap()) as usize;
-    let logs_len1 = u32::from_le_bytes(buf[12..16].try_into().unwrap()) as usize;
-    let logs_offset2 = u32::from_le_bytes(buf[16..20].try_into().unwrap()) as usize;
-    let logs_len2 = u32::from_le_bytes(buf[20..24].try_into().unwrap()) as usize;
-    let mut output = vec![0; output_len];
-    memory.read(&store, output_offset, &mut output)?;
-    let mut logs1 = vec![0; logs_len1];
-    memory.read(&store, logs_offset1, &mut logs1)?;
-    let mut logs2 = vec![0; logs_len2];
-    memory.read(&store, logs_offset2, &mut logs2)?;
-    let mut logs = Vec::with_capacity(logs_len1 + logs_len2);
-    logs.extend(logs1);
-    logs.extend(logs2);
-
-    drop(store);
-
-    let logs = String::from_utf8_lossy(&logs).to_string();
-    if let Err(e) = result {
-        return Err(anyhow::anyhow!(CallFuncError {
-            trap_error: e,
-            logs,
-        }));
-    }
-
-    Ok((output, logs, instructions))
+    Ok((result.output, result.logs, result.fuel))
 }
 
 fn decode_msgpack_output(output: Vec<u8>) -> Result<serde_json::Value> {
@@ -126,26 +63,22 @@ fn run_wasm_api_example(example: &str, input: serde_json::Value) -> Result<serde
     decode_msgpack_output(output)
 }
 
-#[derive(Debug)]
-struct CallFuncError {
-    trap_error: Error,
-    logs: String,
-}
-
-impl Display for CallFuncError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}\n\nLogs: {}", self.trap_error, self.logs)
-    }
-}
-
 static ECHO_EXAMPLE_RESULT: LazyLock<Result<()>> = LazyLock::new(|| prepare_example("echo"));
 static BENCHMARK_EXAMPLE_RESULT: LazyLock<Result<()>> =
     LazyLock::new(|| prepare_example("cart-checkout-validation-wasm-api"));
+static SHIPPING_DISCOUNT_EXAMPLE_RESULT: LazyLock<Result<()>> =
+    LazyLock::new(|| prepare_example("shipping-discount-wasm-api"));
+static FOR_EACH_UNTIL_EXAMPLE_RESULT: LazyLock<Result<()>> =
+    LazyLock::new(|| prepare_example("for-each-until"));
 static LOG_EXAMPLE_RESULT: LazyLock<Result<()>> = LazyLock::new(|| prepare_example("log"));
 static PANIC_EXAMPLE_RESULT: LazyLock<Result<()>> = LazyLock::new(|| prepare_example("panic"));
+static RUN_MACRO_EXAMPLE_RESULT: LazyLock<Result<()>> =
+    LazyLock::new(|| prepare_example("run-macro"));
 static LOG_LEN_EXAMPLE_RESULT: LazyLock<Result<()>> = LazyLock::new(|| prepare_example("log-len"));
 static LOG_PAST_CAPACITY_EXAMPLE_RESULT: LazyLock<Result<()>> =
     LazyLock::new(|| prepare_example("log-past-capacity"));
+static LOG_RECORDS_EXAMPLE_RESULT: LazyLock<Result<()>> =
+    LazyLock::new(|| prepare_example("log-records"));
 
 #[test]
 fn test_echo_with_bool_input() -> Result<()> {
@@ -266,6 +199,117 @@ fn test_echo_with_array_input() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_echo_with_json_input() -> Result<()> {
+    ECHO_EXAMPLE_RESULT
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("Failed to prepare example: {}", e))?;
+    let input = serde_json::json!({
+        "foo": [1, -2, 3.5, null, true],
+        "bar": { "baz": "caf\u{e9} \"quoted\"\n" },
+        "qux": [],
+    });
+    let result = run_module_with_encodings(
+        &example_module_path("echo"),
+        &provider_module_path(),
+        serde_json::to_string(&input)?.as_bytes(),
+        InputEncoding::Json,
+        OutputEncoding::Msgpack,
+    )?;
+    assert_eq!(decode_msgpack_output(result.output)?, input);
+    Ok(())
+}
+
+/// Functions built for `wasm32-wasip2` import WASI 0.2 interfaces, which are linked as traps, so
+/// this also checks that the API never calls them, e.g. to read stdin.
+#[test]
+fn test_echo_wasip2() -> Result<()> {
+    prepare_wasip2_example("echo")?;
+    let input = serde_json::json!({ "foo": [1, -2, 3.5, null, true], "bar": "baz" });
+    let result = run_module(
+        &wasip2_example_module_path("echo"),
+        &provider_module_path(),
+        &prepare_wasm_api_input(input.clone())?,
+    )?;
+    assert_eq!(decode_msgpack_output(result.output)?, input);
+    Ok(())
+}
+
+#[test]
+fn test_echo_with_invalid_json_input() -> Result<()> {
+    ECHO_EXAMPLE_RESULT
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("Failed to prepare example: {}", e))?;
+    let error = run_module_with_encodings(
+        &example_module_path("echo"),
+        &provider_module_path(),
+        b"{\"foo\": }",
+        InputEncoding::Json,
+        OutputEncoding::Msgpack,
+    )
+    .unwrap_err();
+    assert!(error.downcast_ref::<CallFuncError>().is_some());
+    Ok(())
+}
+
+#[test]
+fn test_echo_with_json_output() -> Result<()> {
+    ECHO_EXAMPLE_RESULT
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("Failed to prepare example: {}", e))?;
+    prepare_provider_with_json_output()?;
+    let input = serde_json::json!({ "foo": [1, 2.5, "three"], "bar": {} });
+    let result = run_module_with_encodings(
+        &example_module_path("echo"),
+        &json_output_provider_module_path(),
+        &prepare_wasm_api_input(input.clone())?,
+        InputEncoding::Msgpack,
+        OutputEncoding::Json,
+    )?;
+    assert_eq!(
+        String::from_utf8(result.output)?,
+        serde_json::to_string_pretty(&input)?
+    );
+    Ok(())
+}
+
+#[test]
+fn test_echo_with_compact_json_output() -> Result<()> {
+    ECHO_EXAMPLE_RESULT
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("Failed to prepare example: {}", e))?;
+    prepare_provider_with_json_output()?;
+    let input = serde_json::json!({ "foo": [1, 2.5, "three"], "bar": {} });
+    let result = run_module_with_encodings(
+        &example_module_path("echo"),
+        &json_output_provider_module_path(),
+        &prepare_wasm_api_input(input.clone())?,
+        InputEncoding::Msgpack,
+        OutputEncoding::CompactJson,
+    )?;
+    assert_eq!(
+        String::from_utf8(result.output)?,
+        serde_json::to_string(&input)?
+    );
+    Ok(())
+}
+
+#[test]
+fn test_json_output_needs_feature() -> Result<()> {
+    ECHO_EXAMPLE_RESULT
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("Failed to prepare example: {}", e))?;
+    let result = run_module_with_encodings(
+        &example_module_path("echo"),
+        &provider_module_path(),
+        &prepare_wasm_api_input(serde_json::json!(null))?,
+        InputEncoding::Msgpack,
+        OutputEncoding::Json,
+    );
+    assert!(result.is_err());
+    Ok(())
+}
+
 /// Generates a cart with the specified number of items for testing.
 ///
 /// # Arguments
@@ -291,6 +335,49 @@ fn generate_cart_with_size(size: usize, traverse_all: bool) -> serde_json::Value
     })
 }
 
+fn generate_shipping_discount_input(size: usize, minimum_subtotal: f64) -> serde_json::Value {
+    let lines = (0..size)
+        .map(|i| {
+            serde_json::json!({
+                "quantity": i % 3 + 1,
+                "cost": {
+                    "amountPerQuantity": {
+                        "amount": format!("{}.99", i % 50 + 1),
+                        "currencyCode": "CAD"
+                    }
+                },
+                "merchandise": {
+                    "__typename": "ProductVariant",
+                    "id": format!("gid://shopify/ProductVariant/{}", i + 1),
+                    "title": format!("Sample Product {}", i + 1),
+                    "product": {
+                        "id": format!("gid://shopify/Product/{}", i + 1),
+                        "hasAnyTag": i % 10 == 0
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    serde_json::json!({
+        "cart": {
+            "lines": lines,
+            "deliveryGroups": [
+                { "id": "gid://shopify/CartDeliveryGroup/1" },
+                { "id": "gid://shopify/CartDeliveryGroup/2" }
+            ]
+        },
+        "discount": {
+            "metafield": {
+                "jsonValue": {
+                    "minimumSubtotal": minimum_subtotal,
+                    "percentage": 20.0
+                }
+            }
+        }
+    })
+}
+
 #[test]
 fn test_echo_with_large_string_input() -> Result<()> {
     ECHO_EXAMPLE_RESULT
@@ -320,6 +407,54 @@ fn test_echo_with_large_array_input() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_echo_with_100k_element_array_input() -> Result<()> {
+    ECHO_EXAMPLE_RESULT
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("Failed to prepare example: {}", e))?;
+
+    let large_array: Vec<i32> = (0..100_000).collect();
+    assert_eq!(
+        run_wasm_api_example("echo", serde_json::json!(large_array))?,
+        serde_json::json!(large_array)
+    );
+    Ok(())
+}
+
+#[test]
+fn test_echo_with_long_string_input() -> Result<()> {
+    ECHO_EXAMPLE_RESULT
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("Failed to prepare example: {}", e))?;
+
+    // Strings around the NaN-box length limit, and longer than the initial buffer for long strings.
+    for len in [16_382, 16_383, 16_384, 65_536, 100_000] {
+        let long_string = "a".repeat(len);
+        assert_eq!(
+            run_wasm_api_example("echo", serde_json::json!(long_string))?,
+            serde_json::json!(long_string)
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_echo_with_large_obj_input() -> Result<()> {
+    ECHO_EXAMPLE_RESULT
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("Failed to prepare example: {}", e))?;
+
+    let large_object: serde_json::Map<String, serde_json::Value> = (0..20_000)
+        .map(|i| (format!("key{i}"), serde_json::json!(i)))
+        .collect();
+    let large_object = serde_json::Value::Object(large_object);
+    assert_eq!(
+        run_wasm_api_example("echo", large_object.clone())?,
+        large_object
+    );
+    Ok(())
+}
+
 #[test]
 fn test_fuel_consumption_within_threshold() -> Result<()> {
     BENCHMARK_EXAMPLE_RESULT
@@ -330,7 +465,7 @@ fn test_fuel_consumption_within_threshold() -> Result<()> {
     let (_, _, wasm_api_fuel) = run_example("cart-checkout-validation-wasm-api", wasm_api_input)?;
     eprintln!("WASM API fuel: {}", wasm_api_fuel);
     // Using a target fuel value as reference similar to the Javy example
-    assert_fuel_consumed_within_threshold(9637, wasm_api_fuel);
+    assert_fuel_consumed_within_threshold(9_608, wasm_api_fuel);
     Ok(())
 }
 
@@ -345,7 +480,7 @@ fn test_benchmark_with_input() -> Result<()> {
     let wasm_api_input = prepare_wasm_api_input(input.clone())?;
     let (_, _, wasm_api_fuel) = run_example("cart-checkout-validation-wasm-api", wasm_api_input)?;
 
-    assert_fuel_consumed_within_threshold(9_637, wasm_api_fuel);
+    assert_fuel_consumed_within_threshold(9_608, wasm_api_fuel);
 
     Ok(())
 }
@@ -361,7 +496,130 @@ fn test_benchmark_with_input_early_exit() -> Result<()> {
     let wasm_api_input = prepare_wasm_api_input(input.clone())?;
     let (_, _, wasm_api_fuel) = run_example("cart-checkout-validation-wasm-api", wasm_api_input)?;
 
-    assert_fuel_consumed_within_threshold(9_017, wasm_api_fuel);
+    assert_fuel_consumed_within_threshold(9_155, wasm_api_fuel);
+
+    Ok(())
+}
+
+#[test]
+fn test_echo_long_string_fuel_consumption_within_threshold() -> Result<()> {
+    ECHO_EXAMPLE_RESULT
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("Failed to prepare example: {}", e))?;
+
+    let run = |len| -> Result<u64> {
+        let input = prepare_wasm_api_input(serde_json::json!("a".repeat(len)))?;
+        Ok(run_example("echo", input)?.2)
+    };
+    // the string is copied into the output in bulk, so the fuel shouldn't grow with its length
+    assert_fuel_consumed_within_threshold(3_972, run(100_000)?);
+    assert_fuel_consumed_within_threshold(4_548, run(1_000_000)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_shipping_discount() -> Result<()> {
+    SHIPPING_DISCOUNT_EXAMPLE_RESULT
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("Failed to prepare example: {}", e))?;
+
+    let output = run_wasm_api_example(
+        "shipping-discount-wasm-api",
+        generate_shipping_discount_input(500, 100.0),
+    )?;
+    assert_eq!(
+        output,
+        serde_json::json!({
+            "operations": [{
+                "deliveryDiscountsAdd": {
+                    "candidates": [{
+                        "message": "20% off shipping",
+                        "targets": [
+                            { "deliveryGroup": { "id": "gid://shopify/CartDeliveryGroup/1" } },
+                            { "deliveryGroup": { "id": "gid://shopify/CartDeliveryGroup/2" } }
+                        ],
+                        "value": { "percentage": { "value": 20.0 } }
+                    }],
+                    "selectionStrategy": "ALL"
+                }
+            }]
+        })
+    );
+
+    let output = run_wasm_api_example(
+        "shipping-discount-wasm-api",
+        generate_shipping_discount_input(500, 1_000_000.0),
+    )?;
+    assert_eq!(output, serde_json::json!({ "operations": [] }));
+
+    Ok(())
+}
+
+#[test]
+fn test_shipping_discount_fuel_consumption_within_threshold() -> Result<()> {
+    SHIPPING_DISCOUNT_EXAMPLE_RESULT
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("Failed to prepare example: {}", e))?;
+
+    let run = |size| -> Result<u64> {
+        let input = prepare_wasm_api_input(generate_shipping_discount_input(size, 100.0))?;
+        Ok(run_example("shipping-discount-wasm-api", input)?.2)
+    };
+    // Reading each line's properties with a shape takes 1_537_772 and 7_556_102 fuel without it.
+    assert_fuel_consumed_within_threshold(1_359_937, run(100)?);
+    assert_fuel_consumed_within_threshold(6_678_426, run(500)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_shipping_discount_with_wide_lines_fuel_consumption_within_threshold() -> Result<()> {
+    SHIPPING_DISCOUNT_EXAMPLE_RESULT
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("Failed to prepare example: {}", e))?;
+
+    // Lines with many other properties, whose keys sort before the ones the function reads, so
+    // finding each property means skipping past the others. Property hints in the provider skip
+    // the key comparisons for every line after the first.
+    let mut input = generate_shipping_discount_input(100, 100.0);
+    for line in input["cart"]["lines"].as_array_mut().unwrap() {
+        for i in 0..20 {
+            line[format!("attribute{i}")] = serde_json::json!(i);
+        }
+    }
+    let input = prepare_wasm_api_input(input)?;
+    let fuel = run_example("shipping-discount-wasm-api", input)?.2;
+    assert_fuel_consumed_within_threshold(2_131_625, fuel);
+
+    Ok(())
+}
+
+#[test]
+fn test_for_each_until() -> Result<()> {
+    FOR_EACH_UNTIL_EXAMPLE_RESULT
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("Failed to prepare example: {e}"))?;
+
+    // Sizes around the batch sizes, which grow up to 32 elements.
+    for len in [0, 1, 2, 3, 31, 32, 33, 63, 64, 100, 1_000] {
+        let input: Vec<i32> = (0..len).collect();
+        assert_eq!(
+            run_wasm_api_example("for-each-until", serde_json::json!(input))?,
+            serde_json::json!({ "sum": (0..len).sum::<i32>(), "visited": len }),
+        );
+    }
+
+    let input: Vec<i32> = (0..100).map(|i| if i == 40 { -1 } else { i }).collect();
+    assert_eq!(
+        run_wasm_api_example("for-each-until", serde_json::json!(input))?,
+        serde_json::json!({ "sum": (0..40).sum::<i32>(), "visited": 41 }),
+    );
+
+    assert_eq!(
+        run_wasm_api_example("for-each-until", serde_json::json!({ "a": 1 }))?,
+        serde_json::json!({ "sum": 0, "visited": 0 }),
+    );
 
     Ok(())
 }
@@ -373,7 +631,7 @@ fn test_log() -> Result<()> {
         .map_err(|e| anyhow::anyhow!("Failed to prepare example: {e}"))?;
     let (_, logs, fuel) = run_example("log", vec![])?;
     assert_eq!(logs, "Hi!\nHello\nHere's a third string\n✌️\n");
-    assert_fuel_consumed_within_threshold(466, fuel);
+    assert_fuel_consumed_within_threshold(533, fuel);
     Ok(())
 }
 
@@ -386,17 +644,17 @@ fn test_log_len() -> Result<()> {
         Ok(run_example("log-len", prepare_wasm_api_input(serde_json::json!(len))?)?.2)
     };
     let fuel = run(1)?;
-    assert_fuel_consumed_within_threshold(744, fuel);
+    assert_fuel_consumed_within_threshold(796, fuel);
     let fuel = run(500)?;
-    assert_fuel_consumed_within_threshold(2_750, fuel);
+    assert_fuel_consumed_within_threshold(2_929, fuel);
     let fuel = run(1_000)?;
-    assert_fuel_consumed_within_threshold(4_375, fuel);
+    assert_fuel_consumed_within_threshold(4_644, fuel);
     let fuel = run(5_000)?;
-    assert_fuel_consumed_within_threshold(17_411, fuel);
+    assert_fuel_consumed_within_threshold(18_404, fuel);
     let fuel = run(10_000)?;
-    assert_fuel_consumed_within_threshold(33_706, fuel);
+    assert_fuel_consumed_within_threshold(35_604, fuel);
     let fuel = run(100_000)?;
-    assert_fuel_consumed_within_threshold(327_055, fuel);
+    assert_fuel_consumed_within_threshold(345_245, fuel);
     Ok(())
 }
 
@@ -405,9 +663,45 @@ fn test_log_past_capacity() -> Result<()> {
     LOG_PAST_CAPACITY_EXAMPLE_RESULT
         .as_ref()
         .map_err(|e| anyhow::anyhow!("Failed to prepare example: {e}"))?;
-    let (_, logs, fuel) = run_example("log-past-capacity", vec![])?;
-    assert_eq!(logs, format!("{}{}", "a".repeat(991), "b".repeat(10)));
-    assert_fuel_consumed_within_threshold(928, fuel);
+    let result = run_module(
+        &example_module_path("log-past-capacity"),
+        &provider_module_path(),
+        &[],
+    )?;
+    assert_eq!(
+        result.logs,
+        format!("{}{}", "a".repeat(991), "b".repeat(10))
+    );
+    assert_eq!(result.log_dropped_len, 4);
+    assert_fuel_consumed_within_threshold(1_036, result.fuel);
+    Ok(())
+}
+
+#[test]
+fn test_log_records() -> Result<()> {
+    LOG_RECORDS_EXAMPLE_RESULT
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("Failed to prepare example: {e}"))?;
+    let result = run_module(
+        &example_module_path("log-records"),
+        &provider_module_path(),
+        &[],
+    )?;
+    assert_eq!(result.logs, "Hi!\n");
+    let record = |level, sequence, message: &str| LogRecord {
+        level,
+        sequence,
+        message: message.to_string(),
+    };
+    assert_eq!(
+        decode_log_records(&result.log_records)?,
+        vec![
+            record(Level::Info, 0, "Starting"),
+            record(Level::Warn, 1, "No cart lines"),
+            record(Level::Error, 2, "✌️"),
+        ]
+    );
+    assert_fuel_consumed_within_threshold(1_210, result.fuel);
     Ok(())
 }
 
@@ -427,3 +721,105 @@ at the disco
     );
     Ok(())
 }
+
+#[test]
+fn test_run_macro() -> Result<()> {
+    RUN_MACRO_EXAMPLE_RESULT
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("Failed to prepare example: {e}"))?;
+    assert_eq!(
+        run_wasm_api_example("run-macro", serde_json::json!(21))?,
+        serde_json::json!(42)
+    );
+    Ok(())
+}
+
+#[test]
+fn test_run_macro_logs_errors() -> Result<()> {
+    RUN_MACRO_EXAMPLE_RESULT
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("Failed to prepare example: {e}"))?;
+    let input = prepare_wasm_api_input(serde_json::json!(i32::MAX))?;
+    let error = run_example("run-macro", input)
+        .unwrap_err()
+        .downcast::<CallFuncError>()?;
+    assert_eq!(error.logs, "Function failed: input too large\n");
+
+    let input = prepare_wasm_api_input(serde_json::json!("21"))?;
+    let error = run_example("run-macro", input)
+        .unwrap_err()
+        .downcast::<CallFuncError>()?;
+    assert_eq!(error.logs, "Failed to deserialize input: Invalid type\n");
+    Ok(())
+}
+
+/// A guest with a bump `malloc`, as TinyGo exports, which echoes a string input by reading it into
+/// a buffer it allocates, and writes null for any other input.
+const GUEST_ALLOCATOR_ECHO_WAT: &str = r#"
+(module
+    (import "shopify_function_v2" "shopify_function_input_get" (func $input_get (result i64)))
+    (import "shopify_function_v2" "shopify_function_input_read_utf8_str_alloc" (func $read_utf8_str_alloc (param i64) (result i64)))
+    (import "shopify_function_v2" "shopify_function_output_new_utf8_str" (func $output_new_utf8_str (param i32 i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_output_new_null" (func $output_new_null (result i32)))
+    (memory (export "memory") 4)
+    (global $heap (mut i32) (i32.const 1024))
+    (func (export "malloc") (param $len i32) (result i32)
+        global.get $heap
+        global.get $heap
+        local.get $len
+        i32.add
+        global.set $heap)
+    (func (export "_start")
+        (local $len_and_ptr i64)
+        (local $len i32)
+        call $input_get
+        call $read_utf8_str_alloc
+        local.tee $len_and_ptr
+        i64.const 32
+        i64.shr_u
+        i32.wrap_i64
+        local.tee $len
+        i32.const -1
+        i32.eq
+        if
+            call $output_new_null
+            drop
+            return
+        end
+        local.get $len_and_ptr
+        i32.wrap_i64
+        local.get $len
+        call $output_new_utf8_str
+        drop)
+)
+"#;
+
+#[test]
+fn test_read_utf8_str_alloc_with_guest_allocator() -> Result<()> {
+    prepare_provider()?;
+    let module = walrus::Module::from_buffer(&wat::parse_str(GUEST_ALLOCATOR_ECHO_WAT)?)?;
+    let mut module = shopify_function_trampoline::TrampolineCodegen::new(module)?.apply()?;
+    let module_path =
+        std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("guest-allocator-echo.wasm");
+    module.emit_wasm_file(&module_path)?;
+
+    for input in [
+        serde_json::json!(""),
+        serde_json::json!("hello"),
+        serde_json::json!("a".repeat(100_000)),
+        serde_json::json!(5),
+    ] {
+        let result = run_module(
+            &module_path,
+            &provider_module_path(),
+            &prepare_wasm_api_input(input.clone())?,
+        )?;
+        let expected = if input.is_string() {
+            input
+        } else {
+            serde_json::json!(null)
+        };
+        assert_eq!(decode_msgpack_output(result.output)?, expected);
+    }
+    Ok(())
+}
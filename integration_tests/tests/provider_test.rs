@@ -0,0 +1,211 @@
+use anyhow::Result;
+use integration_tests::{prepare_provider, provider_module_path};
+use shopify_function_wasm_api_core::host::{
+    input_offset, FinalizeResults, HostError, InputCompression, InputEncoding, InputKeyValidation,
+    OutputCompression,
+};
+use wasmtime::{Engine, Instance, Module, Store};
+
+fn instantiate_provider() -> Result<(Store<()>, Instance)> {
+    prepare_provider()?;
+    let engine = Engine::default();
+    let module = Module::from_file(&engine, provider_module_path())?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    Ok((store, instance))
+}
+
+fn initialize(store: &mut Store<()>, instance: &Instance, input_len: u32) -> Result<u32> {
+    instance
+        .get_typed_func::<u32, u32>(&mut *store, "initialize")?
+        .call(store, input_len)
+}
+
+fn finalize(store: &mut Store<()>, instance: &Instance) -> Result<FinalizeResults> {
+    let offset = instance
+        .get_typed_func::<(), u32>(&mut *store, "finalize")?
+        .call(&mut *store, ())?;
+    let memory = instance.get_memory(&mut *store, "memory").unwrap();
+    Ok(FinalizeResults::read(
+        memory.data(&*store),
+        offset as usize,
+    )?)
+}
+
+#[test]
+fn test_finalize_results_version() -> Result<()> {
+    let (mut store, instance) = instantiate_provider()?;
+    let version = instance
+        .get_typed_func::<(), u32>(&mut store, "finalize_results_version")?
+        .call(&mut store, ())?;
+    assert_eq!(FinalizeResults::check_version(version), Ok(()));
+    Ok(())
+}
+
+#[test]
+fn test_initialize_with_input_too_large() -> Result<()> {
+    let (mut store, instance) = instantiate_provider()?;
+    let result = initialize(&mut store, &instance, u32::MAX)?;
+    assert_eq!(
+        input_offset(result, u32::MAX as usize),
+        Err(HostError::InputTooLarge {
+            len: u32::MAX as usize
+        })
+    );
+
+    // the provider can still be initialized with a valid input afterwards
+    let result = initialize(&mut store, &instance, 16)?;
+    assert!(input_offset(result, 16).is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_initialize_with_unknown_encoding() -> Result<()> {
+    let (mut store, instance) = instantiate_provider()?;
+    let result = instance
+        .get_typed_func::<(u32, u32), u32>(&mut store, "initialize_with_encoding")?
+        .call(&mut store, (16, 2))?;
+    assert_eq!(result, 0);
+
+    let result = instance
+        .get_typed_func::<(u32, u32), u32>(&mut store, "initialize_with_encoding")?
+        .call(&mut store, (16, InputEncoding::Json as u32))?;
+    assert!(input_offset(result, 16).is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_input_compression_needs_feature() -> Result<()> {
+    let (mut store, instance) = instantiate_provider()?;
+    let supported =
+        instance.get_typed_func::<u32, u32>(&mut store, "input_compression_supported")?;
+    assert_eq!(
+        supported.call(&mut store, InputCompression::None as u32)?,
+        1
+    );
+    for compression in [
+        InputCompression::Gzip as u32,
+        InputCompression::Zstd as u32,
+        3,
+    ] {
+        assert_eq!(supported.call(&mut store, compression)?, 0);
+    }
+
+    let initialize_with_compression = instance
+        .get_typed_func::<(u32, u32, u32), u32>(&mut store, "initialize_with_compression")?;
+    let result = initialize_with_compression.call(
+        &mut store,
+        (
+            16,
+            InputEncoding::Msgpack as u32,
+            InputCompression::Gzip as u32,
+        ),
+    )?;
+    assert_eq!(result, 0);
+    let result = initialize_with_compression.call(
+        &mut store,
+        (
+            16,
+            InputEncoding::Msgpack as u32,
+            InputCompression::None as u32,
+        ),
+    )?;
+    assert!(input_offset(result, 16).is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_initialize_with_arena() -> Result<()> {
+    let (mut store, instance) = instantiate_provider()?;
+    let initialize_with_arena = instance
+        .get_typed_func::<(u32, u32, u32, u32, u32), u32>(&mut store, "initialize_with_arena")?;
+    let encoding = InputEncoding::Msgpack as u32;
+    let compression = InputCompression::None as u32;
+
+    // the first chunk of the arena can't be allocated
+    let result =
+        initialize_with_arena.call(&mut store, (16, encoding, compression, u32::MAX, 0))?;
+    assert_eq!(result, 0);
+
+    let result =
+        initialize_with_arena.call(&mut store, (16, encoding, compression, 4096, 1 << 20))?;
+    assert!(input_offset(result, 16).is_ok());
+
+    // unsupported compressions are rejected as with `initialize_with_compression`
+    let result = initialize_with_arena.call(
+        &mut store,
+        (16, encoding, InputCompression::Gzip as u32, 0, 0),
+    )?;
+    assert_eq!(result, 0);
+    Ok(())
+}
+
+#[test]
+fn test_finalize_without_output() -> Result<()> {
+    let (mut store, instance) = instantiate_provider()?;
+    initialize(&mut store, &instance, 0)?;
+    let results = finalize(&mut store, &instance)?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    assert_eq!(results.output(memory.data(&store))?, b"");
+    assert_eq!(results.logs(memory.data(&store))?, b"");
+    assert_eq!(results.compression()?, OutputCompression::None);
+    Ok(())
+}
+
+#[test]
+fn test_output_compression_needs_feature() -> Result<()> {
+    let (mut store, instance) = instantiate_provider()?;
+    assert!(instance
+        .get_func(&mut store, "set_output_compression")
+        .is_none());
+    Ok(())
+}
+
+#[test]
+fn test_finalize_before_initialize() -> Result<()> {
+    let (mut store, instance) = instantiate_provider()?;
+    let results = finalize(&mut store, &instance)?;
+    assert_eq!(results.output.len, 0);
+    Ok(())
+}
+
+#[test]
+fn test_set_input_key_validation() -> Result<()> {
+    let (mut store, instance) = instantiate_provider()?;
+    initialize(&mut store, &instance, 0)?;
+    let set_input_key_validation =
+        instance.get_typed_func::<u32, u32>(&mut store, "set_input_key_validation")?;
+    assert_eq!(
+        set_input_key_validation.call(&mut store, InputKeyValidation::Sorted as u32)?,
+        1
+    );
+    assert_eq!(set_input_key_validation.call(&mut store, 3)?, 0);
+    Ok(())
+}
+
+#[test]
+fn test_set_retain_string_interner() -> Result<()> {
+    let (mut store, instance) = instantiate_provider()?;
+    let set_retain_string_interner =
+        instance.get_typed_func::<u32, u32>(&mut store, "set_retain_string_interner")?;
+    let intern_utf8_str =
+        instance.get_typed_func::<u32, u64>(&mut store, "_shopify_function_intern_utf8_str")?;
+    let interned_utf8_str_exists = instance
+        .get_typed_func::<u32, u32>(&mut store, "_shopify_function_interned_utf8_str_exists")?;
+
+    initialize(&mut store, &instance, 0)?;
+    // the ID is in the upper half of the result, and the address to copy the string to in the lower
+    let id = (intern_utf8_str.call(&mut store, 3)? >> 32) as u32;
+    assert_eq!(set_retain_string_interner.call(&mut store, 1)?, 1);
+    assert_eq!(set_retain_string_interner.call(&mut store, 2)?, 0);
+
+    // the string is kept for each input until the host stops retaining it
+    initialize(&mut store, &instance, 0)?;
+    assert_eq!(interned_utf8_str_exists.call(&mut store, id)?, 1);
+    initialize(&mut store, &instance, 0)?;
+    assert_eq!(interned_utf8_str_exists.call(&mut store, id)?, 1);
+    assert_eq!(set_retain_string_interner.call(&mut store, 0)?, 1);
+    initialize(&mut store, &instance, 0)?;
+    assert_eq!(interned_utf8_str_exists.call(&mut store, id)?, 0);
+    Ok(())
+}
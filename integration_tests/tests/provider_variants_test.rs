@@ -0,0 +1,102 @@
+use anyhow::Result;
+use integration_tests::{
+    example_module_path, prepare_example, prepare_provider_variant, provider_module_path,
+    run_module, RunResult, PROVIDER_VARIANTS,
+};
+use std::path::Path;
+
+/// The examples run against each provider variant, with their inputs.
+fn cases() -> Vec<(&'static str, serde_json::Value)> {
+    let lines: Vec<_> = (1..=100)
+        .map(|i| {
+            serde_json::json!({
+                "quantity": 1,
+                "merchandise": {
+                    "id": format!("gid://shopify/ProductVariant/{i}"),
+                    "title": format!("Sample Product {i}"),
+                },
+            })
+        })
+        .collect();
+    vec![
+        (
+            "echo",
+            serde_json::json!({
+                "string": "✌️ hello",
+                "numbers": [0, -1, 1.5, i64::MAX, u64::MAX],
+                "nested": { "bool": true, "null": null },
+            }),
+        ),
+        (
+            "cart-checkout-validation-wasm-api",
+            serde_json::json!({ "cart": { "lines": lines } }),
+        ),
+        ("log", serde_json::json!(null)),
+    ]
+}
+
+fn run_cases(provider_path: &Path) -> Result<Vec<RunResult>> {
+    cases()
+        .into_iter()
+        .map(|(example, input)| {
+            run_module(
+                &example_module_path(example),
+                provider_path,
+                &rmp_serde::to_vec(&input)?,
+            )
+        })
+        .collect()
+}
+
+/// Checks that providers built with other opt levels and features behave the same as the
+/// production build, and prints the size and fuel of each, so the trade-offs between them are
+/// measured. Run with `--nocapture` to see them.
+#[test]
+fn test_provider_variants_match_production() -> Result<()> {
+    for (example, _) in cases() {
+        prepare_example(example)?;
+    }
+    let production = run_cases(&provider_module_path())?;
+
+    let examples: Vec<_> = cases().into_iter().map(|(example, _)| example).collect();
+    println!("{:<28} {:>10} {}", "provider", "size", examples.join(" / "));
+    let print_row = |name: &str, path: &Path, results: &[RunResult]| -> Result<()> {
+        let fuel: Vec<_> = results
+            .iter()
+            .zip(&production)
+            .map(|(result, production)| {
+                let change = (result.fuel as f64 / production.fuel as f64 - 1.0) * 100.0;
+                format!("{} ({change:+.1}%)", result.fuel)
+            })
+            .collect();
+        println!(
+            "{name:<28} {:>10} {}",
+            std::fs::metadata(path)?.len(),
+            fuel.join(" / ")
+        );
+        Ok(())
+    };
+    print_row("production", &provider_module_path(), &production)?;
+
+    for variant in PROVIDER_VARIANTS {
+        let provider_path = prepare_provider_variant(variant)?;
+        let results = run_cases(&provider_path)?;
+        for ((example, _), (result, production)) in
+            cases().iter().zip(results.iter().zip(&production))
+        {
+            assert_eq!(
+                result.output, production.output,
+                "{example} output differs with the {} provider",
+                variant.name
+            );
+            assert_eq!(
+                (&result.logs, &result.log_records),
+                (&production.logs, &production.log_records),
+                "{example} logs differ with the {} provider",
+                variant.name
+            );
+        }
+        print_row(variant.name, &provider_path, &results)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,47 @@
+export type WasmSource = WebAssembly.Module | BufferSource | Response;
+
+export interface RunFunctionOptions {
+  /** The trampolined function. */
+  function: WasmSource;
+  /** The input to the function. */
+  input: unknown;
+  /** The provider, which defaults to the one bundled with this package. */
+  provider?: WasmSource;
+  /** Other imports of the function, e.g. WASI imports for functions built for `wasm32-wasip1`. */
+  imports?: WebAssembly.Imports;
+}
+
+export interface RunFunctionResult {
+  /** The output written by the function, or `undefined` if it didn't write any. */
+  output: unknown;
+  /** The logs written by the function. */
+  logs: string;
+  /** The log records written by the function, oldest first. */
+  logRecords: LogRecord[];
+}
+
+export interface LogRecord {
+  /** The level of the record, or its value if this package doesn't know the level. */
+  level: "info" | "warn" | "error" | number;
+  /** The number of records logged before this one, including any that were dropped. */
+  sequence: number;
+  /** The message. */
+  message: string;
+}
+
+/** Thrown when a function traps, with the logs it wrote before trapping. */
+export class FunctionRunError extends Error {
+  readonly logs: string;
+}
+
+/** Runs a trampolined function with a JSON input. */
+export function runFunction(options: RunFunctionOptions): Promise<RunFunctionResult>;
+
+/** Loads the provider module bundled with this package. */
+export function loadProvider(): Promise<WebAssembly.Module>;
+
+/** Encodes a JSON value as MessagePack. */
+export function encode(value: unknown): Uint8Array;
+
+/** Decodes a MessagePack value. 64-bit integers are decoded as numbers, so they may lose precision. */
+export function decode(bytes: Uint8Array): unknown;
@@ -0,0 +1,134 @@
+// Runs trampolined Shopify Functions against the provider in JavaScript hosts, such as browsers,
+// using only the standard WebAssembly API.
+
+import { decode, encode } from "./msgpack.js";
+
+export { decode, encode };
+
+/**
+ * Thrown when a function traps, with the logs it wrote before trapping.
+ */
+export class FunctionRunError extends Error {
+  constructor(cause, logs) {
+    super(`Function trapped: ${cause?.message ?? cause}`, { cause });
+    this.name = "FunctionRunError";
+    this.logs = logs;
+  }
+}
+
+async function compile(source) {
+  if (source instanceof WebAssembly.Module) {
+    return source;
+  }
+  if (typeof Response !== "undefined" && source instanceof Response) {
+    return WebAssembly.compileStreaming(source);
+  }
+  return WebAssembly.compile(source);
+}
+
+// The version of the layout of the results returned by the provider's `finalize` export.
+const FINALIZE_RESULTS_VERSION = 3;
+
+// The names of the levels of log records, by their value.
+const LOG_LEVELS = ["info", "warn", "error"];
+
+/**
+ * Loads the provider module bundled with this package.
+ * @returns {Promise<WebAssembly.Module>}
+ */
+export async function loadProvider() {
+  const url = new URL("./shopify_function_provider.wasm", import.meta.url);
+  if (url.protocol === "file:") {
+    const { readFile } = await import("node:fs/promises");
+    return compile(await readFile(url));
+  }
+  return compile(fetch(url));
+}
+
+/**
+ * The name of the module that functions import the provider from, which is versioned with the
+ * provider's major version.
+ * @param {WebAssembly.Instance} provider
+ * @returns {string}
+ */
+function providerModuleName(provider) {
+  const version = provider.exports._shopify_function_provider_version();
+  return `shopify_function_v${BigInt(version) >> 32n}`;
+}
+
+/**
+ * Runs a trampolined function with a JSON input.
+ *
+ * A new provider instance is created for each run, so runs don't share any state.
+ *
+ * @param {object} options
+ * @param {WebAssembly.Module | BufferSource | Response} options.function The trampolined function.
+ * @param {unknown} options.input The input to the function.
+ * @param {WebAssembly.Module | BufferSource | Response} [options.provider] The provider, which
+ *   defaults to the one bundled with this package.
+ * @param {WebAssembly.Imports} [options.imports] Other imports of the function, e.g. WASI imports
+ *   for functions built for `wasm32-wasip1`.
+ * @returns {Promise<{ output: unknown, logs: string, logRecords: LogRecord[] }>}
+ */
+export async function runFunction({ function: func, input, provider, imports = {} }) {
+  const [functionModule, providerModule] = await Promise.all([
+    compile(func),
+    provider === undefined ? loadProvider() : compile(provider),
+  ]);
+
+  const providerInstance = await WebAssembly.instantiate(providerModule, {});
+  const { memory, initialize, finalize, finalize_results_version } = providerInstance.exports;
+  const version = finalize_results_version();
+  if (version !== FINALIZE_RESULTS_VERSION) {
+    throw new Error(`Unsupported finalize results version ${version}`);
+  }
+
+  const inputBytes = encode(input);
+  const inputOffset = initialize(inputBytes.length);
+  if (inputOffset === 0) {
+    throw new Error(`The provider could not allocate ${inputBytes.length} bytes for the input`);
+  }
+  new Uint8Array(memory.buffer, inputOffset, inputBytes.length).set(inputBytes);
+
+  const functionInstance = await WebAssembly.instantiate(functionModule, {
+    ...imports,
+    [providerModuleName(providerInstance)]: providerInstance.exports,
+  });
+
+  let trap;
+  try {
+    functionInstance.exports._start();
+  } catch (e) {
+    trap = e;
+  }
+
+  // the memory may have grown, so views are only created once the function has finished. Views
+  // outside of the memory throw a RangeError. The output is never compressed, as compression isn't
+  // requested.
+  const [
+    outputOffset,
+    outputLen,
+    logsOffset1,
+    logsLen1,
+    logsOffset2,
+    logsLen2,
+    ,
+    logRecordsOffset,
+    logRecordsLen,
+  ] = new Uint32Array(memory.buffer, finalize(), 9);
+  const logBytes = new Uint8Array(logsLen1 + logsLen2);
+  logBytes.set(new Uint8Array(memory.buffer, logsOffset1, logsLen1));
+  logBytes.set(new Uint8Array(memory.buffer, logsOffset2, logsLen2), logsLen1);
+  const logs = new TextDecoder().decode(logBytes);
+
+  if (trap !== undefined) {
+    throw new FunctionRunError(trap, logs);
+  }
+
+  const outputBytes = new Uint8Array(memory.buffer, outputOffset, outputLen);
+  const output = outputLen === 0 ? undefined : decode(outputBytes);
+  const logRecords = decode(new Uint8Array(memory.buffer, logRecordsOffset, logRecordsLen)).map(
+    ({ level, sequence, message }) => ({ level: LOG_LEVELS[level] ?? level, sequence, message }),
+  );
+  return { output, logs, logRecords };
+}
@@ -0,0 +1,237 @@
+// A minimal MessagePack codec for JSON values, which is the format the provider reads its input in
+// and writes its output in.
+
+const textEncoder = new TextEncoder();
+const textDecoder = new TextDecoder();
+
+class Writer {
+  constructor() {
+    this.bytes = new Uint8Array(1024);
+    this.view = new DataView(this.bytes.buffer);
+    this.len = 0;
+  }
+
+  reserve(len) {
+    if (this.len + len <= this.bytes.length) {
+      return;
+    }
+    let capacity = this.bytes.length * 2;
+    while (capacity < this.len + len) {
+      capacity *= 2;
+    }
+    const bytes = new Uint8Array(capacity);
+    bytes.set(this.bytes.subarray(0, this.len));
+    this.bytes = bytes;
+    this.view = new DataView(bytes.buffer);
+  }
+
+  u8(value) {
+    this.reserve(1);
+    this.view.setUint8(this.len, value);
+    this.len += 1;
+  }
+
+  u16(value) {
+    this.reserve(2);
+    this.view.setUint16(this.len, value);
+    this.len += 2;
+  }
+
+  u32(value) {
+    this.reserve(4);
+    this.view.setUint32(this.len, value);
+    this.len += 4;
+  }
+
+  raw(bytes) {
+    this.reserve(bytes.length);
+    this.bytes.set(bytes, this.len);
+    this.len += bytes.length;
+  }
+
+  // Writes the marker and length of a string, array or map, using the smallest format that fits.
+  header(len, fixMarker, fixMax, marker8, marker16, marker32) {
+    if (len <= fixMax) {
+      this.u8(fixMarker | len);
+    } else if (marker8 !== undefined && len < 0x100) {
+      this.u8(marker8);
+      this.u8(len);
+    } else if (len < 0x10000) {
+      this.u8(marker16);
+      this.u16(len);
+    } else {
+      this.u8(marker32);
+      this.u32(len);
+    }
+  }
+
+  number(value) {
+    if (!Number.isInteger(value) || !Number.isSafeInteger(value)) {
+      this.u8(0xcb);
+      this.reserve(8);
+      this.view.setFloat64(this.len, value);
+      this.len += 8;
+    } else if (value >= 0 && value < 0x80) {
+      this.u8(value);
+    } else if (value >= -32 && value < 0) {
+      this.u8(value & 0xff);
+    } else if (value >= -0x80000000 && value <= 0x7fffffff) {
+      this.u8(0xd2);
+      this.reserve(4);
+      this.view.setInt32(this.len, value);
+      this.len += 4;
+    } else {
+      this.u8(value < 0 ? 0xd3 : 0xcf);
+      this.reserve(8);
+      this.view.setBigInt64(this.len, BigInt(value));
+      this.len += 8;
+    }
+  }
+
+  value(value) {
+    if (value === null || value === undefined) {
+      this.u8(0xc0);
+    } else if (value === false) {
+      this.u8(0xc2);
+    } else if (value === true) {
+      this.u8(0xc3);
+    } else if (typeof value === "number") {
+      this.number(value);
+    } else if (typeof value === "string") {
+      const bytes = textEncoder.encode(value);
+      this.header(bytes.length, 0xa0, 31, 0xd9, 0xda, 0xdb);
+      this.raw(bytes);
+    } else if (Array.isArray(value)) {
+      this.header(value.length, 0x90, 15, undefined, 0xdc, 0xdd);
+      for (const element of value) {
+        this.value(element);
+      }
+    } else if (typeof value === "object") {
+      const entries = Object.entries(value).filter(([, v]) => v !== undefined);
+      this.header(entries.length, 0x80, 15, undefined, 0xde, 0xdf);
+      for (const [key, v] of entries) {
+        this.value(key);
+        this.value(v);
+      }
+    } else {
+      throw new TypeError(`Cannot encode a value of type ${typeof value}`);
+    }
+  }
+}
+
+/**
+ * Encodes a JSON value as MessagePack.
+ * @param {unknown} value
+ * @returns {Uint8Array}
+ */
+export function encode(value) {
+  const writer = new Writer();
+  writer.value(value);
+  return writer.bytes.slice(0, writer.len);
+}
+
+class Reader {
+  constructor(bytes) {
+    this.bytes = bytes;
+    this.view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+    this.pos = 0;
+  }
+
+  advance(len) {
+    const pos = this.pos;
+    if (pos + len > this.bytes.length) {
+      throw new RangeError("Unexpected end of MessagePack input");
+    }
+    this.pos += len;
+    return pos;
+  }
+
+  u8() {
+    return this.view.getUint8(this.advance(1));
+  }
+
+  u16() {
+    return this.view.getUint16(this.advance(2));
+  }
+
+  u32() {
+    return this.view.getUint32(this.advance(4));
+  }
+
+  str(len) {
+    const pos = this.advance(len);
+    return textDecoder.decode(this.bytes.subarray(pos, pos + len));
+  }
+
+  bin(len) {
+    const pos = this.advance(len);
+    return this.bytes.slice(pos, pos + len);
+  }
+
+  array(len) {
+    const array = new Array(len);
+    for (let i = 0; i < len; i++) {
+      array[i] = this.value();
+    }
+    return array;
+  }
+
+  map(len) {
+    const map = {};
+    for (let i = 0; i < len; i++) {
+      const key = this.value();
+      map[key] = this.value();
+    }
+    return map;
+  }
+
+  value() {
+    const marker = this.u8();
+    if (marker < 0x80) return marker;
+    if (marker < 0x90) return this.map(marker & 0x0f);
+    if (marker < 0xa0) return this.array(marker & 0x0f);
+    if (marker < 0xc0) return this.str(marker & 0x1f);
+    if (marker >= 0xe0) return marker - 0x100;
+    switch (marker) {
+      case 0xc0: return null;
+      case 0xc2: return false;
+      case 0xc3: return true;
+      case 0xc4: return this.bin(this.u8());
+      case 0xc5: return this.bin(this.u16());
+      case 0xc6: return this.bin(this.u32());
+      case 0xca: return this.view.getFloat32(this.advance(4));
+      case 0xcb: return this.view.getFloat64(this.advance(8));
+      case 0xcc: return this.u8();
+      case 0xcd: return this.u16();
+      case 0xce: return this.u32();
+      case 0xcf: return Number(this.view.getBigUint64(this.advance(8)));
+      case 0xd0: return this.view.getInt8(this.advance(1));
+      case 0xd1: return this.view.getInt16(this.advance(2));
+      case 0xd2: return this.view.getInt32(this.advance(4));
+      case 0xd3: return Number(this.view.getBigInt64(this.advance(8)));
+      case 0xd9: return this.str(this.u8());
+      case 0xda: return this.str(this.u16());
+      case 0xdb: return this.str(this.u32());
+      case 0xdc: return this.array(this.u16());
+      case 0xdd: return this.array(this.u32());
+      case 0xde: return this.map(this.u16());
+      case 0xdf: return this.map(this.u32());
+      default:
+        throw new TypeError(`Unsupported MessagePack marker 0x${marker.toString(16)}`);
+    }
+  }
+}
+
+/**
+ * Decodes a MessagePack value. 64-bit integers are decoded as numbers, so they may lose precision.
+ * @param {Uint8Array} bytes
+ * @returns {unknown}
+ */
+export function decode(bytes) {
+  const reader = new Reader(bytes);
+  const value = reader.value();
+  if (reader.pos !== bytes.length) {
+    throw new RangeError("Unexpected trailing bytes after MessagePack value");
+  }
+  return value;
+}
@@ -0,0 +1,90 @@
+import assert from "node:assert/strict";
+import { readFile } from "node:fs/promises";
+import { test } from "node:test";
+
+import { decode, encode, FunctionRunError, runFunction } from "../index.js";
+
+// Examples built for wasm32-unknown-unknown and trampolined, as described in the README.
+const examplesDir = new URL("../../target/wasm32-unknown-unknown/release/examples/", import.meta.url);
+
+function example(name) {
+  return readFile(new URL(`${name}.merged.wasm`, examplesDir));
+}
+
+// Trampolined functions have their own memory as well as the provider's.
+const multiMemory = WebAssembly.validate(
+  new Uint8Array([0, 97, 115, 109, 1, 0, 0, 0, 5, 5, 2, 0, 1, 0, 1]),
+);
+const skipWithoutMultiMemory = multiMemory ? false : "the host does not support multi-memory";
+
+test("msgpack round trip", () => {
+  const values = [
+    null,
+    true,
+    false,
+    0,
+    127,
+    -32,
+    -33,
+    255,
+    65_536,
+    -2_147_483_648,
+    2 ** 40,
+    -(2 ** 40),
+    1.5,
+    "",
+    "a".repeat(31),
+    "✌️".repeat(100),
+    "a".repeat(70_000),
+    [],
+    Array.from({ length: 20 }, (_, i) => i),
+    {},
+    Object.fromEntries(Array.from({ length: 20 }, (_, i) => [`key${i}`, { nested: [i] }])),
+  ];
+  for (const value of values) {
+    assert.deepEqual(decode(encode(value)), value);
+  }
+});
+
+test("runs a function using the provider's memory", async () => {
+  const func = await readFile(new URL("minimal.wasm", import.meta.url));
+  assert.deepEqual(await runFunction({ function: func, input: 21 }), {
+    output: [42, true],
+    logs: "",
+    logRecords: [],
+  });
+  await assert.rejects(runFunction({ function: func, input: -1 }), FunctionRunError);
+});
+
+test("runs a function", { skip: skipWithoutMultiMemory }, async () => {
+  const input = { foo: "bar", numbers: [1, 2.5, -3], nested: { a: null, b: true } };
+  const { output, logs } = await runFunction({ function: await example("echo"), input });
+  assert.deepEqual(output, input);
+  assert.equal(logs, "");
+});
+
+test("returns logs", { skip: skipWithoutMultiMemory }, async () => {
+  const { logs } = await runFunction({ function: await example("log"), input: null });
+  assert.equal(logs, "Hi!\nHello\nHere's a third string\n✌️\n");
+});
+
+test("returns log records", { skip: skipWithoutMultiMemory }, async () => {
+  const { logs, logRecords } = await runFunction({
+    function: await example("log-records"),
+    input: null,
+  });
+  assert.equal(logs, "Hi!\n");
+  assert.deepEqual(logRecords, [
+    { level: "info", sequence: 0, message: "Starting" },
+    { level: "warn", sequence: 1, message: "No cart lines" },
+    { level: "error", sequence: 2, message: "✌️" },
+  ]);
+});
+
+test("reports traps with logs", { skip: skipWithoutMultiMemory }, async () => {
+  await assert.rejects(runFunction({ function: await example("panic"), input: null }), (error) => {
+    assert(error instanceof FunctionRunError);
+    assert.match(error.logs, /at the disco/);
+    return true;
+  });
+});
@@ -0,0 +1,22 @@
+;; A function that imports the provider's memory instead of having its own, so it runs on hosts
+;; without multi-memory support. It outputs `[2 * input, true]` for a number input, and traps if
+;; the input is negative.
+(module
+  (import "shopify_function_v2" "memory" (memory 1))
+  (import "shopify_function_v2" "_shopify_function_input_get" (func $input_get (result i64)))
+  (import "shopify_function_v2" "_shopify_function_output_new_array" (func $new_array (param i32) (result i32)))
+  (import "shopify_function_v2" "_shopify_function_output_new_i32" (func $new_i32 (param i32) (result i32)))
+  (import "shopify_function_v2" "_shopify_function_output_new_bool" (func $new_bool (param i32) (result i32)))
+  (import "shopify_function_v2" "_shopify_function_output_finish_array" (func $finish_array (result i32)))
+
+  (func (export "_start")
+    (local $input f64)
+    ;; numbers are NaN-boxed as their f64 bits
+    (local.set $input (f64.reinterpret_i64 (call $input_get)))
+    (if (f64.lt (local.get $input) (f64.const 0))
+      (then unreachable))
+    (drop (call $new_array (i32.const 2)))
+    (drop (call $new_i32 (i32.mul (i32.trunc_f64_s (local.get $input)) (i32.const 2))))
+    (drop (call $new_bool (i32.const 1)))
+    (drop (call $finish_array)))
+)
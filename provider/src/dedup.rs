@@ -0,0 +1,140 @@
+use crate::{decorate_for_target, Context};
+use shopify_function_wasm_api_core::InternedStringId;
+use std::{
+    collections::HashSet,
+    hash::{BuildHasherDefault, DefaultHasher},
+};
+
+/// A set of keys inserted by the guest to skip duplicates, such as operations keyed by ID, without
+/// building a set of strings in the guest.
+#[derive(Default)]
+pub(crate) struct DedupSet {
+    /// Whether each interned string was inserted, indexed by interned string ID, as IDs are dense.
+    interned: Vec<bool>,
+    /// Hashes of keys computed by the guest.
+    hashes: HashSet<u64, BuildHasherDefault<DefaultHasher>>,
+}
+
+impl DedupSet {
+    fn insert_interned(&mut self, id: InternedStringId) -> bool {
+        if id >= self.interned.len() {
+            self.interned.resize(id + 1, false);
+        }
+        !std::mem::replace(&mut self.interned[id], true)
+    }
+}
+
+decorate_for_target! {
+    /// Returns the ID of a new empty set, which is valid until it's dropped.
+    fn shopify_function_dedup_set_new() -> usize {
+        Context::with_mut(|context| {
+            // the IDs of dropped sets are reused, like those of input iterators
+            match context.dedup_sets.iter().position(Option::is_none) {
+                Some(id) => {
+                    context.dedup_sets[id] = Some(DedupSet::default());
+                    id
+                }
+                None => {
+                    context.dedup_sets.push(Some(DedupSet::default()));
+                    context.dedup_sets.len() - 1
+                }
+            }
+        })
+    }
+}
+
+decorate_for_target! {
+    /// Returns 1 if the interned string wasn't in the set, or if the set or string doesn't exist, and 0 otherwise.
+    fn shopify_function_dedup_set_insert_interned(set: usize, id: InternedStringId) -> u32 {
+        Context::with_mut(|context| {
+            // unknown sets and strings count as new, so nothing is skipped by mistake
+            if !context.string_interner.contains(id) {
+                return 1;
+            }
+            // the same string can be interned more than once, so it's keyed by its first ID
+            let id = context.string_interner.canonical_id(id);
+            match context.dedup_sets.get_mut(set) {
+                Some(Some(set)) => set.insert_interned(id) as u32,
+                _ => 1,
+            }
+        })
+    }
+}
+
+decorate_for_target! {
+    /// Returns 1 if the hash wasn't in the set, or if the set doesn't exist, and 0 otherwise.
+    fn shopify_function_dedup_set_insert_hash(set: usize, hash: u64) -> u32 {
+        Context::with_mut(|context| match context.dedup_sets.get_mut(set) {
+            Some(Some(set)) => set.hashes.insert(hash) as u32,
+            _ => 1,
+        })
+    }
+}
+
+decorate_for_target! {
+    /// Drops the set, so its ID can be reused.
+    fn shopify_function_dedup_set_drop(set: usize) -> () {
+        Context::with_mut(|context| {
+            if let Some(slot) = context.dedup_sets.get_mut(set) {
+                *slot = None;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::initialize_from_bytes;
+    use shopify_function_wasm_api_core::host::InputEncoding;
+
+    #[test]
+    fn test_dedup_set() {
+        initialize_from_bytes(b"null".to_vec(), InputEncoding::Json);
+        let (a, b) = Context::with_mut(|context| {
+            (
+                context.string_interner.intern(b"a"),
+                context.string_interner.intern(b"b"),
+            )
+        });
+        let set = shopify_function_dedup_set_new();
+        let other_set = shopify_function_dedup_set_new();
+        assert_ne!(set, other_set);
+        assert_eq!(shopify_function_dedup_set_insert_interned(set, b), 1);
+        assert_eq!(shopify_function_dedup_set_insert_interned(set, a), 1);
+        assert_eq!(shopify_function_dedup_set_insert_interned(set, b), 0);
+        // strings interned again by the guest have another ID, but are the same key
+        let (b_again, ptr) = Context::with_mut(|context| context.string_interner.preallocate(1));
+        unsafe { *(ptr as *mut u8) = b'b' };
+        assert_ne!(b_again, b);
+        assert_eq!(shopify_function_dedup_set_insert_interned(set, b_again), 0);
+        assert_eq!(shopify_function_dedup_set_insert_hash(set, 42), 1);
+        assert_eq!(shopify_function_dedup_set_insert_hash(set, 42), 0);
+        // sets are independent
+        assert_eq!(shopify_function_dedup_set_insert_interned(other_set, b), 1);
+        assert_eq!(shopify_function_dedup_set_insert_hash(other_set, 42), 1);
+
+        // unknown strings and sets count as new
+        assert_eq!(
+            shopify_function_dedup_set_insert_interned(set, usize::MAX),
+            1
+        );
+        assert_eq!(
+            shopify_function_dedup_set_insert_interned(set, usize::MAX),
+            1
+        );
+        shopify_function_dedup_set_drop(set);
+        assert_eq!(shopify_function_dedup_set_insert_hash(set, 42), 1);
+        assert_eq!(shopify_function_dedup_set_insert_hash(set, 42), 1);
+
+        // the IDs of dropped sets are reused for new, empty sets
+        assert_eq!(shopify_function_dedup_set_new(), set);
+        assert_eq!(shopify_function_dedup_set_insert_interned(set, b), 1);
+
+        // sets don't outlive the input
+        initialize_from_bytes(b"null".to_vec(), InputEncoding::Json);
+        let b = Context::with_mut(|context| context.string_interner.intern(b"b"));
+        assert_eq!(shopify_function_dedup_set_insert_hash(other_set, 42), 1);
+        assert_eq!(shopify_function_dedup_set_insert_interned(other_set, b), 1);
+    }
+}
@@ -0,0 +1,69 @@
+use crate::{decorate_for_target, Context};
+use shopify_function_wasm_api_core::{
+    host_call::HostCallId,
+    read::{ErrorCode, NanBox, Val},
+    InternedStringId,
+};
+
+impl Context {
+    fn start_host_call(
+        &mut self,
+        _capability: InternedStringId,
+        _argument: InternedStringId,
+    ) -> HostCallId {
+        let id = self.host_call_count;
+        self.host_call_count += 1;
+        id
+    }
+
+    fn poll_host_call(&self, id: HostCallId) -> NanBox {
+        if id >= self.host_call_count {
+            return NanBox::error(ErrorCode::UnknownHostCall);
+        }
+        // no capabilities are supported yet, so every call finishes as unsupported
+        NanBox::error(ErrorCode::HostCallUnsupported)
+    }
+}
+
+decorate_for_target! {
+    fn shopify_function_host_call_start(
+        capability: InternedStringId,
+        argument: InternedStringId,
+    ) -> HostCallId {
+        Context::with_mut(|context| {
+            context.start_host_call(capability, argument)
+        })
+    }
+}
+
+decorate_for_target! {
+    fn shopify_function_host_call_poll(id: HostCallId) -> Val {
+        Context::with(|context| {
+            context.poll_host_call(id).to_bits()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shopify_function_wasm_api_core::read::ValueRef;
+
+    #[test]
+    fn test_host_calls_are_unsupported() {
+        let mut context = Context::new(Vec::new());
+        assert_eq!(
+            context.poll_host_call(0).try_decode().unwrap(),
+            ValueRef::Error(ErrorCode::UnknownHostCall)
+        );
+        let first = context.start_host_call(0, 0);
+        let second = context.start_host_call(0, 0);
+        assert_ne!(first, second);
+        [first, second].into_iter().for_each(|id| {
+            assert_eq!(
+                context.poll_host_call(id).try_decode().unwrap(),
+                ValueRef::Error(ErrorCode::HostCallUnsupported)
+            );
+        });
+    }
+}
@@ -1,14 +1,27 @@
 mod alloc;
+pub mod dedup;
+pub mod host_call;
 pub mod log;
 pub mod read;
 mod string_interner;
 pub mod write;
 
 use bumpalo::Bump;
+use dedup::DedupSet;
+use read::{InputIter, Pin, PropertyHint, Shape};
 use rmp::encode::ByteBuf;
+#[cfg(all(target_family = "wasm", feature = "json-output"))]
+use shopify_function_wasm_api_core::host::OutputEncoding;
+#[cfg(target_family = "wasm")]
+use shopify_function_wasm_api_core::host::{FinalizeResults, MemoryRegion, OutputCompression};
+use shopify_function_wasm_api_core::{
+    host::{ArenaConfig, InputCompression, InputEncoding, InputKeyValidation},
+    read::{ErrorCode, Val},
+    InternedStringId,
+};
 use std::cell::RefCell;
 use string_interner::StringInterner;
-use write::State;
+use write::{OutputCheckpoint, SkipNullsObject, State};
 
 pub const PROVIDER_MODULE_NAME: &str =
     concat!("shopify_function_v", env!("CARGO_PKG_VERSION_MAJOR"));
@@ -21,11 +34,86 @@ type DoubleUsize = u64;
 struct Context {
     bump_allocator: bumpalo::Bump,
     input_bytes: Vec<u8>,
+    /// The encoding of `input_bytes`, which are converted to msgpack when the input is first read.
+    input_encoding: InputEncoding,
+    /// The compression of `input_bytes`, which are decompressed when the input is first read.
+    input_compression: InputCompression,
+    /// How the keys of objects in the input are checked when it's first read.
+    input_key_validation: InputKeyValidation,
+    /// Whether the keys of objects in the input were checked.
+    input_keys_validated: bool,
+    /// Whether the keys of objects in the input were checked to be sorted, so property lookups can
+    /// stop once they're past where the key would be.
+    input_keys_sorted: bool,
     output_bytes: ByteBuf,
+    /// The number of bytes past the end of `output_bytes` that the guest is copying into, which are
+    /// added to it by `commit_output` once the copy is done.
+    uncommitted_output_len: usize,
+    /// The number of bytes of output the host has read while the function was running.
+    output_read_len: usize,
+    /// The number of bytes the host read that were removed from the start of `output_bytes`.
+    /// Positions in the output, such as those of length markers and checkpoints, include them.
+    output_removed_len: usize,
+    /// The output converted to JSON by `finalize`, when JSON output was requested.
+    #[cfg(all(target_family = "wasm", feature = "json-output"))]
+    json_output: Option<Vec<u8>>,
+    #[cfg(all(target_family = "wasm", feature = "json-output"))]
+    output_encoding: OutputEncoding,
+    #[cfg(all(target_family = "wasm", feature = "zstd-output"))]
+    output_compression: write::compression::OutputCompressionConfig,
+    /// The output compressed by `finalize`, when compression was requested.
+    #[cfg(all(target_family = "wasm", feature = "zstd-output"))]
+    compressed_output: Option<Vec<u8>>,
     logs: Logs,
+    log_records: LogRecords,
     write_state: State,
     write_parent_state_stack: Vec<State>,
+    /// Whether the output was returned to a native caller, which dropping a context in debug builds
+    /// checks for.
+    #[cfg(not(target_family = "wasm"))]
+    output_finalized: bool,
+    /// Positions in the output of the length markers of unfinished dynamic-length arrays and objects
+    /// that skip null fields, which are patched when they're finished.
+    length_marker_positions: Vec<usize>,
+    /// Objects being written that leave out null fields, innermost last.
+    skip_nulls_objects: Vec<SkipNullsObject>,
+    /// Whether objects started while it's set leave out fields whose value is null, set by the
+    /// guest. Nulls written while it's unset are kept, so they can be written explicitly.
+    skip_nulls: bool,
+    /// Checkpoints the output can be rolled back to, ordered by ID.
+    output_checkpoints: Vec<OutputCheckpoint>,
+    next_output_checkpoint_id: usize,
     string_interner: StringInterner,
+    /// Whether the interned strings and shapes are kept when the provider is next initialized, set
+    /// by the host. The native provider always keeps them.
+    #[cfg(target_family = "wasm")]
+    retain_string_interner: bool,
+    /// Where each interned string was last found as an object key, indexed by interned string ID.
+    property_hints: Vec<PropertyHint>,
+    /// Object shapes, indexed by shape ID.
+    shapes: Vec<Shape>,
+    /// Iterators over arrays and objects in the input, indexed by iterator ID. Dropped iterators
+    /// leave a `None` whose ID is reused.
+    input_iters: Vec<Option<InputIter>>,
+    /// Values in the input the guest pinned, indexed by the low bits of their pin ID. Unpinned
+    /// values leave a `None` whose index is reused by a pin with another ID.
+    pinned_values: Vec<Option<Pin>>,
+    /// Sets of keys the guest inserted to skip duplicates, indexed by set ID. Dropped sets leave a
+    /// `None` whose ID is reused.
+    dedup_sets: Vec<Option<DedupSet>>,
+    /// Scratch buffer for values read in batches, such as array elements or the properties of a
+    /// shape, reused across calls.
+    value_batch: Vec<Val>,
+    /// Scratch buffer for bytes copied to the guest, such as values encoded as MessagePack or arrays
+    /// of booleans packed into bits, reused across calls.
+    msgpack_buffer: Vec<u8>,
+    /// The number of host calls started, which is also the ID of the next one.
+    host_call_count: usize,
+    /// The seed derived from the input, computed the first time it's requested.
+    deterministic_seed: Option<u64>,
+    /// The number of reads of the input left before they fail with `ErrorCode::BudgetExceeded`,
+    /// set by the guest. It can't run out within the fuel limit unless it's set.
+    work_budget: usize,
 }
 
 thread_local! {
@@ -34,7 +122,7 @@ thread_local! {
 
 #[cfg(target_family = "wasm")]
 thread_local! {
-    static OUTPUT_AND_LOG_PTRS: RefCell<[usize; 6]> = const { RefCell::new([0; 6]) };
+    static FINALIZE_RESULTS: RefCell<FinalizeResults> = RefCell::new(FinalizeResults::default());
 }
 
 impl Default for Context {
@@ -42,11 +130,47 @@ impl Default for Context {
         Self {
             bump_allocator: Bump::new(),
             input_bytes: Vec::new(),
+            input_encoding: InputEncoding::Msgpack,
+            input_compression: InputCompression::None,
+            input_key_validation: InputKeyValidation::None,
+            input_keys_validated: false,
+            input_keys_sorted: false,
             output_bytes: ByteBuf::with_capacity(1024),
+            uncommitted_output_len: 0,
+            output_read_len: 0,
+            output_removed_len: 0,
+            #[cfg(all(target_family = "wasm", feature = "json-output"))]
+            json_output: None,
+            #[cfg(all(target_family = "wasm", feature = "json-output"))]
+            output_encoding: OutputEncoding::Msgpack,
+            #[cfg(all(target_family = "wasm", feature = "zstd-output"))]
+            output_compression: Default::default(),
+            #[cfg(all(target_family = "wasm", feature = "zstd-output"))]
+            compressed_output: None,
             logs: Logs::default(),
+            log_records: LogRecords::default(),
             write_state: State::Start,
             write_parent_state_stack: Vec::new(),
+            #[cfg(not(target_family = "wasm"))]
+            output_finalized: false,
+            length_marker_positions: Vec::new(),
+            skip_nulls_objects: Vec::new(),
+            skip_nulls: false,
+            output_checkpoints: Vec::new(),
+            next_output_checkpoint_id: 0,
             string_interner: StringInterner::new(),
+            #[cfg(target_family = "wasm")]
+            retain_string_interner: false,
+            property_hints: Vec::new(),
+            shapes: Vec::new(),
+            input_iters: Vec::new(),
+            pinned_values: Vec::new(),
+            dedup_sets: Vec::new(),
+            value_batch: Vec::new(),
+            msgpack_buffer: Vec::new(),
+            host_call_count: 0,
+            deterministic_seed: None,
+            work_budget: usize::MAX,
         }
     }
 }
@@ -60,6 +184,53 @@ impl Context {
         }
     }
 
+    /// Get the output in the requested encoding. Output that isn't finished or can't be represented
+    /// as JSON is empty.
+    #[cfg(all(target_family = "wasm", feature = "json-output"))]
+    fn finalize_output(&mut self) -> &[u8] {
+        match self.output_encoding {
+            OutputEncoding::Msgpack => self.unread_output(),
+            OutputEncoding::Json | OutputEncoding::CompactJson => {
+                let pretty = self.output_encoding == OutputEncoding::Json;
+                let json = (self.write_state == write::State::End)
+                    .then(|| write::json::from_msgpack(self.output_bytes.as_slice(), pretty))
+                    .flatten();
+                self.json_output.insert(json.unwrap_or_default())
+            }
+        }
+    }
+
+    /// Decompress the input if it is compressed.
+    fn decompress_input(&mut self) -> Result<(), ErrorCode> {
+        if self.input_compression != InputCompression::None {
+            self.input_bytes =
+                read::compression::decompress(self.input_compression, &self.input_bytes)?;
+            self.input_compression = InputCompression::None;
+        }
+        Ok(())
+    }
+
+    /// Decompress the input, and convert it to msgpack if it is in another encoding.
+    fn decode_input(&mut self) -> Result<(), ErrorCode> {
+        self.decompress_input()?;
+        match self.input_encoding {
+            InputEncoding::Msgpack => {}
+            InputEncoding::Json => {
+                // the seed is derived from the input as it was passed
+                self.deterministic_seed
+                    .get_or_insert_with(|| fnv1a_64(&self.input_bytes));
+                self.input_bytes = read::json::to_msgpack(&self.input_bytes)?;
+                self.input_encoding = InputEncoding::Msgpack;
+            }
+        }
+        if self.input_key_validation != InputKeyValidation::None && !self.input_keys_validated {
+            read::keys::validate(&self.input_bytes, self.input_key_validation)?;
+            self.input_keys_validated = true;
+            self.input_keys_sorted = self.input_key_validation == InputKeyValidation::Sorted;
+        }
+        Ok(())
+    }
+
     fn with<F, T>(f: F) -> T
     where
         F: FnOnce(&Context) -> T,
@@ -73,20 +244,32 @@ impl Context {
     {
         CONTEXT.with_borrow_mut(f)
     }
+
+    /// Like [`Context::with_mut`], for calls that write or read the output. The bytes the guest
+    /// copied into the output after the last of these calls are committed first.
+    fn with_output<F, T>(f: F) -> T
+    where
+        F: FnOnce(&mut Context) -> T,
+    {
+        CONTEXT.with_borrow_mut(|context| {
+            context.commit_output();
+            f(context)
+        })
+    }
 }
 
 macro_rules! decorate_for_target {
-    ($(#[doc = $docs:tt])? fn $fn_name:ident($($args:tt)*) -> $ret:ty {
+    ($(#[doc = $docs:tt])* fn $fn_name:ident($($args:tt)*) -> $ret:ty {
         $($body:tt)*
     }) => {
         #[cfg(target_family = "wasm")]
-        $(#[doc = $docs])?
+        $(#[doc = $docs])*
         #[export_name = concat!("_", stringify!($fn_name))]
         extern "C" fn $fn_name($($args)*) -> $ret {
             $($body)*
         }
         #[cfg(not(target_family = "wasm"))]
-        $(#[doc = $docs])?
+        $(#[doc = $docs])*
         pub fn $fn_name($($args)*) -> $ret {
             $($body)*
         }
@@ -95,47 +278,342 @@ macro_rules! decorate_for_target {
 
 pub(crate) use decorate_for_target;
 
-use crate::log::Logs;
+use crate::log::{LogRecords, Logs};
 
 #[cfg(target_family = "wasm")]
 #[export_name = "initialize"]
 extern "C" fn initialize(input_len: usize) -> *const u8 {
+    initialize_with_encoding(input_len, InputEncoding::Msgpack as u32)
+}
+
+#[cfg(target_family = "wasm")]
+#[export_name = "initialize_with_encoding"]
+extern "C" fn initialize_with_encoding(input_len: usize, encoding: u32) -> *const u8 {
+    initialize_with_compression(input_len, encoding, InputCompression::None as u32)
+}
+
+#[cfg(target_family = "wasm")]
+#[export_name = "initialize_with_compression"]
+extern "C" fn initialize_with_compression(
+    input_len: usize,
+    encoding: u32,
+    compression: u32,
+) -> *const u8 {
+    let arena = ArenaConfig::default();
+    initialize_with_arena(
+        input_len,
+        encoding,
+        compression,
+        arena.initial_capacity,
+        arena.allocation_limit,
+    )
+}
+
+#[cfg(target_family = "wasm")]
+#[export_name = "initialize_with_arena"]
+extern "C" fn initialize_with_arena(
+    input_len: usize,
+    encoding: u32,
+    compression: u32,
+    arena_initial_capacity: usize,
+    arena_allocation_limit: usize,
+) -> *const u8 {
+    let Some(bump_allocator) = new_arena(ArenaConfig {
+        initial_capacity: arena_initial_capacity,
+        allocation_limit: arena_allocation_limit,
+    }) else {
+        return std::ptr::null();
+    };
+    let Some(input_encoding) = InputEncoding::from_repr(encoding) else {
+        return std::ptr::null();
+    };
+    let Some(input_compression) = InputCompression::from_repr(compression)
+        .filter(|compression| read::compression::is_supported(*compression))
+    else {
+        return std::ptr::null();
+    };
     CONTEXT.with_borrow_mut(|context| {
+        use std::mem;
+
+        let retained = context.retain_string_interner.then(|| {
+            (
+                mem::take(&mut context.string_interner),
+                mem::take(&mut context.shapes),
+            )
+        });
         *context = Context::default();
-        context.input_bytes = vec![0; input_len];
+        if let Some((string_interner, shapes)) = retained {
+            context.string_interner = string_interner;
+            context.shapes = shapes;
+            context.retain_string_interner = true;
+        }
+        context.bump_allocator = bump_allocator;
+        context.input_encoding = input_encoding;
+        context.input_compression = input_compression;
+        // a null pointer tells the host the input is too large, rather than trapping
+        if context.input_bytes.try_reserve_exact(input_len).is_err() {
+            return std::ptr::null();
+        }
+        context.input_bytes.resize(input_len, 0);
         context.input_bytes.as_ptr()
     })
 }
 
+/// Create the arena the input is read into, or `None` if its first chunk can't be allocated.
+fn new_arena(config: ArenaConfig) -> Option<Bump> {
+    let bump = match config.initial_capacity {
+        0 => Bump::new(),
+        capacity => Bump::try_with_capacity(capacity).ok()?,
+    };
+    bump.set_allocation_limit(Some(config.allocation_limit).filter(|limit| *limit != 0));
+    Some(bump)
+}
+
 #[cfg(not(target_family = "wasm"))]
 pub fn initialize_from_msgpack_bytes(bytes: Vec<u8>) {
+    initialize_from_bytes(bytes, InputEncoding::Msgpack)
+}
+
+#[cfg(not(target_family = "wasm"))]
+pub fn initialize_from_bytes(bytes: Vec<u8>, encoding: InputEncoding) {
+    initialize_from_compressed_bytes(bytes, encoding, InputCompression::None);
+}
+
+/// Initialize the provider with compressed input, as a host does with `initialize_with_compression`.
+/// Returns `false`, leaving the provider as it was, if `compression` isn't supported.
+#[cfg(not(target_family = "wasm"))]
+pub fn initialize_from_compressed_bytes(
+    bytes: Vec<u8>,
+    encoding: InputEncoding,
+    compression: InputCompression,
+) -> bool {
+    initialize_with_arena(bytes, encoding, compression, ArenaConfig::default())
+}
+
+/// Initialize the provider with an arena sized by `arena`, as a host does with
+/// `initialize_with_arena`. Returns `false`, leaving the provider as it was, if `compression` isn't
+/// supported or the arena's first chunk can't be allocated.
+#[cfg(not(target_family = "wasm"))]
+pub fn initialize_with_arena(
+    bytes: Vec<u8>,
+    encoding: InputEncoding,
+    compression: InputCompression,
+    arena: ArenaConfig,
+) -> bool {
+    if !read::compression::is_supported(compression) {
+        return false;
+    }
+    let Some(bump_allocator) = new_arena(arena) else {
+        return false;
+    };
     CONTEXT.with_borrow_mut(|context| {
         use std::mem;
 
+        // interned strings and shapes are kept, as they're often created once per thread
         let string_interner = mem::take(&mut context.string_interner);
+        let shapes = mem::take(&mut context.shapes);
         *context = Context::new(bytes);
+        context.bump_allocator = bump_allocator;
+        context.input_encoding = encoding;
+        context.input_compression = compression;
         context.string_interner = string_interner;
+        context.shapes = shapes;
+    });
+    true
+}
+
+/// A provider context that isn't installed on any thread, so that hosts can keep several
+/// independent ones in the same process, e.g. to evaluate functions natively. The provider's
+/// functions use the current thread's context, which [`Instance::swap`] exchanges with this one.
+#[cfg(not(target_family = "wasm"))]
+pub struct Instance(Context);
+
+#[cfg(not(target_family = "wasm"))]
+impl Instance {
+    /// Create an instance with its own input, string interner and output.
+    pub fn new(bytes: Vec<u8>, encoding: InputEncoding) -> Self {
+        let mut context = Context::new(bytes);
+        context.input_encoding = encoding;
+        Self(context)
+    }
+
+    /// Exchange this instance with the current thread's context. Swapping again restores the
+    /// thread's context, and this instance's state is kept in between.
+    pub fn swap(&mut self) {
+        CONTEXT.with_borrow_mut(|context| std::mem::swap(context, &mut self.0));
+    }
+}
+
+/// Whether this provider supports input with `compression`, for hosts to check before calling
+/// `initialize_with_compression`.
+#[cfg(target_family = "wasm")]
+#[export_name = "input_compression_supported"]
+extern "C" fn input_compression_supported(compression: u32) -> u32 {
+    InputCompression::from_repr(compression)
+        .is_some_and(read::compression::is_supported)
+        .into()
+}
+
+#[cfg(not(target_family = "wasm"))]
+pub fn input_compression_supported(compression: InputCompression) -> bool {
+    read::compression::is_supported(compression)
+}
+
+/// Returns the generation of the current thread's string interner, which identifies the interner
+/// that interned string IDs obtained on this thread belong to.
+#[cfg(not(target_family = "wasm"))]
+pub fn string_interner_generation() -> usize {
+    CONTEXT.with_borrow(|context| context.string_interner.generation())
+}
+
+/// Returns the strings interned on the current thread with their IDs, in the order they were
+/// interned.
+#[cfg(not(target_family = "wasm"))]
+pub fn interned_strings() -> Vec<(InternedStringId, Vec<u8>)> {
+    CONTEXT.with_borrow(|context| {
+        context
+            .string_interner
+            .iter()
+            .map(|(id, bytes)| (id, bytes.to_vec()))
+            .collect()
     })
 }
 
 #[cfg(target_family = "wasm")]
 #[export_name = "finalize"]
-extern "C" fn finalize() -> *const usize {
-    Context::with(|context| {
-        OUTPUT_AND_LOG_PTRS.with_borrow_mut(|output_and_log_ptrs| {
-            let output = context.output_bytes.as_vec();
-            output_and_log_ptrs[0] = output.as_ptr() as usize;
-            output_and_log_ptrs[1] = output.len();
-            let (log_offset1, log_len1, log_offset2, log_len2) = context.logs.read_ptrs();
-            output_and_log_ptrs[2] = log_offset1 as _;
-            output_and_log_ptrs[3] = log_len1;
-            output_and_log_ptrs[4] = log_offset2 as _;
-            output_and_log_ptrs[5] = log_len2;
-            output_and_log_ptrs.as_ptr()
+extern "C" fn finalize() -> *const FinalizeResults {
+    // pointers and lengths are 32 bits, so they're converted without loss
+    let region = |ptr: *const u8, len: usize| MemoryRegion {
+        offset: ptr as u32,
+        len: len as u32,
+    };
+    Context::with_output(|context| {
+        FINALIZE_RESULTS.with_borrow_mut(|results| {
+            #[cfg(feature = "zstd-output")]
+            let compression = context.output_compression;
+            #[cfg(feature = "json-output")]
+            let output = context.finalize_output();
+            #[cfg(not(feature = "json-output"))]
+            let output = context.unread_output();
+            #[cfg(feature = "zstd-output")]
+            let (output, output_compression) = match compression.compress(output) {
+                Some(compressed) => (
+                    context.compressed_output.insert(compressed).as_slice(),
+                    compression.compression,
+                ),
+                None => (output, OutputCompression::None),
+            };
+            #[cfg(not(feature = "zstd-output"))]
+            let output_compression = OutputCompression::None;
+            let output = region(output.as_ptr(), output.len());
+            let (log_ptr1, log_len1, log_ptr2, log_len2) = context.logs.read_ptrs();
+            let log_records = context.log_records.encode();
+            *results = FinalizeResults {
+                output,
+                logs: [region(log_ptr1, log_len1), region(log_ptr2, log_len2)],
+                output_compression: output_compression as u32,
+                log_records: region(log_records.as_ptr(), log_records.len()),
+            };
+            results as *const FinalizeResults
         })
     })
 }
 
+#[cfg(target_family = "wasm")]
+#[export_name = "output_chunk_available"]
+extern "C" fn output_chunk_available() -> usize {
+    Context::with_output(|context| context.output_chunk_available())
+}
+
+#[cfg(target_family = "wasm")]
+#[export_name = "output_read_chunk"]
+extern "C" fn output_read_chunk(len: usize) -> *const u8 {
+    Context::with_output(|context| {
+        context
+            .read_output_chunk(len)
+            .map_or(std::ptr::null(), <[u8]>::as_ptr)
+    })
+}
+
+#[cfg(target_family = "wasm")]
+#[export_name = "log_dropped_len"]
+extern "C" fn log_dropped_len() -> usize {
+    Context::with(|context| context.logs.dropped_len())
+}
+
+#[cfg(all(target_family = "wasm", feature = "json-output"))]
+#[export_name = "set_output_encoding"]
+extern "C" fn set_output_encoding(encoding: u32) -> u32 {
+    let Some(output_encoding) = OutputEncoding::from_repr(encoding) else {
+        return 0;
+    };
+    CONTEXT.with_borrow_mut(|context| context.output_encoding = output_encoding);
+    1
+}
+
+#[cfg(all(target_family = "wasm", feature = "zstd-output"))]
+#[export_name = "set_output_compression"]
+extern "C" fn set_output_compression(compression: u32, threshold: u32) -> u32 {
+    let Some(compression) = OutputCompression::from_repr(compression) else {
+        return 0;
+    };
+    CONTEXT.with_borrow_mut(|context| {
+        context.output_compression = write::compression::OutputCompressionConfig {
+            compression,
+            threshold: threshold as usize,
+        }
+    });
+    1
+}
+
+/// Keep the interned strings and shapes when the provider is next initialized if `retain` is 1, so
+/// a guest that's kept warm too can keep using the IDs it cached, or reset them with the rest of
+/// the provider if it's 0. Returns 1 if it was set, or 0 for other values.
+#[cfg(target_family = "wasm")]
+#[export_name = "set_retain_string_interner"]
+extern "C" fn set_retain_string_interner(retain: u32) -> u32 {
+    let retain = match retain {
+        0 => false,
+        1 => true,
+        _ => return 0,
+    };
+    CONTEXT.with_borrow_mut(|context| context.retain_string_interner = retain);
+    1
+}
+
+/// Set how the keys of objects in the input are checked when it's first read. Returns 1 if it was
+/// set, or 0 for an unknown [`InputKeyValidation`] or once the keys were checked.
+#[cfg(target_family = "wasm")]
+#[export_name = "set_input_key_validation"]
+extern "C" fn set_input_key_validation(validation: u32) -> u32 {
+    InputKeyValidation::from_repr(validation)
+        .is_some_and(set_input_key_validation_inner)
+        .into()
+}
+
+/// Set how the keys of objects in the input are checked when it's first read, as a host does with
+/// `set_input_key_validation`. Returns `false` if the keys were already checked.
+#[cfg(not(target_family = "wasm"))]
+pub fn set_input_key_validation(validation: InputKeyValidation) -> bool {
+    set_input_key_validation_inner(validation)
+}
+
+fn set_input_key_validation_inner(validation: InputKeyValidation) -> bool {
+    Context::with_mut(|context| {
+        if context.input_keys_validated {
+            return false;
+        }
+        context.input_key_validation = validation;
+        true
+    })
+}
+
+#[cfg(target_family = "wasm")]
+#[export_name = "finalize_results_version"]
+extern "C" fn finalize_results_version() -> u32 {
+    FinalizeResults::VERSION
+}
+
 decorate_for_target! {
     fn shopify_function_intern_utf8_str(len: usize) -> DoubleUsize {
         Context::with_mut(|context| {
@@ -144,3 +622,386 @@ decorate_for_target! {
         })
     }
 }
+
+decorate_for_target! {
+    fn shopify_function_interned_utf8_str_exists(id: InternedStringId) -> u32 {
+        Context::with(|context| {
+            context.string_interner.contains(id) as u32
+        })
+    }
+}
+
+/// Hash bytes with 64-bit FNV-1a, which is simple, fast for small inputs, and stable across
+/// platforms and releases.
+fn fnv1a_64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+decorate_for_target! {
+    fn shopify_function_deterministic_seed() -> u64 {
+        Context::with_mut(|context| {
+            // the seed is derived from the input as it was before it was compressed, and JSON input
+            // is hashed before it's converted to msgpack, in `decode_input`. Input that fails to
+            // decompress is hashed as it was passed, and the error is returned when it's read.
+            let _ = context.decompress_input();
+            *context
+                .deterministic_seed
+                .get_or_insert_with(|| fnv1a_64(&context.input_bytes))
+        })
+    }
+}
+
+decorate_for_target! {
+    /// Sets the number of reads of the input left before they fail with a `BudgetExceeded` error.
+    fn shopify_function_set_work_budget(budget: usize) -> () {
+        Context::with_mut(|context| context.work_budget = budget)
+    }
+}
+
+/// Parse a component of the package version at compile time.
+const fn parse_version_component(component: &str) -> u64 {
+    let bytes = component.as_bytes();
+    let mut value = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        value = value * 10 + (bytes[i] - b'0') as u64;
+        i += 1;
+    }
+    value
+}
+
+/// The version of this provider, with 16 bits each for the major, minor and patch versions.
+const PROVIDER_VERSION: u64 = (parse_version_component(env!("CARGO_PKG_VERSION_MAJOR")) << 32)
+    | (parse_version_component(env!("CARGO_PKG_VERSION_MINOR")) << 16)
+    | parse_version_component(env!("CARGO_PKG_VERSION_PATCH"));
+
+decorate_for_target! {
+    /// Bits 32 to 47 are the major version, bits 16 to 31 the minor version, and bits 0 to 15 the patch version.
+    fn shopify_function_provider_version() -> u64 {
+        PROVIDER_VERSION
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read::shopify_function_input_get;
+    #[cfg(feature = "gzip-input")]
+    use shopify_function_wasm_api_core::read::NanBox;
+
+    #[test]
+    fn test_provider_version() {
+        let version = shopify_function_provider_version();
+        assert_eq!(
+            format!(
+                "{}.{}.{}",
+                version >> 32,
+                (version >> 16) & 0xffff,
+                version & 0xffff
+            ),
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+
+    #[test]
+    fn test_deterministic_seed() {
+        initialize_from_bytes(br#"{"a":[1,2]}"#.to_vec(), InputEncoding::Json);
+        let seed = shopify_function_deterministic_seed();
+        assert_eq!(shopify_function_deterministic_seed(), seed);
+
+        initialize_from_bytes(br#"{"a":[1,2]}"#.to_vec(), InputEncoding::Json);
+        shopify_function_input_get();
+        assert_eq!(shopify_function_deterministic_seed(), seed);
+
+        initialize_from_bytes(br#"{"a":[2,1]}"#.to_vec(), InputEncoding::Json);
+        assert_ne!(shopify_function_deterministic_seed(), seed);
+    }
+
+    #[test]
+    fn test_input_key_validation() {
+        use shopify_function_wasm_api_core::read::{NanBox, ValueRef};
+
+        let input_get = || NanBox::from_bits(shopify_function_input_get()).try_decode();
+        for (validation, expected_error) in [
+            (InputKeyValidation::None, None),
+            (InputKeyValidation::Unique, None),
+            (
+                InputKeyValidation::Sorted,
+                Some(ErrorCode::UnsortedObjectKeys),
+            ),
+        ] {
+            initialize_from_bytes(br#"{"b":1,"a":2}"#.to_vec(), InputEncoding::Json);
+            assert!(set_input_key_validation(validation));
+            match expected_error {
+                Some(error) => assert_eq!(input_get(), Ok(ValueRef::Error(error))),
+                None => assert!(matches!(input_get(), Ok(ValueRef::Object { len: 2, .. }))),
+            }
+        }
+
+        // the validation can't change once the keys were checked
+        initialize_from_bytes(br#"{"a":1}"#.to_vec(), InputEncoding::Json);
+        assert!(set_input_key_validation(InputKeyValidation::Unique));
+        input_get().unwrap();
+        assert!(!set_input_key_validation(InputKeyValidation::Sorted));
+    }
+
+    #[test]
+    fn test_work_budget() {
+        use crate::read::shopify_function_input_get_at_index;
+        use shopify_function_wasm_api_core::read::{NanBox, ValueRef};
+
+        initialize_from_bytes(b"[1,2,3]".to_vec(), InputEncoding::Json);
+        shopify_function_set_work_budget(2);
+        let input = shopify_function_input_get();
+        let get_at_index = |index| {
+            NanBox::from_bits(shopify_function_input_get_at_index(input, index)).try_decode()
+        };
+        assert_eq!(get_at_index(0), Ok(ValueRef::Number(1.0)));
+        assert_eq!(get_at_index(1), Ok(ValueRef::Number(2.0)));
+        assert_eq!(
+            get_at_index(2),
+            Ok(ValueRef::Error(ErrorCode::BudgetExceeded))
+        );
+
+        // the budget is reset with the input
+        initialize_from_bytes(b"[1]".to_vec(), InputEncoding::Json);
+        let input = shopify_function_input_get();
+        assert_eq!(
+            NanBox::from_bits(shopify_function_input_get_at_index(input, 0)).try_decode(),
+            Ok(ValueRef::Number(1.0))
+        );
+    }
+
+    #[test]
+    fn test_arena_allocation_limit() {
+        use crate::read::shopify_function_input_get_at_index;
+        use shopify_function_wasm_api_core::read::{NanBox, ValueRef};
+
+        let input = format!("[{}[1]]", "1,".repeat(99));
+        let arena = ArenaConfig {
+            initial_capacity: 0,
+            allocation_limit: 1024,
+        };
+        assert!(initialize_with_arena(
+            input.clone().into_bytes(),
+            InputEncoding::Json,
+            InputCompression::None,
+            arena
+        ));
+        // the array's 100 elements don't fit in the arena, so it can't be read
+        assert_eq!(
+            NanBox::from_bits(shopify_function_input_get()).try_decode(),
+            Ok(ValueRef::Error(ErrorCode::OutOfMemory))
+        );
+
+        // the first chunk can be larger than the default, and isn't limited by the default either
+        let arena = ArenaConfig {
+            initial_capacity: 64 * 1024,
+            allocation_limit: 0,
+        };
+        assert!(initialize_with_arena(
+            input.into_bytes(),
+            InputEncoding::Json,
+            InputCompression::None,
+            arena
+        ));
+        Context::with(|context| {
+            assert!(context.bump_allocator.chunk_capacity() >= 64 * 1024);
+        });
+        let input = shopify_function_input_get();
+        assert!(matches!(
+            NanBox::from_bits(shopify_function_input_get_at_index(input, 99)).try_decode(),
+            Ok(ValueRef::Array { len: 1, .. })
+        ));
+
+        // the provider is left as it was if the first chunk can't be allocated
+        let arena = ArenaConfig {
+            initial_capacity: isize::MAX as usize,
+            allocation_limit: 0,
+        };
+        assert!(!initialize_with_arena(
+            b"null".to_vec(),
+            InputEncoding::Json,
+            InputCompression::None,
+            arena
+        ));
+        assert!(matches!(
+            NanBox::from_bits(shopify_function_input_get_at_index(input, 99)).try_decode(),
+            Ok(ValueRef::Array { len: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_utf8_str_trim_eq_normalization() {
+        use crate::read::shopify_function_input_utf8_str_trim_eq;
+        use shopify_function_wasm_api_core::read::{NanBox, ValueRef};
+
+        initialize_from_bytes(b"\" caf\\u00e9\\n\"".to_vec(), InputEncoding::Json);
+        let input = shopify_function_input_get();
+        let trim_eq = |other: &str, normalize| {
+            NanBox::from_bits(shopify_function_input_utf8_str_trim_eq(
+                input,
+                other.as_ptr() as _,
+                other.len(),
+                normalize,
+            ))
+            .try_decode()
+        };
+        assert_eq!(trim_eq("caf\u{e9}", 0), Ok(ValueRef::Bool(true)));
+        assert_eq!(trim_eq("cafe\u{301}", 0), Ok(ValueRef::Bool(false)));
+        #[cfg(feature = "nfc-normalization")]
+        assert_eq!(trim_eq("cafe\u{301}", 1), Ok(ValueRef::Bool(true)));
+        #[cfg(not(feature = "nfc-normalization"))]
+        assert_eq!(
+            trim_eq("cafe\u{301}", 1),
+            Ok(ValueRef::Error(ErrorCode::NormalizationUnsupported))
+        );
+    }
+
+    #[test]
+    fn test_pinned_values() {
+        use crate::read::{
+            shopify_function_input_get_at_index, shopify_function_input_get_pinned,
+            shopify_function_input_pin, shopify_function_input_unpin,
+        };
+        use shopify_function_wasm_api_core::read::{NanBox, ValueRef};
+
+        initialize_from_bytes(br#"[{"a":1},2]"#.to_vec(), InputEncoding::Json);
+        let input = shopify_function_input_get();
+        let first = shopify_function_input_get_at_index(input, 0);
+        let pin = shopify_function_input_pin(first);
+        let second_pin = shopify_function_input_pin(shopify_function_input_get_at_index(input, 1));
+        // reading past the pinned value doesn't change it
+        assert_eq!(shopify_function_input_get_pinned(pin), first);
+        assert_eq!(
+            NanBox::from_bits(shopify_function_input_get_pinned(second_pin)).try_decode(),
+            Ok(ValueRef::Number(2.0))
+        );
+
+        shopify_function_input_unpin(pin);
+        let unknown = Ok(ValueRef::Error(ErrorCode::UnknownPinnedValue));
+        assert_eq!(
+            NanBox::from_bits(shopify_function_input_get_pinned(pin)).try_decode(),
+            unknown
+        );
+        // unpinned indices are reused, so pinning in a loop doesn't grow the table, but with new
+        // IDs, so unpinning an old ID doesn't unpin the new pin
+        let index = |pin: usize| pin & ((1 << (usize::BITS / 2)) - 1);
+        let pin_again = shopify_function_input_pin(first);
+        assert_ne!(pin_again, pin);
+        assert_eq!(index(pin_again), index(pin));
+        shopify_function_input_unpin(pin);
+        assert_eq!(shopify_function_input_get_pinned(pin_again), first);
+        shopify_function_input_unpin(pin_again);
+
+        // pins don't outlive the input they were read from, even when their index is pinned again
+        initialize_from_bytes(b"[3]".to_vec(), InputEncoding::Json);
+        let input = shopify_function_input_get();
+        let new_pin = shopify_function_input_pin(input);
+        let new_second_pin = shopify_function_input_pin(input);
+        for stale in [pin, second_pin] {
+            assert_eq!(
+                NanBox::from_bits(shopify_function_input_get_pinned(stale)).try_decode(),
+                unknown
+            );
+            shopify_function_input_unpin(stale);
+        }
+        assert_eq!(shopify_function_input_get_pinned(new_pin), input);
+        assert_eq!(shopify_function_input_get_pinned(new_second_pin), input);
+    }
+
+    #[test]
+    fn test_interned_obj_key_at_index() {
+        use crate::read::{
+            shopify_function_input_get_at_index,
+            shopify_function_input_get_interned_obj_key_at_index,
+        };
+
+        initialize_from_bytes(
+            br#"[{"b":1,"a":2},{"a":3},4]"#.to_vec(),
+            InputEncoding::Json,
+        );
+        let input = shopify_function_input_get();
+        let [first, second, number] =
+            [0, 1, 2].map(|index| shopify_function_input_get_at_index(input, index));
+        let b = shopify_function_input_get_interned_obj_key_at_index(first, 0);
+        let a = shopify_function_input_get_interned_obj_key_at_index(first, 1);
+        // keys with the same bytes are interned once
+        assert_eq!(
+            shopify_function_input_get_interned_obj_key_at_index(second, 0),
+            a
+        );
+        assert_ne!(a, b);
+        Context::with(|context| {
+            assert_eq!(context.string_interner.get(a), b"a");
+            assert_eq!(context.string_interner.get(b), b"b");
+        });
+
+        assert_eq!(
+            shopify_function_input_get_interned_obj_key_at_index(second, 1),
+            usize::MAX
+        );
+        assert_eq!(
+            shopify_function_input_get_interned_obj_key_at_index(number, 0),
+            usize::MAX
+        );
+    }
+
+    #[test]
+    fn test_initialize_from_compressed_bytes() {
+        let supported = initialize_from_compressed_bytes(
+            b"\x1f\x8b".to_vec(),
+            InputEncoding::Msgpack,
+            InputCompression::Gzip,
+        );
+        assert_eq!(
+            supported,
+            input_compression_supported(InputCompression::Gzip)
+        );
+    }
+
+    #[cfg(feature = "gzip-input")]
+    #[test]
+    fn test_gzip_input() {
+        use std::io::Write;
+
+        let json = br#"{"a":[1,2]}"#;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(json).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        initialize_from_bytes(json.to_vec(), InputEncoding::Json);
+        let seed = shopify_function_deterministic_seed();
+
+        assert!(initialize_from_compressed_bytes(
+            compressed,
+            InputEncoding::Json,
+            InputCompression::Gzip
+        ));
+        assert_eq!(shopify_function_deterministic_seed(), seed);
+        let input = NanBox::from_bits(shopify_function_input_get());
+        assert!(matches!(
+            input.try_decode(),
+            Ok(shopify_function_wasm_api_core::read::ValueRef::Object { len: 1, .. })
+        ));
+    }
+
+    #[cfg(feature = "gzip-input")]
+    #[test]
+    fn test_invalid_gzip_input() {
+        assert!(initialize_from_compressed_bytes(
+            b"not gzip".to_vec(),
+            InputEncoding::Msgpack,
+            InputCompression::Gzip
+        ));
+        let input = NanBox::from_bits(shopify_function_input_get());
+        assert!(matches!(
+            input.try_decode(),
+            Ok(shopify_function_wasm_api_core::read::ValueRef::Error(
+                ErrorCode::ReadError
+            ))
+        ));
+    }
+}
@@ -1,8 +1,14 @@
-use std::ptr;
+use std::{cell::Cell, collections::VecDeque, ptr};
 
-use crate::{decorate_for_target, Context};
+use rmp::encode;
 
-static mut LOG_RET_AREA: [usize; 5] = [0; 5];
+use crate::{decorate_for_target, Context, DoubleUsize};
+
+thread_local! {
+    /// Where [`shopify_function_log_new_utf8_str`] writes its results for the guest to read. It has
+    /// no destructor, so it lives as long as the thread, and the pointer to it stays valid.
+    static LOG_RET_AREA: Cell<[usize; 6]> = const { Cell::new([0; 6]) };
+}
 // One more byte so we can check if we're truncating.
 const CAPACITY: usize = 1001;
 
@@ -14,6 +20,8 @@ pub(crate) struct Logs {
     buffer: [u8; CAPACITY],
     offset: usize,
     len: usize,
+    /// The number of bytes dropped from the logs, either pushed out by newer logs or too long to fit.
+    dropped_len: usize,
 }
 
 impl Default for Logs {
@@ -22,12 +30,14 @@ impl Default for Logs {
             buffer: [0; CAPACITY],
             offset: 0,
             len: 0,
+            dropped_len: 0,
         }
     }
 }
 
 impl Logs {
     fn append(&mut self, mut len: usize) -> (usize, *const u8, usize, *const u8, usize) {
+        let (original_len, appended_len) = (self.len, len);
         let mut source_offset = 0;
         let dst_offset1 = unsafe { self.buffer.as_ptr().add(self.offset) };
         let len1;
@@ -54,10 +64,32 @@ impl Logs {
         }
 
         self.offset = (self.offset + len) % CAPACITY;
+        self.dropped_len += original_len + appended_len - self.len;
 
         (source_offset, dst_offset1, len1, dst_offset2, len2)
     }
 
+    /// Append `bytes` to the logs.
+    fn write(&mut self, bytes: &[u8]) {
+        let (source_offset, ptr1, len1, _, len2) = self.append(bytes.len());
+        let bytes = &bytes[source_offset..];
+        let start = ptr1 as usize - self.buffer.as_ptr() as usize;
+        self.buffer[start..start + len1].copy_from_slice(&bytes[..len1]);
+        // the rest wraps around to the start of the buffer
+        self.buffer[..len2].copy_from_slice(&bytes[len1..]);
+    }
+
+    pub(crate) fn dropped_len(&self) -> usize {
+        self.dropped_len
+    }
+
+    /// The logs, oldest first.
+    #[cfg(not(target_family = "wasm"))]
+    fn to_vec(&self) -> Vec<u8> {
+        let read_offset = if self.len < CAPACITY { 0 } else { self.offset };
+        [&self.buffer[read_offset..], &self.buffer[..read_offset]].concat()[..self.len].to_vec()
+    }
+
     #[cfg(target_family = "wasm")]
     pub(crate) fn read_ptrs(&self) -> (*const u8, usize, *const u8, usize) {
         // _After_ filling the buffer, the read offset will _always_ be the
@@ -78,6 +110,83 @@ impl Logs {
     }
 }
 
+/// The number of bytes of messages the log records are kept within, not counting the newest record,
+/// which is always kept.
+const RECORDS_CAPACITY: usize = 1000;
+
+/// A log record with a level, whose message is copied in by the guest.
+#[derive(Debug)]
+struct LogRecord {
+    level: u32,
+    sequence: usize,
+    message: Vec<u8>,
+}
+
+/// The log records, kept apart from the plain-text logs, which drop the oldest records once their
+/// messages are longer than [`RECORDS_CAPACITY`].
+#[derive(Debug, Default)]
+pub(crate) struct LogRecords {
+    records: VecDeque<LogRecord>,
+    /// The combined length of the messages of `records`.
+    len: usize,
+    /// The number of records logged, including those dropped.
+    logged: usize,
+    /// The records encoded by `finalize`.
+    #[cfg(target_family = "wasm")]
+    encoded: Vec<u8>,
+}
+
+impl LogRecords {
+    /// Add a record whose message is `len` bytes, returning the number of records dropped to make
+    /// room for it and the address to copy the message to.
+    fn append(&mut self, level: u32, len: usize) -> (usize, *const u8) {
+        let mut dropped = 0;
+        while self.len + len > RECORDS_CAPACITY {
+            let Some(record) = self.records.pop_front() else {
+                break;
+            };
+            self.len -= record.message.len();
+            dropped += 1;
+        }
+        self.records.push_back(LogRecord {
+            level,
+            sequence: self.logged,
+            message: vec![0; len],
+        });
+        self.len += len;
+        self.logged += 1;
+        // the record was just pushed
+        (dropped, self.records.back().unwrap().message.as_ptr())
+    }
+
+    /// Encode the records as described in the core crate's `log` module.
+    fn encode_into(&self, bytes: &mut Vec<u8>) {
+        // writing to a `Vec` can't fail
+        encode::write_array_len(bytes, self.records.len() as u32).unwrap();
+        for record in &self.records {
+            encode::write_map_len(bytes, 3).unwrap();
+            encode::write_str(bytes, "level").unwrap();
+            encode::write_uint(bytes, record.level as u64).unwrap();
+            encode::write_str(bytes, "sequence").unwrap();
+            encode::write_uint(bytes, record.sequence as u64).unwrap();
+            encode::write_str(bytes, "message").unwrap();
+            // the message is copied as it is, as the provider doesn't check it's UTF-8
+            encode::write_str_len(bytes, record.message.len() as u32).unwrap();
+            bytes.extend_from_slice(&record.message);
+        }
+    }
+
+    /// Encode the records for `finalize`, which points the host to the returned bytes.
+    #[cfg(target_family = "wasm")]
+    pub(crate) fn encode(&mut self) -> &[u8] {
+        let mut encoded = std::mem::take(&mut self.encoded);
+        encoded.clear();
+        self.encode_into(&mut encoded);
+        self.encoded = encoded;
+        &self.encoded
+    }
+}
+
 impl Context {
     fn allocate_log(&mut self, len: usize) -> (usize, *const u8, usize, *const u8, usize) {
         self.logs.append(len)
@@ -85,26 +194,160 @@ impl Context {
 }
 
 decorate_for_target! {
+    /// Returns the address of the source offset, the first destination and its length, the second destination and its length, and the number of bytes dropped from the logs.
     fn shopify_function_log_new_utf8_str(len: usize) -> *const usize {
         Context::with_mut(|context| {
+            let dropped_len = context.logs.dropped_len;
             let (src_offset, ptr1, len1, ptr2, len2) = context.allocate_log(len);
-            #[allow(static_mut_refs)] // This is _technically_ safe given this is single threaded.
-            unsafe {
-                LOG_RET_AREA[0] = src_offset;
-                LOG_RET_AREA[1] = ptr1 as usize;
-                LOG_RET_AREA[2] = len1;
-                LOG_RET_AREA[3] = ptr2 as usize;
-                LOG_RET_AREA[4] = len2;
-                LOG_RET_AREA.as_ptr()
+            LOG_RET_AREA.with(|area| {
+                area.set([
+                    src_offset,
+                    ptr1 as usize,
+                    len1,
+                    ptr2 as usize,
+                    len2,
+                    context.logs.dropped_len - dropped_len,
+                ]);
+                area.as_ptr().cast::<usize>().cast_const()
+            })
+        })
+    }
+}
+
+decorate_for_target! {
+    /// Writes each interned string with its ID to the logs, one per line, and returns the number of interned strings.
+    fn shopify_function_log_interned_strings() -> usize {
+        Context::with_mut(|context| {
+            let Context { string_interner, logs, .. } = context;
+            for (id, bytes) in string_interner.iter() {
+                logs.write(format!("{id}: {:?}\n", String::from_utf8_lossy(bytes)).as_bytes());
             }
+            string_interner.iter().len()
         })
     }
 }
 
+decorate_for_target! {
+    /// The most significant 32 bits are the number of records dropped to make room, the least significant 32 bits are the address to copy the message to.
+    fn shopify_function_log_new_record(level: u32, len: usize) -> DoubleUsize {
+        Context::with_mut(|context| {
+            let (dropped, ptr) = context.log_records.append(level, len);
+            ((dropped as DoubleUsize) << usize::BITS) | ptr as DoubleUsize
+        })
+    }
+}
+
+/// The log records logged so far, encoded as described in the core crate's `log` module.
+#[cfg(not(target_family = "wasm"))]
+pub fn log_records() -> Vec<u8> {
+    Context::with(|context| {
+        let mut bytes = Vec::new();
+        context.log_records.encode_into(&mut bytes);
+        bytes
+    })
+}
+
+/// The logs written so far, oldest first.
+#[cfg(not(target_family = "wasm"))]
+pub fn logs() -> Vec<u8> {
+    Context::with(|context| context.logs.to_vec())
+}
+
+/// The number of bytes dropped from the logs because they exceeded the capacity, which hosts can
+/// report so that truncated logs aren't mistaken for complete ones.
+#[cfg(not(target_family = "wasm"))]
+pub fn log_dropped_len() -> usize {
+    Context::with(|context| context.logs.dropped_len())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn contents(logs: &Logs) -> Vec<u8> {
+        logs.to_vec()
+    }
+
+    #[test]
+    fn test_write() {
+        let mut logs = Logs::default();
+        logs.write(b"hello ");
+        logs.write(b"world");
+        assert_eq!(contents(&logs), b"hello world");
+
+        // wraps around, keeping the newest bytes
+        let long: Vec<u8> = (0..CAPACITY + 10).map(|i| b'a' + (i % 26) as u8).collect();
+        logs.write(&long[..CAPACITY - 5]);
+        logs.write(&long[CAPACITY - 5..]);
+        assert_eq!(contents(&logs), &long[10..]);
+        assert_eq!(logs.dropped_len, 21);
+    }
+
+    #[test]
+    fn test_log_new_utf8_str_dropped_len() {
+        Context::with_mut(|context| *context = Context::new(Vec::new()));
+        let dropped_len = |len| unsafe { *shopify_function_log_new_utf8_str(len).add(5) };
+        assert_eq!(dropped_len(CAPACITY - 1), 0);
+        assert_eq!(dropped_len(3), 2);
+        assert_eq!(dropped_len(CAPACITY + 4), CAPACITY + 4);
+        assert_eq!(log_dropped_len(), CAPACITY + 6);
+    }
+
+    #[test]
+    fn test_log_new_utf8_str_ret_area() {
+        Context::with_mut(|context| *context = Context::new(Vec::new()));
+        let ret_area = |len| unsafe {
+            std::ptr::read(shopify_function_log_new_utf8_str(len).cast::<[usize; 6]>())
+        };
+        let buffer = Context::with(|context| context.logs.buffer.as_ptr() as usize);
+        assert_eq!(ret_area(CAPACITY - 2), [0, buffer, CAPACITY - 2, 0, 0, 0]);
+        assert_eq!(ret_area(4), [0, buffer + CAPACITY - 2, 2, buffer, 2, 2]);
+        // the start of a message too long to fit is dropped
+        let [src_offset, ptr1, len1, ptr2, len2, dropped_len] = ret_area(CAPACITY + 3);
+        assert_eq!(
+            [src_offset, ptr1, len1, ptr2, len2],
+            [3, buffer + 2, CAPACITY - 2, buffer, 2]
+        );
+        assert_eq!(dropped_len, CAPACITY + 3);
+    }
+
+    #[test]
+    fn test_log_interned_strings() {
+        Context::with_mut(|context| *context = Context::new(Vec::new()));
+        for s in ["foo", "bar\n"] {
+            let (_, ptr) =
+                Context::with_mut(|context| context.string_interner.preallocate(s.len()));
+            unsafe { std::ptr::copy_nonoverlapping(s.as_ptr(), ptr as *mut u8, s.len()) };
+        }
+        assert_eq!(shopify_function_log_interned_strings(), 2);
+        Context::with(|context| {
+            assert_eq!(contents(&context.logs), b"0: \"foo\"\n1: \"bar\\n\"\n");
+        });
+    }
+
+    #[test]
+    fn test_log_new_record() {
+        Context::with_mut(|context| *context = Context::new(Vec::new()));
+        let log = |level, message: &str| {
+            let result = shopify_function_log_new_record(level, message.len());
+            let ptr = result as usize as *mut u8;
+            unsafe { std::ptr::copy_nonoverlapping(message.as_ptr(), ptr, message.len()) };
+            (result >> usize::BITS) as usize
+        };
+        assert_eq!(log(0, "checked 2 lines"), 0);
+        assert_eq!(log(1, &"a".repeat(RECORDS_CAPACITY - 15)), 0);
+        // the oldest records are dropped to make room, but the newest is always kept
+        assert_eq!(log(2, &"c".repeat(RECORDS_CAPACITY - 10)), 2);
+        assert_eq!(log(1, &"b".repeat(RECORDS_CAPACITY + 1)), 1);
+        assert_eq!(log(2, ""), 1);
+
+        let records: Vec<serde_json::Value> = rmp_serde::from_slice(&log_records()).unwrap();
+        assert_eq!(
+            records,
+            [serde_json::json!({ "level": 2, "sequence": 4, "message": "" })]
+        );
+    }
+
     #[test]
     fn test_append_fits_in_buffer() {
         let mut logs = Logs::default();
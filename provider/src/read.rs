@@ -1,17 +1,48 @@
-use crate::{decorate_for_target, Context};
+use crate::{decorate_for_target, string_interner::StringInterner, Context, DoubleUsize};
+use bumpalo::{AllocOrInitError, Bump};
 use shopify_function_wasm_api_core::{
     read::{ErrorCode, NanBox, Val, ValueRef as NanBoxValueRef},
     InternedStringId,
 };
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "nfc-normalization")]
+use unicode_normalization::UnicodeNormalization;
 
+pub(crate) mod compression;
+pub(crate) mod json;
+pub(crate) mod keys;
 mod lazy_value_ref;
 
-pub(crate) use lazy_value_ref::LazyValueRef;
+pub(crate) use lazy_value_ref::{LazyValueRef, PropertyHint};
+
+/// Allocate the value `f` returns in the arena, failing with `ErrorCode::OutOfMemory` rather than
+/// aborting if it can't be allocated, e.g. because the host limited the arena's size.
+fn try_alloc_in<T>(
+    bump: &Bump,
+    f: impl FnOnce() -> Result<T, ErrorCode>,
+) -> Result<&mut T, ErrorCode> {
+    bump.try_alloc_try_with(f).map_err(|error| match error {
+        AllocOrInitError::Alloc(_) => ErrorCode::OutOfMemory,
+        AllocOrInitError::Init(error) => error,
+    })
+}
+
+/// Spend one unit of the work budget the guest set with `shopify_function_set_work_budget`, for a
+/// read that looks up or traverses values in the input.
+fn spend_work_budget(work_budget: &mut usize) -> Result<(), ErrorCode> {
+    *work_budget = work_budget
+        .checked_sub(1)
+        .ok_or(ErrorCode::BudgetExceeded)?;
+    Ok(())
+}
 
 decorate_for_target! {
     fn shopify_function_input_get() -> Val {
         Context::with_mut(|context| {
-            match context.bump_allocator.alloc_try_with(|| {
+            if let Err(e) = context.decode_input() {
+                return NanBox::error(e).to_bits();
+            }
+            match try_alloc_in(&context.bump_allocator, || {
                 LazyValueRef::new(&context.input_bytes, 0, &context.bump_allocator)
                     .map(|(value, _)| value)
             }) {
@@ -28,7 +59,10 @@ decorate_for_target! {
         ptr: usize,
         len: usize,
     ) -> Val {
-        Context::with(|context| {
+        Context::with_mut(|context| {
+            if let Err(e) = spend_work_budget(&mut context.work_budget) {
+                return NanBox::error(e).to_bits();
+            }
             let v = NanBox::from_bits(scope);
             match v.try_decode() {
                 Ok(NanBoxValueRef::Object { ptr: obj_ptr, .. }) => {
@@ -39,11 +73,12 @@ decorate_for_target! {
                     };
                     match value.get_object_property(
                         query,
+                        context.input_keys_sorted,
                         &context.input_bytes,
                         &context.bump_allocator,
                     ) {
                         Ok(Some(value)) => value.encode().to_bits(),
-                        Ok(None) => NanBox::null().to_bits(),
+                        Ok(None) => NanBox::missing().to_bits(),
                         Err(e) => NanBox::error(e).to_bits(),
                     }
                 }
@@ -59,22 +94,34 @@ decorate_for_target! {
         scope: Val,
         interned_string_id: InternedStringId,
     ) -> Val {
-        Context::with(|context| {
+        Context::with_mut(|context| {
+            let Context { input_bytes, bump_allocator, string_interner, property_hints, input_keys_sorted, work_budget, .. } = context;
+            if let Err(e) = spend_work_budget(work_budget) {
+                return NanBox::error(e).to_bits();
+            }
             let v = NanBox::from_bits(scope);
             match v.try_decode() {
                 Ok(NanBoxValueRef::Object { ptr: obj_ptr, .. }) => {
-                    let query = context.string_interner.get(interned_string_id);
+                    if !string_interner.contains(interned_string_id) {
+                        return NanBox::error(ErrorCode::UnknownInternedString).to_bits();
+                    }
+                    let query = string_interner.get(interned_string_id);
                     let value = match LazyValueRef::mut_from_raw(obj_ptr as _) {
                         Ok(value) => value,
                         Err(e) => return NanBox::error(e).to_bits(),
                     };
-                    match value.get_object_property(
+                    if interned_string_id >= property_hints.len() {
+                        property_hints.resize(interned_string_id + 1, PropertyHint::default());
+                    }
+                    match value.get_object_property_with_hint(
                         query,
-                        &context.input_bytes,
-                        &context.bump_allocator,
+                        *input_keys_sorted,
+                        &mut property_hints[interned_string_id],
+                        input_bytes,
+                        bump_allocator,
                     ) {
                         Ok(Some(value)) => value.encode().to_bits(),
-                        Ok(None) => NanBox::null().to_bits(),
+                        Ok(None) => NanBox::missing().to_bits(),
                         Err(e) => NanBox::error(e).to_bits(),
                     }
                 }
@@ -90,7 +137,10 @@ decorate_for_target! {
         scope: Val,
         index: usize,
     ) -> Val {
-        Context::with(|context| {
+        Context::with_mut(|context| {
+            if let Err(e) = spend_work_budget(&mut context.work_budget) {
+                return NanBox::error(e).to_bits();
+            }
             let v = NanBox::from_bits(scope);
             match v.try_decode() {
                 Ok(NanBoxValueRef::Array { ptr, len: _ } | NanBoxValueRef::Object { ptr, len: _ }) => {
@@ -114,12 +164,42 @@ decorate_for_target! {
     }
 }
 
+decorate_for_target! {
+    fn shopify_function_input_get_array_slice(
+        scope: Val,
+        start: usize,
+        end: usize,
+    ) -> Val {
+        Context::with_mut(|context| {
+            if let Err(e) = spend_work_budget(&mut context.work_budget) {
+                return NanBox::error(e).to_bits();
+            }
+            let v = NanBox::from_bits(scope);
+            match v.try_decode() {
+                Ok(NanBoxValueRef::Array { ptr, .. }) => {
+                    match try_alloc_in(&context.bump_allocator, || {
+                        LazyValueRef::slice(ptr as _, start, end)
+                    }) {
+                        Ok(slice) => slice.encode().to_bits(),
+                        Err(e) => NanBox::error(e).to_bits(),
+                    }
+                }
+                Ok(_) => NanBox::error(ErrorCode::NotAnArray).to_bits(),
+                Err(_) => NanBox::error(ErrorCode::DecodeError).to_bits(),
+            }
+        })
+    }
+}
+
 decorate_for_target! {
     fn shopify_function_input_get_obj_key_at_index(
         scope: Val,
         index: usize,
     ) -> Val {
-        Context::with(|context| {
+        Context::with_mut(|context| {
+            if let Err(e) = spend_work_budget(&mut context.work_budget) {
+                return NanBox::error(e).to_bits();
+            }
             let v = NanBox::from_bits(scope);
             match v.try_decode() {
                 Ok(NanBoxValueRef::Object { ptr, .. }) => {
@@ -143,15 +223,396 @@ decorate_for_target! {
     }
 }
 
+decorate_for_target! {
+    /// Returns the interned string ID of the object's key at the index, or `usize::MAX` if it can't be read.
+    fn shopify_function_input_get_interned_obj_key_at_index(
+        scope: Val,
+        index: usize,
+    ) -> InternedStringId {
+        Context::with_mut(|context| {
+            if spend_work_budget(&mut context.work_budget).is_err() {
+                return usize::MAX;
+            }
+            let Ok(NanBoxValueRef::Object { ptr, .. }) = NanBox::from_bits(scope).try_decode() else {
+                return usize::MAX;
+            };
+            let Ok(value) = LazyValueRef::mut_from_raw(ptr as _) else {
+                return usize::MAX;
+            };
+            match value.get_key_at_index(index, &context.input_bytes, &context.bump_allocator) {
+                Ok(key) => context
+                    .string_interner
+                    .intern(key.key_bytes(&context.input_bytes)),
+                Err(_) => usize::MAX,
+            }
+        })
+    }
+}
+
+decorate_for_target! {
+    /// Returns the address of the NaN-boxed key and value of the object's entry at the index, one after the other.
+    fn shopify_function_input_get_obj_entry_at_index_addr(
+        scope: Val,
+        index: usize,
+    ) -> usize {
+        Context::with_mut(|context| {
+            let Context { input_bytes, bump_allocator, value_batch, work_budget, .. } = context;
+            value_batch.clear();
+            if let Err(e) = spend_work_budget(work_budget) {
+                value_batch.extend([NanBox::error(e).to_bits(); 2]);
+                return value_batch.as_ptr() as usize;
+            }
+            let (key, value) = match NanBox::from_bits(scope).try_decode() {
+                Ok(NanBoxValueRef::Object { ptr, .. }) => match LazyValueRef::mut_from_raw(ptr as _) {
+                    Ok(object) => {
+                        let key = match object.get_key_at_index(index, input_bytes, bump_allocator) {
+                            Ok(key) => key.encode(),
+                            Err(e) => NanBox::error(e),
+                        };
+                        let value = match object.get_at_index(index, input_bytes, bump_allocator) {
+                            Ok(value) => value.encode(),
+                            Err(e) => NanBox::error(e),
+                        };
+                        (key, value)
+                    }
+                    Err(e) => (NanBox::error(e), NanBox::error(e)),
+                },
+                Ok(_) => (NanBox::error(ErrorCode::NotAnObject), NanBox::error(ErrorCode::NotAnObject)),
+                Err(_) => (NanBox::error(ErrorCode::ReadError), NanBox::error(ErrorCode::ReadError)),
+            };
+            value_batch.extend([key.to_bits(), value.to_bits()]);
+            value_batch.as_ptr() as usize
+        })
+    }
+}
+
+decorate_for_target! {
+    fn shopify_function_input_get_array_elements_addr_and_len(
+        scope: Val,
+        start: usize,
+        max: usize,
+    ) -> DoubleUsize {
+        Context::with_mut(|context| {
+            let Context { input_bytes, bump_allocator, value_batch, work_budget, .. } = context;
+            value_batch.clear();
+            let len = match NanBox::from_bits(scope).try_decode() {
+                Ok(NanBoxValueRef::Array { ptr, .. }) => match LazyValueRef::mut_from_raw(ptr as _) {
+                    Ok(value) => {
+                        let end = value
+                            .get_value_length()
+                            .unwrap_or(0)
+                            .min(start.saturating_add(max));
+                        for index in start..end {
+                            let element = match spend_work_budget(work_budget)
+                                .and_then(|()| value.get_at_index(index, input_bytes, bump_allocator))
+                            {
+                                Ok(element) => element.encode(),
+                                Err(e) => NanBox::error(e),
+                            };
+                            value_batch.push(element.to_bits());
+                        }
+                        value_batch.len()
+                    }
+                    Err(_) => usize::MAX,
+                },
+                _ => usize::MAX,
+            };
+            // most significant half is the number of elements, least significant half is the address
+            ((len as DoubleUsize) << usize::BITS) | (value_batch.as_ptr() as usize as DoubleUsize)
+        })
+    }
+}
+
+/// A cursor over the elements of an array or the entries of an object, which are read in order.
+pub(crate) struct InputIter {
+    /// The address of the array or object.
+    ptr: usize,
+    /// The index of the next element or entry.
+    next: usize,
+}
+
+decorate_for_target! {
+    /// Returns the ID of a new iterator over the elements of the array or the entries of the object, or -1 if the value is neither.
+    fn shopify_function_input_iter_new(scope: Val) -> usize {
+        Context::with_mut(|context| {
+            let ptr = match NanBox::from_bits(scope).try_decode() {
+                Ok(NanBoxValueRef::Array { ptr, .. } | NanBoxValueRef::Object { ptr, .. }) => ptr,
+                _ => return usize::MAX,
+            };
+            let iter = InputIter { ptr, next: 0 };
+            // the IDs of dropped iterators are reused, so iterating in a loop doesn't grow the list
+            match context.input_iters.iter().position(Option::is_none) {
+                Some(id) => {
+                    context.input_iters[id] = Some(iter);
+                    id
+                }
+                None => {
+                    context.input_iters.push(Some(iter));
+                    context.input_iters.len() - 1
+                }
+            }
+        })
+    }
+}
+
+decorate_for_target! {
+    /// Returns the next element of the array or value of the object, or an `IndexOutOfBounds` error once there are none.
+    fn shopify_function_input_iter_next(iter: usize) -> Val {
+        Context::with_mut(|context| {
+            let Context { input_bytes, bump_allocator, input_iters, work_budget, .. } = context;
+            if let Err(e) = spend_work_budget(work_budget) {
+                return NanBox::error(e).to_bits();
+            }
+            let Some(Some(iter)) = input_iters.get_mut(iter) else {
+                return NanBox::error(ErrorCode::UnknownIterator).to_bits();
+            };
+            let value = match LazyValueRef::mut_from_raw(iter.ptr as _) {
+                Ok(value) => value,
+                Err(e) => return NanBox::error(e).to_bits(),
+            };
+            // the elements before the cursor are processed, so reading the next one doesn't read
+            // past any others
+            let result = match value.get_at_index(iter.next, input_bytes, bump_allocator) {
+                Ok(value) => value.encode(),
+                Err(e) => NanBox::error(e),
+            };
+            iter.next = iter.next.saturating_add(1);
+            result.to_bits()
+        })
+    }
+}
+
+decorate_for_target! {
+    /// Returns the key of the object's entry last returned by `shopify_function_input_iter_next`.
+    fn shopify_function_input_iter_key(iter: usize) -> Val {
+        Context::with_mut(|context| {
+            let Context { input_bytes, bump_allocator, input_iters, work_budget, .. } = context;
+            if let Err(e) = spend_work_budget(work_budget) {
+                return NanBox::error(e).to_bits();
+            }
+            let Some(Some(iter)) = input_iters.get_mut(iter) else {
+                return NanBox::error(ErrorCode::UnknownIterator).to_bits();
+            };
+            let Some(index) = iter.next.checked_sub(1) else {
+                return NanBox::error(ErrorCode::IndexOutOfBounds).to_bits();
+            };
+            let value = match LazyValueRef::mut_from_raw(iter.ptr as _) {
+                Ok(value) => value,
+                Err(e) => return NanBox::error(e).to_bits(),
+            };
+            match value.get_key_at_index(index, input_bytes, bump_allocator) {
+                Ok(key) => key.encode().to_bits(),
+                Err(e) => NanBox::error(e).to_bits(),
+            }
+        })
+    }
+}
+
+decorate_for_target! {
+    /// Drops the iterator, so its ID can be reused.
+    fn shopify_function_input_iter_drop(iter: usize) -> () {
+        Context::with_mut(|context| {
+            if let Some(slot) = context.input_iters.get_mut(iter) {
+                *slot = None;
+            }
+        })
+    }
+}
+
+/// The number of low bits of a pin ID that index `Context::pinned_values`. The high bits are the
+/// pin's generation, so the ID of an unpinned value, or of a value pinned from an earlier input,
+/// doesn't refer to a later pin that reuses its index.
+const PIN_INDEX_BITS: u32 = usize::BITS / 2;
+const PIN_INDEX_MASK: usize = (1 << PIN_INDEX_BITS) - 1;
+
+/// The generation of the next pin, shared by all contexts so pin IDs aren't repeated when the input
+/// is replaced. Generations wrap around after `2^(usize::BITS - PIN_INDEX_BITS)` pins.
+static NEXT_PIN_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// A value in the input the guest pinned.
+pub(crate) struct Pin {
+    /// The generation in the high bits of the pin's ID.
+    generation: usize,
+    value: Val,
+}
+
+/// The index and generation of a pin ID.
+fn split_pin_id(pin: usize) -> (usize, usize) {
+    (pin & PIN_INDEX_MASK, pin >> PIN_INDEX_BITS)
+}
+
+/// Returns the pin with the ID, unless it was unpinned or pinned from another input.
+fn find_pin(pinned_values: &mut [Option<Pin>], pin: usize) -> Option<&mut Option<Pin>> {
+    let (index, generation) = split_pin_id(pin);
+    pinned_values
+        .get_mut(index)
+        .filter(|slot| slot.as_ref().is_some_and(|p| p.generation == generation))
+}
+
+decorate_for_target! {
+    /// Returns the ID of a new pin of the value, which `shopify_function_input_get_pinned` returns until it's unpinned,
+    /// or -1 if too many values are pinned.
+    fn shopify_function_input_pin(scope: Val) -> usize {
+        Context::with_mut(|context| {
+            // values are never moved or freed while the context lives, so the value is kept as it
+            // is, and the indices of unpinned values are reused so pinning in a loop doesn't grow
+            // the list
+            let index = match context.pinned_values.iter().position(Option::is_none) {
+                Some(index) => index,
+                // the last index is never used, so -1 is never a pin ID
+                None if context.pinned_values.len() < PIN_INDEX_MASK => {
+                    context.pinned_values.push(None);
+                    context.pinned_values.len() - 1
+                }
+                None => return usize::MAX,
+            };
+            let generation = NEXT_PIN_GENERATION.fetch_add(1, Ordering::Relaxed)
+                & (usize::MAX >> PIN_INDEX_BITS);
+            context.pinned_values[index] = Some(Pin {
+                generation,
+                value: scope,
+            });
+            (generation << PIN_INDEX_BITS) | index
+        })
+    }
+}
+
+decorate_for_target! {
+    /// Returns the pinned value, or an `UnknownPinnedValue` error if it was unpinned or pinned from another input.
+    fn shopify_function_input_get_pinned(pin: usize) -> Val {
+        Context::with_mut(|context| match find_pin(&mut context.pinned_values, pin) {
+            Some(Some(pin)) => pin.value,
+            _ => NanBox::error(ErrorCode::UnknownPinnedValue).to_bits(),
+        })
+    }
+}
+
+decorate_for_target! {
+    /// Unpins the value, so its ID is no longer valid. IDs that were already unpinned or pinned from another input are
+    /// ignored.
+    fn shopify_function_input_unpin(pin: usize) -> () {
+        Context::with_mut(|context| {
+            if let Some(slot) = find_pin(&mut context.pinned_values, pin) {
+                *slot = None;
+            }
+        })
+    }
+}
+
+/// The keys of objects with a known shape, whose properties are read together.
+pub(crate) struct Shape {
+    keys: Vec<InternedStringId>,
+    /// Where each key was last found, so objects of the same shape are read without a key scan.
+    hints: Vec<PropertyHint>,
+}
+
+decorate_for_target! {
+    /// Returns the new shape's ID in the most significant half, and the address to copy its interned string IDs to in the least significant half.
+    fn shopify_function_input_new_shape(len: usize) -> DoubleUsize {
+        Context::with_mut(|context| {
+            let id = context.shapes.len();
+            context.shapes.push(Shape {
+                keys: vec![0; len],
+                hints: vec![PropertyHint::default(); len],
+            });
+            let addr = context.shapes[id].keys.as_ptr() as usize;
+            ((id as DoubleUsize) << usize::BITS) | (addr as DoubleUsize)
+        })
+    }
+}
+
+fn read_shape_property<'a>(
+    object: &mut LazyValueRef<'a>,
+    key: InternedStringId,
+    hint: &mut PropertyHint,
+    keys_sorted: bool,
+    input_bytes: &[u8],
+    bump_allocator: &'a Bump,
+    string_interner: &StringInterner,
+) -> NanBox {
+    if !string_interner.contains(key) {
+        return NanBox::error(ErrorCode::UnknownInternedString);
+    }
+    match object.get_object_property_with_hint(
+        string_interner.get(key),
+        keys_sorted,
+        hint,
+        input_bytes,
+        bump_allocator,
+    ) {
+        Ok(Some(value)) => value.encode(),
+        Ok(None) => NanBox::missing(),
+        Err(e) => NanBox::error(e),
+    }
+}
+
+decorate_for_target! {
+    fn shopify_function_input_get_shape_values_addr_and_len(
+        scope: Val,
+        shape: usize,
+        max: usize,
+    ) -> DoubleUsize {
+        Context::with_mut(|context| {
+            let Context { input_bytes, bump_allocator, string_interner, shapes, value_batch, input_keys_sorted, work_budget, .. } = context;
+            value_batch.clear();
+            let len = match shapes.get_mut(shape) {
+                Some(Shape { keys, hints }) => {
+                    let mut object = match NanBox::from_bits(scope).try_decode() {
+                        Ok(NanBoxValueRef::Object { ptr, .. }) => LazyValueRef::mut_from_raw(ptr as _),
+                        Ok(_) => Err(ErrorCode::NotAnObject),
+                        Err(_) => Err(ErrorCode::DecodeError),
+                    };
+                    for (key, hint) in keys.iter().zip(hints.iter_mut()).take(max) {
+                        let value = match spend_work_budget(work_budget).and_then(|()| object.as_mut().map_err(|e| *e)) {
+                            Ok(object) => read_shape_property(object, *key, hint, *input_keys_sorted, input_bytes, bump_allocator, string_interner),
+                            Err(e) => NanBox::error(e),
+                        };
+                        value_batch.push(value.to_bits());
+                    }
+                    value_batch.len()
+                }
+                None => usize::MAX,
+            };
+            // most significant half is the number of values, least significant half is the address
+            ((len as DoubleUsize) << usize::BITS) | (value_batch.as_ptr() as usize as DoubleUsize)
+        })
+    }
+}
+
+decorate_for_target! {
+    fn shopify_function_input_materialize(scope: Val) -> Val {
+        Context::with_mut(|context| {
+            if let Err(e) = spend_work_budget(&mut context.work_budget) {
+                return NanBox::error(e).to_bits();
+            }
+            let v = NanBox::from_bits(scope);
+            match v.try_decode() {
+                Ok(NanBoxValueRef::Array { ptr, .. } | NanBoxValueRef::Object { ptr, .. }) => {
+                    let value = match LazyValueRef::mut_from_raw(ptr as _) {
+                        Ok(value) => value,
+                        Err(e) => return NanBox::error(e).to_bits(),
+                    };
+                    match value.finish_processing(&context.input_bytes, &context.bump_allocator) {
+                        Ok(_) => scope,
+                        Err(e) => NanBox::error(e).to_bits(),
+                    }
+                }
+                Ok(_) => scope,
+                Err(_) => NanBox::error(ErrorCode::DecodeError).to_bits(),
+            }
+        })
+    }
+}
+
 decorate_for_target! {
     fn shopify_function_input_get_val_len(scope: Val) -> usize {
         let v = NanBox::from_bits(scope);
         match v.try_decode() {
-            Ok(NanBoxValueRef::String { ptr, .. } | NanBoxValueRef::Array { ptr, .. } | NanBoxValueRef::Object { ptr, .. }) => {
+            Ok(NanBoxValueRef::String { ptr, .. } | NanBoxValueRef::Bytes { ptr, .. } | NanBoxValueRef::Array { ptr, .. } | NanBoxValueRef::Object { ptr, .. }) => {
                 let Ok(value) = LazyValueRef::mut_from_raw(ptr as _) else {
                     return usize::MAX;
                 };
-                value.get_value_length()
+                value.get_value_length().unwrap_or(usize::MAX)
             }
             _ => usize::MAX,
         }
@@ -162,7 +623,10 @@ decorate_for_target! {
     fn shopify_function_input_get_utf8_str_addr(
         ptr: usize,
     ) -> usize {
-        Context::with(|context| {
+        Context::with_mut(|context| {
+            // the guest copies the string from the address without checking it, so the read is
+            // charged but still served, and the reads after it fail once the budget is spent
+            let _ = spend_work_budget(&mut context.work_budget);
             let Ok(value) = LazyValueRef::mut_from_raw(ptr as _) else {
                 return 0;
             };
@@ -170,3 +634,213 @@ decorate_for_target! {
         })
     }
 }
+
+decorate_for_target! {
+    fn shopify_function_input_get_utf8_str_addr_and_len(scope: Val) -> DoubleUsize {
+        Context::with_mut(|context| {
+            if spend_work_budget(&mut context.work_budget).is_err() {
+                return (usize::MAX as DoubleUsize) << usize::BITS;
+            }
+            let (addr, len) = match NanBox::from_bits(scope).try_decode() {
+                Ok(NanBoxValueRef::String { ptr, .. } | NanBoxValueRef::Bytes { ptr, .. }) => match LazyValueRef::mut_from_raw(ptr as _) {
+                    Ok(value) => (
+                        value.get_utf8_str_addr(&context.input_bytes),
+                        value.get_value_length().unwrap_or(usize::MAX),
+                    ),
+                    Err(_) => (0, usize::MAX),
+                },
+                _ => (0, usize::MAX),
+            };
+            // most significant half is the length, least significant half is the address
+            ((len as DoubleUsize) << usize::BITS) | (addr as DoubleUsize)
+        })
+    }
+}
+
+/// Whether `a` and `b` are equal once leading and trailing whitespace is trimmed from both,
+/// comparing their NFC normalizations if `normalize` is set, so strings with composed and
+/// decomposed accents are equal. Normalizing fails with `ErrorCode::NormalizationUnsupported`
+/// unless the provider is built with the `nfc-normalization` feature.
+fn utf8_str_trim_eq(a: &str, b: &str, normalize: bool) -> Result<bool, ErrorCode> {
+    let (a, b) = (a.trim(), b.trim());
+    if !normalize {
+        return Ok(a == b);
+    }
+    #[cfg(feature = "nfc-normalization")]
+    {
+        // strings have equal NFC normalizations exactly when their NFD normalizations are equal,
+        // and decomposing doesn't need the composition tables
+        Ok(a.nfd().eq(b.nfd()))
+    }
+    #[cfg(not(feature = "nfc-normalization"))]
+    Err(ErrorCode::NormalizationUnsupported)
+}
+
+decorate_for_target! {
+    /// Returns whether the string value equals the `len` bytes at `ptr` once both are trimmed,
+    /// comparing their NFC normalizations if `normalize` is 1. This is false when the value isn't
+    /// a string or the bytes aren't UTF-8, and an error if the provider can't normalize strings.
+    fn shopify_function_input_utf8_str_trim_eq(
+        scope: Val,
+        ptr: usize,
+        len: usize,
+        normalize: u32,
+    ) -> Val {
+        Context::with_mut(|context| {
+            if let Err(e) = spend_work_budget(&mut context.work_budget) {
+                return NanBox::error(e).to_bits();
+            }
+            let Ok(NanBoxValueRef::String { ptr: str_ptr, .. }) = NanBox::from_bits(scope).try_decode() else {
+                return NanBox::bool(false).to_bits();
+            };
+            let Ok(value) = LazyValueRef::mut_from_raw(str_ptr as _) else {
+                return NanBox::bool(false).to_bits();
+            };
+            let Some(value_len) = value.get_value_length() else {
+                return NanBox::bool(false).to_bits();
+            };
+            let addr = value.get_utf8_str_addr(&context.input_bytes);
+            let value = unsafe { std::slice::from_raw_parts(addr as *const u8, value_len) };
+            let other = unsafe { std::slice::from_raw_parts(ptr as *const u8, len) };
+            let eq = match (std::str::from_utf8(value), std::str::from_utf8(other)) {
+                (Ok(value), Ok(other)) => utf8_str_trim_eq(value, other, normalize == 1),
+                _ => Ok(false),
+            };
+            match eq {
+                Ok(eq) => NanBox::bool(eq).to_bits(),
+                Err(e) => NanBox::error(e).to_bits(),
+            }
+        })
+    }
+}
+
+/// Encode an input value as MessagePack, as [`LazyValueRef::encode_msgpack`] does.
+pub(crate) fn encode_msgpack<'a>(
+    scope: Val,
+    input_bytes: &'a [u8],
+    bump_allocator: &Bump,
+    msgpack_buffer: &'a mut Vec<u8>,
+) -> Result<&'a [u8], ErrorCode> {
+    let nan_box = NanBox::from_bits(scope);
+    let mut scalar;
+    let value = match nan_box.try_decode() {
+        // a missing property has no encoding
+        _ if nan_box.is_missing() => return Err(ErrorCode::ReadError),
+        Ok(
+            NanBoxValueRef::String { ptr, .. }
+            | NanBoxValueRef::Bytes { ptr, .. }
+            | NanBoxValueRef::Array { ptr, .. }
+            | NanBoxValueRef::Object { ptr, .. },
+        ) => LazyValueRef::mut_from_raw(ptr as _)?,
+        Ok(NanBoxValueRef::Null) => {
+            scalar = LazyValueRef::Null;
+            &mut scalar
+        }
+        Ok(NanBoxValueRef::Bool(b)) => {
+            scalar = LazyValueRef::Bool(b);
+            &mut scalar
+        }
+        Ok(NanBoxValueRef::Number(n)) => {
+            scalar = LazyValueRef::Number(n);
+            &mut scalar
+        }
+        Ok(NanBoxValueRef::Error(e)) => return Err(e),
+        Err(_) => return Err(ErrorCode::DecodeError),
+    };
+    value.encode_msgpack(input_bytes, bump_allocator, msgpack_buffer)
+}
+
+decorate_for_target! {
+    /// Returns the length of the value encoded as MessagePack in the most significant half, and the address of the encoding in the least significant half.
+    fn shopify_function_input_get_msgpack_addr_and_len(scope: Val) -> DoubleUsize {
+        Context::with_mut(|context| {
+            let Context { input_bytes, bump_allocator, msgpack_buffer, work_budget, .. } = context;
+            if spend_work_budget(work_budget).is_err() {
+                return (usize::MAX as DoubleUsize) << usize::BITS;
+            }
+            let (addr, len) = match encode_msgpack(scope, input_bytes, bump_allocator, msgpack_buffer) {
+                Ok(encoded) => (encoded.as_ptr() as usize, encoded.len()),
+                Err(_) => (0, usize::MAX),
+            };
+            ((len as DoubleUsize) << usize::BITS) | (addr as DoubleUsize)
+        })
+    }
+}
+
+/// Pack an array of booleans into bits, eight to a byte with the first element in the least
+/// significant bit of the first byte. Fails if the value is not an array or has elements other than
+/// booleans.
+pub(crate) fn pack_bool_bits<'a>(
+    scope: Val,
+    input_bytes: &[u8],
+    bump_allocator: &Bump,
+    buffer: &'a mut Vec<u8>,
+) -> Result<&'a [u8], ErrorCode> {
+    let NanBoxValueRef::Array { ptr, .. } = NanBox::from_bits(scope)
+        .try_decode()
+        .map_err(|_| ErrorCode::DecodeError)?
+    else {
+        return Err(ErrorCode::NotAnArray);
+    };
+    let value = LazyValueRef::mut_from_raw(ptr as _)?;
+    let len = value.get_value_length().ok_or(ErrorCode::NotAnArray)?;
+    buffer.clear();
+    buffer.resize(len.div_ceil(8), 0);
+    for index in 0..len {
+        match value.get_at_index(index, input_bytes, bump_allocator)? {
+            LazyValueRef::Bool(true) => buffer[index / 8] |= 1 << (index % 8),
+            LazyValueRef::Bool(false) => {}
+            _ => return Err(ErrorCode::ReadError),
+        }
+    }
+    Ok(buffer)
+}
+
+decorate_for_target! {
+    /// Returns the number of bytes of the array's booleans packed into bits in the most significant half, and their address in the least significant half.
+    fn shopify_function_input_get_bool_bits_addr_and_len(scope: Val) -> DoubleUsize {
+        Context::with_mut(|context| {
+            let Context { input_bytes, bump_allocator, msgpack_buffer, work_budget, .. } = context;
+            if spend_work_budget(work_budget).is_err() {
+                return (usize::MAX as DoubleUsize) << usize::BITS;
+            }
+            let (addr, len) = match pack_bool_bits(scope, input_bytes, bump_allocator, msgpack_buffer) {
+                Ok(bits) => (bits.as_ptr() as usize, bits.len()),
+                Err(_) => (0, usize::MAX),
+            };
+            ((len as DoubleUsize) << usize::BITS) | (addr as DoubleUsize)
+        })
+    }
+}
+
+decorate_for_target! {
+    /// Returns the number of bytes of the string from the offset, up to the max, in the most significant half, and their address in the least significant half.
+    fn shopify_function_input_get_utf8_str_chunk_addr_and_len(
+        scope: Val,
+        offset: usize,
+        max: usize,
+    ) -> DoubleUsize {
+        Context::with_mut(|context| {
+            if spend_work_budget(&mut context.work_budget).is_err() {
+                return (usize::MAX as DoubleUsize) << usize::BITS;
+            }
+            let (addr, len) = match NanBox::from_bits(scope).try_decode() {
+                Ok(NanBoxValueRef::String { ptr, .. }) => match LazyValueRef::mut_from_raw(ptr as _) {
+                    Ok(value) => match value.get_value_length() {
+                        Some(len) => {
+                            let offset = offset.min(len);
+                            (
+                                value.get_utf8_str_addr(&context.input_bytes) + offset,
+                                (len - offset).min(max),
+                            )
+                        }
+                        None => (0, usize::MAX),
+                    },
+                    Err(_) => (0, usize::MAX),
+                },
+                _ => (0, usize::MAX),
+            };
+            ((len as DoubleUsize) << usize::BITS) | (addr as DoubleUsize)
+        })
+    }
+}
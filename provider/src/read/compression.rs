@@ -0,0 +1,101 @@
+//! Decompresses input passed to `initialize_with_compression` when it is first read.
+
+#[cfg(any(feature = "gzip-input", feature = "zstd-input"))]
+use std::io::Read;
+
+use shopify_function_wasm_api_core::{host::InputCompression, read::ErrorCode};
+
+/// Whether this provider was built with support for `compression`.
+pub(crate) fn is_supported(compression: InputCompression) -> bool {
+    match compression {
+        InputCompression::None => true,
+        InputCompression::Gzip => cfg!(feature = "gzip-input"),
+        InputCompression::Zstd => cfg!(feature = "zstd-input"),
+    }
+}
+
+/// Decompress `input`, returning [`ErrorCode::ReadError`] if it is not valid for `compression`.
+pub(crate) fn decompress(
+    compression: InputCompression,
+    input: &[u8],
+) -> Result<Vec<u8>, ErrorCode> {
+    match compression {
+        InputCompression::None => Ok(input.to_vec()),
+        #[cfg(feature = "gzip-input")]
+        InputCompression::Gzip => read_all(flate2::read::GzDecoder::new(input), input.len()),
+        #[cfg(feature = "zstd-input")]
+        InputCompression::Zstd => {
+            let compressed_len = input.len();
+            let mut input = input;
+            let decoder = ruzstd::decoding::StreamingDecoder::new(&mut input)
+                .map_err(|_| ErrorCode::ReadError)?;
+            read_all(decoder, compressed_len)
+        }
+        // `initialize_with_compression` doesn't accept unsupported compressions
+        #[allow(unreachable_patterns)]
+        _ => Err(ErrorCode::ReadError),
+    }
+}
+
+#[cfg(any(feature = "gzip-input", feature = "zstd-input"))]
+fn read_all(mut decoder: impl Read, compressed_len: usize) -> Result<Vec<u8>, ErrorCode> {
+    // msgpack input usually compresses to less than a quarter of its size
+    let mut output = Vec::with_capacity(compressed_len.saturating_mul(4));
+    decoder
+        .read_to_end(&mut output)
+        .map_err(|_| ErrorCode::ReadError)?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_supported() {
+        assert!(is_supported(InputCompression::None));
+        assert_eq!(
+            is_supported(InputCompression::Gzip),
+            cfg!(feature = "gzip-input")
+        );
+        assert_eq!(
+            is_supported(InputCompression::Zstd),
+            cfg!(feature = "zstd-input")
+        );
+    }
+
+    #[cfg(feature = "gzip-input")]
+    #[test]
+    fn test_decompress_gzip() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"\x91\xa1a").unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(
+            decompress(InputCompression::Gzip, &compressed),
+            Ok(b"\x91\xa1a".to_vec())
+        );
+        assert_eq!(
+            decompress(InputCompression::Gzip, &compressed[..compressed.len() / 2]),
+            Err(ErrorCode::ReadError)
+        );
+    }
+
+    #[cfg(feature = "zstd-input")]
+    #[test]
+    fn test_decompress_zstd() {
+        let compressed = ruzstd::encoding::compress_to_vec(
+            &b"\x91\xa1a"[..],
+            ruzstd::encoding::CompressionLevel::Fastest,
+        );
+        assert_eq!(
+            decompress(InputCompression::Zstd, &compressed),
+            Ok(b"\x91\xa1a".to_vec())
+        );
+        assert_eq!(
+            decompress(InputCompression::Zstd, b"not zstd"),
+            Err(ErrorCode::ReadError)
+        );
+    }
+}
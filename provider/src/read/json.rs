@@ -0,0 +1,420 @@
+//! Converts JSON input to msgpack when it is first read, so it is then read lazily like msgpack
+//! input.
+
+use rmp::encode;
+use shopify_function_wasm_api_core::read::ErrorCode;
+
+/// An array or object whose length is written once it is closed.
+struct Container {
+    /// The position in the output of the length, which is written as a 32-bit placeholder.
+    len_position: usize,
+    len: u32,
+    is_object: bool,
+}
+
+struct Transcoder<'a> {
+    json: &'a [u8],
+    position: usize,
+    output: Vec<u8>,
+    /// Buffer for strings with escape sequences, reused across strings.
+    unescaped: Vec<u8>,
+}
+
+/// Convert a JSON document to msgpack, returning [`ErrorCode::ReadError`] if it is not valid JSON.
+///
+/// Nesting is tracked on the heap rather than by recursion, so deeply nested input can't overflow
+/// the stack.
+pub(crate) fn to_msgpack(json: &[u8]) -> Result<Vec<u8>, ErrorCode> {
+    std::str::from_utf8(json).map_err(|_| ErrorCode::ReadError)?;
+    Transcoder {
+        json,
+        position: 0,
+        output: Vec::with_capacity(json.len()),
+        unescaped: Vec::new(),
+    }
+    .transcode()
+}
+
+impl Transcoder<'_> {
+    fn transcode(mut self) -> Result<Vec<u8>, ErrorCode> {
+        let mut containers: Vec<Container> = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.next_byte()? {
+                b'{' => {
+                    containers.push(self.open(true));
+                    self.skip_whitespace();
+                    if self.next_byte_if(b'}') {
+                        self.close(containers.pop().unwrap());
+                    } else {
+                        self.key()?;
+                        continue;
+                    }
+                }
+                b'[' => {
+                    containers.push(self.open(false));
+                    self.skip_whitespace();
+                    if self.next_byte_if(b']') {
+                        self.close(containers.pop().unwrap());
+                    } else {
+                        continue;
+                    }
+                }
+                b'"' => self.string()?,
+                b't' => {
+                    self.literal(b"rue")?;
+                    encode::write_bool(&mut self.output, true).unwrap(); // infallible unwrap
+                }
+                b'f' => {
+                    self.literal(b"alse")?;
+                    encode::write_bool(&mut self.output, false).unwrap(); // infallible unwrap
+                }
+                b'n' => {
+                    self.literal(b"ull")?;
+                    encode::write_nil(&mut self.output).unwrap(); // infallible unwrap
+                }
+                b'-' | b'0'..=b'9' => self.number()?,
+                _ => return Err(ErrorCode::ReadError),
+            }
+
+            // A value was read, so continue with the containers it completes.
+            loop {
+                let Some(container) = containers.last_mut() else {
+                    self.skip_whitespace();
+                    if self.position != self.json.len() {
+                        return Err(ErrorCode::ReadError);
+                    }
+                    return Ok(self.output);
+                };
+                container.len += 1;
+                let is_object = container.is_object;
+                self.skip_whitespace();
+                match self.next_byte()? {
+                    b',' => {
+                        if is_object {
+                            self.key()?;
+                        }
+                        break;
+                    }
+                    b'}' if is_object => self.close(containers.pop().unwrap()),
+                    b']' if !is_object => self.close(containers.pop().unwrap()),
+                    _ => return Err(ErrorCode::ReadError),
+                }
+            }
+        }
+    }
+
+    fn next_byte(&mut self) -> Result<u8, ErrorCode> {
+        let byte = *self.json.get(self.position).ok_or(ErrorCode::ReadError)?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn next_byte_if(&mut self, byte: u8) -> bool {
+        let matches = self.json.get(self.position) == Some(&byte);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.json.get(self.position) {
+            self.position += 1;
+        }
+    }
+
+    fn literal(&mut self, rest: &[u8]) -> Result<(), ErrorCode> {
+        if !self.json[self.position..].starts_with(rest) {
+            return Err(ErrorCode::ReadError);
+        }
+        self.position += rest.len();
+        Ok(())
+    }
+
+    fn open(&mut self, is_object: bool) -> Container {
+        let len_position = self.output.len() + 1;
+        // the maximum length is written so the 32-bit length format is used, as for dynamic arrays
+        if is_object {
+            encode::write_map_len(&mut self.output, u32::MAX).unwrap(); // infallible unwrap
+        } else {
+            encode::write_array_len(&mut self.output, u32::MAX).unwrap(); // infallible unwrap
+        }
+        Container {
+            len_position,
+            len: 0,
+            is_object,
+        }
+    }
+
+    fn close(&mut self, container: Container) {
+        self.output[container.len_position..container.len_position + 4]
+            .copy_from_slice(&container.len.to_be_bytes());
+    }
+
+    /// Read an object key and the colon after it.
+    fn key(&mut self) -> Result<(), ErrorCode> {
+        self.skip_whitespace();
+        if self.next_byte()? != b'"' {
+            return Err(ErrorCode::ReadError);
+        }
+        self.string()?;
+        self.skip_whitespace();
+        if self.next_byte()? != b':' {
+            return Err(ErrorCode::ReadError);
+        }
+        Ok(())
+    }
+
+    /// Read a string after its opening quote.
+    fn string(&mut self) -> Result<(), ErrorCode> {
+        let start = self.position;
+        loop {
+            match self.next_byte()? {
+                b'"' => {
+                    // strings without escape sequences are copied as they are
+                    let bytes = &self.json[start..self.position - 1];
+                    encode::write_str_len(&mut self.output, bytes.len() as u32).unwrap(); // infallible unwrap
+                    self.output.extend_from_slice(bytes);
+                    return Ok(());
+                }
+                b'\\' => break,
+                0..=0x1f => return Err(ErrorCode::ReadError),
+                _ => {}
+            }
+        }
+
+        self.unescaped.clear();
+        self.unescaped
+            .extend_from_slice(&self.json[start..self.position - 1]);
+        loop {
+            // the backslash of an escape sequence has just been read
+            match self.next_byte()? {
+                b'"' => self.unescaped.push(b'"'),
+                b'\\' => self.unescaped.push(b'\\'),
+                b'/' => self.unescaped.push(b'/'),
+                b'b' => self.unescaped.push(0x08),
+                b'f' => self.unescaped.push(0x0c),
+                b'n' => self.unescaped.push(b'\n'),
+                b'r' => self.unescaped.push(b'\r'),
+                b't' => self.unescaped.push(b'\t'),
+                b'u' => {
+                    let c = self.unicode_escape()?;
+                    let mut buf = [0; 4];
+                    self.unescaped
+                        .extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                _ => return Err(ErrorCode::ReadError),
+            }
+            loop {
+                match self.next_byte()? {
+                    b'"' => {
+                        encode::write_str_len(&mut self.output, self.unescaped.len() as u32)
+                            .unwrap(); // infallible unwrap
+                        self.output.extend_from_slice(&self.unescaped);
+                        return Ok(());
+                    }
+                    b'\\' => break,
+                    0..=0x1f => return Err(ErrorCode::ReadError),
+                    byte => self.unescaped.push(byte),
+                }
+            }
+        }
+    }
+
+    /// Read the code point of a `\u` escape sequence, combining surrogate pairs.
+    fn unicode_escape(&mut self) -> Result<char, ErrorCode> {
+        let high = self.hex4()?;
+        let code_point = match high {
+            0xd800..=0xdbff => {
+                if !self.next_byte_if(b'\\') || !self.next_byte_if(b'u') {
+                    return Err(ErrorCode::ReadError);
+                }
+                let low = self.hex4()?;
+                if !(0xdc00..=0xdfff).contains(&low) {
+                    return Err(ErrorCode::ReadError);
+                }
+                0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+            }
+            _ => high,
+        };
+        char::from_u32(code_point).ok_or(ErrorCode::ReadError)
+    }
+
+    fn hex4(&mut self) -> Result<u32, ErrorCode> {
+        let digits = self
+            .json
+            .get(self.position..self.position + 4)
+            .ok_or(ErrorCode::ReadError)?;
+        let mut value = 0;
+        for &digit in digits {
+            let digit = (digit as char).to_digit(16).ok_or(ErrorCode::ReadError)?;
+            value = value * 16 + digit;
+        }
+        self.position += 4;
+        Ok(value)
+    }
+
+    /// Read a number after its first byte. Integers are written as integers when they fit in 64
+    /// bits, and other numbers as 64-bit floats.
+    fn number(&mut self) -> Result<(), ErrorCode> {
+        let start = self.position - 1;
+        if self.json[start] == b'-' {
+            self.position += 1;
+        }
+        match self.json.get(self.position - 1) {
+            Some(b'0') => {}
+            Some(b'1'..=b'9') => self.digits(),
+            _ => return Err(ErrorCode::ReadError),
+        }
+        let mut is_integer = true;
+        if self.next_byte_if(b'.') {
+            is_integer = false;
+            self.required_digits()?;
+        }
+        if self.next_byte_if(b'e') || self.next_byte_if(b'E') {
+            is_integer = false;
+            if !self.next_byte_if(b'+') {
+                self.next_byte_if(b'-');
+            }
+            self.required_digits()?;
+        }
+
+        // the bytes were validated as UTF-8 up front, and a number is only ASCII
+        let number = std::str::from_utf8(&self.json[start..self.position]).unwrap();
+        if is_integer {
+            if let Ok(int) = number.parse::<i64>() {
+                encode::write_sint(&mut self.output, int).unwrap(); // infallible unwrap
+                return Ok(());
+            }
+            if let Ok(uint) = number.parse::<u64>() {
+                encode::write_uint(&mut self.output, uint).unwrap(); // infallible unwrap
+                return Ok(());
+            }
+        }
+        let float = number.parse::<f64>().map_err(|_| ErrorCode::ReadError)?;
+        encode::write_f64(&mut self.output, float).unwrap(); // infallible unwrap
+        Ok(())
+    }
+
+    fn digits(&mut self) {
+        while let Some(b'0'..=b'9') = self.json.get(self.position) {
+            self.position += 1;
+        }
+    }
+
+    fn required_digits(&mut self) -> Result<(), ErrorCode> {
+        let start = self.position;
+        self.digits();
+        if self.position == start {
+            return Err(ErrorCode::ReadError);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcode(json: &str) -> Result<serde_json::Value, ErrorCode> {
+        to_msgpack(json.as_bytes()).map(|bytes| rmp_serde::from_slice(&bytes).unwrap())
+    }
+
+    #[test]
+    fn test_values() {
+        [
+            "null",
+            "true",
+            "false",
+            "0",
+            "-1",
+            "42",
+            "-9223372036854775808",
+            "18446744073709551615",
+            "1.5",
+            "-0.25e2",
+            "1E+3",
+            "\"\"",
+            "\"hello\"",
+            "[]",
+            "{}",
+            "[1, [2, [3, []]], {}]",
+            r#"{"a": {"b": [true, null]}, "c": "d", "e": {}}"#,
+            r#"[{"id": "gid://shopify/CartLine/1", "quantity": 2}]"#,
+        ]
+        .into_iter()
+        .for_each(|json| {
+            let expected: serde_json::Value = serde_json::from_str(json).unwrap();
+            assert_eq!(transcode(json), Ok(expected), "{json}");
+        });
+    }
+
+    #[test]
+    fn test_whitespace() {
+        assert_eq!(
+            transcode(" \t\n\r{ \"a\" : [ 1 , 2 ] } \n"),
+            Ok(serde_json::json!({ "a": [1, 2] }))
+        );
+    }
+
+    #[test]
+    fn test_escapes() {
+        let json = r#""a\"b\\c\/d\b\f\n\r\t é \u00e9 \ud83d\ude00 ✌️""#;
+        let expected: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(transcode(json), Ok(expected));
+    }
+
+    #[test]
+    fn test_deeply_nested() {
+        let depth = 100_000;
+        let json = format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        let bytes = to_msgpack(json.as_bytes()).unwrap();
+        // each array is written with a 5-byte header
+        assert_eq!(bytes.len(), depth * 5);
+    }
+
+    #[test]
+    fn test_large_containers() {
+        let array: Vec<usize> = (0..100_000).collect();
+        let json = serde_json::to_string(&serde_json::json!({ "array": array })).unwrap();
+        assert_eq!(transcode(&json), Ok(serde_json::json!({ "array": array })));
+    }
+
+    #[test]
+    fn test_invalid() {
+        [
+            "",
+            " ",
+            "nul",
+            "truex",
+            "01",
+            "-",
+            "1.",
+            "1e",
+            ".5",
+            "+1",
+            "\"abc",
+            "\"\\x\"",
+            "\"\\u12\"",
+            "\"\\ud83d\"",
+            "\"\\ude00\"",
+            "\"a\nb\"",
+            "[1,]",
+            "[1 2]",
+            "{\"a\" 1}",
+            "{\"a\": 1,}",
+            "{1: 2}",
+            "{\"a\": 1]",
+            "[}",
+            "[1] [2]",
+            "'a'",
+        ]
+        .into_iter()
+        .for_each(|json| {
+            assert_eq!(transcode(json), Err(ErrorCode::ReadError), "{json:?}");
+        });
+
+        assert_eq!(to_msgpack(b"\"\xff\""), Err(ErrorCode::ReadError));
+    }
+}
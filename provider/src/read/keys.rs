@@ -0,0 +1,298 @@
+//! Checks the keys of the objects in the input, for hosts that set an [`InputKeyValidation`].
+
+use rmp::Marker;
+use shopify_function_wasm_api_core::{host::InputKeyValidation, read::ErrorCode};
+
+/// An array or map whose elements are being checked.
+struct Container {
+    remaining: usize,
+    /// The index in `Validator::keys` of the map's first key, or `None` for arrays.
+    keys_start: Option<usize>,
+}
+
+struct Validator<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    validation: InputKeyValidation,
+    /// The keys of the maps being checked, innermost last.
+    keys: Vec<&'a [u8]>,
+}
+
+/// Check that the keys of every object in the msgpack `bytes` are unique, and sorted by their bytes
+/// if `validation` is [`InputKeyValidation::Sorted`]. Keys that aren't strings and truncated input
+/// are [`ErrorCode::ReadError`]s, as they are when the input is read.
+pub(crate) fn validate(bytes: &[u8], validation: InputKeyValidation) -> Result<(), ErrorCode> {
+    if validation == InputKeyValidation::None {
+        return Ok(());
+    }
+    Validator {
+        bytes,
+        position: 0,
+        validation,
+        keys: Vec::new(),
+    }
+    .validate()
+}
+
+impl<'a> Validator<'a> {
+    fn validate(mut self) -> Result<(), ErrorCode> {
+        let mut containers: Vec<Container> = Vec::new();
+        loop {
+            // maps alternate between keys and values, starting with a key
+            let map_keys_start = containers
+                .last()
+                .filter(|container| container.remaining % 2 == 0)
+                .and_then(|container| container.keys_start);
+            if let Some(keys_start) = map_keys_start {
+                let key = self.read_key()?;
+                self.check_key(keys_start, key)?;
+                self.keys.push(key);
+            } else if let Some(container) = self.read_value()? {
+                containers.push(container);
+                continue;
+            }
+
+            // an element was read, which may finish its container, and in turn the container's
+            // parent
+            loop {
+                let Some(container) = containers.last_mut() else {
+                    return Ok(());
+                };
+                container.remaining -= 1;
+                if container.remaining > 0 {
+                    break;
+                }
+                if let Some(keys_start) = container.keys_start {
+                    self.check_unique_keys(keys_start)?;
+                    self.keys.truncate(keys_start);
+                }
+                containers.pop();
+            }
+        }
+    }
+
+    /// Check a key against the previous key of its map, if keys must be sorted.
+    fn check_key(&self, keys_start: usize, key: &[u8]) -> Result<(), ErrorCode> {
+        if self.validation != InputKeyValidation::Sorted {
+            return Ok(());
+        }
+        match self.keys[keys_start..].last() {
+            Some(previous) if *previous == key => Err(ErrorCode::DuplicateObjectKey),
+            Some(previous) if *previous > key => Err(ErrorCode::UnsortedObjectKeys),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that the keys of a finished map are unique, if they weren't already checked in order.
+    fn check_unique_keys(&mut self, keys_start: usize) -> Result<(), ErrorCode> {
+        if self.validation != InputKeyValidation::Unique {
+            return Ok(());
+        }
+        let keys = &mut self.keys[keys_start..];
+        keys.sort_unstable();
+        if keys.windows(2).any(|pair| pair[0] == pair[1]) {
+            return Err(ErrorCode::DuplicateObjectKey);
+        }
+        Ok(())
+    }
+
+    fn read_key(&mut self) -> Result<&'a [u8], ErrorCode> {
+        let len = match Marker::from_u8(self.next_byte()?) {
+            Marker::FixStr(len) => len as usize,
+            Marker::Str8 => self.read_len::<1>()?,
+            Marker::Str16 => self.read_len::<2>()?,
+            Marker::Str32 => self.read_len::<4>()?,
+            Marker::Reserved => return Err(ErrorCode::ReservedMarker),
+            _ => return Err(ErrorCode::ReadError),
+        };
+        self.next_slice(len)
+    }
+
+    /// Read a value, skipping it unless it's a non-empty array or map, which is returned to have
+    /// its elements read.
+    fn read_value(&mut self) -> Result<Option<Container>, ErrorCode> {
+        let (len, is_map) = match Marker::from_u8(self.next_byte()?) {
+            Marker::FixArray(len) => (len as usize, false),
+            Marker::Array16 => (self.read_len::<2>()?, false),
+            Marker::Array32 => (self.read_len::<4>()?, false),
+            Marker::FixMap(len) => (len as usize, true),
+            Marker::Map16 => (self.read_len::<2>()?, true),
+            Marker::Map32 => (self.read_len::<4>()?, true),
+            marker => {
+                let len = match marker {
+                    Marker::U8 | Marker::I8 => 1,
+                    Marker::U16 | Marker::I16 => 2,
+                    Marker::U32 | Marker::I32 | Marker::F32 => 4,
+                    Marker::U64 | Marker::I64 | Marker::F64 => 8,
+                    Marker::FixStr(len) => len as usize,
+                    Marker::Str8 | Marker::Bin8 => self.read_len::<1>()?,
+                    Marker::Str16 | Marker::Bin16 => self.read_len::<2>()?,
+                    Marker::Str32 | Marker::Bin32 => self.read_len::<4>()?,
+                    // extension data is preceded by its type
+                    Marker::FixExt1 => 1 + 1,
+                    Marker::FixExt2 => 1 + 2,
+                    Marker::FixExt4 => 1 + 4,
+                    Marker::FixExt8 => 1 + 8,
+                    Marker::FixExt16 => 1 + 16,
+                    Marker::Ext8 => self.read_len::<1>()? + 1,
+                    Marker::Ext16 => self.read_len::<2>()? + 1,
+                    Marker::Ext32 => self.read_len::<4>()?.saturating_add(1),
+                    Marker::Reserved => return Err(ErrorCode::ReservedMarker),
+                    _ => 0,
+                };
+                self.next_slice(len)?;
+                return Ok(None);
+            }
+        };
+        if len == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Container {
+            // maps have a key and a value per element
+            remaining: if is_map {
+                len.checked_mul(2).ok_or(ErrorCode::ReadError)?
+            } else {
+                len
+            },
+            keys_start: is_map.then_some(self.keys.len()),
+        }))
+    }
+
+    fn next_byte(&mut self) -> Result<u8, ErrorCode> {
+        let byte = *self.bytes.get(self.position).ok_or(ErrorCode::ReadError)?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn next_slice(&mut self, len: usize) -> Result<&'a [u8], ErrorCode> {
+        // `len` comes from the input, so compare against the remaining bytes rather than adding it
+        // to the position, which could overflow
+        if len > self.bytes.len() - self.position {
+            return Err(ErrorCode::ReadError);
+        }
+        let slice = &self.bytes[self.position..self.position + len];
+        self.position += len;
+        Ok(slice)
+    }
+
+    /// Read a big-endian length of `N` bytes.
+    fn read_len<const N: usize>(&mut self) -> Result<usize, ErrorCode> {
+        let mut be_bytes = [0; 8];
+        be_bytes[8 - N..].copy_from_slice(self.next_slice(N)?);
+        Ok(u64::from_be_bytes(be_bytes) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmp::encode;
+
+    /// An object with `keys`, whose values are empty objects, in an array after a string value.
+    fn input(keys: &[&str]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        encode::write_array_len(&mut bytes, 2).unwrap();
+        encode::write_str(&mut bytes, "z").unwrap();
+        encode::write_map_len(&mut bytes, keys.len() as u32).unwrap();
+        for key in keys {
+            encode::write_str(&mut bytes, key).unwrap();
+            encode::write_map_len(&mut bytes, 0).unwrap();
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_validate() {
+        for (keys, unique, sorted) in [
+            (&["a", "b", "c"][..], Ok(()), Ok(())),
+            (&["b", "a"], Ok(()), Err(ErrorCode::UnsortedObjectKeys)),
+            (
+                &["a", "b", "a"],
+                Err(ErrorCode::DuplicateObjectKey),
+                Err(ErrorCode::UnsortedObjectKeys),
+            ),
+            (
+                &["a", "a"],
+                Err(ErrorCode::DuplicateObjectKey),
+                Err(ErrorCode::DuplicateObjectKey),
+            ),
+            // keys are compared by their bytes
+            (&["B", "a"], Ok(()), Ok(())),
+            (&[], Ok(()), Ok(())),
+        ] {
+            let bytes = input(keys);
+            assert_eq!(validate(&bytes, InputKeyValidation::None), Ok(()));
+            assert_eq!(
+                validate(&bytes, InputKeyValidation::Unique),
+                unique,
+                "{keys:?}"
+            );
+            assert_eq!(
+                validate(&bytes, InputKeyValidation::Sorted),
+                sorted,
+                "{keys:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_nested_objects() {
+        let nested = |inner_keys: &[&str]| {
+            let mut bytes = Vec::new();
+            encode::write_map_len(&mut bytes, 2).unwrap();
+            encode::write_str(&mut bytes, "a").unwrap();
+            encode::write_map_len(&mut bytes, inner_keys.len() as u32).unwrap();
+            for key in inner_keys {
+                encode::write_str(&mut bytes, key).unwrap();
+                encode::write_nil(&mut bytes).unwrap();
+            }
+            encode::write_str(&mut bytes, "b").unwrap();
+            bytes.extend(input(&["a", "b"]));
+            bytes
+        };
+        // the same keys in different objects aren't duplicates
+        assert_eq!(
+            validate(&nested(&["b"]), InputKeyValidation::Sorted),
+            Ok(())
+        );
+        assert_eq!(
+            validate(&nested(&["b", "b"]), InputKeyValidation::Unique),
+            Err(ErrorCode::DuplicateObjectKey)
+        );
+        assert_eq!(
+            validate(&nested(&["c", "b"]), InputKeyValidation::Sorted),
+            Err(ErrorCode::UnsortedObjectKeys)
+        );
+    }
+
+    #[test]
+    fn test_validate_invalid_input() {
+        let bytes = input(&["a", "b"]);
+        assert_eq!(
+            validate(&bytes[..bytes.len() - 1], InputKeyValidation::Unique),
+            Err(ErrorCode::ReadError)
+        );
+        // a map with a number key
+        assert_eq!(
+            validate(&[0x81, 0x01, 0xc0], InputKeyValidation::Unique),
+            Err(ErrorCode::ReadError)
+        );
+        assert_eq!(
+            validate(&[0x91, 0xc1], InputKeyValidation::Unique),
+            Err(ErrorCode::ReservedMarker)
+        );
+        // map lengths come from the input, so a long one fails once the input ends
+        assert_eq!(
+            validate(&[0xdf, 0xff, 0xff, 0xff, 0xff], InputKeyValidation::Sorted),
+            Err(ErrorCode::ReadError)
+        );
+        // binary and extension values are skipped
+        assert_eq!(
+            validate(
+                &[0x92, 0xc4, 0x01, 0x00, 0xd4, 0x01, 0x00],
+                InputKeyValidation::Sorted
+            ),
+            Ok(())
+        );
+    }
+}
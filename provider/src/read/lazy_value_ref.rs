@@ -1,9 +1,28 @@
 use crate::read::{ErrorCode, NanBox};
 use bumpalo::{collections::Vec, Bump};
 use rmp::Marker;
+use std::{alloc::Layout, cmp::Ordering, ops::Range, ptr::NonNull};
 
 pub(crate) type LazyValueRefPtr<'a> = *mut LazyValueRef<'a>;
 
+/// Create a vector with room for `capacity` elements in the arena, failing with
+/// `ErrorCode::OutOfMemory` rather than aborting if it can't be allocated, e.g. because the host
+/// limited the arena's size. Elements are then pushed without checking the vector can grow, as
+/// there's room for all of them: each takes at least a byte, so there can't be more than remain in
+/// the input.
+fn try_vec_with_capacity_in<T>(capacity: usize, bump: &Bump) -> Result<Vec<'_, T>, ErrorCode> {
+    if capacity == 0 {
+        return Ok(Vec::new_in(bump));
+    }
+    let layout = Layout::array::<T>(capacity).map_err(|_| ErrorCode::OutOfMemory)?;
+    let ptr = bump
+        .try_alloc_layout(layout)
+        .map_err(|_| ErrorCode::OutOfMemory)?;
+    // the buffer was allocated in `bump` with room for `capacity` elements, as `with_capacity_in`
+    // would allocate it
+    Ok(unsafe { Vec::from_raw_parts_in(ptr.cast().as_ptr(), 0, capacity, bump) })
+}
+
 struct Cursor<'a> {
     bytes: &'a [u8],
     position: usize,
@@ -28,6 +47,35 @@ impl<'a> Cursor<'a> {
         Ok(marker)
     }
 
+    /// The number of bytes left to read.
+    fn remaining(&self) -> usize {
+        self.length.saturating_sub(self.position)
+    }
+
+    fn read_str(&mut self, len: usize) -> Result<StringRef, ErrorCode> {
+        // `len` comes from the input, so compare against the remaining bytes rather than adding
+        // it to the position, which could overflow.
+        if len > self.length - self.position {
+            return Err(ErrorCode::ReadError);
+        }
+
+        let string_ref = StringRef {
+            ptr: self.position,
+            len,
+        };
+        self.position += len;
+        Ok(string_ref)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), ErrorCode> {
+        // see `read_str`
+        if len > self.length - self.position {
+            return Err(ErrorCode::ReadError);
+        }
+        self.position += len;
+        Ok(())
+    }
+
     fn read_f32(&mut self) -> Result<f32, ErrorCode> {
         if self.position + 4 > self.length {
             return Err(ErrorCode::ReadError);
@@ -149,15 +197,42 @@ pub(crate) struct StringRef {
     len: usize,
 }
 
+/// Where a key was last found in an object, used to look it up directly in objects of the same
+/// shape. See [`LazyValueRef::get_object_property_with_hint`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct PropertyHint {
+    object_len: usize,
+    index: usize,
+}
+
+impl Default for PropertyHint {
+    fn default() -> Self {
+        // no object has this many properties, so the hint is never used until it's updated
+        Self {
+            object_len: usize::MAX,
+            index: 0,
+        }
+    }
+}
+
 #[derive(PartialEq, Debug)]
 pub(crate) struct ObjectRef<'a> {
+    /// The position of the object's marker in the input.
+    start_position: usize,
     len: usize,
     /// The key will always be a `StringRef`, but we type it as a `LazyValueRef` so that we can
     /// return it as a `NanBox`.
     processed_elements: Vec<'a, (LazyValueRef<'a>, LazyValueRef<'a>)>,
     end_position_of_last_processed_element: usize,
+    /// Whether the keys are sorted by their bytes, checked once all of the elements of a wide
+    /// object are processed. See [`ObjectRef::find_property`].
+    keys_sorted: Option<bool>,
 }
 
+/// The number of properties from which fully processed objects are checked for sorted keys, to be
+/// binary searched. Smaller objects are searched linearly, which is as fast.
+const BINARY_SEARCH_MIN_LEN: usize = 16;
+
 impl<'a> ObjectRef<'a> {
     fn get_at_index(
         &mut self,
@@ -208,9 +283,64 @@ impl<'a> ObjectRef<'a> {
     fn get_property(
         &mut self,
         key: &[u8],
+        keys_sorted: bool,
+        bytes: &[u8],
+        bump: &'a Bump,
+    ) -> Result<Option<&LazyValueRef<'a>>, ErrorCode> {
+        let index_of_value = self.find_property(key, keys_sorted, bytes, bump)?;
+        Ok(index_of_value.map(|i| &self.processed_elements[i].1))
+    }
+
+    /// Like [`ObjectRef::get_property`], but first checks the index in `hint`, which is where the
+    /// key was last found in an object with the same number of properties. Objects with the same
+    /// shape (e.g. cart lines) have their keys at the same indices, so this skips comparing the
+    /// key with every key before it. `hint` is updated with where the key was found.
+    ///
+    /// If an object has duplicate keys, the hinted one is returned, which may not be the first.
+    fn get_property_with_hint(
+        &mut self,
+        key: &[u8],
+        keys_sorted: bool,
+        hint: &mut PropertyHint,
         bytes: &[u8],
         bump: &'a Bump,
     ) -> Result<Option<&LazyValueRef<'a>>, ErrorCode> {
+        if hint.object_len == self.len {
+            let (key_ref, _) = self.get_at_index(hint.index, bytes, bump)?;
+            if matches!(key_ref, LazyValueRef::String(StringRef { ptr, len }) if &bytes[*ptr..*ptr + *len] == key)
+            {
+                return Ok(Some(&self.processed_elements[hint.index].1));
+            }
+        }
+
+        let index_of_value = self.find_property(key, keys_sorted, bytes, bump)?;
+        if let Some(index) = index_of_value {
+            *hint = PropertyHint {
+                object_len: self.len,
+                index,
+            };
+        }
+        Ok(index_of_value.map(|i| &self.processed_elements[i].1))
+    }
+
+    /// Find the index of the property with `key`, processing the object's elements until it's
+    /// found.
+    ///
+    /// The processed keys are binary searched if `keys_sorted`, when the host guaranteed sorted
+    /// keys, or once all of the keys of a wide object are processed and found to be sorted. Wide
+    /// objects, such as configuration, are often read property by property, so lookups after the
+    /// keys are checked take O(log n) comparisons rather than O(n).
+    fn find_property(
+        &mut self,
+        key: &[u8],
+        keys_sorted: bool,
+        bytes: &[u8],
+        bump: &'a Bump,
+    ) -> Result<Option<usize>, ErrorCode> {
+        if keys_sorted || self.processed_keys_sorted(bytes) {
+            return self.find_sorted_property(key, bytes, bump);
+        }
+
         let index_of_value_in_existing =
             self.processed_elements.iter().position(|(key_value, _)| {
                 matches!(key_value, LazyValueRef::String(StringRef { ptr, len }) if {
@@ -265,7 +395,53 @@ impl<'a> ObjectRef<'a> {
             }
         };
 
-        Ok(index_of_value.map(|i| &self.processed_elements[i].1))
+        Ok(index_of_value)
+    }
+
+    /// Whether all of the keys are processed and sorted, for objects with at least
+    /// [`BINARY_SEARCH_MIN_LEN`] properties. The keys are only compared the first time.
+    fn processed_keys_sorted(&mut self, bytes: &[u8]) -> bool {
+        if self.len < BINARY_SEARCH_MIN_LEN || self.processed_elements.len() < self.len {
+            return false;
+        }
+        *self.keys_sorted.get_or_insert_with(|| {
+            self.processed_elements
+                .windows(2)
+                .all(|pair| pair[0].0.key_bytes(bytes) < pair[1].0.key_bytes(bytes))
+        })
+    }
+
+    /// Like [`ObjectRef::find_property`], for objects whose keys are known to be sorted by their
+    /// bytes. The processed elements are binary searched, and processing stops at the first key
+    /// after `key`.
+    ///
+    /// Kept out of line so lookups in unvalidated input aren't slowed down.
+    #[inline(never)]
+    fn find_sorted_property(
+        &mut self,
+        key: &[u8],
+        bytes: &[u8],
+        bump: &'a Bump,
+    ) -> Result<Option<usize>, ErrorCode> {
+        match self
+            .processed_elements
+            .binary_search_by(|(key_value, _)| key_value.key_bytes(bytes).cmp(key))
+        {
+            Ok(index) => return Ok(Some(index)),
+            // the key would be before an element that was already processed
+            Err(index) if index < self.processed_elements.len() => return Ok(None),
+            Err(_) => {}
+        }
+
+        for index in self.processed_elements.len()..self.len {
+            let (key_ref, _) = self.get_at_index(index, bytes, bump)?;
+            match key_ref.key_bytes(bytes).cmp(key) {
+                Ordering::Equal => return Ok(Some(index)),
+                Ordering::Greater => return Ok(None),
+                Ordering::Less => {}
+            }
+        }
+        Ok(None)
     }
 
     fn finish_processing(
@@ -308,6 +484,8 @@ impl<'a> ObjectRef<'a> {
 
 #[derive(Debug, PartialEq)]
 pub(crate) struct ArrayRef<'a> {
+    /// The position of the array's marker in the input.
+    start_position: usize,
     len: usize,
     processed_elements: Vec<'a, LazyValueRef<'a>>,
     end_position_of_last_processed_element: usize,
@@ -380,6 +558,79 @@ impl<'a> ArrayRef<'a> {
 
         Ok(Some(self.end_position_of_last_processed_element))
     }
+
+    /// Returns the range of the input taken by the elements from `start` up to `end`, processing
+    /// the elements up to `end` to find it.
+    fn elements_range(
+        &mut self,
+        start: usize,
+        end: usize,
+        bytes: &[u8],
+        bump: &'a Bump,
+    ) -> Result<Range<usize>, ErrorCode> {
+        if start >= end {
+            return Ok(0..0);
+        }
+        self.get_at_index(end - 1, bytes, bump)?;
+
+        // the elements follow the marker and the length, which is part of the marker for fixarrays
+        let mut position = self.start_position
+            + match Marker::from_u8(bytes[self.start_position]) {
+                Marker::Array16 => 3,
+                Marker::Array32 => 5,
+                _ => 1,
+            };
+        let mut range_start = position;
+        for (index, element) in self.processed_elements[..end].iter_mut().enumerate() {
+            if index == start {
+                range_start = position;
+            }
+            position = match element.finish_processing(bytes, bump)? {
+                Some(end_position) => end_position,
+                // other values are read in full by `new`, which finds where they end
+                None => LazyValueRef::new(bytes, position, bump)?
+                    .1
+                    .expect("`new` returns the end position of values that aren't composite"),
+            };
+        }
+        Ok(range_start..position)
+    }
+}
+
+/// A view over a range of an array's elements, which are read from the array as they're accessed.
+#[derive(Debug, PartialEq)]
+pub(crate) struct SliceRef<'a> {
+    /// The sliced array, which is never itself a slice.
+    array: NonNull<LazyValueRef<'a>>,
+    start: usize,
+    len: usize,
+}
+
+impl<'a> SliceRef<'a> {
+    fn array(&self) -> Result<&'a mut LazyValueRef<'a>, ErrorCode> {
+        LazyValueRef::mut_from_raw(self.array.as_ptr())
+    }
+
+    fn get_at_index(
+        &self,
+        index: usize,
+        bytes: &[u8],
+        bump: &'a Bump,
+    ) -> Result<&'a LazyValueRef<'a>, ErrorCode> {
+        if index >= self.len {
+            return Err(ErrorCode::IndexOutOfBounds);
+        }
+        self.array()?.get_at_index(self.start + index, bytes, bump)
+    }
+
+    fn elements_range(&self, bytes: &[u8], bump: &'a Bump) -> Result<Range<usize>, ErrorCode> {
+        match self.array()? {
+            LazyValueRef::Array(array_ref) => {
+                array_ref.elements_range(self.start, self.start + self.len, bytes, bump)
+            }
+            _ => Err(ErrorCode::NotAnArray),
+        }
+    }
 }
 
 /// A lazy value reference.
@@ -399,6 +650,12 @@ pub(crate) enum LazyValueRef<'a> {
     String(StringRef),
     Array(ArrayRef<'a>),
     Object(ObjectRef<'a>),
+    /// A range of an array's elements, which is encoded as an array.
+    Slice(SliceRef<'a>),
+    /// Binary data, which is read like a string but isn't checked to be UTF-8.
+    Bytes(StringRef),
+    /// An extension type, which is skipped over but can't be read.
+    Unsupported,
 }
 
 impl<'a> LazyValueRef<'a> {
@@ -419,6 +676,15 @@ impl<'a> LazyValueRef<'a> {
                 let ptr = self as *const _;
                 NanBox::obj(ptr as _, *len)
             }
+            LazyValueRef::Slice(SliceRef { len, .. }) => {
+                let ptr = self as *const _;
+                NanBox::array(ptr as _, *len)
+            }
+            LazyValueRef::Bytes(StringRef { len, .. }) => {
+                let ptr = self as *const _;
+                NanBox::bytes(ptr as _, *len)
+            }
+            LazyValueRef::Unsupported => NanBox::error(ErrorCode::UnsupportedValue),
         }
     }
 
@@ -428,10 +694,39 @@ impl<'a> LazyValueRef<'a> {
         if raw.is_null() {
             return Err(ErrorCode::ReadError);
         }
-        // Safety: we've verified the pointer is not null
+        debug_assert!(raw.is_aligned(), "misaligned value {raw:p}");
+        // Safety: the pointer isn't null, and non-null pointers are only decoded from NaN-boxed
+        // values the provider encoded from values in the bump allocator, which lives as long as the
+        // context. The guest calls into the provider one call at a time, so nothing else borrows
+        // the value while it's being processed.
         Ok(unsafe { &mut *raw })
     }
 
+    /// Create a slice of the array or slice at `raw` with its elements from `start` up to `end`.
+    ///
+    /// Like slicing a `Vec`, `start` is the index of the first element and `end` is one past the
+    /// last, but both are clamped to the length of the array, so the slice is empty rather than an
+    /// error if they're out of bounds. A slice of a slice is a slice of the underlying array.
+    pub(crate) fn slice(
+        raw: LazyValueRefPtr<'a>,
+        start: usize,
+        end: usize,
+    ) -> Result<Self, ErrorCode> {
+        let raw = NonNull::new(raw).ok_or(ErrorCode::ReadError)?;
+        let (array, offset, len) = match Self::mut_from_raw(raw.as_ptr())? {
+            Self::Array(ArrayRef { len, .. }) => (raw, 0, *len),
+            Self::Slice(SliceRef { array, start, len }) => (*array, *start, *len),
+            _ => return Err(ErrorCode::NotAnArray),
+        };
+        let start = start.min(len);
+        let end = end.clamp(start, len);
+        Ok(Self::Slice(SliceRef {
+            array,
+            start: offset + start,
+            len: end - start,
+        }))
+    }
+
     /// Create a new lazy value reference from a byte slice and a position.
     ///
     /// The 2-tuple in the Ok variant contains the lazy value reference as well
@@ -490,43 +785,27 @@ impl<'a> LazyValueRef<'a> {
             // String types
             Marker::FixStr(len) => {
                 let len = len as usize;
-                Ok((
-                    Self::String(StringRef {
-                        ptr: cursor.position,
-                        len,
-                    }),
-                    Some(cursor.position + len),
-                ))
+                cursor
+                    .read_str(len)
+                    .map(|string_ref| (Self::String(string_ref), Some(cursor.position)))
             }
             Marker::Str8 => {
                 let len = cursor.read_u8().map(|n| n as usize)?;
-                Ok((
-                    Self::String(StringRef {
-                        ptr: cursor.position,
-                        len,
-                    }),
-                    Some(cursor.position + len),
-                ))
+                cursor
+                    .read_str(len)
+                    .map(|string_ref| (Self::String(string_ref), Some(cursor.position)))
             }
             Marker::Str16 => {
                 let len = cursor.read_u16().map(|n| n as usize)?;
-                Ok((
-                    Self::String(StringRef {
-                        ptr: cursor.position,
-                        len,
-                    }),
-                    Some(cursor.position + len),
-                ))
+                cursor
+                    .read_str(len)
+                    .map(|string_ref| (Self::String(string_ref), Some(cursor.position)))
             }
             Marker::Str32 => {
                 let len = cursor.read_u32().map(|n| n as usize)?;
-                Ok((
-                    Self::String(StringRef {
-                        ptr: cursor.position,
-                        len,
-                    }),
-                    Some(cursor.position + len),
-                ))
+                cursor
+                    .read_str(len)
+                    .map(|string_ref| (Self::String(string_ref), Some(cursor.position)))
             }
 
             // Map types
@@ -534,9 +813,11 @@ impl<'a> LazyValueRef<'a> {
                 let len = len as usize;
                 Ok((
                     Self::Object(ObjectRef {
+                        start_position: position,
                         len,
-                        processed_elements: Vec::with_capacity_in(len, bump),
+                        processed_elements: try_vec_with_capacity_in(len, bump)?,
                         end_position_of_last_processed_element: cursor.position,
+                        keys_sorted: None,
                     }),
                     None,
                 ))
@@ -545,20 +826,30 @@ impl<'a> LazyValueRef<'a> {
                 let len = cursor.read_u16().map(|n| n as usize)?;
                 Ok((
                     Self::Object(ObjectRef {
+                        start_position: position,
                         len,
-                        processed_elements: Vec::with_capacity_in(len, bump),
+                        processed_elements: try_vec_with_capacity_in(len, bump)?,
                         end_position_of_last_processed_element: cursor.position,
+                        keys_sorted: None,
                     }),
                     None,
                 ))
             }
+            // 32-bit lengths come straight from the input, so the capacity is bounded by the
+            // remaining bytes (every element takes at least one) to avoid huge allocations for
+            // malformed input.
             Marker::Map32 => {
                 let len = cursor.read_u32().map(|n| n as usize)?;
                 Ok((
                     Self::Object(ObjectRef {
+                        start_position: position,
                         len,
-                        processed_elements: Vec::with_capacity_in(len, bump),
+                        processed_elements: try_vec_with_capacity_in(
+                            len.min(cursor.remaining()),
+                            bump,
+                        )?,
                         end_position_of_last_processed_element: cursor.position,
+                        keys_sorted: None,
                     }),
                     None,
                 ))
@@ -569,8 +860,9 @@ impl<'a> LazyValueRef<'a> {
                 let len = len as usize;
                 Ok((
                     Self::Array(ArrayRef {
+                        start_position: position,
                         len,
-                        processed_elements: Vec::with_capacity_in(len, bump),
+                        processed_elements: try_vec_with_capacity_in(len, bump)?,
                         end_position_of_last_processed_element: cursor.position,
                     }),
                     None,
@@ -580,42 +872,111 @@ impl<'a> LazyValueRef<'a> {
                 let len = cursor.read_u16().map(|n| n as usize)?;
                 Ok((
                     Self::Array(ArrayRef {
+                        start_position: position,
                         len,
-                        processed_elements: Vec::with_capacity_in(len, bump),
+                        processed_elements: try_vec_with_capacity_in(len, bump)?,
                         end_position_of_last_processed_element: cursor.position,
                     }),
                     None,
                 ))
             }
+            // See `Marker::Map32`.
             Marker::Array32 => {
                 let len = cursor.read_u32().map(|n| n as usize)?;
                 Ok((
                     Self::Array(ArrayRef {
+                        start_position: position,
                         len,
-                        processed_elements: Vec::with_capacity_in(len, bump),
+                        processed_elements: try_vec_with_capacity_in(
+                            len.min(cursor.remaining()),
+                            bump,
+                        )?,
                         end_position_of_last_processed_element: cursor.position,
                     }),
                     None,
                 ))
             }
 
-            // Unknown or unsupported marker
-            _ => Err(ErrorCode::ReadError),
+            // Binary data, which is kept as a range of the input like strings
+            Marker::Bin8 => {
+                let len = cursor.read_u8().map(|n| n as usize)?;
+                cursor
+                    .read_str(len)
+                    .map(|bytes_ref| (Self::Bytes(bytes_ref), Some(cursor.position)))
+            }
+            Marker::Bin16 => {
+                let len = cursor.read_u16().map(|n| n as usize)?;
+                cursor
+                    .read_str(len)
+                    .map(|bytes_ref| (Self::Bytes(bytes_ref), Some(cursor.position)))
+            }
+            Marker::Bin32 => {
+                let len = cursor.read_u32().map(|n| n as usize)?;
+                cursor
+                    .read_str(len)
+                    .map(|bytes_ref| (Self::Bytes(bytes_ref), Some(cursor.position)))
+            }
+
+            // Extension types can't be read, but are skipped over so the values after them can be.
+            // extension data is preceded by its type
+            Marker::FixExt1 => Self::skip_unsupported(&mut cursor, 1 + 1),
+            Marker::FixExt2 => Self::skip_unsupported(&mut cursor, 1 + 2),
+            Marker::FixExt4 => Self::skip_unsupported(&mut cursor, 1 + 4),
+            Marker::FixExt8 => Self::skip_unsupported(&mut cursor, 1 + 8),
+            Marker::FixExt16 => Self::skip_unsupported(&mut cursor, 1 + 16),
+            Marker::Ext8 => {
+                let len = cursor.read_u8().map(|n| n as usize)?;
+                Self::skip_unsupported(&mut cursor, len + 1)
+            }
+            Marker::Ext16 => {
+                let len = cursor.read_u16().map(|n| n as usize)?;
+                Self::skip_unsupported(&mut cursor, len + 1)
+            }
+            Marker::Ext32 => {
+                let len = cursor.read_u32().map(|n| n as usize)?;
+                Self::skip_unsupported(&mut cursor, len.saturating_add(1))
+            }
+
+            Marker::Reserved => Err(ErrorCode::ReservedMarker),
+        }
+    }
+
+    fn skip_unsupported(
+        cursor: &mut Cursor,
+        len: usize,
+    ) -> Result<(Self, Option<usize>), ErrorCode> {
+        cursor.skip(len)?;
+        Ok((Self::Unsupported, Some(cursor.position)))
+    }
+
+    /// Returns the exact length of a string, byte array, array, or object, regardless of
+    /// whether it fits in the length bits of its NaN-box, or `None` for all
+    /// other values.
+    pub(crate) fn get_value_length(&self) -> Option<usize> {
+        match self {
+            Self::String(StringRef { len, .. }) | Self::Bytes(StringRef { len, .. }) => Some(*len),
+            Self::Array(ArrayRef { len, .. }) => Some(*len),
+            Self::Object(ObjectRef { len, .. }) => Some(*len),
+            Self::Slice(SliceRef { len, .. }) => Some(*len),
+            _ => None,
         }
     }
 
-    pub(crate) fn get_value_length(&self) -> usize {
+    /// The bytes of an object key, which is always a string.
+    pub(crate) fn key_bytes<'b>(&self, bytes: &'b [u8]) -> &'b [u8] {
         match self {
-            Self::String(StringRef { len, .. }) => *len,
-            Self::Array(ArrayRef { len, .. }) => *len,
-            Self::Object(ObjectRef { len, .. }) => *len,
-            _ => 0,
+            Self::String(StringRef { ptr, len }) => &bytes[*ptr..*ptr + *len],
+            _ => &[],
         }
     }
 
+    /// Returns the address of the bytes of a string or byte array in the input, or 0 for all other
+    /// values.
     pub(crate) fn get_utf8_str_addr(&self, bytes: &[u8]) -> usize {
         match self {
-            Self::String(StringRef { ptr, .. }) => bytes[*ptr..].as_ptr() as usize,
+            Self::String(StringRef { ptr, .. }) | Self::Bytes(StringRef { ptr, .. }) => {
+                bytes[*ptr..].as_ptr() as usize
+            }
             _ => 0,
         }
     }
@@ -629,6 +990,7 @@ impl<'a> LazyValueRef<'a> {
         match self {
             Self::Array(array_ref) => array_ref.get_at_index(index, bytes, bump),
             Self::Object(obj_ref) => obj_ref.get_at_index(index, bytes, bump).map(|v| &v.1),
+            Self::Slice(slice_ref) => slice_ref.get_at_index(index, bytes, bump),
             _ => Err(ErrorCode::NotIndexable),
         }
     }
@@ -645,31 +1007,105 @@ impl<'a> LazyValueRef<'a> {
         }
     }
 
+    /// Get the property with `key`. `keys_sorted` is whether the object's keys are known to be
+    /// sorted by their bytes, which the host may guarantee with an `InputKeyValidation`.
     pub(crate) fn get_object_property<'b>(
         &'b mut self,
         key: &[u8],
+        keys_sorted: bool,
+        bytes: &[u8],
+        bump: &'a Bump,
+    ) -> Result<Option<&'b Self>, ErrorCode> {
+        match self {
+            Self::Object(obj_ref) => obj_ref.get_property(key, keys_sorted, bytes, bump),
+            _ => Err(ErrorCode::NotAnObject),
+        }
+    }
+
+    pub(crate) fn get_object_property_with_hint<'b>(
+        &'b mut self,
+        key: &[u8],
+        keys_sorted: bool,
+        hint: &mut PropertyHint,
         bytes: &[u8],
         bump: &'a Bump,
     ) -> Result<Option<&'b Self>, ErrorCode> {
         match self {
-            Self::Object(obj_ref) => obj_ref.get_property(key, bytes, bump),
+            Self::Object(obj_ref) => {
+                obj_ref.get_property_with_hint(key, keys_sorted, hint, bytes, bump)
+            }
             _ => Err(ErrorCode::NotAnObject),
         }
     }
 
+    /// Returns the value encoded as MessagePack. Arrays and objects are processed to their end and
+    /// returned as they are in the input, and slices copy their elements as they are in the input
+    /// after a new array length, while other values are encoded into `buffer`, with numbers that
+    /// have no fractional part encoded as integers.
+    pub(crate) fn encode_msgpack<'b>(
+        &mut self,
+        bytes: &'b [u8],
+        bump: &'a Bump,
+        buffer: &'b mut std::vec::Vec<u8>,
+    ) -> Result<&'b [u8], ErrorCode> {
+        buffer.clear();
+        // writing to a `Vec` is infallible
+        match self {
+            Self::Null => rmp::encode::write_nil(buffer).unwrap(),
+            Self::Bool(b) => rmp::encode::write_bool(buffer, *b).unwrap(),
+            Self::Number(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => {
+                rmp::encode::write_sint(buffer, *n as i64).unwrap();
+            }
+            Self::Number(n) => rmp::encode::write_f64(buffer, *n).unwrap(),
+            Self::String(StringRef { ptr, len }) => {
+                // strings in the input have 32-bit lengths
+                rmp::encode::write_str_len(buffer, *len as u32).unwrap();
+                buffer.extend_from_slice(&bytes[*ptr..*ptr + *len]);
+            }
+            Self::Bytes(StringRef { ptr, len }) => {
+                // binary data in the input has 32-bit lengths
+                rmp::encode::write_bin_len(buffer, *len as u32).unwrap();
+                buffer.extend_from_slice(&bytes[*ptr..*ptr + *len]);
+            }
+            Self::Array(ArrayRef { start_position, .. })
+            | Self::Object(ObjectRef { start_position, .. }) => {
+                let start_position = *start_position;
+                let end_position = self
+                    .finish_processing(bytes, bump)?
+                    .expect("arrays and objects return their end position");
+                return Ok(&bytes[start_position..end_position]);
+            }
+            Self::Slice(slice_ref) => {
+                let range = slice_ref.elements_range(bytes, bump)?;
+                // arrays in the input have 32-bit lengths
+                rmp::encode::write_array_len(buffer, slice_ref.len as u32).unwrap();
+                buffer.extend_from_slice(&bytes[range]);
+            }
+            Self::Unsupported => return Err(ErrorCode::UnsupportedValue),
+        }
+        Ok(buffer)
+    }
+
     /// Returns the end position of the value, if it was a composite type and
     /// therefore was finished during this call. If it was not a composite type,
     /// the end position is not known and None is returned, but the end position
     /// would have been returned in the `new` call to create the value.
-    fn finish_processing(
+    pub(crate) fn finish_processing(
         &mut self,
         bytes: &[u8],
         bump: &'a Bump,
     ) -> Result<Option<usize>, ErrorCode> {
         match self {
             Self::Array(array_ref) => array_ref.finish_processing(bytes, bump),
-            Self::Null | Self::Bool(_) | Self::Number(_) | Self::String { .. } => Ok(None),
+            Self::Null
+            | Self::Bool(_)
+            | Self::Number(_)
+            | Self::String { .. }
+            | Self::Bytes(_)
+            | Self::Unsupported => Ok(None),
             Self::Object(obj_ref) => obj_ref.finish_processing(bytes, bump),
+            // slices aren't elements of other values, so their end position isn't needed
+            Self::Slice(slice_ref) => slice_ref.elements_range(bytes, bump).map(|_| None),
         }
     }
 }
@@ -816,6 +1252,7 @@ mod tests {
         assert_eq!(
             value,
             LazyValueRef::Array(ArrayRef {
+                start_position: 0,
                 len: 3,
                 processed_elements: bumpalo::collections::Vec::new_in(&bump),
                 end_position_of_last_processed_element: 1
@@ -842,6 +1279,7 @@ mod tests {
         let bump = Bump::new();
         let len = 3;
         let value = LazyValueRef::Array(ArrayRef {
+            start_position: 0,
             len,
             processed_elements: bumpalo::collections::Vec::new_in(&bump),
             end_position_of_last_processed_element: 0,
@@ -851,6 +1289,173 @@ mod tests {
         assert_eq!(nanbox, NanBox::array(ptr, len));
     }
 
+    #[test]
+    fn test_mut_from_raw() {
+        assert_eq!(
+            LazyValueRef::mut_from_raw(std::ptr::null_mut()).err(),
+            Some(ErrorCode::ReadError)
+        );
+
+        // values are reached through the pointers in their NaN boxes, as the guest passes them back
+        let bytes = build_msgpack(|w| {
+            encode::write_array_len(w, 1)?;
+            encode::write_array_len(w, 1)?;
+            encode::write_bool(w, true).unwrap();
+            Ok::<_, encode::ValueWriteError<std::convert::Infallible>>(())
+        })
+        .unwrap();
+        let bump = Bump::new();
+        let value = bump.alloc(create_lazy_value(&bytes, &bump));
+        let Ok(shopify_function_wasm_api_core::read::ValueRef::Array { ptr, .. }) =
+            value.encode().try_decode()
+        else {
+            panic!("expected an array");
+        };
+        let value = LazyValueRef::mut_from_raw(ptr as _).unwrap();
+        let inner = value.get_at_index(0, &bytes, &bump).unwrap().encode();
+        let Ok(shopify_function_wasm_api_core::read::ValueRef::Array { ptr, .. }) =
+            inner.try_decode()
+        else {
+            panic!("expected an array");
+        };
+        let inner = LazyValueRef::mut_from_raw(ptr as _).unwrap();
+        assert_eq!(
+            inner.get_at_index(0, &bytes, &bump).unwrap(),
+            &LazyValueRef::Bool(true)
+        );
+    }
+
+    #[test]
+    fn test_encode_msgpack() {
+        // a map with a non-canonical 16-bit length, which is kept as it is, and a string
+        let bytes = [0x92, 0xde, 0x00, 0x01, 0xa1, b'a', 0x90, 0xa2, b'b', b'c'];
+        let bump = Bump::new();
+        let mut buffer = Vec::new();
+        let mut value = create_lazy_value(&bytes, &bump);
+        let object = value.get_at_index(0, &bytes, &bump).unwrap() as *const _ as *mut _;
+        let object = LazyValueRef::mut_from_raw(object).unwrap();
+        assert_eq!(
+            object.encode_msgpack(&bytes, &bump, &mut buffer).unwrap(),
+            &bytes[1..7]
+        );
+        assert_eq!(
+            value.encode_msgpack(&bytes, &bump, &mut buffer).unwrap(),
+            &bytes[..]
+        );
+
+        fn encoded<'a>(
+            mut value: LazyValueRef<'a>,
+            bytes: &[u8],
+            bump: &'a Bump,
+        ) -> Result<Vec<u8>, ErrorCode> {
+            let mut buffer = Vec::new();
+            value
+                .encode_msgpack(bytes, bump, &mut buffer)
+                .map(<[u8]>::to_vec)
+        }
+        assert_eq!(encoded(LazyValueRef::Null, &bytes, &bump), Ok(vec![0xc0]));
+        assert_eq!(
+            encoded(LazyValueRef::Bool(true), &bytes, &bump),
+            Ok(vec![0xc3])
+        );
+        assert_eq!(
+            encoded(LazyValueRef::Number(-3.0), &bytes, &bump),
+            Ok(vec![0xfd])
+        );
+        assert_eq!(
+            encoded(LazyValueRef::Number(1.5), &bytes, &bump),
+            Ok(build_msgpack(|w| encode::write_f64(w, 1.5)).unwrap())
+        );
+        assert_eq!(
+            encoded(
+                LazyValueRef::String(StringRef { ptr: 8, len: 2 }),
+                &bytes,
+                &bump
+            ),
+            Ok(bytes[7..].to_vec())
+        );
+        assert_eq!(
+            encoded(LazyValueRef::Unsupported, &bytes, &bump),
+            Err(ErrorCode::UnsupportedValue)
+        );
+
+        // whole-number floats are encoded as integers on their own, but arrays and objects
+        // containing them are kept byte for byte, so their encoding hashes the same as the input's
+        let float = build_msgpack(|w| encode::write_f64(w, 2.0)).unwrap();
+        assert_eq!(
+            encoded(create_lazy_value(&float, &bump), &float, &bump),
+            Ok(vec![0x02])
+        );
+        let bytes = [&[0x91][..], &float].concat();
+        assert_eq!(
+            encoded(create_lazy_value(&bytes, &bump), &bytes, &bump),
+            Ok(bytes.clone())
+        );
+        let bytes = [&[0x81, 0xa1, b'a'][..], &float].concat();
+        assert_eq!(
+            encoded(create_lazy_value(&bytes, &bump), &bytes, &bump),
+            Ok(bytes.clone())
+        );
+    }
+
+    #[test]
+    fn test_slice() {
+        // [1, [2], "a", 4]
+        let bytes = [0x94, 0x01, 0x91, 0x02, 0xa1, b'a', 0x04];
+        let bump = Bump::new();
+        let mut buffer = Vec::new();
+        let array: LazyValueRefPtr = bump.alloc(create_lazy_value(&bytes, &bump));
+
+        let mut slice = LazyValueRef::slice(array, 1, 3).unwrap();
+        assert_eq!(slice.get_value_length(), Some(2));
+        assert!(matches!(
+            slice.get_at_index(0, &bytes, &bump).unwrap(),
+            LazyValueRef::Array(_)
+        ));
+        assert_eq!(
+            slice.get_at_index(2, &bytes, &bump).err(),
+            Some(ErrorCode::IndexOutOfBounds)
+        );
+        assert_eq!(
+            slice.encode_msgpack(&bytes, &bump, &mut buffer).unwrap(),
+            [0x92, 0x91, 0x02, 0xa1, b'a']
+        );
+
+        // slices of slices are slices of the array, and out of bounds ranges are clamped
+        let slice: LazyValueRefPtr = bump.alloc(slice);
+        let mut nested = LazyValueRef::slice(slice, 1, 10).unwrap();
+        assert!(
+            matches!(&nested, LazyValueRef::Slice(SliceRef { array: a, start: 2, len: 1 }) if a.as_ptr() == array)
+        );
+        assert_eq!(
+            nested.get_at_index(0, &bytes, &bump).unwrap(),
+            &LazyValueRef::String(StringRef { ptr: 5, len: 1 })
+        );
+        let mut empty = LazyValueRef::slice(array, 5, 2).unwrap();
+        assert_eq!(empty.get_value_length(), Some(0));
+        assert_eq!(
+            empty.encode_msgpack(&bytes, &bump, &mut buffer).unwrap(),
+            [0x90]
+        );
+
+        // the elements of arrays with 16-bit lengths start after the length
+        let bytes = [0xdc, 0x00, 0x02, 0x01, 0x02];
+        let array: LazyValueRefPtr = bump.alloc(create_lazy_value(&bytes, &bump));
+        assert_eq!(
+            LazyValueRef::slice(array, 1, 2)
+                .unwrap()
+                .encode_msgpack(&bytes, &bump, &mut buffer)
+                .unwrap(),
+            [0x91, 0x02]
+        );
+
+        let mut number = LazyValueRef::Number(1.0);
+        assert_eq!(
+            LazyValueRef::slice(&mut number, 0, 1).err(),
+            Some(ErrorCode::NotAnArray)
+        );
+    }
+
     #[test]
     fn test_get_at_index_array() {
         let bytes = build_msgpack(|w| {
@@ -939,9 +1544,11 @@ mod tests {
         assert_eq!(
             value,
             LazyValueRef::Object(ObjectRef {
+                start_position: 0,
                 len: 2,
                 processed_elements: bumpalo::collections::Vec::new_in(&bump),
-                end_position_of_last_processed_element: 1
+                end_position_of_last_processed_element: 1,
+                keys_sorted: None,
             })
         );
 
@@ -950,7 +1557,7 @@ mod tests {
             .enumerate()
             .for_each(|(i, (k, v))| {
                 let property = value
-                    .get_object_property(k.as_bytes(), &bytes, &bump)
+                    .get_object_property(k.as_bytes(), false, &bytes, &bump)
                     .unwrap()
                     .unwrap();
                 assert_eq!(property, &LazyValueRef::Number(*v as f64));
@@ -971,9 +1578,11 @@ mod tests {
         let bump = Bump::new();
         let len = 2;
         let value = LazyValueRef::Object(ObjectRef {
+            start_position: 0,
             len,
             processed_elements: bumpalo::collections::Vec::new_in(&bump),
             end_position_of_last_processed_element: 0,
+            keys_sorted: None,
         });
         let nanbox = value.encode();
         let ptr = &value as *const _ as usize;
@@ -995,18 +1604,63 @@ mod tests {
         let mut value = create_lazy_value(&bytes, &bump);
 
         let property = value
-            .get_object_property(b"a", &bytes, &bump)
+            .get_object_property(b"a", false, &bytes, &bump)
             .unwrap()
             .unwrap();
         assert_eq!(property, &LazyValueRef::Number(1.0));
 
         let property = value
-            .get_object_property(b"b", &bytes, &bump)
+            .get_object_property(b"b", false, &bytes, &bump)
             .unwrap()
             .unwrap();
         assert_eq!(property.encode(), NanBox::number(2.0));
     }
 
+    #[test]
+    fn test_get_object_property_with_hint() {
+        let objects = [["a", "b"], ["a", "b"], ["b", "a"]].map(|keys| {
+            build_msgpack(|w| {
+                encode::write_map_len(w, 2)?;
+                keys.iter().enumerate().try_for_each(|(i, key)| {
+                    encode::write_str(w, key)?;
+                    encode::write_i32(w, i as i32)
+                })
+            })
+            .unwrap()
+        });
+
+        let bump = Bump::new();
+        let mut hint = PropertyHint::default();
+        for (bytes, expected_index) in objects.iter().zip([1, 1, 0]) {
+            let mut value = create_lazy_value(bytes, &bump);
+            let property = value
+                .get_object_property_with_hint(b"b", false, &mut hint, bytes, &bump)
+                .unwrap()
+                .unwrap();
+            assert_eq!(property, &LazyValueRef::Number(expected_index as f64));
+            assert_eq!(
+                hint,
+                PropertyHint {
+                    object_len: 2,
+                    index: expected_index
+                }
+            );
+        }
+
+        let mut value = create_lazy_value(&objects[0], &bump);
+        let property = value
+            .get_object_property_with_hint(b"c", false, &mut hint, &objects[0], &bump)
+            .unwrap();
+        assert_eq!(property, None);
+        assert_eq!(
+            hint,
+            PropertyHint {
+                object_len: 2,
+                index: 0
+            }
+        );
+    }
+
     #[test]
     fn test_get_object_property_not_found() {
         let bytes = build_msgpack(|w| {
@@ -1019,16 +1673,118 @@ mod tests {
         let bump = Bump::new();
         let mut value = create_lazy_value(&bytes, &bump);
 
-        let result = value.get_object_property(b"b", &bytes, &bump).unwrap();
+        let result = value
+            .get_object_property(b"b", false, &bytes, &bump)
+            .unwrap();
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_get_object_property_sorted_keys() {
+        let bytes = build_msgpack(|w| {
+            encode::write_map_len(w, 3)?;
+            ["a", "c", "e"].iter().enumerate().try_for_each(|(i, key)| {
+                encode::write_str(w, key)?;
+                encode::write_i32(w, i as i32)
+            })
+        })
+        .unwrap();
+        let bump = Bump::new();
+        let mut value = create_lazy_value(&bytes, &bump);
+        let processed_len = |value: &LazyValueRef| match value {
+            LazyValueRef::Object(object) => object.processed_elements.len(),
+            _ => unreachable!(),
+        };
+
+        // the lookup stops at "c", which is after "b"
+        let result = value
+            .get_object_property(b"b", true, &bytes, &bump)
+            .unwrap();
+        assert!(result.is_none());
+        assert_eq!(processed_len(&value), 2);
+
+        // "a" and "c" are found among the processed elements, and "b" is known to be missing
+        let result = value
+            .get_object_property(b"c", true, &bytes, &bump)
+            .unwrap();
+        assert_eq!(result, Some(&LazyValueRef::Number(1.0)));
+        let result = value
+            .get_object_property(b"b", true, &bytes, &bump)
+            .unwrap();
+        assert!(result.is_none());
+        assert_eq!(processed_len(&value), 2);
+
+        let result = value
+            .get_object_property(b"e", true, &bytes, &bump)
+            .unwrap();
+        assert_eq!(result, Some(&LazyValueRef::Number(2.0)));
+        let result = value
+            .get_object_property(b"f", true, &bytes, &bump)
+            .unwrap();
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_get_object_property_detects_sorted_keys() {
+        let build = |keys: &[String]| {
+            build_msgpack(|w| {
+                encode::write_map_len(w, keys.len() as u32)?;
+                keys.iter().enumerate().try_for_each(|(i, key)| {
+                    encode::write_str(w, key)?;
+                    encode::write_i32(w, i as i32)
+                })
+            })
+            .unwrap()
+        };
+        let sorted_keys = (0..BINARY_SEARCH_MIN_LEN)
+            .map(|i| format!("k{i:02}"))
+            .collect::<std::vec::Vec<_>>();
+        let mut unsorted_keys = sorted_keys.clone();
+        unsorted_keys.swap(0, 1);
+        let narrow_keys = sorted_keys[..BINARY_SEARCH_MIN_LEN - 1].to_vec();
+        let keys_sorted = |value: &LazyValueRef| match value {
+            LazyValueRef::Object(object) => object.keys_sorted,
+            _ => unreachable!(),
+        };
+
+        for (keys, expected) in [
+            (&sorted_keys, Some(true)),
+            (&unsorted_keys, Some(false)),
+            (&narrow_keys, None),
+        ] {
+            let bytes = build(keys);
+            let bump = Bump::new();
+            let mut value = create_lazy_value(&bytes, &bump);
+
+            // the keys are checked once all of them are processed by looking up a missing key
+            let result = value
+                .get_object_property(b"k", false, &bytes, &bump)
+                .unwrap();
+            assert!(result.is_none());
+            assert_eq!(keys_sorted(&value), None);
+
+            for (i, key) in keys.iter().enumerate() {
+                let result = value
+                    .get_object_property(key.as_bytes(), false, &bytes, &bump)
+                    .unwrap();
+                assert_eq!(result, Some(&LazyValueRef::Number(i as f64)), "{key}");
+            }
+            let result = value
+                .get_object_property(b"l", false, &bytes, &bump)
+                .unwrap();
+            assert!(result.is_none());
+            assert_eq!(keys_sorted(&value), expected);
+        }
+    }
+
     #[test]
     fn test_get_object_property_not_an_object() {
         let bytes = build_msgpack(|w| encode::write_array_len(w, 0).map(|_| ())).unwrap();
         let bump = Bump::new();
         let mut value = create_lazy_value(&bytes, &bump);
-        let error = value.get_object_property(b"a", &bytes, &bump).unwrap_err();
+        let error = value
+            .get_object_property(b"a", false, &bytes, &bump)
+            .unwrap_err();
         assert_eq!(error, ErrorCode::NotAnObject);
     }
 
@@ -1065,6 +1821,38 @@ mod tests {
         assert_eq!(error, ErrorCode::IndexOutOfBounds);
     }
 
+    #[test]
+    fn test_get_value_length_beyond_wasm_nan_box_length() {
+        // One more element than fits in the NaN-box length on 32-bit targets.
+        let len = (1 << 14) as usize;
+        let bytes = build_msgpack(|w| {
+            encode::write_array_len(w, len as u32)?;
+            for i in 0..len {
+                encode::write_u32(w, i as u32)?;
+            }
+            Ok::<_, encode::ValueWriteError<std::convert::Infallible>>(())
+        })
+        .unwrap();
+        let bump = Bump::new();
+        let value = create_lazy_value(&bytes, &bump);
+        assert_eq!(value.get_value_length(), Some(len));
+        assert_eq!(
+            value.encode(),
+            NanBox::array(
+                &value as *const _ as usize,
+                len.min(NanBox::MAX_VALUE_LENGTH)
+            )
+        );
+    }
+
+    #[test]
+    fn test_get_value_length_not_a_collection() {
+        let bytes = build_msgpack(encode::write_nil).unwrap();
+        let bump = Bump::new();
+        let value = create_lazy_value(&bytes, &bump);
+        assert_eq!(value.get_value_length(), None);
+    }
+
     #[test]
     fn test_get_key_at_index_not_an_object() {
         let bytes = build_msgpack(|w| encode::write_array_len(w, 0).map(|_| ())).unwrap();
@@ -1073,4 +1861,157 @@ mod tests {
         let error = value.get_key_at_index(0, &bytes, &bump).unwrap_err();
         assert_eq!(error, ErrorCode::NotAnObject);
     }
+
+    fn parse(bytes: &[u8]) -> Result<(), ErrorCode> {
+        let bump = Bump::new();
+        let (mut value, _) = LazyValueRef::new(bytes, 0, &bump)?;
+        value.finish_processing(bytes, &bump).map(|_| ())
+    }
+
+    #[test]
+    fn test_truncated_input_at_every_marker_type() {
+        let encodings: &[&[u8]] = &[
+            &[0xc0],                                                       // nil
+            &[0xc3],                                                       // true
+            &[0x01],                                                       // fixpos
+            &[0xff],                                                       // fixneg
+            &[0xcc, 0xc8],                                                 // u8
+            &[0xcd, 0x01, 0x00],                                           // u16
+            &[0xce, 0x00, 0x01, 0x00, 0x00],                               // u32
+            &[0xcf, 0, 0, 0, 0x01, 0, 0, 0, 0],                            // u64
+            &[0xd0, 0x9c],                                                 // i8
+            &[0xd1, 0xff, 0x00],                                           // i16
+            &[0xd2, 0xff, 0xff, 0x00, 0x00],                               // i32
+            &[0xd3, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0],                   // i64
+            &[0xca, 0x3f, 0x80, 0x00, 0x00],                               // f32
+            &[0xcb, 0x3f, 0xf0, 0, 0, 0, 0, 0, 0],                         // f64
+            &[0xa3, b'a', b'b', b'c'],                                     // fixstr
+            &[0xd9, 0x03, b'a', b'b', b'c'],                               // str8
+            &[0xda, 0x00, 0x03, b'a', b'b', b'c'],                         // str16
+            &[0xdb, 0x00, 0x00, 0x00, 0x03, b'a', b'b', b'c'],             // str32
+            &[0x92, 0x01, 0xa1, b'a'],                                     // fixarray
+            &[0xdc, 0x00, 0x02, 0x01, 0xa1, b'a'],                         // array16
+            &[0xdd, 0x00, 0x00, 0x00, 0x02, 0x01, 0xa1, b'a'],             // array32
+            &[0x81, 0xa1, b'a', 0x01],                                     // fixmap
+            &[0xde, 0x00, 0x01, 0xa1, b'a', 0x01],                         // map16
+            &[0xdf, 0x00, 0x00, 0x00, 0x01, 0xa1, b'a', 0x01],             // map32
+            &[0xc4, 0x02, 0x01, 0x02],                                     // bin8
+            &[0xc5, 0x00, 0x02, 0x01, 0x02],                               // bin16
+            &[0xc6, 0x00, 0x00, 0x00, 0x02, 0x01, 0x02],                   // bin32
+            &[0xd4, 0x01, 0x01],                                           // fixext1
+            &[0xd5, 0x01, 0x01, 0x02],                                     // fixext2
+            &[0xd6, 0x01, 0x01, 0x02, 0x03, 0x04],                         // fixext4
+            &[0xd7, 0x01, 1, 2, 3, 4, 5, 6, 7, 8],                         // fixext8
+            &[0xd8, 0x01, 1, 2, 3, 4, 5, 6, 7, 8, 1, 2, 3, 4, 5, 6, 7, 8], // fixext16
+            &[0xc7, 0x02, 0x01, 0x01, 0x02],                               // ext8
+            &[0xc8, 0x00, 0x02, 0x01, 0x01, 0x02],                         // ext16
+            &[0xc9, 0x00, 0x00, 0x00, 0x02, 0x01, 0x01, 0x02],             // ext32
+        ];
+
+        for bytes in encodings {
+            assert_eq!(parse(bytes), Ok(()), "{bytes:x?}");
+            for len in 0..bytes.len() {
+                assert_eq!(
+                    parse(&bytes[..len]),
+                    Err(ErrorCode::ReadError),
+                    "{:x?}",
+                    &bytes[..len]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_bytes() {
+        let bump = Bump::new();
+        for encoded in [
+            &[0xc4, 0x02, 0x00, 0xff][..],               // bin8
+            &[0xc5, 0x00, 0x02, 0x00, 0xff],             // bin16
+            &[0xc6, 0x00, 0x00, 0x00, 0x02, 0x00, 0xff], // bin32
+        ] {
+            let (mut value, end) = LazyValueRef::new(encoded, 0, &bump).unwrap();
+            let ptr = encoded.len() - 2;
+            assert_eq!(value, LazyValueRef::Bytes(StringRef { ptr, len: 2 }));
+            assert_eq!(end, Some(encoded.len()));
+            assert_eq!(value.get_value_length(), Some(2));
+            assert_eq!(
+                value.get_utf8_str_addr(encoded),
+                encoded[ptr..].as_ptr() as usize
+            );
+            // binary data is copied to the output as bin8, the smallest encoding that fits it
+            let mut buffer = Vec::new();
+            assert_eq!(
+                value.encode_msgpack(encoded, &bump, &mut buffer),
+                Ok(&[0xc4, 0x02, 0x00, 0xff][..])
+            );
+            assert_eq!(
+                value.finish_processing(encoded, &bump),
+                Ok(None),
+                "{encoded:x?}"
+            );
+
+            // the values after it in an array can be read
+            let bytes = [&[0x92][..], encoded, &[0x2a]].concat();
+            let (mut array, _) = LazyValueRef::new(&bytes, 0, &bump).unwrap();
+            assert_eq!(
+                array.get_at_index(1, &bytes, &bump).unwrap(),
+                &LazyValueRef::Number(42.0)
+            );
+        }
+    }
+
+    #[test]
+    fn test_unsupported_values_are_skipped() {
+        let bump = Bump::new();
+        for unsupported in [
+            &[0xd4, 0x01, 0xff][..],                                       // fixext1
+            &[0xd5, 0x01, 0xff, 0xff],                                     // fixext2
+            &[0xd6, 0x01, 0xff, 0xff, 0xff, 0xff],                         // fixext4
+            &[0xd7, 0x01, 0, 0, 0, 0, 0, 0, 0, 0],                         // fixext8
+            &[0xd8, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], // fixext16
+            &[0xc7, 0x01, 0x01, 0xff],                                     // ext8
+            &[0xc8, 0x00, 0x01, 0x01, 0xff],                               // ext16
+            &[0xc9, 0x00, 0x00, 0x00, 0x01, 0x01, 0xff],                   // ext32
+        ] {
+            let (value, end) = LazyValueRef::new(unsupported, 0, &bump).unwrap();
+            assert_eq!(value, LazyValueRef::Unsupported, "{unsupported:x?}");
+            assert_eq!(end, Some(unsupported.len()), "{unsupported:x?}");
+            assert_eq!(
+                value.encode().try_decode(),
+                Ok(shopify_function_wasm_api_core::read::ValueRef::Error(
+                    ErrorCode::UnsupportedValue
+                ))
+            );
+
+            // the values after it in an array can still be read
+            let bytes = [&[0x92][..], unsupported, &[0x2a]].concat();
+            let (mut array, _) = LazyValueRef::new(&bytes, 0, &bump).unwrap();
+            assert_eq!(
+                array.get_at_index(1, &bytes, &bump).unwrap(),
+                &LazyValueRef::Number(42.0)
+            );
+        }
+    }
+
+    #[test]
+    fn test_reserved_marker() {
+        assert_eq!(parse(&[0xc1]), Err(ErrorCode::ReservedMarker));
+        assert_eq!(parse(&[0x91, 0xc1]), Err(ErrorCode::ReservedMarker));
+    }
+
+    #[test]
+    fn test_collection_length_larger_than_input() {
+        let bump = Bump::new();
+        for bytes in [
+            &[0xdd, 0xff, 0xff, 0xff, 0xff][..],
+            &[0xdf, 0xff, 0xff, 0xff, 0xff][..],
+        ] {
+            let (mut value, _) = LazyValueRef::new(bytes, 0, &bump).unwrap();
+            assert_eq!(value.get_value_length(), Some(u32::MAX as usize));
+            assert_eq!(
+                value.get_at_index(0, bytes, &bump).unwrap_err(),
+                ErrorCode::ReadError
+            );
+        }
+    }
 }
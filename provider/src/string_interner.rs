@@ -1,10 +1,34 @@
 use core::ffi::c_void;
 use shopify_function_wasm_api_core::InternedStringId;
+use std::{
+    collections::HashMap,
+    hash::{BuildHasherDefault, DefaultHasher},
+};
 
-#[derive(Default)]
+#[cfg(not(target_family = "wasm"))]
+static NEXT_GENERATION: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// Stores interned strings as raw bytes. They are not required to be valid UTF-8, so lookups with
+/// them are exact byte comparisons.
 pub(crate) struct StringInterner {
     buf: Vec<u8>,
     spans: Vec<(usize, usize)>,
+    /// The first ID of each string, by the FNV-1a hash of its bytes, for finding strings that are
+    /// already interned. Strings are copied in after they're preallocated, so they're only added
+    /// the next time a string is looked up.
+    ids_by_hash: HashMap<u64, InternedStringId, BuildHasherDefault<DefaultHasher>>,
+    /// The number of strings that have been added to `ids_by_hash`.
+    hashed_len: usize,
+    /// Uniquely identifies this interner, so IDs interned with one interner (e.g. on another
+    /// thread when running tests) can be told apart from IDs interned with this one.
+    #[cfg(not(target_family = "wasm"))]
+    generation: usize,
+}
+
+impl Default for StringInterner {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl StringInterner {
@@ -12,9 +36,18 @@ impl StringInterner {
         Self {
             buf: Default::default(),
             spans: Default::default(),
+            ids_by_hash: Default::default(),
+            hashed_len: 0,
+            #[cfg(not(target_family = "wasm"))]
+            generation: NEXT_GENERATION.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
         }
     }
 
+    #[cfg(not(target_family = "wasm"))]
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
     pub fn preallocate(&mut self, len: usize) -> (InternedStringId, *const c_void) {
         let offset = self.buf.len();
         self.buf.resize(offset + len, 0);
@@ -23,10 +56,60 @@ impl StringInterner {
         (id, self.buf[offset..].as_ptr() as *const c_void)
     }
 
+    /// Add the strings copied in since the last lookup to `ids_by_hash`.
+    fn hash_pending(&mut self) {
+        while self.hashed_len < self.spans.len() {
+            let hash = crate::fnv1a_64(self.get(self.hashed_len));
+            self.ids_by_hash.entry(hash).or_insert(self.hashed_len);
+            self.hashed_len += 1;
+        }
+    }
+
+    /// Intern `bytes`, returning the ID of a string with the same bytes if there already is one.
+    pub fn intern(&mut self, bytes: &[u8]) -> InternedStringId {
+        self.hash_pending();
+        let hash = crate::fnv1a_64(bytes);
+        if let Some(&id) = self.ids_by_hash.get(&hash) {
+            // strings whose hashes collide are interned again rather than shared
+            if self.get(id) == bytes {
+                return id;
+            }
+        }
+        let (id, _) = self.preallocate(bytes.len());
+        let (offset, len) = self.spans[id];
+        self.buf[offset..offset + len].copy_from_slice(bytes);
+        self.ids_by_hash.entry(hash).or_insert(id);
+        self.hashed_len += 1;
+        id
+    }
+
+    /// The first ID of a known string with the same bytes as `id`, as strings the guest interns
+    /// aren't shared, so the same string can have more than one ID.
+    pub fn canonical_id(&mut self, id: InternedStringId) -> InternedStringId {
+        self.hash_pending();
+        let bytes = self.get(id);
+        match self.ids_by_hash.get(&crate::fnv1a_64(bytes)) {
+            Some(&first_id) if self.get(first_id) == bytes => first_id,
+            _ => id,
+        }
+    }
+
+    pub fn contains(&self, id: InternedStringId) -> bool {
+        id < self.spans.len()
+    }
+
     pub fn get(&self, id: InternedStringId) -> &[u8] {
         let (offset, len) = self.spans[id];
         &self.buf[offset..offset + len]
     }
+
+    /// Iterate over the interned strings and their IDs, in the order they were interned.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (InternedStringId, &[u8])> {
+        self.spans
+            .iter()
+            .enumerate()
+            .map(|(id, &(offset, len))| (id, &self.buf[offset..offset + len]))
+    }
 }
 
 #[cfg(test)]
@@ -52,6 +135,13 @@ mod tests {
         assert_eq!(interner.spans[1], (5, 10));
     }
 
+    #[test]
+    fn test_generation_is_unique() {
+        let interner = StringInterner::new();
+        let other = StringInterner::new();
+        assert_ne!(interner.generation(), other.generation());
+    }
+
     #[test]
     fn test_get() {
         let mut interner = StringInterner::new();
@@ -71,6 +161,48 @@ mod tests {
         assert_eq!(interner.get(id2), b"world!");
     }
 
+    #[test]
+    fn test_iter() {
+        let mut interner = StringInterner::new();
+        assert_eq!(interner.iter().len(), 0);
+
+        for s in [&b"foo"[..], b"", b"\xff"] {
+            let (_, ptr) = interner.preallocate(s.len());
+            unsafe { std::ptr::copy_nonoverlapping(s.as_ptr(), ptr as *mut u8, s.len()) };
+        }
+        assert_eq!(
+            interner.iter().collect::<Vec<_>>(),
+            [(0, &b"foo"[..]), (1, b""), (2, b"\xff")]
+        );
+    }
+
+    #[test]
+    fn test_intern() {
+        let mut interner = StringInterner::new();
+        let (preallocated, ptr) = interner.preallocate(3);
+        unsafe { std::ptr::copy_nonoverlapping(b"foo".as_ptr(), ptr as *mut u8, 3) };
+
+        // strings that were preallocated and copied in are found too
+        assert_eq!(interner.intern(b"foo"), preallocated);
+        let bar = interner.intern(b"bar");
+        assert_ne!(bar, preallocated);
+        assert_eq!(interner.intern(b"bar"), bar);
+        assert_eq!(interner.get(bar), b"bar");
+        let empty = interner.intern(b"");
+        assert_eq!(interner.intern(b""), empty);
+        assert_eq!(interner.iter().len(), 3);
+    }
+
+    #[test]
+    fn test_contains() {
+        let mut interner = StringInterner::new();
+        assert!(!interner.contains(0));
+
+        let (id, _) = interner.preallocate(5);
+        assert!(interner.contains(id));
+        assert!(!interner.contains(id + 1));
+    }
+
     #[test]
     #[should_panic]
     fn test_get_invalid_id() {
@@ -1,11 +1,42 @@
-use crate::{decorate_for_target, Context, DoubleUsize};
-use rmp::encode;
-use shopify_function_wasm_api_core::write::WriteResult;
+use crate::{decorate_for_target, read, Context, DoubleUsize};
+use rmp::{encode, Marker};
+#[cfg(all(target_family = "wasm", feature = "json-output"))]
+use shopify_function_wasm_api_core::host::OutputEncoding;
+use shopify_function_wasm_api_core::{
+    read::{NanBox, Val, ValueKind},
+    write::WriteResult,
+};
 
+#[cfg(all(feature = "zstd-output", any(target_family = "wasm", test)))]
+pub(crate) mod compression;
+#[cfg(any(feature = "json-output", test))]
+pub(crate) mod json;
 mod state;
 
 pub(crate) use state::State;
 
+/// A snapshot of the write side of the context, which the output can be rolled back to.
+pub(crate) struct OutputCheckpoint {
+    id: usize,
+    output_len: usize,
+    write_state: State,
+    write_parent_state_stack: Vec<State>,
+    length_marker_positions: Vec<usize>,
+    skip_nulls_objects: Vec<SkipNullsObject>,
+}
+
+/// An object being written that leaves out fields whose value is null.
+#[derive(Clone)]
+pub(crate) struct SkipNullsObject {
+    /// The number of values the object is nested in, which identifies it while it's written.
+    depth: usize,
+    /// The length of the object, less the fields that were left out.
+    length: usize,
+    /// The position in the output of the last string written in the object, which is the key of
+    /// the value written next.
+    key_position: usize,
+}
+
 impl Context {
     fn write_bool(&mut self, bool: bool) -> WriteResult {
         let result = self.write_state.write_non_string_scalar();
@@ -17,6 +48,9 @@ impl Context {
     }
 
     fn write_nil(&mut self) -> WriteResult {
+        if self.skip_null() {
+            return WriteResult::Ok;
+        }
         let result = self.write_state.write_non_string_scalar();
         if result != WriteResult::Ok {
             return result;
@@ -25,15 +59,84 @@ impl Context {
         WriteResult::Ok
     }
 
+    /// Remove the key of the null being written instead of writing it, if the current value is an
+    /// object that skips null fields and they're being skipped, returning whether it was removed.
+    fn skip_null(&mut self) -> bool {
+        if !self.skip_nulls {
+            return false;
+        }
+        let Some(object) = self.skip_nulls_objects.last_mut() else {
+            return false;
+        };
+        if object.depth != self.write_parent_state_stack.len()
+            || !self.write_state.expects_object_value()
+        {
+            return false;
+        }
+        // the key can't be removed if rolling back to a checkpoint would restore the output after it
+        if self
+            .output_checkpoints
+            .last()
+            .is_some_and(|checkpoint| checkpoint.output_len > object.key_position)
+        {
+            return false;
+        }
+        object.length -= 1;
+        self.write_state.skip_object_value();
+        // the key was written after the object's length marker, so it hasn't been read yet
+        self.output_bytes
+            .as_mut_vec()
+            .truncate(object.key_position - self.output_removed_len);
+        true
+    }
+
+    /// Record the position of a string about to be written, if it's written directly in an object
+    /// that skips null fields, as it may be a key. If it isn't written after all, its key position
+    /// is overwritten by the next key written before a null can be skipped.
+    fn record_string_position(&mut self) {
+        self.record_string_position_at(
+            self.output_removed_len + self.output_bytes.as_slice().len(),
+        );
+    }
+
+    /// Like [`Context::record_string_position`], for a string already written at `position`.
+    fn record_string_position_at(&mut self, position: usize) {
+        if let Some(object) = self.skip_nulls_objects.last_mut() {
+            if object.depth == self.write_parent_state_stack.len() {
+                object.key_position = position;
+            }
+        }
+    }
+
     fn write_i32(&mut self, int: i32) -> WriteResult {
         let result = self.write_state.write_non_string_scalar();
         if result != WriteResult::Ok {
             return result;
         }
+        // `write_sint` picks the smallest encoding, so small integers such as quantities take a
+        // single byte
         encode::write_sint(&mut self.output_bytes, int as i64).unwrap(); // infallible unwrap
         WriteResult::Ok
     }
 
+    fn write_i64(&mut self, int: i64) -> WriteResult {
+        let result = self.write_state.write_non_string_scalar();
+        if result != WriteResult::Ok {
+            return result;
+        }
+        encode::write_sint(&mut self.output_bytes, int).unwrap(); // infallible unwrap
+        WriteResult::Ok
+    }
+
+    fn write_u64(&mut self, int: u64) -> WriteResult {
+        let result = self.write_state.write_non_string_scalar();
+        if result != WriteResult::Ok {
+            return result;
+        }
+        encode::write_uint(&mut self.output_bytes, int).unwrap(); // infallible unwrap
+        WriteResult::Ok
+    }
+
     fn write_f64(&mut self, float: f64) -> WriteResult {
         let result = self.write_state.write_non_string_scalar();
         if result != WriteResult::Ok {
@@ -43,19 +146,169 @@ impl Context {
         WriteResult::Ok
     }
 
-    fn allocate_utf8_str(&mut self, len: usize) -> (WriteResult, *const u8) {
+    /// Write a value read from the input by copying its encoding, so arrays and objects are written
+    /// without reading them value by value.
+    fn write_input_value(&mut self, scope: Val) -> WriteResult {
+        let kind = NanBox::from_bits(scope).kind();
+        if kind == ValueKind::Null && self.skip_null() {
+            return WriteResult::Ok;
+        }
+        if kind == ValueKind::String {
+            self.record_string_position();
+        }
+        let Context {
+            input_bytes,
+            bump_allocator,
+            msgpack_buffer,
+            output_bytes,
+            write_state,
+            ..
+        } = self;
+        let Ok(encoded) = read::encode_msgpack(scope, input_bytes, bump_allocator, msgpack_buffer)
+        else {
+            return WriteResult::InvalidInputValue;
+        };
+        // strings can be object keys, while other values can't
+        let result = if kind == ValueKind::String {
+            write_state.write_string()
+        } else {
+            write_state.write_non_string_scalar()
+        };
+        if result != WriteResult::Ok {
+            return result;
+        }
+        output_bytes.as_mut_vec().extend_from_slice(encoded);
+        WriteResult::Ok
+    }
+
+    fn allocate_utf8_str(&mut self, len: usize) -> (WriteResult, *mut u8) {
+        self.record_string_position();
         let result = self.write_state.write_string();
         if result != WriteResult::Ok {
-            return (result, std::ptr::null());
+            return (result, std::ptr::null_mut());
         }
         encode::write_str_len(&mut self.output_bytes, len as u32).unwrap(); // infallible unwrap
-        let original_len = self.output_bytes.as_slice().len();
-        // fill in the new bytes with zeros; the trampoline will copy the string to overwrite them
-        self.output_bytes.as_mut_vec().resize(original_len + len, 0);
-        (
-            WriteResult::Ok,
-            self.output_bytes.as_slice()[original_len..].as_ptr(),
-        )
+        (WriteResult::Ok, self.allocate_output(len))
+    }
+
+    /// Like [`Context::allocate_utf8_str`], but for binary data, which can't be an object key.
+    fn allocate_bytes(&mut self, len: usize) -> (WriteResult, *mut u8) {
+        let result = self.write_state.write_non_string_scalar();
+        if result != WriteResult::Ok {
+            return (result, std::ptr::null_mut());
+        }
+        encode::write_bin_len(&mut self.output_bytes, len as u32).unwrap(); // infallible unwrap
+        (WriteResult::Ok, self.allocate_output(len))
+    }
+
+    /// Reserve `len` bytes at the end of the output for the guest to copy into, returning their
+    /// address. They aren't zero-filled first only to be overwritten, so they're only added to the
+    /// output by [`Context::commit_output`] once they've been copied.
+    fn allocate_output(&mut self, len: usize) -> *mut u8 {
+        self.commit_output();
+        let bytes = self.output_bytes.as_mut_vec();
+        bytes.reserve(len);
+        self.uncommitted_output_len = len;
+        bytes.spare_capacity_mut().as_mut_ptr().cast()
+    }
+
+    /// Add the bytes reserved by [`Context::allocate_output`] to the output. The guest copies into
+    /// them after the host call that reserved them returns, so this is called at the start of the
+    /// next call that writes or reads the output, by [`Context::with_output`].
+    pub(crate) fn commit_output(&mut self) {
+        // most calls have nothing to commit, so that's checked without a call
+        if self.uncommitted_output_len > 0 {
+            self.commit_uncommitted_output();
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn commit_uncommitted_output(&mut self) {
+        let len = std::mem::take(&mut self.uncommitted_output_len);
+        let bytes = self.output_bytes.as_mut_vec();
+        // SAFETY: the capacity was reserved by `allocate_output`, and the guest has copied into it,
+        // as it must before making another host call
+        unsafe { bytes.set_len(bytes.len() + len) };
+    }
+
+    /// Extend the output by `len` bytes for the guest to copy a MessagePack fragment into, which is
+    /// written as a value by [`Context::finish_msgpack`]. The space is always allocated, so the
+    /// guest can copy the fragment before the write state is checked.
+    fn allocate_msgpack(&mut self, len: usize) -> (WriteResult, *mut u8) {
+        (WriteResult::Ok, self.allocate_output(len))
+    }
+
+    /// Write the MessagePack fragment in the last `len` bytes of the output as one value, updating
+    /// the write state as if the value had been written by itself. The fragment is removed if it
+    /// isn't exactly one complete value, or the value can't be written where it is.
+    fn finish_msgpack(&mut self, len: usize) -> WriteResult {
+        let Some(start) = self.output_bytes.as_slice().len().checked_sub(len) else {
+            return WriteResult::InvalidMsgpack;
+        };
+        let fragment = &self.output_bytes.as_slice()[start..];
+        if msgpack_value_len(fragment) != Some(len) {
+            self.output_bytes.as_mut_vec().truncate(start);
+            return WriteResult::InvalidMsgpack;
+        }
+        let result = match Marker::from_u8(fragment[0]) {
+            // nulls may be left out of objects, which writing them by themselves takes care of
+            Marker::Null => {
+                self.output_bytes.as_mut_vec().truncate(start);
+                return self.write_nil();
+            }
+            // strings can be object keys, while other values can't
+            Marker::FixStr(_) | Marker::Str8 | Marker::Str16 | Marker::Str32 => {
+                self.record_string_position_at(self.output_removed_len + start);
+                self.write_state.write_string()
+            }
+            _ => self.write_state.write_non_string_scalar(),
+        };
+        if result != WriteResult::Ok {
+            self.output_bytes.as_mut_vec().truncate(start);
+        }
+        result
+    }
+
+    fn start_dynamic_utf8_str(&mut self, len_hint: usize) -> WriteResult {
+        self.record_string_position();
+        let marker_position = self.output_len();
+        let result = self
+            .write_state
+            .start_utf8_str(marker_position, &mut self.write_parent_state_stack);
+        if result != WriteResult::Ok {
+            return result;
+        }
+        // the maximum length always uses a 32-bit length marker, so any length can be patched in when
+        // the string is finished
+        encode::write_str_len(&mut self.output_bytes, u32::MAX).unwrap(); // infallible unwrap
+        self.output_bytes.as_mut_vec().reserve(len_hint);
+        WriteResult::Ok
+    }
+
+    fn allocate_utf8_str_chunk(&mut self, len: usize) -> (WriteResult, *mut u8) {
+        if self.write_state.utf8_str_marker_position().is_none() {
+            return (WriteResult::NotAString, std::ptr::null_mut());
+        }
+        (WriteResult::Ok, self.allocate_output(len))
+    }
+
+    fn finish_utf8_str(&mut self) -> WriteResult {
+        let marker_position = self.write_state.utf8_str_marker_position();
+        let result = self
+            .write_state
+            .finish_utf8_str(&mut self.write_parent_state_stack);
+        if result != WriteResult::Ok {
+            return result;
+        }
+        // the position is only missing if finishing failed above
+        let marker_position = marker_position.unwrap();
+        let len = self.output_len() - marker_position - 5;
+        // the marker can't have been read yet, so it's still in the buffer
+        let marker_index = marker_position - self.output_removed_len;
+        self.output_bytes.as_mut_vec()[marker_index + 1..marker_index + 5]
+            .copy_from_slice(&(len as u32).to_be_bytes());
+        WriteResult::Ok
     }
 
     fn start_object(&mut self, len: usize) -> WriteResult {
@@ -65,17 +318,43 @@ impl Context {
         if result != WriteResult::Ok {
             return result;
         }
+        if self.skip_nulls {
+            let marker_position = self.output_len();
+            self.length_marker_positions.push(marker_position);
+            self.skip_nulls_objects.push(SkipNullsObject {
+                depth: self.write_parent_state_stack.len(),
+                length: len,
+                key_position: marker_position,
+            });
+        }
         encode::write_map_len(&mut self.output_bytes, len as u32).unwrap(); // infallible unwrap
         WriteResult::Ok
     }
 
     fn finish_object(&mut self) -> WriteResult {
+        let depth = self.write_parent_state_stack.len();
         let result = self
             .write_state
             .finish_object(&mut self.write_parent_state_stack);
         if result != WriteResult::Ok {
             return result;
         }
+        if let Some(object) = self
+            .skip_nulls_objects
+            .pop_if(|object| object.depth == depth)
+        {
+            // as with dynamic-length arrays, the position was the last one pushed. The length can
+            // only have shrunk, so it's patched in with the encoding of the length it started with
+            let len = object.length;
+            let marker_position = self.length_marker_positions.pop().unwrap();
+            let marker_index = marker_position - self.output_removed_len;
+            let marker = &mut self.output_bytes.as_mut_vec()[marker_index..];
+            match marker[0] {
+                0xde => marker[1..3].copy_from_slice(&(len as u16).to_be_bytes()),
+                0xdf => marker[1..5].copy_from_slice(&(len as u32).to_be_bytes()),
+                _ => marker[0] = 0x80 | len as u8,
+            }
+        }
         WriteResult::Ok
     }
 
@@ -90,20 +369,164 @@ impl Context {
         WriteResult::Ok
     }
 
+    fn start_dynamic_array(&mut self) -> WriteResult {
+        let result = self
+            .write_state
+            .start_dynamic_array(&mut self.write_parent_state_stack);
+        if result != WriteResult::Ok {
+            return result;
+        }
+        self.length_marker_positions.push(self.output_len());
+        // the maximum length always uses a 32-bit length marker, so any length can be patched in when
+        // the array is finished
+        encode::write_array_len(&mut self.output_bytes, u32::MAX).unwrap(); // infallible unwrap
+        WriteResult::Ok
+    }
+
     fn finish_array(&mut self) -> WriteResult {
+        let dynamic_array_len = self.write_state.dynamic_array_len();
         let result = self
             .write_state
             .finish_array(&mut self.write_parent_state_stack);
         if result != WriteResult::Ok {
             return result;
         }
+        if let Some(len) = dynamic_array_len {
+            // dynamic-length arrays are finished in the reverse order they were started, and the
+            // position was pushed when this one was started
+            let marker_position = self.length_marker_positions.pop().unwrap();
+            let marker_index = marker_position - self.output_removed_len;
+            self.output_bytes.as_mut_vec()[marker_index + 1..marker_index + 5]
+                .copy_from_slice(&(len as u32).to_be_bytes());
+        }
+        WriteResult::Ok
+    }
+
+    fn discard_output(&mut self) -> WriteResult {
+        if self.output_read_len > 0 {
+            return WriteResult::OutputAlreadyRead;
+        }
+        self.output_bytes.as_mut_vec().clear();
+        self.write_state = State::Start;
+        self.write_parent_state_stack.clear();
+        self.length_marker_positions.clear();
+        self.skip_nulls_objects.clear();
+        self.output_checkpoints.clear();
+        WriteResult::Ok
+    }
+
+    fn checkpoint_output(&mut self) -> usize {
+        let id = self.next_output_checkpoint_id;
+        self.next_output_checkpoint_id += 1;
+        self.output_checkpoints.push(OutputCheckpoint {
+            id,
+            output_len: self.output_len(),
+            write_state: self.write_state.clone(),
+            write_parent_state_stack: self.write_parent_state_stack.clone(),
+            length_marker_positions: self.length_marker_positions.clone(),
+            skip_nulls_objects: self.skip_nulls_objects.clone(),
+        });
+        id
+    }
+
+    fn rollback_output(&mut self, id: usize) -> WriteResult {
+        let Ok(index) = self
+            .output_checkpoints
+            .binary_search_by_key(&id, |checkpoint| checkpoint.id)
+        else {
+            return WriteResult::UnknownCheckpoint;
+        };
+        // checkpoints created after this one refer to output that no longer exists
+        self.output_checkpoints.truncate(index + 1);
+        let checkpoint = &self.output_checkpoints[index];
+        // bytes written after a checkpoint aren't available to read, so they're still in the buffer
+        self.output_bytes
+            .as_mut_vec()
+            .truncate(checkpoint.output_len - self.output_removed_len);
+        self.write_state.clone_from(&checkpoint.write_state);
+        self.write_parent_state_stack
+            .clone_from(&checkpoint.write_parent_state_stack);
+        self.length_marker_positions
+            .clone_from(&checkpoint.length_marker_positions);
+        self.skip_nulls_objects
+            .clone_from(&checkpoint.skip_nulls_objects);
         WriteResult::Ok
     }
 
+    /// The position of the end of the output, including bytes removed after the host read them.
+    pub(crate) fn output_len(&self) -> usize {
+        self.output_removed_len + self.output_bytes.as_slice().len()
+    }
+
+    /// The output the host hasn't read yet.
+    pub(crate) fn unread_output(&self) -> &[u8] {
+        &self.output_bytes.as_slice()[self.output_read_len - self.output_removed_len..]
+    }
+
+    /// The number of bytes of output that won't change and that the host hasn't read yet.
+    ///
+    /// Bytes can still change if they're the length marker of a dynamic-length array or string that
+    /// isn't finished, or an unfinished object that skips null fields, whose length marker is
+    /// patched and whose null fields are removed. They can also change if they were written after a
+    /// checkpoint, as they're removed when rolling back to it. This includes the length markers that
+    /// would be unfinished after rolling back.
+    pub(crate) fn output_chunk_available(&self) -> usize {
+        #[cfg(all(target_family = "wasm", feature = "json-output"))]
+        if self.output_encoding != OutputEncoding::Msgpack {
+            // the output is converted to JSON all at once when it's finished
+            return 0;
+        }
+        let current = (
+            self.output_len(),
+            &self.length_marker_positions,
+            &self.write_state,
+        );
+        let checkpoints = self.output_checkpoints.iter().map(|checkpoint| {
+            (
+                checkpoint.output_len,
+                &checkpoint.length_marker_positions,
+                &checkpoint.write_state,
+            )
+        });
+        let end = std::iter::once(current)
+            .chain(checkpoints)
+            .map(|(output_len, length_marker_positions, write_state)| {
+                // markers are pushed as values are nested, so the first is the earliest, and a string
+                // can't contain an array or object
+                length_marker_positions
+                    .first()
+                    .copied()
+                    .or(write_state.utf8_str_marker_position())
+                    .unwrap_or(output_len)
+            })
+            .min()
+            .unwrap(); // infallible unwrap, as the current output is always included
+        end - self.output_read_len
+    }
+
+    /// Read `len` bytes of the output that are available, or `None` if fewer are available.
+    ///
+    /// The previously read bytes are removed from the buffer, as the host has copied them by the
+    /// time it calls the provider again.
+    pub(crate) fn read_output_chunk(&mut self, len: usize) -> Option<&[u8]> {
+        if len > self.output_chunk_available() {
+            return None;
+        }
+        self.output_bytes
+            .as_mut_vec()
+            .drain(..self.output_read_len - self.output_removed_len);
+        self.output_removed_len = self.output_read_len;
+        self.output_read_len += len;
+        Some(&self.output_bytes.as_slice()[..len])
+    }
+
     fn write_interned_utf8_str(
         &mut self,
         id: shopify_function_wasm_api_core::InternedStringId,
     ) -> WriteResult {
+        if !self.string_interner.contains(id) {
+            return WriteResult::UnknownInternedString;
+        }
         let string_data = self.string_interner.get(id);
         let len = string_data.len();
         let ptr = string_data.as_ptr();
@@ -111,14 +534,15 @@ impl Context {
         if result != WriteResult::Ok {
             return result;
         }
-        unsafe { std::ptr::copy_nonoverlapping(ptr, output_ptr as *mut u8, len) };
+        unsafe { std::ptr::copy_nonoverlapping(ptr, output_ptr, len) };
+        self.commit_output();
         WriteResult::Ok
     }
 }
 
 decorate_for_target! {
     fn shopify_function_output_new_bool(bool: u32) -> WriteResult {
-        Context::with_mut(|context| {
+        Context::with_output(|context| {
             context.write_bool(bool != 0)
         })
     }
@@ -126,7 +550,7 @@ decorate_for_target! {
 
 decorate_for_target! {
     fn shopify_function_output_new_null() -> WriteResult {
-        Context::with_mut(|context| {
+        Context::with_output(|context| {
             context.write_nil()
         })
     }
@@ -134,35 +558,115 @@ decorate_for_target! {
 
 decorate_for_target! {
     fn shopify_function_output_new_i32(int: i32) -> WriteResult {
-        Context::with_mut(|context| {
+        Context::with_output(|context| {
             context.write_i32(int)
         })
     }
 }
 
+decorate_for_target! {
+    fn shopify_function_output_new_i64(int: i64) -> WriteResult {
+        Context::with_output(|context| {
+            context.write_i64(int)
+        })
+    }
+}
+
+decorate_for_target! {
+    fn shopify_function_output_new_u64(int: u64) -> WriteResult {
+        Context::with_output(|context| {
+            context.write_u64(int)
+        })
+    }
+}
+
 decorate_for_target! {
     fn shopify_function_output_new_f64(float: f64) -> WriteResult {
-        Context::with_mut(|context| {
+        Context::with_output(|context| {
             context.write_f64(float)
         })
     }
 }
 
+decorate_for_target! {
+    /// Writes a value read from the input, copying it as it is encoded in the input.
+    fn shopify_function_output_new_input_value(scope: Val) -> WriteResult {
+        Context::with_output(|context| {
+            context.write_input_value(scope)
+        })
+    }
+}
+
 decorate_for_target! {
     /// The most significant 32 bits are the result, the least significant 32 bits are the pointer.
     fn shopify_function_output_new_utf8_str(len: usize) -> DoubleUsize {
-        Context::with_mut(|context| {
+        Context::with_output(|context| {
             let (result, ptr) = context.allocate_utf8_str(len);
             ((result as DoubleUsize) << usize::BITS) | ptr as DoubleUsize
         })
     }
 }
 
+decorate_for_target! {
+    /// The most significant 32 bits are the result, the least significant 32 bits are the pointer.
+    fn shopify_function_output_new_bytes(len: usize) -> DoubleUsize {
+        Context::with_output(|context| {
+            let (result, ptr) = context.allocate_bytes(len);
+            ((result as DoubleUsize) << usize::BITS) | ptr as DoubleUsize
+        })
+    }
+}
+
+decorate_for_target! {
+    /// The most significant 32 bits are the result, the least significant 32 bits are the pointer.
+    fn shopify_function_output_new_msgpack(len: usize) -> DoubleUsize {
+        Context::with_output(|context| {
+            let (result, ptr) = context.allocate_msgpack(len);
+            ((result as DoubleUsize) << usize::BITS) | ptr as DoubleUsize
+        })
+    }
+}
+
+decorate_for_target! {
+    /// Writes the fragment copied by `shopify_function_output_new_msgpack` as one value.
+    fn shopify_function_output_finish_msgpack(len: usize) -> WriteResult {
+        Context::with_output(|context| {
+            context.finish_msgpack(len)
+        })
+    }
+}
+
+decorate_for_target! {
+    fn shopify_function_output_new_dynamic_utf8_str(len_hint: usize) -> WriteResult {
+        Context::with_output(|context| {
+            context.start_dynamic_utf8_str(len_hint)
+        })
+    }
+}
+
+decorate_for_target! {
+    /// The most significant 32 bits are the result, the least significant 32 bits are the pointer.
+    fn shopify_function_output_write_utf8_str_chunk(len: usize) -> DoubleUsize {
+        Context::with_output(|context| {
+            let (result, ptr) = context.allocate_utf8_str_chunk(len);
+            ((result as DoubleUsize) << usize::BITS) | ptr as DoubleUsize
+        })
+    }
+}
+
+decorate_for_target! {
+    fn shopify_function_output_finish_utf8_str() -> WriteResult {
+        Context::with_output(|context| {
+            context.finish_utf8_str()
+        })
+    }
+}
+
 decorate_for_target! {
     fn shopify_function_output_new_object(
         len: usize,
     ) -> WriteResult {
-        Context::with_mut(|context| {
+        Context::with_output(|context| {
             context.start_object(len)
         })
     }
@@ -170,7 +674,7 @@ decorate_for_target! {
 
 decorate_for_target! {
     fn shopify_function_output_finish_object() -> WriteResult {
-        Context::with_mut(|context| {
+        Context::with_output(|context| {
             context.finish_object()
         })
     }
@@ -180,15 +684,23 @@ decorate_for_target! {
     fn shopify_function_output_new_array(
         len: usize,
     ) -> WriteResult {
-        Context::with_mut(|context| {
+        Context::with_output(|context| {
             context.start_array(len)
         })
     }
 }
 
+decorate_for_target! {
+    fn shopify_function_output_new_dynamic_array() -> WriteResult {
+        Context::with_output(|context| {
+            context.start_dynamic_array()
+        })
+    }
+}
+
 decorate_for_target! {
     fn shopify_function_output_finish_array() -> WriteResult {
-        Context::with_mut(|context| {
+        Context::with_output(|context| {
             context.finish_array()
         })
     }
@@ -198,28 +710,159 @@ decorate_for_target! {
     fn shopify_function_output_new_interned_utf8_str(
         id: shopify_function_wasm_api_core::InternedStringId,
     ) -> WriteResult {
-        Context::with_mut(|context| {
+        Context::with_output(|context| {
             context.write_interned_utf8_str(id)
         })
     }
 }
 
+decorate_for_target! {
+    fn shopify_function_output_discard() -> WriteResult {
+        Context::with_output(|context| {
+            context.discard_output()
+        })
+    }
+}
+
+decorate_for_target! {
+    fn shopify_function_output_checkpoint() -> usize {
+        Context::with_output(|context| {
+            context.checkpoint_output()
+        })
+    }
+}
+
+decorate_for_target! {
+    fn shopify_function_output_rollback(id: usize) -> WriteResult {
+        Context::with_output(|context| {
+            context.rollback_output(id)
+        })
+    }
+}
+
+decorate_for_target! {
+    /// Returns the number of bytes of msgpack output written so far, including bytes the host already read.
+    fn shopify_function_output_len() -> usize {
+        Context::with_output(|context| context.output_len())
+    }
+}
+
+decorate_for_target! {
+    /// Sets whether objects started afterwards leave out null fields, returning the previous setting.
+    fn shopify_function_output_set_skip_nulls(skip: u32) -> u32 {
+        Context::with_output(|context| {
+            std::mem::replace(&mut context.skip_nulls, skip != 0) as u32
+        })
+    }
+}
+
+/// Returns the length of the MessagePack value at the start of `bytes`, or `None` if it's truncated,
+/// has a string that isn't valid UTF-8, or has an extension value, which the output doesn't support.
+fn msgpack_value_len(bytes: &[u8]) -> Option<usize> {
+    fn take<'a>(rest: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+        let taken = rest.get(..len)?;
+        *rest = &rest[len..];
+        Some(taken)
+    }
+    fn take_len(rest: &mut &[u8], size: usize) -> Option<usize> {
+        let len = take(rest, size)?
+            .iter()
+            .fold(0usize, |len, byte| len << 8 | *byte as usize);
+        Some(len)
+    }
+
+    let mut rest = bytes;
+    // containers are counted by their elements rather than recursed into, so deeply nested
+    // fragments can't overflow the stack
+    let mut remaining: usize = 1;
+    while remaining > 0 {
+        remaining -= 1;
+        let (data_len, elements, is_str) = match Marker::from_u8(*take(&mut rest, 1)?.first()?) {
+            Marker::Null | Marker::True | Marker::False | Marker::FixPos(_) | Marker::FixNeg(_) => {
+                (0, 0, false)
+            }
+            Marker::U8 | Marker::I8 => (1, 0, false),
+            Marker::U16 | Marker::I16 => (2, 0, false),
+            Marker::U32 | Marker::I32 | Marker::F32 => (4, 0, false),
+            Marker::U64 | Marker::I64 | Marker::F64 => (8, 0, false),
+            Marker::FixStr(len) => (len as usize, 0, true),
+            Marker::Str8 => (take_len(&mut rest, 1)?, 0, true),
+            Marker::Str16 => (take_len(&mut rest, 2)?, 0, true),
+            Marker::Str32 => (take_len(&mut rest, 4)?, 0, true),
+            Marker::Bin8 => (take_len(&mut rest, 1)?, 0, false),
+            Marker::Bin16 => (take_len(&mut rest, 2)?, 0, false),
+            Marker::Bin32 => (take_len(&mut rest, 4)?, 0, false),
+            Marker::FixArray(len) => (0, len as usize, false),
+            Marker::Array16 => (0, take_len(&mut rest, 2)?, false),
+            Marker::Array32 => (0, take_len(&mut rest, 4)?, false),
+            Marker::FixMap(len) => (0, len as usize * 2, false),
+            Marker::Map16 => (0, take_len(&mut rest, 2)? * 2, false),
+            Marker::Map32 => (0, take_len(&mut rest, 4)?.checked_mul(2)?, false),
+            _ => return None,
+        };
+        let data = take(&mut rest, data_len)?;
+        if is_str && std::str::from_utf8(data).is_err() {
+            return None;
+        }
+        remaining = remaining.checked_add(elements)?;
+    }
+    Some(bytes.len() - rest.len())
+}
+
+/// Describe the value being written to the output, if one was started but not finished, or was
+/// finished but never returned with [`shopify_function_output_finalize_and_return_msgpack_bytes`].
+#[cfg(not(target_family = "wasm"))]
+pub fn unfinished_output_description() -> Option<String> {
+    Context::with_output(|context| match &context.write_state {
+        State::Start => None,
+        State::End if context.output_finalized => None,
+        State::End => Some(String::from("the value was finished but never finalized")),
+        state => Some(format!(
+            "{state:?}, nested {} level(s) deep",
+            context.write_parent_state_stack.len()
+        )),
+    })
+}
+
 #[cfg(not(target_family = "wasm"))]
 pub fn shopify_function_output_finalize_and_return_msgpack_bytes() -> (WriteResult, Vec<u8>) {
-    Context::with_mut(|context| {
-        let Context {
-            output_bytes,
-            write_state,
-            ..
-        } = &context;
-        if *write_state != State::End {
+    Context::with_output(|context| {
+        if context.write_state != State::End {
             return (WriteResult::ValueNotFinished, Vec::new());
         }
-        let bytes = output_bytes.as_slice().to_vec();
+        context.output_finalized = true;
+        let bytes = context.unread_output().to_vec();
         (WriteResult::Ok, bytes)
     })
 }
 
+/// The number of bytes of output that can be read with [`output_read_chunk`] while the function is
+/// running.
+#[cfg(not(target_family = "wasm"))]
+pub fn output_chunk_available() -> usize {
+    Context::with_output(|context| context.output_chunk_available())
+}
+
+/// Read `len` bytes of the output while the function is running, or `None` if fewer are available.
+/// The output returned when it's finalized doesn't include the bytes already read.
+#[cfg(not(target_family = "wasm"))]
+pub fn output_read_chunk(len: usize) -> Option<Vec<u8>> {
+    Context::with_output(|context| context.read_output_chunk(len).map(<[u8]>::to_vec))
+}
+
+/// Like [`shopify_function_output_finalize_and_return_msgpack_bytes`], but returns the output as
+/// pretty-printed JSON.
+#[cfg(all(not(target_family = "wasm"), feature = "json-output"))]
+pub fn shopify_function_output_finalize_and_return_json_bytes() -> (WriteResult, Vec<u8>) {
+    match shopify_function_output_finalize_and_return_msgpack_bytes() {
+        (WriteResult::Ok, bytes) => match json::from_msgpack(&bytes, true) {
+            Some(json) => (WriteResult::Ok, json),
+            None => (WriteResult::IoError, Vec::new()),
+        },
+        (result, _) => (result, Vec::new()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -234,7 +877,8 @@ mod tests {
         if result != WriteResult::Ok {
             return result;
         }
-        unsafe { std::ptr::copy_nonoverlapping(key.as_ptr(), ptr as *mut u8, key.len()) };
+        unsafe { std::ptr::copy_nonoverlapping(key.as_ptr(), ptr, key.len()) };
+        context.commit_output();
         WriteResult::Ok
     }
 
@@ -268,6 +912,62 @@ mod tests {
         assert_eq!(json, serde_json::json!(42));
     }
 
+    #[test]
+    fn test_write_context_i32_smallest_encoding() {
+        let cases: &[(i32, &[u8])] = &[
+            (0, &[0x00]),
+            (127, &[0x7f]),
+            (128, &[0xcc, 0x80]),
+            (255, &[0xcc, 0xff]),
+            (256, &[0xcd, 0x01, 0x00]),
+            (65_535, &[0xcd, 0xff, 0xff]),
+            (65_536, &[0xce, 0x00, 0x01, 0x00, 0x00]),
+            (i32::MAX, &[0xce, 0x7f, 0xff, 0xff, 0xff]),
+            (-1, &[0xff]),
+            (-32, &[0xe0]),
+            (-33, &[0xd0, 0xdf]),
+            (-128, &[0xd0, 0x80]),
+            (-129, &[0xd1, 0xff, 0x7f]),
+            (-32_768, &[0xd1, 0x80, 0x00]),
+            (-32_769, &[0xd2, 0xff, 0xff, 0x7f, 0xff]),
+            (i32::MIN, &[0xd2, 0x80, 0x00, 0x00, 0x00]),
+        ];
+        for &(int, bytes) in cases {
+            let mut context = Context::new(Vec::new());
+            assert_eq!(context.write_i32(int), WriteResult::Ok);
+            assert_eq!(context.output_bytes.as_slice(), bytes, "{int}");
+        }
+    }
+
+    #[test]
+    fn test_write_context_i64_and_u64() {
+        let cases: &[(i64, &[u8])] = &[
+            (1, &[0x01]),
+            (-1, &[0xff]),
+            (i32::MAX as i64 + 1, &[0xce, 0x80, 0x00, 0x00, 0x00]),
+            (
+                i64::MAX,
+                &[0xcf, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            ),
+            (
+                i64::MIN,
+                &[0xd3, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ),
+        ];
+        for &(int, bytes) in cases {
+            let mut context = Context::new(Vec::new());
+            assert_eq!(context.write_i64(int), WriteResult::Ok);
+            assert_eq!(context.output_bytes.as_slice(), bytes, "{int}");
+            assert_eq!(context.write_i64(int), WriteResult::ValueAlreadyWritten);
+        }
+
+        let mut context = Context::new(Vec::new());
+        assert_eq!(context.write_u64(u64::MAX), WriteResult::Ok);
+        assert_eq!(context.write_state, State::End);
+        let json = bytes_to_json(context.output_bytes.as_slice());
+        assert_eq!(json, serde_json::json!(u64::MAX));
+    }
+
     #[test]
     fn test_write_context_f64() {
         let mut context = Context::new(Vec::new());
@@ -285,15 +985,80 @@ mod tests {
         let (result, ptr) = context.allocate_utf8_str(s.len());
         assert_eq!(result, WriteResult::Ok);
         unsafe {
-            std::ptr::copy_nonoverlapping(s.as_ptr(), ptr as *mut u8, s.len());
+            std::ptr::copy_nonoverlapping(s.as_ptr(), ptr, s.len());
         }
+        context.commit_output();
         let (result, ptr) = context.allocate_utf8_str(s.len());
         assert_eq!(result, WriteResult::ValueAlreadyWritten);
-        assert_eq!(ptr, std::ptr::null());
+        assert_eq!(ptr, std::ptr::null_mut());
         let json = bytes_to_json(context.output_bytes.as_slice());
         assert_eq!(json, serde_json::json!(s));
     }
 
+    #[test]
+    fn test_write_context_bytes() {
+        let mut context = Context::new(Vec::new());
+        assert_eq!(context.start_object(1), WriteResult::Ok);
+        // binary data can't be a key
+        assert_eq!(context.allocate_bytes(1).0, WriteResult::ExpectedKey);
+        assert_eq!(write_key(&mut context, "hash"), WriteResult::Ok);
+        let (result, ptr) = context.allocate_bytes(2);
+        assert_eq!(result, WriteResult::Ok);
+        unsafe {
+            std::ptr::copy_nonoverlapping([0x00, 0xff].as_ptr(), ptr, 2);
+        }
+        context.commit_output();
+        assert_eq!(context.finish_object(), WriteResult::Ok);
+        assert_eq!(
+            context.output_bytes.as_slice(),
+            [&[0x81, 0xa4][..], b"hash", &[0xc4, 0x02, 0x00, 0xff]].concat()
+        );
+    }
+
+    #[test]
+    fn test_write_context_input_value() {
+        let input = serde_json::json!({"line": {"id": "1", "quantity": 2}, "note": "gift"});
+        Context::with_mut(|context| {
+            *context = Context::new(rmp_serde::to_vec(&input).unwrap());
+        });
+        let root = crate::read::shopify_function_input_get();
+        let get = |key: &str| {
+            crate::read::shopify_function_input_get_obj_prop(root, key.as_ptr() as usize, key.len())
+        };
+        let (line, note, missing) = (get("line"), get("note"), get("missing"));
+        Context::with_mut(|context| {
+            assert_eq!(context.start_object(3), WriteResult::Ok);
+            assert_eq!(context.write_input_value(line), WriteResult::ExpectedKey);
+            // strings from the input can be keys
+            assert_eq!(context.write_input_value(note), WriteResult::Ok);
+            assert_eq!(context.write_input_value(line), WriteResult::Ok);
+            assert_eq!(write_key(context, "line"), WriteResult::Ok);
+            assert_eq!(context.write_input_value(line), WriteResult::Ok);
+            assert_eq!(write_key(context, "note"), WriteResult::Ok);
+            assert_eq!(
+                context.write_input_value(missing),
+                WriteResult::InvalidInputValue
+            );
+            assert_eq!(context.write_input_value(note), WriteResult::Ok);
+            assert_eq!(context.finish_object(), WriteResult::Ok);
+            let json = bytes_to_json(context.output_bytes.as_slice());
+            assert_eq!(
+                json,
+                serde_json::json!({"gift": input["line"], "line": input["line"], "note": "gift"})
+            );
+        });
+    }
+
+    #[test]
+    fn test_write_context_unknown_interned_utf8_str() {
+        let mut context = Context::new(Vec::new());
+        assert_eq!(
+            context.write_interned_utf8_str(0),
+            WriteResult::UnknownInternedString
+        );
+        assert_eq!(context.write_state, State::Start);
+    }
+
     #[test]
     fn test_write_context_object() {
         let mut context = Context::new(Vec::new());
@@ -311,6 +1076,69 @@ mod tests {
         assert_eq!(json, serde_json::json!({ "key": false, "other_key": {} }));
     }
 
+    #[test]
+    fn test_write_context_object_skipping_nulls() {
+        assert_eq!(shopify_function_output_set_skip_nulls(1), 0);
+        assert_eq!(shopify_function_output_set_skip_nulls(0), 1);
+
+        let mut context = Context::new(Vec::new());
+        context.skip_nulls = true;
+        assert_eq!(context.start_object(3), WriteResult::Ok);
+        assert_eq!(write_key(&mut context, "note"), WriteResult::Ok);
+        assert_eq!(context.write_nil(), WriteResult::Ok);
+        assert_eq!(write_key(&mut context, "nested"), WriteResult::Ok);
+        assert_eq!(context.start_object(1), WriteResult::Ok);
+        assert_eq!(write_key(&mut context, "kept"), WriteResult::Ok);
+        // nulls written while nulls aren't skipped are kept
+        context.skip_nulls = false;
+        assert_eq!(context.write_nil(), WriteResult::Ok);
+        context.skip_nulls = true;
+        assert_eq!(context.finish_object(), WriteResult::Ok);
+        assert_eq!(write_key(&mut context, "other"), WriteResult::Ok);
+        assert_eq!(context.write_nil(), WriteResult::Ok);
+        assert_eq!(context.finish_object(), WriteResult::Ok);
+        assert_eq!(context.output_bytes.as_slice()[0], 0x81);
+        let json = bytes_to_json(context.output_bytes.as_slice());
+        assert_eq!(json, serde_json::json!({ "nested": { "kept": null } }));
+
+        // lengths encoded with more bytes are patched in with the same encoding
+        let mut context = Context::new(Vec::new());
+        context.skip_nulls = true;
+        assert_eq!(context.start_array(1), WriteResult::Ok);
+        assert_eq!(context.start_object(20), WriteResult::Ok);
+        for i in 0..20 {
+            assert_eq!(write_key(&mut context, &i.to_string()), WriteResult::Ok);
+            if i % 2 == 0 {
+                assert_eq!(context.write_nil(), WriteResult::Ok);
+            } else {
+                assert_eq!(context.write_i32(i), WriteResult::Ok);
+            }
+        }
+        assert_eq!(context.output_chunk_available(), 1);
+        assert_eq!(context.finish_object(), WriteResult::Ok);
+        assert_eq!(context.finish_array(), WriteResult::Ok);
+        assert_eq!(context.output_bytes.as_slice()[1..4], [0xde, 0, 10]);
+        let json = bytes_to_json(context.output_bytes.as_slice());
+        let fields = (1..20).step_by(2).map(|i| (i.to_string(), i.into()));
+        assert_eq!(
+            json,
+            serde_json::json!([serde_json::Map::from_iter(fields)])
+        );
+
+        // a null written after a checkpoint is kept, as rolling back would restore its key
+        let mut context = Context::new(Vec::new());
+        context.skip_nulls = true;
+        assert_eq!(context.start_object(2), WriteResult::Ok);
+        assert_eq!(write_key(&mut context, "a"), WriteResult::Ok);
+        context.checkpoint_output();
+        assert_eq!(context.write_nil(), WriteResult::Ok);
+        assert_eq!(write_key(&mut context, "b"), WriteResult::Ok);
+        assert_eq!(context.write_nil(), WriteResult::Ok);
+        assert_eq!(context.finish_object(), WriteResult::Ok);
+        let json = bytes_to_json(context.output_bytes.as_slice());
+        assert_eq!(json, serde_json::json!({ "a": null }));
+    }
+
     #[test]
     fn test_write_context_array() {
         let mut context = Context::new(Vec::new());
@@ -324,4 +1152,236 @@ mod tests {
         let json = bytes_to_json(context.output_bytes.as_slice());
         assert_eq!(json, serde_json::json!([true, []]));
     }
+
+    #[test]
+    fn test_write_context_dynamic_array() {
+        let mut context = Context::new(Vec::new());
+        assert_eq!(context.start_dynamic_array(), WriteResult::Ok);
+        assert_eq!(context.write_bool(true), WriteResult::Ok);
+        assert_eq!(context.start_dynamic_array(), WriteResult::Ok);
+        assert_eq!(context.finish_array(), WriteResult::Ok);
+        assert_eq!(context.start_array(1), WriteResult::Ok);
+        assert_eq!(context.start_dynamic_array(), WriteResult::Ok);
+        (0..100_000).for_each(|i| assert_eq!(context.write_i32(i), WriteResult::Ok));
+        assert_eq!(context.finish_array(), WriteResult::Ok);
+        assert_eq!(context.finish_array(), WriteResult::Ok);
+        assert_eq!(context.finish_array(), WriteResult::Ok);
+        assert_eq!(
+            context.start_dynamic_array(),
+            WriteResult::ValueAlreadyWritten
+        );
+        let json = bytes_to_json(context.output_bytes.as_slice());
+        let elements = (0..100_000).collect::<Vec<_>>();
+        assert_eq!(json, serde_json::json!([true, [], [elements]]));
+    }
+
+    #[test]
+    fn test_write_context_dynamic_utf8_str() {
+        let mut context = Context::new(Vec::new());
+        assert_eq!(context.start_array(2), WriteResult::Ok);
+        assert_eq!(context.start_dynamic_utf8_str(4), WriteResult::Ok);
+        assert_eq!(context.write_bool(true), WriteResult::ValueNotFinished);
+        for chunk in ["ab", "", "cd", "é"] {
+            let (result, ptr) = context.allocate_utf8_str_chunk(chunk.len());
+            assert_eq!(result, WriteResult::Ok);
+            unsafe { std::ptr::copy_nonoverlapping(chunk.as_ptr(), ptr, chunk.len()) };
+            context.commit_output();
+        }
+        assert_eq!(context.finish_utf8_str(), WriteResult::Ok);
+        assert_eq!(
+            context.allocate_utf8_str_chunk(1).0,
+            WriteResult::NotAString
+        );
+        assert_eq!(context.finish_utf8_str(), WriteResult::NotAString);
+        assert_eq!(context.start_dynamic_utf8_str(0), WriteResult::Ok);
+        assert_eq!(context.finish_utf8_str(), WriteResult::Ok);
+        assert_eq!(context.finish_array(), WriteResult::Ok);
+        let json = bytes_to_json(context.output_bytes.as_slice());
+        assert_eq!(json, serde_json::json!(["abcdé", ""]));
+    }
+
+    fn write_msgpack(context: &mut Context, fragment: &[u8]) -> WriteResult {
+        let (result, ptr) = context.allocate_msgpack(fragment.len());
+        assert_eq!(result, WriteResult::Ok);
+        unsafe { std::ptr::copy_nonoverlapping(fragment.as_ptr(), ptr, fragment.len()) };
+        context.commit_output();
+        context.finish_msgpack(fragment.len())
+    }
+
+    #[test]
+    fn test_write_context_msgpack() {
+        let metadata = rmp_serde::to_vec_named(&serde_json::json!({
+            "version": 2,
+            "tags": ["a", "é"],
+        }))
+        .unwrap();
+        let mut context = Context::new(Vec::new());
+        assert_eq!(context.start_object(3), WriteResult::Ok);
+        // a string fragment can be a key
+        assert_eq!(
+            write_msgpack(&mut context, &[0xa4, b'm', b'e', b't', b'a']),
+            WriteResult::Ok
+        );
+        assert_eq!(write_msgpack(&mut context, &metadata), WriteResult::Ok);
+        assert_eq!(write_key(&mut context, "count"), WriteResult::Ok);
+        assert_eq!(
+            write_msgpack(&mut context, &[0xcd, 0x01, 0x00]),
+            WriteResult::Ok
+        );
+        // other fragments can't be keys, and are removed when they can't be written
+        assert_eq!(
+            write_msgpack(&mut context, &[0x90]),
+            WriteResult::ExpectedKey
+        );
+        assert_eq!(write_key(&mut context, "valid"), WriteResult::Ok);
+        assert_eq!(write_msgpack(&mut context, &[0xc3]), WriteResult::Ok);
+        assert_eq!(context.finish_object(), WriteResult::Ok);
+        assert_eq!(
+            write_msgpack(&mut context, &[0xc3]),
+            WriteResult::ValueAlreadyWritten
+        );
+        let json = bytes_to_json(context.output_bytes.as_slice());
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "meta": { "version": 2, "tags": ["a", "é"] },
+                "count": 256,
+                "valid": true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_write_context_invalid_msgpack() {
+        let cases: &[&[u8]] = &[
+            // empty
+            &[],
+            // truncated string and array
+            &[0xa3, b'a', b'b'],
+            &[0x92, 0x01],
+            // trailing bytes
+            &[0x01, 0x02],
+            // string that isn't UTF-8
+            &[0xa1, 0xff],
+            // extension value
+            &[0xd4, 0x01, 0x00],
+            // array claiming more elements than there are bytes
+            &[0xdd, 0xff, 0xff, 0xff, 0xff, 0xc0],
+        ];
+        for fragment in cases {
+            let mut context = Context::new(Vec::new());
+            assert_eq!(context.start_array(1), WriteResult::Ok);
+            assert_eq!(
+                write_msgpack(&mut context, fragment),
+                WriteResult::InvalidMsgpack,
+                "{fragment:?}"
+            );
+            assert_eq!(write_msgpack(&mut context, &[0x07]), WriteResult::Ok);
+            assert_eq!(context.finish_array(), WriteResult::Ok);
+            let json = bytes_to_json(context.output_bytes.as_slice());
+            assert_eq!(json, serde_json::json!([7]));
+        }
+        // fragments longer than the output can't have been copied to it
+        let mut context = Context::new(Vec::new());
+        assert_eq!(context.finish_msgpack(1), WriteResult::InvalidMsgpack);
+    }
+
+    #[test]
+    fn test_write_context_msgpack_null_skipped() {
+        let mut context = Context::new(Vec::new());
+        context.skip_nulls = true;
+        assert_eq!(context.start_object(2), WriteResult::Ok);
+        assert_eq!(write_msgpack(&mut context, &[0xa1, b'a']), WriteResult::Ok);
+        assert_eq!(write_msgpack(&mut context, &[0xc0]), WriteResult::Ok);
+        assert_eq!(write_key(&mut context, "b"), WriteResult::Ok);
+        assert_eq!(write_msgpack(&mut context, &[0x01]), WriteResult::Ok);
+        assert_eq!(context.finish_object(), WriteResult::Ok);
+        let json = bytes_to_json(context.output_bytes.as_slice());
+        assert_eq!(json, serde_json::json!({ "b": 1 }));
+    }
+
+    #[test]
+    fn test_write_context_discard_output() {
+        let mut context = Context::new(Vec::new());
+        assert_eq!(context.start_object(1), WriteResult::Ok);
+        assert_eq!(write_key(&mut context, "key"), WriteResult::Ok);
+        assert_eq!(context.start_dynamic_array(), WriteResult::Ok);
+        assert_eq!(context.write_bool(true), WriteResult::Ok);
+        assert_eq!(context.discard_output(), WriteResult::Ok);
+        assert_eq!(context.write_state, State::Start);
+        assert!(context.output_bytes.as_slice().is_empty());
+        assert_eq!(context.start_array(1), WriteResult::Ok);
+        assert_eq!(context.write_bool(false), WriteResult::Ok);
+        assert_eq!(context.finish_array(), WriteResult::Ok);
+        let json = bytes_to_json(context.output_bytes.as_slice());
+        assert_eq!(json, serde_json::json!([false]));
+    }
+
+    #[test]
+    fn test_write_context_rollback_output() {
+        let mut context = Context::new(Vec::new());
+        assert_eq!(context.start_object(2), WriteResult::Ok);
+        assert_eq!(write_key(&mut context, "lines"), WriteResult::Ok);
+        assert_eq!(context.start_dynamic_array(), WriteResult::Ok);
+        assert_eq!(context.write_i32(1), WriteResult::Ok);
+        let checkpoint = context.checkpoint_output();
+        assert_eq!(context.start_array(2), WriteResult::Ok);
+        assert_eq!(context.write_i32(2), WriteResult::Ok);
+        let later_checkpoint = context.checkpoint_output();
+        assert_eq!(context.rollback_output(checkpoint), WriteResult::Ok);
+        assert_eq!(
+            context.rollback_output(later_checkpoint),
+            WriteResult::UnknownCheckpoint
+        );
+        assert_eq!(context.write_i32(3), WriteResult::Ok);
+        assert_eq!(context.finish_array(), WriteResult::Ok);
+        assert_eq!(write_key(&mut context, "count"), WriteResult::Ok);
+        assert_eq!(context.write_i32(2), WriteResult::Ok);
+        assert_eq!(context.finish_object(), WriteResult::Ok);
+        let json = bytes_to_json(context.output_bytes.as_slice());
+        assert_eq!(json, serde_json::json!({ "lines": [1, 3], "count": 2 }));
+
+        assert_eq!(context.rollback_output(checkpoint), WriteResult::Ok);
+        assert_eq!(context.discard_output(), WriteResult::Ok);
+        assert_eq!(
+            context.rollback_output(checkpoint),
+            WriteResult::UnknownCheckpoint
+        );
+    }
+
+    #[test]
+    fn test_write_context_read_output_chunk() {
+        let mut context = Context::new(Vec::new());
+        let mut output = Vec::new();
+        assert_eq!(context.start_array(3), WriteResult::Ok);
+        assert_eq!(context.write_bool(true), WriteResult::Ok);
+        assert_eq!(context.output_chunk_available(), 2);
+        output.extend(context.read_output_chunk(1).unwrap());
+        assert_eq!(context.read_output_chunk(2), None);
+        assert_eq!(context.start_dynamic_array(), WriteResult::Ok);
+        assert_eq!(context.write_i32(1), WriteResult::Ok);
+        assert_eq!(context.output_chunk_available(), 1);
+        assert_eq!(context.finish_array(), WriteResult::Ok);
+        assert_eq!(context.output_chunk_available(), 7);
+        output.extend(context.read_output_chunk(7).unwrap());
+        assert_eq!(context.start_dynamic_utf8_str(0), WriteResult::Ok);
+        let (result, ptr) = context.allocate_utf8_str_chunk(2);
+        assert_eq!(result, WriteResult::Ok);
+        unsafe { std::ptr::copy_nonoverlapping(b"ab".as_ptr(), ptr, 2) };
+        context.commit_output();
+        assert_eq!(context.output_chunk_available(), 0);
+        assert_eq!(context.discard_output(), WriteResult::OutputAlreadyRead);
+        assert_eq!(context.finish_utf8_str(), WriteResult::Ok);
+        assert_eq!(context.finish_array(), WriteResult::Ok);
+        output.extend(context.unread_output());
+        assert_eq!(bytes_to_json(&output), serde_json::json!([true, [1], "ab"]));
+
+        let mut context = Context::new(Vec::new());
+        assert_eq!(context.start_dynamic_array(), WriteResult::Ok);
+        assert_eq!(context.write_i32(1), WriteResult::Ok);
+        context.checkpoint_output();
+        assert_eq!(context.finish_array(), WriteResult::Ok);
+        // rolling back to the checkpoint would leave the array unfinished
+        assert_eq!(context.output_chunk_available(), 0);
+    }
 }
@@ -0,0 +1,54 @@
+//! Compresses the output returned by `finalize`, for providers built with the `zstd-output`
+//! feature.
+
+use shopify_function_wasm_api_core::host::OutputCompression;
+
+/// How hosts asked for the output to be compressed with `set_output_compression`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct OutputCompressionConfig {
+    pub(crate) compression: OutputCompression,
+    /// Output of at most this many bytes isn't compressed, as it's cheaper to copy than compress.
+    pub(crate) threshold: usize,
+}
+
+impl OutputCompressionConfig {
+    /// Compress `output` if it is longer than the threshold, returning the compressed output.
+    pub(crate) fn compress(&self, output: &[u8]) -> Option<Vec<u8>> {
+        match self.compression {
+            OutputCompression::None => None,
+            OutputCompression::Zstd => (output.len() > self.threshold).then(|| {
+                ruzstd::encoding::compress_to_vec(
+                    output,
+                    ruzstd::encoding::CompressionLevel::Fastest,
+                )
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_compress() {
+        let config = OutputCompressionConfig {
+            compression: OutputCompression::Zstd,
+            threshold: 16,
+        };
+        assert_eq!(config.compress(&[0x90; 16]), None);
+
+        let output = [0x90; 1024];
+        let compressed = config.compress(&output).unwrap();
+        assert!(compressed.len() < output.len());
+        let mut decompressed = Vec::new();
+        ruzstd::decoding::StreamingDecoder::new(&compressed[..])
+            .unwrap()
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, output);
+
+        assert_eq!(OutputCompressionConfig::default().compress(&output), None);
+    }
+}
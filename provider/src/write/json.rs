@@ -0,0 +1,321 @@
+//! Converts the msgpack output to pretty-printed or compact JSON, for providers built with the
+//! `json-output` feature.
+
+use rmp::Marker;
+use shopify_function_wasm_api_core::codec::FloatBuffer;
+use std::io::Write;
+
+/// An array or map whose elements are being written.
+struct Container {
+    len: u32,
+    remaining: u32,
+    is_map: bool,
+}
+
+struct Transcoder<'a> {
+    msgpack: &'a [u8],
+    position: usize,
+    output: Vec<u8>,
+    pretty: bool,
+}
+
+/// Convert a msgpack value to JSON, indented by two spaces if `pretty` or without whitespace
+/// otherwise, returning `None` if it isn't a single msgpack value that can be represented as JSON.
+/// Non-finite floats are written as `null`.
+pub(crate) fn from_msgpack(msgpack: &[u8], pretty: bool) -> Option<Vec<u8>> {
+    Transcoder {
+        msgpack,
+        position: 0,
+        output: Vec::with_capacity(if pretty {
+            msgpack.len() * 2
+        } else {
+            msgpack.len()
+        }),
+        pretty,
+    }
+    .transcode()
+}
+
+impl Transcoder<'_> {
+    fn transcode(mut self) -> Option<Vec<u8>> {
+        let mut containers: Vec<Container> = Vec::new();
+        loop {
+            let marker = Marker::from_u8(self.next_byte()?);
+            match marker {
+                Marker::Null => self.output.extend_from_slice(b"null"),
+                Marker::True => self.output.extend_from_slice(b"true"),
+                Marker::False => self.output.extend_from_slice(b"false"),
+                Marker::FixPos(int) => self.write_number(int),
+                Marker::FixNeg(int) => self.write_number(int),
+                Marker::U8 => self.write_be_number(u8::from_be_bytes)?,
+                Marker::U16 => self.write_be_number(u16::from_be_bytes)?,
+                Marker::U32 => self.write_be_number(u32::from_be_bytes)?,
+                Marker::U64 => self.write_be_number(u64::from_be_bytes)?,
+                Marker::I8 => self.write_be_number(i8::from_be_bytes)?,
+                Marker::I16 => self.write_be_number(i16::from_be_bytes)?,
+                Marker::I32 => self.write_be_number(i32::from_be_bytes)?,
+                Marker::I64 => self.write_be_number(i64::from_be_bytes)?,
+                Marker::F32 => {
+                    let float = f32::from_be_bytes(self.next_bytes()?);
+                    self.write_float(float.into())
+                }
+                Marker::F64 => {
+                    let float = f64::from_be_bytes(self.next_bytes()?);
+                    self.write_float(float)
+                }
+                Marker::FixStr(_) | Marker::Str8 | Marker::Str16 | Marker::Str32 => {
+                    self.write_string(marker)?
+                }
+                Marker::FixArray(_) | Marker::Array16 | Marker::Array32 => {
+                    let len = self.container_len(marker)?;
+                    self.output.push(b'[');
+                    containers.push(Container {
+                        len,
+                        remaining: len,
+                        is_map: false,
+                    });
+                }
+                Marker::FixMap(_) | Marker::Map16 | Marker::Map32 => {
+                    let len = self.container_len(marker)?;
+                    self.output.push(b'{');
+                    containers.push(Container {
+                        len,
+                        remaining: len,
+                        is_map: true,
+                    });
+                }
+                _ => return None,
+            }
+
+            // continue with the next element of the innermost unfinished container
+            loop {
+                let depth = containers.len();
+                let Some(container) = containers.last_mut() else {
+                    return (self.position == self.msgpack.len()).then_some(self.output);
+                };
+                if container.remaining == 0 {
+                    let container = containers.pop().unwrap();
+                    if container.len > 0 {
+                        self.write_newline_and_indent(depth - 1);
+                    }
+                    self.output.push(if container.is_map { b'}' } else { b']' });
+                    continue;
+                }
+                let is_first = container.remaining == container.len;
+                container.remaining -= 1;
+                let is_map = container.is_map;
+                if !is_first {
+                    self.output.push(b',');
+                }
+                self.write_newline_and_indent(depth);
+                if is_map {
+                    // keys are always strings
+                    let marker = Marker::from_u8(self.next_byte()?);
+                    if !matches!(
+                        marker,
+                        Marker::FixStr(_) | Marker::Str8 | Marker::Str16 | Marker::Str32
+                    ) {
+                        return None;
+                    }
+                    self.write_string(marker)?;
+                    self.output
+                        .extend_from_slice(if self.pretty { b": " } else { b":" });
+                }
+                break;
+            }
+        }
+    }
+
+    fn next_byte(&mut self) -> Option<u8> {
+        let byte = *self.msgpack.get(self.position)?;
+        self.position += 1;
+        Some(byte)
+    }
+
+    fn next_bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let bytes = self.msgpack.get(self.position..self.position + N)?;
+        self.position += N;
+        Some(bytes.try_into().unwrap())
+    }
+
+    fn len(&mut self, marker: Marker) -> Option<u32> {
+        match marker {
+            Marker::FixStr(len) | Marker::FixArray(len) | Marker::FixMap(len) => Some(len as u32),
+            Marker::Str8 => self.next_bytes().map(u8::from_be_bytes).map(u32::from),
+            Marker::Str16 | Marker::Array16 | Marker::Map16 => {
+                self.next_bytes().map(u16::from_be_bytes).map(u32::from)
+            }
+            Marker::Str32 | Marker::Array32 | Marker::Map32 => {
+                self.next_bytes().map(u32::from_be_bytes)
+            }
+            _ => None,
+        }
+    }
+
+    fn container_len(&mut self, marker: Marker) -> Option<u32> {
+        let len = self.len(marker)?;
+        // each element takes at least one byte, so longer containers are malformed
+        if len as usize > self.msgpack.len() - self.position {
+            return None;
+        }
+        Some(len)
+    }
+
+    fn write_newline_and_indent(&mut self, depth: usize) {
+        if !self.pretty {
+            return;
+        }
+        self.output.push(b'\n');
+        self.output.extend(std::iter::repeat_n(b' ', depth * 2));
+    }
+
+    fn write_number(&mut self, number: impl std::fmt::Display) {
+        write!(self.output, "{number}").unwrap(); // infallible unwrap
+    }
+
+    fn write_be_number<const N: usize, T: std::fmt::Display>(
+        &mut self,
+        from_be_bytes: fn([u8; N]) -> T,
+    ) -> Option<()> {
+        let number = from_be_bytes(self.next_bytes()?);
+        self.write_number(number);
+        Some(())
+    }
+
+    fn write_float(&mut self, float: f64) {
+        match FloatBuffer::new().format(float) {
+            Some(formatted) => self.output.extend_from_slice(formatted.as_bytes()),
+            None => self.output.extend_from_slice(b"null"),
+        }
+    }
+
+    fn write_string(&mut self, marker: Marker) -> Option<()> {
+        let len = self.len(marker)? as usize;
+        let bytes = self.msgpack.get(self.position..self.position + len)?;
+        self.position += len;
+        self.output.push(b'"');
+        for &byte in bytes {
+            match byte {
+                b'"' => self.output.extend_from_slice(b"\\\""),
+                b'\\' => self.output.extend_from_slice(b"\\\\"),
+                b'\n' => self.output.extend_from_slice(b"\\n"),
+                b'\r' => self.output.extend_from_slice(b"\\r"),
+                b'\t' => self.output.extend_from_slice(b"\\t"),
+                0x08 => self.output.extend_from_slice(b"\\b"),
+                0x0c => self.output.extend_from_slice(b"\\f"),
+                0..=0x1f => write!(self.output, "\\u{byte:04x}").unwrap(), // infallible unwrap
+                _ => self.output.push(byte),
+            }
+        }
+        self.output.push(b'"');
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcode(value: &serde_json::Value, pretty: bool) -> String {
+        let msgpack = rmp_serde::to_vec(value).unwrap();
+        String::from_utf8(from_msgpack(&msgpack, pretty).unwrap()).unwrap()
+    }
+
+    fn values() -> [serde_json::Value; 11] {
+        [
+            serde_json::json!(null),
+            serde_json::json!(true),
+            serde_json::json!(-1),
+            serde_json::json!(u64::MAX),
+            serde_json::json!(i64::MIN),
+            serde_json::json!(1.5),
+            serde_json::json!("a \"quoted\"\n\tstring \u{1} é"),
+            serde_json::json!([]),
+            serde_json::json!({}),
+            serde_json::json!([1, [2, []], {}]),
+            serde_json::json!({
+                "discounts": [{ "value": { "percentage": { "value": 10.0 } } }],
+                "empty": {},
+                "message": null,
+            }),
+        ]
+    }
+
+    #[test]
+    fn test_matches_serde_json_pretty() {
+        values().iter().for_each(|value| {
+            assert_eq!(
+                transcode(value, true),
+                serde_json::to_string_pretty(value).unwrap()
+            );
+        });
+    }
+
+    #[test]
+    fn test_matches_serde_json_compact() {
+        values().iter().for_each(|value| {
+            assert_eq!(
+                transcode(value, false),
+                serde_json::to_string(value).unwrap()
+            );
+        });
+    }
+
+    #[test]
+    fn test_floats() {
+        let mut msgpack = Vec::new();
+        rmp::encode::write_array_len(&mut msgpack, 4).unwrap();
+        rmp::encode::write_f64(&mut msgpack, f64::NAN).unwrap();
+        rmp::encode::write_f64(&mut msgpack, f64::INFINITY).unwrap();
+        rmp::encode::write_f64(&mut msgpack, 1e300).unwrap();
+        rmp::encode::write_f32(&mut msgpack, 0.5).unwrap();
+        let json = from_msgpack(&msgpack, false).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&json).unwrap(),
+            serde_json::json!([null, null, 1e300, 0.5])
+        );
+    }
+
+    #[test]
+    fn test_float_formatting() {
+        for (float, expected) in [
+            (1.0, "1.0"),
+            (0.1, "0.1"),
+            (1e300, "1e300"),
+            (1.5e-7, "1.5e-7"),
+        ] {
+            let mut msgpack = Vec::new();
+            rmp::encode::write_f64(&mut msgpack, float).unwrap();
+            assert_eq!(from_msgpack(&msgpack, false).unwrap(), expected.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_deeply_nested() {
+        let depth = 10_000;
+        let msgpack = vec![0x91; depth - 1]
+            .into_iter()
+            .chain([0x90])
+            .collect::<Vec<u8>>();
+        let json = from_msgpack(&msgpack, true).unwrap();
+        assert!(json.starts_with(b"[\n  [\n    ["));
+    }
+
+    #[test]
+    fn test_invalid() {
+        [
+            &[][..],
+            &[0x92, 0x01],
+            &[0x81, 0x01, 0x01],
+            &[0xdd, 0xff, 0xff, 0xff, 0xff],
+            &[0xa3, b'a'],
+            &[0xc4, 0x01, 0x00],
+            &[0x01, 0x02],
+        ]
+        .into_iter()
+        .for_each(|msgpack| {
+            assert_eq!(from_msgpack(msgpack, true), None, "{msgpack:?}");
+            assert_eq!(from_msgpack(msgpack, false), None, "{msgpack:?}");
+        });
+    }
+}
@@ -1,11 +1,15 @@
 use shopify_function_wasm_api_core::write::WriteResult;
 
-#[derive(Default, Debug, PartialEq, Eq)]
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub(crate) enum State {
     #[default]
     Start,
     Object(ObjectState),
     Array(ArrayState),
+    /// A string written in chunks, whose length marker is at `marker_position` in the output.
+    Utf8Str {
+        marker_position: usize,
+    },
     End,
 }
 
@@ -51,6 +55,7 @@ impl State {
                 );
                 WriteResult::Ok
             }
+            State::Utf8Str { .. } => WriteResult::ValueNotFinished,
             State::End => WriteResult::ValueAlreadyWritten,
         }
     }
@@ -63,6 +68,7 @@ impl State {
             }
             State::Object(object_state) => object_state.write_string(),
             State::Array(array_state) => array_state.write_value(),
+            State::Utf8Str { .. } => WriteResult::ValueNotFinished,
             State::End => WriteResult::ValueAlreadyWritten,
         }
     }
@@ -75,6 +81,7 @@ impl State {
             }
             State::Object(object_state) => object_state.write_non_string_value(),
             State::Array(array_state) => array_state.write_value(),
+            State::Utf8Str { .. } => WriteResult::ValueNotFinished,
             State::End => WriteResult::ValueAlreadyWritten,
         }
     }
@@ -92,6 +99,19 @@ impl State {
         }
     }
 
+    /// Whether the current value is an object whose key was written, so its value is next.
+    pub fn expects_object_value(&self) -> bool {
+        matches!(self, State::Object(object_state) if !object_state.num_inserted.is_multiple_of(2))
+    }
+
+    /// Remove the field of an object whose key was written, when its value is skipped.
+    pub fn skip_object_value(&mut self) {
+        if let State::Object(object_state) = self {
+            object_state.length -= 1;
+            object_state.num_inserted -= 1;
+        }
+    }
+
     pub fn start_array(
         &mut self,
         length: usize,
@@ -133,14 +153,22 @@ impl State {
                 );
                 WriteResult::Ok
             }
+            State::Utf8Str { .. } => WriteResult::ValueNotFinished,
             State::End => WriteResult::ValueAlreadyWritten,
         }
     }
 
+    /// Start an array whose length is not known until it is finished.
+    pub fn start_dynamic_array(&mut self, parent_state_stack: &mut Vec<State>) -> WriteResult {
+        self.start_array(ArrayState::DYNAMIC_LENGTH, parent_state_stack)
+    }
+
     pub fn finish_array(&mut self, parent_state_stack: &mut Vec<State>) -> WriteResult {
         match self {
             State::Array(array_state) => {
-                if array_state.num_inserted != array_state.length {
+                if array_state.length != ArrayState::DYNAMIC_LENGTH
+                    && array_state.num_inserted != array_state.length
+                {
                     return WriteResult::ArrayLengthError;
                 }
                 *self = parent_state_stack.pop().unwrap_or(State::End);
@@ -150,6 +178,50 @@ impl State {
         }
     }
 
+    /// The number of elements written so far, if the current value is a dynamic-length array.
+    pub fn dynamic_array_len(&self) -> Option<usize> {
+        match self {
+            State::Array(array_state) if array_state.length == ArrayState::DYNAMIC_LENGTH => {
+                Some(array_state.num_inserted)
+            }
+            _ => None,
+        }
+    }
+
+    /// Start a string that is written in chunks, once its length marker is written at
+    /// `marker_position` in the output.
+    pub fn start_utf8_str(
+        &mut self,
+        marker_position: usize,
+        parent_state_stack: &mut Vec<State>,
+    ) -> WriteResult {
+        let result = self.write_string();
+        if result != WriteResult::Ok {
+            return result;
+        }
+        self.swap_and_push(State::Utf8Str { marker_position }, parent_state_stack);
+        WriteResult::Ok
+    }
+
+    pub fn finish_utf8_str(&mut self, parent_state_stack: &mut Vec<State>) -> WriteResult {
+        match self {
+            State::Utf8Str { .. } => {
+                *self = parent_state_stack.pop().unwrap_or(State::End);
+                WriteResult::Ok
+            }
+            _ => WriteResult::NotAString,
+        }
+    }
+
+    /// The position of the length marker in the output, if the current value is a string being
+    /// written in chunks.
+    pub fn utf8_str_marker_position(&self) -> Option<usize> {
+        match self {
+            State::Utf8Str { marker_position } => Some(*marker_position),
+            _ => None,
+        }
+    }
+
     fn swap_and_push(&mut self, new_state: State, parent_state_stack: &mut Vec<State>) {
         let mut new_state = new_state;
         std::mem::swap(self, &mut new_state);
@@ -157,7 +229,7 @@ impl State {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct ObjectState {
     /// The length of the object. This is the number of key-value pairs.
     length: usize,
@@ -184,13 +256,16 @@ impl ObjectState {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct ArrayState {
     length: usize,
     num_inserted: usize,
 }
 
 impl ArrayState {
+    /// The length of dynamic-length arrays, which can't be exceeded.
+    const DYNAMIC_LENGTH: usize = usize::MAX;
+
     fn write_value(&mut self) -> WriteResult {
         if self.num_inserted >= self.length {
             return WriteResult::ArrayLengthError;
@@ -262,6 +337,29 @@ mod tests {
         assert_eq!(parent_state_stack, vec![]);
     }
 
+    #[test]
+    fn test_skip_object_value() {
+        let mut state = State::Start;
+        let mut parent_state_stack = Vec::new();
+        assert_eq!(
+            state.start_object(2, &mut parent_state_stack),
+            WriteResult::Ok
+        );
+        assert!(!state.expects_object_value());
+        assert_eq!(state.write_string(), WriteResult::Ok);
+        assert!(state.expects_object_value());
+        state.skip_object_value();
+        assert!(!state.expects_object_value());
+        assert_eq!(state.write_string(), WriteResult::Ok);
+        assert_eq!(state.write_non_string_scalar(), WriteResult::Ok);
+        assert_eq!(state.write_string(), WriteResult::ObjectLengthError);
+        assert_eq!(
+            state.finish_object(&mut parent_state_stack),
+            WriteResult::Ok
+        );
+        assert_eq!(state, State::End);
+    }
+
     #[test]
     fn test_array() {
         let mut state = State::Start;
@@ -296,4 +394,71 @@ mod tests {
         );
         assert_eq!(parent_state_stack, vec![]);
     }
+
+    #[test]
+    fn test_dynamic_array() {
+        let mut state = State::Start;
+        let mut parent_state_stack = Vec::new();
+        assert_eq!(
+            state.start_dynamic_array(&mut parent_state_stack),
+            WriteResult::Ok
+        );
+        assert_eq!(state.dynamic_array_len(), Some(0));
+        assert_eq!(state.write_non_string_scalar(), WriteResult::Ok);
+        assert_eq!(state.write_string(), WriteResult::Ok);
+        assert_eq!(
+            state.start_dynamic_array(&mut parent_state_stack),
+            WriteResult::Ok
+        );
+        assert_eq!(state.dynamic_array_len(), Some(0));
+        assert_eq!(state.finish_array(&mut parent_state_stack), WriteResult::Ok);
+        assert_eq!(state.dynamic_array_len(), Some(3));
+        assert_eq!(
+            state.start_array(1, &mut parent_state_stack),
+            WriteResult::Ok
+        );
+        assert_eq!(state.dynamic_array_len(), None);
+        assert_eq!(
+            state.finish_array(&mut parent_state_stack),
+            WriteResult::ArrayLengthError
+        );
+        assert_eq!(state.write_non_string_scalar(), WriteResult::Ok);
+        assert_eq!(state.finish_array(&mut parent_state_stack), WriteResult::Ok);
+        assert_eq!(state.finish_array(&mut parent_state_stack), WriteResult::Ok);
+        assert_eq!(state, State::End);
+        assert_eq!(parent_state_stack, vec![]);
+    }
+
+    #[test]
+    fn test_utf8_str() {
+        let mut state = State::Start;
+        let mut parent_state_stack = Vec::new();
+        assert_eq!(
+            state.start_array(2, &mut parent_state_stack),
+            WriteResult::Ok
+        );
+        assert_eq!(
+            state.start_utf8_str(1, &mut parent_state_stack),
+            WriteResult::Ok
+        );
+        assert_eq!(state.utf8_str_marker_position(), Some(1));
+        assert_eq!(state.write_string(), WriteResult::ValueNotFinished);
+        assert_eq!(
+            state.finish_array(&mut parent_state_stack),
+            WriteResult::NotAnArray
+        );
+        assert_eq!(
+            state.finish_utf8_str(&mut parent_state_stack),
+            WriteResult::Ok
+        );
+        assert_eq!(state.utf8_str_marker_position(), None);
+        assert_eq!(
+            state.finish_utf8_str(&mut parent_state_stack),
+            WriteResult::NotAString
+        );
+        assert_eq!(state.write_string(), WriteResult::Ok);
+        assert_eq!(state.finish_array(&mut parent_state_stack), WriteResult::Ok);
+        assert_eq!(state, State::End);
+        assert_eq!(parent_state_stack, vec![]);
+    }
 }
@@ -0,0 +1,343 @@
+//! Types for the function's input, from the selections of its input query.
+
+use std::collections::{BTreeSet, HashMap};
+
+use graphql_parser::query::{
+    Definition, Document, Field, FragmentDefinition, OperationDefinition, Selection, SelectionSet,
+    TypeCondition,
+};
+
+use crate::{
+    rust,
+    schema::{self, Kind, Schema},
+    CodegenOptions, Error,
+};
+
+/// The name of the root struct when the query has no name.
+const DEFAULT_ROOT_NAME: &str = "Input";
+
+pub(crate) fn generate<'s, 'q>(
+    schema: &'s Schema<'s>,
+    query: &'q Document<'q, &'q str>,
+    options: &'s CodegenOptions,
+) -> Result<String, Error> {
+    let mut operation = None;
+    let mut fragments = HashMap::new();
+    for definition in &query.definitions {
+        match definition {
+            Definition::Operation(OperationDefinition::Query(query)) => {
+                if operation.is_some() {
+                    return Err(Error::Query("only one query can be defined".to_string()));
+                }
+                operation = Some((query.name, &query.selection_set));
+            }
+            Definition::Operation(OperationDefinition::SelectionSet(selection_set)) => {
+                if operation.is_some() {
+                    return Err(Error::Query("only one query can be defined".to_string()));
+                }
+                operation = Some((None, selection_set));
+            }
+            Definition::Operation(_) => {
+                return Err(Error::Query(
+                    "the input query can't be a mutation or subscription".to_string(),
+                ))
+            }
+            Definition::Fragment(fragment) => {
+                fragments.insert(fragment.name, fragment);
+            }
+        }
+    }
+    let Some((name, selection_set)) = operation else {
+        return Err(Error::Query("no query is defined".to_string()));
+    };
+
+    let mut generator = Generator {
+        schema,
+        options,
+        fragments,
+        code: String::new(),
+        names: BTreeSet::new(),
+        enums: BTreeSet::new(),
+    };
+    let name = name.map_or_else(|| DEFAULT_ROOT_NAME.to_string(), rust::pascal_case);
+    generator.composite(&name, schema.query_type, &[selection_set])?;
+    for name in std::mem::take(&mut generator.enums) {
+        generator.enumeration(name)?;
+    }
+    Ok(generator.code)
+}
+
+struct Generator<'s, 'q> {
+    schema: &'s Schema<'s>,
+    options: &'s CodegenOptions,
+    fragments: HashMap<&'q str, &'q FragmentDefinition<'q, &'q str>>,
+    code: String,
+    /// The names of the generated types, which must be unique.
+    names: BTreeSet<String>,
+    /// The enums used by the generated structs, generated after them.
+    enums: BTreeSet<&'s str>,
+}
+
+/// The fields selected under one key, which GraphQL merges.
+struct Selected<'q> {
+    key: &'q str,
+    fields: Vec<&'q Field<'q, &'q str>>,
+}
+
+impl<'s, 'q> Generator<'s, 'q> {
+    fn add_name(&mut self, name: &str) -> Result<(), Error> {
+        if self.names.insert(name.to_string()) {
+            Ok(())
+        } else {
+            Err(Error::Query(format!(
+                "more than one input type would be named `{name}`, use an alias to rename one of \
+                 the fields"
+            )))
+        }
+    }
+
+    /// The fields selected on objects of type `ty`, including those of fragments that apply to them.
+    fn collect(
+        &self,
+        ty: &str,
+        selection_set: &'q SelectionSet<'q, &'q str>,
+        selected: &mut Vec<Selected<'q>>,
+    ) -> Result<(), Error> {
+        for selection in &selection_set.items {
+            let (condition, selection_set) = match selection {
+                Selection::Field(field) => {
+                    let key = field.alias.unwrap_or(field.name);
+                    match selected.iter_mut().find(|selected| selected.key == key) {
+                        Some(selected) => selected.fields.push(field),
+                        None => selected.push(Selected {
+                            key,
+                            fields: vec![field],
+                        }),
+                    }
+                    continue;
+                }
+                Selection::InlineFragment(fragment) => (
+                    fragment
+                        .type_condition
+                        .as_ref()
+                        .map(|TypeCondition::On(condition)| *condition),
+                    &fragment.selection_set,
+                ),
+                Selection::FragmentSpread(spread) => {
+                    let fragment = self.fragment(spread.fragment_name)?;
+                    let TypeCondition::On(condition) = fragment.type_condition;
+                    (Some(condition), &fragment.selection_set)
+                }
+            };
+            if condition.is_none_or(|condition| self.schema.condition_applies(condition, ty)) {
+                self.collect(ty, selection_set, selected)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn fragment(&self, name: &str) -> Result<&'q FragmentDefinition<'q, &'q str>, Error> {
+        self.fragments
+            .get(name)
+            .copied()
+            .ok_or_else(|| Error::Query(format!("fragment `{name}` is not defined")))
+    }
+
+    /// The object types with fragments in the selection sets, in the order they're first selected.
+    fn fragment_types(
+        &self,
+        selection_sets: &[&'q SelectionSet<'q, &'q str>],
+    ) -> Result<Vec<&'q str>, Error> {
+        let mut types = Vec::new();
+        for selection_set in selection_sets {
+            for selection in &selection_set.items {
+                let condition = match selection {
+                    Selection::Field(_) => continue,
+                    Selection::InlineFragment(fragment) => match &fragment.type_condition {
+                        Some(TypeCondition::On(condition)) => *condition,
+                        None => continue,
+                    },
+                    Selection::FragmentSpread(spread) => {
+                        let TypeCondition::On(condition) =
+                            self.fragment(spread.fragment_name)?.type_condition;
+                        condition
+                    }
+                };
+                if self.schema.is_object(condition) && !types.contains(&condition) {
+                    types.push(condition);
+                }
+            }
+        }
+        Ok(types)
+    }
+
+    /// Generate the type read from objects of type `ty` with the selection sets, named `name`.
+    fn composite(
+        &mut self,
+        name: &str,
+        ty: &str,
+        selection_sets: &[&'q SelectionSet<'q, &'q str>],
+    ) -> Result<(), Error> {
+        self.add_name(name)?;
+        let fragment_types = if self.schema.is_abstract(ty) {
+            self.fragment_types(selection_sets)?
+        } else {
+            Vec::new()
+        };
+        if fragment_types.is_empty() {
+            return self.object(name, ty, selection_sets);
+        }
+
+        let mut selected = Vec::new();
+        for selection_set in selection_sets {
+            self.collect(ty, selection_set, &mut selected)?;
+        }
+        let Some(typename) = selected.iter().find(|selected| {
+            selected
+                .fields
+                .iter()
+                .any(|field| field.name == "__typename")
+        }) else {
+            return Err(Error::Query(format!(
+                "the selection of `{name}` has fragments on types of `{ty}`, so it must select \
+                 `__typename`"
+            )));
+        };
+        let typename_key = typename.key;
+        let mut variants = Vec::new();
+        for variant_type in fragment_types {
+            let variant_name = format!("{name}{}", rust::pascal_case(variant_type));
+            self.add_name(&variant_name)?;
+            self.object(&variant_name, variant_type, selection_sets)?;
+            variants.push(rust::Variant {
+                ident: rust::pascal_case(variant_type),
+                key: variant_type,
+                ty: variant_name,
+                description: self.schema.description(variant_type),
+            });
+        }
+        rust::write_input_abstract_enum(
+            &mut self.code,
+            name,
+            self.schema.description(ty),
+            typename_key,
+            &variants,
+        );
+        Ok(())
+    }
+
+    /// Generate the struct read from objects of type `ty` with the selection sets, named `name`.
+    fn object(
+        &mut self,
+        name: &str,
+        ty: &str,
+        selection_sets: &[&'q SelectionSet<'q, &'q str>],
+    ) -> Result<(), Error> {
+        let mut selected = Vec::new();
+        for selection_set in selection_sets {
+            self.collect(ty, selection_set, &mut selected)?;
+        }
+        let mut fields = Vec::with_capacity(selected.len());
+        for Selected {
+            key,
+            fields: selected,
+        } in selected
+        {
+            let field = selected[0];
+            let (rust_type, description) = if field.name == "__typename" {
+                ("::std::string::String".to_string(), None)
+            } else {
+                let definition = self.schema.field(ty, field.name).ok_or_else(|| {
+                    Error::Query(format!("type `{ty}` has no field `{}`", field.name))
+                })?;
+                let named = schema::named_type(&definition.field_type);
+                let named = match self.schema.kind(named)? {
+                    Kind::Scalar => self.options.scalar_type(named)?,
+                    Kind::Enum(_) => {
+                        self.enums.insert(named);
+                        named.to_string()
+                    }
+                    Kind::Composite => {
+                        let selection_sets: Vec<_> =
+                            selected.iter().map(|field| &field.selection_set).collect();
+                        if selection_sets.iter().all(|set| set.items.is_empty()) {
+                            return Err(Error::Query(format!(
+                                "field `{}` of type `{ty}` must have a selection",
+                                field.name
+                            )));
+                        }
+                        let field_name = format!("{name}{}", rust::pascal_case(key));
+                        self.composite(&field_name, named, &selection_sets)?;
+                        field_name
+                    }
+                    Kind::InputObject(_) => {
+                        return Err(Error::Schema(format!(
+                            "field `{}` of type `{ty}` is an input object",
+                            field.name
+                        )))
+                    }
+                };
+                let mut rust_type = schema::rust_type(&definition.field_type, &named);
+                // fields that may be skipped are missing from the input
+                let conditional = selected.iter().any(|field| {
+                    field
+                        .directives
+                        .iter()
+                        .any(|directive| matches!(directive.name, "include" | "skip"))
+                });
+                if conditional && !rust_type.starts_with("::core::option::Option<") {
+                    rust_type = format!("::core::option::Option<{rust_type}>");
+                }
+                (rust_type, definition.description.as_deref())
+            };
+            fields.push(rust::Field {
+                ident: rust::field_ident(key),
+                key,
+                ty: rust_type,
+                description,
+            });
+        }
+        rust::write_input_struct(&mut self.code, name, self.schema.description(ty), &fields);
+        Ok(())
+    }
+
+    fn enumeration(&mut self, name: &str) -> Result<(), Error> {
+        let Kind::Enum(enumeration) = self.schema.kind(name)? else {
+            unreachable!("only enums are added to `enums`");
+        };
+        self.add_name(name)?;
+        let values = enum_values(enumeration)?;
+        rust::write_input_enum(
+            &mut self.code,
+            name,
+            enumeration.description.as_deref(),
+            &values,
+        );
+        Ok(())
+    }
+}
+
+/// The values of an enum, whose identifiers must be unique and distinct from `Other`.
+pub(crate) fn enum_values<'a>(
+    enumeration: &'a graphql_parser::schema::EnumType<'a, &'a str>,
+) -> Result<Vec<rust::EnumValue<'a>>, Error> {
+    let mut idents = BTreeSet::from(["Other".to_string()]);
+    enumeration
+        .values
+        .iter()
+        .map(|value| {
+            let ident = rust::pascal_case(value.name);
+            if !idents.insert(ident.clone()) {
+                return Err(Error::Schema(format!(
+                    "value `{}` of enum `{}` would be named `{ident}`, which is already used",
+                    value.name, enumeration.name
+                )));
+            }
+            Ok(rust::EnumValue {
+                ident,
+                value: value.name,
+                description: value.description.as_deref(),
+            })
+        })
+        .collect()
+}
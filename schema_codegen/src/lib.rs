@@ -0,0 +1,153 @@
+//! # Shopify Function schema codegen
+//!
+//! Generates the input and output types of a function from its target's GraphQL schema and input
+//! query, with `Deserialize` and `Serialize` implementations for `shopify_function_wasm_api`, so
+//! they don't have to be written by hand or kept in sync with the schema.
+//!
+//! The generated code has two modules:
+//!
+//! - `input` has a struct for each selection set of the input query, named after the path to it,
+//!   such as `InputCartLines` for `cart { lines { ... } }` in `query Input`. Each struct reads all
+//!   of its fields with one cached shape. Selections on interfaces and unions with fragments on
+//!   object types are enums with a variant for each of them, chosen by the object's `__typename`,
+//!   which the selection must include.
+//! - `output` has a struct for each input object reachable from the arguments of the schema's
+//!   mutation fields, named after the input object, whose keys are interned. Input objects with
+//!   the `@oneOf` directive are enums with a variant for each field.
+//!
+//! Enums are generated in the module they're used in, and input enums have an `Other` variant for
+//! values added to the schema later. Custom scalars are read and written as strings, except for
+//! `JSON`, which has to be given a type in [`CodegenOptions::scalars`].
+//!
+//! Types are generated from a build script with [`generate_file`]:
+//!
+//! ```no_run
+//! // in the `main` of build.rs
+//! println!("cargo:rerun-if-changed=schema.graphql");
+//! println!("cargo:rerun-if-changed=src/run.graphql");
+//! let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("schema.rs");
+//! shopify_function_schema_codegen::generate_file(
+//!     "schema.graphql",
+//!     "src/run.graphql",
+//!     out,
+//!     &Default::default(),
+//! )
+//! .unwrap();
+//! ```
+//!
+//! and included with `include!(concat!(env!("OUT_DIR"), "/schema.rs"));`, or from the command line
+//! with `schema-codegen --schema schema.graphql --query src/run.graphql --output src/schema.rs`.
+
+use std::{collections::BTreeMap, fs, io, path::Path, path::PathBuf};
+
+mod input;
+mod output;
+mod rust;
+mod schema;
+
+/// An error generating types.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// A file could not be read or written.
+    #[error("{}: {source}", path.display())]
+    Io {
+        /// The path of the file.
+        path: PathBuf,
+        /// The underlying error.
+        source: io::Error,
+    },
+    /// The schema is not valid, or has types that can't be generated.
+    #[error("invalid schema: {0}")]
+    Schema(String),
+    /// The input query is not valid for the schema, or has selections that can't be generated.
+    #[error("invalid query: {0}")]
+    Query(String),
+}
+
+/// Options for generating types.
+#[derive(Debug, Clone, Default)]
+pub struct CodegenOptions {
+    /// The Rust type of each custom scalar, by its GraphQL name, such as `"JSON"` to
+    /// `"shopify_function_wasm_api::Value"`. The types must implement `Deserialize` to be used in
+    /// the input, and `Serialize` to be used in the output.
+    pub scalars: BTreeMap<String, String>,
+}
+
+impl CodegenOptions {
+    fn scalar_type(&self, name: &str) -> Result<String, Error> {
+        if let Some(ty) = self.scalars.get(name) {
+            return Ok(ty.clone());
+        }
+        Ok(match name {
+            "Int" => "i32",
+            "Float" => "f64",
+            "Boolean" => "bool",
+            "JSON" => {
+                return Err(Error::Schema(
+                    "scalar `JSON` has no Rust type, set one in the `scalars` option".to_string(),
+                ))
+            }
+            _ => "::std::string::String",
+        }
+        .to_string())
+    }
+}
+
+/// Generate the input and output types of a function, from the SDL of its target's schema and its
+/// input query.
+pub fn generate(schema: &str, query: &str) -> Result<String, Error> {
+    generate_with_options(schema, query, &CodegenOptions::default())
+}
+
+/// Generate the input and output types of a function, with options.
+pub fn generate_with_options(
+    schema: &str,
+    query: &str,
+    options: &CodegenOptions,
+) -> Result<String, Error> {
+    let schema_document = graphql_parser::parse_schema::<&str>(schema)
+        .map_err(|err| Error::Schema(err.to_string()))?;
+    let query_document =
+        graphql_parser::parse_query::<&str>(query).map_err(|err| Error::Query(err.to_string()))?;
+    let schema = schema::Schema::new(&schema_document)?;
+
+    let mut code = String::from(
+        "// Generated by shopify_function_schema_codegen from the function's schema and input query.\n\
+         // Don't edit this file, generate it again when either of them changes.\n\n",
+    );
+    // functions rarely use every output type and every input field
+    code.push_str(
+        "/// The function's input, as selected by its input query.\n#[allow(dead_code)]\n",
+    );
+    code.push_str("pub mod input {\n    use shopify_function_wasm_api as sf;\n\n");
+    code.push_str(&input::generate(&schema, &query_document, options)?);
+    code.push_str("}\n\n/// The function's output.\n#[allow(dead_code)]\npub mod output {\n");
+    code.push_str("    use shopify_function_wasm_api as sf;\n\n");
+    code.push_str(&output::generate(&schema, options)?);
+    code.push_str("}\n");
+    // items end with a blank line, which isn't needed before the end of a module
+    Ok(code.replace("\n\n}\n", "\n}\n"))
+}
+
+/// Generate the input and output types of a function from its schema and input query files,
+/// writing them to `output`.
+pub fn generate_file(
+    schema: impl AsRef<Path>,
+    query: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    options: &CodegenOptions,
+) -> Result<(), Error> {
+    let read = |path: &Path| {
+        fs::read_to_string(path).map_err(|source| Error::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+    };
+    let code = generate_with_options(&read(schema.as_ref())?, &read(query.as_ref())?, options)?;
+    let output = output.as_ref();
+    fs::write(output, code).map_err(|source| Error::Io {
+        path: output.to_path_buf(),
+        source,
+    })
+}
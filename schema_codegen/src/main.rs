@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::Parser;
+use shopify_function_schema_codegen::{generate_file, CodegenOptions};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// Path to the function target's GraphQL schema
+    #[arg(short, long)]
+    schema: PathBuf,
+
+    /// Path to the function's input query
+    #[arg(short, long)]
+    query: PathBuf,
+
+    /// Path to the Rust file to write
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// The Rust type of a custom scalar, as `NAME=TYPE`, such as `JSON=my_crate::Json`
+    #[arg(long = "scalar", value_name = "NAME=TYPE", value_parser = parse_scalar)]
+    scalars: Vec<(String, String)>,
+}
+
+fn parse_scalar(scalar: &str) -> Result<(String, String), String> {
+    scalar
+        .split_once('=')
+        .map(|(name, ty)| (name.to_string(), ty.to_string()))
+        .ok_or_else(|| format!("expected `NAME=TYPE`, got `{scalar}`"))
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let options = CodegenOptions {
+        scalars: args.scalars.into_iter().collect(),
+    };
+    generate_file(&args.schema, &args.query, &args.output, &options)
+        .with_context(|| format!("failed to generate {}", args.output.display()))
+}
@@ -0,0 +1,109 @@
+//! Types for the function's output, from the arguments of the schema's mutation fields.
+
+use std::collections::BTreeSet;
+
+use graphql_parser::schema::{InputObjectType, Type};
+
+use crate::{
+    input::enum_values,
+    rust,
+    schema::{self, Kind, Schema},
+    CodegenOptions, Error,
+};
+
+pub(crate) fn generate<'s>(
+    schema: &'s Schema<'s>,
+    options: &'s CodegenOptions,
+) -> Result<String, Error> {
+    let mut generator = Generator {
+        schema,
+        options,
+        code: String::new(),
+        generated: BTreeSet::new(),
+    };
+    for field in schema.mutation_fields()? {
+        for argument in &field.arguments {
+            generator.named_type(schema::named_type(&argument.value_type))?;
+        }
+    }
+    Ok(generator.code)
+}
+
+struct Generator<'s> {
+    schema: &'s Schema<'s>,
+    options: &'s CodegenOptions,
+    code: String,
+    /// The input objects and enums that have been generated, or are being generated.
+    generated: BTreeSet<&'s str>,
+}
+
+impl<'s> Generator<'s> {
+    /// The Rust type of a named input type, generating it if it's an input object or enum.
+    fn named_type(&mut self, named: &'s str) -> Result<String, Error> {
+        Ok(match self.schema.kind(named)? {
+            Kind::Scalar => self.options.scalar_type(named)?,
+            Kind::Enum(enumeration) => {
+                if self.generated.insert(named) {
+                    let values = enum_values(enumeration)?;
+                    rust::write_output_enum(
+                        &mut self.code,
+                        named,
+                        enumeration.description.as_deref(),
+                        &values,
+                    );
+                }
+                named.to_string()
+            }
+            Kind::InputObject(object) => {
+                if self.generated.insert(named) {
+                    self.input_object(object)?;
+                }
+                named.to_string()
+            }
+            Kind::Composite => {
+                return Err(Error::Schema(format!(
+                    "mutation argument type `{named}` is not an input type"
+                )))
+            }
+        })
+    }
+
+    fn input_object(&mut self, object: &'s InputObjectType<'s, &'s str>) -> Result<(), Error> {
+        let description = object.description.as_deref();
+        if object
+            .directives
+            .iter()
+            .any(|directive| directive.name == "oneOf")
+        {
+            let mut variants = Vec::with_capacity(object.fields.len());
+            for field in &object.fields {
+                // the fields of `@oneOf` input objects are nullable, but the variant's value isn't
+                let ty = match &field.value_type {
+                    Type::NonNullType(_) => field.value_type.clone(),
+                    ty => Type::NonNullType(Box::new(ty.clone())),
+                };
+                let named = self.named_type(schema::named_type(&ty))?;
+                variants.push(rust::Variant {
+                    ident: rust::pascal_case(field.name),
+                    key: field.name,
+                    ty: schema::rust_type(&ty, &named),
+                    description: field.description.as_deref(),
+                });
+            }
+            rust::write_output_one_of_enum(&mut self.code, object.name, description, &variants);
+        } else {
+            let mut fields = Vec::with_capacity(object.fields.len());
+            for field in &object.fields {
+                let named = self.named_type(schema::named_type(&field.value_type))?;
+                fields.push(rust::Field {
+                    ident: rust::field_ident(field.name),
+                    key: field.name,
+                    ty: schema::rust_type(&field.value_type, &named),
+                    description: field.description.as_deref(),
+                });
+            }
+            rust::write_output_struct(&mut self.code, object.name, description, &fields);
+        }
+        Ok(())
+    }
+}
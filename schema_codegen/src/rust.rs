@@ -0,0 +1,413 @@
+//! Rust naming and the code written for generated types.
+
+use std::fmt::Write;
+
+/// `writeln!` to a `String`, which doesn't fail.
+macro_rules! emit {
+    ($out:expr, $($arg:tt)*) => {
+        writeln!($out, $($arg)*).expect("writing to a String doesn't fail")
+    };
+}
+
+/// Keywords that can't be used as identifiers, even as raw identifiers.
+const NON_RAW_KEYWORDS: &[&str] = &["crate", "self", "super", "Self"];
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+/// Convert a GraphQL name in `camelCase`, `PascalCase` or `SCREAMING_SNAKE_CASE` to `PascalCase`.
+pub(crate) fn pascal_case(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    for word in name.split('_').filter(|word| !word.is_empty()) {
+        let mut chars = word.chars();
+        let first = chars.next().expect("words aren't empty");
+        result.extend(first.to_uppercase());
+        // words of a SCREAMING_SNAKE_CASE name are lowercased, camelCase words are kept as is
+        if word.chars().all(|c| !c.is_lowercase()) {
+            result.extend(chars.flat_map(char::to_lowercase));
+        } else {
+            result.extend(chars);
+        }
+    }
+    result
+}
+
+/// Convert a GraphQL name in `camelCase` to `snake_case`, keeping runs of capitals as one word.
+pub(crate) fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.trim_start_matches('_').chars().collect();
+    let mut result = String::with_capacity(name.len() + 4);
+    for (index, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && index > 0 {
+            let previous = chars[index - 1];
+            let next_is_lowercase = chars.get(index + 1).is_some_and(|c| c.is_lowercase());
+            if previous.is_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_uppercase() && next_is_lowercase)
+            {
+                result.push('_');
+            }
+        }
+        result.extend(c.to_lowercase());
+    }
+    result
+}
+
+/// The identifier of a struct field for a GraphQL field.
+pub(crate) fn field_ident(name: &str) -> String {
+    let ident = snake_case(name);
+    if NON_RAW_KEYWORDS.contains(&ident.as_str()) {
+        format!("{ident}_")
+    } else if KEYWORDS.contains(&ident.as_str()) {
+        format!("r#{ident}")
+    } else {
+        ident
+    }
+}
+
+/// Write `description` as a doc comment at `indent`.
+pub(crate) fn write_doc(out: &mut String, indent: &str, description: Option<&str>) {
+    for line in description.into_iter().flat_map(str::lines) {
+        let line = line.trim_end();
+        if line.is_empty() {
+            emit!(out, "{indent}///");
+        } else {
+            emit!(out, "{indent}/// {line}");
+        }
+    }
+}
+
+/// A field of a generated struct.
+pub(crate) struct Field<'a> {
+    pub(crate) ident: String,
+    pub(crate) key: &'a str,
+    pub(crate) ty: String,
+    pub(crate) description: Option<&'a str>,
+}
+
+/// A variant of a generated enum that wraps a value.
+pub(crate) struct Variant<'a> {
+    pub(crate) ident: String,
+    /// The `__typename` of input variants, or the key of output variants.
+    pub(crate) key: &'a str,
+    pub(crate) ty: String,
+    pub(crate) description: Option<&'a str>,
+}
+
+fn write_struct(out: &mut String, name: &str, description: Option<&str>, fields: &[Field]) {
+    write_doc(out, "    ", description);
+    emit!(out, "    #[derive(Debug, Clone, PartialEq)]");
+    emit!(out, "    pub struct {name} {{");
+    for field in fields {
+        write_doc(out, "        ", field.description);
+        emit!(out, "        pub {}: {},", field.ident, field.ty);
+    }
+    emit!(out, "    }}\n");
+}
+
+fn quoted_keys<'a>(keys: impl Iterator<Item = &'a str>) -> String {
+    keys.map(|key| format!("{key:?}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Write a struct that is read from an input object with a shape of its fields' keys.
+pub(crate) fn write_input_struct(
+    out: &mut String,
+    name: &str,
+    description: Option<&str>,
+    fields: &[Field],
+) {
+    write_struct(out, name, description, fields);
+    let len = fields.len();
+    let keys = quoted_keys(fields.iter().map(|field| field.key));
+    emit!(
+        out,
+        "    impl sf::Deserialize for {name} {{
+        fn deserialize(value: &sf::Value) -> ::core::result::Result<Self, sf::read::Error> {{
+            static SHAPE: sf::CachedShape<{len}> = sf::CachedShape::new(&[{keys}]);
+            if !value.is_obj() {{
+                return ::core::result::Result::Err(sf::read::Error::InvalidType);
+            }}
+            let values = value.read_shape(&SHAPE.load());
+            ::core::result::Result::Ok(Self {{"
+    );
+    for (index, field) in fields.iter().enumerate() {
+        emit!(
+            out,
+            "                {}: sf::Deserialize::deserialize(&values[{index}])?,",
+            field.ident
+        );
+    }
+    emit!(out, "            }})\n        }}\n    }}\n");
+}
+
+/// Write a struct that is written as an output object with a property for each field.
+pub(crate) fn write_output_struct(
+    out: &mut String,
+    name: &str,
+    description: Option<&str>,
+    fields: &[Field],
+) {
+    write_struct(out, name, description, fields);
+    let len = fields.len();
+    emit!(
+        out,
+        "    impl sf::Serialize for {name} {{
+        fn serialize(
+            &self,
+            context: &mut sf::Context,
+        ) -> ::core::result::Result<(), sf::write::Error> {{
+            static KEYS: [sf::CachedInternedStringId; {len}] = ["
+    );
+    for field in fields {
+        emit!(
+            out,
+            "                sf::CachedInternedStringId::new({:?}),",
+            field.key
+        );
+    }
+    emit!(
+        out,
+        "            ];
+            context.write_object_fields(
+                |object| {{"
+    );
+    for (index, field) in fields.iter().enumerate() {
+        emit!(
+            out,
+            "                    object.interned_field(KEYS[{index}].load(), &self.{})?;",
+            field.ident
+        );
+    }
+    emit!(
+        out,
+        "                    ::core::result::Result::Ok(())
+                }},
+                {len},
+            )
+        }}
+    }}
+"
+    );
+}
+
+fn write_enum_variants(out: &mut String, variants: &[Variant]) {
+    for variant in variants {
+        write_doc(out, "        ", variant.description);
+        emit!(out, "        {}({}),", variant.ident, variant.ty);
+    }
+}
+
+/// Write an enum for an abstract type, read as the variant whose type is the object's `__typename`,
+/// or as `Other` for any other type.
+pub(crate) fn write_input_abstract_enum(
+    out: &mut String,
+    name: &str,
+    description: Option<&str>,
+    typename_key: &str,
+    variants: &[Variant],
+) {
+    write_doc(out, "    ", description);
+    emit!(out, "    #[derive(Debug, Clone, PartialEq)]");
+    emit!(out, "    pub enum {name} {{");
+    write_enum_variants(out, variants);
+    emit!(
+        out,
+        "        /// An object of a type without a fragment in the query."
+    );
+    emit!(out, "        Other,\n    }}\n");
+    emit!(
+        out,
+        "    impl sf::Deserialize for {name} {{
+        fn deserialize(value: &sf::Value) -> ::core::result::Result<Self, sf::read::Error> {{
+            static TYPENAME: sf::CachedInternedStringId = sf::CachedInternedStringId::new({typename_key:?});
+            let typename: ::std::string::String =
+                sf::Deserialize::deserialize(&value.get_interned_obj_prop(TYPENAME.load()))?;
+            ::core::result::Result::Ok(match typename.as_str() {{"
+    );
+    for variant in variants {
+        emit!(
+            out,
+            "                {:?} => Self::{}(sf::Deserialize::deserialize(value)?),",
+            variant.key,
+            variant.ident
+        );
+    }
+    emit!(
+        out,
+        "                _ => Self::Other,\n            }})\n        }}\n    }}\n"
+    );
+}
+
+/// Write a `@oneOf` input object, written as an object whose only property is the variant's key.
+pub(crate) fn write_output_one_of_enum(
+    out: &mut String,
+    name: &str,
+    description: Option<&str>,
+    variants: &[Variant],
+) {
+    write_doc(out, "    ", description);
+    emit!(out, "    #[derive(Debug, Clone, PartialEq)]");
+    emit!(out, "    pub enum {name} {{");
+    write_enum_variants(out, variants);
+    let len = variants.len();
+    emit!(
+        out,
+        "    }}
+
+    impl sf::Serialize for {name} {{
+        fn serialize(
+            &self,
+            context: &mut sf::Context,
+        ) -> ::core::result::Result<(), sf::write::Error> {{
+            static KEYS: [sf::CachedInternedStringId; {len}] = ["
+    );
+    for variant in variants {
+        emit!(
+            out,
+            "                sf::CachedInternedStringId::new({:?}),",
+            variant.key
+        );
+    }
+    emit!(
+        out,
+        "            ];
+            context.write_object_fields(
+                |object| match self {{"
+    );
+    for (index, variant) in variants.iter().enumerate() {
+        emit!(
+            out,
+            "                    Self::{}(value) => object.interned_field(KEYS[{index}].load(), value),",
+            variant.ident
+        );
+    }
+    emit!(
+        out,
+        "                }},
+                1,
+            )
+        }}
+    }}
+"
+    );
+}
+
+/// A value of a GraphQL enum.
+pub(crate) struct EnumValue<'a> {
+    pub(crate) ident: String,
+    pub(crate) value: &'a str,
+    pub(crate) description: Option<&'a str>,
+}
+
+fn write_enum(
+    out: &mut String,
+    name: &str,
+    description: Option<&str>,
+    values: &[EnumValue],
+    other: bool,
+) {
+    write_doc(out, "    ", description);
+    emit!(out, "    #[derive(Debug, Clone, PartialEq)]");
+    emit!(out, "    pub enum {name} {{");
+    for value in values {
+        write_doc(out, "        ", value.description);
+        emit!(out, "        {},", value.ident);
+    }
+    if other {
+        emit!(
+            out,
+            "        /// A value added to the schema after these types were generated."
+        );
+        emit!(out, "        Other(::std::string::String),");
+    }
+    emit!(out, "    }}\n");
+}
+
+/// Write an enum read from input strings, with an `Other` variant for values added to the schema
+/// later, so functions keep working when they are.
+pub(crate) fn write_input_enum(
+    out: &mut String,
+    name: &str,
+    description: Option<&str>,
+    values: &[EnumValue],
+) {
+    write_enum(out, name, description, values, true);
+    emit!(
+        out,
+        "    impl sf::Deserialize for {name} {{
+        fn deserialize(value: &sf::Value) -> ::core::result::Result<Self, sf::read::Error> {{
+            let value: ::std::string::String = sf::Deserialize::deserialize(value)?;
+            ::core::result::Result::Ok(match value.as_str() {{"
+    );
+    for value in values {
+        emit!(
+            out,
+            "                {:?} => Self::{},",
+            value.value,
+            value.ident
+        );
+    }
+    emit!(
+        out,
+        "                _ => Self::Other(value),\n            }})\n        }}\n    }}\n"
+    );
+}
+
+/// Write an enum written as output strings.
+pub(crate) fn write_output_enum(
+    out: &mut String,
+    name: &str,
+    description: Option<&str>,
+    values: &[EnumValue],
+) {
+    write_enum(out, name, description, values, false);
+    emit!(
+        out,
+        "    impl sf::Serialize for {name} {{
+        fn serialize(
+            &self,
+            context: &mut sf::Context,
+        ) -> ::core::result::Result<(), sf::write::Error> {{
+            context.write_utf8_str(match self {{"
+    );
+    for value in values {
+        emit!(
+            out,
+            "                Self::{} => {:?},",
+            value.ident,
+            value.value
+        );
+    }
+    emit!(out, "            }})\n        }}\n    }}\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pascal_case() {
+        assert_eq!(pascal_case("cartLines"), "CartLines");
+        assert_eq!(pascal_case("FIRST_ORDER"), "FirstOrder");
+        assert_eq!(pascal_case("ProductVariant"), "ProductVariant");
+        assert_eq!(pascal_case("__typename"), "Typename");
+        assert_eq!(pascal_case("ID"), "Id");
+    }
+
+    #[test]
+    fn test_field_ident() {
+        assert_eq!(field_ident("merchandiseId"), "merchandise_id");
+        assert_eq!(field_ident("productVariantID"), "product_variant_id");
+        assert_eq!(field_ident("HTMLTitle"), "html_title");
+        assert_eq!(field_ident("line2"), "line2");
+        assert_eq!(field_ident("__typename"), "typename");
+        assert_eq!(field_ident("type"), "r#type");
+        assert_eq!(field_ident("self"), "self_");
+    }
+}
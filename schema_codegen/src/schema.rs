@@ -0,0 +1,164 @@
+//! An index of the type definitions of a GraphQL schema.
+
+use std::collections::HashMap;
+
+use graphql_parser::schema::{Definition, Document, Field, Type, TypeDefinition};
+
+use crate::Error;
+
+/// The scalars every GraphQL schema has, without defining them.
+const BUILT_IN_SCALARS: &[&str] = &["ID", "String", "Int", "Float", "Boolean"];
+
+pub(crate) struct Schema<'a> {
+    types: HashMap<&'a str, &'a TypeDefinition<'a, &'a str>>,
+    pub(crate) query_type: &'a str,
+    pub(crate) mutation_type: &'a str,
+}
+
+/// The kind of type a named type is, as far as the generated code is concerned.
+pub(crate) enum Kind<'a> {
+    Scalar,
+    Enum(&'a graphql_parser::schema::EnumType<'a, &'a str>),
+    /// An object type, interface or union, read from an object of the input.
+    Composite,
+    InputObject(&'a graphql_parser::schema::InputObjectType<'a, &'a str>),
+}
+
+impl<'a> Schema<'a> {
+    pub(crate) fn new(document: &'a Document<'a, &'a str>) -> Result<Self, Error> {
+        let mut types = HashMap::new();
+        let mut query_type = "Query";
+        let mut mutation_type = "Mutation";
+        for definition in &document.definitions {
+            match definition {
+                Definition::SchemaDefinition(schema) => {
+                    query_type = schema.query.unwrap_or(query_type);
+                    mutation_type = schema.mutation.unwrap_or(mutation_type);
+                }
+                Definition::TypeDefinition(ty) => {
+                    if types.insert(type_name(ty), ty).is_some() {
+                        return Err(Error::Schema(format!(
+                            "type `{}` is defined more than once",
+                            type_name(ty)
+                        )));
+                    }
+                }
+                Definition::TypeExtension(_) | Definition::DirectiveDefinition(_) => {}
+            }
+        }
+        let schema = Self {
+            types,
+            query_type,
+            mutation_type,
+        };
+        schema.kind(query_type)?;
+        Ok(schema)
+    }
+
+    pub(crate) fn kind(&self, name: &str) -> Result<Kind<'a>, Error> {
+        if BUILT_IN_SCALARS.contains(&name) {
+            return Ok(Kind::Scalar);
+        }
+        match self.types.get(name) {
+            Some(TypeDefinition::Scalar(_)) => Ok(Kind::Scalar),
+            Some(TypeDefinition::Enum(ty)) => Ok(Kind::Enum(ty)),
+            Some(TypeDefinition::Object(_) | TypeDefinition::Interface(_))
+            | Some(TypeDefinition::Union(_)) => Ok(Kind::Composite),
+            Some(TypeDefinition::InputObject(ty)) => Ok(Kind::InputObject(ty)),
+            None => Err(Error::Schema(format!("type `{name}` is not defined"))),
+        }
+    }
+
+    pub(crate) fn description(&self, name: &str) -> Option<&'a str> {
+        let description = match self.types.get(name)? {
+            TypeDefinition::Scalar(ty) => &ty.description,
+            TypeDefinition::Object(ty) => &ty.description,
+            TypeDefinition::Interface(ty) => &ty.description,
+            TypeDefinition::Union(ty) => &ty.description,
+            TypeDefinition::Enum(ty) => &ty.description,
+            TypeDefinition::InputObject(ty) => &ty.description,
+        };
+        description.as_deref()
+    }
+
+    /// Whether the type is an interface or union, whose objects may be of several types.
+    pub(crate) fn is_abstract(&self, name: &str) -> bool {
+        matches!(
+            self.types.get(name),
+            Some(TypeDefinition::Interface(_) | TypeDefinition::Union(_))
+        )
+    }
+
+    pub(crate) fn is_object(&self, name: &str) -> bool {
+        matches!(self.types.get(name), Some(TypeDefinition::Object(_)))
+    }
+
+    /// The field of an object type or interface.
+    pub(crate) fn field(&self, ty: &str, name: &str) -> Option<&'a Field<'a, &'a str>> {
+        let fields = match self.types.get(ty)? {
+            TypeDefinition::Object(ty) => &ty.fields,
+            TypeDefinition::Interface(ty) => &ty.fields,
+            _ => return None,
+        };
+        fields.iter().find(|field| field.name == name)
+    }
+
+    /// The fields of the mutation type, whose arguments are the function's output.
+    pub(crate) fn mutation_fields(&self) -> Result<&'a [Field<'a, &'a str>], Error> {
+        match self.types.get(self.mutation_type) {
+            Some(TypeDefinition::Object(ty)) => Ok(&ty.fields),
+            _ => Err(Error::Schema(format!(
+                "mutation type `{}` is not defined",
+                self.mutation_type
+            ))),
+        }
+    }
+
+    /// Whether a fragment on `condition` applies to objects of type `ty`.
+    pub(crate) fn condition_applies(&self, condition: &str, ty: &str) -> bool {
+        if condition == ty {
+            return true;
+        }
+        match (self.types.get(condition), self.types.get(ty)) {
+            (Some(TypeDefinition::Union(union)), _) => union.types.contains(&ty),
+            (Some(TypeDefinition::Interface(_)), Some(TypeDefinition::Object(object))) => {
+                object.implements_interfaces.contains(&condition)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn type_name<'a>(ty: &TypeDefinition<'a, &'a str>) -> &'a str {
+    match ty {
+        TypeDefinition::Scalar(ty) => ty.name,
+        TypeDefinition::Object(ty) => ty.name,
+        TypeDefinition::Interface(ty) => ty.name,
+        TypeDefinition::Union(ty) => ty.name,
+        TypeDefinition::Enum(ty) => ty.name,
+        TypeDefinition::InputObject(ty) => ty.name,
+    }
+}
+
+/// The name of the type a (possibly list or non-null) type is made of.
+pub(crate) fn named_type<'a>(ty: &Type<'a, &'a str>) -> &'a str {
+    match ty {
+        Type::NamedType(name) => name,
+        Type::ListType(ty) | Type::NonNullType(ty) => named_type(ty),
+    }
+}
+
+/// The Rust type of a (possibly list or non-null) type, whose named type is `named`.
+pub(crate) fn rust_type<'a>(ty: &Type<'a, &'a str>, named: &str) -> String {
+    match ty {
+        Type::NonNullType(ty) => match &**ty {
+            Type::ListType(ty) => format!("::std::vec::Vec<{}>", rust_type(ty, named)),
+            _ => named.to_string(),
+        },
+        Type::ListType(ty) => format!(
+            "::core::option::Option<::std::vec::Vec<{}>>",
+            rust_type(ty, named)
+        ),
+        Type::NamedType(_) => format!("::core::option::Option<{named}>"),
+    }
+}
@@ -0,0 +1,156 @@
+use shopify_function_schema_codegen::{generate, generate_with_options, CodegenOptions, Error};
+use shopify_function_wasm_api::{Context, Deserialize, Serialize};
+
+// the generated code isn't formatted, so it's compared as is
+#[rustfmt::skip]
+#[path = "generated/run.rs"]
+mod generated;
+
+use generated::{input, output};
+
+const SCHEMA: &str = include_str!("fixtures/schema.graphql");
+const QUERY: &str = include_str!("fixtures/run.graphql");
+
+#[test]
+fn test_generated_code_is_up_to_date() {
+    assert!(
+        generate(SCHEMA, QUERY).unwrap() == include_str!("generated/run.rs"),
+        "generate the code again with `cargo run -p shopify_function_schema_codegen -- --schema \
+         schema_codegen/tests/fixtures/schema.graphql --query \
+         schema_codegen/tests/fixtures/run.graphql --output schema_codegen/tests/generated/run.rs`"
+    );
+}
+
+#[test]
+fn test_read_input() {
+    let context = Context::new_with_input(serde_json::json!({
+        "cart": {
+            "lines": [
+                {
+                    "id": "gid://shopify/CartLine/1",
+                    "quantity": 2,
+                    "cost": { "amountPerQuantity": { "amount": "10.0", "currencyCode": "CAD" } },
+                    "merchandise": {
+                        "__typename": "ProductVariant",
+                        "id": "gid://shopify/ProductVariant/1",
+                        "product": { "handle": "shirt", "isGiftCard": false },
+                    },
+                },
+                {
+                    "id": "gid://shopify/CartLine/2",
+                    "quantity": 1,
+                    "cost": { "amountPerQuantity": { "amount": "5.0", "currencyCode": "JPY" } },
+                    "merchandise": { "__typename": "CustomProduct", "title": "Engraving" },
+                },
+            ],
+            "giftWrap": null,
+            "cost": { "subtotalAmount": { "amount": "25.0", "currencyCode": "CAD" } },
+        },
+        "discountNode": { "metafield": { "value": "{}" } },
+    }));
+    let input = input::RunInput::deserialize(&context.input_get().unwrap()).unwrap();
+
+    let [first, second] = &input.cart.lines[..] else {
+        panic!("expected two lines");
+    };
+    assert_eq!(first.quantity, 2);
+    assert_eq!(
+        first.cost.amount_per_quantity.currency_code,
+        input::CurrencyCode::Cad
+    );
+    let input::RunInputCartLinesMerchandise::ProductVariant(variant) = &first.merchandise else {
+        panic!("expected a product variant");
+    };
+    assert_eq!(variant.id, "gid://shopify/ProductVariant/1");
+    assert_eq!(variant.product.handle, "shirt");
+    assert_eq!(
+        second.cost.amount_per_quantity.currency_code,
+        input::CurrencyCode::Other("JPY".to_string())
+    );
+    let input::RunInputCartLinesMerchandise::CustomProduct(custom) = &second.merchandise else {
+        panic!("expected a custom product");
+    };
+    assert_eq!(custom.title, "Engraving");
+    assert_eq!(input.cart.gift_wrap, None);
+    assert_eq!(input.cart.cost.subtotal_amount.amount, "25.0");
+    assert_eq!(
+        input
+            .discount_node
+            .metafield
+            .map(|metafield| metafield.value),
+        Some("{}".to_string())
+    );
+}
+
+#[test]
+fn test_write_output() {
+    let mut context = Context::new_with_input(serde_json::json!({}));
+    let result = output::FunctionRunResult {
+        discounts: vec![output::Discount {
+            message: None,
+            targets: vec![output::Target::CartLine(output::CartLineTarget {
+                id: "gid://shopify/CartLine/1".to_string(),
+                quantity: Some(1),
+            })],
+            value: output::Value::Percentage(output::Percentage {
+                value: "10.0".to_string(),
+            }),
+        }],
+        discount_application_strategy: output::DiscountApplicationStrategy::First,
+    };
+    result.serialize(&mut context).unwrap();
+    assert_eq!(
+        context.finalize_output_and_return().unwrap(),
+        serde_json::json!({
+            "discounts": [{
+                "message": null,
+                "targets": [{ "cartLine": { "id": "gid://shopify/CartLine/1", "quantity": 1 } }],
+                "value": { "percentage": { "value": "10.0" } },
+            }],
+            "discountApplicationStrategy": "FIRST",
+        })
+    );
+}
+
+fn query_error(query: &str) -> String {
+    match generate(SCHEMA, query) {
+        Err(Error::Query(message)) => message,
+        result => panic!("expected a query error, got {result:?}"),
+    }
+}
+
+#[test]
+fn test_query_errors() {
+    assert_eq!(
+        query_error("{ cart { total } }"),
+        "type `Cart` has no field `total`"
+    );
+    assert_eq!(
+        query_error("{ cart }"),
+        "field `cart` of type `Input` must have a selection"
+    );
+    assert_eq!(
+        query_error("{ cart { lines { merchandise { ... on CustomProduct { title } } } } }"),
+        "the selection of `InputCartLinesMerchandise` has fragments on types of `Merchandise`, so \
+         it must select `__typename`"
+    );
+    assert_eq!(
+        query_error("{ cart { ...Missing } }"),
+        "fragment `Missing` is not defined"
+    );
+}
+
+#[test]
+fn test_json_scalar_needs_a_type() {
+    let query = "{ discountNode { metafield(key: \"a\") { jsonValue } } }";
+    assert!(matches!(generate(SCHEMA, query), Err(Error::Schema(_))));
+    let options = CodegenOptions {
+        scalars: [(
+            "JSON".to_string(),
+            "shopify_function_wasm_api::Value".to_string(),
+        )]
+        .into(),
+    };
+    let code = generate_with_options(SCHEMA, query, &options).unwrap();
+    assert!(code.contains("pub json_value: shopify_function_wasm_api::Value,"));
+}
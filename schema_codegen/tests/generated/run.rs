@@ -0,0 +1,555 @@
+// Generated by shopify_function_schema_codegen from the function's schema and input query.
+// Don't edit this file, generate it again when either of them changes.
+
+/// The function's input, as selected by its input query.
+#[allow(dead_code)]
+pub mod input {
+    use shopify_function_wasm_api as sf;
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RunInputCartLinesCostAmountPerQuantity {
+        pub amount: ::std::string::String,
+        pub currency_code: CurrencyCode,
+    }
+
+    impl sf::Deserialize for RunInputCartLinesCostAmountPerQuantity {
+        fn deserialize(value: &sf::Value) -> ::core::result::Result<Self, sf::read::Error> {
+            static SHAPE: sf::CachedShape<2> = sf::CachedShape::new(&["amount", "currencyCode"]);
+            if !value.is_obj() {
+                return ::core::result::Result::Err(sf::read::Error::InvalidType);
+            }
+            let values = value.read_shape(&SHAPE.load());
+            ::core::result::Result::Ok(Self {
+                amount: sf::Deserialize::deserialize(&values[0])?,
+                currency_code: sf::Deserialize::deserialize(&values[1])?,
+            })
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RunInputCartLinesCost {
+        pub amount_per_quantity: RunInputCartLinesCostAmountPerQuantity,
+    }
+
+    impl sf::Deserialize for RunInputCartLinesCost {
+        fn deserialize(value: &sf::Value) -> ::core::result::Result<Self, sf::read::Error> {
+            static SHAPE: sf::CachedShape<1> = sf::CachedShape::new(&["amountPerQuantity"]);
+            if !value.is_obj() {
+                return ::core::result::Result::Err(sf::read::Error::InvalidType);
+            }
+            let values = value.read_shape(&SHAPE.load());
+            ::core::result::Result::Ok(Self {
+                amount_per_quantity: sf::Deserialize::deserialize(&values[0])?,
+            })
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RunInputCartLinesMerchandiseProductVariantProduct {
+        pub handle: ::std::string::String,
+        pub is_gift_card: bool,
+    }
+
+    impl sf::Deserialize for RunInputCartLinesMerchandiseProductVariantProduct {
+        fn deserialize(value: &sf::Value) -> ::core::result::Result<Self, sf::read::Error> {
+            static SHAPE: sf::CachedShape<2> = sf::CachedShape::new(&["handle", "isGiftCard"]);
+            if !value.is_obj() {
+                return ::core::result::Result::Err(sf::read::Error::InvalidType);
+            }
+            let values = value.read_shape(&SHAPE.load());
+            ::core::result::Result::Ok(Self {
+                handle: sf::Deserialize::deserialize(&values[0])?,
+                is_gift_card: sf::Deserialize::deserialize(&values[1])?,
+            })
+        }
+    }
+
+    /// A variant of a product in the store's catalog.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RunInputCartLinesMerchandiseProductVariant {
+        pub typename: ::std::string::String,
+        pub id: ::std::string::String,
+        pub product: RunInputCartLinesMerchandiseProductVariantProduct,
+    }
+
+    impl sf::Deserialize for RunInputCartLinesMerchandiseProductVariant {
+        fn deserialize(value: &sf::Value) -> ::core::result::Result<Self, sf::read::Error> {
+            static SHAPE: sf::CachedShape<3> = sf::CachedShape::new(&["__typename", "id", "product"]);
+            if !value.is_obj() {
+                return ::core::result::Result::Err(sf::read::Error::InvalidType);
+            }
+            let values = value.read_shape(&SHAPE.load());
+            ::core::result::Result::Ok(Self {
+                typename: sf::Deserialize::deserialize(&values[0])?,
+                id: sf::Deserialize::deserialize(&values[1])?,
+                product: sf::Deserialize::deserialize(&values[2])?,
+            })
+        }
+    }
+
+    /// A product that isn't in the store's catalog.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RunInputCartLinesMerchandiseCustomProduct {
+        pub typename: ::std::string::String,
+        pub title: ::std::string::String,
+    }
+
+    impl sf::Deserialize for RunInputCartLinesMerchandiseCustomProduct {
+        fn deserialize(value: &sf::Value) -> ::core::result::Result<Self, sf::read::Error> {
+            static SHAPE: sf::CachedShape<2> = sf::CachedShape::new(&["__typename", "title"]);
+            if !value.is_obj() {
+                return ::core::result::Result::Err(sf::read::Error::InvalidType);
+            }
+            let values = value.read_shape(&SHAPE.load());
+            ::core::result::Result::Ok(Self {
+                typename: sf::Deserialize::deserialize(&values[0])?,
+                title: sf::Deserialize::deserialize(&values[1])?,
+            })
+        }
+    }
+
+    /// The merchandise of a cart line.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum RunInputCartLinesMerchandise {
+        /// A variant of a product in the store's catalog.
+        ProductVariant(RunInputCartLinesMerchandiseProductVariant),
+        /// A product that isn't in the store's catalog.
+        CustomProduct(RunInputCartLinesMerchandiseCustomProduct),
+        /// An object of a type without a fragment in the query.
+        Other,
+    }
+
+    impl sf::Deserialize for RunInputCartLinesMerchandise {
+        fn deserialize(value: &sf::Value) -> ::core::result::Result<Self, sf::read::Error> {
+            static TYPENAME: sf::CachedInternedStringId = sf::CachedInternedStringId::new("__typename");
+            let typename: ::std::string::String =
+                sf::Deserialize::deserialize(&value.get_interned_obj_prop(TYPENAME.load()))?;
+            ::core::result::Result::Ok(match typename.as_str() {
+                "ProductVariant" => Self::ProductVariant(sf::Deserialize::deserialize(value)?),
+                "CustomProduct" => Self::CustomProduct(sf::Deserialize::deserialize(value)?),
+                _ => Self::Other,
+            })
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RunInputCartLines {
+        pub id: ::std::string::String,
+        pub quantity: i32,
+        pub cost: RunInputCartLinesCost,
+        pub merchandise: RunInputCartLinesMerchandise,
+    }
+
+    impl sf::Deserialize for RunInputCartLines {
+        fn deserialize(value: &sf::Value) -> ::core::result::Result<Self, sf::read::Error> {
+            static SHAPE: sf::CachedShape<4> = sf::CachedShape::new(&["id", "quantity", "cost", "merchandise"]);
+            if !value.is_obj() {
+                return ::core::result::Result::Err(sf::read::Error::InvalidType);
+            }
+            let values = value.read_shape(&SHAPE.load());
+            ::core::result::Result::Ok(Self {
+                id: sf::Deserialize::deserialize(&values[0])?,
+                quantity: sf::Deserialize::deserialize(&values[1])?,
+                cost: sf::Deserialize::deserialize(&values[2])?,
+                merchandise: sf::Deserialize::deserialize(&values[3])?,
+            })
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RunInputCartGiftWrap {
+        pub value: ::core::option::Option<::std::string::String>,
+    }
+
+    impl sf::Deserialize for RunInputCartGiftWrap {
+        fn deserialize(value: &sf::Value) -> ::core::result::Result<Self, sf::read::Error> {
+            static SHAPE: sf::CachedShape<1> = sf::CachedShape::new(&["value"]);
+            if !value.is_obj() {
+                return ::core::result::Result::Err(sf::read::Error::InvalidType);
+            }
+            let values = value.read_shape(&SHAPE.load());
+            ::core::result::Result::Ok(Self {
+                value: sf::Deserialize::deserialize(&values[0])?,
+            })
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RunInputCartCostSubtotalAmount {
+        pub amount: ::std::string::String,
+        pub currency_code: CurrencyCode,
+    }
+
+    impl sf::Deserialize for RunInputCartCostSubtotalAmount {
+        fn deserialize(value: &sf::Value) -> ::core::result::Result<Self, sf::read::Error> {
+            static SHAPE: sf::CachedShape<2> = sf::CachedShape::new(&["amount", "currencyCode"]);
+            if !value.is_obj() {
+                return ::core::result::Result::Err(sf::read::Error::InvalidType);
+            }
+            let values = value.read_shape(&SHAPE.load());
+            ::core::result::Result::Ok(Self {
+                amount: sf::Deserialize::deserialize(&values[0])?,
+                currency_code: sf::Deserialize::deserialize(&values[1])?,
+            })
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RunInputCartCost {
+        pub subtotal_amount: RunInputCartCostSubtotalAmount,
+    }
+
+    impl sf::Deserialize for RunInputCartCost {
+        fn deserialize(value: &sf::Value) -> ::core::result::Result<Self, sf::read::Error> {
+            static SHAPE: sf::CachedShape<1> = sf::CachedShape::new(&["subtotalAmount"]);
+            if !value.is_obj() {
+                return ::core::result::Result::Err(sf::read::Error::InvalidType);
+            }
+            let values = value.read_shape(&SHAPE.load());
+            ::core::result::Result::Ok(Self {
+                subtotal_amount: sf::Deserialize::deserialize(&values[0])?,
+            })
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RunInputCart {
+        /// The lines of the cart.
+        pub lines: ::std::vec::Vec<RunInputCartLines>,
+        /// The cart's attribute with the given key.
+        pub gift_wrap: ::core::option::Option<RunInputCartGiftWrap>,
+        /// The cost of the cart.
+        pub cost: RunInputCartCost,
+    }
+
+    impl sf::Deserialize for RunInputCart {
+        fn deserialize(value: &sf::Value) -> ::core::result::Result<Self, sf::read::Error> {
+            static SHAPE: sf::CachedShape<3> = sf::CachedShape::new(&["lines", "giftWrap", "cost"]);
+            if !value.is_obj() {
+                return ::core::result::Result::Err(sf::read::Error::InvalidType);
+            }
+            let values = value.read_shape(&SHAPE.load());
+            ::core::result::Result::Ok(Self {
+                lines: sf::Deserialize::deserialize(&values[0])?,
+                gift_wrap: sf::Deserialize::deserialize(&values[1])?,
+                cost: sf::Deserialize::deserialize(&values[2])?,
+            })
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RunInputDiscountNodeMetafield {
+        pub value: ::std::string::String,
+    }
+
+    impl sf::Deserialize for RunInputDiscountNodeMetafield {
+        fn deserialize(value: &sf::Value) -> ::core::result::Result<Self, sf::read::Error> {
+            static SHAPE: sf::CachedShape<1> = sf::CachedShape::new(&["value"]);
+            if !value.is_obj() {
+                return ::core::result::Result::Err(sf::read::Error::InvalidType);
+            }
+            let values = value.read_shape(&SHAPE.load());
+            ::core::result::Result::Ok(Self {
+                value: sf::Deserialize::deserialize(&values[0])?,
+            })
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RunInputDiscountNode {
+        pub metafield: ::core::option::Option<RunInputDiscountNodeMetafield>,
+    }
+
+    impl sf::Deserialize for RunInputDiscountNode {
+        fn deserialize(value: &sf::Value) -> ::core::result::Result<Self, sf::read::Error> {
+            static SHAPE: sf::CachedShape<1> = sf::CachedShape::new(&["metafield"]);
+            if !value.is_obj() {
+                return ::core::result::Result::Err(sf::read::Error::InvalidType);
+            }
+            let values = value.read_shape(&SHAPE.load());
+            ::core::result::Result::Ok(Self {
+                metafield: sf::Deserialize::deserialize(&values[0])?,
+            })
+        }
+    }
+
+    /// The input object for the function.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RunInput {
+        /// The cart.
+        pub cart: RunInputCart,
+        /// The discount node executing the function.
+        pub discount_node: RunInputDiscountNode,
+    }
+
+    impl sf::Deserialize for RunInput {
+        fn deserialize(value: &sf::Value) -> ::core::result::Result<Self, sf::read::Error> {
+            static SHAPE: sf::CachedShape<2> = sf::CachedShape::new(&["cart", "discountNode"]);
+            if !value.is_obj() {
+                return ::core::result::Result::Err(sf::read::Error::InvalidType);
+            }
+            let values = value.read_shape(&SHAPE.load());
+            ::core::result::Result::Ok(Self {
+                cart: sf::Deserialize::deserialize(&values[0])?,
+                discount_node: sf::Deserialize::deserialize(&values[1])?,
+            })
+        }
+    }
+
+    /// A subset of the [ISO 4217](https://en.wikipedia.org/wiki/ISO_4217) currency codes.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum CurrencyCode {
+        Cad,
+        Eur,
+        Usd,
+        /// A value added to the schema after these types were generated.
+        Other(::std::string::String),
+    }
+
+    impl sf::Deserialize for CurrencyCode {
+        fn deserialize(value: &sf::Value) -> ::core::result::Result<Self, sf::read::Error> {
+            let value: ::std::string::String = sf::Deserialize::deserialize(value)?;
+            ::core::result::Result::Ok(match value.as_str() {
+                "CAD" => Self::Cad,
+                "EUR" => Self::Eur,
+                "USD" => Self::Usd,
+                _ => Self::Other(value),
+            })
+        }
+    }
+}
+
+/// The function's output.
+#[allow(dead_code)]
+pub mod output {
+    use shopify_function_wasm_api as sf;
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct CartLineTarget {
+        pub id: ::std::string::String,
+        pub quantity: ::core::option::Option<i32>,
+    }
+
+    impl sf::Serialize for CartLineTarget {
+        fn serialize(
+            &self,
+            context: &mut sf::Context,
+        ) -> ::core::result::Result<(), sf::write::Error> {
+            static KEYS: [sf::CachedInternedStringId; 2] = [
+                sf::CachedInternedStringId::new("id"),
+                sf::CachedInternedStringId::new("quantity"),
+            ];
+            context.write_object_fields(
+                |object| {
+                    object.interned_field(KEYS[0].load(), &self.id)?;
+                    object.interned_field(KEYS[1].load(), &self.quantity)?;
+                    ::core::result::Result::Ok(())
+                },
+                2,
+            )
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct ProductVariantTarget {
+        pub id: ::std::string::String,
+        pub quantity: ::core::option::Option<i32>,
+    }
+
+    impl sf::Serialize for ProductVariantTarget {
+        fn serialize(
+            &self,
+            context: &mut sf::Context,
+        ) -> ::core::result::Result<(), sf::write::Error> {
+            static KEYS: [sf::CachedInternedStringId; 2] = [
+                sf::CachedInternedStringId::new("id"),
+                sf::CachedInternedStringId::new("quantity"),
+            ];
+            context.write_object_fields(
+                |object| {
+                    object.interned_field(KEYS[0].load(), &self.id)?;
+                    object.interned_field(KEYS[1].load(), &self.quantity)?;
+                    ::core::result::Result::Ok(())
+                },
+                2,
+            )
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Target {
+        CartLine(CartLineTarget),
+        ProductVariant(ProductVariantTarget),
+    }
+
+    impl sf::Serialize for Target {
+        fn serialize(
+            &self,
+            context: &mut sf::Context,
+        ) -> ::core::result::Result<(), sf::write::Error> {
+            static KEYS: [sf::CachedInternedStringId; 2] = [
+                sf::CachedInternedStringId::new("cartLine"),
+                sf::CachedInternedStringId::new("productVariant"),
+            ];
+            context.write_object_fields(
+                |object| match self {
+                    Self::CartLine(value) => object.interned_field(KEYS[0].load(), value),
+                    Self::ProductVariant(value) => object.interned_field(KEYS[1].load(), value),
+                },
+                1,
+            )
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct FixedAmount {
+        pub amount: ::std::string::String,
+        pub applies_to_each_item: ::core::option::Option<bool>,
+    }
+
+    impl sf::Serialize for FixedAmount {
+        fn serialize(
+            &self,
+            context: &mut sf::Context,
+        ) -> ::core::result::Result<(), sf::write::Error> {
+            static KEYS: [sf::CachedInternedStringId; 2] = [
+                sf::CachedInternedStringId::new("amount"),
+                sf::CachedInternedStringId::new("appliesToEachItem"),
+            ];
+            context.write_object_fields(
+                |object| {
+                    object.interned_field(KEYS[0].load(), &self.amount)?;
+                    object.interned_field(KEYS[1].load(), &self.applies_to_each_item)?;
+                    ::core::result::Result::Ok(())
+                },
+                2,
+            )
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Percentage {
+        pub value: ::std::string::String,
+    }
+
+    impl sf::Serialize for Percentage {
+        fn serialize(
+            &self,
+            context: &mut sf::Context,
+        ) -> ::core::result::Result<(), sf::write::Error> {
+            static KEYS: [sf::CachedInternedStringId; 1] = [
+                sf::CachedInternedStringId::new("value"),
+            ];
+            context.write_object_fields(
+                |object| {
+                    object.interned_field(KEYS[0].load(), &self.value)?;
+                    ::core::result::Result::Ok(())
+                },
+                1,
+            )
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Value {
+        FixedAmount(FixedAmount),
+        Percentage(Percentage),
+    }
+
+    impl sf::Serialize for Value {
+        fn serialize(
+            &self,
+            context: &mut sf::Context,
+        ) -> ::core::result::Result<(), sf::write::Error> {
+            static KEYS: [sf::CachedInternedStringId; 2] = [
+                sf::CachedInternedStringId::new("fixedAmount"),
+                sf::CachedInternedStringId::new("percentage"),
+            ];
+            context.write_object_fields(
+                |object| match self {
+                    Self::FixedAmount(value) => object.interned_field(KEYS[0].load(), value),
+                    Self::Percentage(value) => object.interned_field(KEYS[1].load(), value),
+                },
+                1,
+            )
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Discount {
+        pub message: ::core::option::Option<::std::string::String>,
+        pub targets: ::std::vec::Vec<Target>,
+        pub value: Value,
+    }
+
+    impl sf::Serialize for Discount {
+        fn serialize(
+            &self,
+            context: &mut sf::Context,
+        ) -> ::core::result::Result<(), sf::write::Error> {
+            static KEYS: [sf::CachedInternedStringId; 3] = [
+                sf::CachedInternedStringId::new("message"),
+                sf::CachedInternedStringId::new("targets"),
+                sf::CachedInternedStringId::new("value"),
+            ];
+            context.write_object_fields(
+                |object| {
+                    object.interned_field(KEYS[0].load(), &self.message)?;
+                    object.interned_field(KEYS[1].load(), &self.targets)?;
+                    object.interned_field(KEYS[2].load(), &self.value)?;
+                    ::core::result::Result::Ok(())
+                },
+                3,
+            )
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum DiscountApplicationStrategy {
+        /// Only apply the first discount.
+        First,
+        /// Only apply the discount with the largest value.
+        Maximum,
+    }
+
+    impl sf::Serialize for DiscountApplicationStrategy {
+        fn serialize(
+            &self,
+            context: &mut sf::Context,
+        ) -> ::core::result::Result<(), sf::write::Error> {
+            context.write_utf8_str(match self {
+                Self::First => "FIRST",
+                Self::Maximum => "MAXIMUM",
+            })
+        }
+    }
+
+    /// The result of the function.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct FunctionRunResult {
+        /// The discounts to apply.
+        pub discounts: ::std::vec::Vec<Discount>,
+        /// How to apply the discounts.
+        pub discount_application_strategy: DiscountApplicationStrategy,
+    }
+
+    impl sf::Serialize for FunctionRunResult {
+        fn serialize(
+            &self,
+            context: &mut sf::Context,
+        ) -> ::core::result::Result<(), sf::write::Error> {
+            static KEYS: [sf::CachedInternedStringId; 2] = [
+                sf::CachedInternedStringId::new("discounts"),
+                sf::CachedInternedStringId::new("discountApplicationStrategy"),
+            ];
+            context.write_object_fields(
+                |object| {
+                    object.interned_field(KEYS[0].load(), &self.discounts)?;
+                    object.interned_field(KEYS[1].load(), &self.discount_application_strategy)?;
+                    ::core::result::Result::Ok(())
+                },
+                2,
+            )
+        }
+    }
+}
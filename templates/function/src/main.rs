@@ -0,0 +1,160 @@
+use shopify_function_wasm_api::{
+    read::Error as ReadError, write::Error as WriteError, CachedInternedStringId, Context,
+    Deserialize, Serialize, Value,
+};
+use std::error::Error;
+
+#[cfg_attr(target_os = "unknown", export_name = "_start")]
+fn main() {
+    run().unwrap()
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    shopify_function_wasm_api::init_panic_handler();
+    let mut context = Context::new();
+    let input = Input::deserialize(&context.input_get()?)?;
+    let output = function(input);
+    output.serialize(&mut context)?;
+    Ok(())
+}
+
+fn function(input: Input) -> Output {
+    let errors = input
+        .cart
+        .lines
+        .iter()
+        .filter(|line| line.quantity > 1)
+        .map(|_| FunctionError {
+            localized_message: String::from("Not possible to order more than one of each"),
+            target: String::from("$.cart"),
+        })
+        .take(1)
+        .collect();
+
+    Output { errors }
+}
+
+static CART: CachedInternedStringId = CachedInternedStringId::new("cart");
+static LINES: CachedInternedStringId = CachedInternedStringId::new("lines");
+static QUANTITY: CachedInternedStringId = CachedInternedStringId::new("quantity");
+static ERRORS: CachedInternedStringId = CachedInternedStringId::new("errors");
+static LOCALIZED_MESSAGE: CachedInternedStringId = CachedInternedStringId::new("localizedMessage");
+static TARGET: CachedInternedStringId = CachedInternedStringId::new("target");
+
+struct Input {
+    cart: Cart,
+}
+
+struct Cart {
+    lines: Vec<CartLine>,
+}
+
+struct CartLine {
+    quantity: i32,
+}
+
+impl Deserialize for Input {
+    fn deserialize(value: &Value) -> Result<Self, ReadError> {
+        if !value.is_obj() {
+            return Err(ReadError::InvalidType);
+        }
+        Ok(Input {
+            cart: Cart::deserialize(&value.get_interned_obj_prop(CART.load()))?,
+        })
+    }
+}
+
+impl Deserialize for Cart {
+    fn deserialize(value: &Value) -> Result<Self, ReadError> {
+        if !value.is_obj() {
+            return Err(ReadError::InvalidType);
+        }
+        Ok(Cart {
+            lines: Vec::deserialize(&value.get_interned_obj_prop(LINES.load()))?,
+        })
+    }
+}
+
+impl Deserialize for CartLine {
+    fn deserialize(value: &Value) -> Result<Self, ReadError> {
+        if !value.is_obj() {
+            return Err(ReadError::InvalidType);
+        }
+        Ok(CartLine {
+            quantity: i32::deserialize(&value.get_interned_obj_prop(QUANTITY.load()))?,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Output {
+    errors: Vec<FunctionError>,
+}
+
+#[derive(Debug, PartialEq)]
+struct FunctionError {
+    localized_message: String,
+    target: String,
+}
+
+impl Serialize for Output {
+    fn serialize(&self, context: &mut Context) -> Result<(), WriteError> {
+        context.write_object(
+            |context| {
+                context.write_interned_utf8_str(ERRORS.load())?;
+                self.errors.serialize(context)
+            },
+            1,
+        )
+    }
+}
+
+impl Serialize for FunctionError {
+    fn serialize(&self, context: &mut Context) -> Result<(), WriteError> {
+        context.write_object(
+            |context| {
+                context.write_interned_utf8_str(LOCALIZED_MESSAGE.load())?;
+                context.write_utf8_str(&self.localized_message)?;
+                context.write_interned_utf8_str(TARGET.load())?;
+                context.write_utf8_str(&self.target)
+            },
+            2,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_function(input: serde_json::Value) -> serde_json::Value {
+        let mut context = Context::new_with_input(input);
+        let input = Input::deserialize(&context.input_get().unwrap()).unwrap();
+        function(input).serialize(&mut context).unwrap();
+        context.finalize_output_and_return().unwrap()
+    }
+
+    #[test]
+    fn test_no_errors_for_single_quantities() {
+        let output = run_function(serde_json::json!({
+            "cart": { "lines": [{ "quantity": 1 }, { "quantity": 1 }] }
+        }));
+        assert_eq!(output, serde_json::json!({ "errors": [] }));
+    }
+
+    #[test]
+    fn test_error_for_quantity_greater_than_one() {
+        let output = run_function(serde_json::json!({
+            "cart": { "lines": [{ "quantity": 1 }, { "quantity": 2 }, { "quantity": 3 }] }
+        }));
+        assert_eq!(
+            output,
+            serde_json::json!({
+                "errors": [{
+                    "localizedMessage": "Not possible to order more than one of each",
+                    "target": "$.cart",
+                }]
+            })
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! Detection of the allocator exported by a guest module.
+//!
+//! Most imports copy into a buffer the guest passes in, which it must size before the call. Guests
+//! whose runtimes can't easily do that, such as TinyGo and Swift, can instead use imports that
+//! allocate the buffer in guest memory with the guest's own allocator, which the trampoline calls
+//! before copying into it. Toolchains export their allocators under different names, so the
+//! trampoline looks for each of [`GUEST_ALLOCATOR_EXPORTS`].
+
+use walrus::{ExportItem, FunctionId, InstrSeqBuilder, LocalId, Module, ValType};
+
+/// The names of the allocator exports that are detected, in order of preference.
+pub const GUEST_ALLOCATOR_EXPORTS: &[&str] = &["cabi_realloc", "canonical_abi_realloc", "malloc"];
+
+/// An allocator exported by a guest module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GuestAllocator {
+    /// `cabi_realloc(old_ptr, old_len, align, new_len) -> ptr`, the allocator of the component
+    /// model, which is exported by its bindings generators, or its older name,
+    /// `canonical_abi_realloc`.
+    Realloc(FunctionId),
+    /// `malloc(len) -> ptr`, which is exported by TinyGo and by modules linking wasi-libc.
+    Malloc(FunctionId),
+}
+
+impl GuestAllocator {
+    /// Find the allocator exported by `module`. Exports with one of the names in
+    /// [`GUEST_ALLOCATOR_EXPORTS`], but not the signature of that allocator, are ignored.
+    pub fn find(module: &Module) -> Option<Self> {
+        GUEST_ALLOCATOR_EXPORTS.iter().find_map(|&name| {
+            let export = module.exports.iter().find(|export| export.name == name)?;
+            let ExportItem::Function(func) = export.item else {
+                return None;
+            };
+            let ty = module.types.get(module.funcs.get(func).ty());
+            match (name, ty.params(), ty.results()) {
+                ("malloc", [ValType::I32], [ValType::I32]) => Some(Self::Malloc(func)),
+                (
+                    "cabi_realloc" | "canonical_abi_realloc",
+                    [ValType::I32, ValType::I32, ValType::I32, ValType::I32],
+                    [ValType::I32],
+                ) => Some(Self::Realloc(func)),
+                _ => None,
+            }
+        })
+    }
+
+    /// Emit a call allocating `len` bytes with an alignment of 1, leaving the pointer on the stack.
+    pub(crate) fn emit_alloc(self, builder: &mut InstrSeqBuilder, len: LocalId) {
+        match self {
+            Self::Realloc(func) => {
+                // a new allocation has no old pointer or length
+                builder
+                    .i32_const(0)
+                    .i32_const(0)
+                    .i32_const(1)
+                    .local_get(len)
+                    .call(func);
+            }
+            Self::Malloc(func) => {
+                builder.local_get(len).call(func);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn find_in_wat(wat: &str) -> Option<GuestAllocator> {
+        let module = Module::from_buffer(&wat::parse_str(wat).unwrap()).unwrap();
+        GuestAllocator::find(&module)
+    }
+
+    #[test]
+    fn test_find() {
+        assert!(matches!(
+            find_in_wat(
+                r#"(module
+                    (func (export "malloc") (param i32) (result i32) i32.const 0)
+                    (func (export "cabi_realloc") (param i32 i32 i32 i32) (result i32) i32.const 0)
+                )"#
+            ),
+            Some(GuestAllocator::Realloc(_))
+        ));
+        assert!(matches!(
+            find_in_wat(
+                r#"(module (func (export "malloc") (param i32) (result i32) i32.const 0))"#
+            ),
+            Some(GuestAllocator::Malloc(_))
+        ));
+        // exports with the wrong signature aren't allocators
+        assert_eq!(
+            find_in_wat(
+                r#"(module (func (export "malloc") (param i64) (result i64) i64.const 0))"#
+            ),
+            None
+        );
+        assert_eq!(find_in_wat("(module)"), None);
+    }
+}
@@ -0,0 +1,207 @@
+//! Instrumentation that counts calls to the provider, for finding host-call hotspots in a function
+//! without recompiling it.
+//!
+//! Each provider function the guest imports is wrapped in a function that increments a counter
+//! before calling it. The counts are read by calling the exported `__sf_call_counts` function with
+//! the index of an import, which returns the number of calls, or -1 if there is no import at that
+//! index. The names of the imports, in index order and separated by newlines, are stored in the
+//! `__sf_call_counts` custom section.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use walrus::{
+    ir::{dfs_pre_order_mut, BinaryOp, Value, VisitorMut},
+    ConstExpr, ElementItems, FunctionBuilder, FunctionId, FunctionKind, ImportKind, Module,
+    RawCustomSection, ValType,
+};
+
+use crate::PROVIDER_MODULE_NAME;
+
+/// The name of the export that returns call counts, and of the custom section naming them.
+pub const CALL_COUNTS_NAME: &str = "__sf_call_counts";
+
+/// The guest's functions and provider imports, recorded before the trampoline rewrites them.
+pub(crate) struct Instrumentation {
+    guest_funcs: Vec<FunctionId>,
+    provider_imports: Vec<(String, FunctionId)>,
+}
+
+impl Instrumentation {
+    pub(crate) fn new(module: &Module) -> Result<Self> {
+        if module
+            .exports
+            .iter()
+            .any(|export| export.name == CALL_COUNTS_NAME)
+        {
+            bail!("Found existing export named `{CALL_COUNTS_NAME}`, which instrumentation adds. Ensure the module hasn't already been instrumented.");
+        }
+        let guest_funcs = module.funcs.iter_local().map(|(id, _)| id).collect();
+        let provider_imports = module
+            .imports
+            .iter()
+            .filter_map(|import| match import.kind {
+                ImportKind::Function(func) if import.module == PROVIDER_MODULE_NAME => {
+                    Some((import.name.clone(), func))
+                }
+                _ => None,
+            })
+            .collect();
+        Ok(Self {
+            guest_funcs,
+            provider_imports,
+        })
+    }
+
+    /// Count the guest's calls to the provider, once the trampoline has been applied.
+    pub(crate) fn apply(self, module: &mut Module) {
+        let mut wrappers = HashMap::new();
+        let mut counters = Vec::new();
+        for (_, func) in &self.provider_imports {
+            let counter = module.globals.add_local(
+                ValType::I64,
+                true,
+                false,
+                ConstExpr::Value(Value::I64(0)),
+            );
+            let ty = module.types.get(module.funcs.get(*func).ty());
+            let (params, results) = (ty.params().to_vec(), ty.results().to_vec());
+
+            let mut wrapper = FunctionBuilder::new(&mut module.types, &params, &results);
+            let args = params
+                .iter()
+                .map(|param| module.locals.add(*param))
+                .collect::<Vec<_>>();
+            let mut body = wrapper.func_body();
+            body.global_get(counter)
+                .i64_const(1)
+                .binop(BinaryOp::I64Add)
+                .global_set(counter);
+            for arg in &args {
+                body.local_get(*arg);
+            }
+            body.call(*func);
+
+            wrappers.insert(*func, wrapper.finish(args, &mut module.funcs));
+            counters.push(counter);
+        }
+
+        // only the guest's own functions are redirected, so the wrappers and the functions emitted by
+        // the trampoline still call the provider directly
+        let mut redirect = RedirectCalls(&wrappers);
+        for id in &self.guest_funcs {
+            if let FunctionKind::Local(func) = &mut module.funcs.get_mut(*id).kind {
+                let entry = func.entry_block();
+                dfs_pre_order_mut(&mut redirect, func, entry);
+            }
+        }
+        for element in module.elements.iter_mut() {
+            match &mut element.items {
+                ElementItems::Functions(funcs) => funcs
+                    .iter_mut()
+                    .for_each(|func| redirect.visit_function_id_mut(func)),
+                ElementItems::Expressions(_, exprs) => {
+                    for expr in exprs {
+                        if let ConstExpr::RefFunc(func) = expr {
+                            redirect.visit_function_id_mut(func);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut call_counts =
+            FunctionBuilder::new(&mut module.types, &[ValType::I32], &[ValType::I64]);
+        let index = module.locals.add(ValType::I32);
+        let mut body = call_counts.func_body();
+        for (i, counter) in counters.iter().enumerate() {
+            body.local_get(index)
+                .i32_const(i as i32)
+                .binop(BinaryOp::I32Eq)
+                .if_else(
+                    None,
+                    |then| {
+                        then.global_get(*counter).return_();
+                    },
+                    |_else| {},
+                );
+        }
+        body.i64_const(-1);
+        let call_counts = call_counts.finish(vec![index], &mut module.funcs);
+        module.exports.add(CALL_COUNTS_NAME, call_counts);
+
+        let names = self
+            .provider_imports
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        module.customs.add(RawCustomSection {
+            name: CALL_COUNTS_NAME.to_string(),
+            data: names.join("\n").into_bytes(),
+        });
+    }
+}
+
+/// Replaces references to functions with their wrappers.
+struct RedirectCalls<'a>(&'a HashMap<FunctionId, FunctionId>);
+
+impl VisitorMut for RedirectCalls<'_> {
+    fn visit_function_id_mut(&mut self, func: &mut FunctionId) {
+        if let Some(wrapper) = self.0.get(func) {
+            *func = *wrapper;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::TrampolineCodegen;
+    use walrus::Module;
+
+    #[test]
+    fn test_instrument() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (import "shopify_function_v2" "shopify_function_input_get" (func $input_get (result i64)))
+                (import "shopify_function_v2" "shopify_function_output_new_utf8_str" (func $new_str (param i32 i32) (result i32)))
+                (memory 1)
+                (table 1 funcref)
+                (elem (i32.const 0) $input_get)
+                (func (export "_start")
+                    call $input_get
+                    drop
+                    i32.const 0
+                    i32.const 0
+                    call $new_str
+                    drop
+                )
+            )"#,
+        )
+        .unwrap();
+        let module = Module::from_buffer(&wasm).unwrap();
+        let mut module = TrampolineCodegen::new(module)
+            .unwrap()
+            .with_instrumentation()
+            .apply()
+            .unwrap();
+        insta::assert_snapshot!(wasmprinter::print_bytes(module.emit_wasm()).unwrap());
+    }
+
+    #[test]
+    fn test_instrument_twice() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (memory 1)
+                (func (export "__sf_call_counts") (param i32) (result i64) i64.const -1)
+            )"#,
+        )
+        .unwrap();
+        let module = Module::from_buffer(&wasm).unwrap();
+        let err = TrampolineCodegen::new(module)
+            .unwrap()
+            .with_instrumentation()
+            .apply()
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Found existing export named `__sf_call_counts`, which instrumentation adds. Ensure the module hasn't already been instrumented.");
+    }
+}
@@ -3,14 +3,39 @@ use std::cell::OnceCell;
 use std::path::Path;
 use walrus::{
     ir::{BinaryOp, MemArg, UnaryOp},
-    FunctionBuilder, FunctionId, ImportKind, MemoryId, Module, ValType,
+    ExportItem, FunctionBuilder, FunctionId, ImportKind, MemoryId, Module, ValType,
 };
 
+pub mod guest_alloc;
+pub mod instrument;
+pub mod validate;
+
+use guest_alloc::{GuestAllocator, GUEST_ALLOCATOR_EXPORTS};
+
+/// The entrypoint exported by modules built as WASI 0.2 commands, e.g. Rust's `wasm32-wasip2`
+/// target, instead of `_start`. It returns the command's exit status.
+pub(crate) const WASI_CLI_RUN_EXPORT: &str = "wasi:cli/run@0.2.0#run";
+
 const INPUT_READ_UTF8_STR: &str = "shopify_function_input_read_utf8_str";
+const INPUT_READ_UTF8_STR_WITH_LEN: &str = "shopify_function_input_read_utf8_str_with_len";
+const INPUT_READ_MSGPACK_WITH_LEN: &str = "shopify_function_input_read_msgpack_with_len";
+const INPUT_READ_BOOL_BITS_WITH_LEN: &str = "shopify_function_input_read_bool_bits_with_len";
+const INPUT_READ_UTF8_STR_ALLOC: &str = "shopify_function_input_read_utf8_str_alloc";
+const INPUT_READ_UTF8_STR_CHUNK: &str = "shopify_function_input_read_utf8_str_chunk";
+const INPUT_READ_ARRAY_ELEMENTS: &str = "shopify_function_input_read_array_elements";
+const INPUT_GET_OBJ_ENTRY_AT_INDEX: &str = "shopify_function_input_get_obj_entry_at_index";
+const INPUT_NEW_SHAPE: &str = "shopify_function_input_new_shape";
+const INPUT_READ_SHAPE: &str = "shopify_function_input_read_shape";
 const INPUT_GET_OBJ_PROP: &str = "shopify_function_input_get_obj_prop";
+const INPUT_UTF8_STR_TRIM_EQ: &str = "shopify_function_input_utf8_str_trim_eq";
 const OUTPUT_NEW_STR: &str = "shopify_function_output_new_utf8_str";
+const OUTPUT_NEW_BYTES: &str = "shopify_function_output_new_bytes";
+const OUTPUT_NEW_MSGPACK: &str = "shopify_function_output_new_msgpack";
+const OUTPUT_WRITE_STR_CHUNK: &str = "shopify_function_output_write_utf8_str_chunk";
 const INTERN_STR: &str = "shopify_function_intern_utf8_str";
 const LOG_STR: &str = "shopify_function_log_new_utf8_str";
+const LOG_STR_WITH_DROPPED_LEN: &str = "shopify_function_log_new_utf8_str_with_dropped_len";
+const LOG_RECORD: &str = "shopify_function_log_new_record";
 
 static IMPORTS: &[(&str, &str)] = &[
     ("shopify_function_input_get", "_shopify_function_input_get"),
@@ -19,7 +44,19 @@ static IMPORTS: &[(&str, &str)] = &[
         "_shopify_function_input_get_val_len",
     ),
     (INPUT_READ_UTF8_STR, ""),
+    (INPUT_READ_UTF8_STR_WITH_LEN, ""),
+    (INPUT_READ_UTF8_STR_CHUNK, ""),
+    (INPUT_READ_MSGPACK_WITH_LEN, ""),
+    (INPUT_READ_BOOL_BITS_WITH_LEN, ""),
+    (INPUT_READ_UTF8_STR_ALLOC, ""),
+    (INPUT_READ_ARRAY_ELEMENTS, ""),
+    (INPUT_NEW_SHAPE, "_shopify_function_input_new_shape"),
+    (INPUT_READ_SHAPE, ""),
     (INPUT_GET_OBJ_PROP, "_shopify_function_input_get_obj_prop"),
+    (
+        INPUT_UTF8_STR_TRIM_EQ,
+        "_shopify_function_input_utf8_str_trim_eq",
+    ),
     (
         "shopify_function_input_get_interned_obj_prop",
         "_shopify_function_input_get_interned_obj_prop",
@@ -28,10 +65,48 @@ static IMPORTS: &[(&str, &str)] = &[
         "shopify_function_input_get_at_index",
         "_shopify_function_input_get_at_index",
     ),
+    (
+        "shopify_function_input_get_array_slice",
+        "_shopify_function_input_get_array_slice",
+    ),
     (
         "shopify_function_input_get_obj_key_at_index",
         "_shopify_function_input_get_obj_key_at_index",
     ),
+    (
+        "shopify_function_input_get_interned_obj_key_at_index",
+        "_shopify_function_input_get_interned_obj_key_at_index",
+    ),
+    (INPUT_GET_OBJ_ENTRY_AT_INDEX, ""),
+    (
+        "shopify_function_input_materialize",
+        "_shopify_function_input_materialize",
+    ),
+    (
+        "shopify_function_input_iter_new",
+        "_shopify_function_input_iter_new",
+    ),
+    (
+        "shopify_function_input_iter_next",
+        "_shopify_function_input_iter_next",
+    ),
+    (
+        "shopify_function_input_iter_key",
+        "_shopify_function_input_iter_key",
+    ),
+    (
+        "shopify_function_input_iter_drop",
+        "_shopify_function_input_iter_drop",
+    ),
+    ("shopify_function_input_pin", "_shopify_function_input_pin"),
+    (
+        "shopify_function_input_get_pinned",
+        "_shopify_function_input_get_pinned",
+    ),
+    (
+        "shopify_function_input_unpin",
+        "_shopify_function_input_unpin",
+    ),
     (
         "shopify_function_output_new_bool",
         "_shopify_function_output_new_bool",
@@ -44,16 +119,35 @@ static IMPORTS: &[(&str, &str)] = &[
         "shopify_function_output_new_i32",
         "_shopify_function_output_new_i32",
     ),
+    (
+        "shopify_function_output_new_i64",
+        "_shopify_function_output_new_i64",
+    ),
+    (
+        "shopify_function_output_new_u64",
+        "_shopify_function_output_new_u64",
+    ),
     (
         "shopify_function_output_new_f64",
         "_shopify_function_output_new_f64",
     ),
     (OUTPUT_NEW_STR, "_shopify_function_output_new_utf8_str"),
+    (OUTPUT_NEW_BYTES, "_shopify_function_output_new_bytes"),
+    (OUTPUT_NEW_MSGPACK, "_shopify_function_output_new_msgpack"),
     (INTERN_STR, "_shopify_function_intern_utf8_str"),
     (
         "shopify_function_output_new_interned_utf8_str",
         "_shopify_function_output_new_interned_utf8_str",
     ),
+    (
+        "shopify_function_output_new_dynamic_utf8_str",
+        "_shopify_function_output_new_dynamic_utf8_str",
+    ),
+    (OUTPUT_WRITE_STR_CHUNK, ""),
+    (
+        "shopify_function_output_finish_utf8_str",
+        "_shopify_function_output_finish_utf8_str",
+    ),
     (
         "shopify_function_output_new_object",
         "_shopify_function_output_new_object",
@@ -70,19 +164,137 @@ static IMPORTS: &[(&str, &str)] = &[
         "shopify_function_output_finish_array",
         "_shopify_function_output_finish_array",
     ),
+    (
+        "shopify_function_output_new_dynamic_array",
+        "_shopify_function_output_new_dynamic_array",
+    ),
+    (
+        "shopify_function_output_discard",
+        "_shopify_function_output_discard",
+    ),
+    (
+        "shopify_function_output_checkpoint",
+        "_shopify_function_output_checkpoint",
+    ),
+    (
+        "shopify_function_output_rollback",
+        "_shopify_function_output_rollback",
+    ),
+    (
+        "shopify_function_output_set_skip_nulls",
+        "_shopify_function_output_set_skip_nulls",
+    ),
+    (
+        "shopify_function_output_len",
+        "_shopify_function_output_len",
+    ),
+    (
+        "shopify_function_output_new_input_value",
+        "_shopify_function_output_new_input_value",
+    ),
+    (
+        "shopify_function_dedup_set_new",
+        "_shopify_function_dedup_set_new",
+    ),
+    (
+        "shopify_function_dedup_set_insert_interned",
+        "_shopify_function_dedup_set_insert_interned",
+    ),
+    (
+        "shopify_function_dedup_set_insert_hash",
+        "_shopify_function_dedup_set_insert_hash",
+    ),
+    (
+        "shopify_function_dedup_set_drop",
+        "_shopify_function_dedup_set_drop",
+    ),
     (LOG_STR, "_shopify_function_log_new_utf8_str"),
+    (LOG_STR_WITH_DROPPED_LEN, ""),
+    (LOG_RECORD, ""),
+    (
+        "shopify_function_log_interned_strings",
+        "_shopify_function_log_interned_strings",
+    ),
+    (
+        "shopify_function_interned_utf8_str_exists",
+        "_shopify_function_interned_utf8_str_exists",
+    ),
+    (
+        "shopify_function_provider_version",
+        "_shopify_function_provider_version",
+    ),
+    (
+        "shopify_function_deterministic_seed",
+        "_shopify_function_deterministic_seed",
+    ),
+    (
+        "shopify_function_set_work_budget",
+        "_shopify_function_set_work_budget",
+    ),
+    (
+        "shopify_function_host_call_start",
+        "_shopify_function_host_call_start",
+    ),
+    (
+        "shopify_function_host_call_poll",
+        "_shopify_function_host_call_poll",
+    ),
 ];
 
 pub const PROVIDER_MODULE_NAME: &str =
     concat!("shopify_function_v", env!("CARGO_PKG_VERSION_MAJOR"));
 
+/// Whether `name` can be imported from the provider module, either by a guest module or by a
+/// module that has already been trampolined.
+fn is_known_provider_import(name: &str) -> bool {
+    IMPORTS
+        .iter()
+        .any(|(orig_name, new_name)| *orig_name == name || *new_name == name)
+        || name == "_shopify_function_input_get_utf8_str_addr"
+        || name == "_shopify_function_input_get_utf8_str_addr_and_len"
+        || name == "_shopify_function_input_get_utf8_str_chunk_addr_and_len"
+        || name == "_shopify_function_input_get_msgpack_addr_and_len"
+        || name == "_shopify_function_input_get_bool_bits_addr_and_len"
+        || name == "_shopify_function_input_get_array_elements_addr_and_len"
+        || name == "_shopify_function_input_get_obj_entry_at_index_addr"
+        || name == "_shopify_function_input_get_shape_values_addr_and_len"
+        || name == "_shopify_function_output_write_utf8_str_chunk"
+        || name == "_shopify_function_output_finish_msgpack"
+        || name == "_shopify_function_log_new_record"
+        || name == "_shopify_function_alloc"
+        || name == "memory"
+}
+
 pub fn trampoline_existing_module(
     source_path: impl AsRef<Path>,
     destination_path: impl AsRef<Path>,
+) -> anyhow::Result<()> {
+    trampoline_existing_module_with_options(
+        source_path,
+        destination_path,
+        &TrampolineOptions::default(),
+    )
+}
+
+/// Options for [`trampoline_existing_module_with_options`].
+#[derive(Clone, Debug, Default)]
+pub struct TrampolineOptions {
+    /// Whether to count the module's calls to the provider, as described in [`instrument`].
+    pub instrument: bool,
+    /// A name to rename the module's `memory` export to. Modules whose `memory` export is an
+    /// imported memory are rejected, as hosts expect it to be the module's own memory, so this
+    /// keeps such an export under another name.
+    pub rename_memory_export: Option<String>,
+}
+
+pub fn trampoline_existing_module_with_options(
+    source_path: impl AsRef<Path>,
+    destination_path: impl AsRef<Path>,
+    options: &TrampolineOptions,
 ) -> anyhow::Result<()> {
     let module = Module::from_file(source_path).context("Parsing input module failed")?;
 
-    TrampolineCodegen::new(module)?
+    TrampolineCodegen::new_with_options(module, options)?
         .apply()?
         .emit_wasm_file(destination_path)
 }
@@ -93,25 +305,76 @@ pub struct TrampolineCodegen {
     provider_memory_id: OnceCell<MemoryId>,
     memcpy_to_guest: OnceCell<FunctionId>,
     memcpy_to_provider: OnceCell<FunctionId>,
-    imported_shopify_function_alloc: OnceCell<FunctionId>,
     alloc: OnceCell<FunctionId>,
+    guest_allocator: Option<GuestAllocator>,
+    instrument: bool,
 }
 
 impl TrampolineCodegen {
     pub fn new(module: Module) -> walrus::Result<Self> {
+        Self::new_with_options(module, &TrampolineOptions::default())
+    }
+
+    /// Create a codegen for `module`, applying `options`.
+    pub fn new_with_options(
+        mut module: Module,
+        options: &TrampolineOptions,
+    ) -> walrus::Result<Self> {
+        if let Some(name) = &options.rename_memory_export {
+            Self::rename_memory_export(&mut module, name)?;
+        }
         let guest_memory_id = Self::guest_memory_id(&module)?;
+        if guest_memory_id.is_some() {
+            Self::check_memory_export(&module)?;
+        }
+        let guest_allocator = GuestAllocator::find(&module);
+        // reuse the provider's memory if the module already imports it
+        let provider_memory_id = OnceCell::new();
+        if let Some(import_id) = module.imports.find(PROVIDER_MODULE_NAME, "memory") {
+            if let ImportKind::Memory(memory_id) = module.imports.get(import_id).kind {
+                let _ = provider_memory_id.set(memory_id);
+            }
+        }
 
         Ok(Self {
             module,
             guest_memory_id,
-            provider_memory_id: OnceCell::new(),
+            provider_memory_id,
             memcpy_to_guest: OnceCell::new(),
             memcpy_to_provider: OnceCell::new(),
-            imported_shopify_function_alloc: OnceCell::new(),
             alloc: OnceCell::new(),
+            guest_allocator,
+            instrument: options.instrument,
         })
     }
 
+    /// Count the module's calls to the provider when the trampoline is applied, as described in
+    /// [`instrument`].
+    pub fn with_instrumentation(mut self) -> Self {
+        self.instrument = true;
+        self
+    }
+
+    /// Import `name` from the provider, reusing the module's import of it if it has one, e.g. from
+    /// another import emitted by the trampoline or from a previous pass, so it isn't imported twice.
+    fn provider_func_import(
+        &mut self,
+        name: &str,
+        params: &[ValType],
+        results: &[ValType],
+    ) -> walrus::Result<FunctionId> {
+        if let Some(import_id) = self.module.imports.find(PROVIDER_MODULE_NAME, name) {
+            let ImportKind::Function(func) = self.module.imports.get(import_id).kind else {
+                bail!("Found import named `{name}` that is not a function.");
+            };
+            self.validate_params_and_results(name, func, params, results)?;
+            return Ok(func);
+        }
+        let ty = self.module.types.add(params, results);
+        let (func, _) = self.module.add_import_func(PROVIDER_MODULE_NAME, name, ty);
+        Ok(func)
+    }
+
     fn provider_memory_id(&mut self) -> MemoryId {
         *self.provider_memory_id.get_or_init(|| {
             let (provider_memory_id, _) = self.module.add_import_memory(
@@ -127,6 +390,39 @@ impl TrampolineCodegen {
         })
     }
 
+    /// Hosts read the module's memory through its `memory` export, so it can't be a memory the
+    /// module imports, such as the provider's, which would then be mapped twice.
+    fn check_memory_export(module: &Module) -> walrus::Result<()> {
+        let Some(export) = module.exports.iter().find(|export| export.name == "memory") else {
+            return Ok(());
+        };
+        if let ExportItem::Memory(memory_id) = export.item {
+            if let Some(import_id) = module.memories.get(memory_id).import {
+                let import = module.imports.get(import_id);
+                bail!(
+                    "The `memory` export is the memory imported from `{}::{}`, not the module's own memory. Export the module's own memory as `memory`, or rename the export with `--rename-memory-export <NAME>`.",
+                    import.module,
+                    import.name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn rename_memory_export(module: &mut Module, name: &str) -> walrus::Result<()> {
+        if module.exports.iter().any(|export| export.name == name) {
+            bail!("Cannot rename the `memory` export to `{name}`, as there is already an export with that name.");
+        }
+        if let Some(export) = module
+            .exports
+            .iter_mut()
+            .find(|export| export.name == "memory")
+        {
+            export.name = name.to_string();
+        }
+        Ok(())
+    }
+
     fn guest_memory_id(module: &Module) -> walrus::Result<Option<MemoryId>> {
         let non_imported_memories = module
             .memories
@@ -198,25 +494,11 @@ impl TrampolineCodegen {
         })
     }
 
-    fn emit_shopify_function_alloc_import(&mut self) -> FunctionId {
-        *self.imported_shopify_function_alloc.get_or_init(|| {
-            let shopify_function_alloc_type =
-                self.module.types.add(&[ValType::I32], &[ValType::I32]);
-
-            let (imported_shopify_function_alloc, _) = self.module.add_import_func(
-                PROVIDER_MODULE_NAME,
-                "_shopify_function_alloc",
-                shopify_function_alloc_type,
-            );
-
-            imported_shopify_function_alloc
-        })
-    }
-
-    fn emit_alloc(&mut self) -> FunctionId {
-        let imported_shopify_function_alloc = self.emit_shopify_function_alloc_import();
+    fn emit_alloc(&mut self) -> walrus::Result<FunctionId> {
+        let imported_shopify_function_alloc =
+            self.provider_func_import("_shopify_function_alloc", &[ValType::I32], &[ValType::I32])?;
 
-        *self.alloc.get_or_init(|| {
+        Ok(*self.alloc.get_or_init(|| {
             let mut alloc =
                 FunctionBuilder::new(&mut self.module.types, &[ValType::I32], &[ValType::I32]);
 
@@ -228,7 +510,44 @@ impl TrampolineCodegen {
                 .call(imported_shopify_function_alloc);
 
             alloc.finish(vec![size], &mut self.module.funcs)
-        })
+        }))
+    }
+
+    /// Functions are invoked through `_start`, which WASI 0.2 commands don't export, so export one
+    /// calling their entrypoint and trapping if it exits with an error, like `proc_exit` does.
+    fn emit_start_for_wasi_command(&mut self) -> walrus::Result<()> {
+        if self
+            .module
+            .exports
+            .iter()
+            .any(|export| export.name == "_start")
+        {
+            return Ok(());
+        }
+        let Some(run) = self
+            .module
+            .exports
+            .iter()
+            .find_map(|export| match export.item {
+                ExportItem::Function(func) if export.name == WASI_CLI_RUN_EXPORT => Some(func),
+                _ => None,
+            })
+        else {
+            return Ok(());
+        };
+        self.validate_params_and_results(WASI_CLI_RUN_EXPORT, run, &[], &[ValType::I32])?;
+
+        let mut start = FunctionBuilder::new(&mut self.module.types, &[], &[]);
+        start.func_body().call(run).if_else(
+            None,
+            |then| {
+                then.unreachable();
+            },
+            |_| {},
+        );
+        let start = start.finish(vec![], &mut self.module.funcs);
+        self.module.exports.add("_start", start);
+        Ok(())
     }
 
     fn rename_imported_func(&mut self, func_name: &str, new_name: &str) -> walrus::Result<()> {
@@ -263,123 +582,631 @@ impl TrampolineCodegen {
             &[],
         )?;
 
-        let shopify_function_input_get_utf8_str_addr =
-            self.module.types.add(&[ValType::I32], &[ValType::I32]);
-
-        let (shopify_function_input_get_utf8_str_addr, _) = self.module.add_import_func(
-            PROVIDER_MODULE_NAME,
-            "_shopify_function_input_get_utf8_str_addr",
-            shopify_function_input_get_utf8_str_addr,
-        );
+        let shopify_function_input_get_utf8_str_addr = self.provider_func_import(
+            "_shopify_function_input_get_utf8_str_addr",
+            &[ValType::I32],
+            &[ValType::I32],
+        )?;
+
+        let memcpy_to_guest = self.emit_memcpy_to_guest();
+
+        self.module.replace_imported_func(
+            imported_shopify_function_input_read_utf8_str,
+            |(builder, arg_locals)| {
+                let dst_ptr = arg_locals[0];
+                let src_ptr = arg_locals[1];
+                let len = arg_locals[2];
+
+                builder
+                    .func_body()
+                    .local_get(src_ptr)
+                    .local_get(dst_ptr)
+                    .call(shopify_function_input_get_utf8_str_addr)
+                    .local_get(len)
+                    .call(memcpy_to_guest);
+            },
+        )?;
+
+        Ok(())
+    }
+
+    fn emit_shopify_function_input_read_utf8_str_with_len(&mut self) -> walrus::Result<()> {
+        self.emit_read_bytes_with_len(
+            INPUT_READ_UTF8_STR_WITH_LEN,
+            "_shopify_function_input_get_utf8_str_addr_and_len",
+        )
+    }
+
+    fn emit_shopify_function_input_read_msgpack_with_len(&mut self) -> walrus::Result<()> {
+        self.emit_read_bytes_with_len(
+            INPUT_READ_MSGPACK_WITH_LEN,
+            "_shopify_function_input_get_msgpack_addr_and_len",
+        )
+    }
+
+    fn emit_shopify_function_input_read_bool_bits_with_len(&mut self) -> walrus::Result<()> {
+        self.emit_read_bytes_with_len(
+            INPUT_READ_BOOL_BITS_WITH_LEN,
+            "_shopify_function_input_get_bool_bits_addr_and_len",
+        )
+    }
+
+    /// Replace an import that copies the bytes of a value to the guest, at most `cap` of them, and
+    /// returns their length, with a call to the provider function returning their address and
+    /// length.
+    fn emit_read_bytes_with_len(
+        &mut self,
+        import_name: &str,
+        provider_import_name: &str,
+    ) -> walrus::Result<()> {
+        let Ok(imported_func) = self
+            .module
+            .imports
+            .get_func(PROVIDER_MODULE_NAME, import_name)
+        else {
+            return Ok(());
+        };
+
+        self.validate_params_and_results(
+            import_name,
+            imported_func,
+            &[ValType::I64, ValType::I32, ValType::I32],
+            &[ValType::I32],
+        )?;
+
+        let get_addr_and_len =
+            self.provider_func_import(provider_import_name, &[ValType::I64], &[ValType::I64])?;
+
+        let memcpy_to_guest = self.emit_memcpy_to_guest();
+
+        let addr_and_len = self.module.locals.add(ValType::I64);
+        let len = self.module.locals.add(ValType::I32);
+
+        self.module
+            .replace_imported_func(imported_func, |(builder, arg_locals)| {
+                let scope = arg_locals[0];
+                let dst_ptr = arg_locals[1];
+                let cap = arg_locals[2];
+
+                builder
+                    .func_body()
+                    .local_get(scope)
+                    // most significant 32 bits are the length, least significant 32 bits are the address
+                    .call(get_addr_and_len)
+                    .local_tee(addr_and_len)
+                    .i64_const(32)
+                    .binop(BinaryOp::I64ShrU)
+                    .unop(UnaryOp::I32WrapI64)
+                    .local_set(len)
+                    // a length of -1 means the value can't be read, so there is nothing to copy
+                    .local_get(len)
+                    .i32_const(-1)
+                    .binop(BinaryOp::I32Ne)
+                    .if_else(
+                        None,
+                        |then| {
+                            then.local_get(dst_ptr)
+                                .local_get(addr_and_len)
+                                .unop(UnaryOp::I32WrapI64)
+                                // copy at most `cap` bytes
+                                .local_get(len)
+                                .local_get(cap)
+                                .local_get(len)
+                                .local_get(cap)
+                                .binop(BinaryOp::I32LtU)
+                                .select(None)
+                                .call(memcpy_to_guest);
+                        },
+                        |_else| {},
+                    )
+                    .local_get(len);
+            })?;
+
+        Ok(())
+    }
+
+    /// Emits `shopify_function_input_read_utf8_str_alloc`, which copies a string to a buffer
+    /// allocated with the guest's allocator, and returns the length of the string in the most
+    /// significant 32 bits and the address of the buffer in the least significant 32 bits.
+    fn emit_shopify_function_input_read_utf8_str_alloc(&mut self) -> walrus::Result<()> {
+        let Ok(imported_func) = self
+            .module
+            .imports
+            .get_func(PROVIDER_MODULE_NAME, INPUT_READ_UTF8_STR_ALLOC)
+        else {
+            return Ok(());
+        };
+
+        self.validate_params_and_results(
+            INPUT_READ_UTF8_STR_ALLOC,
+            imported_func,
+            &[ValType::I64],
+            &[ValType::I64],
+        )?;
+
+        let Some(guest_allocator) = self.guest_allocator else {
+            bail!(
+                "{INPUT_READ_UTF8_STR_ALLOC} allocates with the module's allocator, but the module does not export one. Export one of {GUEST_ALLOCATOR_EXPORTS:?}."
+            );
+        };
+
+        let get_addr_and_len = self.provider_func_import(
+            "_shopify_function_input_get_utf8_str_addr_and_len",
+            &[ValType::I64],
+            &[ValType::I64],
+        )?;
+
+        let memcpy_to_guest = self.emit_memcpy_to_guest();
+
+        let addr_and_len = self.module.locals.add(ValType::I64);
+        let len = self.module.locals.add(ValType::I32);
+        let dst_ptr = self.module.locals.add(ValType::I32);
+
+        self.module
+            .replace_imported_func(imported_func, |(builder, arg_locals)| {
+                let scope = arg_locals[0];
+
+                builder
+                    .func_body()
+                    .local_get(scope)
+                    // most significant 32 bits are the length, least significant 32 bits are the address
+                    .call(get_addr_and_len)
+                    .local_tee(addr_and_len)
+                    .i64_const(32)
+                    .binop(BinaryOp::I64ShrU)
+                    .unop(UnaryOp::I32WrapI64)
+                    .local_tee(len)
+                    // a length of -1 means the value can't be read, which is returned as it is
+                    .i32_const(-1)
+                    .binop(BinaryOp::I32Eq)
+                    .if_else(
+                        ValType::I64,
+                        |then| {
+                            then.local_get(addr_and_len);
+                        },
+                        |else_| {
+                            guest_allocator.emit_alloc(else_, len);
+                            else_
+                                .local_tee(dst_ptr)
+                                // trap rather than copy to a null pointer if the allocation failed
+                                .unop(UnaryOp::I32Eqz)
+                                .local_get(len)
+                                .i32_const(0)
+                                .binop(BinaryOp::I32Ne)
+                                .binop(BinaryOp::I32And)
+                                .if_else(
+                                    None,
+                                    |then| {
+                                        then.unreachable();
+                                    },
+                                    |_else| {},
+                                )
+                                .local_get(dst_ptr)
+                                .local_get(addr_and_len)
+                                .unop(UnaryOp::I32WrapI64)
+                                .local_get(len)
+                                .call(memcpy_to_guest)
+                                .local_get(len)
+                                .unop(UnaryOp::I64ExtendUI32)
+                                .i64_const(32)
+                                .binop(BinaryOp::I64Shl)
+                                .local_get(dst_ptr)
+                                .unop(UnaryOp::I64ExtendUI32)
+                                .binop(BinaryOp::I64Or);
+                        },
+                    );
+            })?;
+
+        Ok(())
+    }
+
+    fn emit_shopify_function_input_read_utf8_str_chunk(&mut self) -> walrus::Result<()> {
+        self.emit_read_vals(
+            INPUT_READ_UTF8_STR_CHUNK,
+            "_shopify_function_input_get_utf8_str_chunk_addr_and_len",
+            1,
+        )
+    }
+
+    fn emit_shopify_function_input_read_array_elements(&mut self) -> walrus::Result<()> {
+        self.emit_read_vals(
+            INPUT_READ_ARRAY_ELEMENTS,
+            "_shopify_function_input_get_array_elements_addr_and_len",
+            8,
+        )
+    }
+
+    fn emit_shopify_function_input_get_obj_entry_at_index(&mut self) -> walrus::Result<()> {
+        let Ok(imported_func) = self
+            .module
+            .imports
+            .get_func(PROVIDER_MODULE_NAME, INPUT_GET_OBJ_ENTRY_AT_INDEX)
+        else {
+            return Ok(());
+        };
+
+        self.validate_params_and_results(
+            INPUT_GET_OBJ_ENTRY_AT_INDEX,
+            imported_func,
+            &[ValType::I64, ValType::I32, ValType::I32],
+            &[],
+        )?;
+
+        let provider_func = self.provider_func_import(
+            "_shopify_function_input_get_obj_entry_at_index_addr",
+            &[ValType::I64, ValType::I32],
+            &[ValType::I32],
+        )?;
+
+        let memcpy_to_guest = self.emit_memcpy_to_guest();
+
+        self.module
+            .replace_imported_func(imported_func, |(builder, arg_locals)| {
+                let scope = arg_locals[0];
+                let index = arg_locals[1];
+                let dst_ptr = arg_locals[2];
+
+                // the key and the value are two 8-byte values
+                builder
+                    .func_body()
+                    .local_get(dst_ptr)
+                    .local_get(scope)
+                    .local_get(index)
+                    .call(provider_func)
+                    .i32_const(16)
+                    .call(memcpy_to_guest);
+            })?;
+
+        Ok(())
+    }
+
+    fn emit_shopify_function_input_read_shape(&mut self) -> walrus::Result<()> {
+        self.emit_read_vals(
+            INPUT_READ_SHAPE,
+            "_shopify_function_input_get_shape_values_addr_and_len",
+            8,
+        )
+    }
+
+    /// Emits `import_name`, which takes a scope, an argument, a pointer and a capacity, and copies
+    /// up to the capacity of `value_size`-byte values into the guest at the pointer. The values are
+    /// read by `provider_import_name`, which takes the scope, the argument and the capacity, and
+    /// returns the number of values (or -1 if there are none to copy) and their address in the
+    /// provider.
+    fn emit_read_vals(
+        &mut self,
+        import_name: &str,
+        provider_import_name: &str,
+        value_size: i32,
+    ) -> walrus::Result<()> {
+        let Ok(imported_func) = self
+            .module
+            .imports
+            .get_func(PROVIDER_MODULE_NAME, import_name)
+        else {
+            return Ok(());
+        };
+
+        self.validate_params_and_results(
+            import_name,
+            imported_func,
+            &[ValType::I64, ValType::I32, ValType::I32, ValType::I32],
+            &[ValType::I32],
+        )?;
+
+        let provider_func = self.provider_func_import(
+            provider_import_name,
+            &[ValType::I64, ValType::I32, ValType::I32],
+            &[ValType::I64],
+        )?;
+
+        let memcpy_to_guest = self.emit_memcpy_to_guest();
+
+        let addr_and_len = self.module.locals.add(ValType::I64);
+        let len = self.module.locals.add(ValType::I32);
+
+        self.module
+            .replace_imported_func(imported_func, |(builder, arg_locals)| {
+                let scope = arg_locals[0];
+                let arg = arg_locals[1];
+                let dst_ptr = arg_locals[2];
+                let cap = arg_locals[3];
+
+                builder
+                    .func_body()
+                    .local_get(scope)
+                    .local_get(arg)
+                    .local_get(cap)
+                    // most significant 32 bits are the number of values, least significant 32 bits are the address
+                    .call(provider_func)
+                    .local_tee(addr_and_len)
+                    .i64_const(32)
+                    .binop(BinaryOp::I64ShrU)
+                    .unop(UnaryOp::I32WrapI64)
+                    .local_set(len)
+                    // a length of -1 means there is nothing to copy
+                    .local_get(len)
+                    .i32_const(-1)
+                    .binop(BinaryOp::I32Ne)
+                    .if_else(
+                        None,
+                        |then| {
+                            // the provider reads at most `cap` values
+                            then.local_get(dst_ptr)
+                                .local_get(addr_and_len)
+                                .unop(UnaryOp::I32WrapI64)
+                                .local_get(len)
+                                .i32_const(value_size)
+                                .binop(BinaryOp::I32Mul)
+                                .call(memcpy_to_guest);
+                        },
+                        |_else| {},
+                    )
+                    .local_get(len);
+            })?;
+
+        Ok(())
+    }
+
+    fn emit_shopify_function_input_get_obj_prop(&mut self) -> walrus::Result<()> {
+        if let Ok(imported_shopify_function_input_get_obj_prop) = self
+            .module
+            .imports
+            .get_func(PROVIDER_MODULE_NAME, INPUT_GET_OBJ_PROP)
+        {
+            self.validate_params_and_results(
+                INPUT_GET_OBJ_PROP,
+                imported_shopify_function_input_get_obj_prop,
+                &[ValType::I64, ValType::I32, ValType::I32],
+                &[ValType::I64],
+            )?;
+
+            let provider_shopify_function_input_get_obj_prop = self.provider_func_import(
+                "_shopify_function_input_get_obj_prop",
+                &[ValType::I64, ValType::I32, ValType::I32],
+                &[ValType::I64],
+            )?;
+
+            let alloc = self.emit_alloc()?;
+            let memcpy_to_provider = self.emit_memcpy_to_provider();
+
+            let dst_ptr = self.module.locals.add(ValType::I32);
+
+            self.module.replace_imported_func(
+                imported_shopify_function_input_get_obj_prop,
+                |(builder, arg_locals)| {
+                    let scope = arg_locals[0];
+                    let src_ptr = arg_locals[1];
+                    let len = arg_locals[2];
+
+                    builder
+                        .func_body()
+                        .local_get(len)
+                        .call(alloc)
+                        .local_tee(dst_ptr)
+                        .local_get(src_ptr)
+                        .local_get(len)
+                        .call(memcpy_to_provider)
+                        .local_get(scope)
+                        .local_get(dst_ptr)
+                        .local_get(len)
+                        .call(provider_shopify_function_input_get_obj_prop);
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Emits `shopify_function_input_utf8_str_trim_eq`, which copies the string to compare with to
+    /// the provider before comparing them there.
+    fn emit_shopify_function_input_utf8_str_trim_eq(&mut self) -> walrus::Result<()> {
+        let Ok(imported_func) = self
+            .module
+            .imports
+            .get_func(PROVIDER_MODULE_NAME, INPUT_UTF8_STR_TRIM_EQ)
+        else {
+            return Ok(());
+        };
+
+        let params = [ValType::I64, ValType::I32, ValType::I32, ValType::I32];
+        self.validate_params_and_results(
+            INPUT_UTF8_STR_TRIM_EQ,
+            imported_func,
+            &params,
+            &[ValType::I64],
+        )?;
+
+        let provider_func = self.provider_func_import(
+            "_shopify_function_input_utf8_str_trim_eq",
+            &params,
+            &[ValType::I64],
+        )?;
+
+        let alloc = self.emit_alloc()?;
+        let memcpy_to_provider = self.emit_memcpy_to_provider();
+
+        let dst_ptr = self.module.locals.add(ValType::I32);
+
+        self.module
+            .replace_imported_func(imported_func, |(builder, arg_locals)| {
+                let scope = arg_locals[0];
+                let src_ptr = arg_locals[1];
+                let len = arg_locals[2];
+                let normalize = arg_locals[3];
+
+                builder
+                    .func_body()
+                    .local_get(len)
+                    .call(alloc)
+                    .local_tee(dst_ptr)
+                    .local_get(src_ptr)
+                    .local_get(len)
+                    .call(memcpy_to_provider)
+                    .local_get(scope)
+                    .local_get(dst_ptr)
+                    .local_get(len)
+                    .local_get(normalize)
+                    .call(provider_func);
+            })?;
+
+        Ok(())
+    }
+
+    fn emit_shopify_function_output_new_utf8_str(&mut self) -> walrus::Result<()> {
+        self.emit_write_str(OUTPUT_NEW_STR, "_shopify_function_output_new_utf8_str")
+    }
+
+    fn emit_shopify_function_output_new_bytes(&mut self) -> walrus::Result<()> {
+        self.emit_write_str(OUTPUT_NEW_BYTES, "_shopify_function_output_new_bytes")
+    }
+
+    /// Emits `shopify_function_output_new_msgpack`, which copies the MessagePack fragment at the
+    /// pointer from the guest to the end of the output, and then has the provider check it and
+    /// write it as one value.
+    fn emit_shopify_function_output_new_msgpack(&mut self) -> walrus::Result<()> {
+        let Ok(imported_func) = self
+            .module
+            .imports
+            .get_func(PROVIDER_MODULE_NAME, OUTPUT_NEW_MSGPACK)
+        else {
+            return Ok(());
+        };
+
+        self.validate_params_and_results(
+            OUTPUT_NEW_MSGPACK,
+            imported_func,
+            &[ValType::I32, ValType::I32],
+            &[ValType::I32],
+        )?;
+
+        let provider_new_msgpack = self.provider_func_import(
+            "_shopify_function_output_new_msgpack",
+            &[ValType::I32],
+            &[ValType::I64],
+        )?;
+        let provider_finish_msgpack = self.provider_func_import(
+            "_shopify_function_output_finish_msgpack",
+            &[ValType::I32],
+            &[ValType::I32],
+        )?;
 
-        let memcpy_to_guest = self.emit_memcpy_to_guest();
+        let memcpy_to_provider = self.emit_memcpy_to_provider();
 
-        self.module.replace_imported_func(
-            imported_shopify_function_input_read_utf8_str,
-            |(builder, arg_locals)| {
-                let dst_ptr = arg_locals[0];
-                let src_ptr = arg_locals[1];
-                let len = arg_locals[2];
+        self.module
+            .replace_imported_func(imported_func, |(builder, arg_locals)| {
+                let src_ptr = arg_locals[0];
+                let len = arg_locals[1];
 
                 builder
                     .func_body()
+                    .local_get(len)
+                    // the space is always allocated, so only the pointer in the least significant
+                    // 32 bits is needed
+                    .call(provider_new_msgpack)
+                    .unop(UnaryOp::I32WrapI64) // dst_ptr is on the stack now
                     .local_get(src_ptr)
-                    .local_get(dst_ptr)
-                    .call(shopify_function_input_get_utf8_str_addr)
                     .local_get(len)
-                    .call(memcpy_to_guest);
-            },
-        )?;
+                    .call(memcpy_to_provider)
+                    .local_get(len)
+                    .call(provider_finish_msgpack);
+            })?;
 
         Ok(())
     }
 
-    fn emit_shopify_function_input_get_obj_prop(&mut self) -> walrus::Result<()> {
-        if let Ok(imported_shopify_function_input_get_obj_prop) = self
+    fn emit_shopify_function_output_write_utf8_str_chunk(&mut self) -> walrus::Result<()> {
+        self.emit_write_str(
+            OUTPUT_WRITE_STR_CHUNK,
+            "_shopify_function_output_write_utf8_str_chunk",
+        )
+    }
+
+    /// Emits `import_name`, which takes a pointer and a length, and copies the string at the
+    /// pointer from the guest into the output. The space for it is allocated by
+    /// `provider_import_name`, which takes the length and returns the result and the address in the
+    /// provider.
+    fn emit_write_str(
+        &mut self,
+        import_name: &str,
+        provider_import_name: &str,
+    ) -> walrus::Result<()> {
+        let Ok(imported_func) = self
             .module
             .imports
-            .get_func(PROVIDER_MODULE_NAME, INPUT_GET_OBJ_PROP)
-        {
-            self.validate_params_and_results(
-                INPUT_GET_OBJ_PROP,
-                imported_shopify_function_input_get_obj_prop,
-                &[ValType::I64, ValType::I32, ValType::I32],
-                &[ValType::I64],
-            )?;
+            .get_func(PROVIDER_MODULE_NAME, import_name)
+        else {
+            return Ok(());
+        };
 
-            let shopify_function_input_get_obj_prop_type = self
-                .module
-                .types
-                .add(&[ValType::I64, ValType::I32, ValType::I32], &[ValType::I64]);
+        self.validate_params_and_results(
+            import_name,
+            imported_func,
+            &[ValType::I32, ValType::I32],
+            &[ValType::I32],
+        )?;
 
-            let (provider_shopify_function_input_get_obj_prop, _) = self.module.add_import_func(
-                PROVIDER_MODULE_NAME,
-                "_shopify_function_input_get_obj_prop",
-                shopify_function_input_get_obj_prop_type,
-            );
+        let provider_func =
+            self.provider_func_import(provider_import_name, &[ValType::I32], &[ValType::I64])?;
 
-            let alloc = self.emit_alloc();
-            let memcpy_to_provider = self.emit_memcpy_to_provider();
+        let memcpy_to_provider = self.emit_memcpy_to_provider();
 
-            let dst_ptr = self.module.locals.add(ValType::I32);
+        let output = self.module.locals.add(ValType::I64);
 
-            self.module.replace_imported_func(
-                imported_shopify_function_input_get_obj_prop,
-                |(builder, arg_locals)| {
-                    let scope = arg_locals[0];
-                    let src_ptr = arg_locals[1];
-                    let len = arg_locals[2];
+        self.module
+            .replace_imported_func(imported_func, |(builder, arg_locals)| {
+                let src_ptr = arg_locals[0];
+                let len = arg_locals[1];
 
-                    builder
-                        .func_body()
-                        .local_get(len)
-                        .call(alloc)
-                        .local_tee(dst_ptr)
-                        .local_get(src_ptr)
-                        .local_get(len)
-                        .call(memcpy_to_provider)
-                        .local_get(scope)
-                        .local_get(dst_ptr)
-                        .local_get(len)
-                        .call(provider_shopify_function_input_get_obj_prop);
-                },
-            )?;
-        }
+                builder
+                    .func_body()
+                    .local_get(len)
+                    // most significant 32 bits are the result, least significant 32 bits are the pointer
+                    .call(provider_func)
+                    .local_tee(output)
+                    // extract the result with a bit shift and wrap it to i32
+                    .i64_const(32)
+                    .binop(BinaryOp::I64ShrU)
+                    .unop(UnaryOp::I32WrapI64) // result is on the stack now
+                    // extract the pointer by wrapping the output to i32
+                    .local_get(output)
+                    .unop(UnaryOp::I32WrapI64) // dst_ptr is on the stack now
+                    .local_get(src_ptr)
+                    .local_get(len)
+                    .call(memcpy_to_provider);
+            })?;
 
         Ok(())
     }
 
-    fn emit_shopify_function_output_new_utf8_str(&mut self) -> walrus::Result<()> {
-        let Ok(imported_shopify_function_output_new_utf8_str) = self
+    fn emit_shopify_function_input_new_shape(&mut self) -> walrus::Result<()> {
+        let Ok(imported_shopify_function_input_new_shape) = self
             .module
             .imports
-            .get_func(PROVIDER_MODULE_NAME, "shopify_function_output_new_utf8_str")
+            .get_func(PROVIDER_MODULE_NAME, INPUT_NEW_SHAPE)
         else {
             return Ok(());
         };
 
         self.validate_params_and_results(
-            OUTPUT_NEW_STR,
-            imported_shopify_function_output_new_utf8_str,
+            INPUT_NEW_SHAPE,
+            imported_shopify_function_input_new_shape,
             &[ValType::I32, ValType::I32],
             &[ValType::I32],
         )?;
 
-        let shopify_function_output_new_utf8_str_type =
-            self.module.types.add(&[ValType::I32], &[ValType::I64]);
-
-        let (provider_shopify_function_output_new_utf8_str, _) = self.module.add_import_func(
-            PROVIDER_MODULE_NAME,
-            "_shopify_function_output_new_utf8_str",
-            shopify_function_output_new_utf8_str_type,
-        );
+        let provider_shopify_function_input_new_shape = self.provider_func_import(
+            "_shopify_function_input_new_shape",
+            &[ValType::I32],
+            &[ValType::I64],
+        )?;
 
         let memcpy_to_provider = self.emit_memcpy_to_provider();
 
         let output = self.module.locals.add(ValType::I64);
 
         self.module.replace_imported_func(
-            imported_shopify_function_output_new_utf8_str,
+            imported_shopify_function_input_new_shape,
             |(builder, arg_locals)| {
                 let src_ptr = arg_locals[0];
                 let len = arg_locals[1];
@@ -387,18 +1214,21 @@ impl TrampolineCodegen {
                 builder
                     .func_body()
                     .local_get(len)
-                    // most significant 32 bits are the result, least significant 32 bits are the pointer
-                    .call(provider_shopify_function_output_new_utf8_str)
+                    // most significant 32 bits are the ID, least significant 32 bits are the pointer
+                    .call(provider_shopify_function_input_new_shape)
                     .local_tee(output)
-                    // extract the result with a bit shift and wrap it to i32
+                    // extract the ID with a bit shift and wrap it to i32
                     .i64_const(32)
                     .binop(BinaryOp::I64ShrU)
-                    .unop(UnaryOp::I32WrapI64) // result is on the stack now
-                    // extract the pointer by wrapping the output to i32
+                    .unop(UnaryOp::I32WrapI64) // ID is on the stack now
+                    // extract the pointer with a bit shift and wrap it to i32
                     .local_get(output)
                     .unop(UnaryOp::I32WrapI64) // dst_ptr is on the stack now
                     .local_get(src_ptr)
+                    // interned string IDs are 4 bytes each
                     .local_get(len)
+                    .i32_const(4)
+                    .binop(BinaryOp::I32Mul)
                     .call(memcpy_to_provider);
             },
         )?;
@@ -422,14 +1252,11 @@ impl TrampolineCodegen {
             &[ValType::I32],
         )?;
 
-        let shopify_function_intern_utf8_str_type =
-            self.module.types.add(&[ValType::I32], &[ValType::I64]);
-
-        let (provider_shopify_function_intern_utf8_str, _) = self.module.add_import_func(
-            PROVIDER_MODULE_NAME,
+        let provider_shopify_function_intern_utf8_str = self.provider_func_import(
             "_shopify_function_intern_utf8_str",
-            shopify_function_intern_utf8_str_type,
-        );
+            &[ValType::I32],
+            &[ValType::I64],
+        )?;
 
         let memcpy_to_provider = self.emit_memcpy_to_provider();
 
@@ -464,27 +1291,41 @@ impl TrampolineCodegen {
     }
 
     fn emit_shopify_function_log_new_utf8_str(&mut self) -> walrus::Result<()> {
-        let Ok(imported_shopify_function_log_new_utf8_str) =
-            self.module.imports.get_func(PROVIDER_MODULE_NAME, LOG_STR)
+        self.emit_log_str(LOG_STR, false)
+    }
+
+    fn emit_shopify_function_log_new_utf8_str_with_dropped_len(&mut self) -> walrus::Result<()> {
+        self.emit_log_str(LOG_STR_WITH_DROPPED_LEN, true)
+    }
+
+    /// Emits `import_name`, which takes a pointer and a length and copies the string there to the
+    /// logs, returning the number of bytes dropped from the logs if `returns_dropped_len` is set.
+    fn emit_log_str(&mut self, import_name: &str, returns_dropped_len: bool) -> walrus::Result<()> {
+        let Ok(imported_shopify_function_log_new_utf8_str) = self
+            .module
+            .imports
+            .get_func(PROVIDER_MODULE_NAME, import_name)
         else {
             return Ok(());
         };
 
+        let results: &[ValType] = if returns_dropped_len {
+            &[ValType::I32]
+        } else {
+            &[]
+        };
         self.validate_params_and_results(
-            LOG_STR,
+            import_name,
             imported_shopify_function_log_new_utf8_str,
             &[ValType::I32, ValType::I32],
-            &[],
+            results,
         )?;
 
-        let shopify_function_log_new_utf8_str_type =
-            self.module.types.add(&[ValType::I32], &[ValType::I32]);
-
-        let (provider_shopify_function_log_new_utf8_str, _) = self.module.add_import_func(
-            PROVIDER_MODULE_NAME,
+        let provider_shopify_function_log_new_utf8_str = self.provider_func_import(
             "_shopify_function_log_new_utf8_str",
-            shopify_function_log_new_utf8_str_type,
-        );
+            &[ValType::I32],
+            &[ValType::I32],
+        )?;
 
         let memcpy_to_provider = self.emit_memcpy_to_provider();
         let provider_memory = self.provider_memory_id();
@@ -504,7 +1345,7 @@ impl TrampolineCodegen {
                 builder
                     .func_body()
                     .local_get(len)
-                    // return value is memory address for (src_offset, dst_offset1, len1, dst_offset2, len2)
+                    // return value is memory address for (src_offset, dst_offset1, len1, dst_offset2, len2, dropped_len)
                     .call(provider_shopify_function_log_new_utf8_str)
                     .local_tee(array_addr)
                     .load(
@@ -585,6 +1426,74 @@ impl TrampolineCodegen {
                         },
                         |_else| {},
                     );
+
+                if returns_dropped_len {
+                    builder.func_body().local_get(array_addr).load(
+                        provider_memory,
+                        walrus::ir::LoadKind::I32 { atomic: false },
+                        MemArg {
+                            offset: 20,
+                            align: 4,
+                        },
+                    );
+                }
+            },
+        )?;
+
+        Ok(())
+    }
+
+    fn emit_shopify_function_log_new_record(&mut self) -> walrus::Result<()> {
+        let Ok(imported_shopify_function_log_new_record) = self
+            .module
+            .imports
+            .get_func(PROVIDER_MODULE_NAME, LOG_RECORD)
+        else {
+            return Ok(());
+        };
+
+        self.validate_params_and_results(
+            LOG_RECORD,
+            imported_shopify_function_log_new_record,
+            &[ValType::I32, ValType::I32, ValType::I32],
+            &[ValType::I32],
+        )?;
+
+        let provider_shopify_function_log_new_record = self.provider_func_import(
+            "_shopify_function_log_new_record",
+            &[ValType::I32, ValType::I32],
+            &[ValType::I64],
+        )?;
+
+        let memcpy_to_provider = self.emit_memcpy_to_provider();
+
+        let output = self.module.locals.add(ValType::I64);
+
+        self.module.replace_imported_func(
+            imported_shopify_function_log_new_record,
+            |(builder, arg_locals)| {
+                let level = arg_locals[0];
+                let src_ptr = arg_locals[1];
+                let len = arg_locals[2];
+
+                builder
+                    .func_body()
+                    .local_get(level)
+                    .local_get(len)
+                    // most significant 32 bits are the number of records dropped, least significant
+                    // 32 bits are the pointer
+                    .call(provider_shopify_function_log_new_record)
+                    .local_tee(output)
+                    // extract the number of records dropped with a bit shift and wrap it to i32
+                    .i64_const(32)
+                    .binop(BinaryOp::I64ShrU)
+                    .unop(UnaryOp::I32WrapI64) // number of records dropped is on the stack now
+                    // extract the pointer by wrapping the output to i32
+                    .local_get(output)
+                    .unop(UnaryOp::I32WrapI64) // dst_ptr is on the stack now
+                    .local_get(src_ptr)
+                    .local_get(len)
+                    .call(memcpy_to_provider);
             },
         )?;
 
@@ -598,12 +1507,7 @@ impl TrampolineCodegen {
         }
 
         if let Some(unexpected_import) = self.module.imports.iter().find(|import| {
-            import.module == PROVIDER_MODULE_NAME
-                && (!IMPORTS.iter().any(|(orig_name, new_name)| {
-                    *orig_name == import.name || *new_name == import.name
-                }) && import.name != "_shopify_function_input_get_utf8_str_addr"
-                    && import.name != "_shopify_function_alloc"
-                    && import.name != "memory")
+            import.module == PROVIDER_MODULE_NAME && !is_known_provider_import(&import.name)
         }) {
             bail!(
                 "Found unexpected import named `{}`. Ensure your Shopify CLI is up-to-date and any Wasm imports are correct.",
@@ -620,17 +1524,61 @@ impl TrampolineCodegen {
             );
         }
 
+        let instrumentation = self
+            .instrument
+            .then(|| instrument::Instrumentation::new(&self.module))
+            .transpose()?;
+
         for (original, new) in IMPORTS {
             match *original {
                 INPUT_READ_UTF8_STR => self.emit_shopify_function_input_read_utf8_str()?,
+                INPUT_READ_UTF8_STR_WITH_LEN => {
+                    self.emit_shopify_function_input_read_utf8_str_with_len()?
+                }
+                INPUT_READ_UTF8_STR_CHUNK => {
+                    self.emit_shopify_function_input_read_utf8_str_chunk()?
+                }
+                INPUT_READ_MSGPACK_WITH_LEN => {
+                    self.emit_shopify_function_input_read_msgpack_with_len()?
+                }
+                INPUT_READ_BOOL_BITS_WITH_LEN => {
+                    self.emit_shopify_function_input_read_bool_bits_with_len()?
+                }
+                INPUT_READ_UTF8_STR_ALLOC => {
+                    self.emit_shopify_function_input_read_utf8_str_alloc()?
+                }
+                INPUT_READ_ARRAY_ELEMENTS => {
+                    self.emit_shopify_function_input_read_array_elements()?
+                }
+                INPUT_GET_OBJ_ENTRY_AT_INDEX => {
+                    self.emit_shopify_function_input_get_obj_entry_at_index()?
+                }
+                INPUT_NEW_SHAPE => self.emit_shopify_function_input_new_shape()?,
+                INPUT_READ_SHAPE => self.emit_shopify_function_input_read_shape()?,
                 INPUT_GET_OBJ_PROP => self.emit_shopify_function_input_get_obj_prop()?,
+                INPUT_UTF8_STR_TRIM_EQ => self.emit_shopify_function_input_utf8_str_trim_eq()?,
                 OUTPUT_NEW_STR => self.emit_shopify_function_output_new_utf8_str()?,
+                OUTPUT_NEW_BYTES => self.emit_shopify_function_output_new_bytes()?,
+                OUTPUT_NEW_MSGPACK => self.emit_shopify_function_output_new_msgpack()?,
+                OUTPUT_WRITE_STR_CHUNK => {
+                    self.emit_shopify_function_output_write_utf8_str_chunk()?
+                }
                 INTERN_STR => self.emit_shopify_function_intern_utf8_str()?,
                 LOG_STR => self.emit_shopify_function_log_new_utf8_str()?,
+                LOG_STR_WITH_DROPPED_LEN => {
+                    self.emit_shopify_function_log_new_utf8_str_with_dropped_len()?
+                }
+                LOG_RECORD => self.emit_shopify_function_log_new_record()?,
                 original => self.rename_imported_func(original, new)?,
             };
         }
 
+        self.emit_start_for_wasi_command()?;
+
+        if let Some(instrumentation) = instrumentation {
+            instrumentation.apply(&mut self.module);
+        }
+
         wasmparser::validate(&self.module.emit_wasm())
             .context("Validating output module failed")?;
         Ok(self.module)
@@ -663,7 +1611,7 @@ impl TrampolineCodegen {
 
 #[cfg(test)]
 mod test {
-    use super::{TrampolineCodegen, IMPORTS, PROVIDER_MODULE_NAME};
+    use super::{TrampolineCodegen, TrampolineOptions, IMPORTS, PROVIDER_MODULE_NAME};
     use walrus::Module;
 
     fn trampoline_wat(wat_bytes: &[u8]) -> walrus::Result<String> {
@@ -853,6 +1801,19 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_wrong_result_type_for_get_obj_entry_at_index() {
+        let module = r#"
+        (module
+            (import "shopify_function_v2" "shopify_function_input_get_obj_entry_at_index" (func (param i64 i32 i32) (result i64)))
+            (memory 1)
+        )
+        "#;
+        let result = trampoline_wat(module.as_bytes());
+        let err = result.unwrap_err();
+        assert_eq!(format!("{err:?}"), "Results for shopify_function_input_get_obj_entry_at_index are incorrect. Expected [], got [I64].");
+    }
+
     #[test]
     fn test_wrong_param_type_for_log_str() {
         let module = r#"
@@ -882,6 +1843,134 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_wrong_param_type_for_log_record() {
+        let module = r#"
+        (module
+            (import "shopify_function_v2" "shopify_function_log_new_record" (func (param i32 i32) (result i32)))
+            (memory 1)
+        )
+        "#;
+        let result = trampoline_wat(module.as_bytes());
+        let err = result.unwrap_err();
+        assert_eq!(format!("{err:?}"), "Params for shopify_function_log_new_record are incorrect. Expected [I32, I32, I32], got [I32, I32].");
+    }
+
+    #[test]
+    fn test_read_utf8_str_alloc_without_guest_allocator() {
+        let module = r#"
+        (module
+            (import "shopify_function_v2" "shopify_function_input_read_utf8_str_alloc" (func (param i64) (result i64)))
+            (memory 1)
+        )
+        "#;
+        let result = trampoline_wat(module.as_bytes());
+        let err = result.unwrap_err();
+        assert_eq!(format!("{err:?}"), "shopify_function_input_read_utf8_str_alloc allocates with the module's allocator, but the module does not export one. Export one of [\"cabi_realloc\", \"canonical_abi_realloc\", \"malloc\"].");
+
+        // TinyGo exports `malloc`
+        let module = r#"
+        (module
+            (import "shopify_function_v2" "shopify_function_input_read_utf8_str_alloc" (func (param i64) (result i64)))
+            (memory 1)
+            (func (export "malloc") (param i32) (result i32) i32.const 0)
+        )
+        "#;
+        assert!(trampoline_wat(module.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_start_for_wasi_command() {
+        let module = r#"
+        (module
+            (memory 1)
+            (func (export "wasi:cli/run@0.2.0#run") (result i32) i32.const 0)
+        )
+        "#;
+        let result = trampoline_wat(module.as_bytes()).unwrap();
+        assert!(result.contains(r#"(export "_start""#), "{result}");
+
+        // modules exporting `_start` are left alone
+        let module = r#"
+        (module
+            (memory 1)
+            (func (export "_start"))
+            (func (export "wasi:cli/run@0.2.0#run") (result i32) i32.const 0)
+        )
+        "#;
+        let result = trampoline_wat(module.as_bytes()).unwrap();
+        assert_eq!(result.matches("(export \"_start\"").count(), 1, "{result}");
+    }
+
+    #[test]
+    fn test_existing_provider_import_with_different_signature() {
+        let module = r#"
+        (module
+            (import "shopify_function_v2" "shopify_function_input_read_utf8_str_with_len" (func (param i64 i32 i32) (result i32)))
+            (import "shopify_function_v2" "_shopify_function_input_get_utf8_str_addr_and_len" (func (param i32) (result i64)))
+            (memory 1)
+        )
+        "#;
+        let err = trampoline_wat(module.as_bytes()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Params for _shopify_function_input_get_utf8_str_addr_and_len are incorrect. Expected [I64], got [I32]."
+        );
+    }
+
+    #[test]
+    fn test_memory_export_aliasing_imported_memory() {
+        let module = r#"
+        (module
+            (import "env" "memory" (memory 1))
+            (memory 1)
+            (export "memory" (memory 0))
+        )
+        "#;
+        let err = trampoline_wat(module.as_bytes()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The `memory` export is the memory imported from `env::memory`, not the module's own memory. Export the module's own memory as `memory`, or rename the export with `--rename-memory-export <NAME>`."
+        );
+
+        let wasm = wat::parse_str(module).unwrap();
+        let options = TrampolineOptions {
+            rename_memory_export: Some("env_memory".to_string()),
+            ..Default::default()
+        };
+        let mut result =
+            TrampolineCodegen::new_with_options(Module::from_buffer(&wasm).unwrap(), &options)
+                .unwrap()
+                .apply()
+                .unwrap();
+        let result = wasmprinter::print_bytes(result.emit_wasm()).unwrap();
+        assert!(
+            result.contains(r#"(export "env_memory" (memory 0))"#),
+            "{result}"
+        );
+
+        // the new name can't already be taken
+        let options = TrampolineOptions {
+            rename_memory_export: Some("memory2".to_string()),
+            ..Default::default()
+        };
+        let wasm = wat::parse_str(
+            r#"(module
+                (import "env" "memory" (memory 1))
+                (memory 1)
+                (export "memory" (memory 0))
+                (export "memory2" (memory 1))
+            )"#,
+        )
+        .unwrap();
+        let Err(err) =
+            TrampolineCodegen::new_with_options(Module::from_buffer(&wasm).unwrap(), &options)
+        else {
+            panic!("expected an error");
+        };
+        assert_eq!(err.to_string(), "Cannot rename the `memory` export to `memory2`, as there is already an export with that name.");
+    }
+
     #[test]
     fn test_unexpected_import() {
         let module = r#"
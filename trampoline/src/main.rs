@@ -1,26 +1,105 @@
-use std::{path::PathBuf, process};
+use std::{fs, path::PathBuf, process};
 
-use clap::Parser;
-use shopify_function_trampoline::trampoline_existing_module;
+use clap::{Parser, Subcommand};
+use shopify_function_trampoline::{
+    trampoline_existing_module_with_options,
+    validate::{validate_module, Severity, ValidateOptions, DEFAULT_MAX_MODULE_SIZE},
+    TrampolineOptions,
+};
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Path to input Wasm file
-    #[arg(short, long)]
-    input: PathBuf,
+    #[arg(short, long, required = true)]
+    input: Option<PathBuf>,
 
     /// Path to output Wasm file
-    #[arg(short, long)]
-    output: PathBuf,
+    #[arg(short, long, required = true)]
+    output: Option<PathBuf>,
+
+    /// Count calls to the provider, which are read with the `__sf_call_counts` export
+    #[arg(long)]
+    instrument: bool,
+
+    /// Rename the module's `memory` export, for modules exporting an imported memory as `memory`
+    #[arg(long, value_name = "NAME")]
+    rename_memory_export: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check a function Wasm module for common problems
+    Validate {
+        /// Path to the Wasm file to validate
+        path: PathBuf,
+
+        /// Maximum module size in bytes
+        #[arg(long, default_value_t = DEFAULT_MAX_MODULE_SIZE)]
+        max_size: usize,
+
+        /// Report imports that read clocks or random numbers as errors
+        #[arg(long)]
+        deterministic: bool,
+    },
 }
 
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
-    if let Err(err) = trampoline_existing_module(args.input, args.output) {
-        eprintln!("Error: {err:?}");
+    match args.command {
+        Some(Command::Validate {
+            path,
+            max_size,
+            deterministic,
+        }) => validate(
+            path,
+            ValidateOptions {
+                max_module_size: max_size,
+                deterministic,
+            },
+        ),
+        None => {
+            let (Some(input), Some(output)) = (args.input, args.output) else {
+                unreachable!("input and output are required without a subcommand");
+            };
+            let options = TrampolineOptions {
+                instrument: args.instrument,
+                rename_memory_export: args.rename_memory_export,
+            };
+            if let Err(err) = trampoline_existing_module_with_options(input, output, &options) {
+                eprintln!("Error: {err:?}");
+                process::exit(1);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn validate(path: PathBuf, options: ValidateOptions) -> anyhow::Result<()> {
+    let result = fs::read(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|bytes| validate_module(&bytes, &options));
+    let diagnostics = match result {
+        Ok(diagnostics) => diagnostics,
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            process::exit(1);
+        }
+    };
+
+    for diagnostic in &diagnostics {
+        eprintln!("{diagnostic}");
+    }
+    if diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Error)
+    {
         process::exit(1);
     }
+    println!("{} is a valid function module", path.display());
     Ok(())
 }
@@ -1,33 +1,83 @@
 (module
     ;; General
     (import "shopify_function_v2" "shopify_function_intern_utf8_str" (func (param i32 i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_interned_utf8_str_exists" (func (param i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_provider_version" (func (result i64)))
+    (import "shopify_function_v2" "shopify_function_deterministic_seed" (func (result i64)))
+    (import "shopify_function_v2" "shopify_function_set_work_budget" (func (param i32)))
+    (import "shopify_function_v2" "shopify_function_host_call_start" (func (param i32 i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_host_call_poll" (func (param i32) (result i64)))
 
     ;; Read.
     (import "shopify_function_v2" "shopify_function_input_get" (func (result i64)))
     (import "shopify_function_v2" "shopify_function_input_get_obj_prop" (func (param i64 i32 i32) (result i64)))
     (import "shopify_function_v2" "shopify_function_input_get_interned_obj_prop" (func (param i64 i32) (result i64)))
     (import "shopify_function_v2" "shopify_function_input_get_at_index" (func (param i64 i32) (result i64)))
+    (import "shopify_function_v2" "shopify_function_input_get_array_slice" (func (param i64 i32 i32) (result i64)))
     (import "shopify_function_v2" "shopify_function_input_get_obj_key_at_index" (func (param i64 i32) (result i64)))
+    (import "shopify_function_v2" "shopify_function_input_get_interned_obj_key_at_index" (func (param i64 i32) (result i32)))
     (import "shopify_function_v2" "shopify_function_input_get_val_len" (func (param i64) (result i32)))
     (import "shopify_function_v2" "shopify_function_input_read_utf8_str" (func (param i32 i32 i32)))
+    (import "shopify_function_v2" "shopify_function_input_read_utf8_str_with_len" (func (param i64 i32 i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_input_materialize" (func (param i64) (result i64)))
+    (import "shopify_function_v2" "shopify_function_input_iter_new" (func (param i64) (result i32)))
+    (import "shopify_function_v2" "shopify_function_input_iter_next" (func (param i32) (result i64)))
+    (import "shopify_function_v2" "shopify_function_input_iter_key" (func (param i32) (result i64)))
+    (import "shopify_function_v2" "shopify_function_input_iter_drop" (func (param i32)))
+    (import "shopify_function_v2" "shopify_function_input_pin" (func (param i64) (result i32)))
+    (import "shopify_function_v2" "shopify_function_input_get_pinned" (func (param i32) (result i64)))
+    (import "shopify_function_v2" "shopify_function_input_unpin" (func (param i32)))
+    (import "shopify_function_v2" "shopify_function_input_read_array_elements" (func (param i64 i32 i32 i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_input_new_shape" (func (param i32 i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_input_read_shape" (func (param i64 i32 i32 i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_input_get_obj_entry_at_index" (func (param i64 i32 i32)))
+    (import "shopify_function_v2" "shopify_function_input_read_utf8_str_chunk" (func (param i64 i32 i32 i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_input_read_msgpack_with_len" (func (param i64 i32 i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_input_read_bool_bits_with_len" (func (param i64 i32 i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_input_read_utf8_str_alloc" (func (param i64) (result i64)))
+    (import "shopify_function_v2" "shopify_function_input_utf8_str_trim_eq" (func (param i64 i32 i32 i32) (result i64)))
 
     ;; Write.
     (import "shopify_function_v2" "shopify_function_output_new_bool" (func (param i32) (result i32)))
     (import "shopify_function_v2" "shopify_function_output_new_null" (func (result i32)))
     (import "shopify_function_v2" "shopify_function_output_new_i32" (func (param i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_output_new_i64" (func (param i64) (result i32)))
+    (import "shopify_function_v2" "shopify_function_output_new_u64" (func (param i64) (result i32)))
     (import "shopify_function_v2" "shopify_function_output_new_f64" (func (param f64) (result i32)))
     (import "shopify_function_v2" "shopify_function_output_new_utf8_str" (func (param i32 i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_output_new_bytes" (func (param i32 i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_output_new_msgpack" (func (param i32 i32) (result i32)))
     (import "shopify_function_v2" "shopify_function_output_new_object" (func (param i32) (result i32)))
     (import "shopify_function_v2" "shopify_function_output_finish_object" (func (result i32)))
     (import "shopify_function_v2" "shopify_function_output_new_array" (func (param i32) (result i32)))
     (import "shopify_function_v2" "shopify_function_output_finish_array" (func (result i32)))
     (import "shopify_function_v2" "shopify_function_output_new_interned_utf8_str" (func (param i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_output_new_dynamic_array" (func (result i32)))
+    (import "shopify_function_v2" "shopify_function_output_discard" (func (result i32)))
+    (import "shopify_function_v2" "shopify_function_output_checkpoint" (func (result i32)))
+    (import "shopify_function_v2" "shopify_function_output_rollback" (func (param i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_output_set_skip_nulls" (func (param i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_output_len" (func (result i32)))
+    (import "shopify_function_v2" "shopify_function_dedup_set_new" (func (result i32)))
+    (import "shopify_function_v2" "shopify_function_dedup_set_insert_interned" (func (param i32 i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_dedup_set_insert_hash" (func (param i32 i64) (result i32)))
+    (import "shopify_function_v2" "shopify_function_dedup_set_drop" (func (param i32)))
+    (import "shopify_function_v2" "shopify_function_output_new_dynamic_utf8_str" (func (param i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_output_write_utf8_str_chunk" (func (param i32 i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_output_finish_utf8_str" (func (result i32)))
+    (import "shopify_function_v2" "shopify_function_output_new_input_value" (func (param i64) (result i32)))
 
     ;; Log.
     (import "shopify_function_v2" "shopify_function_log_new_utf8_str" (func (param i32 i32)))
+    (import "shopify_function_v2" "shopify_function_log_new_utf8_str_with_dropped_len" (func (param i32 i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_log_new_record" (func (param i32 i32 i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_log_interned_strings" (func (result i32)))
 
     ;; Memory
     (memory 1)
 
     (export "memory" (memory 0))
+
+    ;; Allocator, for imports that allocate in guest memory
+    (func (export "cabi_realloc") (param i32 i32 i32 i32) (result i32) i32.const 0)
 )
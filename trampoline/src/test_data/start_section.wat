@@ -0,0 +1,29 @@
+(module
+    ;; a module with a start section, which runs when it's instantiated, and imports the provider
+    ;; functions and memory the trampoline emits, which must not be imported again
+    (import "shopify_function_v2" "shopify_function_input_read_utf8_str_with_len" (func $read_utf8_str_with_len (param i64 i32 i32) (result i32)))
+    (import "shopify_function_v2" "shopify_function_input_read_utf8_str_alloc" (func $read_utf8_str_alloc (param i64) (result i64)))
+    (import "shopify_function_v2" "shopify_function_log_new_utf8_str" (func $log (param i32 i32)))
+    (import "shopify_function_v2" "_shopify_function_input_get_utf8_str_addr_and_len" (func $get_utf8_str_addr_and_len (param i64) (result i64)))
+    (import "shopify_function_v2" "memory" (memory 1))
+    (memory (export "memory") 1)
+    (func $malloc (export "malloc") (param i32) (result i32) i32.const 0)
+    (func $init
+        i64.const 0
+        i32.const 0
+        i32.const 0
+        call $read_utf8_str_with_len
+        drop
+        i64.const 0
+        call $read_utf8_str_alloc
+        drop
+        i64.const 0
+        call $get_utf8_str_addr_and_len
+        drop
+        i32.const 0
+        i32.const 0
+        call $log
+    )
+    (start $init)
+    (func (export "_start"))
+)
@@ -0,0 +1,321 @@
+//! Lints for Shopify Function Wasm modules.
+//!
+//! These checks catch common problems with function modules before they are deployed, and work on
+//! modules both before and after the trampoline has been applied.
+
+use std::fmt;
+
+use anyhow::{Context, Result};
+use walrus::{ExportItem, Module};
+
+use crate::{
+    guest_alloc::{GuestAllocator, GUEST_ALLOCATOR_EXPORTS},
+    is_known_provider_import, INPUT_READ_UTF8_STR_ALLOC, PROVIDER_MODULE_NAME, WASI_CLI_RUN_EXPORT,
+};
+
+/// The module name of WASI preview 1 imports.
+const WASI_MODULE_NAME: &str = "wasi_snapshot_preview1";
+
+/// The prefix of the module names of WASI 0.2 imports, e.g. `wasi:cli/stdout@0.2.0`.
+const WASI_0_2_MODULE_PREFIX: &str = "wasi:";
+
+/// WASI imports commonly pulled in by language runtimes that are supported when running functions.
+const ALLOWED_WASI_IMPORTS: &[&str] = &[
+    "args_get",
+    "args_sizes_get",
+    "clock_time_get",
+    "environ_get",
+    "environ_sizes_get",
+    "fd_close",
+    "fd_fdstat_get",
+    "fd_seek",
+    "fd_write",
+    "proc_exit",
+    "random_get",
+];
+
+/// WASI imports that read clocks or random numbers, which make functions behave differently each
+/// time they run with the same input.
+const NON_DETERMINISTIC_WASI_IMPORTS: &[&str] = &["clock_res_get", "clock_time_get", "random_get"];
+
+/// The default maximum module size in bytes.
+pub const DEFAULT_MAX_MODULE_SIZE: usize = 256 * 1024;
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// The module will not work as a function.
+    Error,
+    /// The module may work, but should be fixed.
+    Warning,
+}
+
+/// A problem found while validating a module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// How serious the problem is.
+    pub severity: Severity,
+    /// A description of the problem and how to fix it.
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+        }
+    }
+
+    fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error: {}", self.message),
+            Severity::Warning => write!(f, "warning: {}", self.message),
+        }
+    }
+}
+
+/// Options for [`validate_module`].
+#[derive(Clone, Debug)]
+pub struct ValidateOptions {
+    /// Modules larger than this many bytes are reported as errors.
+    pub max_module_size: usize,
+    /// Whether WASI imports that read clocks or random numbers are reported as errors, for
+    /// functions that must behave the same every time they run with the same input.
+    pub deterministic: bool,
+}
+
+impl Default for ValidateOptions {
+    fn default() -> Self {
+        Self {
+            max_module_size: DEFAULT_MAX_MODULE_SIZE,
+            deterministic: false,
+        }
+    }
+}
+
+/// Validate a function module, returning any problems found. Fails if the bytes are not a valid
+/// Wasm module.
+pub fn validate_module(bytes: &[u8], options: &ValidateOptions) -> Result<Vec<Diagnostic>> {
+    let module = Module::from_buffer(bytes).context("Parsing module failed")?;
+    let mut diagnostics = Vec::new();
+
+    let guest_memories = module
+        .memories
+        .iter()
+        .filter(|memory| {
+            memory
+                .import
+                .is_none_or(|import| module.imports.get(import).module != PROVIDER_MODULE_NAME)
+        })
+        .count();
+    if guest_memories != 1 {
+        diagnostics.push(Diagnostic::error(format!(
+            "Expected exactly one memory, found {guest_memories}. Functions must define a single memory; if you are using Rust, ensure you are not enabling multi-memory."
+        )));
+    }
+
+    // the trampoline exports `_start` for WASI 0.2 commands
+    let has_start = module.exports.iter().any(|export| {
+        (export.name == "_start" || export.name == WASI_CLI_RUN_EXPORT)
+            && matches!(export.item, ExportItem::Function(_))
+    });
+    if !has_start {
+        diagnostics.push(Diagnostic::error(
+            "Missing `_start` function export. Functions are invoked through `_start`; if you are using Rust, export your entrypoint with `#[export_name = \"_start\"]` or build a binary crate.",
+        ));
+    }
+
+    for import in module.imports.iter() {
+        if import.module == PROVIDER_MODULE_NAME {
+            if !is_known_provider_import(&import.name) {
+                diagnostics.push(Diagnostic::error(format!(
+                    "Unknown import `{}` from `{PROVIDER_MODULE_NAME}`. Ensure your Shopify CLI and function API dependencies are up-to-date.",
+                    import.name
+                )));
+            } else if import.name == INPUT_READ_UTF8_STR_ALLOC
+                && GuestAllocator::find(&module).is_none()
+            {
+                diagnostics.push(Diagnostic::error(format!(
+                    "Import `{INPUT_READ_UTF8_STR_ALLOC}` allocates with the module's allocator, but the module does not export one. Export one of {GUEST_ALLOCATOR_EXPORTS:?}."
+                )));
+            }
+        } else if import.module.starts_with("shopify_function_v") {
+            diagnostics.push(Diagnostic::error(format!(
+                "Import `{}` is from unsupported module `{}`. Imports must be from `{PROVIDER_MODULE_NAME}`.",
+                import.name, import.module
+            )));
+        } else if import.module == WASI_MODULE_NAME {
+            if options.deterministic
+                && NON_DETERMINISTIC_WASI_IMPORTS.contains(&import.name.as_str())
+            {
+                diagnostics.push(Diagnostic::error(format!(
+                    "WASI import `{}` reads clocks or random numbers, so the function may not behave the same every time it runs with the same input. Seed pseudo-random number generators with the input instead, e.g. with `Context::deterministic_seed`; if you are using Rust, note that `HashMap::new` seeds itself randomly.",
+                    import.name
+                )));
+            } else if !ALLOWED_WASI_IMPORTS.contains(&import.name.as_str()) {
+                diagnostics.push(Diagnostic::warning(format!(
+                    "WASI import `{}` is not supported and will fail if called. Avoid using the APIs that require it, such as file system or network access.",
+                    import.name
+                )));
+            }
+        } else if import.module.starts_with(WASI_0_2_MODULE_PREFIX) {
+            diagnostics.push(Diagnostic::warning(format!(
+                "WASI 0.2 import `{}` from `{}` is not supported and will fail if called. Avoid using the APIs that require it, such as reading stdin or writing stdout.",
+                import.name, import.module
+            )));
+        } else {
+            diagnostics.push(Diagnostic::error(format!(
+                "Import `{}` is from unknown module `{}`. Functions can only import from `{PROVIDER_MODULE_NAME}` and `{WASI_MODULE_NAME}`.",
+                import.name, import.module
+            )));
+        }
+    }
+
+    if bytes.len() > options.max_module_size {
+        diagnostics.push(Diagnostic::error(format!(
+            "Module is {} bytes, which exceeds the limit of {} bytes. Build in release mode and consider optimizing for size (e.g. `opt-level = \"z\"`, `lto = true`, `strip = true`).",
+            bytes.len(),
+            options.max_module_size
+        )));
+    }
+
+    Ok(diagnostics)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn validate_wat(wat: &str, options: &ValidateOptions) -> Vec<Diagnostic> {
+        let bytes = wat::parse_str(wat).unwrap();
+        validate_module(&bytes, options).unwrap()
+    }
+
+    #[test]
+    fn test_valid_module() {
+        let diagnostics = validate_wat(
+            r#"(module
+                (import "shopify_function_v2" "shopify_function_input_get" (func (result i64)))
+                (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (func (export "_start"))
+            )"#,
+            &ValidateOptions::default(),
+        );
+        assert_eq!(diagnostics, vec![]);
+    }
+
+    #[test]
+    fn test_valid_trampolined_module() {
+        let diagnostics = validate_wat(
+            r#"(module
+                (import "shopify_function_v2" "memory" (memory 1))
+                (import "shopify_function_v2" "_shopify_function_input_get" (func (result i64)))
+                (memory (export "memory") 1)
+                (func (export "_start"))
+            )"#,
+            &ValidateOptions::default(),
+        );
+        assert_eq!(diagnostics, vec![]);
+    }
+
+    #[test]
+    fn test_invalid_module() {
+        let diagnostics = validate_wat(
+            r#"(module
+                (import "shopify_function_v2" "shopify_function_unknown" (func))
+                (import "shopify_function_v1" "shopify_function_input_get" (func (result i64)))
+                (import "wasi_snapshot_preview1" "path_open" (func))
+                (import "env" "foo" (func))
+                (func (export "main"))
+            )"#,
+            &ValidateOptions {
+                max_module_size: 10,
+                ..Default::default()
+            },
+        );
+        let diagnostics = diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        insta::assert_debug_snapshot!(diagnostics);
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let wat = r#"(module
+            (import "wasi_snapshot_preview1" "random_get" (func (param i32 i32) (result i32)))
+            (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (func (export "_start"))
+        )"#;
+        assert_eq!(validate_wat(wat, &ValidateOptions::default()), vec![]);
+        let diagnostics = validate_wat(
+            wat,
+            &ValidateOptions {
+                deterministic: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert!(diagnostics[0].message.contains("`random_get`"));
+    }
+
+    #[test]
+    fn test_wasi_command() {
+        let diagnostics = validate_wat(
+            r#"(module
+                (import "wasi:cli/stdout@0.2.0" "get-stdout" (func (result i32)))
+                (memory (export "memory") 1)
+                (func (export "wasi:cli/run@0.2.0#run") (result i32) i32.const 0)
+            )"#,
+            &ValidateOptions::default(),
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert!(diagnostics[0].message.contains("`get-stdout`"));
+    }
+
+    #[test]
+    fn test_guest_allocator() {
+        let wat = |allocator: &str| {
+            format!(
+                r#"(module
+                    (import "shopify_function_v2" "shopify_function_input_read_utf8_str_alloc" (func (param i64) (result i64)))
+                    (memory (export "memory") 1)
+                    (func (export "_start"))
+                    {allocator}
+                )"#
+            )
+        };
+        let options = ValidateOptions::default();
+        assert_eq!(
+            validate_wat(
+                &wat(r#"(func (export "malloc") (param i32) (result i32) i32.const 0)"#),
+                &options
+            ),
+            vec![]
+        );
+        let diagnostics = validate_wat(&wat(""), &options);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert!(diagnostics[0].message.contains("cabi_realloc"));
+    }
+
+    #[test]
+    fn test_invalid_bytes() {
+        let err = validate_module(b"not wasm", &ValidateOptions::default()).unwrap_err();
+        assert_eq!(err.to_string(), "Parsing module failed");
+    }
+}
@@ -123,3 +123,31 @@ fn test_overwrites_existing_output_file() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_cli_validates_wasm_module() -> Result<()> {
+    ECHO_EXAMPLE
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("Failed to build example: {}", e))?;
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .args(["validate", echo_module_path().to_str().unwrap()])
+        .assert()
+        .success()
+        .code(0)
+        .stdout(predicates::str::contains("is a valid function module"));
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .args([
+            "validate",
+            echo_module_path().to_str().unwrap(),
+            "--max-size",
+            "1",
+        ])
+        .assert()
+        .failure()
+        .code(1)
+        .stderr(predicates::str::contains("error: Module is"));
+
+    Ok(())
+}
@@ -0,0 +1,288 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::{bail, Context};
+use shopify_function_trampoline::validate::{validate_module, Severity, ValidateOptions};
+use wasmparser::{Parser, Payload};
+
+/// The WASI 0.2 target, whose linker wraps modules in a component unless told not to.
+const WASIP2_TARGET: &str = "wasm32-wasip2";
+
+/// Build a function crate to Wasm, apply the trampoline, validate the result, and print a report.
+pub fn build_function(package: &str, target: &str, output: Option<PathBuf>) -> anyhow::Result<()> {
+    let mut command = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".into()));
+    // the artifact is found from cargo's messages, as the function may be in another workspace with
+    // its own target directory
+    command
+        .args(["build", "--release", "--target", target, "-p", package])
+        .arg("--message-format=json-render-diagnostics")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit());
+    if target == WASIP2_TARGET {
+        // functions are core modules
+        let (name, value) = rustflags_env(target, "-Clink-arg=--skip-wit-component", |name| {
+            env::var(name).ok()
+        });
+        command.env(name, value);
+    }
+    let build = command.output().context("Failed to run cargo build")?;
+    if !build.status.success() {
+        bail!("Building `{package}` failed: {}", build.status);
+    }
+
+    let input = wasm_artifact(&String::from_utf8_lossy(&build.stdout), package)
+        .with_context(|| format!("Cargo didn't report a Wasm binary built for `{package}`"))?;
+    let output = output.unwrap_or_else(|| input.with_extension("merged.wasm"));
+    shopify_function_trampoline::trampoline_existing_module(&input, &output)
+        .with_context(|| format!("Failed to apply trampoline to `{}`", input.display()))?;
+
+    let bytes = fs::read(&output)?;
+    check_module(&bytes)
+        .with_context(|| format!("`{}` is not a valid function module", output.display()))?;
+
+    let report = Report::new(&bytes)?;
+    println!("Built {}", output.display());
+    println!(
+        "Size: {} bytes (before trampoline: {} bytes)",
+        bytes.len(),
+        fs::metadata(&input)?.len()
+    );
+    print!("{report}");
+    Ok(())
+}
+
+/// Run the same lints as the trampoline's `validate` command, printing any problems found, and fail
+/// if any of them are errors.
+fn check_module(bytes: &[u8]) -> anyhow::Result<()> {
+    let diagnostics = validate_module(bytes, &ValidateOptions::default())?;
+    for diagnostic in &diagnostics {
+        eprintln!("{diagnostic}");
+    }
+    let errors = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .count();
+    if errors > 0 {
+        bail!("Validation found {errors} error(s)");
+    }
+    Ok(())
+}
+
+/// The environment variable and value that add `flag` to the flags passed to rustc when building
+/// for `target`, keeping any flags already set with `var`. Cargo only reads the first of these
+/// variables that is set, so the flag is added to that one.
+fn rustflags_env(
+    target: &str,
+    flag: &str,
+    var: impl Fn(&str) -> Option<String>,
+) -> (String, String) {
+    let target_var = format!(
+        "CARGO_TARGET_{}_RUSTFLAGS",
+        target.to_uppercase().replace(['-', '.'], "_")
+    );
+    let (name, separator) = if var("CARGO_ENCODED_RUSTFLAGS").is_some() {
+        (String::from("CARGO_ENCODED_RUSTFLAGS"), '\x1f')
+    } else if var("RUSTFLAGS").is_some() {
+        (String::from("RUSTFLAGS"), ' ')
+    } else {
+        (target_var, ' ')
+    };
+    let value = match var(&name).filter(|flags| !flags.is_empty()) {
+        Some(flags) => format!("{flags}{separator}{flag}"),
+        None => flag.to_string(),
+    };
+    (name, value)
+}
+
+/// The path of the Wasm binary named `package` in the messages of a `cargo build` run with
+/// `--message-format=json`, one JSON object per line.
+fn wasm_artifact(messages: &str, package: &str) -> Option<PathBuf> {
+    messages
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter(|message| {
+            message["reason"] == "compiler-artifact"
+                && message["target"]["name"] == package
+                && message["target"]["kind"]
+                    .as_array()
+                    .is_some_and(|kinds| kinds.iter().any(|kind| kind == "bin"))
+        })
+        .flat_map(|message| match message["filenames"].as_array() {
+            Some(filenames) => filenames.clone(),
+            None => Vec::new(),
+        })
+        .filter_map(|filename| filename.as_str().map(PathBuf::from))
+        .find(|filename| filename.extension().is_some_and(|ext| ext == "wasm"))
+}
+
+pub(crate) fn target_dir() -> PathBuf {
+    env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| workspace_root().join("target"))
+}
+
+pub(crate) fn workspace_root() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask is in the workspace root")
+}
+
+/// The imports and exports of a Wasm module.
+#[derive(Debug, Default, PartialEq)]
+struct Report {
+    imports: Vec<String>,
+    exports: Vec<String>,
+}
+
+impl Report {
+    fn new(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut report = Report::default();
+        for payload in Parser::new(0).parse_all(bytes) {
+            match payload? {
+                Payload::ImportSection(section) => {
+                    for import in section.into_imports() {
+                        let import = import?;
+                        report
+                            .imports
+                            .push(format!("{}.{}", import.module, import.name));
+                    }
+                }
+                Payload::ExportSection(section) => {
+                    for export in section {
+                        report.exports.push(export?.name.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(report)
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Imports:")?;
+        for import in &self.imports {
+            writeln!(f, "  {import}")?;
+        }
+        writeln!(f, "Exports:")?;
+        for export in &self.exports {
+            writeln!(f, "  {export}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rustflags_env() {
+        let flag = "-Clink-arg=--skip-wit-component";
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+
+        assert_eq!(
+            rustflags_env(WASIP2_TARGET, flag, env(&[])),
+            ("CARGO_TARGET_WASM32_WASIP2_RUSTFLAGS".into(), flag.into())
+        );
+        assert_eq!(
+            rustflags_env(
+                WASIP2_TARGET,
+                flag,
+                env(&[("CARGO_TARGET_WASM32_WASIP2_RUSTFLAGS", "-Copt-level=s")])
+            ),
+            (
+                "CARGO_TARGET_WASM32_WASIP2_RUSTFLAGS".into(),
+                format!("-Copt-level=s {flag}")
+            )
+        );
+        assert_eq!(
+            rustflags_env(WASIP2_TARGET, flag, env(&[("RUSTFLAGS", "-Dwarnings")])),
+            ("RUSTFLAGS".into(), format!("-Dwarnings {flag}"))
+        );
+        assert_eq!(
+            rustflags_env(
+                WASIP2_TARGET,
+                flag,
+                env(&[
+                    ("CARGO_ENCODED_RUSTFLAGS", "-C\x1fopt-level=s"),
+                    ("RUSTFLAGS", "-Dwarnings")
+                ])
+            ),
+            (
+                "CARGO_ENCODED_RUSTFLAGS".into(),
+                format!("-C\x1fopt-level=s\x1f{flag}")
+            )
+        );
+    }
+
+    #[test]
+    fn test_wasm_artifact() {
+        let messages = [
+            r#"{"reason":"compiler-artifact","target":{"kind":["lib"],"name":"shopify_function_wasm_api"},"filenames":["/lib/target/wasm32-wasip1/release/deps/libshopify_function_wasm_api.rlib"]}"#,
+            r#"{"reason":"compiler-artifact","target":{"kind":["bin"],"name":"my-function"},"filenames":["/my-function/target/wasm32-wasip1/release/my-function.wasm"]}"#,
+            r#"{"reason":"build-finished","success":true}"#,
+        ]
+        .join("\n");
+        assert_eq!(
+            wasm_artifact(&messages, "my-function"),
+            Some(PathBuf::from(
+                "/my-function/target/wasm32-wasip1/release/my-function.wasm"
+            ))
+        );
+        assert_eq!(wasm_artifact(&messages, "other-function"), None);
+        assert_eq!(wasm_artifact("", "my-function"), None);
+    }
+
+    #[test]
+    fn test_check_module() {
+        let module = wat::parse_str(
+            r#"(module
+                (import "wasi_snapshot_preview1" "path_open" (func (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (func (export "_start"))
+            )"#,
+        )
+        .unwrap();
+        // unsupported WASI imports are only warnings
+        check_module(&module).unwrap();
+
+        let module = wat::parse_str(r#"(module (func (export "_start")))"#).unwrap();
+        let err = check_module(&module).unwrap_err();
+        assert_eq!(err.to_string(), "Validation found 1 error(s)");
+    }
+
+    #[test]
+    fn test_report() {
+        let bytes = wat::parse_str(
+            r#"(module
+                (import "shopify_function_v2" "_shopify_function_input_get" (func (result i64)))
+                (memory (export "memory") 1)
+                (func (export "_start"))
+            )"#,
+        )
+        .unwrap();
+        let report = Report::new(&bytes).unwrap();
+        assert_eq!(
+            report,
+            Report {
+                imports: vec!["shopify_function_v2._shopify_function_input_get".into()],
+                exports: vec!["memory".into(), "_start".into()],
+            }
+        );
+        assert_eq!(
+            report.to_string(),
+            "Imports:\n  shopify_function_v2._shopify_function_input_get\nExports:\n  memory\n  _start\n"
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use std::{env, fs, process::Command};
+
+use anyhow::{bail, Context};
+
+use crate::build_function::{target_dir, workspace_root};
+
+/// The target the provider is built for, which has no imports, so JavaScript hosts can instantiate
+/// it without WASI or any other glue.
+const TARGET: &str = "wasm32-unknown-unknown";
+
+/// Build the provider to Wasm and copy it into the `provider-js` package.
+pub fn build_provider_js() -> anyhow::Result<()> {
+    let status = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".into()))
+        .args([
+            "build",
+            "--release",
+            "--target",
+            TARGET,
+            "-p",
+            "shopify_function_provider",
+        ])
+        .status()
+        .context("Failed to run cargo build")?;
+    if !status.success() {
+        bail!("Building the provider failed: {status}");
+    }
+
+    let input = target_dir()
+        .join(TARGET)
+        .join("release")
+        .join("shopify_function_provider.wasm");
+    let output = workspace_root()
+        .join("provider-js")
+        .join("shopify_function_provider.wasm");
+    fs::copy(&input, &output).with_context(|| {
+        format!(
+            "Failed to copy `{}` to `{}`",
+            input.display(),
+            output.display()
+        )
+    })?;
+    println!("Built {}", output.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version_line(path: &str, prefix: &str) -> String {
+        let contents = fs::read_to_string(workspace_root().join(path)).unwrap();
+        contents
+            .lines()
+            .map(str::trim)
+            .find(|line| line.starts_with(prefix))
+            .unwrap_or_else(|| panic!("no version in `{path}`"))
+            .to_string()
+    }
+
+    #[test]
+    fn test_package_version_matches_provider() {
+        let provider_version = version_line("provider/Cargo.toml", "version = ");
+        let package_version = version_line("provider-js/package.json", "\"version\": ");
+        assert_eq!(
+            provider_version.trim_start_matches("version = "),
+            package_version
+                .trim_start_matches("\"version\": ")
+                .trim_end_matches(','),
+        );
+    }
+}
@@ -0,0 +1,22 @@
+use std::fs;
+
+use anyhow::Context;
+use shopify_function_wasm_api_core::abi;
+
+use crate::build_function::workspace_root;
+
+/// Write the C header and JSON descriptor of the ABI to the `abi` directory of the core crate.
+pub fn generate_abi() -> anyhow::Result<()> {
+    let dir = workspace_root().join("core").join("abi");
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create `{}`", dir.display()))?;
+    for (file_name, contents) in [
+        (abi::C_HEADER_FILE_NAME, abi::c_header()),
+        (abi::JSON_DESCRIPTOR_FILE_NAME, abi::json_descriptor()),
+    ] {
+        let path = dir.join(file_name);
+        fs::write(&path, contents)
+            .with_context(|| format!("Failed to write `{}`", path.display()))?;
+        println!("Generated {}", path.display());
+    }
+    Ok(())
+}
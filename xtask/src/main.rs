@@ -0,0 +1,192 @@
+mod build_function;
+mod build_provider_js;
+mod generate_abi;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use clap::{Parser, Subcommand};
+
+const TEMPLATE_NAME: &str = "function-template";
+const TEMPLATE_CARGO_TOML: &str = include_str!("../../templates/function/Cargo.toml");
+const TEMPLATE_MAIN_RS: &str = include_str!("../../templates/function/src/main.rs");
+
+/// Profile for generated functions, tuned for small Wasm modules. The template itself is a member of
+/// this workspace, where only the root profile applies, so it is added when generating a function.
+const RELEASE_PROFILE: &str = r#"
+[profile.release]
+opt-level = "z"
+lto = true
+codegen-units = 1
+strip = true
+panic = "abort"
+"#;
+
+#[derive(Parser, Debug)]
+#[command(name = "cargo shopify-function", version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Create a new function crate from the template
+    New {
+        /// Name of the function crate
+        name: String,
+
+        /// Directory to create the function crate in. Defaults to a directory named after the crate
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+    },
+    /// Build a function to Wasm, apply the trampoline, validate it, and report its imports, exports
+    /// and size
+    BuildFunction {
+        /// Package to build
+        #[arg(short, long)]
+        package: String,
+
+        /// Target to build for
+        #[arg(long, default_value = "wasm32-wasip1")]
+        target: String,
+
+        /// Path to the output Wasm file. Defaults to `<package>.merged.wasm` next to the build output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Build the provider to Wasm and copy it into the `provider-js` package, to run functions in
+    /// browsers and other JavaScript hosts
+    BuildProviderJs,
+    /// Generate the C header and JSON descriptor of the enums and constants shared by the provider,
+    /// the guest API and hosts
+    GenerateAbi,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    match args.command {
+        Command::New { name, path } => {
+            let path = path.unwrap_or_else(|| PathBuf::from(&name));
+            new_function(&name, &path)?;
+            println!("Created function `{name}` in {}", path.display());
+        }
+        Command::BuildFunction {
+            package,
+            target,
+            output,
+        } => build_function::build_function(&package, &target, output)?,
+        Command::BuildProviderJs => build_provider_js::build_provider_js()?,
+        Command::GenerateAbi => generate_abi::generate_abi()?,
+    }
+    Ok(())
+}
+
+fn new_function(name: &str, path: &Path) -> anyhow::Result<()> {
+    validate_name(name)?;
+    if path.exists() {
+        bail!("Destination `{}` already exists", path.display());
+    }
+
+    fs::create_dir_all(path.join("src"))
+        .with_context(|| format!("Failed to create `{}`", path.display()))?;
+    fs::write(path.join("Cargo.toml"), render_cargo_toml(name))?;
+    fs::write(path.join("src/main.rs"), TEMPLATE_MAIN_RS)?;
+    fs::write(path.join(".gitignore"), "/target\n")?;
+    Ok(())
+}
+
+fn validate_name(name: &str) -> anyhow::Result<()> {
+    let valid = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        && name.starts_with(|c: char| c.is_ascii_alphabetic());
+    if !valid {
+        bail!("Invalid crate name `{name}`: must start with a letter and only contain letters, numbers, `-` and `_`");
+    }
+    Ok(())
+}
+
+/// Render the template manifest for a standalone crate: rename the package, depend on the published
+/// API crate instead of the workspace path, and add the release profile.
+fn render_cargo_toml(name: &str) -> String {
+    let mut manifest = TEMPLATE_CARGO_TOML
+        .replace(
+            &format!("name = \"{TEMPLATE_NAME}\""),
+            &format!("name = \"{name}\""),
+        )
+        .replace("path = \"../../api\", ", "");
+    manifest.push_str(RELEASE_PROFILE);
+    manifest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("xtask-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn test_render_cargo_toml() {
+        let manifest = render_cargo_toml("my-function");
+        assert!(manifest.contains("name = \"my-function\""));
+        assert!(!manifest.contains(TEMPLATE_NAME));
+        assert!(!manifest.contains("path ="));
+        assert!(manifest.contains("shopify_function_wasm_api = { version = "));
+        assert!(manifest.ends_with(RELEASE_PROFILE));
+    }
+
+    #[test]
+    fn test_new_function() {
+        let path = temp_path("new");
+        new_function("my-function", &path).unwrap();
+
+        let manifest = fs::read_to_string(path.join("Cargo.toml")).unwrap();
+        assert_eq!(manifest, render_cargo_toml("my-function"));
+        let main = fs::read_to_string(path.join("src/main.rs")).unwrap();
+        assert_eq!(main, TEMPLATE_MAIN_RS);
+        assert!(path.join(".gitignore").exists());
+
+        let err = new_function("my-function", &path).unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    #[ignore = "resolves the published API crate from the registry, so it's run separately in CI"]
+    fn test_new_function_compiles_against_published_api() {
+        let path = temp_path("check");
+        new_function("my-function", &path).unwrap();
+
+        let cargo = std::env::var("CARGO").unwrap_or_else(|_| String::from("cargo"));
+        let output = std::process::Command::new(cargo)
+            .args(["check", "--all-targets", "--quiet"])
+            .current_dir(&path)
+            .output()
+            .unwrap();
+        fs::remove_dir_all(&path).unwrap();
+
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    #[test]
+    fn test_invalid_name() {
+        for name in ["", "1function", "my function", "../function"] {
+            let path = temp_path("invalid");
+            let err = new_function(name, &path).unwrap_err();
+            assert!(err.to_string().contains("Invalid crate name"), "{name}");
+            assert!(!path.exists());
+        }
+    }
+}